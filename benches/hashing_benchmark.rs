@@ -0,0 +1,98 @@
+//! Payload hashing benchmarks
+//!
+//! - `sha256`: throughput of the portable `sha2` crate against the hashing
+//!   PutObjects use, which is `ring` with `--features simd-sha256`
+//! - `crc32c`: throughput of the CRC32C multipart parts are checksummed
+//!   with, for comparison with `sha256`
+//! - `runtime_stall`: how long a task queued behind the hashing of a 100MB
+//!   body waits for the runtime, hashing inline or on the blocking pool
+//!
+//! ```text
+//! cargo bench --bench hashing_benchmark
+//! cargo bench --bench hashing_benchmark --features simd-sha256
+//! ```
+
+use bytes::Bytes;
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use mizuchi_uploadr::s3::{checksum, hashing};
+use sha2::{Digest, Sha256};
+use std::time::{Duration, Instant};
+
+const MB: usize = 1024 * 1024;
+
+fn benchmark_sha256(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sha256");
+    group.sample_size(10);
+
+    for size in [MB, 100 * MB, 256 * MB] {
+        let data = vec![0x5au8; size];
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_function(format!("sha2/{}MB", size / MB), |b| {
+            b.iter(|| hex::encode(Sha256::digest(black_box(&data))))
+        });
+        group.bench_function(format!("configured/{}MB", size / MB), |b| {
+            b.iter(|| hashing::sha256_hex(black_box(&data)))
+        });
+    }
+
+    group.finish();
+}
+
+fn benchmark_crc32c(c: &mut Criterion) {
+    let mut group = c.benchmark_group("crc32c");
+    group.sample_size(10);
+
+    for size in [MB, 100 * MB, 256 * MB] {
+        let data = vec![0x5au8; size];
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_function(format!("{}MB", size / MB), |b| {
+            b.iter(|| checksum::crc32c_base64(black_box(&data)))
+        });
+    }
+
+    group.finish();
+}
+
+fn benchmark_runtime_stall(c: &mut Criterion) {
+    let mut group = c.benchmark_group("runtime_stall");
+    group.sample_size(10);
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let body = Bytes::from(vec![0x5au8; 100 * MB]);
+
+    for (name, threshold) in [("inline/100MB", u64::MAX), ("offloaded/100MB", 0)] {
+        group.bench_function(name, |b| {
+            b.iter_custom(|iters| {
+                let mut stalled = Duration::ZERO;
+                for _ in 0..iters {
+                    let body = body.clone();
+                    stalled += runtime.block_on(async move {
+                        let started = Instant::now();
+                        let hashed = tokio::spawn(async move {
+                            hashing::sha256_hex_offloaded(&body, threshold).await
+                        });
+                        // Queued behind the hashing task on the only worker
+                        let waited = tokio::spawn(async move { started.elapsed() })
+                            .await
+                            .unwrap();
+                        black_box(hashed.await.unwrap());
+                        waited
+                    });
+                }
+                stalled
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    benchmark_sha256,
+    benchmark_crc32c,
+    benchmark_runtime_stall
+);
+criterion_main!(benches);
@@ -55,6 +55,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         sampling: SamplingConfig {
             strategy: "always".to_string(), // Sample all traces for demo
             ratio: 1.0,
+            rules: None,
         },
         batch: BatchConfig {
             max_queue_size: 2048,
@@ -117,6 +118,7 @@ async fn simulate_authentication() -> Result<(), Box<dyn std::error::Error>> {
         query: None,
         method: "PUT".to_string(),
         path: "/uploads/file.txt".to_string(),
+        peer_certificates: Vec::new(),
     };
 
     // This will create an "auth.jwt" span
@@ -136,6 +138,7 @@ async fn simulate_authorization() -> Result<(), Box<dyn std::error::Error>> {
         policy_path: "mizuchi/allow".to_string(),
         timeout: None,
         cache_ttl: None,
+        deny_cache_ttl: None,
     });
 
     // Create a mock authz request
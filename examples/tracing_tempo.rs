@@ -68,6 +68,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         sampling: SamplingConfig {
             strategy: "ratio".to_string(), // Sample 50% for demo
             ratio: 0.5,
+            rules: None,
         },
         batch: BatchConfig {
             max_queue_size: 4096,         // Larger queue for production-like setup
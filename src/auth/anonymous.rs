@@ -0,0 +1,273 @@
+//! Anonymous upload constraints
+//!
+//! Buckets with `auth.allow_anonymous` accept requests that carry no
+//! credentials, but every such request must stay within the configured
+//! limits: a maximum size, an allow-list of content types, a key prefix
+//! jail and a per-client-IP rate limit. Authenticated requests are not
+//! constrained.
+//!
+//! The rate limit is a token bucket per instance. With a shared
+//! [`Store`](crate::state::Store), instances instead count each client's
+//! requests per clock minute together, allowing `burst` (by default
+//! `requests_per_minute`) of them.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::auth::anonymous::AnonymousPolicy;
+//! use mizuchi_uploadr::config::{AnonymousConfig, RateLimitConfig};
+//!
+//! let policy = AnonymousPolicy::new(&AnonymousConfig {
+//!     max_size: 10 * 1024 * 1024,
+//!     allowed_content_types: vec!["image/*".into()],
+//!     key_prefix: "dropbox/".into(),
+//!     rate_limit: RateLimitConfig {
+//!         requests_per_minute: 30,
+//!         burst: None,
+//!     },
+//! });
+//! assert!(policy.check_key("dropbox/cat.png").is_ok());
+//! assert!(policy.check_content_type(Some("image/png")).is_ok());
+//! ```
+
+use crate::config::AnonymousConfig;
+use crate::state::Store;
+use crate::upload::content_type;
+use dashmap::DashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tracing::warn;
+
+/// Clients tracked before idle rate limit entries are pruned
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Reasons an anonymous request is rejected
+#[derive(Error, Debug, PartialEq)]
+pub enum AnonymousError {
+    #[error("Rate limit exceeded, retry after {retry_after_secs}s")]
+    RateLimited { retry_after_secs: u64 },
+
+    #[error("Upload exceeds the anonymous size limit of {max_size} bytes")]
+    TooLarge { max_size: u64 },
+
+    #[error("Content type not allowed for anonymous uploads: {0}")]
+    ContentTypeNotAllowed(String),
+
+    #[error("Anonymous uploads must use keys under '{0}'")]
+    KeyOutsidePrefix(String),
+}
+
+/// Token bucket state for one client
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Enforces the constraints for anonymous uploads to one bucket
+pub struct AnonymousPolicy {
+    max_size: u64,
+    content_types: Vec<String>,
+    key_prefix: String,
+    /// Tokens added per second
+    rate: f64,
+    burst: f64,
+    clients: DashMap<IpAddr, TokenBucket>,
+    /// Shared request counts, keyed under this prefix
+    shared: Option<(Arc<dyn Store>, String)>,
+}
+
+impl AnonymousPolicy {
+    /// Create a policy from bucket configuration
+    pub fn new(config: &AnonymousConfig) -> Self {
+        let per_minute = config.rate_limit.requests_per_minute;
+        Self {
+            max_size: config.max_size,
+            content_types: config
+                .allowed_content_types
+                .iter()
+                .map(|t| t.trim().to_ascii_lowercase())
+                .collect(),
+            key_prefix: config.key_prefix.trim_start_matches('/').to_string(),
+            rate: f64::from(per_minute) / 60.0,
+            burst: f64::from(config.rate_limit.burst.unwrap_or(per_minute)),
+            clients: DashMap::new(),
+            shared: None,
+        }
+    }
+
+    /// Count requests in `store`, shared with other instances, under the
+    /// bucket's name
+    #[must_use]
+    pub fn with_store(mut self, store: Arc<dyn Store>, bucket: &str) -> Self {
+        self.shared = Some((store, format!("anonymous:{}:", bucket)));
+        self
+    }
+
+    /// Maximum upload size in bytes
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    /// Take one request from the client's rate limit budget
+    pub fn check_rate(&self, client: IpAddr) -> Result<(), AnonymousError> {
+        let now = Instant::now();
+        if self.clients.len() > MAX_TRACKED_CLIENTS {
+            // Entries that have refilled completely carry no state worth keeping
+            let full_after = self.burst / self.rate;
+            self.clients
+                .retain(|_, bucket| now.duration_since(bucket.updated).as_secs_f64() < full_after);
+        }
+
+        let mut bucket = self.clients.entry(client).or_insert(TokenBucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let wait = (1.0 - bucket.tokens) / self.rate;
+            Err(AnonymousError::RateLimited {
+                retry_after_secs: wait.ceil() as u64,
+            })
+        }
+    }
+
+    /// Take one request from the client's rate limit budget, in the shared
+    /// store if the policy has one
+    ///
+    /// Falls back to this instance's budget while the store is unreachable.
+    pub async fn check_shared_rate(&self, client: IpAddr) -> Result<(), AnonymousError> {
+        let Some((store, prefix)) = &self.shared else {
+            return self.check_rate(client);
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let (minute, retry_after_secs) = (now / 60, 60 - now % 60);
+        let key = format!("{}{}:{}", prefix, client, minute);
+
+        match store.incr_by(&key, 1, Duration::from_secs(60)).await {
+            Ok(count) if count as f64 <= self.burst => Ok(()),
+            Ok(_) => Err(AnonymousError::RateLimited { retry_after_secs }),
+            Err(e) => {
+                warn!("Shared anonymous rate limit unavailable: {}", e);
+                self.check_rate(client)
+            }
+        }
+    }
+
+    /// Check that a key stays inside the prefix jail
+    pub fn check_key(&self, key: &str) -> Result<(), AnonymousError> {
+        let escapes = key.split('/').any(|segment| segment == "..");
+        if escapes || !key.starts_with(&self.key_prefix) {
+            return Err(AnonymousError::KeyOutsidePrefix(self.key_prefix.clone()));
+        }
+        Ok(())
+    }
+
+    /// Check the request content type against the allow-list
+    ///
+    /// Parameters such as `charset` are ignored. A missing content type is
+    /// rejected.
+    pub fn check_content_type(&self, content_type: Option<&str>) -> Result<(), AnonymousError> {
+        let mime = content_type.map(content_type::essence).unwrap_or_default();
+        let allowed = !mime.is_empty() && content_type::matches_any(&self.content_types, &mime);
+
+        if allowed {
+            Ok(())
+        } else {
+            Err(AnonymousError::ContentTypeNotAllowed(
+                content_type.unwrap_or("(none)").to_string(),
+            ))
+        }
+    }
+
+    /// Check a declared or received upload size
+    pub fn check_size(&self, size: u64) -> Result<(), AnonymousError> {
+        if size > self.max_size {
+            return Err(AnonymousError::TooLarge {
+                max_size: self.max_size,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RateLimitConfig;
+
+    fn policy(requests_per_minute: u32, burst: Option<u32>) -> AnonymousPolicy {
+        AnonymousPolicy::new(&AnonymousConfig {
+            max_size: 100,
+            allowed_content_types: vec!["image/*".into(), "text/plain".into()],
+            key_prefix: "public/".into(),
+            rate_limit: RateLimitConfig {
+                requests_per_minute,
+                burst,
+            },
+        })
+    }
+
+    #[test]
+    fn test_content_types() {
+        let policy = policy(60, None);
+        assert!(policy.check_content_type(Some("image/png")).is_ok());
+        assert!(policy
+            .check_content_type(Some("Text/Plain; charset=utf-8"))
+            .is_ok());
+        assert!(policy.check_content_type(Some("text/html")).is_err());
+        assert!(policy.check_content_type(None).is_err());
+    }
+
+    #[test]
+    fn test_key_jail() {
+        let policy = policy(60, None);
+        assert!(policy.check_key("public/a.png").is_ok());
+        assert!(policy.check_key("private/a.png").is_err());
+        assert!(policy.check_key("public/../private/a.png").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shared_rate_limit() {
+        let store: Arc<dyn Store> = Arc::new(crate::state::MemoryStore::new());
+        let instances = [
+            policy(60, Some(2)).with_store(Arc::clone(&store), "uploads"),
+            policy(60, Some(2)).with_store(Arc::clone(&store), "uploads"),
+        ];
+        let client: IpAddr = "203.0.113.7".parse().unwrap();
+
+        assert!(instances[0].check_shared_rate(client).await.is_ok());
+        assert!(instances[1].check_shared_rate(client).await.is_ok());
+        assert!(matches!(
+            instances[0].check_shared_rate(client).await,
+            Err(AnonymousError::RateLimited { .. })
+        ));
+    }
+
+    #[test]
+    fn test_rate_limit_burst() {
+        let policy = policy(60, Some(2));
+        let client: IpAddr = "203.0.113.7".parse().unwrap();
+        assert!(policy.check_rate(client).is_ok());
+        assert!(policy.check_rate(client).is_ok());
+        assert_eq!(
+            policy.check_rate(client),
+            Err(AnonymousError::RateLimited {
+                retry_after_secs: 1
+            })
+        );
+
+        // Other clients have their own budget
+        assert!(policy.check_rate("203.0.113.8".parse().unwrap()).is_ok());
+    }
+}
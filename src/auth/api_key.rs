@@ -0,0 +1,363 @@
+//! API key authentication
+//!
+//! For clients that cannot do JWT or SigV4 (e.g. internal batch jobs). The
+//! key is sent in a header (`x-api-key` by default), hashed and looked up in
+//! the inline keys from config and then in an optional file or Redis store.
+//! Only hashes are stored: hex SHA-256 of the key, or HMAC-SHA256 keyed with
+//! a server-side pepper when one is configured.
+//!
+//! Each key may be scoped to buckets and object key prefixes; requests
+//! outside the scope are rejected with [`AuthError::Forbidden`].
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::auth::api_key::{hash_key, ApiKeyAuthenticator, StaticKeyStore};
+//! use mizuchi_uploadr::config::ApiKeyRecord;
+//! use std::sync::Arc;
+//!
+//! let record = ApiKeyRecord {
+//!     id: "nightly-export".into(),
+//!     hash: hash_key("s3cr3t-key", None),
+//!     buckets: vec!["uploads".into()],
+//!     prefixes: vec!["exports/".into()],
+//! };
+//! let auth = ApiKeyAuthenticator::new("x-api-key", None)
+//!     .with_store(Arc::new(StaticKeyStore::new(vec![record])))
+//!     .scoped_to_bucket("uploads", "/uploads");
+//! ```
+
+use super::{AuthError, AuthRequest, AuthResult, Authenticator};
+use crate::config::{ApiKeyConfig, ApiKeyRecord, ApiKeyStoreConfig};
+use crate::redis::RedisClient;
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use parking_lot::RwLock;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Hash an API key for storage or lookup
+///
+/// Returns lowercase hex. With a pepper the digest is HMAC-SHA256, so a
+/// leaked key store cannot be brute-forced without the server secret.
+pub fn hash_key(key: &str, pepper: Option<&str>) -> String {
+    match pepper {
+        Some(pepper) => {
+            let mut mac = Hmac::<Sha256>::new_from_slice(pepper.as_bytes())
+                .expect("HMAC can take key of any size");
+            mac.update(key.as_bytes());
+            hex::encode(mac.finalize().into_bytes())
+        }
+        None => hex::encode(Sha256::digest(key.as_bytes())),
+    }
+}
+
+/// Source of hashed API key records
+#[async_trait]
+pub trait KeyStore: Send + Sync {
+    /// Look up the record for a key hash
+    async fn lookup(&self, hash: &str) -> Result<Option<ApiKeyRecord>, AuthError>;
+}
+
+/// Keys held in memory (inline config keys)
+pub struct StaticKeyStore {
+    records: HashMap<String, ApiKeyRecord>,
+}
+
+impl StaticKeyStore {
+    pub fn new(records: Vec<ApiKeyRecord>) -> Self {
+        Self {
+            records: records
+                .into_iter()
+                .map(|r| (r.hash.to_ascii_lowercase(), r))
+                .collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl KeyStore for StaticKeyStore {
+    async fn lookup(&self, hash: &str) -> Result<Option<ApiKeyRecord>, AuthError> {
+        Ok(self.records.get(hash).cloned())
+    }
+}
+
+/// Keys read from a YAML or JSON file, reloaded when its mtime changes
+pub struct FileKeyStore {
+    path: String,
+    cache: RwLock<(Option<SystemTime>, Arc<StaticKeyStore>)>,
+}
+
+impl FileKeyStore {
+    /// Load the key file; fails if it cannot be read or parsed
+    pub fn new(path: &str) -> Result<Self, AuthError> {
+        let store = Self {
+            path: path.to_string(),
+            cache: RwLock::new((None, Arc::new(StaticKeyStore::new(Vec::new())))),
+        };
+        store.current()?;
+        Ok(store)
+    }
+
+    /// Current records, reloading the file if it changed
+    fn current(&self) -> Result<Arc<StaticKeyStore>, AuthError> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .map_err(|e| AuthError::ConfigError(format!("Failed to read {}: {}", self.path, e)))?;
+        {
+            let cache = self.cache.read();
+            if cache.0 == Some(modified) {
+                return Ok(Arc::clone(&cache.1));
+            }
+        }
+
+        let contents = std::fs::read_to_string(&self.path)
+            .map_err(|e| AuthError::ConfigError(format!("Failed to read {}: {}", self.path, e)))?;
+        let records: Vec<ApiKeyRecord> = serde_yaml::from_str(&contents).map_err(|e| {
+            AuthError::ConfigError(format!("Invalid key file {}: {}", self.path, e))
+        })?;
+        let store = Arc::new(StaticKeyStore::new(records));
+        *self.cache.write() = (Some(modified), Arc::clone(&store));
+        Ok(store)
+    }
+}
+
+#[async_trait]
+impl KeyStore for FileKeyStore {
+    async fn lookup(&self, hash: &str) -> Result<Option<ApiKeyRecord>, AuthError> {
+        self.current()?.lookup(hash).await
+    }
+}
+
+/// Keys stored in Redis as JSON records under `key_prefix` + hash
+pub struct RedisKeyStore {
+    client: RedisClient,
+    key_prefix: String,
+}
+
+impl RedisKeyStore {
+    pub fn new(client: RedisClient, key_prefix: &str) -> Self {
+        Self {
+            client,
+            key_prefix: key_prefix.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl KeyStore for RedisKeyStore {
+    async fn lookup(&self, hash: &str) -> Result<Option<ApiKeyRecord>, AuthError> {
+        let key = format!("{}{}", self.key_prefix, hash);
+        let Some(value) = self
+            .client
+            .get(&key)
+            .await
+            .map_err(|e| AuthError::ConfigError(format!("API key store unavailable: {}", e)))?
+        else {
+            return Ok(None);
+        };
+        serde_json::from_slice(&value)
+            .map(Some)
+            .map_err(|e| AuthError::ConfigError(format!("Invalid API key record {}: {}", key, e)))
+    }
+}
+
+/// API key authenticator
+pub struct ApiKeyAuthenticator {
+    header: String,
+    pepper: Option<String>,
+    stores: Vec<Arc<dyn KeyStore>>,
+    /// Bucket name and path prefix the authenticator serves
+    bucket: Option<(String, String)>,
+}
+
+impl ApiKeyAuthenticator {
+    /// Create an authenticator reading keys from `header`, with no stores
+    pub fn new(header: &str, pepper: Option<&str>) -> Self {
+        Self {
+            header: header.to_ascii_lowercase(),
+            pepper: pepper.map(str::to_string),
+            stores: Vec::new(),
+            bucket: None,
+        }
+    }
+
+    /// Create an authenticator from bucket configuration
+    ///
+    /// Inline keys are consulted first, then the configured store.
+    pub fn from_config(config: &ApiKeyConfig) -> Result<Self, AuthError> {
+        let mut authenticator = Self::new(&config.header, config.pepper.as_deref())
+            .with_store(Arc::new(StaticKeyStore::new(config.keys.clone())));
+        authenticator = match config.store {
+            Some(ApiKeyStoreConfig::File { ref path }) => {
+                authenticator.with_store(Arc::new(FileKeyStore::new(path)?))
+            }
+            Some(ApiKeyStoreConfig::Redis {
+                ref url,
+                ref key_prefix,
+            }) => {
+                let client = RedisClient::from_url(url)
+                    .map_err(|e| AuthError::ConfigError(e.to_string()))?;
+                authenticator.with_store(Arc::new(RedisKeyStore::new(client, key_prefix)))
+            }
+            None => authenticator,
+        };
+        Ok(authenticator)
+    }
+
+    /// Append a key store
+    #[must_use]
+    pub fn with_store(mut self, store: Arc<dyn KeyStore>) -> Self {
+        self.stores.push(store);
+        self
+    }
+
+    /// Enforce key scopes for the bucket served at `path_prefix`
+    ///
+    /// Without a bucket, keys restricted to specific buckets are rejected.
+    #[must_use]
+    pub fn scoped_to_bucket(mut self, name: &str, path_prefix: &str) -> Self {
+        self.bucket = Some((name.to_string(), path_prefix.to_string()));
+        self
+    }
+
+    async fn lookup(&self, hash: &str) -> Result<Option<ApiKeyRecord>, AuthError> {
+        for store in &self.stores {
+            if let Some(record) = store.lookup(hash).await? {
+                return Ok(Some(record));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Check the request against the key's bucket and prefix scopes
+    fn check_scope(&self, record: &ApiKeyRecord, path: &str) -> Result<(), AuthError> {
+        let (bucket, key) = match self.bucket {
+            Some((ref name, ref prefix)) => (
+                Some(name.as_str()),
+                path.strip_prefix(prefix.as_str())
+                    .unwrap_or(path)
+                    .trim_start_matches('/'),
+            ),
+            None => (None, path.trim_start_matches('/')),
+        };
+
+        let bucket_allowed = record.buckets.is_empty()
+            || bucket.is_some_and(|bucket| record.buckets.iter().any(|b| b == bucket));
+        let key_allowed = record.prefixes.is_empty()
+            || (!key.split('/').any(|segment| segment == "..")
+                && record.prefixes.iter().any(|p| key.starts_with(p.as_str())));
+
+        if bucket_allowed && key_allowed {
+            Ok(())
+        } else {
+            Err(AuthError::Forbidden(format!(
+                "API key '{}' is not allowed to write {}",
+                record.id, path
+            )))
+        }
+    }
+}
+
+#[async_trait]
+impl Authenticator for ApiKeyAuthenticator {
+    async fn authenticate(&self, request: &AuthRequest) -> Result<AuthResult, AuthError> {
+        let key = request
+            .headers
+            .get(&self.header)
+            .map(|key| key.trim())
+            .filter(|key| !key.is_empty())
+            .ok_or(AuthError::MissingAuth)?;
+
+        let hash = hash_key(key, self.pepper.as_deref());
+        let record = self
+            .lookup(&hash)
+            .await?
+            .ok_or_else(|| AuthError::InvalidToken("Unknown API key".into()))?;
+        self.check_scope(&record, &request.path)?;
+
+        let mut claims = HashMap::new();
+        claims.insert("auth_method".into(), serde_json::json!("api_key"));
+        claims.insert("buckets".into(), serde_json::json!(record.buckets));
+        claims.insert("prefixes".into(), serde_json::json!(record.prefixes));
+        Ok(AuthResult {
+            subject: record.id,
+            claims,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(key: Option<&str>, path: &str) -> AuthRequest {
+        AuthRequest {
+            headers: key
+                .map(|k| [("x-api-key".to_string(), k.to_string())].into())
+                .unwrap_or_default(),
+            query: None,
+            method: "PUT".into(),
+            path: path.into(),
+            peer_certificates: vec![],
+        }
+    }
+
+    fn authenticator(pepper: Option<&str>) -> ApiKeyAuthenticator {
+        let record = ApiKeyRecord {
+            id: "batch".into(),
+            hash: hash_key("k3y", pepper),
+            buckets: vec!["uploads".into()],
+            prefixes: vec!["batch/".into()],
+        };
+        ApiKeyAuthenticator::new("X-Api-Key", pepper)
+            .with_store(Arc::new(StaticKeyStore::new(vec![record])))
+            .scoped_to_bucket("uploads", "/uploads")
+    }
+
+    #[test]
+    fn test_hash_key_with_pepper_differs() {
+        assert_eq!(hash_key("k3y", None).len(), 64);
+        assert_ne!(hash_key("k3y", None), hash_key("k3y", Some("pepper")));
+    }
+
+    #[tokio::test]
+    async fn test_valid_key_in_scope() {
+        let result = authenticator(Some("pepper"))
+            .authenticate(&request(Some("k3y"), "/uploads/batch/a.csv"))
+            .await
+            .unwrap();
+        assert_eq!(result.subject, "batch");
+    }
+
+    #[tokio::test]
+    async fn test_rejections() {
+        let auth = authenticator(None);
+        assert!(matches!(
+            auth.authenticate(&request(None, "/uploads/batch/a.csv"))
+                .await
+                .unwrap_err(),
+            AuthError::MissingAuth
+        ));
+        assert!(matches!(
+            auth.authenticate(&request(Some("wrong"), "/uploads/batch/a.csv"))
+                .await
+                .unwrap_err(),
+            AuthError::InvalidToken(_)
+        ));
+        assert!(matches!(
+            auth.authenticate(&request(Some("k3y"), "/uploads/other/a.csv"))
+                .await
+                .unwrap_err(),
+            AuthError::Forbidden(_)
+        ));
+        assert!(matches!(
+            auth.authenticate(&request(Some("k3y"), "/uploads/batch/../x"))
+                .await
+                .unwrap_err(),
+            AuthError::Forbidden(_)
+        ));
+    }
+}
@@ -0,0 +1,288 @@
+//! Authenticator chains
+//!
+//! Composes the authenticators configured for a bucket. In
+//! [`AuthChainMode::FirstSuccess`] mode the authenticators are tried in order
+//! and the first one that accepts the request wins; in
+//! [`AuthChainMode::AllMustPass`] mode every authenticator must accept it.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::auth::chain::{AnonymousAuthenticator, AuthChain};
+//! use mizuchi_uploadr::auth::jwt::JwtAuthenticator;
+//! use mizuchi_uploadr::config::AuthChainMode;
+//! use std::sync::Arc;
+//!
+//! let chain = AuthChain::new(AuthChainMode::FirstSuccess)
+//!     .with("jwt", Arc::new(JwtAuthenticator::new_hs256("secret")))
+//!     .with("anonymous", Arc::new(AnonymousAuthenticator));
+//! assert_eq!(chain.len(), 2);
+//! ```
+
+use super::api_key::ApiKeyAuthenticator;
+use super::jwt::JwtAuthenticator;
+use super::mtls::MtlsAuthenticator;
+use super::sigv4::SigV4Authenticator;
+use super::token_exchange::TokenExchangeAuthenticator;
+use super::{AuthError, AuthRequest, AuthResult, Authenticator};
+use crate::config::{AuthChainMode, AuthMethod, BucketConfig};
+use crate::metrics;
+use crate::state::Store;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Subject reported for requests accepted by [`AnonymousAuthenticator`]
+pub const ANONYMOUS_SUBJECT: &str = "anonymous";
+
+/// Authenticator that accepts every request
+pub struct AnonymousAuthenticator;
+
+#[async_trait]
+impl Authenticator for AnonymousAuthenticator {
+    async fn authenticate(&self, _request: &AuthRequest) -> Result<AuthResult, AuthError> {
+        let mut claims = HashMap::new();
+        claims.insert("auth_method".into(), serde_json::json!("anonymous"));
+        Ok(AuthResult {
+            subject: ANONYMOUS_SUBJECT.into(),
+            claims,
+        })
+    }
+}
+
+/// Ordered list of named authenticators
+pub struct AuthChain {
+    mode: AuthChainMode,
+    authenticators: Vec<(String, Arc<dyn Authenticator>)>,
+}
+
+impl AuthChain {
+    /// Create an empty chain
+    pub fn new(mode: AuthChainMode) -> Self {
+        Self {
+            mode,
+            authenticators: Vec::new(),
+        }
+    }
+
+    /// Append an authenticator; `name` is used in logs and metric labels
+    #[must_use]
+    pub fn with(mut self, name: &str, authenticator: Arc<dyn Authenticator>) -> Self {
+        self.authenticators.push((name.to_string(), authenticator));
+        self
+    }
+
+    /// Build the chain for a bucket's auth configuration
+    ///
+    /// The mTLS authenticator is passed in because it is loaded once at
+    /// startup alongside the TLS listener.
+    pub fn from_config(
+        bucket: &BucketConfig,
+        mtls: Option<Arc<MtlsAuthenticator>>,
+    ) -> Result<Self, AuthError> {
+        Self::from_config_with_state(bucket, mtls, None)
+    }
+
+    /// Like [`from_config`](Self::from_config), keeping authenticator state
+    /// such as the SigV4 replay cache in the shared `state` store
+    pub fn from_config_with_state(
+        bucket: &BucketConfig,
+        mtls: Option<Arc<MtlsAuthenticator>>,
+        state: Option<&Arc<dyn Store>>,
+    ) -> Result<Self, AuthError> {
+        let config = &bucket.auth;
+        let order = if config.chain.order.is_empty() {
+            [
+                AuthMethod::Mtls,
+                AuthMethod::Sigv4,
+                AuthMethod::Jwt,
+                AuthMethod::ApiKey,
+            ]
+            .into_iter()
+            .filter(|method| match method {
+                AuthMethod::Mtls => config.mtls.is_some(),
+                AuthMethod::Sigv4 => config.sigv4.is_some(),
+                AuthMethod::Jwt => config.jwt.is_some(),
+                AuthMethod::ApiKey => config.api_key.is_some(),
+                AuthMethod::Anonymous => false,
+            })
+            .collect()
+        } else {
+            config.chain.order.clone()
+        };
+
+        let not_configured = |method: AuthMethod| {
+            AuthError::ConfigError(format!("{} is not configured", method.as_str()))
+        };
+
+        let mut chain = Self::new(config.chain.mode);
+        for method in order {
+            let authenticator: Arc<dyn Authenticator> = match method {
+                AuthMethod::Mtls => mtls.clone().ok_or_else(|| not_configured(method))?,
+                AuthMethod::Sigv4 => {
+                    let sigv4 = config
+                        .sigv4
+                        .as_ref()
+                        .ok_or_else(|| not_configured(method))?;
+                    Arc::new(SigV4Authenticator::from_config_with_state(sigv4, state)?)
+                }
+                AuthMethod::Jwt => {
+                    let jwt = config.jwt.as_ref().ok_or_else(|| not_configured(method))?;
+                    let authenticator = JwtAuthenticator::from_config(jwt)?;
+                    match jwt.token_exchange {
+                        Some(ref exchange) => Arc::new(TokenExchangeAuthenticator::new(
+                            authenticator,
+                            exchange.clone(),
+                        )),
+                        None => Arc::new(authenticator),
+                    }
+                }
+                AuthMethod::ApiKey => {
+                    let api_key = config
+                        .api_key
+                        .as_ref()
+                        .ok_or_else(|| not_configured(method))?;
+                    Arc::new(
+                        ApiKeyAuthenticator::from_config(api_key)?
+                            .scoped_to_bucket(&bucket.name, &bucket.path_prefix),
+                    )
+                }
+                AuthMethod::Anonymous => Arc::new(AnonymousAuthenticator),
+            };
+            chain = chain.with(method.as_str(), authenticator);
+        }
+        Ok(chain)
+    }
+
+    /// Composition mode
+    pub fn mode(&self) -> AuthChainMode {
+        self.mode
+    }
+
+    /// Names of the chained authenticators, in order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.authenticators.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Number of chained authenticators
+    pub fn len(&self) -> usize {
+        self.authenticators.len()
+    }
+
+    /// Whether the chain has no authenticators
+    pub fn is_empty(&self) -> bool {
+        self.authenticators.is_empty()
+    }
+
+    /// Run one authenticator and record its outcome
+    async fn attempt(
+        name: &str,
+        authenticator: &dyn Authenticator,
+        request: &AuthRequest,
+    ) -> Result<AuthResult, AuthError> {
+        let start = Instant::now();
+        let result = authenticator.authenticate(request).await;
+        let status = match result {
+            Ok(_) => "success",
+            Err(AuthError::MissingAuth) => "missing",
+            Err(_) => "failure",
+        };
+        metrics::record_auth_outcome(name, status, start.elapsed().as_secs_f64());
+        result
+    }
+}
+
+#[async_trait]
+impl Authenticator for AuthChain {
+    /// Authenticate a request against the chain
+    ///
+    /// In first-success mode, a failure is reported from the last
+    /// authenticator that found credentials it could not accept, so an
+    /// expired bearer token is reported as such even when SigV4 runs first.
+    /// Requests without credentials for any authenticator get
+    /// [`AuthError::MissingAuth`]. In all-must-pass mode the first failure is
+    /// returned; the subject comes from the first authenticator and claims
+    /// are merged, with earlier authenticators taking precedence.
+    async fn authenticate(&self, request: &AuthRequest) -> Result<AuthResult, AuthError> {
+        if self.authenticators.is_empty() {
+            return Err(AuthError::ConfigError(
+                "No authenticators configured".into(),
+            ));
+        }
+
+        match self.mode {
+            AuthChainMode::FirstSuccess => {
+                let mut failure = None;
+                for (name, authenticator) in &self.authenticators {
+                    match Self::attempt(name, authenticator.as_ref(), request).await {
+                        Ok(result) => return Ok(result),
+                        Err(AuthError::MissingAuth) => {}
+                        Err(e) => failure = Some(e),
+                    }
+                }
+                Err(failure.unwrap_or(AuthError::MissingAuth))
+            }
+            AuthChainMode::AllMustPass => {
+                let mut combined: Option<AuthResult> = None;
+                for (name, authenticator) in &self.authenticators {
+                    let result = Self::attempt(name, authenticator.as_ref(), request).await?;
+                    match combined {
+                        None => combined = Some(result),
+                        Some(ref mut combined) => {
+                            for (key, value) in result.claims {
+                                combined.claims.entry(key).or_insert(value);
+                            }
+                        }
+                    }
+                }
+                Ok(combined.expect("chain is not empty"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(headers: &[(&str, &str)]) -> AuthRequest {
+        AuthRequest {
+            headers: headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            query: None,
+            method: "PUT".into(),
+            path: "/uploads/file.txt".into(),
+            peer_certificates: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_empty_chain_is_config_error() {
+        let chain = AuthChain::new(AuthChainMode::FirstSuccess);
+        let err = chain.authenticate(&request(&[])).await.unwrap_err();
+        assert!(matches!(err, AuthError::ConfigError(_)));
+    }
+
+    #[tokio::test]
+    async fn test_falls_through_to_anonymous() {
+        let chain = AuthChain::new(AuthChainMode::FirstSuccess)
+            .with("jwt", Arc::new(JwtAuthenticator::new_hs256("secret")))
+            .with("anonymous", Arc::new(AnonymousAuthenticator));
+
+        let result = chain.authenticate(&request(&[])).await.unwrap();
+        assert_eq!(result.subject, ANONYMOUS_SUBJECT);
+    }
+
+    #[tokio::test]
+    async fn test_all_must_pass_stops_at_first_failure() {
+        let chain = AuthChain::new(AuthChainMode::AllMustPass)
+            .with("anonymous", Arc::new(AnonymousAuthenticator))
+            .with("jwt", Arc::new(JwtAuthenticator::new_hs256("secret")));
+
+        let err = chain.authenticate(&request(&[])).await.unwrap_err();
+        assert!(matches!(err, AuthError::MissingAuth));
+    }
+}
@@ -22,13 +22,15 @@ use super::{AuthError, AuthRequest, AuthResult, Authenticator};
 use async_trait::async_trait;
 use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 
+/// Default minimum interval between two fetches of the JWKS endpoint
+const DEFAULT_REFETCH_COOLDOWN: Duration = Duration::from_secs(30);
+
 /// A single JSON Web Key
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Jwk {
     /// Key type (e.g., "RSA", "EC")
     pub kty: String,
@@ -120,7 +122,7 @@ impl Jwk {
 }
 
 /// JSON Web Key Set
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Jwks {
     pub keys: Vec<Jwk>,
 }
@@ -128,12 +130,57 @@ pub struct Jwks {
 /// Cached JWKS with metadata
 struct CachedJwks {
     jwks: Jwks,
-    fetched_at: std::time::Instant,
+    /// Key set replaced by the last rotation, still accepted so tokens
+    /// signed just before the IdP rotated keep validating
+    previous: Option<Jwks>,
+    fetched_at: Instant,
+    /// Last fetch attempt (successful or not), used for the cool-down
+    last_attempt: Option<Instant>,
+}
+
+impl CachedJwks {
+    fn new(jwks: Jwks) -> Self {
+        Self {
+            jwks,
+            previous: None,
+            fetched_at: Instant::now(),
+            last_attempt: None,
+        }
+    }
+
+    /// Find a key in the current or previous key set
+    fn find(&self, kid: &str) -> Option<&Jwk> {
+        std::iter::once(&self.jwks)
+            .chain(self.previous.as_ref())
+            .flat_map(|jwks| jwks.keys.iter())
+            .find(|k| k.kid.as_deref() == Some(kid))
+    }
+
+    /// Install a freshly fetched key set, keeping the old one on rotation
+    fn rotate(&mut self, jwks: Jwks) {
+        if jwks != self.jwks {
+            self.previous = Some(std::mem::replace(&mut self.jwks, jwks));
+        }
+        self.fetched_at = Instant::now();
+    }
+
+    /// Record a fetch attempt unless one happened within `cooldown`
+    fn claim_attempt(&mut self, cooldown: Duration) -> bool {
+        if self.last_attempt.is_some_and(|t| t.elapsed() < cooldown) {
+            return false;
+        }
+        self.last_attempt = Some(Instant::now());
+        true
+    }
 }
 
 /// JWKS-based JWT Authenticator
 ///
-/// Fetches keys from a JWKS endpoint and caches them.
+/// Fetches keys from a JWKS endpoint and caches them. Keys are refreshed
+/// when the cache TTL expires (in the background with
+/// [`with_background_refresh`](Self::with_background_refresh)) and when a
+/// token references an unknown `kid`. Fetches are rate limited by a
+/// cool-down, and a failed refresh keeps serving the cached keys.
 ///
 /// # Example
 ///
@@ -145,6 +192,7 @@ struct CachedJwks {
 /// let auth = JwksAuthenticator::new("https://auth.example.com/.well-known/jwks.json")
 ///     .await?
 ///     .with_cache_ttl(Duration::from_secs(3600))
+///     .with_background_refresh()
 ///     .with_issuer("https://auth.example.com")
 ///     .with_audience("my-api");
 /// # Ok(())
@@ -160,6 +208,9 @@ pub struct JwksAuthenticator {
     /// Cache TTL
     cache_ttl: Duration,
 
+    /// Minimum interval between fetches
+    refetch_cooldown: Duration,
+
     /// HTTP client for fetching keys
     client: reqwest::Client,
 
@@ -178,11 +229,9 @@ impl JwksAuthenticator {
 
         Ok(Self {
             endpoint: Some(endpoint.to_string()),
-            cache: Arc::new(RwLock::new(CachedJwks {
-                jwks,
-                fetched_at: std::time::Instant::now(),
-            })),
+            cache: Arc::new(RwLock::new(CachedJwks::new(jwks))),
             cache_ttl: Duration::from_secs(3600), // Default: 1 hour
+            refetch_cooldown: DEFAULT_REFETCH_COOLDOWN,
             client,
             required_issuer: None,
             required_audience: None,
@@ -196,11 +245,9 @@ impl JwksAuthenticator {
 
         Ok(Self {
             endpoint: None,
-            cache: Arc::new(RwLock::new(CachedJwks {
-                jwks,
-                fetched_at: std::time::Instant::now(),
-            })),
+            cache: Arc::new(RwLock::new(CachedJwks::new(jwks))),
             cache_ttl: Duration::from_secs(3600),
+            refetch_cooldown: DEFAULT_REFETCH_COOLDOWN,
             client: reqwest::Client::new(),
             required_issuer: None,
             required_audience: None,
@@ -219,6 +266,37 @@ impl JwksAuthenticator {
         self.cache_ttl
     }
 
+    /// Set the minimum interval between fetches of the JWKS endpoint
+    ///
+    /// Bounds how often tokens with unknown `kid`s (or a failing endpoint)
+    /// can trigger a refetch. Defaults to 30 seconds.
+    #[must_use]
+    pub fn with_refetch_cooldown(mut self, cooldown: Duration) -> Self {
+        self.refetch_cooldown = cooldown;
+        self
+    }
+
+    /// Refresh the keys in a background task every cache TTL
+    ///
+    /// Failed refreshes are retried after the refetch cool-down. The task
+    /// stops once the authenticator is dropped. Must be called from within a
+    /// Tokio runtime, after [`with_cache_ttl`](Self::with_cache_ttl) and
+    /// [`with_refetch_cooldown`](Self::with_refetch_cooldown). Does nothing
+    /// for authenticators created with [`from_json`](Self::from_json).
+    #[must_use]
+    pub fn with_background_refresh(self) -> Self {
+        if let Some(endpoint) = self.endpoint.clone() {
+            tokio::spawn(background_refresh(
+                Arc::downgrade(&self.cache),
+                self.client.clone(),
+                endpoint,
+                self.cache_ttl,
+                self.refetch_cooldown.min(self.cache_ttl),
+            ));
+        }
+        self
+    }
+
     /// Set the required issuer (`iss` claim)
     ///
     /// Tokens without this issuer will be rejected.
@@ -244,14 +322,11 @@ impl JwksAuthenticator {
     }
 
     /// Find a key by its key ID (kid)
+    ///
+    /// Keys from the key set replaced by the last rotation are still found.
     pub async fn find_key(&self, kid: &str) -> Option<Jwk> {
         let cache = self.cache.read().await;
-        cache
-            .jwks
-            .keys
-            .iter()
-            .find(|k| k.kid.as_deref() == Some(kid))
-            .cloned()
+        cache.find(kid).cloned()
     }
 
     /// Fetch JWKS from endpoint
@@ -278,23 +353,54 @@ impl JwksAuthenticator {
         Ok(jwks)
     }
 
-    /// Refresh the JWKS cache if needed
+    /// Refresh the JWKS cache if the TTL has expired
+    ///
+    /// A failed refresh only fails the request when there are no cached
+    /// keys to fall back on.
     async fn refresh_if_needed(&self) -> Result<(), AuthError> {
-        let needs_refresh = {
-            let cache = self.cache.read().await;
-            cache.fetched_at.elapsed() > self.cache_ttl
+        let Some(endpoint) = &self.endpoint else {
+            return Ok(());
         };
 
-        if needs_refresh {
-            if let Some(endpoint) = &self.endpoint {
-                let new_jwks = Self::fetch_jwks(&self.client, endpoint).await?;
-                let mut cache = self.cache.write().await;
-                cache.jwks = new_jwks;
-                cache.fetched_at = std::time::Instant::now();
-            }
+        let expired = self.cache.read().await.fetched_at.elapsed() > self.cache_ttl;
+        if !expired
+            || !self
+                .cache
+                .write()
+                .await
+                .claim_attempt(self.refetch_cooldown)
+        {
+            return Ok(());
         }
 
-        Ok(())
+        match refresh_cache(&self.cache, &self.client, endpoint, "expired").await {
+            Err(e) if !self.has_keys().await => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Look up a `kid`, refetching the key set once if it is unknown
+    ///
+    /// Refetches are subject to the cool-down, so tokens with bogus `kid`s
+    /// cannot hammer the IdP.
+    async fn find_key_or_refetch(&self, kid: &str) -> Option<Jwk> {
+        if let Some(jwk) = self.find_key(kid).await {
+            return Some(jwk);
+        }
+
+        let endpoint = self.endpoint.as_ref()?;
+        if !self
+            .cache
+            .write()
+            .await
+            .claim_attempt(self.refetch_cooldown)
+        {
+            return None;
+        }
+        refresh_cache(&self.cache, &self.client, endpoint, "unknown_kid")
+            .await
+            .ok()?;
+        self.find_key(kid).await
     }
 
     /// Extract token from request
@@ -319,6 +425,54 @@ impl JwksAuthenticator {
     }
 }
 
+/// Fetch the key set and install it in the cache
+///
+/// `trigger` labels the refresh metrics ("scheduled", "expired" or
+/// "unknown_kid").
+async fn refresh_cache(
+    cache: &RwLock<CachedJwks>,
+    client: &reqwest::Client,
+    endpoint: &str,
+    trigger: &str,
+) -> Result<(), AuthError> {
+    let result = JwksAuthenticator::fetch_jwks(client, endpoint).await;
+    crate::metrics::record_jwks_refresh(trigger, result.is_ok());
+
+    match result {
+        Ok(jwks) => {
+            cache.write().await.rotate(jwks);
+            Ok(())
+        }
+        Err(e) => {
+            tracing::warn!(endpoint, trigger, error = %e, "JWKS refresh failed, keeping cached keys");
+            Err(e)
+        }
+    }
+}
+
+/// Periodically refresh the cache until the authenticator is dropped
+async fn background_refresh(
+    cache: Weak<RwLock<CachedJwks>>,
+    client: reqwest::Client,
+    endpoint: String,
+    ttl: Duration,
+    retry_after: Duration,
+) {
+    let mut delay = ttl;
+    loop {
+        tokio::time::sleep(delay).await;
+        let Some(cache) = cache.upgrade() else {
+            return;
+        };
+
+        cache.write().await.last_attempt = Some(Instant::now());
+        delay = match refresh_cache(&cache, &client, &endpoint, "scheduled").await {
+            Ok(()) => ttl,
+            Err(_) => retry_after,
+        };
+    }
+}
+
 #[async_trait]
 impl Authenticator for JwksAuthenticator {
     async fn authenticate(&self, request: &AuthRequest) -> Result<AuthResult, AuthError> {
@@ -333,7 +487,7 @@ impl Authenticator for JwksAuthenticator {
 
         // Find the key
         let jwk = if let Some(kid) = &header.kid {
-            self.find_key(kid)
+            self.find_key_or_refetch(kid)
                 .await
                 .ok_or_else(|| AuthError::InvalidToken(format!("Key not found: {}", kid)))?
         } else {
@@ -381,27 +535,18 @@ impl Authenticator for JwksAuthenticator {
         }
 
         // Decode and validate token
-        let token_data = decode::<super::jwt::Claims>(&token, &decoding_key, &validation).map_err(
-            |e| match e.kind() {
-                jsonwebtoken::errors::ErrorKind::ExpiredSignature => AuthError::TokenExpired,
-                jsonwebtoken::errors::ErrorKind::InvalidSignature => AuthError::InvalidSignature,
-                _ => AuthError::InvalidToken(e.to_string()),
-            },
-        )?;
-
-        // Build result
-        let mut claims_map = HashMap::new();
-        if let Some(iss) = &token_data.claims.iss {
-            claims_map.insert("iss".into(), serde_json::Value::String(iss.clone()));
-        }
-        if let Some(aud) = &token_data.claims.aud {
-            claims_map.insert("aud".into(), serde_json::Value::String(aud.clone()));
-        }
-
-        Ok(AuthResult {
-            subject: token_data.claims.sub,
-            claims: claims_map,
-        })
+        let token_data = decode::<serde_json::Map<String, serde_json::Value>>(
+            &token,
+            &decoding_key,
+            &validation,
+        )
+        .map_err(|e| match e.kind() {
+            jsonwebtoken::errors::ErrorKind::ExpiredSignature => AuthError::TokenExpired,
+            jsonwebtoken::errors::ErrorKind::InvalidSignature => AuthError::InvalidSignature,
+            _ => AuthError::InvalidToken(e.to_string()),
+        })?;
+
+        super::jwt::auth_result_from_claims(token_data.claims)
     }
 }
 
@@ -446,4 +591,47 @@ mod tests {
         let result = jwk.to_decoding_key();
         assert!(result.is_ok());
     }
+
+    fn jwks(kids: &[&str]) -> Jwks {
+        Jwks {
+            keys: kids
+                .iter()
+                .map(|kid| Jwk {
+                    kty: "RSA".to_string(),
+                    kid: Some(kid.to_string()),
+                    key_use: None,
+                    alg: None,
+                    n: None,
+                    e: None,
+                    crv: None,
+                    x: None,
+                    y: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_rotation_keeps_previous_key_set() {
+        let mut cache = CachedJwks::new(jwks(&["a"]));
+        cache.rotate(jwks(&["b"]));
+        assert!(cache.find("a").is_some());
+        assert!(cache.find("b").is_some());
+
+        // Refetching an unchanged set does not drop the previous keys
+        cache.rotate(jwks(&["b"]));
+        assert!(cache.find("a").is_some());
+
+        cache.rotate(jwks(&["c"]));
+        assert!(cache.find("a").is_none());
+        assert!(cache.find("b").is_some());
+    }
+
+    #[test]
+    fn test_claim_attempt_cooldown() {
+        let mut cache = CachedJwks::new(jwks(&[]));
+        assert!(cache.claim_attempt(Duration::from_secs(60)));
+        assert!(!cache.claim_attempt(Duration::from_secs(60)));
+        assert!(cache.claim_attempt(Duration::ZERO));
+    }
 }
@@ -3,10 +3,13 @@
 //! Supports HS256, RS256, ES256 algorithms and JWKS endpoints.
 //! Reference implementation: https://github.com/julianshen/yatagarasu/blob/master/src/auth/jwt.rs
 
+use super::revocation::RevocationList;
 use super::{AuthError, AuthRequest, AuthResult, Authenticator};
+use crate::config::JwtConfig;
 use async_trait::async_trait;
 use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 /// JWT Claims
 #[derive(Debug, Serialize, Deserialize)]
@@ -21,6 +24,28 @@ pub struct Claims {
     pub aud: Option<String>,
 }
 
+/// Build an [`AuthResult`] from decoded token claims
+///
+/// All claims except the subject and the validity timestamps are reported,
+/// so that custom claims (tenant, scopes, groups, ...) can be mapped into
+/// authorization decisions.
+pub(crate) fn auth_result_from_claims(
+    claims: serde_json::Map<String, serde_json::Value>,
+) -> Result<AuthResult, AuthError> {
+    let registered: Claims = serde_json::from_value(serde_json::Value::Object(claims.clone()))
+        .map_err(|e| AuthError::InvalidToken(format!("Invalid claims: {}", e)))?;
+
+    let claims = claims
+        .into_iter()
+        .filter(|(name, _)| !matches!(name.as_str(), "sub" | "exp" | "iat" | "nbf"))
+        .collect();
+
+    Ok(AuthResult {
+        subject: registered.sub,
+        claims,
+    })
+}
+
 /// JWT Authenticator
 ///
 /// Supports HS256 (HMAC), RS256 (RSA), and ES256 (ECDSA P-256) algorithms.
@@ -41,6 +66,8 @@ pub struct Claims {
 pub struct JwtAuthenticator {
     decoding_key: DecodingKey,
     validation: Validation,
+    /// Rejects revoked tokens when set
+    revocations: Option<Arc<RevocationList>>,
 }
 
 impl JwtAuthenticator {
@@ -55,6 +82,7 @@ impl JwtAuthenticator {
         Self {
             decoding_key,
             validation,
+            revocations: None,
         }
     }
 
@@ -70,6 +98,7 @@ impl JwtAuthenticator {
         Ok(Self {
             decoding_key,
             validation,
+            revocations: None,
         })
     }
 
@@ -87,6 +116,38 @@ impl JwtAuthenticator {
         Ok(Self {
             decoding_key,
             validation,
+            revocations: None,
+        })
+    }
+
+    /// Create a JWT authenticator from bucket configuration
+    ///
+    /// The `secret` holds the HMAC secret for HS256 or the PEM public key
+    /// for RS256/ES256.
+    pub fn from_config(config: &JwtConfig) -> Result<Self, AuthError> {
+        let secret = config
+            .secret
+            .as_deref()
+            .ok_or_else(|| AuthError::ConfigError("JWT auth requires a secret".into()))?;
+
+        let authenticator = match config.algorithm.to_uppercase().as_str() {
+            "HS256" => Self::new_hs256(secret),
+            "RS256" => Self::new_rs256(secret)
+                .map_err(|e| AuthError::ConfigError(format!("Invalid RS256 key: {}", e)))?,
+            "ES256" => Self::new_es256(secret)
+                .map_err(|e| AuthError::ConfigError(format!("Invalid ES256 key: {}", e)))?,
+            alg => {
+                return Err(AuthError::ConfigError(format!(
+                    "Unsupported JWT algorithm: {}",
+                    alg
+                )))
+            }
+        };
+
+        Ok(match config.revocation {
+            Some(ref revocation) => authenticator
+                .with_revocation_list(Arc::new(RevocationList::from_config(revocation)?)),
+            None => authenticator,
         })
     }
 
@@ -109,8 +170,18 @@ impl JwtAuthenticator {
         self
     }
 
+    /// Reject tokens revoked by `list`
+    ///
+    /// See [`revocation`](super::revocation) for how the list is kept
+    /// up to date.
+    #[must_use]
+    pub fn with_revocation_list(mut self, list: Arc<RevocationList>) -> Self {
+        self.revocations = Some(list);
+        self
+    }
+
     /// Extract token from request
-    fn extract_token(&self, request: &AuthRequest) -> Option<String> {
+    pub(crate) fn extract_token(&self, request: &AuthRequest) -> Option<String> {
         // Try Authorization header first
         if let Some(auth) = request.headers.get("authorization") {
             if let Some(token) = auth.strip_prefix("Bearer ") {
@@ -146,33 +217,30 @@ impl Authenticator for JwtAuthenticator {
     async fn authenticate(&self, request: &AuthRequest) -> Result<AuthResult, AuthError> {
         let token = self.extract_token(request).ok_or(AuthError::MissingAuth)?;
 
-        let token_data =
-            decode::<Claims>(&token, &self.decoding_key, &self.validation).map_err(|e| match e
-                .kind()
-            {
-                jsonwebtoken::errors::ErrorKind::ExpiredSignature => AuthError::TokenExpired,
-                jsonwebtoken::errors::ErrorKind::InvalidSignature => AuthError::InvalidSignature,
-                _ => AuthError::InvalidToken(e.to_string()),
-            })?;
-
-        let mut claims_map = std::collections::HashMap::new();
-        if let Some(iss) = &token_data.claims.iss {
-            claims_map.insert("iss".into(), serde_json::Value::String(iss.clone()));
-        }
-        if let Some(aud) = &token_data.claims.aud {
-            claims_map.insert("aud".into(), serde_json::Value::String(aud.clone()));
+        let token_data = decode::<serde_json::Map<String, serde_json::Value>>(
+            &token,
+            &self.decoding_key,
+            &self.validation,
+        )
+        .map_err(|e| match e.kind() {
+            jsonwebtoken::errors::ErrorKind::ExpiredSignature => AuthError::TokenExpired,
+            jsonwebtoken::errors::ErrorKind::InvalidSignature => AuthError::InvalidSignature,
+            _ => AuthError::InvalidToken(e.to_string()),
+        })?;
+
+        if let Some(ref revocations) = self.revocations {
+            revocations.check(&token_data.claims).await?;
         }
 
+        let result = auth_result_from_claims(token_data.claims)?;
+
         #[cfg(feature = "tracing")]
         tracing::info!(
-            subject = %token_data.claims.sub,
+            subject = %result.subject,
             "JWT authentication successful"
         );
 
-        Ok(AuthResult {
-            subject: token_data.claims.sub,
-            claims: claims_map,
-        })
+        Ok(result)
     }
 }
 
@@ -194,6 +262,7 @@ mod tests {
             query: None,
             method: "PUT".into(),
             path: "/bucket/key".into(),
+            peer_certificates: Vec::new(),
         };
 
         let result = auth.authenticate(&request).await;
@@ -1,16 +1,29 @@
 //! Authentication module
 //!
-//! Provides JWT and SigV4 authentication.
+//! Provides JWT, SigV4, API key and mTLS client-certificate authentication, composed
+//! per bucket by a [`chain::AuthChain`]. SigV4 secret keys may come from external
+//! stores ([`sigv4_store`]), JWTs may be exchanged for delegated tokens
+//! ([`token_exchange`]) and revoked before they expire ([`revocation`]).
 //!
 //! Note: JWT implementation can be referenced from Yatagarasu:
 //! https://github.com/julianshen/yatagarasu/tree/master/src/auth
 
+use crate::error::{Categorized, ErrorCategory};
 use async_trait::async_trait;
 use thiserror::Error;
 
+pub mod anonymous;
+pub mod api_key;
+pub mod chain;
 pub mod jwks;
 pub mod jwt;
+pub mod mtls;
+pub mod ocsp;
+pub mod revocation;
 pub mod sigv4;
+pub mod sigv4_replay;
+pub mod sigv4_store;
+pub mod token_exchange;
 
 #[cfg(feature = "tracing")]
 pub mod jwt_tracing;
@@ -29,11 +42,44 @@ pub enum AuthError {
     #[error("Token expired")]
     TokenExpired,
 
+    #[error("Token revoked")]
+    TokenRevoked,
+
     #[error("Invalid signature")]
     InvalidSignature,
 
     #[error("JWKS fetch error: {0}")]
     JwksFetchError(String),
+
+    #[error("Token exchange error: {0}")]
+    TokenExchangeError(String),
+
+    #[error("Invalid client certificate: {0}")]
+    InvalidCertificate(String),
+
+    #[error("Authenticator configuration error: {0}")]
+    ConfigError(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+}
+
+impl Categorized for AuthError {
+    fn category(&self) -> ErrorCategory {
+        match self {
+            AuthError::JwksFetchError(_) | AuthError::TokenExchangeError(_) => {
+                ErrorCategory::Backend
+            }
+            AuthError::ConfigError(_) => ErrorCategory::Server,
+            AuthError::MissingAuth
+            | AuthError::InvalidToken(_)
+            | AuthError::TokenExpired
+            | AuthError::TokenRevoked
+            | AuthError::InvalidSignature
+            | AuthError::InvalidCertificate(_)
+            | AuthError::Forbidden(_) => ErrorCategory::Auth,
+        }
+    }
 }
 
 /// Authentication result containing claims
@@ -43,6 +89,13 @@ pub struct AuthResult {
     pub claims: std::collections::HashMap<String, serde_json::Value>,
 }
 
+impl AuthResult {
+    /// Party acting on behalf of the subject (`act.sub` claim, RFC 8693)
+    pub fn actor(&self) -> Option<&str> {
+        self.claims.get("act")?.get("sub")?.as_str()
+    }
+}
+
 /// Authenticator trait
 #[async_trait]
 pub trait Authenticator: Send + Sync {
@@ -57,6 +110,8 @@ pub struct AuthRequest {
     pub query: Option<String>,
     pub method: String,
     pub path: String,
+    /// DER-encoded certificate chain presented over mTLS, leaf first
+    pub peer_certificates: Vec<Vec<u8>>,
 }
 
 #[cfg(test)]
@@ -0,0 +1,314 @@
+//! mTLS client-certificate authentication
+//!
+//! Authenticates requests by the client certificate presented during the TLS
+//! handshake. The chain is verified against a configured CA bundle and,
+//! optionally, certificate revocation lists and the CA's OCSP responder
+//! (see [`ocsp`](super::ocsp)). The subject used for authorization is taken
+//! from the certificate's SAN or CN.
+//!
+//! SANs are prefixed with their type (`uri:`, `dns:` or `email:`), so a
+//! certificate with the DNS name `admin` is never mistaken for one with the
+//! URI `admin`. CNs are decoded from whichever directory string type they
+//! use, including BMPString and UniversalString.
+//!
+//! # Claims
+//!
+//! The [`AuthResult`] claims contain:
+//!
+//! - `cn` - Subject common name (if present)
+//! - `sans` - Subject alternative names (URI, DNS and email entries, prefixed)
+//! - `auth_method` - Always `"mtls"`
+
+use super::ocsp::OcspChecker;
+use super::{AuthError, AuthRequest, AuthResult, Authenticator};
+use crate::config::{CertSubjectSource, MtlsConfig};
+use async_trait::async_trait;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, CertificateRevocationListDer, UnixTime};
+use rustls::server::danger::ClientCertVerifier;
+use rustls::server::WebPkiClientVerifier;
+use rustls::RootCertStore;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// mTLS client-certificate authenticator
+///
+/// # Example
+///
+/// ```no_run
+/// use mizuchi_uploadr::auth::mtls::MtlsAuthenticator;
+/// use mizuchi_uploadr::config::CertSubjectSource;
+///
+/// let ca = std::fs::read("/etc/mizuchi/clients-ca.pem").unwrap();
+/// let auth = MtlsAuthenticator::new(&ca, &[], CertSubjectSource::San).unwrap();
+/// ```
+pub struct MtlsAuthenticator {
+    verifier: Arc<dyn ClientCertVerifier>,
+    subject_source: CertSubjectSource,
+    ocsp: Option<OcspChecker>,
+}
+
+impl std::fmt::Debug for MtlsAuthenticator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MtlsAuthenticator")
+            .field("subject_source", &self.subject_source)
+            .field("ocsp", &self.ocsp.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl MtlsAuthenticator {
+    /// Create an authenticator from PEM-encoded CA certificates and CRLs
+    ///
+    /// # Arguments
+    ///
+    /// * `ca_pem` - CA bundle that client certificates must chain to
+    /// * `crl_pems` - CRLs checked for the client (end-entity) certificate
+    /// * `subject_source` - Certificate field used as the auth subject
+    pub fn new(
+        ca_pem: &[u8],
+        crl_pems: &[Vec<u8>],
+        subject_source: CertSubjectSource,
+    ) -> Result<Self, AuthError> {
+        let mut roots = RootCertStore::empty();
+        for cert in CertificateDer::pem_slice_iter(ca_pem) {
+            let cert =
+                cert.map_err(|e| AuthError::ConfigError(format!("Invalid CA PEM: {}", e)))?;
+            roots
+                .add(cert)
+                .map_err(|e| AuthError::ConfigError(format!("Invalid CA certificate: {}", e)))?;
+        }
+        if roots.is_empty() {
+            return Err(AuthError::ConfigError(
+                "CA bundle contains no certificates".into(),
+            ));
+        }
+
+        let mut crls = Vec::new();
+        for pem in crl_pems {
+            for crl in CertificateRevocationListDer::pem_slice_iter(pem) {
+                crls.push(crl.map_err(|e| AuthError::ConfigError(format!("Invalid CRL: {}", e)))?);
+            }
+        }
+
+        let verifier = WebPkiClientVerifier::builder_with_provider(
+            Arc::new(roots),
+            Arc::new(rustls::crypto::aws_lc_rs::default_provider()),
+        )
+        .with_crls(crls)
+        .only_check_end_entity_revocation()
+        .build()
+        .map_err(|e| AuthError::ConfigError(format!("Failed to build verifier: {}", e)))?;
+
+        Ok(Self {
+            verifier,
+            subject_source,
+            ocsp: None,
+        })
+    }
+
+    /// Also check client certificates with their CA's OCSP responder
+    pub fn with_ocsp(mut self, ocsp: OcspChecker) -> Self {
+        self.ocsp = Some(ocsp);
+        self
+    }
+
+    /// Create an authenticator from bucket configuration, reading files from disk
+    pub fn from_config(config: &MtlsConfig) -> Result<Self, AuthError> {
+        let read = |path: &str| {
+            std::fs::read(path)
+                .map_err(|e| AuthError::ConfigError(format!("Failed to read {}: {}", path, e)))
+        };
+
+        let ca = read(&config.ca_path)?;
+        let crls = config
+            .crl_paths
+            .iter()
+            .map(|path| read(path))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let authenticator = Self::new(&ca, &crls, config.subject)?;
+        match config.ocsp {
+            Some(ref ocsp) => Ok(authenticator.with_ocsp(OcspChecker::new(ocsp.clone(), &ca)?)),
+            None => Ok(authenticator),
+        }
+    }
+}
+
+#[async_trait]
+impl Authenticator for MtlsAuthenticator {
+    async fn authenticate(&self, request: &AuthRequest) -> Result<AuthResult, AuthError> {
+        let (leaf, presented) = request
+            .peer_certificates
+            .split_first()
+            .ok_or(AuthError::MissingAuth)?;
+
+        let end_entity = CertificateDer::from(leaf.as_slice());
+        let intermediates: Vec<CertificateDer<'_>> = presented
+            .iter()
+            .map(|c| CertificateDer::from(c.as_slice()))
+            .collect();
+
+        self.verifier
+            .verify_client_cert(&end_entity, &intermediates, UnixTime::now())
+            .map_err(|e| AuthError::InvalidCertificate(e.to_string()))?;
+
+        if let Some(ref ocsp) = self.ocsp {
+            ocsp.check(leaf, presented).await?;
+        }
+
+        let names = x509::parse_names(leaf).map_err(AuthError::InvalidCertificate)?;
+
+        let subject = match self.subject_source {
+            CertSubjectSource::San => names.sans.first().cloned(),
+            CertSubjectSource::Cn => names.common_name.clone(),
+        }
+        .ok_or_else(|| {
+            AuthError::InvalidCertificate(format!(
+                "Certificate has no {} to use as subject",
+                match self.subject_source {
+                    CertSubjectSource::San => "subject alternative name",
+                    CertSubjectSource::Cn => "common name",
+                }
+            ))
+        })?;
+
+        let mut claims = HashMap::new();
+        if let Some(cn) = names.common_name {
+            claims.insert("cn".to_string(), serde_json::Value::String(cn));
+        }
+        claims.insert("sans".to_string(), serde_json::json!(names.sans));
+        claims.insert("auth_method".to_string(), serde_json::json!("mtls"));
+
+        Ok(AuthResult { subject, claims })
+    }
+}
+
+/// X.509 name extraction
+///
+/// Signature and validity checks are done by the verifier before this runs.
+mod x509 {
+    use x509_parser::asn1_rs::{Any, BmpString, Tag, UniversalString};
+    use x509_parser::certificate::X509Certificate;
+    use x509_parser::extensions::GeneralName;
+    use x509_parser::prelude::FromDer;
+
+    /// Names found in a certificate
+    #[derive(Debug, Default)]
+    pub struct CertNames {
+        pub common_name: Option<String>,
+        pub sans: Vec<String>,
+    }
+
+    /// Extract the subject CN and the URI, DNS and email SANs from a DER
+    /// certificate
+    pub fn parse_names(der: &[u8]) -> Result<CertNames, String> {
+        let (_, cert) =
+            X509Certificate::from_der(der).map_err(|e| format!("Malformed certificate: {}", e))?;
+
+        let common_name = cert
+            .subject()
+            .iter_common_name()
+            .next()
+            .map(|cn| directory_string(cn.attr_value()))
+            .transpose()?;
+
+        let mut sans = Vec::new();
+        let extension = cert
+            .subject_alternative_name()
+            .map_err(|e| format!("Malformed subject alternative names: {}", e))?;
+        for name in extension.iter().flat_map(|san| &san.value.general_names) {
+            match name {
+                GeneralName::URI(uri) => sans.push(format!("uri:{}", uri)),
+                GeneralName::DNSName(dns) => sans.push(format!("dns:{}", dns)),
+                GeneralName::RFC822Name(email) => sans.push(format!("email:{}", email)),
+                GeneralName::Invalid(..) => {
+                    return Err("Malformed subject alternative name".into());
+                }
+                _ => {}
+            }
+        }
+
+        Ok(CertNames { common_name, sans })
+    }
+
+    /// Decode a DirectoryString (or the IA5String some CAs use instead)
+    fn directory_string(value: &Any<'_>) -> Result<String, String> {
+        let malformed = |_| "Malformed common name".to_string();
+        match value.tag() {
+            Tag::Utf8String | Tag::PrintableString | Tag::Ia5String => {
+                std::str::from_utf8(value.as_bytes())
+                    .map(str::to_owned)
+                    .map_err(malformed)
+            }
+            // Latin-1, as OpenSSL and browsers read it in practice
+            Tag::T61String => Ok(value.as_bytes().iter().map(|&b| char::from(b)).collect()),
+            Tag::BmpString => BmpString::try_from(value.clone())
+                .map(|s| s.string())
+                .map_err(|_| "Malformed common name".to_string()),
+            Tag::UniversalString => UniversalString::try_from(value.clone())
+                .map(|s| s.string())
+                .map_err(|_| "Malformed common name".to_string()),
+            tag => Err(format!("Unsupported common name encoding ({})", tag)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> Vec<u8> {
+        std::fs::read(format!(
+            "{}/tests/fixtures/mtls/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        ))
+        .unwrap()
+    }
+
+    fn der(name: &str) -> Vec<u8> {
+        CertificateDer::from_pem_slice(&fixture(name))
+            .unwrap()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_parse_names_reads_cn_and_sans() {
+        let names = x509::parse_names(&der("client.pem")).unwrap();
+        assert_eq!(names.common_name.as_deref(), Some("uploader"));
+        assert_eq!(
+            names.sans,
+            vec![
+                "uri:spiffe://example.org/uploader",
+                "dns:uploader.internal",
+                "email:uploader@example.org"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_names_without_san() {
+        let names = x509::parse_names(&der("cn-only.pem")).unwrap();
+        assert_eq!(names.common_name.as_deref(), Some("cn-only-service"));
+        assert!(names.sans.is_empty());
+    }
+
+    #[test]
+    fn test_parse_names_decodes_wide_strings() {
+        let names = x509::parse_names(&der("cn-bmp.pem")).unwrap();
+        assert_eq!(names.common_name.as_deref(), Some("アップローダー"));
+        let names = x509::parse_names(&der("cn-universal.pem")).unwrap();
+        assert_eq!(names.common_name.as_deref(), Some("𝔲𝔭𝔩𝔬𝔞𝔡𝔢𝔯"));
+    }
+
+    #[test]
+    fn test_parse_names_rejects_garbage() {
+        assert!(x509::parse_names(&[0x30, 0x05, 0x01]).is_err());
+    }
+
+    #[test]
+    fn test_empty_ca_bundle_rejected() {
+        assert!(MtlsAuthenticator::new(b"", &[], CertSubjectSource::San).is_err());
+    }
+}
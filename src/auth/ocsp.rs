@@ -0,0 +1,457 @@
+//! OCSP checks of mTLS client certificates
+//!
+//! A CRL only revokes a certificate once it is republished and reloaded.
+//! With OCSP the status of each client certificate is asked of the CA's
+//! responder instead: the one named in the certificate's Authority
+//! Information Access extension, or the one configured.
+//!
+//! Responses must be signed by the certificate's issuer, or by a responder
+//! certificate the issuer signed for OCSP signing, and must be current. The
+//! status is cached per certificate until the response's `nextUpdate`, for
+//! at most `cache_secs`, so the responder is not asked on every request.
+//!
+//! Revoked certificates are always rejected. When the status cannot be
+//! learned (the responder is unreachable, answers with an error or does not
+//! know the certificate) the certificate is rejected too, unless
+//! `soft_fail` is set.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use mizuchi_uploadr::auth::mtls::MtlsAuthenticator;
+//! use mizuchi_uploadr::auth::ocsp::OcspChecker;
+//! use mizuchi_uploadr::config::{CertSubjectSource, MtlsOcspConfig};
+//!
+//! let ca = std::fs::read("/etc/mizuchi/clients-ca.pem").unwrap();
+//! let ocsp = OcspChecker::new(
+//!     MtlsOcspConfig {
+//!         responder_url: None,
+//!         timeout_ms: 5000,
+//!         cache_secs: 300,
+//!         soft_fail: false,
+//!     },
+//!     &ca,
+//! )
+//! .unwrap();
+//! let auth = MtlsAuthenticator::new(&ca, &[], CertSubjectSource::San)
+//!     .unwrap()
+//!     .with_ocsp(ocsp);
+//! ```
+
+use super::AuthError;
+use crate::config::MtlsOcspConfig;
+use lru::LruCache;
+use parking_lot::Mutex;
+use rustls::crypto::WebPkiSupportedAlgorithms;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::CertificateDer;
+use sha1::{Digest, Sha1};
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::warn;
+use x509_cert::der::asn1::{BitString, Null, OctetString};
+use x509_cert::der::oid::db::{rfc5280, rfc5912, rfc6960};
+use x509_cert::der::{Decode, Encode};
+use x509_cert::ext::pkix::name::GeneralName;
+use x509_cert::ext::pkix::{AuthorityInfoAccessSyntax, ExtendedKeyUsage};
+use x509_cert::spki::{AlgorithmIdentifierOwned, SubjectPublicKeyInfoOwned};
+use x509_cert::Certificate;
+use x509_ocsp::{
+    BasicOcspResponse, CertId, CertStatus, OcspRequest, OcspResponse, OcspResponseStatus, Request,
+    TbsRequest,
+};
+
+/// Most certificate statuses kept
+const CACHE_CAPACITY: usize = 10_000;
+
+/// Clock skew allowed between the proxy and the responder
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(300);
+
+/// Certificate status learned from a responder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Good,
+    Revoked,
+    Unknown,
+}
+
+struct CacheEntry {
+    status: Status,
+    expires_at: Instant,
+}
+
+/// Checks client certificates with their CA's OCSP responder
+pub struct OcspChecker {
+    config: MtlsOcspConfig,
+    client: reqwest::Client,
+    /// CA certificates, where issuers not sent by the client are found
+    ca_certs: Vec<Certificate>,
+    algorithms: WebPkiSupportedAlgorithms,
+    /// Keyed by the DER of the certificate's OCSP `CertID`
+    cache: Mutex<LruCache<Vec<u8>, CacheEntry>>,
+}
+
+impl OcspChecker {
+    /// Check certificates issued by the CAs of `ca_pem` as `config` says
+    pub fn new(config: MtlsOcspConfig, ca_pem: &[u8]) -> Result<Self, AuthError> {
+        let ca_certs = CertificateDer::pem_slice_iter(ca_pem)
+            .map(|cert| {
+                let cert =
+                    cert.map_err(|e| AuthError::ConfigError(format!("Invalid CA PEM: {}", e)))?;
+                Certificate::from_der(&cert)
+                    .map_err(|e| AuthError::ConfigError(format!("Invalid CA certificate: {}", e)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()
+            .expect("Failed to build HTTP client");
+
+        Ok(Self {
+            config,
+            client,
+            ca_certs,
+            algorithms: rustls::crypto::aws_lc_rs::default_provider()
+                .signature_verification_algorithms,
+            cache: Mutex::new(LruCache::new(
+                NonZeroUsize::new(CACHE_CAPACITY).expect("cache capacity is non-zero"),
+            )),
+        })
+    }
+
+    /// Check the status of `leaf`, issued by one of `intermediates` or a CA
+    ///
+    /// The chain must already have been verified.
+    pub async fn check(&self, leaf: &[u8], intermediates: &[Vec<u8>]) -> Result<(), AuthError> {
+        let status = match self.status(leaf, intermediates).await {
+            Ok(status) => status,
+            Err(e) if self.config.soft_fail => {
+                warn!(error = %e, "OCSP check failed, accepting certificate");
+                return Ok(());
+            }
+            Err(e) => {
+                return Err(AuthError::InvalidCertificate(format!(
+                    "OCSP check failed: {}",
+                    e
+                )))
+            }
+        };
+
+        match status {
+            Status::Good => Ok(()),
+            Status::Revoked => Err(AuthError::InvalidCertificate(
+                "Certificate revoked (OCSP)".into(),
+            )),
+            Status::Unknown if self.config.soft_fail => {
+                warn!("OCSP responder does not know the certificate, accepting it");
+                Ok(())
+            }
+            Status::Unknown => Err(AuthError::InvalidCertificate(
+                "Certificate status unknown to the OCSP responder".into(),
+            )),
+        }
+    }
+
+    /// Status of `leaf`, from the cache or its responder
+    async fn status(&self, leaf: &[u8], intermediates: &[Vec<u8>]) -> Result<Status, String> {
+        let leaf =
+            Certificate::from_der(leaf).map_err(|e| format!("Malformed certificate: {}", e))?;
+        let presented = intermediates
+            .iter()
+            .filter_map(|der| Certificate::from_der(der).ok())
+            .collect::<Vec<_>>();
+        let issuer = presented
+            .iter()
+            .chain(&self.ca_certs)
+            .find(|cert| cert.tbs_certificate.subject == leaf.tbs_certificate.issuer)
+            .ok_or("issuer certificate not found")?;
+
+        let cert_id = cert_id(&leaf, issuer)?;
+        let key = cert_id.to_der().map_err(|e| e.to_string())?;
+        if let Some(status) = self.cached(&key) {
+            return Ok(status);
+        }
+
+        let url = match self.config.responder_url {
+            Some(ref url) => url.clone(),
+            None => responder_url(&leaf).ok_or("certificate names no OCSP responder")?,
+        };
+        let response = self.fetch(&url, &cert_id).await?;
+        let (status, next_update) = self.verify(&response, &cert_id, issuer)?;
+
+        let mut ttl = Duration::from_secs(self.config.cache_secs);
+        if let Some(next_update) = next_update {
+            let remaining = next_update
+                .duration_since(SystemTime::now())
+                .unwrap_or_default();
+            ttl = ttl.min(remaining);
+        }
+        self.cache.lock().put(
+            key,
+            CacheEntry {
+                status,
+                expires_at: Instant::now() + ttl,
+            },
+        );
+
+        Ok(status)
+    }
+
+    fn cached(&self, key: &[u8]) -> Option<Status> {
+        let mut cache = self.cache.lock();
+        match cache.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.status),
+            Some(_) => {
+                cache.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Ask the responder at `url` about `cert_id`
+    async fn fetch(&self, url: &str, cert_id: &CertId) -> Result<Vec<u8>, String> {
+        let request = OcspRequest {
+            tbs_request: TbsRequest {
+                request_list: vec![Request {
+                    req_cert: cert_id.clone(),
+                    single_request_extensions: None,
+                }],
+                ..Default::default()
+            },
+            optional_signature: None,
+        };
+        let body = request.to_der().map_err(|e| e.to_string())?;
+
+        let response = self
+            .client
+            .post(url)
+            .header("content-type", "application/ocsp-request")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| format!("responder {} unreachable: {}", url, e))?;
+        if !response.status().is_success() {
+            return Err(format!("responder {} returned {}", url, response.status()));
+        }
+        response
+            .bytes()
+            .await
+            .map(|body| body.to_vec())
+            .map_err(|e| format!("responder {} unreachable: {}", url, e))
+    }
+
+    /// Status of `cert_id` in `response`, and when it should be refreshed
+    ///
+    /// The response must be signed by `issuer` or a responder it delegated
+    /// to, and current.
+    fn verify(
+        &self,
+        response: &[u8],
+        cert_id: &CertId,
+        issuer: &Certificate,
+    ) -> Result<(Status, Option<SystemTime>), String> {
+        let response =
+            OcspResponse::from_der(response).map_err(|e| format!("malformed response: {}", e))?;
+        if response.response_status != OcspResponseStatus::Successful {
+            return Err(format!("responder answered {:?}", response.response_status));
+        }
+        let bytes = response
+            .response_bytes
+            .filter(|bytes| bytes.response_type == rfc6960::ID_PKIX_OCSP_BASIC)
+            .ok_or("response is not a basic OCSP response")?;
+        let basic = BasicOcspResponse::from_der(bytes.response.as_bytes())
+            .map_err(|e| format!("malformed response: {}", e))?;
+
+        let tbs = basic
+            .tbs_response_data
+            .to_der()
+            .map_err(|e| e.to_string())?;
+        let issuer_key = &issuer.tbs_certificate.subject_public_key_info;
+        let signed_by = |key: &SubjectPublicKeyInfoOwned| {
+            self.verify_signature(key, &basic.signature_algorithm, &basic.signature, &tbs)
+        };
+        let now = SystemTime::now();
+        let authorized = signed_by(issuer_key)
+            || basic.certs.iter().flatten().any(|responder| {
+                self.is_delegated_responder(responder, issuer, now)
+                    && signed_by(&responder.tbs_certificate.subject_public_key_info)
+            });
+        if !authorized {
+            return Err("response is not signed by the issuer or its responder".into());
+        }
+
+        let single = basic
+            .tbs_response_data
+            .responses
+            .iter()
+            .find(|single| same_certificate(&single.cert_id, cert_id))
+            .ok_or("response does not cover the certificate")?;
+        if single.this_update.0.to_system_time() > now + MAX_CLOCK_SKEW {
+            return Err("response is not yet valid".into());
+        }
+        let next_update = single.next_update.map(|time| time.0.to_system_time());
+        if next_update.is_some_and(|next_update| next_update + MAX_CLOCK_SKEW < now) {
+            return Err("response is stale".into());
+        }
+
+        let status = match single.cert_status {
+            CertStatus::Good(_) => Status::Good,
+            CertStatus::Revoked(_) => Status::Revoked,
+            CertStatus::Unknown(_) => Status::Unknown,
+        };
+        Ok((status, next_update))
+    }
+
+    /// Whether `responder` was issued by `issuer` for signing OCSP
+    /// responses (RFC 6960 section 4.2.2.2), and is valid at `now`
+    fn is_delegated_responder(
+        &self,
+        responder: &Certificate,
+        issuer: &Certificate,
+        now: SystemTime,
+    ) -> bool {
+        let tbs = &responder.tbs_certificate;
+        let validity = &tbs.validity;
+        let ocsp_signing = tbs
+            .extensions
+            .iter()
+            .flatten()
+            .filter(|extension| extension.extn_id == rfc5280::ID_CE_EXT_KEY_USAGE)
+            .filter_map(|extension| {
+                ExtendedKeyUsage::from_der(extension.extn_value.as_bytes()).ok()
+            })
+            .any(|usage| usage.0.contains(&rfc5280::ID_KP_OCSP_SIGNING));
+        let Ok(tbs_der) = tbs.to_der() else {
+            return false;
+        };
+
+        tbs.issuer == issuer.tbs_certificate.subject
+            && ocsp_signing
+            && validity.not_before.to_system_time() <= now
+            && now <= validity.not_after.to_system_time()
+            && self.verify_signature(
+                &issuer.tbs_certificate.subject_public_key_info,
+                &responder.signature_algorithm,
+                &responder.signature,
+                &tbs_der,
+            )
+    }
+
+    /// Whether `signature` of `message` verifies with `key`
+    fn verify_signature(
+        &self,
+        key: &SubjectPublicKeyInfoOwned,
+        algorithm: &AlgorithmIdentifierOwned,
+        signature: &BitString,
+        message: &[u8],
+    ) -> bool {
+        let (Ok(key_algorithm), Ok(signature_algorithm), Some(signature)) = (
+            algorithm_id(&key.algorithm),
+            algorithm_id(algorithm),
+            signature.as_bytes(),
+        ) else {
+            return false;
+        };
+
+        self.algorithms
+            .all
+            .iter()
+            .filter(|supported| {
+                supported.public_key_alg_id().as_ref() == key_algorithm.as_slice()
+                    && supported.signature_alg_id().as_ref() == signature_algorithm.as_slice()
+            })
+            .any(|supported| {
+                supported
+                    .verify_signature(key.subject_public_key.raw_bytes(), message, signature)
+                    .is_ok()
+            })
+    }
+}
+
+/// OCSP `CertID` of `cert`, issued by `issuer`
+fn cert_id(cert: &Certificate, issuer: &Certificate) -> Result<CertId, String> {
+    let issuer_name = issuer
+        .tbs_certificate
+        .subject
+        .to_der()
+        .map_err(|e| e.to_string())?;
+    let issuer_key = issuer
+        .tbs_certificate
+        .subject_public_key_info
+        .subject_public_key
+        .raw_bytes();
+    let hash = |data: &[u8]| OctetString::new(Sha1::digest(data).to_vec());
+
+    Ok(CertId {
+        hash_algorithm: AlgorithmIdentifierOwned {
+            oid: rfc5912::ID_SHA_1,
+            parameters: Some(Null.into()),
+        },
+        issuer_name_hash: hash(&issuer_name).map_err(|e| e.to_string())?,
+        issuer_key_hash: hash(issuer_key).map_err(|e| e.to_string())?,
+        serial_number: cert.tbs_certificate.serial_number.clone(),
+    })
+}
+
+/// Whether two `CertID`s identify the same certificate
+///
+/// Responders may encode the SHA-1 parameters differently from the request.
+fn same_certificate(a: &CertId, b: &CertId) -> bool {
+    a.hash_algorithm.oid == b.hash_algorithm.oid
+        && a.issuer_name_hash == b.issuer_name_hash
+        && a.issuer_key_hash == b.issuer_key_hash
+        && a.serial_number == b.serial_number
+}
+
+/// OCSP responder named in the Authority Information Access extension
+pub fn responder_url(cert: &Certificate) -> Option<String> {
+    cert.tbs_certificate
+        .extensions
+        .iter()
+        .flatten()
+        .filter(|extension| extension.extn_id == rfc5280::ID_PE_AUTHORITY_INFO_ACCESS)
+        .filter_map(|extension| {
+            AuthorityInfoAccessSyntax::from_der(extension.extn_value.as_bytes()).ok()
+        })
+        .flat_map(|access| access.0)
+        .filter(|description| description.access_method == rfc5280::ID_AD_OCSP)
+        .find_map(|description| match description.access_location {
+            GeneralName::UniformResourceIdentifier(uri) => Some(uri.to_string()),
+            _ => None,
+        })
+}
+
+/// DER of an AlgorithmIdentifier's contents, as webpki identifies
+/// algorithms
+fn algorithm_id(algorithm: &AlgorithmIdentifierOwned) -> Result<Vec<u8>, x509_cert::der::Error> {
+    let mut der = algorithm.oid.to_der()?;
+    if let Some(ref parameters) = algorithm.parameters {
+        der.extend(parameters.to_der()?);
+    }
+    Ok(der)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn certificate(name: &str) -> Certificate {
+        let pem = std::fs::read(format!(
+            "{}/tests/fixtures/mtls/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        ))
+        .unwrap();
+        Certificate::from_der(&CertificateDer::from_pem_slice(&pem).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_responder_url_from_authority_info_access() {
+        assert_eq!(
+            responder_url(&certificate("client.pem")).as_deref(),
+            Some("http://ocsp.mizuchi.test")
+        );
+        assert_eq!(responder_url(&certificate("cn-only.pem")), None);
+    }
+}
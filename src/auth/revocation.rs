@@ -0,0 +1,366 @@
+//! JWT revocation
+//!
+//! A JWT stays valid until it expires, so a leaked token could otherwise be
+//! used for its whole lifetime. A [`RevocationList`] rejects tokens whose
+//! `jti` is listed, and tokens issued before their subject's "not issued
+//! before" cutoff (e.g. every token of a compromised account, without
+//! knowing their IDs).
+//!
+//! The list is loaded from a [`RevocationStore`] (a file or Redis) and
+//! reloaded by the first request after each refresh interval; requests
+//! arriving during the reload keep using the current list. If a reload fails
+//! the last list stays in use. Until a first load succeeds, tokens are
+//! rejected, since revoked ones could not be told apart.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::auth::jwt::JwtAuthenticator;
+//! use mizuchi_uploadr::auth::revocation::{RevocationList, Revocations};
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! let mut revocations = Revocations::default();
+//! revocations.jtis.insert("4f1c2a9e".into());
+//! revocations.not_before.insert("mallory".into(), 1_760_000_000);
+//!
+//! let auth = JwtAuthenticator::new_hs256("secret").with_revocation_list(Arc::new(
+//!     RevocationList::new(Arc::new(revocations), Duration::from_secs(30)),
+//! ));
+//! ```
+
+use super::AuthError;
+use crate::config::{JwtRevocationConfig, RevocationStoreConfig};
+use crate::metrics;
+use crate::redis::RedisClient;
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Revoked token IDs and subject cutoffs
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct Revocations {
+    /// Revoked `jti`s
+    #[serde(default)]
+    pub jtis: HashSet<String>,
+    /// Unix time per subject; the subject's tokens issued earlier are revoked
+    #[serde(default)]
+    pub not_before: HashMap<String, u64>,
+}
+
+impl Revocations {
+    /// Why the token with `claims` is revoked ("jti" or "not_before"), if it is
+    pub fn reason(&self, claims: &Map<String, Value>) -> Option<&'static str> {
+        if let Some(jti) = claims.get("jti").and_then(Value::as_str) {
+            if self.jtis.contains(jti) {
+                return Some("jti");
+            }
+        }
+        let subject = claims.get("sub").and_then(Value::as_str)?;
+        let cutoff = self.not_before.get(subject)?;
+        match claims.get("iat").and_then(Value::as_u64) {
+            Some(issued_at) if issued_at >= *cutoff => None,
+            // Without `iat` a token cannot show it was issued after the cutoff
+            _ => Some("not_before"),
+        }
+    }
+}
+
+/// Source of the revocation list
+#[async_trait]
+pub trait RevocationStore: Send + Sync {
+    /// Load the whole list
+    async fn load(&self) -> Result<Revocations, AuthError>;
+}
+
+/// A fixed list
+#[async_trait]
+impl RevocationStore for Revocations {
+    async fn load(&self) -> Result<Revocations, AuthError> {
+        Ok(self.clone())
+    }
+}
+
+/// List read from a YAML or JSON file
+///
+/// ```yaml
+/// jtis: ["4f1c2a9e"]
+/// not_before:
+///   mallory: 1760000000
+/// ```
+pub struct FileRevocationStore {
+    path: String,
+}
+
+impl FileRevocationStore {
+    /// Read the list from `path`; fails if it cannot be read or parsed now
+    pub fn new(path: &str) -> Result<Self, AuthError> {
+        parse_file(path, &std::fs::read_to_string(path))?;
+        Ok(Self {
+            path: path.to_string(),
+        })
+    }
+}
+
+fn parse_file(path: &str, contents: &std::io::Result<String>) -> Result<Revocations, AuthError> {
+    let contents = contents
+        .as_ref()
+        .map_err(|e| AuthError::ConfigError(format!("Failed to read {}: {}", path, e)))?;
+    serde_yaml::from_str(contents)
+        .map_err(|e| AuthError::ConfigError(format!("Invalid revocation list {}: {}", path, e)))
+}
+
+#[async_trait]
+impl RevocationStore for FileRevocationStore {
+    async fn load(&self) -> Result<Revocations, AuthError> {
+        parse_file(&self.path, &tokio::fs::read_to_string(&self.path).await)
+    }
+}
+
+/// List kept in Redis: a set `<key_prefix>jti` of revoked token IDs and a
+/// hash `<key_prefix>not_before` of subject cutoffs (Unix time)
+pub struct RedisRevocationStore {
+    client: RedisClient,
+    key_prefix: String,
+}
+
+impl RedisRevocationStore {
+    pub fn new(client: RedisClient, key_prefix: &str) -> Self {
+        Self {
+            client,
+            key_prefix: key_prefix.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl RevocationStore for RedisRevocationStore {
+    async fn load(&self) -> Result<Revocations, AuthError> {
+        let unavailable = |e: crate::redis::RedisError| {
+            AuthError::ConfigError(format!("Revocation list unavailable: {}", e))
+        };
+
+        let jtis = self
+            .client
+            .smembers(&format!("{}jti", self.key_prefix))
+            .await
+            .map_err(unavailable)?
+            .into_iter()
+            .map(|jti| String::from_utf8_lossy(&jti).into_owned())
+            .collect();
+
+        let mut not_before = HashMap::new();
+        let cutoffs = self
+            .client
+            .hgetall(&format!("{}not_before", self.key_prefix))
+            .await
+            .map_err(unavailable)?;
+        for (subject, cutoff) in cutoffs {
+            let subject = String::from_utf8_lossy(&subject).into_owned();
+            let cutoff = std::str::from_utf8(&cutoff)
+                .ok()
+                .and_then(|cutoff| cutoff.trim().parse().ok())
+                .ok_or_else(|| {
+                    AuthError::ConfigError(format!(
+                        "Invalid revocation cutoff for subject '{}'",
+                        subject
+                    ))
+                })?;
+            not_before.insert(subject, cutoff);
+        }
+
+        Ok(Revocations { jtis, not_before })
+    }
+}
+
+/// Revocation list loaded last, and when a load was last attempted
+#[derive(Default)]
+struct Loaded {
+    revocations: Option<Arc<Revocations>>,
+    attempted_at: Option<Instant>,
+}
+
+/// Periodically reloaded revocation list
+pub struct RevocationList {
+    store: Arc<dyn RevocationStore>,
+    refresh_interval: Duration,
+    loaded: RwLock<Loaded>,
+    /// Held while loading, so one request reloads at a time
+    loading: tokio::sync::Mutex<()>,
+}
+
+impl RevocationList {
+    /// List loaded from `store` on first use and every `refresh_interval`
+    pub fn new(store: Arc<dyn RevocationStore>, refresh_interval: Duration) -> Self {
+        Self {
+            store,
+            refresh_interval,
+            loaded: RwLock::new(Loaded::default()),
+            loading: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Create the list described by bucket configuration
+    pub fn from_config(config: &JwtRevocationConfig) -> Result<Self, AuthError> {
+        let store: Arc<dyn RevocationStore> = match config.store {
+            RevocationStoreConfig::File { ref path } => Arc::new(FileRevocationStore::new(path)?),
+            RevocationStoreConfig::Redis {
+                ref url,
+                ref key_prefix,
+            } => {
+                let client = RedisClient::from_url(url)
+                    .map_err(|e| AuthError::ConfigError(e.to_string()))?;
+                Arc::new(RedisRevocationStore::new(client, key_prefix))
+            }
+        };
+        Ok(Self::new(store, Duration::from_secs(config.refresh_secs)))
+    }
+
+    /// Load the list from the store now
+    ///
+    /// On failure the previously loaded list stays in use.
+    pub async fn reload(&self) -> Result<Arc<Revocations>, AuthError> {
+        let result = self.store.load().await;
+        metrics::record_jwt_revocation_refresh(result.is_ok());
+
+        let mut loaded = self.loaded.write();
+        loaded.attempted_at = Some(Instant::now());
+        match result {
+            Ok(revocations) => {
+                let revocations = Arc::new(revocations);
+                loaded.revocations = Some(Arc::clone(&revocations));
+                Ok(revocations)
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to load JWT revocation list");
+                Err(e)
+            }
+        }
+    }
+
+    /// The current list, reloaded when the refresh interval has passed
+    async fn current(&self) -> Result<Arc<Revocations>, AuthError> {
+        let (revocations, due) = {
+            let loaded = self.loaded.read();
+            let due = loaded
+                .attempted_at
+                .is_none_or(|at| at.elapsed() >= self.refresh_interval);
+            (loaded.revocations.clone(), due)
+        };
+
+        match revocations {
+            Some(revocations) if !due => Ok(revocations),
+            // Another request is reloading; use the current list meanwhile
+            Some(revocations) => match self.loading.try_lock() {
+                Ok(_loading) => Ok(self.reload().await.unwrap_or(revocations)),
+                Err(_) => Ok(revocations),
+            },
+            None => {
+                let _loading = self.loading.lock().await;
+                let revocations = self.loaded.read().revocations.clone();
+                match revocations {
+                    // Loaded by another request while waiting
+                    Some(revocations) => Ok(revocations),
+                    None => self.reload().await,
+                }
+            }
+        }
+    }
+
+    /// Reject the token with `claims` if it is revoked
+    pub async fn check(&self, claims: &Map<String, Value>) -> Result<(), AuthError> {
+        match self.current().await?.reason(claims) {
+            Some(reason) => {
+                metrics::record_jwt_revoked(reason);
+                Err(AuthError::TokenRevoked)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn claims(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(claims) => claims,
+            _ => unreachable!(),
+        }
+    }
+
+    fn revocations() -> Revocations {
+        serde_yaml::from_str(
+            r#"
+jtis: ["leaked"]
+not_before:
+  mallory: 1000
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_reason() {
+        let revocations = revocations();
+
+        let leaked = claims(serde_json::json!({"sub": "alice", "jti": "leaked", "iat": 5000}));
+        assert_eq!(revocations.reason(&leaked), Some("jti"));
+
+        let old = claims(serde_json::json!({"sub": "mallory", "iat": 999}));
+        assert_eq!(revocations.reason(&old), Some("not_before"));
+        let undated = claims(serde_json::json!({"sub": "mallory"}));
+        assert_eq!(revocations.reason(&undated), Some("not_before"));
+
+        let reissued = claims(serde_json::json!({"sub": "mallory", "iat": 1000}));
+        assert_eq!(revocations.reason(&reissued), None);
+        let other = claims(serde_json::json!({"sub": "alice", "jti": "fresh", "iat": 1}));
+        assert_eq!(revocations.reason(&other), None);
+    }
+
+    /// Store failing after its first load, counting loads
+    struct Flaky(AtomicUsize);
+
+    #[async_trait]
+    impl RevocationStore for Flaky {
+        async fn load(&self) -> Result<Revocations, AuthError> {
+            match self.0.fetch_add(1, Ordering::SeqCst) {
+                0 => Ok(revocations()),
+                _ => Err(AuthError::ConfigError("store down".into())),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_reload_keeps_list() {
+        let store = Arc::new(Flaky(AtomicUsize::new(0)));
+        let list = RevocationList::new(store.clone(), Duration::ZERO);
+        let leaked = claims(serde_json::json!({"sub": "alice", "jti": "leaked"}));
+
+        for _ in 0..3 {
+            assert!(matches!(
+                list.check(&leaked).await,
+                Err(AuthError::TokenRevoked)
+            ));
+        }
+        assert_eq!(store.0.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_unloaded_list_rejects() {
+        let store = Arc::new(Flaky(AtomicUsize::new(1)));
+        let list = RevocationList::new(store, Duration::from_secs(60));
+        let token = claims(serde_json::json!({"sub": "alice"}));
+
+        assert!(matches!(
+            list.check(&token).await,
+            Err(AuthError::ConfigError(_))
+        ));
+    }
+}
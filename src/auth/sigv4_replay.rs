@@ -0,0 +1,170 @@
+//! SigV4 replay protection
+//!
+//! A signed request stays valid for the whole clock-skew window, so a
+//! captured request could be sent again within it. [`ReplayGuard`]s remember
+//! the signatures already accepted until their timestamp leaves the window;
+//! [`SigV4Authenticator`](super::sigv4::SigV4Authenticator) rejects a
+//! signature it has seen before.
+//!
+//! [`MemoryReplayGuard`] protects a single instance. Behind a load balancer,
+//! use a [`StoreReplayGuard`] over Redis (the guard's own `redis` setting or
+//! the shared `state.redis`) so every instance sees the same signatures.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::auth::sigv4::SigV4Authenticator;
+//! use mizuchi_uploadr::auth::sigv4_replay::MemoryReplayGuard;
+//! use std::sync::Arc;
+//!
+//! let auth = SigV4Authenticator::new("s3", "us-east-1")
+//!     .with_replay_guard(Arc::new(MemoryReplayGuard::new(100_000)));
+//! ```
+
+use super::AuthError;
+use crate::config::SigV4ReplayConfig;
+use crate::redis::RedisClient;
+use crate::state::{RedisStore, Store};
+use async_trait::async_trait;
+use lru::LruCache;
+use parking_lot::Mutex;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Store of signatures already accepted
+#[async_trait]
+pub trait ReplayGuard: Send + Sync {
+    /// Remember `key` for `ttl`, returning `false` if it is already remembered
+    async fn check_and_record(&self, key: &str, ttl: Duration) -> Result<bool, AuthError>;
+}
+
+/// Build the guard described by `config`, or `None` when disabled
+pub fn from_config(config: &SigV4ReplayConfig) -> Result<Option<Arc<dyn ReplayGuard>>, AuthError> {
+    from_config_with_state(config, None)
+}
+
+/// Like [`from_config`], keeping signatures in the shared `state` store
+/// unless the guard has its own Redis
+pub fn from_config_with_state(
+    config: &SigV4ReplayConfig,
+    state: Option<&Arc<dyn Store>>,
+) -> Result<Option<Arc<dyn ReplayGuard>>, AuthError> {
+    if !config.enabled {
+        return Ok(None);
+    }
+    Ok(Some(match (&config.redis, state) {
+        (Some(redis), _) => {
+            let client = RedisClient::from_url(&redis.url)
+                .map_err(|e| AuthError::ConfigError(e.to_string()))?;
+            let store = Arc::new(RedisStore::new(client, &redis.key_prefix));
+            Arc::new(StoreReplayGuard::new(store, ""))
+        }
+        (None, Some(state)) => {
+            Arc::new(StoreReplayGuard::new(Arc::clone(state), SHARED_KEY_PREFIX))
+        }
+        (None, None) => Arc::new(MemoryReplayGuard::new(config.max_entries)),
+    }))
+}
+
+/// Prefix of seen signatures in the shared `state` store
+const SHARED_KEY_PREFIX: &str = "sigv4:seen:";
+
+/// Seen signatures kept in an in-process LRU
+///
+/// When more than `max_entries` signatures are seen within the skew window
+/// the oldest are forgotten early, so size it for the peak request rate
+/// times the window.
+pub struct MemoryReplayGuard {
+    seen: Mutex<LruCache<String, Instant>>,
+}
+
+impl MemoryReplayGuard {
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            seen: Mutex::new(LruCache::new(
+                NonZeroUsize::new(max_entries).unwrap_or(NonZeroUsize::MIN),
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl ReplayGuard for MemoryReplayGuard {
+    async fn check_and_record(&self, key: &str, ttl: Duration) -> Result<bool, AuthError> {
+        let now = Instant::now();
+        let mut seen = self.seen.lock();
+        if seen.get(key).is_some_and(|expires_at| *expires_at > now) {
+            return Ok(false);
+        }
+        seen.put(key.to_string(), now + ttl);
+        Ok(true)
+    }
+}
+
+/// Seen signatures kept in a [`Store`] with `set_nx`, shared between
+/// instances when the store is
+///
+/// Fails closed: while the store is unreachable, signed requests are rejected.
+pub struct StoreReplayGuard {
+    store: Arc<dyn Store>,
+    key_prefix: String,
+}
+
+impl StoreReplayGuard {
+    pub fn new(store: Arc<dyn Store>, key_prefix: &str) -> Self {
+        Self {
+            store,
+            key_prefix: key_prefix.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl ReplayGuard for StoreReplayGuard {
+    async fn check_and_record(&self, key: &str, ttl: Duration) -> Result<bool, AuthError> {
+        let key = format!("{}{}", self.key_prefix, key);
+        self.store
+            .set_nx(&key, b"1", ttl)
+            .await
+            .map_err(|e| AuthError::ConfigError(format!("Replay cache unavailable: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::MemoryStore;
+
+    #[tokio::test]
+    async fn test_memory_guard() {
+        let guard = MemoryReplayGuard::new(2);
+        let ttl = Duration::from_secs(60);
+
+        assert!(guard.check_and_record("a", ttl).await.unwrap());
+        assert!(!guard.check_and_record("a", ttl).await.unwrap());
+        assert!(guard.check_and_record("b", ttl).await.unwrap());
+
+        // Expired entries no longer count as seen
+        assert!(guard.check_and_record("c", Duration::ZERO).await.unwrap());
+        assert!(guard.check_and_record("c", ttl).await.unwrap());
+    }
+
+    #[test]
+    fn test_from_config() {
+        let mut config = SigV4ReplayConfig::default();
+        assert!(from_config(&config).unwrap().is_some());
+
+        config.enabled = false;
+        assert!(from_config(&config).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_store_guard() {
+        let guard = StoreReplayGuard::new(Arc::new(MemoryStore::new()), "seen:");
+        let ttl = Duration::from_secs(60);
+
+        assert!(guard.check_and_record("a", ttl).await.unwrap());
+        assert!(!guard.check_and_record("a", ttl).await.unwrap());
+    }
+}
@@ -0,0 +1,335 @@
+//! OAuth 2.0 token exchange (RFC 8693)
+//!
+//! Lets an uploader act on behalf of an end user. The client presents the
+//! user's JWT, which is validated as usual and then exchanged at the
+//! configured IdP for a token scoped to this proxy. The IdP decides whether
+//! the delegation is allowed; a refused exchange rejects the upload.
+//!
+//! The exchanged token defines the authenticated identity: its `sub` is the
+//! subject and its claims (scopes, audience, ...) are reported instead of
+//! the client token's. The party acting for the subject is reported under
+//! the `act` claim: the exchanged token's `act`, else the client token's,
+//! else the client the user's token was issued to (`azp` or `client_id`).
+//! [`AuthResult::actor`] returns it, and both identities reach upload
+//! receipts and the authorization context.
+//!
+//! The exchanged token comes straight from the token endpoint, so its
+//! claims are read without verifying its signature. Exchanges are cached
+//! until either token expires, so the IdP is called once per client token
+//! rather than once per upload.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::auth::jwt::JwtAuthenticator;
+//! use mizuchi_uploadr::auth::token_exchange::TokenExchangeAuthenticator;
+//! use mizuchi_uploadr::config::TokenExchangeConfig;
+//!
+//! let auth = TokenExchangeAuthenticator::new(
+//!     JwtAuthenticator::new_hs256("secret"),
+//!     TokenExchangeConfig {
+//!         token_endpoint: "https://idp.example.com/oauth2/token".into(),
+//!         client_id: "mizuchi".into(),
+//!         client_secret: Some("client-secret".into()),
+//!         client_secret_file: None,
+//!         audience: Some("uploads".into()),
+//!         scopes: vec!["upload".into()],
+//!         timeout_ms: 5000,
+//!     },
+//! );
+//! ```
+
+use super::jwt::{auth_result_from_claims, JwtAuthenticator};
+use super::{AuthError, AuthRequest, AuthResult, Authenticator};
+use crate::config::TokenExchangeConfig;
+use async_trait::async_trait;
+use base64::Engine;
+use lru::LruCache;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// `grant_type` of a token exchange request
+pub const GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+
+/// Token type of the client's JWT (`subject_token_type`)
+pub const JWT_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:jwt";
+
+/// Token type requested from the IdP (`requested_token_type`)
+pub const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+
+/// Most exchanged tokens kept in memory
+const CACHE_CAPACITY: usize = 10_000;
+
+/// Successful token endpoint response
+#[derive(Debug, Deserialize)]
+struct ExchangeResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<u64>,
+    #[serde(default)]
+    scope: Option<String>,
+}
+
+/// Token endpoint error response (RFC 6749 section 5.2)
+#[derive(Debug, Default, Deserialize)]
+struct ExchangeError {
+    #[serde(default)]
+    error: String,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+/// Identity derived from one client token
+struct CacheEntry {
+    result: AuthResult,
+    expires_at: Instant,
+}
+
+/// JWT authenticator exchanging accepted tokens at an IdP
+pub struct TokenExchangeAuthenticator {
+    jwt: JwtAuthenticator,
+    config: TokenExchangeConfig,
+    client: reqwest::Client,
+    /// Keyed by the SHA-256 of the client token
+    cache: Mutex<LruCache<String, CacheEntry>>,
+}
+
+impl TokenExchangeAuthenticator {
+    /// Exchange the tokens accepted by `jwt` as `config` describes
+    pub fn new(jwt: JwtAuthenticator, config: TokenExchangeConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()
+            .expect("Failed to build HTTP client");
+
+        Self {
+            jwt,
+            config,
+            client,
+            cache: Mutex::new(LruCache::new(
+                NonZeroUsize::new(CACHE_CAPACITY).expect("cache capacity is non-zero"),
+            )),
+        }
+    }
+
+    /// Exchange `token` at the token endpoint
+    ///
+    /// Client errors from the IdP (the delegation was refused) are
+    /// [`AuthError::Forbidden`]; an unreachable or failing IdP is
+    /// [`AuthError::TokenExchangeError`].
+    async fn exchange(&self, token: &str) -> Result<ExchangeResponse, AuthError> {
+        let scope = self.config.scopes.join(" ");
+        let mut form = vec![
+            ("grant_type", GRANT_TYPE),
+            ("subject_token", token),
+            ("subject_token_type", JWT_TOKEN_TYPE),
+            ("requested_token_type", ACCESS_TOKEN_TYPE),
+        ];
+        if let Some(ref audience) = self.config.audience {
+            form.push(("audience", audience));
+        }
+        if !scope.is_empty() {
+            form.push(("scope", &scope));
+        }
+
+        let response = self
+            .client
+            .post(&self.config.token_endpoint)
+            .basic_auth(&self.config.client_id, self.config.client_secret.as_ref())
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| AuthError::TokenExchangeError(e.to_string()))?;
+
+        let status = response.status();
+        if status.is_client_error() {
+            let error: ExchangeError = response.json().await.unwrap_or_default();
+            return Err(AuthError::Forbidden(format!(
+                "Token exchange refused ({}): {}",
+                status,
+                error.error_description.unwrap_or(error.error)
+            )));
+        }
+        if !status.is_success() {
+            return Err(AuthError::TokenExchangeError(format!(
+                "Token endpoint returned {}",
+                status
+            )));
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| AuthError::TokenExchangeError(format!("Invalid response: {}", e)))
+    }
+
+    fn cached(&self, key: &str) -> Option<AuthResult> {
+        let mut cache = self.cache.lock();
+        match cache.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.result.clone()),
+            Some(_) => {
+                cache.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+#[async_trait]
+impl Authenticator for TokenExchangeAuthenticator {
+    async fn authenticate(&self, request: &AuthRequest) -> Result<AuthResult, AuthError> {
+        let token = self
+            .jwt
+            .extract_token(request)
+            .ok_or(AuthError::MissingAuth)?;
+        let client = self.jwt.authenticate(request).await?;
+        let client_claims = jwt_payload(&token).unwrap_or_default();
+
+        let key = hex::encode(Sha256::digest(token.as_bytes()));
+        if let Some(result) = self.cached(&key) {
+            return Ok(result);
+        }
+
+        let response = self.exchange(&token).await?;
+        let issued_claims = jwt_payload(&response.access_token);
+        let lifetime = [
+            response.expires_in,
+            expires_in(&client_claims),
+            issued_claims.as_ref().and_then(expires_in),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        let result = delegated_result(client, &client_claims, issued_claims, response.scope);
+
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            subject = %result.subject,
+            actor = result.actor().unwrap_or("-"),
+            "Token exchange successful"
+        );
+
+        if let Some(seconds) = lifetime.filter(|seconds| *seconds > 0) {
+            self.cache.lock().put(
+                key,
+                CacheEntry {
+                    result: result.clone(),
+                    expires_at: Instant::now() + Duration::from_secs(seconds),
+                },
+            );
+        }
+        Ok(result)
+    }
+}
+
+/// Identity of an exchange: the issued token's, with the actor filled in
+///
+/// Opaque issued tokens (or JWTs without `sub` and `exp`) keep the client
+/// token's identity.
+fn delegated_result(
+    client: AuthResult,
+    client_claims: &Map<String, Value>,
+    issued_claims: Option<Map<String, Value>>,
+    scope: Option<String>,
+) -> AuthResult {
+    let mut result = issued_claims
+        .and_then(|claims| auth_result_from_claims(claims).ok())
+        .unwrap_or_else(|| client.clone());
+
+    if let Some(scope) = scope {
+        result.claims.insert("scope".into(), Value::String(scope));
+    }
+    if !result.claims.contains_key("act") {
+        let actor = client.claims.get("act").cloned().or_else(|| {
+            ["azp", "client_id"]
+                .into_iter()
+                .find_map(|claim| client_claims.get(claim)?.as_str())
+                .map(|sub| serde_json::json!({ "sub": sub }))
+        });
+        if let Some(actor) = actor {
+            result.claims.insert("act".into(), actor);
+        }
+    }
+    result
+}
+
+/// Claims of a JWT, read without verifying it
+fn jwt_payload(token: &str) -> Option<Map<String, Value>> {
+    let payload = token.split('.').nth(1)?;
+    let json = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(payload)
+        .ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+/// Seconds until the `exp` claim, if any
+fn expires_in(claims: &Map<String, Value>) -> Option<u64> {
+    let exp = claims.get("exp")?.as_u64()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some(exp.saturating_sub(now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn unsigned(claims: Value) -> String {
+        let encode = |value: &Value| {
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(value.to_string())
+        };
+        format!(
+            "{}.{}.sig",
+            encode(&serde_json::json!({"alg": "none"})),
+            encode(&claims)
+        )
+    }
+
+    fn client(claims: &[(&str, Value)]) -> (AuthResult, Map<String, Value>) {
+        let map: Map<String, Value> = claims
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect();
+        let result = AuthResult {
+            subject: "alice".into(),
+            claims: map.clone().into_iter().collect::<HashMap<_, _>>(),
+        };
+        (result, map)
+    }
+
+    #[test]
+    fn test_jwt_payload() {
+        let token = unsigned(serde_json::json!({"sub": "alice", "exp": 10}));
+        let claims = jwt_payload(&token).unwrap();
+        assert_eq!(claims["sub"], "alice");
+        assert!(jwt_payload("opaque-token").is_none());
+    }
+
+    #[test]
+    fn test_issued_token_defines_identity() {
+        let (result, claims) = client(&[("azp", "uploader".into())]);
+        let issued = jwt_payload(&unsigned(serde_json::json!({
+            "sub": "alice@downstream",
+            "exp": 4_000_000_000u64,
+            "act": {"sub": "mizuchi"},
+        })));
+
+        let result = delegated_result(result, &claims, issued, Some("upload".into()));
+        assert_eq!(result.subject, "alice@downstream");
+        assert_eq!(result.actor(), Some("mizuchi"));
+        assert_eq!(result.claims["scope"], "upload");
+    }
+
+    #[test]
+    fn test_opaque_token_keeps_client_identity() {
+        let (result, claims) = client(&[("azp", "uploader".into())]);
+
+        let result = delegated_result(result, &claims, None, None);
+        assert_eq!(result.subject, "alice");
+        assert_eq!(result.actor(), Some("uploader"));
+    }
+}
@@ -0,0 +1,297 @@
+//! Authorization decision cache
+//!
+//! A size-bounded LRU of allow/deny decisions that can be shared by every
+//! authorizer of the server. Entries expire after the TTL of the authorizer
+//! that stored them, and can be dropped early per subject (e.g. when a user
+//! is revoked) through the admin API instead of waiting for the TTL.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::authz::cache::{DecisionCache, DecisionKey};
+//! use mizuchi_uploadr::authz::AuthzRequest;
+//! use std::time::Duration;
+//!
+//! let cache = DecisionCache::new(1000);
+//! let request = AuthzRequest {
+//!     subject: "alice".into(),
+//!     action: "upload".into(),
+//!     resource: "bucket/uploads/a.txt".into(),
+//!     context: Default::default(),
+//! };
+//! let key = DecisionKey::new("opa:allow", &request);
+//!
+//! cache.insert(key.clone(), &request.subject, true, Duration::from_secs(60));
+//! assert_eq!(cache.get(&key), Some(true));
+//!
+//! assert_eq!(cache.invalidate_subject("alice"), 1);
+//! assert_eq!(cache.get(&key), None);
+//! ```
+
+use super::AuthzRequest;
+use lru::LruCache;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Default maximum number of cached decisions
+pub const DEFAULT_CAPACITY: usize = 10_000;
+
+/// Identifies one authorizer's decision on one request
+///
+/// Holds the full canonical request along with its digest, so requests
+/// whose digests collide still get their own decisions.
+#[derive(Debug, Clone)]
+pub struct DecisionKey {
+    digest: u64,
+    canonical: Arc<str>,
+}
+
+impl DecisionKey {
+    /// Key over the namespace, subject, action, resource and context
+    ///
+    /// `namespace` separates authorizers sharing a cache (e.g. the OPA URL
+    /// and policy path), since they can decide differently.
+    pub fn new(namespace: &str, request: &AuthzRequest) -> Self {
+        let mut context: Vec<_> = request
+            .context
+            .iter()
+            .map(|(key, value)| (key.as_str(), value))
+            .collect();
+        context.sort_by_key(|(key, _)| *key);
+        Self::canonical(namespace, request, &context)
+    }
+
+    /// Key for authorizers whose decision does not depend on the context
+    pub fn ignoring_context(namespace: &str, request: &AuthzRequest) -> Self {
+        Self::canonical(namespace, request, &[])
+    }
+
+    fn canonical(
+        namespace: &str,
+        request: &AuthzRequest,
+        context: &[(&str, &serde_json::Value)],
+    ) -> Self {
+        // A JSON array keeps the fields apart whatever characters they hold
+        let canonical = serde_json::json!([
+            namespace,
+            request.subject,
+            request.action,
+            request.resource,
+            context
+        ])
+        .to_string();
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        canonical.hash(&mut hasher);
+        Self {
+            digest: hasher.finish(),
+            canonical: canonical.into(),
+        }
+    }
+}
+
+impl PartialEq for DecisionKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.digest == other.digest && self.canonical == other.canonical
+    }
+}
+
+impl Eq for DecisionKey {}
+
+impl Hash for DecisionKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.digest);
+    }
+}
+
+/// A cached decision
+struct Entry {
+    allowed: bool,
+    subject: String,
+    expires_at: Instant,
+}
+
+/// Size-bounded LRU cache of authorization decisions
+pub struct DecisionCache {
+    entries: Mutex<LruCache<DecisionKey, Entry>>,
+}
+
+impl DecisionCache {
+    /// Create a cache holding at most `capacity` decisions (at least one)
+    pub fn new(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Look up an unexpired decision
+    pub fn get(&self, key: &DecisionKey) -> Option<bool> {
+        let mut entries = self.entries.lock().expect("decision cache lock poisoned");
+        match entries.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.allowed),
+            Some(_) => {
+                entries.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Store a decision for `subject`, evicting the least recently used
+    /// entry when full
+    pub fn insert(&self, key: DecisionKey, subject: &str, allowed: bool, ttl: Duration) {
+        let entry = Entry {
+            allowed,
+            subject: subject.to_string(),
+            expires_at: Instant::now() + ttl,
+        };
+        self.entries
+            .lock()
+            .expect("decision cache lock poisoned")
+            .put(key, entry);
+    }
+
+    /// Drop every decision about `subject`, returning how many were removed
+    pub fn invalidate_subject(&self, subject: &str) -> usize {
+        let mut entries = self.entries.lock().expect("decision cache lock poisoned");
+        let keys: Vec<_> = entries
+            .iter()
+            .filter(|(_, entry)| entry.subject == subject)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &keys {
+            entries.pop(key);
+        }
+        keys.len()
+    }
+
+    /// Drop every decision, returning how many were removed
+    pub fn clear(&self) -> usize {
+        let mut entries = self.entries.lock().expect("decision cache lock poisoned");
+        let removed = entries.len();
+        entries.clear();
+        removed
+    }
+
+    /// Number of cached decisions (including expired ones not yet evicted)
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .expect("decision cache lock poisoned")
+            .len()
+    }
+
+    /// Check if the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for DecisionCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn request(subject: &str, resource: &str) -> AuthzRequest {
+        AuthzRequest {
+            subject: subject.into(),
+            action: "upload".into(),
+            resource: resource.into(),
+            context: HashMap::new(),
+        }
+    }
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn test_lru_eviction() {
+        let cache = DecisionCache::new(2);
+        let keys: Vec<_> = ["a", "b", "c"]
+            .iter()
+            .map(|r| DecisionKey::new("ns", &request("alice", r)))
+            .collect();
+
+        cache.insert(keys[0].clone(), "alice", true, TTL);
+        cache.insert(keys[1].clone(), "alice", true, TTL);
+        assert_eq!(cache.get(&keys[0]), Some(true)); // keys[1] is now least recent
+        cache.insert(keys[2].clone(), "alice", false, TTL);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&keys[1]), None);
+        assert_eq!(cache.get(&keys[0]), Some(true));
+        assert_eq!(cache.get(&keys[2]), Some(false));
+    }
+
+    #[test]
+    fn test_expiry() {
+        let cache = DecisionCache::new(10);
+        let key = DecisionKey::new("ns", &request("alice", "a"));
+        cache.insert(key.clone(), "alice", true, Duration::ZERO);
+        assert_eq!(cache.get(&key), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_invalidate_subject() {
+        let cache = DecisionCache::new(10);
+        for (subject, resource) in [("alice", "a"), ("alice", "b"), ("bob", "a")] {
+            let key = DecisionKey::new("ns", &request(subject, resource));
+            cache.insert(key, subject, true, TTL);
+        }
+
+        assert_eq!(cache.invalidate_subject("alice"), 2);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.clear(), 1);
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut with_context = request("alice", "a");
+        with_context
+            .context
+            .insert("tier".into(), serde_json::json!("premium"));
+        let plain = request("alice", "a");
+
+        assert_ne!(
+            DecisionKey::new("opa", &plain),
+            DecisionKey::new("fga", &plain)
+        );
+        assert_ne!(
+            DecisionKey::new("opa", &plain),
+            DecisionKey::new("opa", &with_context)
+        );
+        assert_eq!(
+            DecisionKey::ignoring_context("fga", &plain),
+            DecisionKey::ignoring_context("fga", &with_context)
+        );
+    }
+
+    #[test]
+    fn test_colliding_digests() {
+        let cache = DecisionCache::new(10);
+        let alice = DecisionKey {
+            digest: 42,
+            ..DecisionKey::new("ns", &request("alice", "a"))
+        };
+        let mallory = DecisionKey {
+            digest: 42,
+            ..DecisionKey::new("ns", &request("mallory", "a"))
+        };
+
+        cache.insert(alice.clone(), "alice", true, TTL);
+        assert_eq!(cache.get(&mallory), None);
+        cache.insert(mallory.clone(), "mallory", false, TTL);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&alice), Some(true));
+        assert_eq!(cache.get(&mallory), Some(false));
+    }
+}
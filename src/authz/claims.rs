@@ -0,0 +1,579 @@
+//! Claim-to-authorization mapping
+//!
+//! Copies selected token claims (tenant, scopes, groups, ...) into
+//! [`AuthzRequest::context`] and optionally jails authenticated uploads under
+//! a key prefix derived from claims, e.g. `tenant/{tenant_id}/`. This gives
+//! multi-tenant isolation without writing a policy for the simple case.
+//!
+//! Claims can also be attached to stored objects as S3 tags
+//! (`x-amz-tagging`), so lifecycle rules and cost allocation can segment
+//! objects by uploader without trusting anything the client sends.
+//!
+//! Token scopes can grant actions on key prefixes (scope `upload:images`
+//! allows `upload` under `images/`). A request whose scopes don't grant it is
+//! denied before any external authorizer is asked.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::authz::claims::ClaimMapper;
+//! use mizuchi_uploadr::config::ClaimMappingConfig;
+//! use std::collections::HashMap;
+//!
+//! let mapper = ClaimMapper::new(&ClaimMappingConfig {
+//!     context: [("tenant".to_string(), "tenant_id".to_string())].into(),
+//!     key_prefix: Some("tenant/{tenant_id}/".into()),
+//!     tags: [("tenant".to_string(), "{tenant_id}".to_string())].into(),
+//!     ..Default::default()
+//! })
+//! .unwrap();
+//!
+//! let claims: HashMap<_, _> = [("tenant_id".to_string(), "acme".into())].into();
+//! assert_eq!(mapper.context(&claims)["tenant"], "acme");
+//! assert!(mapper.check_key(&claims, "tenant/acme/report.csv").is_ok());
+//! assert!(mapper.check_key(&claims, "tenant/other/report.csv").is_err());
+//! assert_eq!(mapper.tagging("alice", &claims).as_deref(), Some("tenant=acme"));
+//! ```
+
+use super::{AuthzError, AuthzRequest};
+use crate::auth::AuthResult;
+use crate::config::{ClaimMappingConfig, ScopeRule};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Characters escaped in `x-amz-tagging` (everything but RFC 3986 unreserved)
+pub(crate) const TAG_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Most tags S3 accepts on an object
+const MAX_TAGS: usize = 10;
+/// Longest tag name S3 accepts
+const MAX_TAG_KEY_LEN: usize = 128;
+/// Longest tag value S3 accepts
+const MAX_TAG_VALUE_LEN: usize = 256;
+
+/// Part of a key prefix template
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Segment {
+    Literal(String),
+    Claim(String),
+}
+
+/// Claim holding token scopes unless configured
+const DEFAULT_SCOPE_CLAIM: &str = "scope";
+
+/// Why a token's scopes don't allow a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeMismatch {
+    /// The token carries no scope claim
+    Missing,
+    /// No scope of the token grants the action
+    Action,
+    /// Scopes grant the action, but not on this key
+    Prefix,
+}
+
+impl ScopeMismatch {
+    /// Metric label for this mismatch
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScopeMismatch::Missing => "missing",
+            ScopeMismatch::Action => "action",
+            ScopeMismatch::Prefix => "prefix",
+        }
+    }
+}
+
+/// Maps authentication claims into authorization input for one bucket
+#[derive(Debug, Clone)]
+pub struct ClaimMapper {
+    /// (context entry, claim name) pairs
+    context: Vec<(String, String)>,
+    key_prefix: Option<Vec<Segment>>,
+    /// (tag name, value template) pairs
+    tags: Vec<(String, Vec<Segment>)>,
+    scope_claim: String,
+    scopes: Vec<ScopeRule>,
+}
+
+impl ClaimMapper {
+    /// Create a mapper from bucket configuration
+    ///
+    /// Fails if a template has unbalanced braces or an empty placeholder,
+    /// or if the tags break S3's limits (10 tags, names of 1-128
+    /// characters outside the reserved `aws:` prefix), or if a scope rule
+    /// has an empty scope or action or a prefix with `..` segments.
+    pub fn new(config: &ClaimMappingConfig) -> Result<Self, AuthzError> {
+        let mut context: Vec<_> = config
+            .context
+            .iter()
+            .map(|(entry, claim)| (entry.clone(), claim.clone()))
+            .collect();
+        context.sort();
+
+        if config.tags.len() > MAX_TAGS {
+            return Err(AuthzError::ConfigError(format!(
+                "At most {} tags are allowed, got {}",
+                MAX_TAGS,
+                config.tags.len()
+            )));
+        }
+        let mut tags = Vec::with_capacity(config.tags.len());
+        for (name, template) in &config.tags {
+            if name.is_empty() || name.len() > MAX_TAG_KEY_LEN || name.starts_with("aws:") {
+                return Err(AuthzError::ConfigError(format!(
+                    "Invalid tag name '{}'",
+                    name
+                )));
+            }
+            tags.push((name.clone(), parse_template(template)?));
+        }
+        tags.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for rule in &config.scopes {
+            if rule.scope.is_empty() || rule.action.is_empty() {
+                return Err(AuthzError::ConfigError(format!(
+                    "Scope rule '{}' needs a scope and an action",
+                    rule.scope
+                )));
+            }
+            let prefix = rule.prefix.as_deref().unwrap_or_default();
+            if prefix.split('/').any(|segment| segment == "..") {
+                return Err(AuthzError::ConfigError(format!(
+                    "Invalid prefix '{}' for scope '{}'",
+                    prefix, rule.scope
+                )));
+            }
+        }
+
+        Ok(Self {
+            context,
+            tags,
+            scope_claim: config
+                .scope_claim
+                .clone()
+                .unwrap_or_else(|| DEFAULT_SCOPE_CLAIM.to_string()),
+            scopes: config.scopes.clone(),
+            key_prefix: config
+                .key_prefix
+                .as_deref()
+                .map(parse_template)
+                .transpose()?,
+        })
+    }
+
+    /// Authorization context built from the mapped claims
+    ///
+    /// Claims missing from the token are left out.
+    pub fn context(&self, claims: &HashMap<String, Value>) -> HashMap<String, Value> {
+        self.context
+            .iter()
+            .filter_map(|(entry, claim)| Some((entry.clone(), lookup(claims, claim)?.clone())))
+            .collect()
+    }
+
+    /// Build the authorization request for an authenticated upload
+    pub fn authz_request(&self, auth: &AuthResult, action: &str, resource: &str) -> AuthzRequest {
+        AuthzRequest {
+            subject: auth.subject.clone(),
+            action: action.to_string(),
+            resource: resource.to_string(),
+            context: self.context(&auth.claims),
+        }
+    }
+
+    /// Key prefix for these claims, if the mapping scopes keys
+    ///
+    /// Fails with [`AuthzError::AccessDenied`] when a placeholder claim is
+    /// missing or is not a single path segment, so a token without a tenant
+    /// can never write outside every tenant's prefix.
+    pub fn key_prefix(
+        &self,
+        claims: &HashMap<String, Value>,
+    ) -> Result<Option<String>, AuthzError> {
+        let Some(ref template) = self.key_prefix else {
+            return Ok(None);
+        };
+
+        let mut prefix = String::new();
+        for segment in template {
+            match segment {
+                Segment::Literal(text) => prefix.push_str(text),
+                Segment::Claim(claim) => {
+                    let value = lookup(claims, claim)
+                        .and_then(path_segment)
+                        .ok_or(AuthzError::AccessDenied)?;
+                    prefix.push_str(&value);
+                }
+            }
+        }
+        Ok(Some(prefix))
+    }
+
+    /// `x-amz-tagging` value for an upload by `subject` with these claims
+    ///
+    /// Tags whose placeholders reference missing claims, or whose value
+    /// would exceed S3's 256 character limit, are left out. `{sub}` falls
+    /// back to the authenticated subject for credentials without claims
+    /// (SigV4, API keys). Returns `None` when no tag applies.
+    pub fn tagging(&self, subject: &str, claims: &HashMap<String, Value>) -> Option<String> {
+        let tagging: Vec<String> = self
+            .tags
+            .iter()
+            .filter_map(|(name, template)| {
+                let mut value = String::new();
+                for segment in template {
+                    match segment {
+                        Segment::Literal(text) => value.push_str(text),
+                        Segment::Claim(claim) => match lookup(claims, claim).and_then(tag_value) {
+                            Some(text) => value.push_str(&text),
+                            None if claim == "sub" => value.push_str(subject),
+                            None => return None,
+                        },
+                    }
+                }
+                (value.chars().count() <= MAX_TAG_VALUE_LEN).then(|| {
+                    format!(
+                        "{}={}",
+                        utf8_percent_encode(name, TAG_ENCODE_SET),
+                        utf8_percent_encode(&value, TAG_ENCODE_SET)
+                    )
+                })
+            })
+            .collect();
+        (!tagging.is_empty()).then(|| tagging.join("&"))
+    }
+
+    /// Check that an object key stays under the claim-derived prefix
+    pub fn check_key(&self, claims: &HashMap<String, Value>, key: &str) -> Result<(), AuthzError> {
+        let Some(prefix) = self.key_prefix(claims)? else {
+            return Ok(());
+        };
+
+        let escapes = key.split('/').any(|segment| segment == "..");
+        if escapes || !key.starts_with(prefix.trim_start_matches('/')) {
+            return Err(AuthzError::AccessDenied);
+        }
+        Ok(())
+    }
+
+    /// Check that the token's scopes grant `action` on `key`
+    ///
+    /// Always passes when no scope rules are configured. Scopes are read
+    /// from a space-separated string (OAuth 2.0 `scope`) or a list of
+    /// strings (`scp`).
+    pub fn check_scopes(
+        &self,
+        claims: &HashMap<String, Value>,
+        action: &str,
+        key: &str,
+    ) -> Result<(), ScopeMismatch> {
+        if self.scopes.is_empty() {
+            return Ok(());
+        }
+
+        let scopes: Vec<&str> = match lookup(claims, &self.scope_claim) {
+            Some(Value::String(scopes)) => scopes.split_whitespace().collect(),
+            Some(Value::Array(scopes)) => scopes.iter().filter_map(Value::as_str).collect(),
+            _ => return Err(ScopeMismatch::Missing),
+        };
+
+        let mut granted = self
+            .scopes
+            .iter()
+            .filter(|rule| rule.action == action && scopes.contains(&rule.scope.as_str()))
+            .peekable();
+        if granted.peek().is_none() {
+            return Err(ScopeMismatch::Action);
+        }
+
+        let escapes = key.split('/').any(|segment| segment == "..");
+        let covered = granted.any(|rule| match rule.prefix {
+            Some(ref prefix) => key.starts_with(prefix.trim_start_matches('/')),
+            None => true,
+        });
+        if escapes || !covered {
+            return Err(ScopeMismatch::Prefix);
+        }
+        Ok(())
+    }
+}
+
+/// Look up a claim, descending into nested objects on `.`
+///
+/// A claim whose name contains dots is found before a nested one.
+pub fn lookup<'a>(claims: &'a HashMap<String, Value>, name: &str) -> Option<&'a Value> {
+    if let Some(value) = claims.get(name) {
+        return Some(value);
+    }
+
+    let mut parts = name.split('.');
+    let mut value = claims.get(parts.next()?)?;
+    for part in parts {
+        value = value.as_object()?.get(part)?;
+    }
+    Some(value)
+}
+
+/// Render a claim value as a single key path segment
+pub(crate) fn path_segment(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        _ => return None,
+    };
+    let valid = !text.is_empty() && text != "." && text != ".." && !text.contains('/');
+    valid.then_some(text)
+}
+
+/// Render a scalar claim value as a tag value
+fn tag_value(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+pub(crate) fn parse_template(template: &str) -> Result<Vec<Segment>, AuthzError> {
+    let invalid = |reason: &str| {
+        AuthzError::ConfigError(format!("Invalid template '{}': {}", template, reason))
+    };
+
+    let mut segments = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        if rest[start..].starts_with('}') {
+            return Err(invalid("unmatched '}'"));
+        }
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| invalid("unclosed '{'"))?
+            + start;
+        let claim = rest[start + 1..end].trim();
+        if claim.is_empty() || claim.contains('{') {
+            return Err(invalid("empty or nested placeholder"));
+        }
+        if start > 0 {
+            segments.push(Segment::Literal(rest[..start].to_string()));
+        }
+        segments.push(Segment::Claim(claim.to_string()));
+        rest = &rest[end + 1..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Literal(rest.to_string()));
+    }
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn claims(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn mapper(key_prefix: &str) -> ClaimMapper {
+        ClaimMapper::new(&ClaimMappingConfig {
+            key_prefix: Some(key_prefix.into()),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_template() {
+        assert_eq!(
+            parse_template("tenant/{tenant_id}/{ org.id }").unwrap(),
+            vec![
+                Segment::Literal("tenant/".into()),
+                Segment::Claim("tenant_id".into()),
+                Segment::Literal("/".into()),
+                Segment::Claim("org.id".into()),
+            ]
+        );
+        assert!(parse_template("tenant/{tenant_id").is_err());
+        assert!(parse_template("tenant/}").is_err());
+        assert!(parse_template("tenant/{}/").is_err());
+    }
+
+    #[test]
+    fn test_nested_lookup() {
+        let claims = claims(json!({
+            "realm": {"roles": ["uploader"]},
+            "https://example.com/tenant": "acme",
+        }));
+        assert_eq!(lookup(&claims, "realm.roles"), Some(&json!(["uploader"])));
+        assert_eq!(
+            lookup(&claims, "https://example.com/tenant"),
+            Some(&json!("acme"))
+        );
+        assert_eq!(lookup(&claims, "realm.missing"), None);
+    }
+
+    #[test]
+    fn test_key_prefix_requires_safe_claim() {
+        let mapper = mapper("tenant/{tenant_id}/");
+        assert_eq!(
+            mapper
+                .key_prefix(&claims(json!({"tenant_id": 42})))
+                .unwrap(),
+            Some("tenant/42/".into())
+        );
+        for bad in [
+            json!({}),
+            json!({"tenant_id": ".."}),
+            json!({"tenant_id": "a/b"}),
+        ] {
+            assert!(mapper.key_prefix(&claims(bad)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_check_key() {
+        let mapper = mapper("tenant/{tenant_id}/");
+        let acme = claims(json!({"tenant_id": "acme"}));
+        assert!(mapper.check_key(&acme, "tenant/acme/a.csv").is_ok());
+        assert!(mapper.check_key(&acme, "tenant/acme-evil/a.csv").is_err());
+        assert!(mapper
+            .check_key(&acme, "tenant/acme/../other/a.csv")
+            .is_err());
+    }
+
+    #[test]
+    fn test_tagging() {
+        let mapper = ClaimMapper::new(&ClaimMappingConfig {
+            tags: [
+                ("uploader".to_string(), "{sub}".to_string()),
+                ("tenant".to_string(), "{org.tenant}".to_string()),
+                ("team".to_string(), "{team}".to_string()),
+            ]
+            .into(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let claims = claims(json!({"sub": "alice@example.com", "org": {"tenant": "acme corp"}}));
+        assert_eq!(
+            mapper.tagging("ignored", &claims).as_deref(),
+            Some("tenant=acme%20corp&uploader=alice%40example.com")
+        );
+        // Credentials without claims still tag the subject
+        assert_eq!(
+            mapper.tagging("AKIDEXAMPLE", &HashMap::new()).as_deref(),
+            Some("uploader=AKIDEXAMPLE")
+        );
+    }
+
+    #[test]
+    fn test_tag_limits() {
+        let tags = |names: &[&str]| ClaimMappingConfig {
+            tags: names
+                .iter()
+                .map(|name| (name.to_string(), "{sub}".to_string()))
+                .collect(),
+            ..Default::default()
+        };
+
+        assert!(ClaimMapper::new(&tags(&["aws:owner"])).is_err());
+        assert!(ClaimMapper::new(&tags(&[""])).is_err());
+        let eleven: Vec<String> = (0..11).map(|i| format!("tag{}", i)).collect();
+        let eleven: Vec<&str> = eleven.iter().map(String::as_str).collect();
+        assert!(ClaimMapper::new(&tags(&eleven)).is_err());
+    }
+
+    fn scope_mapper_config() -> ClaimMappingConfig {
+        ClaimMappingConfig {
+            scopes: serde_yaml::from_str(
+                r#"
+- scope: "upload:images"
+  prefix: images/
+- scope: "upload:docs"
+  action: upload
+  prefix: docs/
+- scope: admin
+"#,
+            )
+            .unwrap(),
+            ..Default::default()
+        }
+    }
+
+    fn scope_mapper() -> ClaimMapper {
+        ClaimMapper::new(&scope_mapper_config()).unwrap()
+    }
+
+    #[test]
+    fn test_check_scopes() {
+        let mapper = scope_mapper();
+        let images = claims(json!({"scope": "openid upload:images"}));
+
+        assert_eq!(
+            mapper.check_scopes(&images, "upload", "images/cat.png"),
+            Ok(())
+        );
+        assert_eq!(
+            mapper.check_scopes(&images, "upload", "docs/report.pdf"),
+            Err(ScopeMismatch::Prefix)
+        );
+        assert_eq!(
+            mapper.check_scopes(&images, "upload", "images/../docs/report.pdf"),
+            Err(ScopeMismatch::Prefix)
+        );
+        assert_eq!(
+            mapper.check_scopes(&images, "delete", "images/cat.png"),
+            Err(ScopeMismatch::Action)
+        );
+        assert_eq!(
+            mapper.check_scopes(&claims(json!({"scope": "openid"})), "upload", "images/a"),
+            Err(ScopeMismatch::Action)
+        );
+        assert_eq!(
+            mapper.check_scopes(&HashMap::new(), "upload", "images/a"),
+            Err(ScopeMismatch::Missing)
+        );
+
+        // Scope lists, and rules without a prefix
+        let admin = claims(json!({"scope": ["admin"]}));
+        assert_eq!(mapper.check_scopes(&admin, "upload", "anything/a"), Ok(()));
+    }
+
+    #[test]
+    fn test_scope_claim_and_rules() {
+        let scp = ClaimMapper::new(&ClaimMappingConfig {
+            scope_claim: Some("scp".into()),
+            ..scope_mapper_config()
+        })
+        .unwrap();
+        let docs = claims(json!({"scp": ["upload:docs"], "scope": "upload:images"}));
+        assert_eq!(scp.check_scopes(&docs, "upload", "docs/a.pdf"), Ok(()));
+        assert_eq!(
+            scp.check_scopes(&docs, "upload", "images/a.png"),
+            Err(ScopeMismatch::Prefix)
+        );
+
+        // No rules, no restriction
+        assert_eq!(
+            mapper("x/").check_scopes(&HashMap::new(), "upload", "y"),
+            Ok(())
+        );
+
+        let rule = |scope: &str, prefix: &str| ClaimMappingConfig {
+            scopes: vec![ScopeRule {
+                scope: scope.into(),
+                action: "upload".into(),
+                prefix: Some(prefix.into()),
+            }],
+            ..Default::default()
+        };
+        assert!(ClaimMapper::new(&rule("", "images/")).is_err());
+        assert!(ClaimMapper::new(&rule("upload:images", "images/../")).is_err());
+    }
+}
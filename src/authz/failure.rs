@@ -0,0 +1,192 @@
+//! Authorizer failure handling
+//!
+//! Decides what happens to an upload when the bucket's authorizer fails
+//! (OPA unreachable, OpenFGA timing out, ...). `fail_closed` surfaces the
+//! error so the upload is rejected; `fail_open` allows it, first reusing the
+//! last decision made for the same request when a grace period is set.
+//!
+//! Last-known decisions live in the shared [`DecisionCache`], so revoking a
+//! subject through the admin API also drops them. Every decision made while
+//! the authorizer fails is counted in
+//! `mizuchi_authz_degraded_decisions_total`.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::authz::cache::DecisionCache;
+//! use mizuchi_uploadr::authz::failure::FailurePolicyAuthorizer;
+//! use mizuchi_uploadr::authz::AllowAllAuthorizer;
+//! use mizuchi_uploadr::config::{AuthzFailureConfig, AuthzFailureMode};
+//! use std::sync::Arc;
+//!
+//! let config = AuthzFailureConfig {
+//!     mode: AuthzFailureMode::FailOpen,
+//!     grace_period_secs: Some(300),
+//! };
+//! let authorizer = FailurePolicyAuthorizer::new(
+//!     Arc::new(AllowAllAuthorizer),
+//!     "uploads",
+//!     &config,
+//!     Arc::new(DecisionCache::default()),
+//! );
+//! ```
+
+use super::cache::{DecisionCache, DecisionKey};
+use super::{Authorizer, AuthzError, AuthzRequest};
+use crate::config::{AuthzFailureConfig, AuthzFailureMode};
+use crate::metrics;
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+/// Applies a bucket's failure mode to another authorizer
+pub struct FailurePolicyAuthorizer {
+    inner: Arc<dyn Authorizer>,
+    bucket: String,
+    mode: AuthzFailureMode,
+    grace_period: Option<Duration>,
+    cache: Arc<DecisionCache>,
+    cache_namespace: String,
+}
+
+impl FailurePolicyAuthorizer {
+    /// Wrap `inner`, keeping last-known decisions in `cache`
+    pub fn new(
+        inner: Arc<dyn Authorizer>,
+        bucket: &str,
+        config: &AuthzFailureConfig,
+        cache: Arc<DecisionCache>,
+    ) -> Self {
+        Self {
+            inner,
+            bucket: bucket.to_string(),
+            mode: config.mode,
+            grace_period: config.grace_period_secs.map(Duration::from_secs),
+            cache,
+            cache_namespace: format!("last-known:{}", bucket),
+        }
+    }
+}
+
+#[async_trait]
+impl Authorizer for FailurePolicyAuthorizer {
+    async fn authorize(&self, request: &AuthzRequest) -> Result<bool, AuthzError> {
+        let key = DecisionKey::new(&self.cache_namespace, request);
+        let error = match self.inner.authorize(request).await {
+            Ok(allowed) => {
+                if let Some(grace_period) = self.grace_period {
+                    self.cache
+                        .insert(key.clone(), &request.subject, allowed, grace_period);
+                }
+                return Ok(allowed);
+            }
+            Err(e) => e,
+        };
+
+        if self.mode == AuthzFailureMode::FailClosed {
+            metrics::record_authz_degraded(&self.bucket, "fail_closed");
+            return Err(error);
+        }
+
+        if let Some(allowed) = self.grace_period.and_then(|_| self.cache.get(&key)) {
+            warn!(
+                "Authorizer for bucket {} failed ({}), reusing last-known decision for {}",
+                self.bucket, error, request.subject
+            );
+            metrics::record_authz_degraded(&self.bucket, "last_known");
+            return Ok(allowed);
+        }
+
+        warn!(
+            "Authorizer for bucket {} failed ({}), allowing {} (fail_open)",
+            self.bucket, error, request.subject
+        );
+        metrics::record_authz_degraded(&self.bucket, "fail_open");
+        Ok(true)
+    }
+
+    async fn health_check(&self) -> Result<(), AuthzError> {
+        self.inner.health_check().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Returns `allow` until switched to failing
+    struct Flaky {
+        allow: bool,
+        failing: AtomicBool,
+    }
+
+    #[async_trait]
+    impl Authorizer for Flaky {
+        async fn authorize(&self, _request: &AuthzRequest) -> Result<bool, AuthzError> {
+            if self.failing.load(Ordering::SeqCst) {
+                Err(AuthzError::BackendError("unreachable".into()))
+            } else {
+                Ok(self.allow)
+            }
+        }
+    }
+
+    fn flaky(allow: bool) -> Arc<Flaky> {
+        Arc::new(Flaky {
+            allow,
+            failing: AtomicBool::new(false),
+        })
+    }
+
+    fn request(resource: &str) -> AuthzRequest {
+        AuthzRequest {
+            subject: "alice".into(),
+            action: "upload".into(),
+            resource: resource.into(),
+            context: Default::default(),
+        }
+    }
+
+    fn policy(inner: Arc<Flaky>, mode: AuthzFailureMode, grace: Option<u64>) -> impl Authorizer {
+        let config = AuthzFailureConfig {
+            mode,
+            grace_period_secs: grace,
+        };
+        FailurePolicyAuthorizer::new(inner, "b", &config, Arc::new(DecisionCache::default()))
+    }
+
+    #[tokio::test]
+    async fn test_fail_closed() {
+        let inner = flaky(true);
+        let authz = policy(inner.clone(), AuthzFailureMode::FailClosed, None);
+        assert!(authz.authorize(&request("a")).await.unwrap());
+
+        inner.failing.store(true, Ordering::SeqCst);
+        assert!(authz.authorize(&request("a")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fail_open_reuses_last_known() {
+        let inner = flaky(false);
+        let authz = policy(inner.clone(), AuthzFailureMode::FailOpen, Some(60));
+        assert!(!authz.authorize(&request("a")).await.unwrap());
+
+        inner.failing.store(true, Ordering::SeqCst);
+        // Denied before the outage, still denied
+        assert!(!authz.authorize(&request("a")).await.unwrap());
+        // Never seen: allowed
+        assert!(authz.authorize(&request("b")).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_fail_open_without_grace() {
+        let inner = flaky(false);
+        let authz = policy(inner.clone(), AuthzFailureMode::FailOpen, None);
+        assert!(!authz.authorize(&request("a")).await.unwrap());
+
+        inner.failing.store(true, Ordering::SeqCst);
+        assert!(authz.authorize(&request("a")).await.unwrap());
+    }
+}
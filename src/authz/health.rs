@@ -0,0 +1,198 @@
+//! Authorizer health checks
+//!
+//! A misconfigured authorizer (an OPA policy path no loaded policy defines,
+//! an OpenFGA store or model ID that doesn't exist) would otherwise go
+//! unnoticed until uploads start failing. [`AuthzHealth`] runs each bucket's
+//! [`Authorizer::health_check`] when the server starts and every
+//! [`CHECK_INTERVAL`] after that, logs failures (once, until they change)
+//! and recoveries, and reports failing buckets to `GET /ready`.
+//!
+//! A failing authorizer on a `fail_closed` bucket makes the server unready,
+//! since its uploads are rejected; on a `fail_open` bucket it only degrades
+//! readiness. Each bucket's state is exported as `mizuchi_authz_healthy`.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::authz::health::AuthzHealth;
+//! use mizuchi_uploadr::authz::AllowAllAuthorizer;
+//! use mizuchi_uploadr::config::AuthzFailureMode;
+//! use std::sync::Arc;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let health = AuthzHealth::new().with(
+//!     "uploads",
+//!     Arc::new(AllowAllAuthorizer),
+//!     AuthzFailureMode::FailClosed,
+//! );
+//! health.check().await;
+//! assert!(health.failures().is_empty());
+//! # }
+//! ```
+
+use super::Authorizer;
+use crate::config::AuthzFailureMode;
+use crate::metrics;
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{interval_at, Instant, MissedTickBehavior};
+use tracing::{error, info};
+
+/// Time between periodic health checks
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Health of every bucket's authorizer
+#[derive(Default)]
+pub struct AuthzHealth {
+    authorizers: Vec<(String, Arc<dyn Authorizer>, AuthzFailureMode)>,
+    /// Error of each bucket whose last check failed
+    failures: RwLock<BTreeMap<String, String>>,
+}
+
+impl AuthzHealth {
+    /// Track no authorizers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Track the authorizer of `bucket`, whose failure mode is `mode`
+    #[must_use]
+    pub fn with(
+        mut self,
+        bucket: &str,
+        authorizer: Arc<dyn Authorizer>,
+        mode: AuthzFailureMode,
+    ) -> Self {
+        self.authorizers
+            .push((bucket.to_string(), authorizer, mode));
+        self
+    }
+
+    /// Whether no authorizer is tracked
+    pub fn is_empty(&self) -> bool {
+        self.authorizers.is_empty()
+    }
+
+    /// Check every authorizer concurrently, recording the outcomes
+    pub async fn check(&self) {
+        let results = futures::future::join_all(
+            self.authorizers
+                .iter()
+                .map(|(_, authorizer, _)| authorizer.health_check()),
+        )
+        .await;
+
+        let mut failures = self.failures.write();
+        for ((bucket, _, _), result) in self.authorizers.iter().zip(results) {
+            metrics::record_authz_healthy(bucket, result.is_ok());
+            match result {
+                Ok(()) => {
+                    if failures.remove(bucket).is_some() {
+                        info!("Authorizer for bucket {} is healthy again", bucket);
+                    }
+                }
+                Err(e) => {
+                    let message = e.to_string();
+                    if failures.get(bucket) != Some(&message) {
+                        error!("Authorizer for bucket {} is unhealthy: {}", bucket, message);
+                    }
+                    failures.insert(bucket.clone(), message);
+                }
+            }
+        }
+    }
+
+    /// Error of each bucket whose authorizer failed its last check
+    pub fn failures(&self) -> BTreeMap<String, String> {
+        self.failures.read().clone()
+    }
+
+    /// Whether a failing authorizer rejects uploads (its bucket is
+    /// `fail_closed`)
+    pub fn is_blocking(&self) -> bool {
+        let failures = self.failures.read();
+        self.authorizers.iter().any(|(bucket, _, mode)| {
+            *mode == AuthzFailureMode::FailClosed && failures.contains_key(bucket)
+        })
+    }
+
+    /// Check every [`CHECK_INTERVAL`] in the background
+    ///
+    /// The first check happens one interval after the task is spawned.
+    pub fn spawn(self: &Arc<Self>) -> JoinHandle<()> {
+        let health = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = interval_at(Instant::now() + CHECK_INTERVAL, CHECK_INTERVAL);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                health.check().await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authz::{AllowAllAuthorizer, AuthzError, AuthzRequest};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Healthy until switched to failing
+    #[derive(Default)]
+    struct Switch(AtomicBool);
+
+    #[async_trait]
+    impl Authorizer for Switch {
+        async fn authorize(&self, _request: &AuthzRequest) -> Result<bool, AuthzError> {
+            Ok(true)
+        }
+
+        async fn health_check(&self) -> Result<(), AuthzError> {
+            if self.0.load(Ordering::SeqCst) {
+                return Err(AuthzError::ConfigError("store 'x' does not exist".into()));
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failures_and_recovery() {
+        let closed = Arc::new(Switch::default());
+        let open = Arc::new(Switch::default());
+        let health = AuthzHealth::new()
+            .with("closed", closed.clone(), AuthzFailureMode::FailClosed)
+            .with("open", open.clone(), AuthzFailureMode::FailOpen)
+            .with(
+                "static",
+                Arc::new(AllowAllAuthorizer),
+                AuthzFailureMode::FailClosed,
+            );
+
+        health.check().await;
+        assert!(health.failures().is_empty());
+
+        open.0.store(true, Ordering::SeqCst);
+        health.check().await;
+        assert_eq!(
+            health.failures()["open"],
+            "Configuration error: store 'x' does not exist"
+        );
+        assert!(!health.is_blocking());
+
+        closed.0.store(true, Ordering::SeqCst);
+        health.check().await;
+        assert!(health.is_blocking());
+
+        closed.0.store(false, Ordering::SeqCst);
+        open.0.store(false, Ordering::SeqCst);
+        health.check().await;
+        assert!(health.failures().is_empty());
+        assert!(!health.is_blocking());
+    }
+}
@@ -1,22 +1,39 @@
 //! Authorization module
 //!
-//! Provides OPA and OpenFGA based authorization.
+//! Provides OPA and OpenFGA based authorization, and a built-in static
+//! policy for simple deployments. Authorizers can be combined with
+//! [`AllOfAuthorizer`] and [`AnyOfAuthorizer`], and share a
+//! [`cache::DecisionCache`] so decisions can be invalidated per subject.
 //!
 //! Reference implementations from Yatagarasu:
 //! - OPA: https://github.com/julianshen/yatagarasu/tree/master/src/authz/opa
 //! - OpenFGA: https://github.com/julianshen/yatagarasu/tree/master/src/authz/openfga
 
+use crate::config::AuthzConfig;
+use crate::error::{Categorized, ErrorCategory};
 use async_trait::async_trait;
+use cache::DecisionCache;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
 use thiserror::Error;
+use tracing::{warn, Instrument};
 
+pub mod cache;
+pub mod claims;
+pub mod failure;
+pub mod health;
 pub mod opa;
 pub mod openfga;
+pub mod shadow;
+pub mod singleflight;
+pub mod static_policy;
 
 #[cfg(feature = "tracing")]
 pub mod opa_tracing;
 
 /// Authorization errors
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 pub enum AuthzError {
     #[error("Access denied")]
     AccessDenied,
@@ -31,6 +48,16 @@ pub enum AuthzError {
     ConfigError(String),
 }
 
+impl Categorized for AuthzError {
+    fn category(&self) -> ErrorCategory {
+        match self {
+            AuthzError::AccessDenied => ErrorCategory::Auth,
+            AuthzError::BackendError(_) => ErrorCategory::Backend,
+            AuthzError::PolicyError(_) | AuthzError::ConfigError(_) => ErrorCategory::Server,
+        }
+    }
+}
+
 /// Authorization request
 #[derive(Debug, Clone)]
 pub struct AuthzRequest {
@@ -40,11 +67,326 @@ pub struct AuthzRequest {
     pub context: std::collections::HashMap<String, serde_json::Value>,
 }
 
+impl AuthzRequest {
+    /// Add the object being uploaded to the context (under `object`)
+    #[must_use]
+    pub fn with_object(mut self, object: &ObjectContext) -> Self {
+        self.context.insert(
+            "object".to_string(),
+            serde_json::to_value(object).expect("object context serializes"),
+        );
+        self
+    }
+}
+
+/// Upload details exposed to policies as `context.object`
+///
+/// Lets policies express rules such as "deny videos larger than 1 GB for
+/// non-premium users". `size` is the declared `Content-Length` and is
+/// absent for chunked uploads.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ObjectContext {
+    pub key: String,
+    /// Key up to and including the last `/` (empty at the bucket root)
+    pub key_prefix: String,
+    pub size: Option<u64>,
+    pub content_type: Option<String>,
+    /// "simple" or "multipart"
+    pub upload_type: &'static str,
+    /// Part number of a multipart `UploadPart` request
+    pub part_number: Option<u32>,
+}
+
+impl ObjectContext {
+    /// Describe an upload of `key`, parsing the S3 query string
+    ///
+    /// Requests carrying both `uploadId` and `partNumber` are multipart
+    /// part uploads; everything else is a simple upload.
+    pub fn new(
+        key: &str,
+        size: Option<u64>,
+        content_type: Option<&str>,
+        query: Option<&str>,
+    ) -> Self {
+        let param = |name: &str| {
+            query?
+                .split('&')
+                .filter_map(|pair| pair.split_once('=').or(Some((pair, ""))))
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v)
+        };
+        let part_number = param("uploadId")
+            .and(param("partNumber"))
+            .and_then(|n| n.parse().ok());
+
+        Self {
+            key: key.to_string(),
+            key_prefix: key.rfind('/').map_or("", |i| &key[..=i]).to_string(),
+            size,
+            content_type: content_type.map(str::to_string),
+            upload_type: if part_number.is_some() {
+                "multipart"
+            } else {
+                "simple"
+            },
+            part_number,
+        }
+    }
+}
+
 /// Authorizer trait
 #[async_trait]
 pub trait Authorizer: Send + Sync {
     /// Check if the request is authorized
     async fn authorize(&self, request: &AuthzRequest) -> Result<bool, AuthzError>;
+
+    /// Check that the backend is reachable and knows the configured policy,
+    /// store or model
+    ///
+    /// Run at startup and periodically by [`health::AuthzHealth`]; errors
+    /// should say what to fix. Authorizers without a backend are always
+    /// healthy.
+    async fn health_check(&self) -> Result<(), AuthzError> {
+        Ok(())
+    }
+}
+
+/// Create the authorizer configured for a bucket
+pub fn from_config(config: &AuthzConfig) -> Result<Arc<dyn Authorizer>, AuthzError> {
+    from_config_with_cache(config, &Arc::new(DecisionCache::default()))
+}
+
+/// Create the authorizer configured for a bucket, caching decisions in `cache`
+pub fn from_config_with_cache(
+    config: &AuthzConfig,
+    cache: &Arc<DecisionCache>,
+) -> Result<Arc<dyn Authorizer>, AuthzError> {
+    from_config_with_registry(config, cache, &HashMap::new())
+}
+
+/// Like [`from_config_with_cache`], resolving `custom` authorizers by name
+/// in `registry`
+pub fn from_config_with_registry(
+    config: &AuthzConfig,
+    cache: &Arc<DecisionCache>,
+    registry: &HashMap<String, Arc<dyn Authorizer>>,
+) -> Result<Arc<dyn Authorizer>, AuthzError> {
+    match config {
+        AuthzConfig::Static(policy) => {
+            Ok(Arc::new(static_policy::StaticPolicyAuthorizer::new(policy)))
+        }
+        AuthzConfig::Opa(opa) => {
+            let authorizer = opa::OpaAuthorizer::with_cache(
+                opa::OpaConfig {
+                    url: opa.url.clone(),
+                    policy_path: opa.policy_path.clone(),
+                    timeout: opa.timeout_secs.map(std::time::Duration::from_secs),
+                    cache_ttl: opa.cache_ttl_secs.map(std::time::Duration::from_secs),
+                    deny_cache_ttl: opa.deny_cache_ttl_secs.map(std::time::Duration::from_secs),
+                },
+                Arc::clone(cache),
+            );
+            let authorizer = match opa.decision_logs {
+                Some(ref logs) => authorizer
+                    .with_decision_log(Arc::new(opa::decision_log::DecisionLog::new(logs))),
+                None => authorizer,
+            };
+            Ok(Arc::new(match opa.bundle {
+                Some(ref bundle) => authorizer.with_bundle_config(bundle)?,
+                None => authorizer,
+            }))
+        }
+        AuthzConfig::OpenFga(fga) => {
+            openfga::check_transport(fga.transport)?;
+            Ok(Arc::new(openfga::OpenFgaAuthorizer::with_cache(
+                openfga::OpenFgaConfig {
+                    url: fga.url.clone(),
+                    store_id: fga.store_id.clone(),
+                    authorization_model_id: fga.authorization_model_id.clone(),
+                    timeout: fga.timeout_secs.map(std::time::Duration::from_secs),
+                    cache_ttl: fga.cache_ttl_secs.map(std::time::Duration::from_secs),
+                    deny_cache_ttl: fga.deny_cache_ttl_secs.map(std::time::Duration::from_secs),
+                    object_mapping: fga.object_mapping,
+                    transport: fga.transport,
+                },
+                Arc::clone(cache),
+            )))
+        }
+        AuthzConfig::AllOf { authorizers } => {
+            let mut all_of = AllOfAuthorizer::new();
+            for (i, leg) in authorizers.iter().enumerate() {
+                all_of = all_of.with(
+                    &format!("{}.{}", i, leg.kind()),
+                    from_config_with_registry(leg, cache, registry)?,
+                );
+            }
+            Ok(Arc::new(all_of))
+        }
+        AuthzConfig::AnyOf { authorizers } => {
+            let mut any_of = AnyOfAuthorizer::new();
+            for (i, leg) in authorizers.iter().enumerate() {
+                any_of = any_of.with(
+                    &format!("{}.{}", i, leg.kind()),
+                    from_config_with_registry(leg, cache, registry)?,
+                );
+            }
+            Ok(Arc::new(any_of))
+        }
+        AuthzConfig::Custom { name } => registry.get(name).cloned().ok_or_else(|| {
+            AuthzError::ConfigError(format!("Authorizer '{}' is not registered", name))
+        }),
+    }
+}
+
+/// Named authorizers evaluated in order by a combinator
+type Legs = Vec<(String, Arc<dyn Authorizer>)>;
+
+/// Run one combinator leg inside its own span
+async fn authorize_leg(
+    combinator: &'static str,
+    name: &str,
+    authorizer: &dyn Authorizer,
+    request: &AuthzRequest,
+) -> Result<bool, AuthzError> {
+    let span = tracing::info_span!(
+        "authz.leg",
+        authz.combinator = combinator,
+        authz.leg = name,
+        authz.decision = tracing::field::Empty,
+    );
+    let result = authorizer.authorize(request).instrument(span.clone()).await;
+    span.record(
+        "authz.decision",
+        match result {
+            Ok(true) => "allow",
+            Ok(false) => "deny",
+            Err(_) => "error",
+        },
+    );
+    result
+}
+
+/// Check every leg, reporting the first failure with the leg's name
+async fn check_legs(legs: &Legs) -> Result<(), AuthzError> {
+    for (name, authorizer) in legs {
+        authorizer.health_check().await.map_err(|e| match e {
+            AuthzError::BackendError(e) => AuthzError::BackendError(format!("{}: {}", name, e)),
+            AuthzError::ConfigError(e) => AuthzError::ConfigError(format!("{}: {}", name, e)),
+            AuthzError::PolicyError(e) => AuthzError::PolicyError(format!("{}: {}", name, e)),
+            AuthzError::AccessDenied => AuthzError::AccessDenied,
+        })?;
+    }
+    Ok(())
+}
+
+/// Allows only if every authorizer allows
+///
+/// Authorizers run in order and evaluation stops at the first deny or
+/// error, so e.g. a local static policy can be listed before OPA to avoid
+/// the round-trip for requests it already rejects. An empty combinator
+/// fails with [`AuthzError::ConfigError`] rather than allowing everything.
+///
+/// # Example
+///
+/// ```
+/// use mizuchi_uploadr::authz::{AllOfAuthorizer, AllowAllAuthorizer, DenyAllAuthorizer};
+/// use std::sync::Arc;
+///
+/// let authz = AllOfAuthorizer::new()
+///     .with("allow", Arc::new(AllowAllAuthorizer))
+///     .with("deny", Arc::new(DenyAllAuthorizer));
+/// ```
+#[derive(Default)]
+pub struct AllOfAuthorizer {
+    legs: Legs,
+}
+
+impl AllOfAuthorizer {
+    /// Create an empty combinator
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an authorizer
+    #[must_use]
+    pub fn with(mut self, name: &str, authorizer: Arc<dyn Authorizer>) -> Self {
+        self.legs.push((name.to_string(), authorizer));
+        self
+    }
+}
+
+#[async_trait]
+impl Authorizer for AllOfAuthorizer {
+    async fn authorize(&self, request: &AuthzRequest) -> Result<bool, AuthzError> {
+        if self.legs.is_empty() {
+            return Err(AuthzError::ConfigError("all_of has no authorizers".into()));
+        }
+        for (name, authorizer) in &self.legs {
+            if !authorize_leg("all_of", name, authorizer.as_ref(), request).await? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    async fn health_check(&self) -> Result<(), AuthzError> {
+        check_legs(&self.legs).await
+    }
+}
+
+/// Allows if any authorizer allows
+///
+/// Authorizers run in order and evaluation stops at the first allow. A leg
+/// that fails is skipped; the request is denied if no leg allows, and the
+/// error is returned only when every leg failed.
+#[derive(Default)]
+pub struct AnyOfAuthorizer {
+    legs: Legs,
+}
+
+impl AnyOfAuthorizer {
+    /// Create an empty combinator
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an authorizer
+    #[must_use]
+    pub fn with(mut self, name: &str, authorizer: Arc<dyn Authorizer>) -> Self {
+        self.legs.push((name.to_string(), authorizer));
+        self
+    }
+}
+
+#[async_trait]
+impl Authorizer for AnyOfAuthorizer {
+    async fn authorize(&self, request: &AuthzRequest) -> Result<bool, AuthzError> {
+        let mut last_error = None;
+        let mut denied = false;
+        for (name, authorizer) in &self.legs {
+            match authorize_leg("any_of", name, authorizer.as_ref(), request).await {
+                Ok(true) => return Ok(true),
+                Ok(false) => denied = true,
+                Err(e) => {
+                    warn!("Authorizer {} failed, trying the next one: {}", name, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        match last_error {
+            Some(e) if !denied => Err(e),
+            _ if self.legs.is_empty() => {
+                Err(AuthzError::ConfigError("any_of has no authorizers".into()))
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Every leg is checked: a broken leg is a misconfiguration even while
+    /// the others allow uploads
+    async fn health_check(&self) -> Result<(), AuthzError> {
+        check_legs(&self.legs).await
+    }
 }
 
 /// No-op authorizer that always allows
@@ -80,6 +422,119 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_object_context() {
+        let object = ObjectContext::new(
+            "videos/2026/launch.mp4",
+            Some(1024),
+            Some("video/mp4"),
+            Some("partNumber=3&uploadId=abc"),
+        );
+        assert_eq!(object.key_prefix, "videos/2026/");
+        assert_eq!(object.upload_type, "multipart");
+        assert_eq!(object.part_number, Some(3));
+
+        let simple = ObjectContext::new("a.txt", None, None, Some("partNumber=3"));
+        assert_eq!(simple.key_prefix, "");
+        assert_eq!(simple.upload_type, "simple");
+        assert_eq!(simple.part_number, None);
+
+        let request = test_request().with_object(&object);
+        assert_eq!(request.context["object"]["size"], 1024);
+        assert_eq!(request.context["object"]["content_type"], "video/mp4");
+    }
+
+    /// Counts calls, to check short-circuiting
+    struct Counting(bool, std::sync::atomic::AtomicUsize);
+
+    #[async_trait]
+    impl Authorizer for Counting {
+        async fn authorize(&self, _request: &AuthzRequest) -> Result<bool, AuthzError> {
+            self.1.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(self.0)
+        }
+    }
+
+    struct Failing;
+
+    #[async_trait]
+    impl Authorizer for Failing {
+        async fn authorize(&self, _request: &AuthzRequest) -> Result<bool, AuthzError> {
+            Err(AuthzError::BackendError("unreachable".into()))
+        }
+    }
+
+    fn counting(allow: bool) -> Arc<Counting> {
+        Arc::new(Counting(allow, Default::default()))
+    }
+
+    fn calls(authorizer: &Counting) -> usize {
+        authorizer.1.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn test_all_of_short_circuits_on_deny() {
+        let (deny, allow) = (counting(false), counting(true));
+        let authz = AllOfAuthorizer::new()
+            .with("deny", deny.clone())
+            .with("allow", allow.clone());
+        assert!(!authz.authorize(&test_request()).await.unwrap());
+        assert_eq!((calls(&deny), calls(&allow)), (1, 0));
+
+        let authz = AllOfAuthorizer::new()
+            .with("allow", Arc::new(AllowAllAuthorizer))
+            .with("failing", Arc::new(Failing));
+        assert!(authz.authorize(&test_request()).await.is_err());
+
+        assert!(AllOfAuthorizer::new()
+            .authorize(&test_request())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_any_of_short_circuits_on_allow() {
+        let (allow, deny) = (counting(true), counting(false));
+        let authz = AnyOfAuthorizer::new()
+            .with("allow", allow.clone())
+            .with("deny", deny.clone());
+        assert!(authz.authorize(&test_request()).await.unwrap());
+        assert_eq!((calls(&allow), calls(&deny)), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_any_of_errors() {
+        let authz = AnyOfAuthorizer::new()
+            .with("failing", Arc::new(Failing))
+            .with("allow", Arc::new(AllowAllAuthorizer));
+        assert!(authz.authorize(&test_request()).await.unwrap());
+
+        let authz = AnyOfAuthorizer::new()
+            .with("failing", Arc::new(Failing))
+            .with("deny", Arc::new(DenyAllAuthorizer));
+        assert!(!authz.authorize(&test_request()).await.unwrap());
+
+        let authz = AnyOfAuthorizer::new().with("failing", Arc::new(Failing));
+        assert!(authz.authorize(&test_request()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_custom_resolved_from_registry() {
+        let config: AuthzConfig =
+            serde_yaml::from_str("type: any_of\nauthorizers:\n  - type: custom\n    name: team\n")
+                .unwrap();
+        let cache = Arc::new(DecisionCache::default());
+        let team = counting(true);
+        let registry = HashMap::from([("team".to_string(), team.clone() as Arc<dyn Authorizer>)]);
+
+        let authz = from_config_with_registry(&config, &cache, &registry).unwrap();
+        assert!(authz.authorize(&test_request()).await.unwrap());
+        assert_eq!(calls(&team), 1);
+
+        let err = from_config_with_cache(&config, &cache).err().unwrap();
+        assert!(matches!(err, AuthzError::ConfigError(ref e) if e.contains("'team'")));
+    }
+
     #[tokio::test]
     async fn test_allow_all() {
         let authz = AllowAllAuthorizer;
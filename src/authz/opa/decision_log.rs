@@ -0,0 +1,209 @@
+//! OPA decision logs
+//!
+//! With `decision_logs` configured, every query an [`OpaAuthorizer`] sends
+//! carries a fresh decision ID as `input.decision_id`, so the matching entry
+//! in OPA's own decision log can be found from the proxy's records. The IDs
+//! of the decisions made while handling a request are gathered by
+//! [`collect`]; the server records them in the upload receipt, or in the log
+//! line of a denied upload. Decisions served from the decision cache make no
+//! query and have no ID.
+//!
+//! With a `url`, the proxy also pushes a decision log entry per query to that
+//! sink in the format of OPA's decision log API: gzipped JSON arrays of
+//! events. Events are queued without waiting, and dropped when the queue is
+//! full.
+//!
+//! [`OpaAuthorizer`]: super::OpaAuthorizer
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::authz::opa::decision_log;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let (allowed, decision_ids) = decision_log::collect(async { true }).await;
+//! assert!(allowed);
+//! assert!(decision_ids.is_empty());
+//! # }
+//! ```
+
+use crate::config::OpaDecisionLogConfig;
+use crate::metrics;
+use chrono::{DateTime, Utc};
+use flate2::write::GzEncoder;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::future::Future;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Most events waiting to be pushed
+const QUEUE_CAPACITY: usize = 10_000;
+
+/// Most events pushed in one request
+const MAX_BATCH: usize = 100;
+
+/// Timeout of one push to the sink
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+tokio::task_local! {
+    static DECISION_IDS: Arc<Mutex<Vec<String>>>;
+}
+
+/// Run `future`, returning the IDs of the OPA decisions it made
+pub async fn collect<F: Future>(future: F) -> (F::Output, Vec<String>) {
+    let ids = Arc::new(Mutex::new(Vec::new()));
+    let output = DECISION_IDS.scope(Arc::clone(&ids), future).await;
+    let ids = std::mem::take(&mut *ids.lock());
+    (output, ids)
+}
+
+/// Report a decision made for the current request to the enclosing `collect`
+///
+/// Also used for decisions shared by a check already in flight, which were
+/// recorded by the request that made them.
+pub(crate) fn attribute(decision_id: &str) {
+    // Outside `collect` (e.g. shadow evaluation) the ID isn't reported
+    let _ = DECISION_IDS.try_with(|ids| ids.lock().push(decision_id.to_string()));
+}
+
+/// One entry of the decision log, as OPA's decision log API expects it
+#[derive(Debug, Clone, Serialize)]
+pub struct DecisionEvent {
+    pub decision_id: String,
+    /// Policy path queried
+    pub path: String,
+    pub input: serde_json::Value,
+    pub result: bool,
+    pub timestamp: DateTime<Utc>,
+    pub labels: Labels,
+}
+
+/// Identifies the proxy as the source of an event
+#[derive(Debug, Clone, Serialize)]
+pub struct Labels {
+    pub app: &'static str,
+    pub version: &'static str,
+}
+
+impl Default for Labels {
+    fn default() -> Self {
+        Self {
+            app: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+        }
+    }
+}
+
+/// Decision IDs and, with a sink, the queue of events pushed to it
+pub struct DecisionLog {
+    sender: Option<mpsc::Sender<DecisionEvent>>,
+}
+
+impl DecisionLog {
+    /// Create a decision log, spawning the push worker if `config` has a URL
+    ///
+    /// Must be called from within a tokio runtime when a URL is set.
+    pub fn new(config: &OpaDecisionLogConfig) -> Self {
+        let sender = config.url.as_ref().map(|url| {
+            let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+            tokio::spawn(run_worker(url.clone(), receiver));
+            sender
+        });
+        Self { sender }
+    }
+
+    /// Record a decision made for the current request and queue its event
+    pub(crate) fn record(&self, event: DecisionEvent) {
+        attribute(&event.decision_id);
+
+        if let Some(ref sender) = self.sender {
+            if let Err(mpsc::error::TrySendError::Full(event)) = sender.try_send(event) {
+                tracing::warn!(
+                    decision_id = %event.decision_id,
+                    "Decision log queue full, dropping event"
+                );
+                metrics::record_opa_decision_logs("dropped", 1);
+            }
+        }
+    }
+}
+
+/// Push queued events to `url` in batches
+async fn run_worker(url: String, mut receiver: mpsc::Receiver<DecisionEvent>) {
+    let client = reqwest::Client::builder()
+        .timeout(PUSH_TIMEOUT)
+        .build()
+        .expect("Failed to build HTTP client");
+
+    let mut batch = Vec::with_capacity(MAX_BATCH);
+    while receiver.recv_many(&mut batch, MAX_BATCH).await > 0 {
+        let count = batch.len() as u64;
+        match push(&client, &url, &batch).await {
+            Ok(()) => metrics::record_opa_decision_logs("delivered", count),
+            Err(e) => {
+                tracing::warn!(url = %url, error = %e, "Decision log push failed");
+                metrics::record_opa_decision_logs("failed", count);
+            }
+        }
+        batch.clear();
+    }
+}
+
+/// POST `events` as a gzipped JSON array
+async fn push(client: &reqwest::Client, url: &str, events: &[DecisionEvent]) -> Result<(), String> {
+    let json = serde_json::to_vec(events).map_err(|e| e.to_string())?;
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&json).map_err(|e| e.to_string())?;
+    let body = encoder.finish().map_err(|e| e.to_string())?;
+
+    let response = client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .header("Content-Encoding", "gzip")
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("HTTP {}", response.status().as_u16()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(id: &str) -> DecisionEvent {
+        DecisionEvent {
+            decision_id: id.into(),
+            path: "mizuchi/allow".into(),
+            input: serde_json::json!({}),
+            result: true,
+            timestamp: Utc::now(),
+            labels: Labels::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_collect() {
+        let log = DecisionLog::new(&OpaDecisionLogConfig::default());
+
+        let ((), ids) = collect(async {
+            log.record(event("a"));
+            log.record(event("b"));
+        })
+        .await;
+        assert_eq!(ids, vec!["a", "b"]);
+
+        // Decisions outside `collect` are not attributed to a request
+        log.record(event("c"));
+        let ((), ids) = collect(async {}).await;
+        assert!(ids.is_empty());
+    }
+}
@@ -15,6 +15,7 @@
 //!     policy_path: "mizuchi/allow".to_string(),
 //!     timeout: Some(Duration::from_secs(5)),
 //!     cache_ttl: Some(Duration::from_secs(60)),
+//!     deny_cache_ttl: Some(Duration::from_secs(10)),
 //! };
 //! let authorizer = OpaAuthorizer::new(config);
 //!
@@ -24,23 +25,38 @@
 //!     .policy_path("mizuchi/allow")
 //!     .timeout(Duration::from_secs(5))
 //!     .cache_ttl(Duration::from_secs(60))
+//!     .deny_cache_ttl(Duration::from_secs(10))
 //!     .build()
 //!     .expect("valid config");
 //! ```
+//!
+//! Identical checks made concurrently share one query (see
+//! [`singleflight`](super::singleflight)). With a policy bundle (see
+//! [`wasm`], `opa-wasm` feature) the policy is evaluated in-process
+//! instead of on the OPA server.
+
+pub mod decision_log;
+#[cfg(feature = "opa-wasm")]
+pub mod wasm;
 
+use super::cache::{DecisionCache, DecisionKey};
+use super::singleflight::SingleFlight;
 use super::{Authorizer, AuthzError, AuthzRequest};
+use crate::config::OpaBundleConfig;
+use crate::metrics;
 use async_trait::async_trait;
+use decision_log::{DecisionEvent, DecisionLog};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
-use tokio::sync::RwLock;
+use std::time::Duration;
 
 /// Default timeout for OPA requests (5 seconds)
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
 
-/// Maximum cache size to prevent unbounded memory growth
-const MAX_CACHE_SIZE: usize = 10_000;
+/// Error for bundles configured in builds without the `opa-wasm` feature
+#[cfg(not(feature = "opa-wasm"))]
+const BUNDLE_UNAVAILABLE: &str =
+    "OPA bundles require a build with the `opa-wasm` feature (cargo build --features opa-wasm)";
 
 /// OPA client configuration
 #[derive(Debug, Clone)]
@@ -53,12 +69,9 @@ pub struct OpaConfig {
     pub timeout: Option<Duration>,
     /// Cache TTL for authorization decisions (None = no caching)
     pub cache_ttl: Option<Duration>,
-}
-
-/// Cached authorization decision
-struct CachedDecision {
-    allowed: bool,
-    cached_at: Instant,
+    /// Cache TTL for denials, usually shorter so newly granted access
+    /// takes effect sooner (None = `cache_ttl`, zero = not cached)
+    pub deny_cache_ttl: Option<Duration>,
 }
 
 /// OPA Authorizer
@@ -67,8 +80,17 @@ struct CachedDecision {
 pub struct OpaAuthorizer {
     config: OpaConfig,
     client: reqwest::Client,
-    /// Cache for authorization decisions (key = hash of request)
-    cache: Arc<RwLock<HashMap<String, CachedDecision>>>,
+    /// Decision cache, possibly shared with other authorizers
+    cache: Arc<DecisionCache>,
+    /// Separates this authorizer's decisions in a shared cache
+    cache_namespace: String,
+    /// Gives every query a decision ID when set
+    decision_log: Option<Arc<DecisionLog>>,
+    /// Queries in flight, with their decision and decision ID
+    flights: SingleFlight<Result<(bool, Option<String>), AuthzError>>,
+    /// Policy evaluated in-process instead of on the OPA server
+    #[cfg(feature = "opa-wasm")]
+    bundle: Option<Arc<wasm::OpaBundle>>,
 }
 
 /// Builder for OpaAuthorizer
@@ -78,6 +100,11 @@ pub struct OpaAuthorizerBuilder {
     policy_path: Option<String>,
     timeout: Option<Duration>,
     cache_ttl: Option<Duration>,
+    deny_cache_ttl: Option<Duration>,
+    cache: Option<Arc<DecisionCache>>,
+    decision_log: Option<Arc<DecisionLog>>,
+    #[cfg(feature = "opa-wasm")]
+    bundle: Option<Arc<wasm::OpaBundle>>,
 }
 
 /// OPA request input
@@ -91,6 +118,8 @@ struct OpaInputData {
     subject: String,
     action: String,
     resource: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    decision_id: Option<String>,
     #[serde(flatten)]
     context: std::collections::HashMap<String, serde_json::Value>,
 }
@@ -126,11 +155,40 @@ impl OpaAuthorizerBuilder {
         self
     }
 
+    /// Set the cache TTL of denials
+    pub fn deny_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.deny_cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Store decisions in a shared cache instead of a private one
+    pub fn decision_cache(mut self, cache: Arc<DecisionCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Give every query a decision ID, logged to `log`
+    pub fn decision_log(mut self, log: Arc<DecisionLog>) -> Self {
+        self.decision_log = Some(log);
+        self
+    }
+
+    /// Evaluate the policy of `bundle` in-process; no URL is needed then
+    #[cfg(feature = "opa-wasm")]
+    pub fn bundle(mut self, bundle: Arc<wasm::OpaBundle>) -> Self {
+        self.bundle = Some(bundle);
+        self
+    }
+
     /// Build the OpaAuthorizer
     pub fn build(self) -> Result<OpaAuthorizer, AuthzError> {
+        #[cfg(feature = "opa-wasm")]
         let url = self
             .url
-            .ok_or_else(|| AuthzError::ConfigError("OPA URL is required".into()))?;
+            .or_else(|| self.bundle.as_ref().map(|_| String::new()));
+        #[cfg(not(feature = "opa-wasm"))]
+        let url = self.url;
+        let url = url.ok_or_else(|| AuthzError::ConfigError("OPA URL is required".into()))?;
         let policy_path = self
             .policy_path
             .ok_or_else(|| AuthzError::ConfigError("OPA policy path is required".into()))?;
@@ -140,152 +198,193 @@ impl OpaAuthorizerBuilder {
             policy_path,
             timeout: self.timeout,
             cache_ttl: self.cache_ttl,
+            deny_cache_ttl: self.deny_cache_ttl,
         };
 
-        Ok(OpaAuthorizer::new(config))
+        let authorizer = match self.cache {
+            Some(cache) => OpaAuthorizer::with_cache(config, cache),
+            None => OpaAuthorizer::new(config),
+        };
+        let authorizer = match self.decision_log {
+            Some(log) => authorizer.with_decision_log(log),
+            None => authorizer,
+        };
+        #[cfg(feature = "opa-wasm")]
+        let authorizer = match self.bundle {
+            Some(bundle) => authorizer.with_bundle(bundle),
+            None => authorizer,
+        };
+        Ok(authorizer)
     }
 }
 
 impl OpaAuthorizer {
-    /// Create a new OPA authorizer
+    /// Create a new OPA authorizer with its own decision cache
     pub fn new(config: OpaConfig) -> Self {
+        Self::with_cache(config, Arc::new(DecisionCache::default()))
+    }
+
+    /// Create a new OPA authorizer storing decisions in `cache`
+    pub fn with_cache(config: OpaConfig, cache: Arc<DecisionCache>) -> Self {
         let timeout = config.timeout.unwrap_or(DEFAULT_TIMEOUT);
         let client = reqwest::Client::builder()
             .timeout(timeout)
             .build()
             .expect("Failed to build HTTP client");
+        let cache_namespace = format!("opa:{}/{}", config.url, config.policy_path);
 
         Self {
             config,
             client,
-            cache: Arc::new(RwLock::new(HashMap::new())),
+            cache,
+            cache_namespace,
+            decision_log: None,
+            flights: SingleFlight::new(),
+            #[cfg(feature = "opa-wasm")]
+            bundle: None,
         }
     }
 
-    /// Create a new builder for OpaAuthorizer
-    pub fn builder() -> OpaAuthorizerBuilder {
-        OpaAuthorizerBuilder::default()
+    /// Give every query a decision ID, logged to `log`
+    ///
+    /// See [`decision_log`] for how the IDs are reported.
+    #[must_use]
+    pub fn with_decision_log(mut self, log: Arc<DecisionLog>) -> Self {
+        self.decision_log = Some(log);
+        self
     }
 
-    /// Generate a cache key from the request
-    fn cache_key(request: &AuthzRequest) -> String {
-        use std::hash::{Hash, Hasher};
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        request.subject.hash(&mut hasher);
-        request.action.hash(&mut hasher);
-        request.resource.hash(&mut hasher);
-        // Hash context keys and values
-        let mut context_keys: Vec<_> = request.context.keys().collect();
-        context_keys.sort();
-        for key in context_keys {
-            key.hash(&mut hasher);
-            if let Some(value) = request.context.get(key) {
-                value.to_string().hash(&mut hasher);
-            }
-        }
-        format!("{:x}", hasher.finish())
+    /// Evaluate the policy of `bundle` in-process instead of querying the
+    /// OPA server
+    #[cfg(feature = "opa-wasm")]
+    #[must_use]
+    pub fn with_bundle(mut self, bundle: Arc<wasm::OpaBundle>) -> Self {
+        self.cache_namespace = format!("opa-wasm:{}/{}", bundle.source(), self.config.policy_path);
+        self.bundle = Some(bundle);
+        self
     }
 
-    /// Check cache for a decision
-    async fn check_cache(&self, key: &str) -> Option<bool> {
-        let cache_ttl = self.config.cache_ttl?;
-        let cache = self.cache.read().await;
-        if let Some(cached) = cache.get(key) {
-            if cached.cached_at.elapsed() < cache_ttl {
-                return Some(cached.allowed);
+    /// Evaluate the bundle `config` describes in-process, reloading it
+    /// every `reload_secs`
+    ///
+    /// Must be called from within a tokio runtime when `reload_secs` is set.
+    /// Fails with [`AuthzError::ConfigError`] when built without the
+    /// `opa-wasm` feature, so a configured bundle is never silently ignored.
+    pub fn with_bundle_config(self, config: &OpaBundleConfig) -> Result<Self, AuthzError> {
+        #[cfg(feature = "opa-wasm")]
+        {
+            let bundle = Arc::new(wasm::OpaBundle::new(wasm::BundleSource::from_config(
+                config,
+            )?));
+            if let Some(secs) = config.reload_secs {
+                bundle.spawn_reloader(Duration::from_secs(secs.max(1)));
             }
+            Ok(self.with_bundle(bundle))
+        }
+        #[cfg(not(feature = "opa-wasm"))]
+        {
+            let _ = config;
+            Err(AuthzError::ConfigError(BUNDLE_UNAVAILABLE.into()))
         }
-        None
     }
 
-    /// Store a decision in the cache
-    async fn store_cache(&self, key: String, allowed: bool) {
-        if self.config.cache_ttl.is_some() {
-            let mut cache = self.cache.write().await;
+    /// Create a new builder for OpaAuthorizer
+    pub fn builder() -> OpaAuthorizerBuilder {
+        OpaAuthorizerBuilder::default()
+    }
 
-            // Evict expired entries if cache is getting large
-            if cache.len() >= MAX_CACHE_SIZE {
-                let cache_ttl = self.config.cache_ttl.unwrap();
-                cache.retain(|_, v| v.cached_at.elapsed() < cache_ttl);
-            }
+    /// Cache namespace of the current policy
+    fn namespace(&self) -> std::borrow::Cow<'_, str> {
+        #[cfg(feature = "opa-wasm")]
+        if let Some(ref bundle) = self.bundle {
+            return format!("{}#{}", self.cache_namespace, bundle.revision()).into();
+        }
+        self.cache_namespace.as_str().into()
+    }
 
-            // If still too large after cleanup, remove oldest entries
-            if cache.len() >= MAX_CACHE_SIZE {
-                // Find and remove oldest 10% of entries
-                let to_remove = MAX_CACHE_SIZE / 10;
-                let mut entries: Vec<_> = cache
-                    .iter()
-                    .map(|(k, v)| (k.clone(), v.cached_at))
-                    .collect();
-                entries.sort_by_key(|(_, t)| *t);
-                for (key, _) in entries.into_iter().take(to_remove) {
-                    cache.remove(&key);
-                }
-            }
+    /// Check cache for a decision
+    fn check_cache(&self, key: &DecisionKey) -> Option<bool> {
+        self.config.cache_ttl.or(self.config.deny_cache_ttl)?;
+        self.cache.get(key)
+    }
 
-            cache.insert(
-                key,
-                CachedDecision {
-                    allowed,
-                    cached_at: Instant::now(),
-                },
-            );
+    /// Store a decision in the cache, for the TTL of its outcome
+    fn store_cache(&self, key: DecisionKey, subject: &str, allowed: bool) {
+        let ttl = if allowed {
+            self.config.cache_ttl
+        } else {
+            self.config.deny_cache_ttl.or(self.config.cache_ttl)
+        };
+        if let Some(ttl) = ttl.filter(|ttl| !ttl.is_zero()) {
+            self.cache.insert(key, subject, allowed, ttl);
         }
     }
 
     /// Clear all cached authorization decisions
+    ///
+    /// When the cache is shared this also drops other authorizers' decisions.
     pub async fn clear_cache(&self) {
-        let mut cache = self.cache.write().await;
-        cache.clear();
+        self.cache.clear();
     }
 
     /// Get the current cache size
     pub async fn cache_size(&self) -> usize {
-        let cache = self.cache.read().await;
-        cache.len()
+        self.cache.len()
     }
-}
-
-#[async_trait]
-impl Authorizer for OpaAuthorizer {
-    #[cfg_attr(feature = "tracing", tracing::instrument(
-        name = "authz.opa",
-        skip(self, request),
-        fields(
-            authz.method = "opa",
-            authz.action = %request.action,
-            authz.resource_type = %extract_resource_type(&request.resource),
-            otel.kind = "internal"
-        ),
-        err
-    ))]
-    async fn authorize(&self, request: &AuthzRequest) -> Result<bool, AuthzError> {
-        // Check cache first
-        let cache_key = Self::cache_key(request);
-        if let Some(cached_decision) = self.check_cache(&cache_key).await {
-            #[cfg(feature = "tracing")]
-            tracing::debug!(
-                decision = %if cached_decision { "allow" } else { "deny" },
-                "OPA authorization decision (cached)"
-            );
-            return Ok(cached_decision);
-        }
-
-        let url = format!("{}/v1/data/{}", self.config.url, self.config.policy_path);
 
+    /// Query the policy and cache the decision, returning it with its
+    /// decision ID
+    async fn query(
+        &self,
+        cache_key: DecisionKey,
+        request: &AuthzRequest,
+    ) -> Result<(bool, Option<String>), AuthzError> {
+        let decision_id = self
+            .decision_log
+            .as_ref()
+            .map(|_| uuid::Uuid::new_v4().to_string());
         let input = OpaInput {
             input: OpaInputData {
                 subject: request.subject.clone(),
                 action: request.action.clone(),
                 resource: request.resource.clone(),
+                decision_id: decision_id.clone(),
                 context: request.context.clone(),
             },
         };
 
+        #[cfg(feature = "opa-wasm")]
+        let allowed = match self.bundle {
+            Some(ref bundle) => self.evaluate_bundle(bundle, &input).await?,
+            None => self.post_query(&input).await?,
+        };
+        #[cfg(not(feature = "opa-wasm"))]
+        let allowed = self.post_query(&input).await?;
+
+        // Store in cache
+        self.store_cache(cache_key, &request.subject, allowed);
+
+        if let (Some(log), Some(decision_id)) = (&self.decision_log, &decision_id) {
+            log.record(DecisionEvent {
+                decision_id: decision_id.clone(),
+                path: self.config.policy_path.clone(),
+                input: serde_json::to_value(&input.input).expect("OPA input serializes"),
+                result: allowed,
+                timestamp: chrono::Utc::now(),
+                labels: Default::default(),
+            });
+        }
+
+        Ok((allowed, decision_id))
+    }
+
+    /// Query the policy on the OPA server
+    async fn post_query(&self, input: &OpaInput) -> Result<bool, AuthzError> {
+        let url = format!("{}/v1/data/{}", self.config.url, self.config.policy_path);
         let response = self
             .client
             .post(&url)
-            .json(&input)
+            .json(input)
             .send()
             .await
             .map_err(|e| AuthzError::BackendError(e.to_string()))?;
@@ -302,10 +401,117 @@ impl Authorizer for OpaAuthorizer {
             .await
             .map_err(|e| AuthzError::BackendError(e.to_string()))?;
 
-        let allowed = opa_response.result.unwrap_or(false);
+        Ok(opa_response.result.unwrap_or(false))
+    }
 
-        // Store in cache
-        self.store_cache(cache_key, allowed).await;
+    /// Evaluate the policy of `bundle`; like OPA's API, an undefined
+    /// decision denies
+    #[cfg(feature = "opa-wasm")]
+    async fn evaluate_bundle(
+        &self,
+        bundle: &wasm::OpaBundle,
+        input: &OpaInput,
+    ) -> Result<bool, AuthzError> {
+        let input = serde_json::to_value(&input.input).expect("OPA input serializes");
+        let entrypoint = self.config.policy_path.trim_matches('/');
+        match bundle.evaluate(entrypoint, input).await? {
+            None => Ok(false),
+            Some(serde_json::Value::Bool(allowed)) => Ok(allowed),
+            Some(other) => Err(AuthzError::PolicyError(format!(
+                "OPA policy '{}' returned {}, not a boolean",
+                entrypoint, other
+            ))),
+        }
+    }
+
+    /// GET an OPA API endpoint
+    async fn get_json(&self, endpoint: &str) -> Result<serde_json::Value, AuthzError> {
+        let url = format!("{}{}", self.config.url, endpoint);
+        let response = self.client.get(&url).send().await.map_err(|e| {
+            AuthzError::BackendError(format!("OPA at {} is unreachable: {}", url, e))
+        })?;
+        if !response.status().is_success() {
+            return Err(AuthzError::BackendError(format!(
+                "OPA returned status {} for {}",
+                response.status(),
+                url
+            )));
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| AuthzError::BackendError(e.to_string()))
+    }
+}
+
+/// Packages of the policies OPA has loaded, with their rule names, as
+/// slash-separated paths
+fn loaded_packages(policies: &serde_json::Value) -> Vec<(String, Vec<String>)> {
+    let modules = policies["result"].as_array().map_or(&[][..], Vec::as_slice);
+    modules
+        .iter()
+        .map(|module| {
+            let ast = &module["ast"];
+            let package = ast["package"]["path"]
+                .as_array()
+                .map_or(&[][..], Vec::as_slice)
+                .iter()
+                // The first term is the `data` root
+                .skip(1)
+                .filter_map(|term| term["value"].as_str())
+                .collect::<Vec<_>>()
+                .join("/");
+            let rules = ast["rules"]
+                .as_array()
+                .map_or(&[][..], Vec::as_slice)
+                .iter()
+                .filter_map(|rule| {
+                    let head = &rule["head"];
+                    head["name"].as_str().or(head["ref"][0]["value"].as_str())
+                })
+                .map(str::to_string)
+                .collect();
+            (package, rules)
+        })
+        .collect()
+}
+
+#[async_trait]
+impl Authorizer for OpaAuthorizer {
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        name = "authz.opa",
+        skip(self, request),
+        fields(
+            authz.method = "opa",
+            authz.action = %request.action,
+            authz.resource_type = %extract_resource_type(&request.resource),
+            otel.kind = "internal"
+        ),
+        err
+    ))]
+    async fn authorize(&self, request: &AuthzRequest) -> Result<bool, AuthzError> {
+        // Check cache first
+        let cache_key = DecisionKey::new(&self.namespace(), request);
+        if let Some(cached_decision) = self.check_cache(&cache_key) {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                decision = %if cached_decision { "allow" } else { "deny" },
+                "OPA authorization decision (cached)"
+            );
+            return Ok(cached_decision);
+        }
+
+        let (result, shared) = self
+            .flights
+            .run(cache_key.clone(), self.query(cache_key, request))
+            .await;
+        let (allowed, decision_id) = result?;
+        if shared {
+            metrics::record_authz_coalesced("opa");
+            if let Some(ref decision_id) = decision_id {
+                decision_log::attribute(decision_id);
+            }
+        }
 
         #[cfg(feature = "tracing")]
         tracing::info!(
@@ -315,6 +521,57 @@ impl Authorizer for OpaAuthorizer {
 
         Ok(allowed)
     }
+
+    /// The policy path must name a document OPA knows: base data, or a
+    /// package or rule of a loaded policy (rules that depend on `input`
+    /// are undefined when queried without it)
+    async fn health_check(&self) -> Result<(), AuthzError> {
+        let path = self.config.policy_path.trim_matches('/');
+        #[cfg(feature = "opa-wasm")]
+        if let Some(ref bundle) = self.bundle {
+            let policy = bundle.policy().await?;
+            if policy.entrypoints().contains(&path) {
+                return Ok(());
+            }
+            return Err(AuthzError::ConfigError(format!(
+                "OPA policy path '{}' is not an entrypoint of bundle {}; rebuild it with `opa build -t wasm -e {}` (entrypoints: {})",
+                path,
+                bundle.source(),
+                path,
+                policy.entrypoints().join(", ")
+            )));
+        }
+        let data = self.get_json(&format!("/v1/data/{}", path)).await?;
+        if data.get("result").is_some() {
+            return Ok(());
+        }
+
+        let packages = loaded_packages(&self.get_json("/v1/policies").await?);
+        let defined = packages.iter().any(|(package, rules)| {
+            path == package
+                || rules.iter().any(|rule| {
+                    let document = format!("{}/{}", package, rule);
+                    path == document || path.starts_with(&format!("{}/", document))
+                })
+        });
+        if defined {
+            return Ok(());
+        }
+
+        let mut names: Vec<_> = packages.into_iter().map(|(package, _)| package).collect();
+        names.sort();
+        names.dedup();
+        Err(AuthzError::ConfigError(format!(
+            "OPA policy path '{}' is not defined on {}; check authz.policy_path against the loaded packages: {}",
+            path,
+            self.config.url,
+            if names.is_empty() {
+                "none".to_string()
+            } else {
+                names.join(", ")
+            }
+        )))
+    }
 }
 
 /// Extract resource type from resource path (no PII)
@@ -333,6 +590,37 @@ fn extract_resource_type(resource: &str) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_loaded_packages() {
+        let policies = serde_json::json!({
+            "result": [{
+                "id": "uploads.rego",
+                "ast": {
+                    "package": {"path": [
+                        {"type": "var", "value": "data"},
+                        {"type": "string", "value": "mizuchi"},
+                        {"type": "string", "value": "uploads"},
+                    ]},
+                    "rules": [
+                        {"head": {"name": "allow", "ref": [{"type": "var", "value": "allow"}]}},
+                        {"head": {"ref": [
+                            {"type": "var", "value": "limits"},
+                            {"type": "string", "value": "size"},
+                        ]}},
+                    ],
+                },
+            }]
+        });
+        assert_eq!(
+            loaded_packages(&policies),
+            vec![(
+                "mizuchi/uploads".to_string(),
+                vec!["allow".to_string(), "limits".to_string()]
+            )]
+        );
+        assert!(loaded_packages(&serde_json::json!({})).is_empty());
+    }
+
     #[test]
     fn test_opa_config() {
         let config = OpaConfig {
@@ -340,6 +628,7 @@ mod tests {
             policy_path: "mizuchi/allow".into(),
             timeout: None,
             cache_ttl: None,
+            deny_cache_ttl: None,
         };
         assert_eq!(config.url, "http://localhost:8181");
     }
@@ -351,6 +640,7 @@ mod tests {
             policy_path: "mizuchi/allow".into(),
             timeout: Some(Duration::from_secs(10)),
             cache_ttl: Some(Duration::from_secs(60)),
+            deny_cache_ttl: None,
         };
         assert_eq!(config.timeout, Some(Duration::from_secs(10)));
         assert_eq!(config.cache_ttl, Some(Duration::from_secs(60)));
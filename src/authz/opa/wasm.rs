@@ -0,0 +1,633 @@
+//! Embedded OPA policy evaluation
+//!
+//! With `bundle` configured, an [`OpaAuthorizer`] evaluates a Rego policy
+//! compiled to WASM (`opa build -t wasm -e mizuchi/allow`) in-process under
+//! wasmtime instead of querying an OPA server, which takes a network
+//! round-trip off every upload. Support is compiled in with the `opa-wasm`
+//! feature.
+//!
+//! A bundle is the `.tar.gz` written by `opa build`, whose `policy.wasm` is
+//! evaluated against the merged `data.json` documents, or a bare
+//! `policy.wasm` (or `.wat`) evaluated against empty data. Bundles are read
+//! from disk or fetched over HTTP (with `If-None-Match`), and reloaded
+//! periodically when `reload_secs` is set. A bundle that fails to load
+//! leaves the last good policy in place.
+//!
+//! Each evaluation instantiates the module in a fresh store and drives
+//! OPA's WASM ABI (`opa_eval_ctx_*`, `eval`, `opa_json_parse/dump`).
+//! Policies calling builtins that OPA doesn't compile into the module
+//! (`http.send`, `time.now_ns`, ...) are rejected when loaded.
+//!
+//! [`OpaAuthorizer`]: super::OpaAuthorizer
+
+use crate::authz::AuthzError;
+use crate::config::OpaBundleConfig;
+use crate::s3::hashing::sha256_hex;
+use flate2::read::GzDecoder;
+use parking_lot::RwLock;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use wasmtime::{Caller, Engine, ExternType, Instance, Linker, Memory, Module, Store, TypedFunc};
+
+/// Largest bundle accepted, compressed or not
+const MAX_BUNDLE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Timeout of one bundle download
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where a bundle is loaded from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BundleSource {
+    Path(PathBuf),
+    Url(String),
+}
+
+impl BundleSource {
+    /// Source named by `config`, which must set exactly one of `path` and
+    /// `url`
+    pub fn from_config(config: &OpaBundleConfig) -> Result<Self, AuthzError> {
+        match (&config.path, &config.url) {
+            (Some(path), None) => Ok(Self::Path(PathBuf::from(path))),
+            (None, Some(url)) => Ok(Self::Url(url.clone())),
+            _ => Err(AuthzError::ConfigError(
+                "OPA bundle needs exactly one of `path` and `url`".into(),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for BundleSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Path(path) => write!(f, "{}", path.display()),
+            Self::Url(url) => f.write_str(url),
+        }
+    }
+}
+
+/// Compiled policy of a bundle, with its data
+pub struct WasmPolicy {
+    engine: Engine,
+    module: Module,
+    /// `data.json` documents, merged
+    data: Vec<u8>,
+    /// Entrypoint IDs by name (e.g. `mizuchi/allow`)
+    entrypoints: HashMap<String, i32>,
+}
+
+impl WasmPolicy {
+    /// Compile a bundle: a `.tar.gz` from `opa build`, or a bare module
+    pub fn from_bundle(bundle: &[u8]) -> Result<Self, AuthzError> {
+        if !bundle.starts_with(&[0x1f, 0x8b]) {
+            return Self::new(bundle, b"{}");
+        }
+        let mut archive = Vec::new();
+        GzDecoder::new(bundle)
+            .take(MAX_BUNDLE_BYTES as u64 + 1)
+            .read_to_end(&mut archive)
+            .map_err(|e| invalid(format!("not a gzipped tarball: {}", e)))?;
+        if archive.len() > MAX_BUNDLE_BYTES {
+            return Err(invalid(format!(
+                "exceeds {} bytes uncompressed",
+                MAX_BUNDLE_BYTES
+            )));
+        }
+
+        let mut module = None;
+        let mut data = Value::Object(Map::new());
+        for (name, contents) in tar_entries(&archive)? {
+            let name = name.trim_start_matches("./").trim_start_matches('/');
+            if name == "policy.wasm" {
+                module = Some(contents);
+            } else if name == "data.json" || name.ends_with("/data.json") {
+                let document: Value = serde_json::from_slice(contents)
+                    .map_err(|e| invalid(format!("{}: {}", name, e)))?;
+                let path: Vec<&str> = name.split('/').collect();
+                merge_data(&mut data, &path[..path.len() - 1], document)
+                    .map_err(|e| invalid(format!("{}: {}", name, e)))?;
+            }
+        }
+        let module = module.ok_or_else(|| invalid("no policy.wasm (build with `-t wasm`)"))?;
+        Self::new(module, data.to_string().as_bytes())
+    }
+
+    /// Compile a module (binary or text) evaluated against `data`
+    pub fn new(module: &[u8], data: &[u8]) -> Result<Self, AuthzError> {
+        let data: Value =
+            serde_json::from_slice(data).map_err(|e| invalid(format!("data: {}", e)))?;
+        if !data.is_object() {
+            return Err(invalid("data must be a JSON object"));
+        }
+        let engine = Engine::default();
+        let module = Module::new(&engine, module).map_err(|e| invalid(e.to_string()))?;
+        let mut policy = Self {
+            engine,
+            module,
+            data: data.to_string().into_bytes(),
+            entrypoints: HashMap::new(),
+        };
+
+        let mut session = Session::new(&policy)?;
+        let builtins = session.call::<(), i32>("builtins", ())?;
+        let builtins = session.dump(builtins)?;
+        if let Some(names) = builtins.as_object().filter(|names| !names.is_empty()) {
+            let mut names: Vec<&str> = names.keys().map(String::as_str).collect();
+            names.sort_unstable();
+            return Err(invalid(format!(
+                "policy calls builtins that can't be evaluated in-process: {}",
+                names.join(", ")
+            )));
+        }
+        let entrypoints = session.call::<(), i32>("entrypoints", ())?;
+        let entrypoints = session.dump(entrypoints)?;
+        policy.entrypoints = serde_json::from_value(entrypoints)
+            .map_err(|e| invalid(format!("entrypoints: {}", e)))?;
+        Ok(policy)
+    }
+
+    /// Names of the rules the policy was built with (`opa build -e`)
+    pub fn entrypoints(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.entrypoints.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Evaluate `entrypoint` for `input`, `None` when it is undefined
+    pub fn evaluate(&self, entrypoint: &str, input: &Value) -> Result<Option<Value>, AuthzError> {
+        let id = *self.entrypoints.get(entrypoint).ok_or_else(|| {
+            AuthzError::ConfigError(format!(
+                "OPA policy path '{}' is not an entrypoint of the bundle; entrypoints: {}",
+                entrypoint,
+                self.entrypoints().join(", ")
+            ))
+        })?;
+
+        let mut session = Session::new(self)?;
+        let data = session.load(&self.data)?;
+        let input = session.load(input.to_string().as_bytes())?;
+        let ctx = session.call::<(), i32>("opa_eval_ctx_new", ())?;
+        session.call::<(i32, i32), ()>("opa_eval_ctx_set_input", (ctx, input))?;
+        session.call::<(i32, i32), ()>("opa_eval_ctx_set_data", (ctx, data))?;
+        session.call::<(i32, i32), ()>("opa_eval_ctx_set_entrypoint", (ctx, id))?;
+        let status = session.call::<i32, i32>("eval", ctx)?;
+        if status != 0 {
+            return Err(AuthzError::PolicyError(format!(
+                "OPA policy evaluation failed with status {}",
+                status
+            )));
+        }
+        let result = session.call::<i32, i32>("opa_eval_ctx_get_result", ctx)?;
+
+        // A result set: `[{"result": ...}]`, or `[]` when undefined
+        match session.dump(result)? {
+            Value::Array(mut results) if results.len() <= 1 => Ok(results
+                .pop()
+                .and_then(|mut result| result.get_mut("result").map(Value::take))),
+            other => Err(AuthzError::PolicyError(format!(
+                "OPA policy returned an unexpected result set: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// One instance of a policy module
+struct Session {
+    store: Store<()>,
+    memory: Memory,
+    instance: Instance,
+}
+
+impl Session {
+    fn new(policy: &WasmPolicy) -> Result<Self, AuthzError> {
+        let mut store = Store::new(&policy.engine, ());
+        let memory_type = policy
+            .module
+            .imports()
+            .find_map(|import| match import.ty() {
+                ExternType::Memory(ty) if import.module() == "env" => Some(ty),
+                _ => None,
+            })
+            .ok_or_else(|| invalid("policy.wasm doesn't import `env.memory`"))?;
+        let memory = Memory::new(&mut store, memory_type).map_err(|e| invalid(e.to_string()))?;
+
+        let mut linker = Linker::new(&policy.engine);
+        linker
+            .define(&store, "env", "memory", memory)
+            .map_err(|e| invalid(e.to_string()))?;
+        linker
+            .func_wrap(
+                "env",
+                "opa_abort",
+                move |caller: Caller<'_, ()>, addr: i32| -> wasmtime::Result<()> {
+                    Err(wasmtime::Error::msg(format!(
+                        "policy aborted: {}",
+                        c_string(memory.data(&caller), addr)
+                    )))
+                },
+            )
+            .and_then(|linker| {
+                linker.func_wrap(
+                    "env",
+                    "opa_println",
+                    move |caller: Caller<'_, ()>, addr: i32| {
+                        tracing::debug!(
+                            message = %c_string(memory.data(&caller), addr),
+                            "OPA policy print"
+                        );
+                    },
+                )
+            })
+            .and_then(|linker| {
+                linker.func_wrap("env", "opa_builtin0", |id: i32, _: i32| {
+                    unsupported_builtin(id)
+                })
+            })
+            .and_then(|linker| {
+                linker.func_wrap("env", "opa_builtin1", |id: i32, _: i32, _: i32| {
+                    unsupported_builtin(id)
+                })
+            })
+            .and_then(|linker| {
+                linker.func_wrap("env", "opa_builtin2", |id: i32, _: i32, _: i32, _: i32| {
+                    unsupported_builtin(id)
+                })
+            })
+            .and_then(|linker| {
+                linker.func_wrap(
+                    "env",
+                    "opa_builtin3",
+                    |id: i32, _: i32, _: i32, _: i32, _: i32| unsupported_builtin(id),
+                )
+            })
+            .and_then(|linker| {
+                linker.func_wrap(
+                    "env",
+                    "opa_builtin4",
+                    |id: i32, _: i32, _: i32, _: i32, _: i32, _: i32| unsupported_builtin(id),
+                )
+            })
+            .map_err(|e| invalid(e.to_string()))?;
+
+        let instance = linker
+            .instantiate(&mut store, &policy.module)
+            .map_err(|e| invalid(e.to_string()))?;
+        Ok(Self {
+            store,
+            memory,
+            instance,
+        })
+    }
+
+    /// Call an export of the OPA ABI
+    fn call<P, R>(&mut self, name: &str, params: P) -> Result<R, AuthzError>
+    where
+        P: wasmtime::WasmParams,
+        R: wasmtime::WasmResults,
+    {
+        let func: TypedFunc<P, R> = self
+            .instance
+            .get_typed_func(&mut self.store, name)
+            .map_err(|e| invalid(format!("`{}`: {}", name, e)))?;
+        func.call(&mut self.store, params)
+            .map_err(|e| AuthzError::PolicyError(format!("`{}`: {:#}", name, e)))
+    }
+
+    /// Parse `json` into a value in the instance's heap
+    fn load(&mut self, json: &[u8]) -> Result<i32, AuthzError> {
+        let len = i32::try_from(json.len())
+            .map_err(|_| AuthzError::PolicyError("JSON document exceeds 2GB".into()))?;
+        let ptr = self.call::<i32, i32>("opa_malloc", len)?;
+        self.memory
+            .write(&mut self.store, offset(ptr)?, json)
+            .map_err(|e| AuthzError::PolicyError(e.to_string()))?;
+        match self.call::<(i32, i32), i32>("opa_json_parse", (ptr, len))? {
+            0 => Err(AuthzError::PolicyError(
+                "policy failed to parse JSON".into(),
+            )),
+            addr => Ok(addr),
+        }
+    }
+
+    /// Serialize the value at `addr` out of the instance
+    fn dump(&mut self, addr: i32) -> Result<Value, AuthzError> {
+        let ptr = self.call::<i32, i32>("opa_json_dump", addr)?;
+        let json = c_string(self.memory.data(&self.store), ptr);
+        serde_json::from_str(&json)
+            .map_err(|e| AuthzError::PolicyError(format!("policy returned invalid JSON: {}", e)))
+    }
+}
+
+fn unsupported_builtin(id: i32) -> wasmtime::Result<i32> {
+    Err(wasmtime::Error::msg(format!(
+        "builtin {} is not supported in-process",
+        id
+    )))
+}
+
+fn invalid(message: impl fmt::Display) -> AuthzError {
+    AuthzError::PolicyError(format!("Invalid OPA bundle: {}", message))
+}
+
+fn offset(ptr: i32) -> Result<usize, AuthzError> {
+    usize::try_from(ptr).map_err(|_| AuthzError::PolicyError(format!("invalid pointer {}", ptr)))
+}
+
+/// NUL-terminated string at `addr`, lossily decoded
+fn c_string(memory: &[u8], addr: i32) -> String {
+    let start = usize::try_from(addr)
+        .unwrap_or(usize::MAX)
+        .min(memory.len());
+    let bytes = &memory[start..];
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// Regular files of a ustar archive
+fn tar_entries(archive: &[u8]) -> Result<Vec<(String, &[u8])>, AuthzError> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while let Some(header) = archive.get(offset..offset + 512) {
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        let field = |range: std::ops::Range<usize>| {
+            let bytes = &header[range];
+            let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            String::from_utf8_lossy(&bytes[..end]).into_owned()
+        };
+        let size = usize::from_str_radix(field(124..136).trim(), 8)
+            .map_err(|_| invalid("corrupt tar header"))?;
+        let start = offset + 512;
+        let contents = archive
+            .get(start..start + size)
+            .ok_or_else(|| invalid("truncated tarball"))?;
+        if matches!(header[156], b'0' | 0) {
+            let prefix = field(345..500);
+            let name = field(0..100);
+            let name = if header[257..262] == *b"ustar" && !prefix.is_empty() {
+                format!("{}/{}", prefix, name)
+            } else {
+                name
+            };
+            entries.push((name, contents));
+        }
+        offset = start + size.div_ceil(512) * 512;
+    }
+    Ok(entries)
+}
+
+/// Merge `document` into `data` at `path`
+fn merge_data(data: &mut Value, path: &[&str], document: Value) -> Result<(), String> {
+    let mut target = data;
+    for &segment in path {
+        target = target
+            .as_object_mut()
+            .ok_or("conflicts with another data document")?
+            .entry(segment)
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+    match (target, document) {
+        (Value::Object(target), Value::Object(document)) => {
+            target.extend(document);
+            Ok(())
+        }
+        _ if path.is_empty() => Err("root data must be an object".into()),
+        (target, document) => {
+            *target = document;
+            Ok(())
+        }
+    }
+}
+
+/// Digest and ETag of the bundle last loaded
+#[derive(Default)]
+struct Loaded {
+    digest: Option<String>,
+    etag: Option<String>,
+}
+
+/// Policy bundle of one authorizer, reloaded from its source
+pub struct OpaBundle {
+    source: BundleSource,
+    client: reqwest::Client,
+    policy: RwLock<Option<Arc<WasmPolicy>>>,
+    /// Bumped on every change, so cached decisions of older policies are
+    /// not reused
+    revision: AtomicU64,
+    /// Serializes reloads
+    loaded: tokio::sync::Mutex<Loaded>,
+}
+
+impl OpaBundle {
+    /// Create a bundle loaded from `source` on first use or [`reload`]
+    ///
+    /// [`reload`]: Self::reload
+    pub fn new(source: BundleSource) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            .build()
+            .expect("Failed to build HTTP client");
+        Self {
+            source,
+            client,
+            policy: RwLock::new(None),
+            revision: AtomicU64::new(0),
+            loaded: tokio::sync::Mutex::new(Loaded::default()),
+        }
+    }
+
+    /// Bundle source
+    pub fn source(&self) -> &BundleSource {
+        &self.source
+    }
+
+    /// Number of times a changed bundle was loaded
+    pub fn revision(&self) -> u64 {
+        self.revision.load(Ordering::Acquire)
+    }
+
+    /// Load the bundle if it changed, returning whether it did
+    ///
+    /// On failure the loaded policy, if any, stays in place.
+    pub async fn reload(&self) -> Result<bool, AuthzError> {
+        let mut loaded = self.loaded.lock().await;
+        let (bundle, etag) = match self.source {
+            BundleSource::Path(ref path) => {
+                let bundle = tokio::fs::read(path).await.map_err(|e| {
+                    AuthzError::BackendError(format!(
+                        "Failed to read OPA bundle {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                (bundle, None)
+            }
+            BundleSource::Url(ref url) => match self.fetch(url, loaded.etag.as_deref()).await? {
+                Some(fetched) => fetched,
+                None => return Ok(false),
+            },
+        };
+        if bundle.len() > MAX_BUNDLE_BYTES {
+            return Err(invalid(format!("exceeds {} bytes", MAX_BUNDLE_BYTES)));
+        }
+
+        let digest = sha256_hex(&bundle);
+        if loaded.digest.as_deref() == Some(digest.as_str()) {
+            loaded.etag = etag;
+            return Ok(false);
+        }
+        let policy = tokio::task::spawn_blocking(move || WasmPolicy::from_bundle(&bundle))
+            .await
+            .map_err(|e| AuthzError::PolicyError(e.to_string()))??;
+
+        *self.policy.write() = Some(Arc::new(policy));
+        self.revision.fetch_add(1, Ordering::AcqRel);
+        *loaded = Loaded {
+            digest: Some(digest),
+            etag,
+        };
+        tracing::info!(source = %self.source, "Loaded OPA bundle");
+        Ok(true)
+    }
+
+    /// GET the bundle, `None` when unchanged since `etag`
+    async fn fetch(
+        &self,
+        url: &str,
+        etag: Option<&str>,
+    ) -> Result<Option<(Vec<u8>, Option<String>)>, AuthzError> {
+        let mut request = self.client.get(url);
+        if let Some(etag) = etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        let response = request.send().await.map_err(|e| {
+            AuthzError::BackendError(format!("OPA bundle at {} is unreachable: {}", url, e))
+        })?;
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(AuthzError::BackendError(format!(
+                "OPA bundle server returned status {} for {}",
+                response.status(),
+                url
+            )));
+        }
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(str::to_string);
+        let bundle = response
+            .bytes()
+            .await
+            .map_err(|e| AuthzError::BackendError(e.to_string()))?;
+        Ok(Some((bundle.to_vec(), etag)))
+    }
+
+    /// Loaded policy, loading the bundle on first use
+    pub async fn policy(&self) -> Result<Arc<WasmPolicy>, AuthzError> {
+        if let Some(policy) = self.policy.read().clone() {
+            return Ok(policy);
+        }
+        self.reload().await?;
+        self.policy.read().clone().ok_or_else(|| {
+            AuthzError::BackendError(format!("OPA bundle {} is not loaded", self.source))
+        })
+    }
+
+    /// Evaluate `entrypoint` for `input` on the blocking thread pool
+    pub async fn evaluate(
+        &self,
+        entrypoint: &str,
+        input: Value,
+    ) -> Result<Option<Value>, AuthzError> {
+        let policy = self.policy().await?;
+        let entrypoint = entrypoint.to_string();
+        tokio::task::spawn_blocking(move || policy.evaluate(&entrypoint, &input))
+            .await
+            .map_err(|e| AuthzError::PolicyError(e.to_string()))?
+    }
+
+    /// Reload the bundle every `interval` until it is dropped
+    ///
+    /// Must be called from within a tokio runtime. The first reload runs
+    /// immediately.
+    pub fn spawn_reloader(self: &Arc<Self>, interval: Duration) {
+        let bundle: Weak<Self> = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticks.tick().await;
+                let Some(bundle) = bundle.upgrade() else {
+                    break;
+                };
+                if let Err(e) = bundle.reload().await {
+                    tracing::warn!(
+                        source = %bundle.source,
+                        error = %e,
+                        "OPA bundle reload failed, keeping the loaded policy"
+                    );
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut archive = Vec::new();
+        for (name, contents) in files {
+            let mut header = [0u8; 512];
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            header[124..135].copy_from_slice(format!("{:011o}", contents.len()).as_bytes());
+            header[156] = b'0';
+            header[257..263].copy_from_slice(b"ustar\0");
+            archive.extend_from_slice(&header);
+            archive.extend_from_slice(contents);
+            archive.resize(archive.len().div_ceil(512) * 512, 0);
+        }
+        archive.resize(archive.len() + 1024, 0);
+        archive
+    }
+
+    #[test]
+    fn test_tar_entries() {
+        let archive = tar(&[("/data.json", b"{}"), ("/policy.wasm", &[0u8; 600])]);
+        let entries = tar_entries(&archive).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], ("/data.json".to_string(), &b"{}"[..]));
+        assert_eq!(entries[1].1.len(), 600);
+
+        assert!(tar_entries(&archive[..1800]).is_err());
+    }
+
+    #[test]
+    fn test_merge_data() {
+        let mut data = json!({});
+        merge_data(&mut data, &[], json!({"limits": {"max": 1}})).unwrap();
+        merge_data(&mut data, &["tenants", "acme"], json!({"quota": 5})).unwrap();
+        merge_data(&mut data, &["limits"], json!({"min": 0})).unwrap();
+        assert_eq!(
+            data,
+            json!({
+                "limits": {"max": 1, "min": 0},
+                "tenants": {"acme": {"quota": 5}},
+            })
+        );
+        assert!(merge_data(&mut data, &[], json!([1])).is_err());
+        assert!(merge_data(&mut data, &["limits", "max", "x"], json!({})).is_err());
+    }
+}
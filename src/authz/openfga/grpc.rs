@@ -0,0 +1,294 @@
+//! gRPC transport for OpenFGA checks
+//!
+//! Checks are sent as `openfga.v1.OpenFGAService/Check` calls over a single
+//! HTTP/2 channel, opened on the first check and shared by all later ones.
+//! Each call carries a `grpc-timeout` of the configured timeout, shortened to
+//! what is left of the client's deadline, so OpenFGA gives up on checks
+//! nobody is waiting for.
+//!
+//! The messages are declared by hand below, with the field numbers of
+//! `openfga/v1/openfga_service.proto`, covering only the fields used here.
+
+use super::{CheckRequest, ContextualTuples, TupleKey};
+use crate::authz::AuthzError;
+use crate::s3::deadline;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tonic::codec::ProstCodec;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+
+/// Method path of the Check RPC
+const CHECK_PATH: &str = "/openfga.v1.OpenFGAService/Check";
+
+/// Method path of the GetStore RPC
+const GET_STORE_PATH: &str = "/openfga.v1.OpenFGAService/GetStore";
+
+/// Method path of the ReadAuthorizationModel RPC
+const READ_MODEL_PATH: &str = "/openfga.v1.OpenFGAService/ReadAuthorizationModel";
+
+/// Subset of the `openfga.v1` messages
+mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct CheckRequest {
+        #[prost(string, tag = "1")]
+        pub store_id: String,
+        #[prost(message, optional, tag = "2")]
+        pub tuple_key: Option<CheckRequestTupleKey>,
+        #[prost(message, optional, tag = "3")]
+        pub contextual_tuples: Option<ContextualTupleKeys>,
+        #[prost(string, tag = "4")]
+        pub authorization_model_id: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct CheckRequestTupleKey {
+        #[prost(string, tag = "1")]
+        pub user: String,
+        #[prost(string, tag = "2")]
+        pub relation: String,
+        #[prost(string, tag = "3")]
+        pub object: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ContextualTupleKeys {
+        #[prost(message, repeated, tag = "1")]
+        pub tuple_keys: Vec<TupleKey>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TupleKey {
+        #[prost(string, tag = "1")]
+        pub user: String,
+        #[prost(string, tag = "2")]
+        pub relation: String,
+        #[prost(string, tag = "3")]
+        pub object: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct CheckResponse {
+        #[prost(bool, tag = "1")]
+        pub allowed: bool,
+        #[prost(string, tag = "2")]
+        pub resolution: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct GetStoreRequest {
+        #[prost(string, tag = "1")]
+        pub store_id: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ReadAuthorizationModelRequest {
+        #[prost(string, tag = "1")]
+        pub store_id: String,
+        #[prost(string, tag = "2")]
+        pub id: String,
+    }
+
+    /// Response whose content is not needed (unknown fields are skipped)
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Ignored {}
+}
+
+impl proto::CheckRequest {
+    fn new(store_id: &str, request: &CheckRequest) -> Self {
+        let TupleKey {
+            user,
+            relation,
+            object,
+        } = request.tuple_key.clone();
+        Self {
+            store_id: store_id.to_string(),
+            tuple_key: Some(proto::CheckRequestTupleKey {
+                user,
+                relation,
+                object,
+            }),
+            contextual_tuples: request.contextual_tuples.as_ref().map(
+                |ContextualTuples { tuple_keys }| proto::ContextualTupleKeys {
+                    tuple_keys: tuple_keys
+                        .iter()
+                        .map(|key| proto::TupleKey {
+                            user: key.user.clone(),
+                            relation: key.relation.clone(),
+                            object: key.object.clone(),
+                        })
+                        .collect(),
+                },
+            ),
+            authorization_model_id: request.authorization_model_id.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// OpenFGA gRPC client sharing one channel across checks
+pub(super) struct GrpcClient {
+    url: String,
+    timeout: Duration,
+    channel: OnceCell<Channel>,
+}
+
+impl GrpcClient {
+    /// Client for the gRPC endpoint at `url` (e.g. "http://localhost:8081")
+    pub(super) fn new(url: &str, timeout: Duration) -> Self {
+        Self {
+            url: url.to_string(),
+            timeout,
+            channel: OnceCell::new(),
+        }
+    }
+
+    /// The shared channel, created on first use
+    ///
+    /// The channel connects lazily and reconnects by itself, so creating it
+    /// never waits on OpenFGA.
+    async fn channel(&self) -> Result<Channel, AuthzError> {
+        self.channel
+            .get_or_try_init(|| async {
+                let mut endpoint = Endpoint::from_shared(self.url.clone()).map_err(|e| {
+                    AuthzError::ConfigError(format!("Invalid OpenFGA gRPC URL: {}", e))
+                })?;
+                if self.url.starts_with("https://") {
+                    // tonic builds its TLS config from the process-wide provider
+                    let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+                    endpoint = endpoint
+                        .tls_config(ClientTlsConfig::new().with_webpki_roots())
+                        .map_err(|e| AuthzError::ConfigError(e.to_string()))?;
+                }
+                Ok(endpoint.connect_timeout(self.timeout).connect_lazy())
+            })
+            .await
+            .cloned()
+    }
+
+    /// Call the unary RPC at `path`
+    async fn unary<M, R>(&self, path: &'static str, message: M) -> Result<R, tonic::Status>
+    where
+        M: prost::Message + Send + Sync + 'static,
+        R: prost::Message + Default + Send + Sync + 'static,
+    {
+        let channel = self
+            .channel()
+            .await
+            .map_err(|e| tonic::Status::internal(e.to_string()))?;
+        let mut grpc = tonic::client::Grpc::new(channel);
+        grpc.ready()
+            .await
+            .map_err(|e| tonic::Status::unavailable(e.to_string()))?;
+
+        let timeout = deadline::remaining().map_or(self.timeout, |left| left.min(self.timeout));
+        let mut call = tonic::Request::new(message);
+        call.set_timeout(timeout);
+
+        let response = grpc
+            .unary(call, PathAndQuery::from_static(path), ProstCodec::default())
+            .await?;
+        Ok(response.into_inner())
+    }
+
+    /// Run `request` against `store_id`, returning whether it is allowed
+    pub(super) async fn check(
+        &self,
+        store_id: &str,
+        request: &CheckRequest,
+    ) -> Result<bool, AuthzError> {
+        let response: proto::CheckResponse = self
+            .unary(CHECK_PATH, proto::CheckRequest::new(store_id, request))
+            .await
+            .map_err(|status| {
+                AuthzError::BackendError(format!(
+                    "OpenFGA returned {:?}: {}",
+                    status.code(),
+                    status.message()
+                ))
+            })?;
+        Ok(response.allowed)
+    }
+
+    /// Whether `store_id` exists
+    pub(super) async fn store_exists(&self, store_id: &str) -> Result<bool, AuthzError> {
+        let request = proto::GetStoreRequest {
+            store_id: store_id.to_string(),
+        };
+        exists(
+            self.unary::<_, proto::Ignored>(GET_STORE_PATH, request)
+                .await,
+        )
+    }
+
+    /// Whether `store_id` has the authorization model `model_id`
+    pub(super) async fn model_exists(
+        &self,
+        store_id: &str,
+        model_id: &str,
+    ) -> Result<bool, AuthzError> {
+        let request = proto::ReadAuthorizationModelRequest {
+            store_id: store_id.to_string(),
+            id: model_id.to_string(),
+        };
+        exists(
+            self.unary::<_, proto::Ignored>(READ_MODEL_PATH, request)
+                .await,
+        )
+    }
+}
+
+/// Outcome of a lookup: found, not found (or an invalid ID), or an error
+fn exists(result: Result<proto::Ignored, tonic::Status>) -> Result<bool, AuthzError> {
+    match result {
+        Ok(_) => Ok(true),
+        Err(status)
+            if matches!(
+                status.code(),
+                tonic::Code::NotFound | tonic::Code::InvalidArgument
+            ) =>
+        {
+            Ok(false)
+        }
+        Err(status) => Err(AuthzError::BackendError(format!(
+            "OpenFGA returned {:?}: {}",
+            status.code(),
+            status.message()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+
+    #[test]
+    fn test_check_request_encoding() {
+        let request = CheckRequest {
+            tuple_key: TupleKey {
+                user: "user:alice".into(),
+                relation: "writer".into(),
+                object: "folder:docs/".into(),
+            },
+            contextual_tuples: Some(ContextualTuples {
+                tuple_keys: vec![TupleKey {
+                    user: "bucket:docs".into(),
+                    relation: "parent".into(),
+                    object: "folder:docs/".into(),
+                }],
+            }),
+            authorization_model_id: None,
+        };
+
+        let message = proto::CheckRequest::new("store123", &request);
+        let decoded = proto::CheckRequest::decode(message.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(decoded.store_id, "store123");
+        assert_eq!(decoded.tuple_key.unwrap().object, "folder:docs/");
+        assert_eq!(
+            decoded.contextual_tuples.unwrap().tuple_keys[0].user,
+            "bucket:docs"
+        );
+        // Unset model ID is omitted, so OpenFGA uses the latest model
+        assert!(decoded.authorization_model_id.is_empty());
+    }
+}
@@ -3,6 +3,19 @@
 //! Provides fine-grained access control using OpenFGA.
 //! Reference: https://github.com/julianshen/yatagarasu/tree/master/src/authz/openfga
 //!
+//! Checks target the bucket by default. With [`OpenFgaObjectMapping::Folder`]
+//! or [`OpenFgaObjectMapping::Object`] they target the key's folder or the key
+//! itself, and send contextual tuples describing the folder hierarchy so
+//! permissions can be granted per folder.
+//!
+//! Checks use the HTTP API by default. With [`OpenFgaTransport::Grpc`] (and
+//! the `openfga-grpc` feature) they are sent over OpenFGA's gRPC API instead,
+//! on a reused HTTP/2 channel with the client's deadline propagated; `url` is
+//! then the gRPC endpoint (port 8081 by default).
+//!
+//! Identical checks made concurrently share one call to OpenFGA (see
+//! [`singleflight`](super::singleflight)).
+//!
 //! # Example
 //!
 //! ```no_run
@@ -16,6 +29,9 @@
 //!     authorization_model_id: Some("model-123".to_string()),
 //!     timeout: Some(Duration::from_secs(5)),
 //!     cache_ttl: Some(Duration::from_secs(60)),
+//!     deny_cache_ttl: Some(Duration::from_secs(10)),
+//!     object_mapping: Default::default(),
+//!     transport: Default::default(),
 //! };
 //! let authorizer = OpenFgaAuthorizer::new(config);
 //!
@@ -26,23 +42,33 @@
 //!     .authorization_model_id("model-123")
 //!     .timeout(Duration::from_secs(5))
 //!     .cache_ttl(Duration::from_secs(60))
+//!     .deny_cache_ttl(Duration::from_secs(10))
 //!     .build()
 //!     .expect("valid config");
 //! ```
 
+use super::cache::{DecisionCache, DecisionKey};
+use super::singleflight::SingleFlight;
 use super::{Authorizer, AuthzError, AuthzRequest};
+pub use crate::config::{OpenFgaObjectMapping, OpenFgaTransport};
+use crate::metrics;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
-use tokio::sync::RwLock;
+use std::time::Duration;
+
+#[cfg(feature = "openfga-grpc")]
+mod grpc;
 
 /// Default timeout for OpenFGA requests (5 seconds)
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
 
-/// Maximum cache size to prevent unbounded memory growth
-const MAX_CACHE_SIZE: usize = 10_000;
+/// Error for a gRPC transport in a build without it
+#[cfg_attr(feature = "openfga-grpc", allow(dead_code))]
+const GRPC_UNAVAILABLE: &str = "OpenFGA gRPC transport requires the openfga-grpc feature";
+
+/// Relation linking objects and folders to their parent in contextual tuples
+const PARENT_RELATION: &str = "parent";
 
 /// OpenFGA client configuration
 #[derive(Debug, Clone)]
@@ -57,12 +83,13 @@ pub struct OpenFgaConfig {
     pub timeout: Option<Duration>,
     /// Cache TTL for authorization decisions (None = no caching)
     pub cache_ttl: Option<Duration>,
-}
-
-/// Cached authorization decision
-struct CachedDecision {
-    allowed: bool,
-    cached_at: Instant,
+    /// Cache TTL for denials, usually shorter so newly written tuples take
+    /// effect sooner (None = `cache_ttl`, zero = not cached)
+    pub deny_cache_ttl: Option<Duration>,
+    /// Object checked for an upload (default: the bucket)
+    pub object_mapping: OpenFgaObjectMapping,
+    /// API checks are sent over (default: HTTP)
+    pub transport: OpenFgaTransport,
 }
 
 /// OpenFGA Authorizer
@@ -71,8 +98,15 @@ struct CachedDecision {
 pub struct OpenFgaAuthorizer {
     config: OpenFgaConfig,
     client: reqwest::Client,
-    /// Cache for authorization decisions (key = hash of request)
-    cache: Arc<RwLock<HashMap<String, CachedDecision>>>,
+    /// Client of the gRPC API, with [`OpenFgaTransport::Grpc`]
+    #[cfg(feature = "openfga-grpc")]
+    grpc: Option<grpc::GrpcClient>,
+    /// Decision cache, possibly shared with other authorizers
+    cache: Arc<DecisionCache>,
+    /// Separates this authorizer's decisions in a shared cache
+    cache_namespace: String,
+    /// Checks in flight
+    flights: SingleFlight<Result<bool, AuthzError>>,
 }
 
 /// Builder for OpenFgaAuthorizer
@@ -83,6 +117,10 @@ pub struct OpenFgaAuthorizerBuilder {
     authorization_model_id: Option<String>,
     timeout: Option<Duration>,
     cache_ttl: Option<Duration>,
+    deny_cache_ttl: Option<Duration>,
+    object_mapping: OpenFgaObjectMapping,
+    transport: OpenFgaTransport,
+    cache: Option<Arc<DecisionCache>>,
 }
 
 /// OpenFGA check request
@@ -90,24 +128,71 @@ pub struct OpenFgaAuthorizerBuilder {
 struct CheckRequest {
     tuple_key: TupleKey,
     #[serde(skip_serializing_if = "Option::is_none")]
+    contextual_tuples: Option<ContextualTuples>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     authorization_model_id: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, PartialEq)]
 struct TupleKey {
     user: String,
     relation: String,
     object: String,
 }
 
+/// Tuples considered only for the duration of a check
+#[derive(Debug, Clone, Serialize, PartialEq)]
+struct ContextualTuples {
+    tuple_keys: Vec<TupleKey>,
+}
+
 impl TupleKey {
-    /// Create a tuple key from an authorization request
-    fn from_request(request: &AuthzRequest) -> Self {
-        Self {
+    /// Create the check tuple and contextual tuples for a request
+    fn from_request(
+        request: &AuthzRequest,
+        mapping: OpenFgaObjectMapping,
+    ) -> (Self, Option<ContextualTuples>) {
+        let tuple = |object: String| Self {
             user: format!("user:{}", request.subject),
             relation: OpenFgaAuthorizer::action_to_relation(&request.action).to_string(),
-            object: format!("bucket:{}", request.resource),
+            object,
+        };
+        if mapping == OpenFgaObjectMapping::Bucket {
+            return (tuple(format!("bucket:{}", request.resource)), None);
         }
+
+        let resource = request
+            .resource
+            .strip_prefix("bucket/")
+            .unwrap_or(&request.resource);
+        let (bucket, key) = resource.split_once('/').unwrap_or((resource, ""));
+
+        // Hierarchy from the object up: folder:b/x/y/ -> folder:b/x/ -> folder:b/ -> bucket:b
+        let mut chain = Vec::new();
+        if mapping == OpenFgaObjectMapping::Object {
+            chain.push(format!("object:{}/{}", bucket, key));
+        }
+        let mut prefix = key.rfind('/').map_or("", |i| &key[..=i]);
+        loop {
+            chain.push(format!("folder:{}/{}", bucket, prefix));
+            if prefix.is_empty() {
+                break;
+            }
+            let parent = &prefix[..prefix.len() - 1];
+            prefix = parent.rfind('/').map_or("", |i| &parent[..=i]);
+        }
+        chain.push(format!("bucket:{}", bucket));
+
+        let tuple_keys = chain
+            .windows(2)
+            .map(|pair| Self {
+                user: pair[1].clone(),
+                relation: PARENT_RELATION.to_string(),
+                object: pair[0].clone(),
+            })
+            .collect();
+        let target = chain[0].clone();
+        (tuple(target), Some(ContextualTuples { tuple_keys }))
     }
 }
 
@@ -128,6 +213,8 @@ struct BatchCheckRequest {
 #[derive(Debug, Serialize)]
 struct BatchCheckItem {
     tuple_key: TupleKey,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    contextual_tuples: Option<ContextualTuples>,
 }
 
 /// OpenFGA batch check response
@@ -172,7 +259,34 @@ impl OpenFgaAuthorizerBuilder {
         self
     }
 
+    /// Set the cache TTL of denials
+    pub fn deny_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.deny_cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Set the object checked for an upload
+    pub fn object_mapping(mut self, mapping: OpenFgaObjectMapping) -> Self {
+        self.object_mapping = mapping;
+        self
+    }
+
+    /// Set the API checks are sent over
+    pub fn transport(mut self, transport: OpenFgaTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Store decisions in a shared cache instead of a private one
+    pub fn decision_cache(mut self, cache: Arc<DecisionCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
     /// Build the OpenFgaAuthorizer
+    ///
+    /// Fails for [`OpenFgaTransport::Grpc`] when built without the
+    /// `openfga-grpc` feature.
     pub fn build(self) -> Result<OpenFgaAuthorizer, AuthzError> {
         let url = self
             .url
@@ -187,25 +301,52 @@ impl OpenFgaAuthorizerBuilder {
             authorization_model_id: self.authorization_model_id,
             timeout: self.timeout,
             cache_ttl: self.cache_ttl,
+            deny_cache_ttl: self.deny_cache_ttl,
+            object_mapping: self.object_mapping,
+            transport: self.transport,
         };
+        check_transport(config.transport)?;
 
-        Ok(OpenFgaAuthorizer::new(config))
+        Ok(match self.cache {
+            Some(cache) => OpenFgaAuthorizer::with_cache(config, cache),
+            None => OpenFgaAuthorizer::new(config),
+        })
     }
 }
 
 impl OpenFgaAuthorizer {
-    /// Create a new OpenFGA authorizer
+    /// Create a new OpenFGA authorizer with its own decision cache
     pub fn new(config: OpenFgaConfig) -> Self {
+        Self::with_cache(config, Arc::new(DecisionCache::default()))
+    }
+
+    /// Create a new OpenFGA authorizer storing decisions in `cache`
+    pub fn with_cache(config: OpenFgaConfig, cache: Arc<DecisionCache>) -> Self {
         let timeout = config.timeout.unwrap_or(DEFAULT_TIMEOUT);
         let client = reqwest::Client::builder()
             .timeout(timeout)
             .build()
             .expect("Failed to build HTTP client");
+        let cache_namespace = format!(
+            "openfga:{}/{}/{}/{:?}",
+            config.url,
+            config.store_id,
+            config.authorization_model_id.as_deref().unwrap_or(""),
+            config.object_mapping
+        );
+
+        #[cfg(feature = "openfga-grpc")]
+        let grpc = (config.transport == OpenFgaTransport::Grpc)
+            .then(|| grpc::GrpcClient::new(&config.url, timeout));
 
         Self {
             config,
             client,
-            cache: Arc::new(RwLock::new(HashMap::new())),
+            #[cfg(feature = "openfga-grpc")]
+            grpc,
+            cache,
+            cache_namespace,
+            flights: SingleFlight::new(),
         }
     }
 
@@ -224,72 +365,34 @@ impl OpenFgaAuthorizer {
         }
     }
 
-    /// Generate a cache key from the request
-    fn cache_key(request: &AuthzRequest) -> String {
-        use std::hash::{Hash, Hasher};
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        request.subject.hash(&mut hasher);
-        request.action.hash(&mut hasher);
-        request.resource.hash(&mut hasher);
-        format!("{:x}", hasher.finish())
-    }
-
     /// Check cache for a decision
-    async fn check_cache(&self, key: &str) -> Option<bool> {
-        let cache_ttl = self.config.cache_ttl?;
-        let cache = self.cache.read().await;
-        if let Some(cached) = cache.get(key) {
-            if cached.cached_at.elapsed() < cache_ttl {
-                return Some(cached.allowed);
-            }
-        }
-        None
+    fn check_cache(&self, key: &DecisionKey) -> Option<bool> {
+        self.config.cache_ttl.or(self.config.deny_cache_ttl)?;
+        self.cache.get(key)
     }
 
-    /// Store a decision in the cache
-    async fn store_cache(&self, key: String, allowed: bool) {
-        if self.config.cache_ttl.is_some() {
-            let mut cache = self.cache.write().await;
-
-            // Evict expired entries if cache is getting large
-            if cache.len() >= MAX_CACHE_SIZE {
-                let cache_ttl = self.config.cache_ttl.unwrap();
-                cache.retain(|_, v| v.cached_at.elapsed() < cache_ttl);
-            }
-
-            // If still too large after cleanup, remove oldest entries
-            if cache.len() >= MAX_CACHE_SIZE {
-                let to_remove = MAX_CACHE_SIZE / 10;
-                let mut entries: Vec<_> = cache
-                    .iter()
-                    .map(|(k, v)| (k.clone(), v.cached_at))
-                    .collect();
-                entries.sort_by_key(|(_, t)| *t);
-                for (key, _) in entries.into_iter().take(to_remove) {
-                    cache.remove(&key);
-                }
-            }
-
-            cache.insert(
-                key,
-                CachedDecision {
-                    allowed,
-                    cached_at: Instant::now(),
-                },
-            );
+    /// Store a decision in the cache, for the TTL of its outcome
+    fn store_cache(&self, key: DecisionKey, subject: &str, allowed: bool) {
+        let ttl = if allowed {
+            self.config.cache_ttl
+        } else {
+            self.config.deny_cache_ttl.or(self.config.cache_ttl)
+        };
+        if let Some(ttl) = ttl.filter(|ttl| !ttl.is_zero()) {
+            self.cache.insert(key, subject, allowed, ttl);
         }
     }
 
     /// Clear all cached authorization decisions
+    ///
+    /// When the cache is shared this also drops other authorizers' decisions.
     pub async fn clear_cache(&self) {
-        let mut cache = self.cache.write().await;
-        cache.clear();
+        self.cache.clear();
     }
 
     /// Get the current cache size
     pub async fn cache_size(&self) -> usize {
-        let cache = self.cache.read().await;
-        cache.len()
+        self.cache.len()
     }
 
     /// Perform a batch check for multiple authorization requests
@@ -315,8 +418,13 @@ impl OpenFgaAuthorizer {
 
         let checks: Vec<BatchCheckItem> = requests
             .iter()
-            .map(|r| BatchCheckItem {
-                tuple_key: TupleKey::from_request(r),
+            .map(|r| {
+                let (tuple_key, contextual_tuples) =
+                    TupleKey::from_request(r, self.config.object_mapping);
+                BatchCheckItem {
+                    tuple_key,
+                    contextual_tuples,
+                }
             })
             .collect();
 
@@ -351,6 +459,146 @@ impl OpenFgaAuthorizer {
             .map(|r| r.allowed)
             .collect())
     }
+
+    /// Check `request` over the configured transport and cache the decision
+    async fn check(
+        &self,
+        cache_key: DecisionKey,
+        request: &AuthzRequest,
+    ) -> Result<bool, AuthzError> {
+        let (tuple_key, contextual_tuples) =
+            TupleKey::from_request(request, self.config.object_mapping);
+        let check_request = CheckRequest {
+            tuple_key,
+            contextual_tuples,
+            authorization_model_id: self.config.authorization_model_id.clone(),
+        };
+
+        let allowed = match self.config.transport {
+            OpenFgaTransport::Http => self.check_http(&check_request).await?,
+            OpenFgaTransport::Grpc => self.check_grpc(&check_request).await?,
+        };
+
+        // Store in cache
+        self.store_cache(cache_key, &request.subject, allowed);
+
+        Ok(allowed)
+    }
+
+    /// Run a check over the HTTP API
+    async fn check_http(&self, check_request: &CheckRequest) -> Result<bool, AuthzError> {
+        let url = format!("{}/stores/{}/check", self.config.url, self.config.store_id);
+
+        let response = self
+            .client
+            .post(&url)
+            .json(check_request)
+            .send()
+            .await
+            .map_err(|e| AuthzError::BackendError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(AuthzError::BackendError(format!(
+                "OpenFGA returned status {}",
+                response.status()
+            )));
+        }
+
+        let check_response: CheckResponse = response
+            .json()
+            .await
+            .map_err(|e| AuthzError::BackendError(e.to_string()))?;
+        Ok(check_response.allowed)
+    }
+
+    /// Run a check over the gRPC API
+    #[cfg(feature = "openfga-grpc")]
+    async fn check_grpc(&self, check_request: &CheckRequest) -> Result<bool, AuthzError> {
+        match self.grpc {
+            Some(ref grpc) => grpc.check(&self.config.store_id, check_request).await,
+            None => Err(AuthzError::ConfigError(
+                "OpenFGA gRPC client not initialized".into(),
+            )),
+        }
+    }
+
+    #[cfg(not(feature = "openfga-grpc"))]
+    async fn check_grpc(&self, _check_request: &CheckRequest) -> Result<bool, AuthzError> {
+        Err(AuthzError::ConfigError(GRPC_UNAVAILABLE.into()))
+    }
+
+    /// Whether the HTTP API resource at `path` exists
+    ///
+    /// OpenFGA answers 404 for unknown IDs and 400 for malformed ones.
+    async fn exists_http(&self, path: &str) -> Result<bool, AuthzError> {
+        let url = format!("{}{}", self.config.url, path);
+        let response = self.client.get(&url).send().await.map_err(|e| {
+            AuthzError::BackendError(format!(
+                "OpenFGA at {} is unreachable: {}",
+                self.config.url, e
+            ))
+        })?;
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::BAD_REQUEST => Ok(false),
+            status => Err(AuthzError::BackendError(format!(
+                "OpenFGA returned status {} for {}",
+                status, url
+            ))),
+        }
+    }
+
+    /// Whether the store exists, and the model if one is pinned, as
+    /// `(store, model)`
+    async fn lookup(&self) -> Result<(bool, bool), AuthzError> {
+        let store_id = &self.config.store_id;
+        let model_id = self.config.authorization_model_id.as_deref();
+        match self.config.transport {
+            OpenFgaTransport::Http => {
+                let store = format!("/stores/{}", store_id);
+                if !self.exists_http(&store).await? {
+                    return Ok((false, false));
+                }
+                let model = match model_id {
+                    Some(id) => {
+                        self.exists_http(&format!("{}/authorization-models/{}", store, id))
+                            .await?
+                    }
+                    None => true,
+                };
+                Ok((true, model))
+            }
+            #[cfg(feature = "openfga-grpc")]
+            OpenFgaTransport::Grpc => {
+                let Some(ref grpc) = self.grpc else {
+                    return Err(AuthzError::ConfigError(
+                        "OpenFGA gRPC client not initialized".into(),
+                    ));
+                };
+                if !grpc.store_exists(store_id).await? {
+                    return Ok((false, false));
+                }
+                let model = match model_id {
+                    Some(id) => grpc.model_exists(store_id, id).await?,
+                    None => true,
+                };
+                Ok((true, model))
+            }
+            #[cfg(not(feature = "openfga-grpc"))]
+            OpenFgaTransport::Grpc => Err(AuthzError::ConfigError(GRPC_UNAVAILABLE.into())),
+        }
+    }
+}
+
+/// Ensure `transport` is available in this build
+///
+/// A configured gRPC transport without the `openfga-grpc` feature is an
+/// error rather than a silent fallback to HTTP.
+pub fn check_transport(transport: OpenFgaTransport) -> Result<(), AuthzError> {
+    if transport == OpenFgaTransport::Grpc && cfg!(not(feature = "openfga-grpc")) {
+        return Err(AuthzError::ConfigError(GRPC_UNAVAILABLE.into()));
+    }
+    Ok(())
 }
 
 #[async_trait]
@@ -368,8 +616,9 @@ impl Authorizer for OpenFgaAuthorizer {
     ))]
     async fn authorize(&self, request: &AuthzRequest) -> Result<bool, AuthzError> {
         // Check cache first
-        let cache_key = Self::cache_key(request);
-        if let Some(cached_decision) = self.check_cache(&cache_key).await {
+        // Tuples don't depend on the request context
+        let cache_key = DecisionKey::ignoring_context(&self.cache_namespace, request);
+        if let Some(cached_decision) = self.check_cache(&cache_key) {
             #[cfg(feature = "tracing")]
             tracing::debug!(
                 decision = %if cached_decision { "allow" } else { "deny" },
@@ -378,43 +627,38 @@ impl Authorizer for OpenFgaAuthorizer {
             return Ok(cached_decision);
         }
 
-        let url = format!("{}/stores/{}/check", self.config.url, self.config.store_id);
-
-        let check_request = CheckRequest {
-            tuple_key: TupleKey::from_request(request),
-            authorization_model_id: self.config.authorization_model_id.clone(),
-        };
-
-        let response = self
-            .client
-            .post(&url)
-            .json(&check_request)
-            .send()
-            .await
-            .map_err(|e| AuthzError::BackendError(e.to_string()))?;
-
-        if !response.status().is_success() {
-            return Err(AuthzError::BackendError(format!(
-                "OpenFGA returned status {}",
-                response.status()
-            )));
+        let (result, shared) = self
+            .flights
+            .run(cache_key.clone(), self.check(cache_key, request))
+            .await;
+        if shared {
+            metrics::record_authz_coalesced("openfga");
         }
-
-        let check_response: CheckResponse = response
-            .json()
-            .await
-            .map_err(|e| AuthzError::BackendError(e.to_string()))?;
-
-        // Store in cache
-        self.store_cache(cache_key, check_response.allowed).await;
+        let allowed = result?;
 
         #[cfg(feature = "tracing")]
         tracing::info!(
-            decision = %if check_response.allowed { "allow" } else { "deny" },
+            decision = %if allowed { "allow" } else { "deny" },
             "OpenFGA authorization decision"
         );
 
-        Ok(check_response.allowed)
+        Ok(allowed)
+    }
+
+    /// The store, and the authorization model if one is pinned, must exist
+    async fn health_check(&self) -> Result<(), AuthzError> {
+        match self.lookup().await? {
+            (false, _) => Err(AuthzError::ConfigError(format!(
+                "OpenFGA store '{}' does not exist on {}; check authz.store_id",
+                self.config.store_id, self.config.url
+            ))),
+            (true, false) => Err(AuthzError::ConfigError(format!(
+                "OpenFGA authorization model '{}' does not exist in store '{}'; check authz.authorization_model_id",
+                self.config.authorization_model_id.as_deref().unwrap_or_default(),
+                self.config.store_id
+            ))),
+            (true, true) => Ok(()),
+        }
     }
 }
 
@@ -430,6 +674,44 @@ mod tests {
         assert_eq!(OpenFgaAuthorizer::action_to_relation("unknown"), "viewer");
     }
 
+    #[test]
+    fn test_object_mapping() {
+        let request = AuthzRequest {
+            subject: "alice".into(),
+            action: "upload".into(),
+            resource: "bucket/docs/team/q1/report.csv".into(),
+            context: Default::default(),
+        };
+        let parent = |user: &str, object: &str| TupleKey {
+            user: user.into(),
+            relation: "parent".into(),
+            object: object.into(),
+        };
+
+        let (tuple, contextual) = TupleKey::from_request(&request, OpenFgaObjectMapping::Bucket);
+        assert_eq!(tuple.object, "bucket:bucket/docs/team/q1/report.csv");
+        assert!(contextual.is_none());
+
+        let (tuple, contextual) = TupleKey::from_request(&request, OpenFgaObjectMapping::Folder);
+        assert_eq!(tuple.object, "folder:docs/team/q1/");
+        assert_eq!(
+            contextual.unwrap().tuple_keys,
+            vec![
+                parent("folder:docs/team/", "folder:docs/team/q1/"),
+                parent("folder:docs/", "folder:docs/team/"),
+                parent("bucket:docs", "folder:docs/"),
+            ]
+        );
+
+        let (tuple, contextual) = TupleKey::from_request(&request, OpenFgaObjectMapping::Object);
+        assert_eq!(tuple.object, "object:docs/team/q1/report.csv");
+        assert_eq!(tuple.relation, "writer");
+        assert_eq!(
+            contextual.unwrap().tuple_keys[0],
+            parent("folder:docs/team/q1/", "object:docs/team/q1/report.csv")
+        );
+    }
+
     #[test]
     fn test_openfga_config() {
         let config = OpenFgaConfig {
@@ -438,6 +720,9 @@ mod tests {
             authorization_model_id: Some("model456".into()),
             timeout: None,
             cache_ttl: None,
+            deny_cache_ttl: None,
+            object_mapping: Default::default(),
+            transport: Default::default(),
         };
         assert_eq!(config.store_id, "store123");
     }
@@ -450,6 +735,9 @@ mod tests {
             authorization_model_id: Some("model456".into()),
             timeout: Some(Duration::from_secs(10)),
             cache_ttl: Some(Duration::from_secs(60)),
+            deny_cache_ttl: None,
+            object_mapping: Default::default(),
+            transport: Default::default(),
         };
         assert_eq!(config.timeout, Some(Duration::from_secs(10)));
         assert_eq!(config.cache_ttl, Some(Duration::from_secs(60)));
@@ -473,6 +761,16 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_grpc_transport_requires_feature() {
+        let result = OpenFgaAuthorizer::builder()
+            .url("http://localhost:8081")
+            .store_id("store123")
+            .transport(OpenFgaTransport::Grpc)
+            .build();
+        assert_eq!(result.is_ok(), cfg!(feature = "openfga-grpc"));
+    }
+
     #[test]
     fn test_builder_missing_store_id() {
         let result = OpenFgaAuthorizer::builder()
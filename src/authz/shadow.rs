@@ -0,0 +1,150 @@
+//! Shadow authorization
+//!
+//! Evaluates a second authorizer (`authz_shadow`) alongside the enforced
+//! one and only logs and meters its decision, so a new OPA policy or
+//! OpenFGA model can be validated against production traffic before
+//! cutting over. The shadow runs in the background once the enforced
+//! decision is made, adding no latency; its errors never affect uploads.
+//!
+//! Outcomes are counted in `mizuchi_authz_shadow_decisions_total`:
+//! `agree`, `would_allow` (the shadow allows what is denied), `would_deny`
+//! (the shadow denies what is allowed) and `error` (either side failed).
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::authz::shadow::ShadowAuthorizer;
+//! use mizuchi_uploadr::authz::{AllowAllAuthorizer, DenyAllAuthorizer};
+//! use std::sync::Arc;
+//!
+//! // Enforce the current policy, evaluate the candidate in the shadow
+//! let authorizer = ShadowAuthorizer::new(
+//!     Arc::new(AllowAllAuthorizer),
+//!     Arc::new(DenyAllAuthorizer),
+//!     "uploads",
+//! );
+//! ```
+
+use super::{Authorizer, AuthzError, AuthzRequest};
+use crate::metrics;
+use async_trait::async_trait;
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+/// Enforces one authorizer while evaluating another in the shadow
+pub struct ShadowAuthorizer {
+    enforced: Arc<dyn Authorizer>,
+    shadow: Arc<dyn Authorizer>,
+    bucket: String,
+}
+
+impl ShadowAuthorizer {
+    /// Enforce `enforced`, comparing `shadow` against it
+    pub fn new(enforced: Arc<dyn Authorizer>, shadow: Arc<dyn Authorizer>, bucket: &str) -> Self {
+        Self {
+            enforced,
+            shadow,
+            bucket: bucket.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Authorizer for ShadowAuthorizer {
+    async fn authorize(&self, request: &AuthzRequest) -> Result<bool, AuthzError> {
+        let result = self.enforced.authorize(request).await;
+
+        let enforced = result.as_ref().ok().copied();
+        let shadow = Arc::clone(&self.shadow);
+        let bucket = self.bucket.clone();
+        let request = request.clone();
+        tokio::spawn(async move {
+            let shadowed = shadow.authorize(&request).await;
+            let outcome = compare(enforced, &shadowed);
+            match (outcome, &shadowed) {
+                ("agree", _) => debug!(
+                    "Shadow authorizer for bucket {} agrees on {} {}",
+                    bucket, request.subject, request.resource
+                ),
+                (_, Err(e)) => warn!("Shadow authorizer for bucket {} failed: {}", bucket, e),
+                _ => info!(
+                    "Shadow authorizer for bucket {} {} {} {} (enforced: {:?})",
+                    bucket,
+                    outcome.replace('_', " "),
+                    request.subject,
+                    request.resource,
+                    enforced
+                ),
+            }
+            metrics::record_authz_shadow(&bucket, outcome);
+        });
+
+        result
+    }
+
+    /// Health of the enforced authorizer; a failing shadow is only logged
+    async fn health_check(&self) -> Result<(), AuthzError> {
+        if let Err(e) = self.shadow.health_check().await {
+            warn!(
+                "Shadow authorizer for bucket {} is unhealthy: {}",
+                self.bucket, e
+            );
+        }
+        self.enforced.health_check().await
+    }
+}
+
+/// Outcome label comparing the enforced decision with the shadow's
+fn compare(enforced: Option<bool>, shadowed: &Result<bool, AuthzError>) -> &'static str {
+    match (enforced, shadowed) {
+        (Some(a), Ok(b)) if a == *b => "agree",
+        (Some(false), Ok(true)) => "would_allow",
+        (Some(true), Ok(false)) => "would_deny",
+        _ => "error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authz::{AllowAllAuthorizer, DenyAllAuthorizer};
+    use std::collections::HashMap;
+
+    fn request() -> AuthzRequest {
+        AuthzRequest {
+            subject: "alice".into(),
+            action: "upload".into(),
+            resource: "uploads/a.txt".into(),
+            context: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_enforced_decision_returned() {
+        let allow = ShadowAuthorizer::new(
+            Arc::new(AllowAllAuthorizer),
+            Arc::new(DenyAllAuthorizer),
+            "uploads",
+        );
+        let deny = ShadowAuthorizer::new(
+            Arc::new(DenyAllAuthorizer),
+            Arc::new(AllowAllAuthorizer),
+            "uploads",
+        );
+
+        assert!(allow.authorize(&request()).await.unwrap());
+        assert!(!deny.authorize(&request()).await.unwrap());
+    }
+
+    #[test]
+    fn test_compare() {
+        let failed = || Err(AuthzError::BackendError("unreachable".into()));
+
+        assert_eq!(compare(Some(true), &Ok(true)), "agree");
+        assert_eq!(compare(Some(false), &Ok(false)), "agree");
+        assert_eq!(compare(Some(false), &Ok(true)), "would_allow");
+        assert_eq!(compare(Some(true), &Ok(false)), "would_deny");
+        assert_eq!(compare(Some(true), &failed()), "error");
+        assert_eq!(compare(None, &Ok(true)), "error");
+    }
+}
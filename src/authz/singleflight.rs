@@ -0,0 +1,206 @@
+//! Single-flight deduplication of authorization checks
+//!
+//! A burst of uploads from one client asks the backend the same question
+//! many times at once. [`SingleFlight`] lets the first check of a
+//! [`DecisionKey`] call the backend while identical checks arriving before
+//! it completes wait for its result, so the burst costs one backend call.
+//!
+//! If the caller making the call goes away (its client disconnected) the
+//! waiting checks make the call themselves rather than fail.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::authz::cache::DecisionKey;
+//! use mizuchi_uploadr::authz::singleflight::SingleFlight;
+//! use mizuchi_uploadr::authz::AuthzRequest;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let flights = SingleFlight::new();
+//! let request = AuthzRequest {
+//!     subject: "alice".into(),
+//!     action: "upload".into(),
+//!     resource: "bucket/uploads/a.txt".into(),
+//!     context: Default::default(),
+//! };
+//! let key = DecisionKey::new("opa:allow", &request);
+//!
+//! let (allowed, shared) = flights.run(key, async { true }).await;
+//! assert!(allowed);
+//! assert!(!shared);
+//! # }
+//! ```
+
+use super::cache::DecisionKey;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::future::Future;
+use tokio::sync::watch;
+
+/// Checks in flight, by decision key
+pub struct SingleFlight<T> {
+    flights: Mutex<HashMap<DecisionKey, watch::Receiver<Option<T>>>>,
+}
+
+impl<T: Clone> Default for SingleFlight<T> {
+    fn default() -> Self {
+        Self {
+            flights: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+/// Ends a flight when its caller finishes or is cancelled
+struct Landing<'a, T> {
+    flights: &'a Mutex<HashMap<DecisionKey, watch::Receiver<Option<T>>>>,
+    key: DecisionKey,
+}
+
+impl<T> Drop for Landing<'_, T> {
+    fn drop(&mut self) {
+        self.flights.lock().remove(&self.key);
+    }
+}
+
+impl<T: Clone> SingleFlight<T> {
+    /// No checks in flight
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `call`, or wait for the call already in flight for `key`
+    ///
+    /// Returns the result and whether it came from another caller's call.
+    pub async fn run<F>(&self, key: DecisionKey, call: F) -> (T, bool)
+    where
+        F: Future<Output = T>,
+    {
+        let leader = {
+            let mut flights = self.flights.lock();
+            match flights.get(&key) {
+                Some(receiver) => Err(receiver.clone()),
+                None => {
+                    let (sender, receiver) = watch::channel(None);
+                    flights.insert(key.clone(), receiver);
+                    Ok(sender)
+                }
+            }
+        };
+
+        match leader {
+            Ok(sender) => {
+                let _landing = Landing {
+                    flights: &self.flights,
+                    key,
+                };
+                let value = call.await;
+                sender.send_replace(Some(value.clone()));
+                (value, false)
+            }
+            Err(mut receiver) => {
+                let shared = receiver
+                    .wait_for(Option::is_some)
+                    .await
+                    .ok()
+                    .and_then(|value| value.clone());
+                match shared {
+                    Some(value) => (value, true),
+                    // The caller making the call was cancelled
+                    None => (call.await, false),
+                }
+            }
+        }
+    }
+
+    /// Number of checks in flight
+    pub fn len(&self) -> usize {
+        self.flights.lock().len()
+    }
+
+    /// Whether no check is in flight
+    pub fn is_empty(&self) -> bool {
+        self.flights.lock().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authz::AuthzRequest;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn key(subject: &str) -> DecisionKey {
+        DecisionKey::new(
+            "test",
+            &AuthzRequest {
+                subject: subject.into(),
+                action: "upload".into(),
+                resource: "bucket/uploads/a.txt".into(),
+                context: Default::default(),
+            },
+        )
+    }
+
+    /// A slow check counting its calls
+    async fn slow_check(calls: &AtomicUsize, allowed: bool) -> bool {
+        calls.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        allowed
+    }
+
+    #[tokio::test]
+    async fn test_identical_checks_are_coalesced() {
+        let flights = SingleFlight::new();
+        let calls = AtomicUsize::new(0);
+
+        let results = futures::future::join_all(
+            (0..10).map(|_| flights.run(key("alice"), slow_check(&calls, true))),
+        )
+        .await;
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(results.iter().all(|(allowed, _)| *allowed));
+        assert_eq!(results.iter().filter(|(_, shared)| *shared).count(), 9);
+        assert!(flights.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_different_checks_are_not_coalesced() {
+        let flights = SingleFlight::new();
+        let calls = AtomicUsize::new(0);
+
+        let (alice, bob) = tokio::join!(
+            flights.run(key("alice"), slow_check(&calls, true)),
+            flights.run(key("bob"), slow_check(&calls, false)),
+        );
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(alice, (true, false));
+        assert_eq!(bob, (false, false));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_leader_hands_over() {
+        let flights = Arc::new(SingleFlight::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let leader = tokio::spawn({
+            let (flights, calls) = (Arc::clone(&flights), Arc::clone(&calls));
+            async move { flights.run(key("alice"), slow_check(&calls, true)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let follower = tokio::spawn({
+            let (flights, calls) = (Arc::clone(&flights), Arc::clone(&calls));
+            async move { flights.run(key("alice"), slow_check(&calls, true)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        leader.abort();
+
+        assert_eq!(follower.await.unwrap(), (true, false));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(flights.is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+//! Static policy authorization
+//!
+//! Evaluates allow/deny rules from the bucket configuration in-process, for
+//! deployments that don't want to run OPA or OpenFGA just to say "team-a can
+//! write to `team-a/*`". Rules are checked in order and the first match
+//! decides; the policy default applies when nothing matches.
+//!
+//! Resources use the `bucket/{bucket}/{key}` form shared by all authorizers.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::authz::static_policy::StaticPolicyAuthorizer;
+//! use mizuchi_uploadr::config::StaticPolicyConfig;
+//!
+//! let config: StaticPolicyConfig = serde_yaml::from_str(
+//!     r#"
+//! rules:
+//!   - subjects: ["team-a-*"]
+//!     key_prefix: "team-a/"
+//!     actions: ["upload"]
+//!     effect: allow
+//! "#,
+//! )
+//! .unwrap();
+//! let authorizer = StaticPolicyAuthorizer::new(&config);
+//! ```
+
+use super::{Authorizer, AuthzError, AuthzRequest};
+use crate::config::{PolicyEffect, PolicyRule, StaticPolicyConfig};
+use async_trait::async_trait;
+
+/// In-process authorizer for a static rule list
+pub struct StaticPolicyAuthorizer {
+    rules: Vec<PolicyRule>,
+    default: PolicyEffect,
+}
+
+impl StaticPolicyAuthorizer {
+    /// Create an authorizer from bucket configuration
+    pub fn new(config: &StaticPolicyConfig) -> Self {
+        Self {
+            rules: config.rules.clone(),
+            default: config.default,
+        }
+    }
+
+    /// Effect of the first rule matching the request, or the default
+    pub fn evaluate(&self, request: &AuthzRequest) -> PolicyEffect {
+        let (bucket, key) = split_resource(&request.resource);
+        self.rules
+            .iter()
+            .find(|rule| rule_matches(rule, request, bucket, key))
+            .map_or(self.default, |rule| rule.effect)
+    }
+}
+
+#[async_trait]
+impl Authorizer for StaticPolicyAuthorizer {
+    #[cfg_attr(feature = "tracing", tracing::instrument(
+        name = "authz.static",
+        skip(self, request),
+        fields(
+            authz.method = "static",
+            authz.action = %request.action,
+            otel.kind = "internal"
+        ),
+        err
+    ))]
+    async fn authorize(&self, request: &AuthzRequest) -> Result<bool, AuthzError> {
+        Ok(self.evaluate(request) == PolicyEffect::Allow)
+    }
+}
+
+/// Split a `bucket/{bucket}/{key}` resource into bucket and key
+fn split_resource(resource: &str) -> (&str, &str) {
+    let resource = resource.strip_prefix("bucket/").unwrap_or(resource);
+    resource.split_once('/').unwrap_or((resource, ""))
+}
+
+fn rule_matches(rule: &PolicyRule, request: &AuthzRequest, bucket: &str, key: &str) -> bool {
+    let any = |patterns: &[String], value: &str| {
+        patterns.is_empty() || patterns.iter().any(|p| glob_match(p, value))
+    };
+
+    let key_matches = rule.key_prefix.as_ref().is_none_or(|prefix| {
+        // A subject containing '/' could reach into another subject's prefix
+        if prefix.contains("{subject}") && request.subject.contains('/') {
+            return false;
+        }
+        let prefix = prefix.replace("{subject}", &request.subject);
+        !key.split('/').any(|segment| segment == "..")
+            && key.starts_with(prefix.trim_start_matches('/'))
+    });
+
+    any(&rule.subjects, &request.subject)
+        && any(&rule.buckets, bucket)
+        && (rule.actions.is_empty() || rule.actions.contains(&request.action))
+        && key_matches
+}
+
+/// Match `value` against a pattern where `*` matches any run of characters
+fn glob_match(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = value.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<_> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard: exact match
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn request(subject: &str, resource: &str) -> AuthzRequest {
+        AuthzRequest {
+            subject: subject.into(),
+            action: "upload".into(),
+            resource: resource.into(),
+            context: HashMap::new(),
+        }
+    }
+
+    fn policy(yaml: &str) -> StaticPolicyAuthorizer {
+        StaticPolicyAuthorizer::new(&serde_yaml::from_str(yaml).unwrap())
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("team-a-*", "team-a-ci"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*-bot", "release-bot"));
+        assert!(glob_match("svc-*-prod", "svc-upload-prod"));
+        assert!(!glob_match("svc-*-prod", "svc-upload-dev"));
+        assert!(!glob_match("alice", "alice2"));
+        assert!(!glob_match("a*a", "a"));
+    }
+
+    #[test]
+    fn test_first_match_wins() {
+        let policy = policy(
+            r#"
+rules:
+  - subjects: ["team-a-intern"]
+    effect: deny
+  - subjects: ["team-a-*"]
+    key_prefix: "team-a/"
+    effect: allow
+"#,
+        );
+        let allow = |subject, resource| policy.evaluate(&request(subject, resource));
+        assert_eq!(
+            allow("team-a-ci", "bucket/uploads/team-a/x"),
+            PolicyEffect::Allow
+        );
+        assert_eq!(
+            allow("team-a-intern", "bucket/uploads/team-a/x"),
+            PolicyEffect::Deny
+        );
+        assert_eq!(
+            allow("team-a-ci", "bucket/uploads/team-b/x"),
+            PolicyEffect::Deny
+        );
+        assert_eq!(
+            allow("team-a-ci", "bucket/uploads/team-a/../team-b/x"),
+            PolicyEffect::Deny
+        );
+    }
+
+    #[test]
+    fn test_subject_placeholder_and_bucket() {
+        let policy = policy(
+            r#"
+rules:
+  - buckets: ["home-*"]
+    key_prefix: "users/{subject}/"
+    effect: allow
+"#,
+        );
+        let allow = |subject, resource| policy.evaluate(&request(subject, resource));
+        assert_eq!(
+            allow("bob", "bucket/home-eu/users/bob/a"),
+            PolicyEffect::Allow
+        );
+        assert_eq!(
+            allow("bob", "bucket/home-eu/users/eve/a"),
+            PolicyEffect::Deny
+        );
+        assert_eq!(
+            allow("bob", "bucket/shared/users/bob/a"),
+            PolicyEffect::Deny
+        );
+        assert_eq!(
+            allow("bob/x", "bucket/home-eu/users/bob/x/a"),
+            PolicyEffect::Deny
+        );
+    }
+
+    #[test]
+    fn test_default_effect() {
+        let policy = policy("default: allow\nrules: []");
+        assert_eq!(
+            policy.evaluate(&request("anyone", "bucket/b/k")),
+            PolicyEffect::Allow
+        );
+    }
+}
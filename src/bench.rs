@@ -0,0 +1,278 @@
+//! Upload load generator
+//!
+//! Drives PUTs of a fixed size at a deployment and reports throughput and
+//! latency percentiles, for `mizuchi-uploadr bench` and the end-to-end load
+//! tests. Every request uploads the same random payload under a new key.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use mizuchi_uploadr::bench::{self, BenchOptions};
+//!
+//! # async fn example() {
+//! let options = BenchOptions {
+//!     target: "http://localhost:8080/uploads".into(),
+//!     size: bench::parse_size("1M").unwrap(),
+//!     concurrency: 64,
+//!     requests: 1000,
+//!     ..Default::default()
+//! };
+//! let metrics = bench::run(&reqwest::Client::new(), &options).await;
+//! println!("{:.2} MB/s", metrics.throughput_mbps());
+//! # }
+//! ```
+
+use bytes::Bytes;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// What to upload and how hard
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    /// URL prefix objects are uploaded under (e.g. `http://host/uploads`)
+    pub target: String,
+    /// Payload size in bytes
+    pub size: usize,
+    /// Requests in flight at once
+    pub concurrency: usize,
+    /// Total number of requests
+    pub requests: usize,
+    /// Headers sent with every request (e.g. `Authorization`)
+    pub headers: Vec<(String, String)>,
+    /// First component of every object key
+    pub key_prefix: String,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self {
+            target: String::new(),
+            size: 1024 * 1024,
+            concurrency: 64,
+            requests: 1000,
+            headers: Vec::new(),
+            key_prefix: "bench".into(),
+        }
+    }
+}
+
+/// Performance metrics collected during a load run
+#[derive(Debug, Default)]
+pub struct LoadTestMetrics {
+    pub total_requests: u64,
+    pub successful_requests: u64,
+    pub failed_requests: u64,
+    pub total_bytes: u64,
+    pub total_duration_ms: u64,
+    /// Latency of every request in microseconds
+    pub latencies_us: Vec<u64>,
+}
+
+impl LoadTestMetrics {
+    /// Calculate throughput in MB/s
+    pub fn throughput_mbps(&self) -> f64 {
+        if self.total_duration_ms == 0 {
+            return 0.0;
+        }
+        let bytes_per_sec = (self.total_bytes as f64) / (self.total_duration_ms as f64 / 1000.0);
+        bytes_per_sec / (1024.0 * 1024.0)
+    }
+
+    /// Calculate requests per second
+    pub fn requests_per_sec(&self) -> f64 {
+        if self.total_duration_ms == 0 {
+            return 0.0;
+        }
+        (self.total_requests as f64) / (self.total_duration_ms as f64 / 1000.0)
+    }
+
+    /// Calculate p50 latency
+    pub fn p50_latency_ms(&self) -> f64 {
+        self.percentile(50)
+    }
+
+    /// Calculate p95 latency
+    pub fn p95_latency_ms(&self) -> f64 {
+        self.percentile(95)
+    }
+
+    /// Calculate p99 latency
+    pub fn p99_latency_ms(&self) -> f64 {
+        self.percentile(99)
+    }
+
+    /// Fastest request
+    pub fn min_latency_ms(&self) -> f64 {
+        self.latencies_us
+            .iter()
+            .min()
+            .map_or(0.0, |&us| us as f64 / 1000.0)
+    }
+
+    /// Slowest request
+    pub fn max_latency_ms(&self) -> f64 {
+        self.latencies_us
+            .iter()
+            .max()
+            .map_or(0.0, |&us| us as f64 / 1000.0)
+    }
+
+    fn percentile(&self, p: u8) -> f64 {
+        if self.latencies_us.is_empty() {
+            return 0.0;
+        }
+        let mut sorted = self.latencies_us.clone();
+        sorted.sort_unstable();
+        let idx = (sorted.len() as f64 * (p as f64 / 100.0)) as usize;
+        sorted[idx.min(sorted.len() - 1)] as f64 / 1000.0
+    }
+
+    /// Success rate as percentage
+    pub fn success_rate(&self) -> f64 {
+        if self.total_requests == 0 {
+            return 0.0;
+        }
+        (self.successful_requests as f64 / self.total_requests as f64) * 100.0
+    }
+}
+
+/// Upload `options.requests` objects with `options.concurrency` workers
+pub async fn run(client: &reqwest::Client, options: &BenchOptions) -> LoadTestMetrics {
+    let payload = random_payload(options.size);
+    let target = options.target.trim_end_matches('/').to_string();
+    let next = Arc::new(AtomicU64::new(0));
+    let successful = Arc::new(AtomicU64::new(0));
+    let latencies = Arc::new(parking_lot::Mutex::new(Vec::with_capacity(
+        options.requests,
+    )));
+    let run_id = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+
+    let start = Instant::now();
+    let mut handles = vec![];
+    for _ in 0..options.concurrency.max(1) {
+        let client = client.clone();
+        let payload = payload.clone();
+        let headers = options.headers.clone();
+        let url_prefix = format!("{}/{}-{}", target, options.key_prefix, run_id);
+        let requests = options.requests as u64;
+        let next = Arc::clone(&next);
+        let successful = Arc::clone(&successful);
+        let latencies = Arc::clone(&latencies);
+
+        handles.push(tokio::spawn(async move {
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= requests {
+                    break;
+                }
+                let mut request = client
+                    .put(format!("{}-{}.bin", url_prefix, i))
+                    .body(payload.clone());
+                for (name, value) in &headers {
+                    request = request.header(name, value);
+                }
+
+                let req_start = Instant::now();
+                let result = request.send().await;
+                let latency = req_start.elapsed().as_micros() as u64;
+                latencies.lock().push(latency);
+
+                if matches!(result, Ok(ref resp) if resp.status().is_success()) {
+                    successful.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }));
+    }
+    futures::future::join_all(handles).await;
+
+    let duration = start.elapsed();
+    let successful = successful.load(Ordering::Relaxed);
+    let latencies_us = std::mem::take(&mut *latencies.lock());
+    LoadTestMetrics {
+        total_requests: options.requests as u64,
+        successful_requests: successful,
+        failed_requests: options.requests as u64 - successful,
+        total_bytes: successful * options.size as u64,
+        total_duration_ms: duration.as_millis() as u64,
+        latencies_us,
+    }
+}
+
+/// Incompressible payload of `size` bytes
+pub fn random_payload(size: usize) -> Bytes {
+    // xorshift64*, seeded randomly: fast, and random enough to defeat compression
+    let mut state = RandomState::new().build_hasher().finish() | 1;
+    let mut data = Vec::with_capacity(size + 8);
+    while data.len() < size {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        data.extend_from_slice(&state.wrapping_mul(0x2545_F491_4F6C_DD1D).to_le_bytes());
+    }
+    data.truncate(size);
+    Bytes::from(data)
+}
+
+/// Parse a size such as `512`, `64K`, `1M` or `2GiB` (binary units)
+pub fn parse_size(size: &str) -> Result<usize, String> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: usize = number
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", size))?;
+    let unit = unit.trim().to_ascii_uppercase();
+    let multiplier: usize = match unit.trim_end_matches("IB").trim_end_matches('B') {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => return Err(format!("invalid size unit in '{}'", size)),
+    };
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("size '{}' is too large", size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("64K"), Ok(64 * 1024));
+        assert_eq!(parse_size("1M"), Ok(1024 * 1024));
+        assert_eq!(parse_size("1mb"), Ok(1024 * 1024));
+        assert_eq!(parse_size("2GiB"), Ok(2 << 30));
+        assert!(parse_size("").is_err());
+        assert!(parse_size("1T").is_err());
+        assert!(parse_size("M").is_err());
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let metrics = LoadTestMetrics {
+            latencies_us: (1..=100).map(|ms| ms * 1000).collect(),
+            ..Default::default()
+        };
+
+        assert_eq!(metrics.p50_latency_ms(), 51.0);
+        assert_eq!(metrics.p99_latency_ms(), 100.0);
+        assert_eq!(metrics.min_latency_ms(), 1.0);
+        assert_eq!(metrics.max_latency_ms(), 100.0);
+    }
+
+    #[test]
+    fn test_random_payload() {
+        let payload = random_payload(1000);
+
+        assert_eq!(payload.len(), 1000);
+        assert_ne!(payload, random_payload(1000));
+    }
+}
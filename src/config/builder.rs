@@ -0,0 +1,218 @@
+//! Programmatic configuration
+//!
+//! [`ConfigBuilder`] assembles a [`Config`] in code, for applications that
+//! embed the proxy and would rather not write YAML. Sections that are not
+//! set keep the defaults of an empty YAML section.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::config::{BucketConfig, Config, S3Config};
+//!
+//! let config = Config::builder()
+//!     .address("127.0.0.1:8080")
+//!     .add_bucket(BucketConfig::new(
+//!         "uploads",
+//!         "/uploads",
+//!         S3Config::new("my-bucket", "us-east-1"),
+//!     ))
+//!     .build()
+//!     .unwrap();
+//! assert_eq!(config.buckets.len(), 1);
+//! ```
+
+use super::{
+    AdminConfig, BucketConfig, Config, ConfigError, ListenerConfig, LoggingConfig, MetricsConfig,
+    NotificationsConfig, ServerConfig, StateConfig, TracingConfig,
+};
+
+/// Address the server listens on unless [`ConfigBuilder::address`] is set
+const DEFAULT_ADDRESS: &str = "0.0.0.0:8080";
+
+/// Builder for [`Config`], created by [`Config::builder`]
+#[derive(Debug, Clone)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        Self {
+            config: Config {
+                server: ServerConfig {
+                    address: DEFAULT_ADDRESS.to_string(),
+                    zero_copy: Default::default(),
+                    shutdown: Default::default(),
+                    tls: None,
+                    listeners: Vec::new(),
+                    tcp: Default::default(),
+                    buffer_pool: Default::default(),
+                    http: Default::default(),
+                },
+                buckets: Vec::new(),
+                metrics: MetricsConfig::default(),
+                tracing: None,
+                notifications: NotificationsConfig::default(),
+                admin: AdminConfig::default(),
+                logging: LoggingConfig::default(),
+                state: StateConfig::default(),
+            },
+        }
+    }
+}
+
+impl ConfigBuilder {
+    /// Start from an existing configuration, e.g. one loaded from a file
+    pub fn from_config(config: Config) -> Self {
+        Self { config }
+    }
+
+    /// Set the address the server listens on (`server.address`)
+    pub fn address(mut self, address: &str) -> Self {
+        self.config.server.address = address.to_string();
+        self
+    }
+
+    /// Add a listener (`server.listeners`), which replaces `server.address`
+    pub fn add_listener(mut self, listener: ListenerConfig) -> Self {
+        self.config.server.address.clear();
+        self.config.server.listeners.push(listener);
+        self
+    }
+
+    /// Replace the whole `server` section
+    pub fn server(mut self, server: ServerConfig) -> Self {
+        self.config.server = server;
+        self
+    }
+
+    /// Add a bucket; buckets are matched in the order they were added
+    pub fn add_bucket(mut self, bucket: BucketConfig) -> Self {
+        self.config.buckets.push(bucket);
+        self
+    }
+
+    /// Set the `metrics` section
+    pub fn metrics(mut self, metrics: MetricsConfig) -> Self {
+        self.config.metrics = metrics;
+        self
+    }
+
+    /// Set the `tracing` section
+    pub fn tracing(mut self, tracing: TracingConfig) -> Self {
+        self.config.tracing = Some(tracing);
+        self
+    }
+
+    /// Set the `notifications` section
+    pub fn notifications(mut self, notifications: NotificationsConfig) -> Self {
+        self.config.notifications = notifications;
+        self
+    }
+
+    /// Set the `admin` section
+    pub fn admin(mut self, admin: AdminConfig) -> Self {
+        self.config.admin = admin;
+        self
+    }
+
+    /// Set the `logging` section
+    pub fn logging(mut self, logging: LoggingConfig) -> Self {
+        self.config.logging = logging;
+        self
+    }
+
+    /// Set the `state` section
+    pub fn state(mut self, state: StateConfig) -> Self {
+        self.config.state = state;
+        self
+    }
+
+    /// Validate and return the configuration
+    ///
+    /// Reports the same problems as loading the equivalent YAML file.
+    pub fn build(self) -> Result<Config, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+
+    /// Return the configuration without validating it
+    pub(crate) fn build_unchecked(self) -> Config {
+        self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::S3Config;
+
+    fn bucket(name: &str, prefix: &str) -> BucketConfig {
+        BucketConfig::new(name, prefix, S3Config::new("bucket", "us-east-1"))
+    }
+
+    #[test]
+    fn test_builder_defaults() {
+        let config = Config::builder()
+            .add_bucket(bucket("uploads", "/uploads"))
+            .build()
+            .unwrap();
+
+        assert_eq!(config.server.address, DEFAULT_ADDRESS);
+        assert_eq!(config.buckets[0].name, "uploads");
+        assert!(!config.buckets[0].auth.enabled);
+        assert!(config.tracing.is_none());
+    }
+
+    #[test]
+    fn test_builder_keeps_bucket_order() {
+        let config = Config::builder()
+            .address("127.0.0.1:0")
+            .add_bucket(bucket("a", "/a"))
+            .add_bucket(bucket("b", "/b"))
+            .build()
+            .unwrap();
+
+        assert_eq!(config.server.address, "127.0.0.1:0");
+        let names: Vec<_> = config.buckets.iter().map(|b| b.name.as_str()).collect();
+        assert_eq!(names, ["a", "b"]);
+    }
+
+    #[test]
+    fn test_builder_listeners() {
+        let listener = |name: &str, buckets: &[&str]| ListenerConfig {
+            name: name.into(),
+            address: "127.0.0.1:0".into(),
+            buckets: buckets.iter().map(|b| b.to_string()).collect(),
+            tls: None,
+            tcp: None,
+        };
+        let config = Config::builder()
+            .add_listener(listener("internal", &[]))
+            .add_listener(listener("public", &["a"]))
+            .add_bucket(bucket("a", "/a"))
+            .build()
+            .unwrap();
+
+        assert!(config.server.address.is_empty());
+        let names: Vec<_> = config
+            .server
+            .effective_listeners()
+            .into_iter()
+            .map(|l| l.name)
+            .collect();
+        assert_eq!(names, ["internal", "public"]);
+    }
+
+    #[test]
+    fn test_builder_validates() {
+        let err = Config::builder().build().unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid(ref errors) if errors[0].path == "buckets"));
+
+        let err = Config::builder()
+            .add_bucket(bucket("a", ""))
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("buckets[0].path_prefix"), "{err}");
+    }
+}
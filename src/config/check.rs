@@ -0,0 +1,235 @@
+//! Configuration check (`--check-config`)
+//!
+//! Unlike [`ConfigLoader::load`](super::ConfigLoader::load), which stops at
+//! the first stage that fails, a check reports every problem at once with
+//! the YAML path it concerns: environment variables that were not resolved,
+//! secret files that cannot be read, validation errors and, optionally, JWKS
+//! endpoints that cannot be fetched.
+
+use super::{secrets, Config, ConfigError, ConfigLoader, FieldError};
+use lazy_static::lazy_static;
+use serde_yaml::Value;
+use std::path::Path;
+use std::time::Duration;
+
+/// What to check besides the configuration file itself
+#[derive(Debug, Clone)]
+pub struct CheckOptions {
+    /// Fetch each bucket's `jwks_url` and check it returns a key set
+    pub jwks: bool,
+    /// Timeout of each network check
+    pub timeout: Duration,
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self {
+        Self {
+            jwks: false,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Check a configuration file, returning every problem found
+///
+/// Errors only if the file cannot be read or parsed at all.
+pub async fn check_file<P: AsRef<Path>>(
+    path: P,
+    options: &CheckOptions,
+) -> Result<Vec<FieldError>, ConfigError> {
+    let content = std::fs::read_to_string(path)?;
+    let mut config = ConfigLoader::parse(&content)?;
+    // Placeholders are looked up in the unexpanded document, which tells
+    // them apart from escaped `$${VAR}` literals
+    let document: Value = serde_yaml::from_str(&content)?;
+
+    let mut errors = Vec::new();
+    unresolved_env_vars(&document, "", &mut errors);
+    errors.extend(secrets::resolve(&mut config));
+    errors.extend(config.validation_errors());
+    if options.jwks {
+        errors.extend(check_jwks(&config, options.timeout).await);
+    }
+    Ok(errors)
+}
+
+/// Report `${VAR}` placeholders (without a default) whose variable is unset
+fn unresolved_env_vars(value: &Value, path: &str, errors: &mut Vec<FieldError>) {
+    lazy_static! {
+        static ref PLACEHOLDER_RE: regex_lite::Regex =
+            regex_lite::Regex::new(r"\$(\$)?\{([A-Z_][A-Z0-9_]*)(:?-[^}]*)?\}").unwrap();
+    }
+
+    match value {
+        Value::String(s) => {
+            for cap in PLACEHOLDER_RE.captures_iter(s) {
+                let name = &cap[2];
+                let escaped = cap.get(1).is_some();
+                if !escaped && cap.get(3).is_none() && std::env::var(name).is_err() {
+                    errors.push(FieldError::new(
+                        path,
+                        format!("Environment variable {} is not set", name),
+                    ));
+                }
+            }
+        }
+        Value::Sequence(items) => {
+            for (i, item) in items.iter().enumerate() {
+                unresolved_env_vars(item, &format!("{}[{}]", path, i), errors);
+            }
+        }
+        Value::Mapping(map) => {
+            for (key, item) in map {
+                let key = match key {
+                    Value::String(key) => key.clone(),
+                    other => serde_yaml::to_string(other)
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string(),
+                };
+                let path = if path.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                unresolved_env_vars(item, &path, errors);
+            }
+        }
+        Value::Tagged(tagged) => unresolved_env_vars(&tagged.value, path, errors),
+        _ => {}
+    }
+}
+
+/// Fetch each bucket's JWKS, reporting the ones that are unreachable or
+/// do not return a key set
+async fn check_jwks(config: &Config, timeout: Duration) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    let client = match reqwest::Client::builder().timeout(timeout).build() {
+        Ok(client) => client,
+        Err(e) => {
+            errors.push(FieldError::new(
+                "buckets",
+                format!("Cannot create HTTP client: {}", e),
+            ));
+            return errors;
+        }
+    };
+
+    for (i, bucket) in config.buckets.iter().enumerate() {
+        let Some(url) = bucket
+            .auth
+            .jwt
+            .as_ref()
+            .and_then(|jwt| jwt.jwks_url.as_ref())
+        else {
+            continue;
+        };
+        let result = match client.get(url).send().await {
+            Ok(response) => match response.error_for_status() {
+                Ok(response) => response
+                    .json::<serde_json::Value>()
+                    .await
+                    .map_err(|e| e.to_string())
+                    .and_then(|body| {
+                        body.get("keys")
+                            .is_some_and(|keys| keys.is_array())
+                            .then_some(())
+                            .ok_or_else(|| "response has no \"keys\" array".to_string())
+                    }),
+                Err(e) => Err(e.to_string()),
+            },
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = result {
+            errors.push(FieldError::new(
+                format!("buckets[{}].auth.jwt.jwks_url", i),
+                format!("JWKS at '{}' cannot be fetched: {}", url, e),
+            ));
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn write_config(yaml: &str) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(yaml.as_bytes()).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_unresolved_env_vars() {
+        let document: Value = serde_yaml::from_str(
+            r#"
+buckets:
+  - name: uploads
+    s3:
+      access_key: "${MIZUCHI_CHECK_UNSET_KEY}"
+      secret_key: "${MIZUCHI_CHECK_UNSET_SECRET:-dev}"
+      endpoint: "$${MIZUCHI_CHECK_UNSET_ENDPOINT}"
+"#,
+        )
+        .unwrap();
+
+        let mut errors = Vec::new();
+        unresolved_env_vars(&document, "", &mut errors);
+
+        assert_eq!(
+            errors,
+            vec![FieldError::new(
+                "buckets[0].s3.access_key",
+                "Environment variable MIZUCHI_CHECK_UNSET_KEY is not set"
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_file_reports_every_problem() {
+        let file = write_config(
+            r#"
+server:
+  address: "0.0.0.0:8080"
+buckets:
+  - name: uploads
+    path_prefix: ""
+    s3:
+      bucket: uploads
+      region: us-east-1
+      endpoint: "minio:9000"
+  - name: uploads
+    path_prefix: /other
+    s3:
+      bucket: other
+      region: us-east-1
+"#,
+        );
+
+        let errors = check_file(file.path(), &CheckOptions::default())
+            .await
+            .unwrap();
+        let paths: Vec<_> = errors.iter().map(|e| e.path.as_str()).collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                "buckets[1].name",
+                "buckets[0].path_prefix",
+                "buckets[0].s3.endpoint"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_file_unparseable() {
+        let file = write_config("buckets: [");
+
+        assert!(matches!(
+            check_file(file.path(), &CheckOptions::default()).await,
+            Err(ConfigError::ParseError(_))
+        ));
+    }
+}
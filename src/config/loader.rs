@@ -1,34 +1,30 @@
 //! Configuration loader with environment variable expansion
 
-use super::{Config, ConfigError};
+use super::{expand_env_vars, Config, ConfigError};
 use std::path::Path;
 
 /// Configuration loader
 pub struct ConfigLoader;
 
 impl ConfigLoader {
-    /// Load configuration from a file
+    /// Load configuration from a file, reading `*_file` secrets
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, ConfigError> {
         let content = std::fs::read_to_string(path)?;
-        let expanded = Self::expand_env_vars(&content);
-        let config: Config = serde_yaml::from_str(&expanded)?;
+        let mut config = Self::parse(&content)?;
+        config.resolve_secret_files()?;
         config.validate()?;
         Ok(config)
     }
 
-    /// Expand environment variables in the format ${VAR_NAME}
-    fn expand_env_vars(content: &str) -> String {
-        let mut result = content.to_string();
-        let re = regex_lite::Regex::new(r"\$\{([A-Z_][A-Z0-9_]*)\}").unwrap();
-
-        for cap in re.captures_iter(content) {
-            let var_name = &cap[1];
-            if let Ok(value) = std::env::var(var_name) {
-                result = result.replace(&cap[0], &value);
-            }
-        }
-
-        result
+    /// Parse YAML configuration without validating it
+    ///
+    /// Environment variables are expanded in the raw YAML before parsing, so
+    /// `${VAR}`, `${VAR:-default}` and `${VAR-default}` work in any value,
+    /// including numbers and booleans; `$${VAR}` yields a literal `${VAR}`.
+    /// Values are substituted as-is: quote placeholders whose value may
+    /// contain YAML syntax such as `: ` or `#`.
+    pub fn parse(content: &str) -> Result<Config, ConfigError> {
+        Ok(serde_yaml::from_str(&expand_env_vars(content))?)
     }
 }
 
@@ -40,8 +36,61 @@ mod tests {
     fn test_expand_env_vars() {
         std::env::set_var("TEST_VAR", "test_value");
         let content = "key: ${TEST_VAR}";
-        let expanded = ConfigLoader::expand_env_vars(content);
+        let expanded = expand_env_vars(content);
         assert_eq!(expanded, "key: test_value");
         std::env::remove_var("TEST_VAR");
     }
+
+    #[test]
+    fn test_expand_env_vars_defaults_and_escapes() {
+        std::env::set_var("TEST_LOADER_EMPTY", "");
+        std::env::remove_var("TEST_LOADER_UNSET");
+
+        assert_eq!(expand_env_vars("${TEST_LOADER_EMPTY:-d}"), "d");
+        assert_eq!(expand_env_vars("${TEST_LOADER_EMPTY-d}"), "");
+        assert_eq!(expand_env_vars("${TEST_LOADER_UNSET-d}"), "d");
+        assert_eq!(
+            expand_env_vars("${TEST_LOADER_UNSET}"),
+            "${TEST_LOADER_UNSET}"
+        );
+        assert_eq!(
+            expand_env_vars("$${TEST_LOADER_EMPTY}"),
+            "${TEST_LOADER_EMPTY}"
+        );
+        std::env::remove_var("TEST_LOADER_EMPTY");
+    }
+
+    #[test]
+    fn test_parse_expands_every_field() {
+        std::env::set_var("TEST_LOADER_ENDPOINT", "http://minio:9000");
+        std::env::set_var("TEST_LOADER_PORT", "9191");
+        let yaml = r#"
+server:
+  address: "0.0.0.0:8080"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: uploads
+      region: us-east-1
+      endpoint: "${TEST_LOADER_ENDPOINT}"
+      secret_key: "$${NOT_EXPANDED}"
+metrics:
+  port: ${TEST_LOADER_PORT}
+"#;
+
+        let config = ConfigLoader::parse(yaml).unwrap();
+
+        assert_eq!(
+            config.buckets[0].s3.endpoint.as_deref(),
+            Some("http://minio:9000")
+        );
+        assert_eq!(
+            config.buckets[0].s3.secret_key.as_deref(),
+            Some("${NOT_EXPANDED}")
+        );
+        assert_eq!(config.metrics.port, 9191);
+        std::env::remove_var("TEST_LOADER_ENDPOINT");
+        std::env::remove_var("TEST_LOADER_PORT");
+    }
 }
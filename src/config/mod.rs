@@ -5,11 +5,18 @@
 
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
 use std::path::Path;
 use thiserror::Error;
 
+mod builder;
+mod check;
 mod loader;
+mod secrets;
 
+pub use builder::ConfigBuilder;
+pub use check::{check_file, CheckOptions};
 pub use loader::ConfigLoader;
 
 // ============================================================================
@@ -18,9 +25,11 @@ pub use loader::ConfigLoader;
 
 /// Expand environment variables in a string.
 ///
-/// Supports two syntaxes:
+/// Supports these syntaxes:
 /// - `${VAR_NAME}` - Simple expansion, keeps placeholder if var not found
-/// - `${VAR_NAME:-default}` - Expansion with default value
+/// - `${VAR_NAME:-default}` - Default value if the var is unset or empty
+/// - `${VAR_NAME-default}` - Default value if the var is unset
+/// - `$${VAR_NAME}` - Escape, producing a literal `${VAR_NAME}`
 ///
 /// Variable names must start with a letter or underscore and contain only
 /// uppercase letters, digits, and underscores.
@@ -34,12 +43,15 @@ pub use loader::ConfigLoader;
 ///
 /// let result = expand_env_vars("${MISSING:-default}");
 /// assert_eq!(result, "default");
+///
+/// let result = expand_env_vars("$${MY_VAR}");
+/// assert_eq!(result, "${MY_VAR}");
 /// ```
 fn expand_env_vars(s: &str) -> String {
     // Compile regex once using lazy_static for better performance
     lazy_static! {
         static ref ENV_VAR_RE: regex_lite::Regex =
-            regex_lite::Regex::new(r"\$\{([A-Z_][A-Z0-9_]*)(?::-([^}]+))?\}").unwrap();
+            regex_lite::Regex::new(r"\$(\$)?\{([A-Z_][A-Z0-9_]*)(?:(:?-)([^}]*))?\}").unwrap();
     }
 
     let mut last_match = 0;
@@ -47,26 +59,29 @@ fn expand_env_vars(s: &str) -> String {
 
     for cap in ENV_VAR_RE.captures_iter(s) {
         let full_match = cap.get(0).unwrap();
-        let var_name = cap.get(1).unwrap().as_str();
 
         // Append the text before the match
         result.push_str(&s[last_match..full_match.start()]);
+        last_match = full_match.end();
+
+        // Escaped: drop the extra `$` and keep the placeholder
+        if cap.get(1).is_some() {
+            result.push_str(&full_match.as_str()[1..]);
+            continue;
+        }
 
         // Get value from env, or use default from regex
-        let value = match std::env::var(var_name) {
-            Ok(val) => val,
-            Err(_) => {
-                if let Some(default) = cap.get(2) {
-                    default.as_str().to_string()
-                } else {
-                    // No env var and no default. Keep the original placeholder.
-                    full_match.as_str().to_string()
-                }
+        let var_name = cap.get(2).unwrap().as_str();
+        let value = match (std::env::var(var_name), cap.get(3), cap.get(4)) {
+            (Ok(val), Some(op), Some(default)) if val.is_empty() && op.as_str() == ":-" => {
+                default.as_str().to_string()
             }
+            (Ok(val), _, _) => val,
+            (Err(_), _, Some(default)) => default.as_str().to_string(),
+            // No env var and no default. Keep the original placeholder.
+            (Err(_), _, None) => full_match.as_str().to_string(),
         };
         result.push_str(&value);
-
-        last_match = full_match.end();
     }
 
     // Append the rest of the string after the last match
@@ -75,18 +90,6 @@ fn expand_env_vars(s: &str) -> String {
     result
 }
 
-/// Custom deserializer for strings with environment variable expansion.
-///
-/// This is used with serde's `deserialize_with` attribute to automatically
-/// expand environment variables when deserializing configuration values.
-fn deserialize_with_env<'de, D>(deserializer: D) -> Result<String, D::Error>
-where
-    D: serde::de::Deserializer<'de>,
-{
-    let s = String::deserialize(deserializer)?;
-    Ok(expand_env_vars(&s))
-}
-
 // ============================================================================
 // Validation Helpers
 // ============================================================================
@@ -107,6 +110,40 @@ pub enum ConfigError {
 
     #[error("Invalid configuration: {0}")]
     ValidationError(String),
+
+    #[error("Invalid configuration: {}", join_field_errors(.0))]
+    Invalid(Vec<FieldError>),
+}
+
+/// A validation problem and the YAML path of the field it concerns,
+/// e.g. `buckets[0].auth.jwt.jwks_url`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    pub path: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+fn join_field_errors(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
 }
 
 /// Main configuration structure
@@ -118,6 +155,14 @@ pub struct Config {
     pub metrics: MetricsConfig,
     #[serde(default)]
     pub tracing: Option<TracingConfig>,
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub state: StateConfig,
 }
 
 impl Config {
@@ -126,243 +171,3136 @@ impl Config {
         ConfigLoader::load(path)
     }
 
-    /// Validate the configuration
+    /// Assemble a configuration in code instead of YAML
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
+    /// Validate the configuration, reporting every problem found
     pub fn validate(&self) -> Result<(), ConfigError> {
+        let errors = self.validation_errors();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(errors))
+        }
+    }
+
+    /// Every validation problem, with the YAML path of the offending field
+    pub fn validation_errors(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
         if self.buckets.is_empty() {
-            return Err(ConfigError::ValidationError(
-                "At least one bucket must be configured".into(),
+            errors.push(FieldError::new(
+                "buckets",
+                "At least one bucket must be configured",
+            ));
+        }
+
+        if let Some(ref filter) = self.logging.filter {
+            if let Err(e) = tracing_subscriber::EnvFilter::try_new(filter) {
+                errors.push(FieldError::new(
+                    "logging.filter",
+                    format!("logging.filter is invalid: {}", e),
+                ));
+            }
+        }
+
+        if let Some(ref labels) = self.metrics.tenant_labels {
+            if labels.claim.is_empty() {
+                errors.push(FieldError::new(
+                    "metrics.tenant_labels.claim",
+                    "Tenant labels need a claim naming the tenant",
+                ));
+            }
+        }
+
+        if self.metrics.enabled {
+            if let Err(e) = self.metrics.address.parse::<IpAddr>() {
+                errors.push(FieldError::new(
+                    "metrics.address",
+                    format!(
+                        "metrics.address {:?} is not an IP address: {}",
+                        self.metrics.address, e
+                    ),
+                ));
+            }
+        }
+
+        if self.admin.address.is_some()
+            && self.admin.token.is_none()
+            && self.admin.token_file.is_none()
+        {
+            errors.push(FieldError::new(
+                "admin.token",
+                "admin.address requires admin.token",
             ));
         }
 
-        for bucket in &self.buckets {
+        validate_listeners(self, &mut errors);
+
+        let http2 = &self.server.http.http2;
+        if http2.enabled {
+            if http2.max_concurrent_streams == 0 {
+                errors.push(FieldError::new(
+                    "server.http.http2.max_concurrent_streams",
+                    "server.http.http2.max_concurrent_streams must be greater than 0",
+                ));
+            }
+            let windows = [
+                (
+                    "initial_stream_window_size",
+                    http2.initial_stream_window_size,
+                ),
+                (
+                    "initial_connection_window_size",
+                    http2.initial_connection_window_size,
+                ),
+            ];
+            for (name, size) in windows {
+                if size.is_some_and(|size| size == 0 || size > HTTP2_MAX_WINDOW_SIZE) {
+                    errors.push(FieldError::new(
+                        format!("server.http.http2.{}", name),
+                        format!(
+                            "server.http.http2.{} must be between 1 and {}",
+                            name, HTTP2_MAX_WINDOW_SIZE
+                        ),
+                    ));
+                }
+            }
+            if http2.keep_alive_interval_secs == Some(0) || http2.keep_alive_timeout_secs == 0 {
+                errors.push(FieldError::new(
+                    "server.http.http2",
+                    "server.http.http2 keep-alive interval and timeout must be greater than 0",
+                ));
+            }
+        }
+
+        validate_tenants(&self.buckets, &mut errors);
+
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            let at = format!("buckets[{}]", i);
+
             if bucket.path_prefix.is_empty() {
-                return Err(ConfigError::ValidationError(format!(
-                    "Bucket '{}' has empty path_prefix",
-                    bucket.name
-                )));
+                errors.push(FieldError::new(
+                    format!("{}.path_prefix", at),
+                    format!("Bucket '{}' has empty path_prefix", bucket.name),
+                ));
+            }
+
+            for (j, host) in bucket.hosts.iter().enumerate() {
+                if crate::router::HostPattern::new(host).is_none() {
+                    errors.push(FieldError::new(
+                        format!("{}.hosts[{}]", at, j),
+                        format!(
+                            "Bucket '{}' has invalid host pattern '{}'",
+                            bucket.name, host
+                        ),
+                    ));
+                }
+            }
+
+            if let Some(ref prefix) = bucket.key_prefix {
+                if let Err(e) = crate::upload::key_prefix::KeyPrefix::new(prefix) {
+                    errors.push(FieldError::new(
+                        format!("{}.key_prefix", at),
+                        format!("Bucket '{}' has an invalid key_prefix: {}", bucket.name, e),
+                    ));
+                }
+            }
+
+            if let Some(ref signing) = bucket.upload.signing {
+                if let Err(e) = crate::server::signing::ResponseSigner::new(signing) {
+                    errors.push(FieldError::new(
+                        format!("{}.upload.signing.key", at),
+                        format!("Bucket '{}' has an invalid signing key: {}", bucket.name, e),
+                    ));
+                }
+            }
+
+            if let Err(e) = crate::server::response_headers::ResponseHeaders::new(
+                &bucket.upload.response_headers,
+            ) {
+                errors.push(FieldError::new(
+                    format!("{}.upload.response_headers", at),
+                    format!(
+                        "Bucket '{}' has invalid response_headers: {}",
+                        bucket.name, e
+                    ),
+                ));
+            }
+
+            validate_endpoint(&bucket.s3, &format!("{}.s3", at), &mut errors);
+
+            if let Some(ref hedging) = bucket.s3.hedging {
+                if !(1..=99).contains(&hedging.percentile) {
+                    errors.push(FieldError::new(
+                        format!("{}.s3.hedging.percentile", at),
+                        format!(
+                            "Bucket '{}' has hedging percentile {}, must be between 1 and 99",
+                            bucket.name, hedging.percentile
+                        ),
+                    ));
+                }
+                if hedging.min_delay_ms > hedging.max_delay_ms {
+                    errors.push(FieldError::new(
+                        format!("{}.s3.hedging.min_delay_ms", at),
+                        format!(
+                            "Bucket '{}' has hedging min_delay_ms above max_delay_ms",
+                            bucket.name
+                        ),
+                    ));
+                }
+            }
+
+            if let Some(ref debug_log) = bucket.s3.debug_log {
+                if debug_log.max_files == 0 {
+                    errors.push(FieldError::new(
+                        format!("{}.s3.debug_log.max_files", at),
+                        format!(
+                            "Bucket '{}' keeps no debug log files, max_files must be at least 1",
+                            bucket.name
+                        ),
+                    ));
+                }
+            }
+
+            let dns = bucket.s3.pool.as_ref().and_then(|pool| pool.dns.as_ref());
+            for (host, addrs) in dns.iter().flat_map(|dns| &dns.overrides) {
+                if addrs.is_empty() {
+                    errors.push(FieldError::new(
+                        format!("{}.s3.pool.dns.overrides.{}", at, host),
+                        format!(
+                            "Bucket '{}' overrides '{}' without any address",
+                            bucket.name, host
+                        ),
+                    ));
+                }
+            }
+
+            if let Some(ref replication) = bucket.replication {
+                validate_endpoint(
+                    &replication.target,
+                    &format!("{}.replication.target", at),
+                    &mut errors,
+                );
+                if replication.mode == ReplicationMode::Async {
+                    if replication.queue_capacity == 0 {
+                        errors.push(FieldError::new(
+                            format!("{}.replication.queue_capacity", at),
+                            format!(
+                                "Bucket '{}' replication queue_capacity must be greater than 0",
+                                bucket.name
+                            ),
+                        ));
+                    }
+                    if replication.dead_letter_path.is_none() {
+                        errors.push(FieldError::new(
+                            format!("{}.replication.dead_letter_path", at),
+                            format!(
+                                "Bucket '{}' uses async replication without a dead_letter_path",
+                                bucket.name
+                            ),
+                        ));
+                    }
+                }
+            }
+
+            if let Some(ref failover) = bucket.failover {
+                validate_endpoint(
+                    &failover.target,
+                    &format!("{}.failover.target", at),
+                    &mut errors,
+                );
+                if bucket.storage != StorageConfig::S3 {
+                    errors.push(FieldError::new(
+                        format!("{}.failover", at),
+                        format!(
+                            "Bucket '{}' configures failover but does not use S3 storage",
+                            bucket.name
+                        ),
+                    ));
+                }
+            }
+
+            if let Some(ref regions) = bucket.regions {
+                validate_regions(bucket, regions, &format!("{}.regions", at), &mut errors);
+            }
+
+            let listeners = self.server.effective_listeners();
+            let mut serving = listeners.iter().filter(|l| l.serves(&bucket.name));
+            if !self.server.listeners.is_empty() && serving.clone().next().is_none() {
+                errors.push(FieldError::new(
+                    at.clone(),
+                    format!("Bucket '{}' is not served by any listener", bucket.name),
+                ));
+            }
+            if bucket.auth.mtls.is_some() && !serving.any(|l| l.tls.is_some()) {
+                let message = if self.server.listeners.is_empty() {
+                    "server.tls is not configured"
+                } else {
+                    "no TLS listener serves it"
+                };
+                errors.push(FieldError::new(
+                    format!("{}.auth.mtls", at),
+                    format!(
+                        "Bucket '{}' uses mTLS authentication but {}",
+                        bucket.name, message
+                    ),
+                ));
+            }
+
+            if let Some(ocsp) = bucket
+                .auth
+                .mtls
+                .as_ref()
+                .and_then(|mtls| mtls.ocsp.as_ref())
+            {
+                if let Some(ref url) = ocsp.responder_url {
+                    if !is_valid_http_url(url) {
+                        errors.push(FieldError::new(
+                            format!("{}.auth.mtls.ocsp.responder_url", at),
+                            format!(
+                                "Invalid OCSP responder URL '{}': must start with http:// or https://",
+                                url
+                            ),
+                        ));
+                    }
+                }
+                if ocsp.timeout_ms == 0 {
+                    errors.push(FieldError::new(
+                        format!("{}.auth.mtls.ocsp.timeout_ms", at),
+                        "OCSP timeout must be greater than 0",
+                    ));
+                }
+            }
+
+            if let Some(jwks_url) = bucket
+                .auth
+                .jwt
+                .as_ref()
+                .and_then(|jwt| jwt.jwks_url.as_ref())
+            {
+                if !is_valid_http_url(jwks_url) {
+                    errors.push(FieldError::new(
+                        format!("{}.auth.jwt.jwks_url", at),
+                        format!(
+                            "Invalid JWKS URL '{}': must start with http:// or https://",
+                            jwks_url
+                        ),
+                    ));
+                }
+            }
+
+            if let Some(exchange) = bucket
+                .auth
+                .jwt
+                .as_ref()
+                .and_then(|jwt| jwt.token_exchange.as_ref())
+            {
+                if !is_valid_http_url(&exchange.token_endpoint) {
+                    errors.push(FieldError::new(
+                        format!("{}.auth.jwt.token_exchange.token_endpoint", at),
+                        format!(
+                            "Invalid token endpoint '{}': must start with http:// or https://",
+                            exchange.token_endpoint
+                        ),
+                    ));
+                }
+                if exchange.timeout_ms == 0 {
+                    errors.push(FieldError::new(
+                        format!("{}.auth.jwt.token_exchange.timeout_ms", at),
+                        "Token exchange timeout must be greater than 0",
+                    ));
+                }
+            }
+
+            if let Some(revocation) = bucket
+                .auth
+                .jwt
+                .as_ref()
+                .and_then(|jwt| jwt.revocation.as_ref())
+            {
+                if revocation.refresh_secs == 0 {
+                    errors.push(FieldError::new(
+                        format!("{}.auth.jwt.revocation.refresh_secs", at),
+                        "Revocation list refresh interval must be greater than 0",
+                    ));
+                }
+                if let RevocationStoreConfig::Redis { ref url, .. } = revocation.store {
+                    if !url.starts_with("redis://") {
+                        errors.push(FieldError::new(
+                            format!("{}.auth.jwt.revocation.store.url", at),
+                            format!("Invalid Redis URL '{}': must start with redis://", url),
+                        ));
+                    }
+                }
+            }
+
+            if bucket.authz.is_some() && !bucket.auth.enabled {
+                errors.push(FieldError::new(
+                    format!("{}.authz", at),
+                    format!(
+                        "Bucket '{}' configures authz but auth is not enabled",
+                        bucket.name
+                    ),
+                ));
+            }
+
+            if let Some(ref authz) = bucket.authz {
+                validate_authz(&bucket.name, authz, &format!("{}.authz", at), &mut errors);
+            }
+
+            if let Some(ref shadow) = bucket.authz_shadow {
+                let path = format!("{}.authz_shadow", at);
+                if bucket.authz.is_none() {
+                    errors.push(FieldError::new(
+                        path.clone(),
+                        format!(
+                            "Bucket '{}' configures authz_shadow without authz",
+                            bucket.name
+                        ),
+                    ));
+                }
+                validate_authz(&bucket.name, shadow, &path, &mut errors);
+            }
+
+            if bucket.authz_failure.grace_period_secs.is_some()
+                && bucket.authz_failure.mode != AuthzFailureMode::FailOpen
+            {
+                errors.push(FieldError::new(
+                    format!("{}.authz_failure.grace_period_secs", at),
+                    format!(
+                        "Bucket '{}' authz_failure grace_period_secs requires mode fail_open",
+                        bucket.name
+                    ),
+                ));
+            }
+
+            if let Some(ref reconciliation) = bucket.upload.reconciliation {
+                if reconciliation.interval_secs == 0 {
+                    errors.push(FieldError::new(
+                        format!("{}.upload.reconciliation.interval_secs", at),
+                        format!(
+                            "Bucket '{}' upload reconciliation interval_secs must be greater than 0",
+                            bucket.name
+                        ),
+                    ));
+                }
+            }
+
+            if let Some(ref flow) = bucket.upload.flow_control {
+                let path = format!("{}.upload.flow_control", at);
+                if flow.low_water_mark >= flow.high_water_mark {
+                    errors.push(FieldError::new(
+                        format!("{}.low_water_mark", path),
+                        format!(
+                            "Bucket '{}' flow control low_water_mark must be below high_water_mark",
+                            bucket.name
+                        ),
+                    ));
+                }
+                if flow.high_water_mark < bucket.upload.part_size as u64 {
+                    errors.push(FieldError::new(
+                        format!("{}.high_water_mark", path),
+                        format!(
+                            "Bucket '{}' flow control high_water_mark must be at least part_size",
+                            bucket.name
+                        ),
+                    ));
+                }
+            }
+
+            for (j, rule) in bucket.upload.storage_class_rules.iter().enumerate() {
+                if let (Some(min), Some(max)) = (rule.min_size, rule.max_size) {
+                    if min > max {
+                        errors.push(FieldError::new(
+                            format!("{}.upload.storage_class_rules[{}]", at, j),
+                            format!(
+                                "Bucket '{}' storage class rule has min_size above max_size",
+                                bucket.name
+                            ),
+                        ));
+                    }
+                }
+            }
+
+            if let Some(ref quota) = bucket.upload.quota {
+                let path = format!("{}.upload.quota", at);
+                if quota.limits.is_empty() {
+                    errors.push(FieldError::new(
+                        format!("{}.limits", path),
+                        format!("Bucket '{}' upload quota has no limits", bucket.name),
+                    ));
+                }
+                for (j, limit) in quota.limits.iter().enumerate() {
+                    if limit.max_bytes.is_none() && limit.max_objects.is_none() {
+                        errors.push(FieldError::new(
+                            format!("{}.limits[{}]", path, j),
+                            format!(
+                                "Bucket '{}' upload quota limit needs max_bytes or max_objects",
+                                bucket.name
+                            ),
+                        ));
+                    }
+                }
+                if quota.redis.is_some() && quota.path.is_some() {
+                    errors.push(FieldError::new(
+                        format!("{}.path", path),
+                        format!(
+                            "Bucket '{}' upload quota can use redis or path, not both",
+                            bucket.name
+                        ),
+                    ));
+                }
+            }
+
+            if let Some(ref anonymous) = bucket.auth.allow_anonymous {
+                if anonymous.max_size == 0
+                    || anonymous.allowed_content_types.is_empty()
+                    || anonymous.key_prefix.trim_matches('/').is_empty()
+                    || anonymous.rate_limit.requests_per_minute == 0
+                    || anonymous.rate_limit.burst == Some(0)
+                {
+                    errors.push(FieldError::new(
+                        format!("{}.auth.allow_anonymous", at),
+                        format!(
+                            "Bucket '{}' allow_anonymous needs a non-zero max_size and rate limit, \
+                             allowed_content_types and a non-empty key_prefix",
+                            bucket.name
+                        ),
+                    ));
+                }
+            }
+
+            if let Some(ref sigv4) = bucket.auth.sigv4 {
+                let replay = &sigv4.replay_protection;
+                if replay.enabled && replay.redis.is_none() && replay.max_entries == 0 {
+                    errors.push(FieldError::new(
+                        format!("{}.auth.sigv4.replay_protection.max_entries", at),
+                        format!(
+                            "Bucket '{}' sigv4 replay_protection needs max_entries > 0 without redis",
+                            bucket.name
+                        ),
+                    ));
+                }
+                if let Some(SigV4StoreConfig::Vault {
+                    ref token,
+                    ref token_file,
+                    ..
+                }) = sigv4.store
+                {
+                    if token.is_empty() && token_file.is_none() {
+                        errors.push(FieldError::new(
+                            format!("{}.auth.sigv4.store.token", at),
+                            format!(
+                                "Bucket '{}' sigv4 vault store needs token or token_file",
+                                bucket.name
+                            ),
+                        ));
+                    }
+                }
+            }
+
+            for (j, method) in bucket.auth.chain.order.iter().enumerate() {
+                let configured = match method {
+                    AuthMethod::Mtls => bucket.auth.mtls.is_some(),
+                    AuthMethod::Sigv4 => bucket.auth.sigv4.is_some(),
+                    AuthMethod::Jwt => bucket.auth.jwt.is_some(),
+                    AuthMethod::ApiKey => bucket.auth.api_key.is_some(),
+                    AuthMethod::Anonymous => true,
+                };
+                if !configured {
+                    errors.push(FieldError::new(
+                        format!("{}.auth.chain.order[{}]", at, j),
+                        format!(
+                            "Bucket '{}' auth chain uses {} but it is not configured",
+                            bucket.name,
+                            method.as_str()
+                        ),
+                    ));
+                }
+            }
+
+            if let StorageConfig::Local { ref root } = bucket.storage {
+                if root.trim().is_empty() {
+                    errors.push(FieldError::new(
+                        format!("{}.storage.root", at),
+                        format!(
+                            "Bucket '{}' uses local storage with an empty root",
+                            bucket.name
+                        ),
+                    ));
+                }
             }
         }
 
-        // Validate tracing config if present
+        // Validate tracing config if enabled
         if let Some(ref tracing) = self.tracing {
             if tracing.enabled {
                 // Validate OTLP endpoint is not empty
                 if tracing.otlp.endpoint.is_empty() {
-                    return Err(ConfigError::ValidationError(
-                        "OTLP endpoint cannot be empty when tracing is enabled".into(),
+                    errors.push(FieldError::new(
+                        "tracing.otlp.endpoint",
+                        "OTLP endpoint cannot be empty when tracing is enabled",
                     ));
-                }
-
-                // Validate OTLP endpoint URL format
-                if !is_valid_http_url(&tracing.otlp.endpoint) {
-                    return Err(ConfigError::ValidationError(
-                        "Invalid OTLP endpoint: must start with http:// or https://".into(),
+                } else if !is_valid_http_url(&tracing.otlp.endpoint) {
+                    // Validate OTLP endpoint URL format
+                    errors.push(FieldError::new(
+                        "tracing.otlp.endpoint",
+                        "Invalid OTLP endpoint: must start with http:// or https://",
                     ));
                 }
 
                 // Validate service name is not empty
                 if tracing.service_name.trim().is_empty() {
-                    return Err(ConfigError::ValidationError(
-                        "Service name cannot be empty when tracing is enabled".into(),
+                    errors.push(FieldError::new(
+                        "tracing.service_name",
+                        "Service name cannot be empty when tracing is enabled",
                     ));
                 }
 
                 // Validate OTLP protocol
                 match tracing.otlp.protocol.as_str() {
                     "grpc" | "http/protobuf" => {}
-                    _ => {
-                        return Err(ConfigError::ValidationError(format!(
+                    _ => errors.push(FieldError::new(
+                        "tracing.otlp.protocol",
+                        format!(
                             "Invalid OTLP protocol '{}': must be 'grpc' or 'http/protobuf'",
                             tracing.otlp.protocol
-                        )))
-                    }
+                        ),
+                    )),
                 }
 
                 // Validate compression if specified
                 if let Some(ref compression) = tracing.otlp.compression {
                     match compression.as_str() {
                         "gzip" | "none" => {}
-                        _ => {
-                            return Err(ConfigError::ValidationError(format!(
+                        _ => errors.push(FieldError::new(
+                            "tracing.otlp.compression",
+                            format!(
                                 "Invalid compression '{}': must be 'gzip' or 'none'",
                                 compression
-                            )))
-                        }
+                            ),
+                        )),
                     }
                 }
 
                 // Validate sampling ratio
                 if tracing.sampling.ratio < 0.0 || tracing.sampling.ratio > 1.0 {
-                    return Err(ConfigError::ValidationError(format!(
-                        "Invalid sampling ratio {}: must be between 0.0 and 1.0",
-                        tracing.sampling.ratio
-                    )));
+                    errors.push(FieldError::new(
+                        "tracing.sampling.ratio",
+                        format!(
+                            "Invalid sampling ratio {}: must be between 0.0 and 1.0",
+                            tracing.sampling.ratio
+                        ),
+                    ));
                 }
 
                 // Validate sampling strategy
                 match tracing.sampling.strategy.as_str() {
                     "always" | "never" | "ratio" | "parent_based" => {}
-                    _ => {
-                        return Err(ConfigError::ValidationError(format!(
+                    _ => errors.push(FieldError::new(
+                        "tracing.sampling.strategy",
+                        format!(
                             "Invalid sampling strategy '{}': must be 'always', 'never', 'ratio', or 'parent_based'",
                             tracing.sampling.strategy
-                        )))
-                    }
+                        ),
+                    )),
+                }
+
+                if let Some(ref rules) = tracing.sampling.rules {
+                    validate_tail_sampling(&tracing.sampling.strategy, rules, &mut errors);
+                }
+            }
+        }
+
+        if let Some(ref webhook) = self.server.shutdown.report_webhook {
+            if !is_valid_http_url(&webhook.url) {
+                errors.push(FieldError::new(
+                    "server.shutdown.report_webhook.url",
+                    format!(
+                        "Invalid shutdown report webhook URL '{}': must start with http:// or https://",
+                        webhook.url
+                    ),
+                ));
+            }
+        }
+
+        // Validate notification webhooks
+        if self.notifications.enabled {
+            if self.notifications.queue_capacity == 0 {
+                errors.push(FieldError::new(
+                    "notifications.queue_capacity",
+                    "Notification queue_capacity must be greater than 0",
+                ));
+            }
+
+            for (i, webhook) in self.notifications.webhooks.iter().enumerate() {
+                if !is_valid_http_url(&webhook.url) {
+                    errors.push(FieldError::new(
+                        format!("notifications.webhooks[{}].url", i),
+                        format!(
+                            "Invalid webhook URL '{}': must start with http:// or https://",
+                            webhook.url
+                        ),
+                    ));
                 }
             }
         }
 
-        Ok(())
+        errors
     }
 }
 
 /// Server configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
+    /// Listen address, serving every bucket (unless `listeners` is set)
+    #[serde(default)]
     pub address: String,
     #[serde(default)]
     pub zero_copy: ZeroCopyConfig,
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    #[serde(default)]
+    pub http: HttpConfig,
+    /// Listeners replacing `address` and `tls`, each serving some or all
+    /// buckets, with or without TLS
+    #[serde(default)]
+    pub listeners: Vec<ListenerConfig>,
+    /// Socket options of every listener without its own `tcp`
+    #[serde(default)]
+    pub tcp: TcpConfig,
+    /// Reuse of the buffers single-PUT upload bodies are received into
+    #[serde(default)]
+    pub buffer_pool: BufferPoolConfig,
 }
 
-/// Zero-copy transfer configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ZeroCopyConfig {
-    #[serde(default = "default_zero_copy_enabled")]
-    pub enabled: bool,
-    #[serde(default = "default_pipe_buffer_size")]
-    pub pipe_buffer_size: usize,
-}
+/// Name of the listener on `server.address`
+pub const DEFAULT_LISTENER_NAME: &str = "default";
 
-impl Default for ZeroCopyConfig {
-    fn default() -> Self {
-        Self {
-            enabled: default_zero_copy_enabled(),
-            pipe_buffer_size: default_pipe_buffer_size(),
+impl ServerConfig {
+    /// Listeners to bind: `listeners`, or one on `address` with `tls`
+    /// serving every bucket
+    ///
+    /// Listeners without their own `tcp` get `server.tcp`.
+    pub fn effective_listeners(&self) -> Vec<ListenerConfig> {
+        if self.listeners.is_empty() {
+            return vec![ListenerConfig {
+                name: DEFAULT_LISTENER_NAME.to_string(),
+                address: self.address.clone(),
+                buckets: Vec::new(),
+                tls: self.tls.clone(),
+                tcp: Some(self.tcp.clone()),
+            }];
         }
+        self.listeners
+            .iter()
+            .map(|listener| ListenerConfig {
+                tcp: Some(listener.tcp.clone().unwrap_or_else(|| self.tcp.clone())),
+                ..listener.clone()
+            })
+            .collect()
     }
 }
 
-fn default_zero_copy_enabled() -> bool {
-    true
+/// An address the server listens on
+///
+/// Listeners let one process serve e.g. service traffic on an internal
+/// plaintext port and user uploads on a public TLS port.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListenerConfig {
+    /// Name in logs; also the systemd socket used instead of binding
+    pub name: String,
+    /// Listen address (host:port)
+    pub address: String,
+    /// Names of the buckets served (empty = all)
+    #[serde(default)]
+    pub buckets: Vec<String>,
+    /// TLS for this listener (plaintext when unset)
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Socket options, replacing `server.tcp` for this listener
+    #[serde(default)]
+    pub tcp: Option<TcpConfig>,
 }
 
-fn default_pipe_buffer_size() -> usize {
-    1048576 // 1MB
+impl ListenerConfig {
+    /// Whether the listener serves the bucket named `bucket`
+    pub fn serves(&self, bucket: &str) -> bool {
+        self.buckets.is_empty() || self.buckets.iter().any(|name| name == bucket)
+    }
 }
 
-/// Bucket configuration
+/// Inbound HTTP protocol and connection tuning
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BucketConfig {
-    pub name: String,
-    pub path_prefix: String,
-    pub s3: S3Config,
-    #[serde(default)]
-    pub auth: AuthConfig,
+pub struct HttpConfig {
+    /// Keep HTTP/1.1 connections open between requests
+    #[serde(default = "default_http1_keep_alive")]
+    pub keep_alive: bool,
     #[serde(default)]
-    pub upload: UploadConfig,
+    pub http2: Http2Config,
 }
 
-/// S3 backend configuration
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            keep_alive: default_http1_keep_alive(),
+            http2: Http2Config::default(),
+        }
+    }
+}
+
+fn default_http1_keep_alive() -> bool {
+    true
+}
+
+/// HTTP/2 configuration
+///
+/// HTTP/2 is negotiated with ALPN on TLS listeners and accepted with prior
+/// knowledge (h2c) on plaintext listeners.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct S3Config {
-    pub bucket: String,
-    pub region: String,
+pub struct Http2Config {
+    #[serde(default = "default_http2_enabled")]
+    pub enabled: bool,
+    /// Maximum concurrent streams per connection
+    #[serde(default = "default_http2_max_concurrent_streams")]
+    pub max_concurrent_streams: u32,
+    /// Per-stream flow control window in bytes (HTTP/2 default: 65535)
     #[serde(default)]
-    pub endpoint: Option<String>,
+    pub initial_stream_window_size: Option<u32>,
+    /// Per-connection flow control window in bytes (HTTP/2 default: 65535)
     #[serde(default)]
-    pub access_key: Option<String>,
+    pub initial_connection_window_size: Option<u32>,
+    /// Size flow control windows from measured bandwidth-delay product
+    /// (overrides the initial window sizes)
     #[serde(default)]
-    pub secret_key: Option<String>,
+    pub adaptive_window: bool,
+    /// Interval between keep-alive PINGs (disabled when unset)
+    #[serde(default)]
+    pub keep_alive_interval_secs: Option<u64>,
+    /// Close the connection if a keep-alive PING isn't acknowledged in time
+    #[serde(default = "default_http2_keep_alive_timeout")]
+    pub keep_alive_timeout_secs: u64,
 }
 
-/// Authentication configuration
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
-pub struct AuthConfig {
-    #[serde(default)]
-    pub enabled: bool,
-    #[serde(default)]
-    pub jwt: Option<JwtConfig>,
-    #[serde(default)]
+impl Default for Http2Config {
+    fn default() -> Self {
+        Self {
+            enabled: default_http2_enabled(),
+            max_concurrent_streams: default_http2_max_concurrent_streams(),
+            initial_stream_window_size: None,
+            initial_connection_window_size: None,
+            adaptive_window: false,
+            keep_alive_interval_secs: None,
+            keep_alive_timeout_secs: default_http2_keep_alive_timeout(),
+        }
+    }
+}
+
+fn default_http2_enabled() -> bool {
+    true
+}
+
+fn default_http2_max_concurrent_streams() -> u32 {
+    200
+}
+
+fn default_http2_keep_alive_timeout() -> u64 {
+    20
+}
+
+/// Largest flow control window allowed by RFC 9113
+const HTTP2_MAX_WINDOW_SIZE: u32 = (1 << 31) - 1;
+
+/// Socket options of a listener and its connections
+///
+/// Unset sizes and keepalive leave the kernel defaults. Receive buffers
+/// are set on the listening socket, so they apply from the handshake on
+/// and window scaling can use them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TcpConfig {
+    /// `SO_RCVBUF` in bytes
+    #[serde(default)]
+    pub recv_buffer_size: Option<u32>,
+    /// `SO_SNDBUF` in bytes
+    #[serde(default)]
+    pub send_buffer_size: Option<u32>,
+    /// Set `TCP_NODELAY` on accepted connections
+    #[serde(default)]
+    pub nodelay: bool,
+    /// Idle seconds before keepalive probes are sent (keepalive is off when
+    /// unset)
+    #[serde(default)]
+    pub keepalive_secs: Option<u64>,
+    /// Seconds between unanswered keepalive probes
+    #[serde(default)]
+    pub keepalive_interval_secs: Option<u64>,
+    /// Unanswered probes before the connection is dropped
+    #[serde(default)]
+    pub keepalive_retries: Option<u32>,
+    /// Pending connections queued by the kernel
+    #[serde(default = "default_tcp_backlog")]
+    pub backlog: u32,
+}
+
+impl Default for TcpConfig {
+    fn default() -> Self {
+        Self {
+            recv_buffer_size: None,
+            send_buffer_size: None,
+            nodelay: false,
+            keepalive_secs: None,
+            keepalive_interval_secs: None,
+            keepalive_retries: None,
+            backlog: default_tcp_backlog(),
+        }
+    }
+}
+
+fn default_tcp_backlog() -> u32 {
+    1024
+}
+
+/// TLS listener configuration
+///
+/// When set, the server accepts HTTPS only. Client certificates are requested
+/// (but not required) and verified against the CA bundles of buckets that use
+/// mTLS authentication.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// PEM certificate chain for the server
+    pub cert_path: String,
+    /// PEM private key for the server
+    pub key_path: String,
+}
+
+/// Graceful shutdown configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShutdownConfig {
+    /// Maximum time to wait for in-flight requests after a shutdown signal
+    #[serde(default = "default_drain_timeout_secs")]
+    pub drain_timeout_secs: u64,
+    /// Webhook that receives the shutdown report (in addition to the log)
+    #[serde(default)]
+    pub report_webhook: Option<WebhookConfig>,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            drain_timeout_secs: default_drain_timeout_secs(),
+            report_webhook: None,
+        }
+    }
+}
+
+fn default_drain_timeout_secs() -> u64 {
+    30
+}
+
+/// Zero-copy transfer configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZeroCopyConfig {
+    #[serde(default = "default_zero_copy_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_pipe_buffer_size")]
+    pub pipe_buffer_size: usize,
+}
+
+impl Default for ZeroCopyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_zero_copy_enabled(),
+            pipe_buffer_size: default_pipe_buffer_size(),
+        }
+    }
+}
+
+fn default_zero_copy_enabled() -> bool {
+    true
+}
+
+fn default_pipe_buffer_size() -> usize {
+    1048576 // 1MB
+}
+
+/// Upload body buffer pool configuration
+///
+/// Buffers are sized in whole chunks of `zero_copy.pipe_buffer_size`, and
+/// kept after an upload for the next body of the same size.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BufferPoolConfig {
+    #[serde(default = "default_buffer_pool_enabled")]
+    pub enabled: bool,
+    /// Bytes of idle buffers kept for reuse; larger bodies are never pooled
+    #[serde(default = "default_buffer_pool_max_idle_bytes")]
+    pub max_idle_bytes: usize,
+}
+
+impl Default for BufferPoolConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_buffer_pool_enabled(),
+            max_idle_bytes: default_buffer_pool_max_idle_bytes(),
+        }
+    }
+}
+
+fn default_buffer_pool_enabled() -> bool {
+    true
+}
+
+fn default_buffer_pool_max_idle_bytes() -> usize {
+    64 * 1024 * 1024 // 64MB
+}
+
+/// Bucket configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BucketConfig {
+    pub name: String,
+    pub path_prefix: String,
+    pub s3: S3Config,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub upload: UploadConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub replication: Option<ReplicationConfig>,
+    #[serde(default)]
+    pub failover: Option<FailoverConfig>,
+    /// Authorization of authenticated (and anonymous) uploads
+    #[serde(default)]
+    pub authz: Option<AuthzConfig>,
+    /// What to do when the authorizer fails (e.g. OPA is unreachable)
+    #[serde(default)]
+    pub authz_failure: AuthzFailureConfig,
+    /// Authorizer evaluated alongside `authz` whose decisions are only logged
+    /// and metered, to validate a new policy before cutting over
+    #[serde(default)]
+    pub authz_shadow: Option<AuthzConfig>,
+    /// Tenants served by this bucket, for buckets sharing a path prefix
+    #[serde(default)]
+    pub tenant: Option<TenantConfig>,
+    /// Host patterns this bucket serves (`Host` header), e.g.
+    /// `tenant-a.upload.example.com` or `*.upload.example.com`; any host if empty
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    /// Prefix prepended to every object key, e.g. `incoming/{sub}/`;
+    /// placeholders are claim names (`{sub}` falls back to the subject)
+    #[serde(default)]
+    pub key_prefix: Option<String>,
+    /// Regional S3 targets uploads are routed between by client network
+    /// or observed latency (every upload goes to `s3` if unset)
+    #[serde(default)]
+    pub regions: Option<RegionRoutingConfig>,
+}
+
+impl BucketConfig {
+    /// Create a bucket serving `path_prefix` from `s3`, with every optional
+    /// section at its default (authentication disabled)
+    pub fn new(name: &str, path_prefix: &str, s3: S3Config) -> Self {
+        Self {
+            name: name.to_string(),
+            path_prefix: path_prefix.to_string(),
+            s3,
+            auth: AuthConfig::default(),
+            upload: UploadConfig::default(),
+            storage: StorageConfig::default(),
+            replication: None,
+            failover: None,
+            authz: None,
+            authz_failure: AuthzFailureConfig::default(),
+            authz_shadow: None,
+            tenant: None,
+            hosts: Vec::new(),
+            key_prefix: None,
+            regions: None,
+        }
+    }
+}
+
+/// Tenant routing of a bucket
+///
+/// Several buckets may share a `path_prefix` and route to different S3
+/// accounts or endpoints: each upload goes to the bucket whose `values`
+/// contain the caller's `claim`, or to the bucket of that prefix without a
+/// `tenant` section if none does. Uploads to the prefix are authenticated
+/// with the first bucket declared with it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantConfig {
+    /// Claim naming the caller's tenant (dots reach nested claims)
+    pub claim: String,
+    /// Tenants routed to this bucket
+    pub values: Vec<String>,
+}
+
+/// Behavior when a bucket's authorizer returns an error
+///
+/// With `fail_open` and a grace period, the last decision made for the
+/// same request within the grace period is reused before falling back to
+/// allowing the upload.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuthzFailureConfig {
+    #[serde(default)]
+    pub mode: AuthzFailureMode,
+    /// How long successful decisions stay usable while the authorizer fails
+    #[serde(default)]
+    pub grace_period_secs: Option<u64>,
+}
+
+/// Outcome of an upload whose authorization failed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthzFailureMode {
+    /// Reject the upload
+    #[default]
+    FailClosed,
+    /// Allow the upload (or reuse the last-known decision)
+    FailOpen,
+}
+
+/// Reject duplicate bucket names and ambiguous tenant routing
+fn validate_tenants(buckets: &[BucketConfig], errors: &mut Vec<FieldError>) {
+    for (i, bucket) in buckets.iter().enumerate() {
+        let at = format!("buckets[{}]", i);
+        let earlier = &buckets[..i];
+        if earlier.iter().any(|other| other.name == bucket.name) {
+            errors.push(FieldError::new(
+                format!("{}.name", at),
+                format!("Bucket name '{}' is used more than once", bucket.name),
+            ));
+        }
+
+        if let Some(ref tenant) = bucket.tenant {
+            if tenant.claim.is_empty() || tenant.values.is_empty() {
+                errors.push(FieldError::new(
+                    format!("{}.tenant", at),
+                    format!(
+                        "Bucket '{}' tenant needs a claim and at least one value",
+                        bucket.name
+                    ),
+                ));
+            }
+        }
+
+        let group: Vec<&BucketConfig> = earlier
+            .iter()
+            .filter(|other| other.path_prefix == bucket.path_prefix && other.hosts == bucket.hosts)
+            .collect();
+        let first = group.first().copied().unwrap_or(bucket);
+        if bucket.tenant.is_some() && !first.auth.enabled {
+            errors.push(FieldError::new(
+                format!("{}.tenant", at),
+                format!(
+                    "Bucket '{}' routes by tenant but '{}', which authenticates path_prefix '{}', has auth disabled",
+                    bucket.name, first.name, bucket.path_prefix
+                ),
+            ));
+        }
+
+        for other in group {
+            let message = match (&bucket.tenant, &other.tenant) {
+                (None, None) => format!(
+                    "Buckets '{}' and '{}' share path_prefix '{}' without tenant routing",
+                    other.name, bucket.name, bucket.path_prefix
+                ),
+                (Some(a), Some(b)) if a.claim != b.claim => format!(
+                    "Buckets '{}' and '{}' share path_prefix '{}' but route on different claims",
+                    other.name, bucket.name, bucket.path_prefix
+                ),
+                (Some(a), Some(b)) => {
+                    match a.values.iter().find(|value| b.values.contains(value)) {
+                        Some(value) => format!(
+                            "Tenant '{}' is routed to both '{}' and '{}'",
+                            value, other.name, bucket.name
+                        ),
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            };
+            errors.push(FieldError::new(format!("{}.tenant", at), message));
+        }
+    }
+}
+
+/// Reject combinators without any authorizer, at any depth, and
+/// authorizers with invalid URLs
+fn validate_authz(bucket: &str, authz: &AuthzConfig, path: &str, errors: &mut Vec<FieldError>) {
+    match authz {
+        AuthzConfig::AllOf { authorizers } | AuthzConfig::AnyOf { authorizers } => {
+            if authorizers.is_empty() {
+                errors.push(FieldError::new(
+                    format!("{}.authorizers", path),
+                    format!(
+                        "Bucket '{}' authz {} needs at least one authorizer",
+                        bucket,
+                        authz.kind()
+                    ),
+                ));
+            }
+            for (i, authorizer) in authorizers.iter().enumerate() {
+                validate_authz(
+                    bucket,
+                    authorizer,
+                    &format!("{}.authorizers[{}]", path, i),
+                    errors,
+                );
+            }
+        }
+        AuthzConfig::Opa(OpaAuthzConfig {
+            url,
+            cache_ttl_secs,
+            deny_cache_ttl_secs,
+            ..
+        })
+        | AuthzConfig::OpenFga(OpenFgaAuthzConfig {
+            url,
+            cache_ttl_secs,
+            deny_cache_ttl_secs,
+            ..
+        }) => {
+            if let (Some(allow), Some(deny)) = (cache_ttl_secs, deny_cache_ttl_secs) {
+                if deny > allow {
+                    errors.push(FieldError::new(
+                        format!("{}.deny_cache_ttl_secs", path),
+                        format!(
+                            "Bucket '{}' authz {} deny_cache_ttl_secs ({}) must not exceed cache_ttl_secs ({})",
+                            bucket,
+                            authz.kind(),
+                            deny,
+                            allow
+                        ),
+                    ));
+                }
+            }
+            let bundle = match authz {
+                AuthzConfig::Opa(OpaAuthzConfig {
+                    bundle: Some(bundle),
+                    ..
+                }) => Some(bundle),
+                _ => None,
+            };
+            if bundle.is_none() && !is_valid_http_url(url) {
+                errors.push(FieldError::new(
+                    format!("{}.url", path),
+                    format!(
+                        "Bucket '{}' authz {} URL '{}' must start with http:// or https://",
+                        bucket,
+                        authz.kind(),
+                        url
+                    ),
+                ));
+            }
+            if let Some(bundle) = bundle {
+                validate_opa_bundle(bucket, bundle, &format!("{}.bundle", path), errors);
+            }
+            if let AuthzConfig::Opa(OpaAuthzConfig {
+                decision_logs: Some(OpaDecisionLogConfig { url: Some(sink) }),
+                ..
+            }) = authz
+            {
+                if !is_valid_http_url(sink) {
+                    errors.push(FieldError::new(
+                        format!("{}.decision_logs.url", path),
+                        format!(
+                            "Bucket '{}' OPA decision log URL '{}' must start with http:// or https://",
+                            bucket, sink
+                        ),
+                    ));
+                }
+            }
+        }
+        AuthzConfig::Custom { name } => {
+            if name.is_empty() {
+                errors.push(FieldError::new(
+                    format!("{}.name", path),
+                    format!("Bucket '{}' custom authorizer needs a name", bucket),
+                ));
+            }
+        }
+        AuthzConfig::Static(_) => {}
+    }
+}
+
+/// Require exactly one bundle source, and an HTTP(S) URL
+fn validate_opa_bundle(
+    bucket: &str,
+    bundle: &OpaBundleConfig,
+    path: &str,
+    errors: &mut Vec<FieldError>,
+) {
+    match (&bundle.path, &bundle.url) {
+        (Some(_), None) => {}
+        (None, Some(url)) => {
+            if !is_valid_http_url(url) {
+                errors.push(FieldError::new(
+                    format!("{}.url", path),
+                    format!(
+                        "Bucket '{}' OPA bundle URL '{}' must start with http:// or https://",
+                        bucket, url
+                    ),
+                ));
+            }
+        }
+        _ => errors.push(FieldError::new(
+            path.to_string(),
+            format!(
+                "Bucket '{}' OPA bundle needs exactly one of path and url",
+                bucket
+            ),
+        )),
+    }
+    if bundle.reload_secs == Some(0) {
+        errors.push(FieldError::new(
+            format!("{}.reload_secs", path),
+            format!(
+                "Bucket '{}' OPA bundle reload_secs must be at least 1",
+                bucket
+            ),
+        ));
+    }
+}
+
+/// Reject S3 endpoints that are not HTTP(S) URLs
+fn validate_endpoint(s3: &S3Config, path: &str, errors: &mut Vec<FieldError>) {
+    if let Some(ref endpoint) = s3.endpoint {
+        if !is_valid_http_url(endpoint) {
+            errors.push(FieldError::new(
+                format!("{}.endpoint", path),
+                format!(
+                    "Invalid S3 endpoint '{}': must start with http:// or https://",
+                    endpoint
+                ),
+            ));
+        }
+    }
+}
+
+fn validate_listeners(config: &Config, errors: &mut Vec<FieldError>) {
+    let server = &config.server;
+    validate_tcp(&server.tcp, "server.tcp", errors);
+    if server.buffer_pool.enabled && server.zero_copy.pipe_buffer_size == 0 {
+        errors.push(FieldError::new(
+            "server.zero_copy.pipe_buffer_size",
+            "server.zero_copy.pipe_buffer_size must be greater than 0, as it sizes pooled buffers",
+        ));
+    }
+    if server.listeners.is_empty() {
+        if server.address.is_empty() {
+            errors.push(FieldError::new(
+                "server.address",
+                "server.address is required unless server.listeners is set",
+            ));
+        }
+        return;
+    }
+    if !server.address.is_empty() || server.tls.is_some() {
+        errors.push(FieldError::new(
+            "server.listeners",
+            "server.listeners replaces server.address and server.tls; set one or the other",
+        ));
+    }
+
+    let mut names = Vec::new();
+    for (i, listener) in server.listeners.iter().enumerate() {
+        let at = format!("server.listeners[{}]", i);
+        if listener.name.is_empty()
+            || listener.name == crate::server::systemd::ADMIN_SOCKET_NAME
+            || names.contains(&listener.name.as_str())
+        {
+            errors.push(FieldError::new(
+                format!("{}.name", at),
+                format!(
+                    "Listener name '{}' is empty, reserved or not unique",
+                    listener.name
+                ),
+            ));
+        }
+        names.push(&listener.name);
+        if let Some(ref tcp) = listener.tcp {
+            validate_tcp(tcp, &format!("{}.tcp", at), errors);
+        }
+        if let Err(e) = listener.address.parse::<SocketAddr>() {
+            errors.push(FieldError::new(
+                format!("{}.address", at),
+                format!(
+                    "Listener '{}' address {:?} is invalid: {}",
+                    listener.name, listener.address, e
+                ),
+            ));
+        }
+        for (j, bucket) in listener.buckets.iter().enumerate() {
+            if !config.buckets.iter().any(|b| &b.name == bucket) {
+                errors.push(FieldError::new(
+                    format!("{}.buckets[{}]", at, j),
+                    format!(
+                        "Listener '{}' serves unknown bucket '{}'",
+                        listener.name, bucket
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+fn validate_tcp(tcp: &TcpConfig, path: &str, errors: &mut Vec<FieldError>) {
+    let sizes = [
+        ("recv_buffer_size", tcp.recv_buffer_size),
+        ("send_buffer_size", tcp.send_buffer_size),
+    ];
+    for (name, size) in sizes {
+        if size == Some(0) {
+            errors.push(FieldError::new(
+                format!("{}.{}", path, name),
+                format!("{}.{} must be greater than 0", path, name),
+            ));
+        }
+    }
+    if tcp.backlog == 0 {
+        errors.push(FieldError::new(
+            format!("{}.backlog", path),
+            format!("{}.backlog must be greater than 0", path),
+        ));
+    }
+    if tcp.keepalive_secs.is_none()
+        && (tcp.keepalive_interval_secs.is_some() || tcp.keepalive_retries.is_some())
+    {
+        errors.push(FieldError::new(
+            format!("{}.keepalive_secs", path),
+            format!(
+                "{} sets keepalive probes but not keepalive_secs, so keepalive is off",
+                path
+            ),
+        ));
+    }
+}
+
+fn validate_regions(
+    bucket: &BucketConfig,
+    regions: &RegionRoutingConfig,
+    path: &str,
+    errors: &mut Vec<FieldError>,
+) {
+    if regions.targets.is_empty() {
+        errors.push(FieldError::new(
+            format!("{}.targets", path),
+            format!(
+                "Bucket '{}' routes between regions but has no targets",
+                bucket.name
+            ),
+        ));
+    }
+    if regions.tag.is_empty() {
+        errors.push(FieldError::new(
+            format!("{}.tag", path),
+            format!("Bucket '{}' region tag must not be empty", bucket.name),
+        ));
+    }
+    let mut names = vec![bucket.s3.region.as_str()];
+    for (i, target) in regions.targets.iter().enumerate() {
+        let at = format!("{}.targets[{}]", path, i);
+        if target.name.is_empty() || names.contains(&target.name.as_str()) {
+            errors.push(FieldError::new(
+                format!("{}.name", at),
+                format!(
+                    "Bucket '{}' region name '{}' is empty or not unique \
+                     (the home region is named by s3.region)",
+                    bucket.name, target.name
+                ),
+            ));
+        }
+        names.push(&target.name);
+        validate_endpoint(&target.s3, &format!("{}.s3", at), errors);
+        for cidr in &target.cidrs {
+            if cidr.parse::<ipnet::IpNet>().is_err() {
+                errors.push(FieldError::new(
+                    format!("{}.cidrs", at),
+                    format!("Bucket '{}' has an invalid CIDR '{}'", bucket.name, cidr),
+                ));
+            }
+        }
+    }
+}
+
+/// Authorization backend for a bucket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuthzConfig {
+    /// Rules evaluated in-process, no external policy service
+    Static(StaticPolicyConfig),
+    /// Open Policy Agent server
+    Opa(OpaAuthzConfig),
+    /// OpenFGA relationship checks
+    #[serde(rename = "openfga")]
+    OpenFga(OpenFgaAuthzConfig),
+    /// Allowed only if every listed authorizer allows
+    AllOf { authorizers: Vec<AuthzConfig> },
+    /// Allowed if any listed authorizer allows
+    AnyOf { authorizers: Vec<AuthzConfig> },
+    /// Authorizer registered in code with `Server::register_authorizer`
+    Custom { name: String },
+}
+
+impl AuthzConfig {
+    /// Configured type name, as written in YAML
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AuthzConfig::Static(_) => "static",
+            AuthzConfig::Opa(_) => "opa",
+            AuthzConfig::OpenFga(_) => "openfga",
+            AuthzConfig::AllOf { .. } => "all_of",
+            AuthzConfig::AnyOf { .. } => "any_of",
+            AuthzConfig::Custom { .. } => "custom",
+        }
+    }
+}
+
+/// OPA authorizer configuration
+///
+/// The policy receives `subject`, `action`, `resource` and the request
+/// context (mapped claims and `object`) as its input.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpaAuthzConfig {
+    /// OPA server URL (e.g. "http://localhost:8181"), unused with `bundle`
+    #[serde(default)]
+    pub url: String,
+    /// Policy path (e.g. "mizuchi/allow"), the entrypoint with `bundle`
+    pub policy_path: String,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// Decision cache TTL (no caching when unset)
+    #[serde(default)]
+    pub cache_ttl_secs: Option<u64>,
+    /// Decision cache TTL of denials (`cache_ttl_secs` when unset, not
+    /// cached when 0)
+    #[serde(default)]
+    pub deny_cache_ttl_secs: Option<u64>,
+    /// Decision IDs on every query, optionally pushed to a decision log sink
+    #[serde(default)]
+    pub decision_logs: Option<OpaDecisionLogConfig>,
+    /// Policy bundle evaluated in-process instead of on the OPA server
+    /// (requires the `opa-wasm` feature)
+    #[serde(default)]
+    pub bundle: Option<OpaBundleConfig>,
+}
+
+/// Compiled OPA policy bundle
+///
+/// One of `path` and `url` names the bundle: the `.tar.gz` written by
+/// `opa build -t wasm`, or a bare `policy.wasm`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OpaBundleConfig {
+    /// Bundle file on disk
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Bundle endpoint (e.g. "https://bundles.example.com/mizuchi.tar.gz")
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Check the bundle for changes this often (loaded once when unset)
+    #[serde(default)]
+    pub reload_secs: Option<u64>,
+}
+
+/// OPA decision logging
+///
+/// Every query carries a decision ID (`input.decision_id`) that is recorded
+/// in upload receipts. With `url`, decisions are also pushed there in the
+/// format of OPA's decision log API.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OpaDecisionLogConfig {
+    /// Decision log sink (e.g. "https://logs.example.com/logs")
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+/// OpenFGA authorizer configuration
+///
+/// Uploads are checked as `user:{subject}` against the object chosen by
+/// `object_mapping`, with the relation derived from the action (`writer`
+/// for uploads).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenFgaAuthzConfig {
+    /// OpenFGA server URL (e.g. "http://localhost:8080")
+    pub url: String,
+    pub store_id: String,
+    /// Authorization model (latest when unset)
+    #[serde(default)]
+    pub authorization_model_id: Option<String>,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// Decision cache TTL (no caching when unset)
+    #[serde(default)]
+    pub cache_ttl_secs: Option<u64>,
+    /// Decision cache TTL of denials (`cache_ttl_secs` when unset, not
+    /// cached when 0)
+    #[serde(default)]
+    pub deny_cache_ttl_secs: Option<u64>,
+    #[serde(default)]
+    pub object_mapping: OpenFgaObjectMapping,
+    /// API checks are sent over (`grpc` takes OpenFGA's gRPC `url`)
+    #[serde(default)]
+    pub transport: OpenFgaTransport,
+}
+
+/// API an OpenFGA authorizer sends checks over
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OpenFgaTransport {
+    /// HTTP API (port 8080 by default)
+    #[default]
+    Http,
+    /// gRPC API (port 8081 by default), requires the `openfga-grpc` feature
+    Grpc,
+}
+
+/// OpenFGA object an upload is checked against
+///
+/// `folder` and `object` checks carry contextual tuples linking each object
+/// to its folder, each folder to its parent folder and top-level folders to
+/// their bucket through the `parent` relation, so the model can inherit
+/// permissions (e.g. `define writer: [user] or writer from parent`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OpenFgaObjectMapping {
+    /// `bucket:{resource}`, whole-bucket access
+    #[default]
+    Bucket,
+    /// `folder:{bucket}/{prefix}`, the folder holding the key
+    Folder,
+    /// `object:{bucket}/{key}`, the key itself
+    Object,
+}
+
+/// Static authorization policy
+///
+/// Rules are evaluated in order and the first matching rule decides. When
+/// no rule matches, `default` applies.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StaticPolicyConfig {
+    #[serde(default)]
+    pub rules: Vec<PolicyRule>,
+    #[serde(default)]
+    pub default: PolicyEffect,
+}
+
+/// A static policy rule
+///
+/// Empty lists match anything. Subject and bucket patterns may use `*` as
+/// a wildcard.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyRule {
+    #[serde(default)]
+    pub subjects: Vec<String>,
+    #[serde(default)]
+    pub buckets: Vec<String>,
+    /// Object key prefix; `{subject}` is replaced by the request subject
+    #[serde(default)]
+    pub key_prefix: Option<String>,
+    /// Actions such as "upload"
+    #[serde(default)]
+    pub actions: Vec<String>,
+    pub effect: PolicyEffect,
+}
+
+/// Outcome of a policy rule
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyEffect {
+    Allow,
+    #[default]
+    Deny,
+}
+
+/// Failover to a secondary S3 endpoint when the primary is unhealthy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailoverConfig {
+    /// Fallback S3 target (typically the same bucket behind another endpoint)
+    pub target: S3Config,
+    /// Consecutive 5xx/timeout failures that trip the circuit breaker
+    #[serde(default = "default_failover_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Seconds between probes of a tripped primary endpoint
+    #[serde(default = "default_failover_probe_interval")]
+    pub probe_interval_secs: u64,
+}
+
+fn default_failover_failure_threshold() -> u32 {
+    5
+}
+
+fn default_failover_probe_interval() -> u64 {
+    30
+}
+
+/// Routing of uploads between regional S3 targets
+///
+/// The bucket's own `s3` target is the home region, named by its `region`.
+/// With the `cidr` strategy, an upload goes to the first target listing a
+/// network that contains the client's address, or home if none does. With
+/// `latency`, it goes to the region (home included) with the lowest
+/// observed upload latency. Objects are tagged with the region they were
+/// stored in.
+///
+/// ```yaml
+/// regions:
+///   strategy: cidr
+///   targets:
+///     - name: eu-west-1
+///       cidrs: ["10.20.0.0/16", "2001:db8:20::/48"]
+///       s3:
+///         bucket: uploads-eu
+///         region: eu-west-1
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionRoutingConfig {
+    #[serde(default)]
+    pub strategy: RegionStrategy,
+    /// Regions besides the home region
+    pub targets: Vec<RegionTargetConfig>,
+    /// Object tag holding the name of the region an object was stored in
+    #[serde(default = "default_region_tag")]
+    pub tag: String,
+}
+
+/// Regional S3 target
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionTargetConfig {
+    /// Region name, used in the object tag and metrics
+    pub name: String,
+    pub s3: S3Config,
+    /// Client networks routed here with the `cidr` strategy
+    #[serde(default)]
+    pub cidrs: Vec<String>,
+}
+
+/// How uploads pick a region
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RegionStrategy {
+    /// By the client's address, from each target's `cidrs`
+    #[default]
+    Cidr,
+    /// By the lowest observed upload latency
+    Latency,
+}
+
+fn default_region_tag() -> String {
+    "origin-region".to_string()
+}
+
+/// Replication to a secondary S3 target
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicationConfig {
+    /// Secondary bucket (typically another region or endpoint)
+    pub target: S3Config,
+    #[serde(default)]
+    pub mode: ReplicationMode,
+    /// Maximum replica writes waiting in the async queue
+    #[serde(default = "default_replication_queue_capacity")]
+    pub queue_capacity: usize,
+    /// Retries for each async replica write before it is dead-lettered
+    #[serde(default = "default_replication_max_retries")]
+    pub max_retries: u32,
+    /// File receiving one JSON line per object that failed to replicate (async mode)
+    #[serde(default)]
+    pub dead_letter_path: Option<String>,
+}
+
+/// Replication mode
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplicationMode {
+    /// Write both copies before responding; fail if either write fails
+    #[default]
+    Sync,
+    /// Respond after the primary write; replicate in the background
+    Async,
+}
+
+fn default_replication_queue_capacity() -> usize {
+    1000
+}
+
+fn default_replication_max_retries() -> u32 {
+    3
+}
+
+/// Storage backend selection
+///
+/// Defaults to S3. The local backend writes objects under `root` and is
+/// meant for development and testing only.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type")]
+pub enum StorageConfig {
+    #[default]
+    #[serde(rename = "s3")]
+    S3,
+    #[serde(rename = "local")]
+    Local { root: String },
+}
+
+/// S3 backend configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct S3Config {
+    pub bucket: String,
+    pub region: String,
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    #[serde(default)]
+    pub access_key: Option<String>,
+    #[serde(default)]
+    pub secret_key: Option<String>,
+    /// File holding `access_key` (e.g. a mounted Kubernetes secret)
+    #[serde(default)]
+    pub access_key_file: Option<String>,
+    /// File holding `secret_key`
+    #[serde(default)]
+    pub secret_key_file: Option<String>,
+    /// Outbound connection pool tuning (shared by buckets on the same endpoint)
+    #[serde(default)]
+    pub pool: Option<S3PoolConfig>,
+    /// Sign outbound requests with SigV4A for this region set (e.g. `*`),
+    /// as required by S3 Multi-Region Access Points
+    #[serde(default)]
+    pub sigv4a_region_set: Option<String>,
+    /// Send a second PutObject for small objects when the first is slow
+    #[serde(default)]
+    pub hedging: Option<S3HedgingConfig>,
+    /// How payloads are hashed for signing (optional, uses defaults if not
+    /// specified)
+    #[serde(default)]
+    pub hashing: Option<S3HashingConfig>,
+    /// Checksum S3 verifies for each multipart part (optional, CRC32C if
+    /// not specified)
+    #[serde(default)]
+    pub checksum: Option<S3ChecksumConfig>,
+    /// Log requests to the backend and its responses to a file of their
+    /// own, for debugging (optional, disabled if not specified)
+    #[serde(default)]
+    pub debug_log: Option<S3DebugLogConfig>,
+}
+
+impl S3Config {
+    /// Create the settings for `bucket` in `region`, with no endpoint
+    /// override or static credentials
+    pub fn new(bucket: &str, region: &str) -> Self {
+        Self {
+            bucket: bucket.to_string(),
+            region: region.to_string(),
+            endpoint: None,
+            access_key: None,
+            secret_key: None,
+            access_key_file: None,
+            secret_key_file: None,
+            pool: None,
+            sigv4a_region_set: None,
+            hedging: None,
+            hashing: None,
+            checksum: None,
+            debug_log: None,
+        }
+    }
+}
+
+/// Outbound HTTP connection pool settings for an S3 endpoint
+///
+/// Buckets with the same endpoint and identical settings share one pool.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct S3PoolConfig {
+    /// Maximum idle connections kept per host
+    #[serde(default = "default_pool_max_idle_per_host")]
+    pub max_idle_per_host: usize,
+    /// Close idle connections after this many milliseconds
+    #[serde(default = "default_pool_idle_timeout_ms")]
+    pub idle_timeout_ms: u64,
+    /// Use HTTP/2 (ALPN over https, prior knowledge over plain http)
+    #[serde(default)]
+    pub http2: bool,
+    /// TCP keepalive interval in milliseconds (disabled when unset)
+    #[serde(default = "default_pool_tcp_keepalive_ms")]
+    pub tcp_keepalive_ms: Option<u64>,
+    /// Milliseconds between unanswered keepalive probes
+    #[serde(default)]
+    pub tcp_keepalive_interval_ms: Option<u64>,
+    /// Unanswered keepalive probes before a connection is dropped
+    #[serde(default)]
+    pub tcp_keepalive_retries: Option<u32>,
+    /// Disable Nagle's algorithm
+    #[serde(default = "default_pool_tcp_nodelay")]
+    pub tcp_nodelay: bool,
+    /// Resolve the endpoint with a caching resolver instead of the system
+    /// one
+    #[serde(default)]
+    pub dns: Option<S3DnsConfig>,
+}
+
+impl Default for S3PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_idle_per_host: default_pool_max_idle_per_host(),
+            idle_timeout_ms: default_pool_idle_timeout_ms(),
+            http2: false,
+            tcp_keepalive_ms: default_pool_tcp_keepalive_ms(),
+            tcp_keepalive_interval_ms: None,
+            tcp_keepalive_retries: None,
+            tcp_nodelay: default_pool_tcp_nodelay(),
+            dns: None,
+        }
+    }
+}
+
+fn default_pool_max_idle_per_host() -> usize {
+    64
+}
+
+fn default_pool_idle_timeout_ms() -> u64 {
+    90_000
+}
+
+fn default_pool_tcp_keepalive_ms() -> Option<u64> {
+    Some(60_000)
+}
+
+fn default_pool_tcp_nodelay() -> bool {
+    true
+}
+
+/// DNS settings for outbound S3 connections
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct S3DnsConfig {
+    /// Seconds a lookup is reused for (0 to look up every connection)
+    #[serde(default = "default_dns_cache_ttl_secs")]
+    pub cache_ttl_secs: u64,
+    /// Fixed addresses for hostnames, bypassing DNS
+    #[serde(default)]
+    pub overrides: BTreeMap<String, Vec<IpAddr>>,
+    /// Race IPv6 and IPv4 connections (Happy Eyeballs); when disabled only
+    /// the family of the first resolved address is used
+    #[serde(default = "default_dns_happy_eyeballs")]
+    pub happy_eyeballs: bool,
+}
+
+impl Default for S3DnsConfig {
+    fn default() -> Self {
+        Self {
+            cache_ttl_secs: default_dns_cache_ttl_secs(),
+            overrides: BTreeMap::new(),
+            happy_eyeballs: default_dns_happy_eyeballs(),
+        }
+    }
+}
+
+fn default_dns_cache_ttl_secs() -> u64 {
+    60
+}
+
+fn default_dns_happy_eyeballs() -> bool {
+    true
+}
+
+/// Hedged PutObject settings
+///
+/// A PutObject of at most `max_object_size` bytes that has not completed
+/// after the `percentile` latency of recent PutObjects (clamped to
+/// `min_delay_ms..=max_delay_ms`) is sent again, and whichever attempt
+/// completes first wins. Overwriting an object with the same body is
+/// idempotent, so the duplicate is harmless.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct S3HedgingConfig {
+    /// Largest object hedged, in bytes
+    #[serde(default = "default_hedging_max_object_size")]
+    pub max_object_size: u64,
+    /// Latency percentile of recent PutObjects to wait before hedging
+    #[serde(default = "default_hedging_percentile")]
+    pub percentile: u8,
+    /// Shortest wait before hedging, in milliseconds
+    #[serde(default = "default_hedging_min_delay_ms")]
+    pub min_delay_ms: u64,
+    /// Longest wait before hedging, in milliseconds; also used until enough
+    /// latencies are measured
+    #[serde(default = "default_hedging_max_delay_ms")]
+    pub max_delay_ms: u64,
+}
+
+/// Payload hashing settings of an S3 bucket
+///
+/// PutObject bodies of at least `offload_threshold_bytes` are hashed on the
+/// blocking thread pool, so hashing large bodies does not hold up other
+/// requests on the same runtime worker.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct S3HashingConfig {
+    /// Smallest body hashed on the blocking thread pool, in bytes
+    #[serde(default = "default_hashing_offload_threshold")]
+    pub offload_threshold_bytes: u64,
+}
+
+impl Default for S3HashingConfig {
+    fn default() -> Self {
+        Self {
+            offload_threshold_bytes: default_hashing_offload_threshold(),
+        }
+    }
+}
+
+fn default_hashing_offload_threshold() -> u64 {
+    crate::s3::hashing::DEFAULT_OFFLOAD_THRESHOLD
+}
+
+/// Part checksum settings of an S3 bucket
+///
+/// Parts of multipart uploads are sent with a checksum of `algorithm`,
+/// which S3 verifies before storing the part and again when the upload
+/// completes. CRC32C unless configured otherwise; `none` opts out for
+/// S3-compatible backends that reject checksum headers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct S3ChecksumConfig {
+    #[serde(default)]
+    pub algorithm: ChecksumAlgorithm,
+}
+
+/// Checksum algorithm of multipart parts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumAlgorithm {
+    /// CRC32C (`x-amz-checksum-crc32c`)
+    #[default]
+    Crc32c,
+    /// No checksum, for S3-compatible backends that do not support them
+    None,
+}
+
+/// Debug log of an S3 bucket's backend traffic
+///
+/// Every request to the backend is logged to a rolling file in `dir`: the
+/// request line and headers, with signatures and session tokens redacted,
+/// then the response status, headers and body. Meant for debugging
+/// signature mismatches against S3-compatible backends; payloads are never
+/// logged, but object keys are.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct S3DebugLogConfig {
+    /// Directory of the log files, `s3-<bucket>.<date>.log`
+    pub dir: String,
+    /// How often a new file is started
+    #[serde(default)]
+    pub rotation: DebugLogRotation,
+    /// Files kept, the oldest being deleted when a new one is started
+    #[serde(default = "default_debug_log_max_files")]
+    pub max_files: usize,
+    /// Longest response body logged, in bytes
+    #[serde(default = "default_debug_log_max_body_bytes")]
+    pub max_body_bytes: usize,
+}
+
+/// How often a debug log file is rotated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DebugLogRotation {
+    Hourly,
+    #[default]
+    Daily,
+    /// A single file that is never rotated
+    Never,
+}
+
+fn default_debug_log_max_files() -> usize {
+    7
+}
+
+fn default_debug_log_max_body_bytes() -> usize {
+    4096
+}
+
+impl Default for S3HedgingConfig {
+    fn default() -> Self {
+        Self {
+            max_object_size: default_hedging_max_object_size(),
+            percentile: default_hedging_percentile(),
+            min_delay_ms: default_hedging_min_delay_ms(),
+            max_delay_ms: default_hedging_max_delay_ms(),
+        }
+    }
+}
+
+fn default_hedging_max_object_size() -> u64 {
+    1024 * 1024
+}
+
+fn default_hedging_percentile() -> u8 {
+    99
+}
+
+fn default_hedging_min_delay_ms() -> u64 {
+    50
+}
+
+fn default_hedging_max_delay_ms() -> u64 {
+    2_000
+}
+
+/// Authentication configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuthConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub jwt: Option<JwtConfig>,
+    #[serde(default)]
     pub sigv4: Option<SigV4Config>,
+    #[serde(default)]
+    pub mtls: Option<MtlsConfig>,
+    #[serde(default)]
+    pub api_key: Option<ApiKeyConfig>,
+    /// Order and composition of the configured authenticators
+    #[serde(default)]
+    pub chain: AuthChainConfig,
+    /// Accept requests without credentials, subject to mandatory constraints
+    #[serde(default)]
+    pub allow_anonymous: Option<AnonymousConfig>,
+    /// Map token claims into the authorization context and key scope
+    #[serde(default)]
+    pub claim_mapping: Option<ClaimMappingConfig>,
+    /// Names of authenticators registered in code with
+    /// `Server::register_authenticator`, tried after the configured ones
+    #[serde(default)]
+    pub custom: Vec<String>,
+}
+
+/// Claim-to-authorization mapping
+///
+/// Claim names may use dots to reach nested claims (`realm.roles`).
+/// Placeholders in `key_prefix` and `tags` are claim names in braces, e.g.
+/// `tenant/{tenant_id}/`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClaimMappingConfig {
+    /// Authorization context entry name -> claim name
+    #[serde(default)]
+    pub context: std::collections::HashMap<String, String>,
+    /// Key prefix authenticated uploads must stay under
+    #[serde(default)]
+    pub key_prefix: Option<String>,
+    /// S3 object tag name -> value template, attached to every
+    /// authenticated upload (`{sub}` is the authenticated subject)
+    #[serde(default)]
+    pub tags: std::collections::HashMap<String, String>,
+    /// Claim holding the token's scopes, a space-separated string or a
+    /// list (default `scope`)
+    #[serde(default)]
+    pub scope_claim: Option<String>,
+    /// Actions granted by scopes; when set, authenticated requests need a
+    /// scope granting the action on the object key
+    #[serde(default)]
+    pub scopes: Vec<ScopeRule>,
+}
+
+/// An action on a key prefix granted by a token scope
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScopeRule {
+    /// Scope granting the action, e.g. `upload:images`
+    pub scope: String,
+    /// Granted action
+    #[serde(default = "default_scope_action")]
+    pub action: String,
+    /// Key prefix the action is granted on (none = any key)
+    #[serde(default)]
+    pub prefix: Option<String>,
+}
+
+fn default_scope_action() -> String {
+    "upload".into()
+}
+
+/// API key authentication configuration
+///
+/// Keys are never stored in plaintext: records hold the hex SHA-256 of the
+/// key, or HMAC-SHA256 keyed with `pepper` when one is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyConfig {
+    /// Request header carrying the key
+    #[serde(default = "default_api_key_header")]
+    pub header: String,
+    /// Server-side secret mixed into key hashes (HMAC-SHA256)
+    #[serde(default)]
+    pub pepper: Option<String>,
+    /// File holding `pepper`
+    #[serde(default)]
+    pub pepper_file: Option<String>,
+    /// Keys defined inline
+    #[serde(default)]
+    pub keys: Vec<ApiKeyRecord>,
+    /// External key store consulted after the inline keys
+    #[serde(default)]
+    pub store: Option<ApiKeyStoreConfig>,
+}
+
+fn default_api_key_header() -> String {
+    "x-api-key".into()
+}
+
+/// A hashed API key and its scopes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiKeyRecord {
+    /// Key identifier, reported as the authenticated subject
+    pub id: String,
+    /// Hex digest of the key
+    pub hash: String,
+    /// Buckets the key may upload to (empty = all)
+    #[serde(default)]
+    pub buckets: Vec<String>,
+    /// Object key prefixes the key may upload to (empty = all)
+    #[serde(default)]
+    pub prefixes: Vec<String>,
+}
+
+/// External API key store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ApiKeyStoreConfig {
+    /// YAML or JSON list of [`ApiKeyRecord`]s, reloaded when the file changes
+    File { path: String },
+    /// One JSON [`ApiKeyRecord`] per Redis string at `key_prefix` + hash
+    Redis {
+        url: String,
+        #[serde(default = "default_api_key_redis_prefix")]
+        key_prefix: String,
+    },
+}
+
+fn default_api_key_redis_prefix() -> String {
+    "mizuchi:apikey:".into()
+}
+
+/// Constraints for anonymous (public drop-box) uploads
+///
+/// Every field is required so a public endpoint cannot be opened up without
+/// limits by accident.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnonymousConfig {
+    /// Maximum upload size in bytes
+    pub max_size: u64,
+    /// Accepted content types; `type/*` matches any subtype
+    pub allowed_content_types: Vec<String>,
+    /// Anonymous uploads must use keys under this prefix
+    pub key_prefix: String,
+    /// Per-client-IP request rate limit
+    pub rate_limit: RateLimitConfig,
+}
+
+/// Token bucket rate limit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    /// Sustained request rate
+    pub requests_per_minute: u32,
+    /// Requests allowed in a burst (defaults to `requests_per_minute`)
+    #[serde(default)]
+    pub burst: Option<u32>,
+}
+
+/// Authenticator chain configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuthChainConfig {
+    #[serde(default)]
+    pub mode: AuthChainMode,
+    /// Authenticators to try, in order. Empty means every configured
+    /// authenticator in the order mtls, sigv4, jwt, api_key.
+    #[serde(default)]
+    pub order: Vec<AuthMethod>,
+}
+
+/// How the results of chained authenticators are combined
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthChainMode {
+    /// The first authenticator that accepts the request wins
+    #[default]
+    FirstSuccess,
+    /// Every authenticator must accept the request
+    AllMustPass,
+}
+
+/// Authenticator that can appear in an auth chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    Mtls,
+    Sigv4,
+    Jwt,
+    ApiKey,
+    /// Accepts every request as the `anonymous` subject
+    Anonymous,
+}
+
+impl AuthMethod {
+    /// Name used in logs and metric labels
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthMethod::Mtls => "mtls",
+            AuthMethod::Sigv4 => "sigv4",
+            AuthMethod::Jwt => "jwt",
+            AuthMethod::ApiKey => "api_key",
+            AuthMethod::Anonymous => "anonymous",
+        }
+    }
+}
+
+/// mTLS client-certificate authentication configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MtlsConfig {
+    /// PEM CA bundle that client certificates must chain to
+    pub ca_path: String,
+    /// PEM CRLs checked for revoked client certificates
+    #[serde(default)]
+    pub crl_paths: Vec<String>,
+    /// Certificate field used as the authenticated subject
+    #[serde(default)]
+    pub subject: CertSubjectSource,
+    /// Check client certificates with their CA's OCSP responder (optional,
+    /// disabled if not specified)
+    #[serde(default)]
+    pub ocsp: Option<MtlsOcspConfig>,
+}
+
+/// OCSP checks of mTLS client certificates
+///
+/// The status of each client certificate is asked of `responder_url`, or
+/// of the responder named in the certificate's Authority Information
+/// Access extension, and cached until the response's `nextUpdate` (at most
+/// `cache_secs`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MtlsOcspConfig {
+    /// Responder asked instead of the one named in certificates
+    #[serde(default)]
+    pub responder_url: Option<String>,
+    #[serde(default = "default_ocsp_timeout_ms")]
+    pub timeout_ms: u64,
+    #[serde(default = "default_ocsp_cache_secs")]
+    pub cache_secs: u64,
+    /// Accept certificates whose status cannot be learned (responder
+    /// unreachable, or certificate unknown to it); revoked certificates are
+    /// rejected either way
+    #[serde(default)]
+    pub soft_fail: bool,
+}
+
+fn default_ocsp_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_ocsp_cache_secs() -> u64 {
+    300
+}
+
+/// Where the auth subject is taken from in a client certificate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CertSubjectSource {
+    /// First subject alternative name, prefixed with its type (`uri:`,
+    /// `dns:` or `email:`)
+    #[default]
+    San,
+    /// Subject common name
+    Cn,
+}
+
+/// JWT configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwtConfig {
+    pub secret: Option<String>,
+    /// File holding `secret`
+    #[serde(default)]
+    pub secret_file: Option<String>,
+    pub algorithm: String,
+    #[serde(default)]
+    pub jwks_url: Option<String>,
+    #[serde(default)]
+    pub token_sources: Vec<TokenSource>,
+    /// Exchange accepted tokens at an IdP for a token scoped to this proxy
+    #[serde(default)]
+    pub token_exchange: Option<TokenExchangeConfig>,
+    /// Reject revoked tokens before they expire
+    #[serde(default)]
+    pub revocation: Option<JwtRevocationConfig>,
+}
+
+/// JWT revocation list
+///
+/// Tokens are rejected when their `jti` is listed, or when their subject has
+/// a "not issued before" cutoff later than their `iat`. The list is loaded
+/// from `store` and refreshed every `refresh_secs`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwtRevocationConfig {
+    pub store: RevocationStoreConfig,
+    #[serde(default = "default_revocation_refresh")]
+    pub refresh_secs: u64,
 }
 
-/// JWT configuration
+fn default_revocation_refresh() -> u64 {
+    30
+}
+
+/// Where the JWT revocation list is loaded from
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct JwtConfig {
-    pub secret: Option<String>,
-    pub algorithm: String,
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RevocationStoreConfig {
+    /// YAML or JSON document with `jtis` (revoked token IDs) and
+    /// `not_before` (subject to Unix time cutoff)
+    File { path: String },
+    /// Set `<key_prefix>jti` of revoked token IDs and hash
+    /// `<key_prefix>not_before` of subject cutoffs
+    Redis {
+        url: String,
+        #[serde(default = "default_revocation_redis_prefix")]
+        key_prefix: String,
+    },
+}
+
+fn default_revocation_redis_prefix() -> String {
+    "mizuchi:revoked:".into()
+}
+
+/// OAuth 2.0 token exchange (RFC 8693) of accepted JWTs
+///
+/// The client's token is sent as the `subject_token`; the proxy
+/// authenticates to the token endpoint with `client_id` and
+/// `client_secret` (HTTP Basic).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenExchangeConfig {
+    /// IdP token endpoint
+    pub token_endpoint: String,
+    pub client_id: String,
     #[serde(default)]
-    pub jwks_url: Option<String>,
+    pub client_secret: Option<String>,
+    /// File holding `client_secret`
     #[serde(default)]
-    pub token_sources: Vec<TokenSource>,
+    pub client_secret_file: Option<String>,
+    /// `audience` of the requested token
+    #[serde(default)]
+    pub audience: Option<String>,
+    /// `scope` of the requested token
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// Per-request timeout in milliseconds. Default: 5000
+    #[serde(default = "default_token_exchange_timeout")]
+    pub timeout_ms: u64,
+}
+
+fn default_token_exchange_timeout() -> u64 {
+    5000
+}
+
+/// Token source configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum TokenSource {
+    #[serde(rename = "bearer")]
+    Bearer,
+    #[serde(rename = "query")]
+    Query { name: String },
+    #[serde(rename = "header")]
+    Header { name: String },
+}
+
+/// SigV4 configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigV4Config {
+    pub service: String,
+    pub region: String,
+    /// Access keys accepted from clients
+    #[serde(default)]
+    pub credentials: Vec<SigV4Credential>,
+    /// External credential store consulted after the inline credentials
+    #[serde(default)]
+    pub store: Option<SigV4StoreConfig>,
+    /// Rejection of signed requests that were already accepted once
+    #[serde(default)]
+    pub replay_protection: SigV4ReplayConfig,
+}
+
+/// SigV4 replay protection
+///
+/// Header-signed requests are remembered by access key, `x-amz-date` and
+/// signature for as long as the timestamp is within the allowed clock skew,
+/// and a second request with the same signature is rejected. Presigned URLs
+/// stay reusable until they expire.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigV4ReplayConfig {
+    #[serde(default = "default_replay_enabled")]
+    pub enabled: bool,
+    /// Signatures remembered in memory; the oldest are forgotten first
+    #[serde(default = "default_replay_max_entries")]
+    pub max_entries: usize,
+    /// Share seen signatures between instances through Redis
+    #[serde(default)]
+    pub redis: Option<SigV4ReplayRedisConfig>,
+}
+
+impl Default for SigV4ReplayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_entries: default_replay_max_entries(),
+            redis: None,
+        }
+    }
+}
+
+fn default_replay_enabled() -> bool {
+    true
+}
+
+fn default_replay_max_entries() -> usize {
+    100_000
+}
+
+/// Redis backing for SigV4 replay protection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigV4ReplayRedisConfig {
+    /// `redis://[user][:password@]host[:port][/db]`
+    pub url: String,
+    #[serde(default = "default_replay_redis_prefix")]
+    pub key_prefix: String,
+}
+
+fn default_replay_redis_prefix() -> String {
+    "mizuchi:sigv4:seen:".into()
+}
+
+/// External store of SigV4 client credentials
+///
+/// SigV4 signatures are verified by recomputing them, so stores must hold
+/// the secret keys themselves rather than hashes of them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SigV4StoreConfig {
+    /// YAML or JSON list of [`SigV4Credential`]s, reloaded when the file changes
+    File { path: String },
+    /// HashiCorp Vault KV v2 secret at `<mount>/data/<path_prefix><access key>`,
+    /// holding a `secret_key` field
+    Vault {
+        /// Vault address, e.g. `https://vault.internal:8200`
+        address: String,
+        /// Vault token, required unless `token_file` is set
+        #[serde(default)]
+        token: String,
+        /// File holding `token`
+        #[serde(default)]
+        token_file: Option<String>,
+        #[serde(default = "default_vault_mount")]
+        mount: String,
+        #[serde(default = "default_sigv4_secret_prefix")]
+        path_prefix: String,
+        #[serde(default = "default_sigv4_cache_ttl")]
+        cache_ttl_secs: u64,
+    },
+    /// AWS Secrets Manager secret named `<secret_prefix><access key>`, holding
+    /// the secret key or a JSON object with a `secret_key` field. Requests to
+    /// Secrets Manager are signed with the `AWS_*` environment credentials.
+    SecretsManager {
+        region: String,
+        /// Endpoint override (e.g. a VPC endpoint or LocalStack)
+        #[serde(default)]
+        endpoint: Option<String>,
+        #[serde(default = "default_sigv4_secret_prefix")]
+        secret_prefix: String,
+        #[serde(default = "default_sigv4_cache_ttl")]
+        cache_ttl_secs: u64,
+    },
+}
+
+fn default_vault_mount() -> String {
+    "secret".into()
+}
+
+fn default_sigv4_secret_prefix() -> String {
+    "mizuchi/sigv4/".into()
+}
+
+fn default_sigv4_cache_ttl() -> u64 {
+    300
+}
+
+/// Client access key accepted by SigV4 authentication
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigV4Credential {
+    pub access_key: String,
+    pub secret_key: String,
+}
+
+/// Upload configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadConfig {
+    #[serde(default = "default_multipart_threshold")]
+    pub multipart_threshold: usize,
+    #[serde(default = "default_part_size")]
+    pub part_size: usize,
+    #[serde(default = "default_concurrent_parts")]
+    pub concurrent_parts: usize,
+    /// Periodic cleanup of orphaned multipart uploads (disabled if unset)
+    #[serde(default)]
+    pub reconciliation: Option<MultipartReconciliationConfig>,
+    /// Spill large or unsized request bodies to disk (buffered in memory if unset)
+    #[serde(default)]
+    pub spill: Option<SpillConfig>,
+    /// Stream large bodies to a multipart upload as they arrive, pausing
+    /// the client while too much is buffered (buffered whole if unset)
+    #[serde(default)]
+    pub flow_control: Option<FlowControlConfig>,
+    /// Content type allow-list and sniffing (any content type if unset)
+    #[serde(default)]
+    pub content_type: Option<ContentTypeConfig>,
+    /// WASM plugin run over every upload body (requires the `wasm` feature)
+    #[serde(default)]
+    pub transform: Option<TransformConfig>,
+    /// Compress objects before storing them (stored as sent if unset)
+    #[serde(default)]
+    pub compression: Option<CompressionConfig>,
+    /// Storage class rules, first match wins (bucket default if none match)
+    #[serde(default)]
+    pub storage_class_rules: Vec<StorageClassRule>,
+    /// Upload quotas per subject or tenant (unlimited if unset)
+    #[serde(default)]
+    pub quota: Option<QuotaConfig>,
+    /// Answer ListMultipartUploads (`GET {path_prefix}?uploads`) with the
+    /// caller's own uploads in progress
+    #[serde(default)]
+    pub list_uploads: bool,
+    /// Sign upload responses in `x-mizuchi-signature` (unsigned if unset)
+    #[serde(default)]
+    pub signing: Option<ResponseSigningConfig>,
+    /// Headers added to successful upload responses; values may use
+    /// `{{bucket}}`, `{{key}}`, `{{etag}}` and `{{size}}`
+    #[serde(default)]
+    pub response_headers: std::collections::HashMap<String, String>,
+    /// Record completed uploads for `GET /receipts` on the admin API
+    /// (not recorded if unset)
+    #[serde(default)]
+    pub receipts: Option<ReceiptConfig>,
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self {
+            multipart_threshold: default_multipart_threshold(),
+            part_size: default_part_size(),
+            concurrent_parts: default_concurrent_parts(),
+            reconciliation: None,
+            spill: None,
+            flow_control: None,
+            content_type: None,
+            transform: None,
+            compression: None,
+            storage_class_rules: Vec::new(),
+            quota: None,
+            list_uploads: false,
+            signing: None,
+            response_headers: std::collections::HashMap::new(),
+            receipts: None,
+        }
+    }
+}
+
+fn default_multipart_threshold() -> usize {
+    52428800 // 50MB
+}
+
+fn default_part_size() -> usize {
+    104857600 // 100MB
+}
+
+fn default_concurrent_parts() -> usize {
+    4
+}
+
+/// Content type enforcement for uploads
+///
+/// The declared `Content-Type` must match `allowed_content_types` (exact
+/// types or `type/*` wildcards; empty allows any type). With `sniff`, the
+/// first `sniff_bytes` bytes of the body are matched against known file
+/// signatures as well: a detected type must be allowed too, and must share
+/// the declared type's top-level type (`image`, `application`, ...).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentTypeConfig {
+    #[serde(default)]
+    pub allowed_content_types: Vec<String>,
+    #[serde(default)]
+    pub sniff: bool,
+    #[serde(default = "default_sniff_bytes")]
+    pub sniff_bytes: usize,
+    #[serde(default)]
+    pub enforce: ContentTypeEnforcement,
+}
+
+impl Default for ContentTypeConfig {
+    fn default() -> Self {
+        Self {
+            allowed_content_types: Vec::new(),
+            sniff: false,
+            sniff_bytes: default_sniff_bytes(),
+            enforce: ContentTypeEnforcement::default(),
+        }
+    }
+}
+
+fn default_sniff_bytes() -> usize {
+    8192
+}
+
+/// What to do with an upload whose content type is not allowed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentTypeEnforcement {
+    /// Log the violation and store the upload anyway
+    Log,
+    /// Reject the upload
+    #[default]
+    Reject,
+}
+
+/// WASM transform plugin
+///
+/// The module at `module` (`.wasm`, or `.wat` text) is run over each upload
+/// body, which it may rewrite or reject. Every run gets a fresh instance
+/// with at most `fuel` units of execution and `max_memory_bytes` of linear
+/// memory. Uploads larger than `max_input_bytes` are refused, since the
+/// whole body is handed to the plugin at once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransformConfig {
+    pub module: String,
+    #[serde(default = "default_transform_fuel")]
+    pub fuel: u64,
+    #[serde(default = "default_transform_max_memory")]
+    pub max_memory_bytes: usize,
+    #[serde(default = "default_transform_max_input")]
+    pub max_input_bytes: u64,
+}
+
+fn default_transform_fuel() -> u64 {
+    1_000_000_000
+}
+
+fn default_transform_max_memory() -> usize {
+    268435456 // 256MB
+}
+
+fn default_transform_max_input() -> u64 {
+    67108864 // 64MB
+}
+
+/// Compression of objects before storage
+///
+/// Bodies of at least `min_size` bytes whose content type is not in
+/// `exclude_content_types` (exact types or `type/*` wildcards) are
+/// compressed with `algorithm` and stored with a matching `Content-Encoding`
+/// and the original size in `x-amz-meta-mizuchi-original-size`. Bodies that
+/// would not shrink are stored as sent. `level` defaults to the
+/// algorithm's own default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressionConfig {
+    #[serde(default)]
+    pub algorithm: CompressionAlgorithm,
+    #[serde(default)]
+    pub level: Option<i32>,
+    #[serde(default = "default_compression_min_size")]
+    pub min_size: u64,
+    #[serde(default = "default_compression_exclude_content_types")]
+    pub exclude_content_types: Vec<String>,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: CompressionAlgorithm::default(),
+            level: None,
+            min_size: default_compression_min_size(),
+            exclude_content_types: default_compression_exclude_content_types(),
+        }
+    }
+}
+
+fn default_compression_min_size() -> u64 {
+    1024 // 1KB
+}
+
+/// Content types that are already compressed
+fn default_compression_exclude_content_types() -> Vec<String> {
+    [
+        "image/*",
+        "video/*",
+        "audio/*",
+        "application/zip",
+        "application/gzip",
+        "application/zstd",
+        "application/x-7z-compressed",
+        "application/x-bzip2",
+        "application/x-xz",
+    ]
+    .iter()
+    .map(|t| t.to_string())
+    .collect()
+}
+
+/// Compression algorithm, stored as the object's `Content-Encoding`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompressionAlgorithm {
+    #[default]
+    Gzip,
+    Zstd,
+}
+
+/// Signature of upload responses
+///
+/// Successful uploads answer with an `x-mizuchi-signature` header signing
+/// the stored object's bucket, key, ETag and size and the time of the
+/// upload, so services receiving the key from the client can verify the
+/// upload went through the proxy. `key` is the HMAC secret for
+/// `hmac_sha256`, or a PKCS#8 PEM private key for `ed25519`.
+///
+/// ```yaml
+/// upload:
+///   signing:
+///     algorithm: ed25519
+///     key: "${RESPONSE_SIGNING_KEY}"
+///     key_id: "2024-06"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseSigningConfig {
+    #[serde(default)]
+    pub algorithm: SigningAlgorithm,
+    pub key: String,
+    /// Sent as `kid` so verifiers can pick the key during rotation
+    #[serde(default)]
+    pub key_id: Option<String>,
+}
+
+/// Algorithm of `x-mizuchi-signature`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SigningAlgorithm {
+    #[default]
+    HmacSha256,
+    Ed25519,
+}
+
+/// State shared between proxy instances
+///
+/// With `redis`, anonymous rate limits, the SigV4 replay cache and upload
+/// quota counts are kept in Redis, so every instance behind a load balancer
+/// enforces the same limits. Subsystems with a `redis` setting of their own
+/// keep using it. Without it, each instance keeps its own state.
+///
+/// ```yaml
+/// state:
+///   redis:
+///     url: "redis://:${REDIS_PASSWORD}@redis:6379/0"
+///     key_prefix: "mizuchi:"
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StateConfig {
+    #[serde(default)]
+    pub redis: Option<StateRedisConfig>,
+}
+
+/// Redis holding the state shared between instances
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateRedisConfig {
+    /// `redis://[user][:password@]host[:port][/db]`
+    pub url: String,
+    #[serde(default = "default_state_redis_prefix")]
+    pub key_prefix: String,
+}
+
+fn default_state_redis_prefix() -> String {
+    "mizuchi:".into()
+}
+
+/// Upload receipts
+///
+/// Every completed upload is appended to the JSON lines file at `path` with
+/// its key, ETag, size, subject and time, and can be looked up through the
+/// admin API's `GET /receipts` without S3 list permissions. Receipts older
+/// than `retention_days` are dropped when the proxy starts. Buckets may
+/// share a file.
+///
+/// ```yaml
+/// upload:
+///   receipts:
+///     path: /var/lib/mizuchi/receipts.jsonl
+///     retention_days: 30
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptConfig {
+    pub path: String,
+    #[serde(default = "default_receipt_retention_days")]
+    pub retention_days: u32,
+}
+
+fn default_receipt_retention_days() -> u32 {
+    90
+}
+
+/// Upload quotas
+///
+/// Bytes and objects uploaded are counted per subject, or per value of the
+/// `claim` claim (e.g. a tenant ID) when the caller has one, over calendar
+/// windows in UTC. An upload that would go over a limit is rejected with an
+/// S3-style `QuotaExceeded` error. Counts are shared between instances
+/// through `redis`; otherwise they are kept by this instance, in the JSON
+/// file at `path` if set so that they survive restarts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotaConfig {
+    #[serde(default)]
+    pub claim: Option<String>,
+    pub limits: Vec<QuotaLimit>,
+    #[serde(default)]
+    pub redis: Option<QuotaRedisConfig>,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// Limit on what one subject or tenant may upload in a window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotaLimit {
+    pub window: QuotaWindow,
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+    #[serde(default)]
+    pub max_objects: Option<u64>,
+}
+
+/// Calendar window a quota is counted over, in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaWindow {
+    Daily,
+    Monthly,
+}
+
+impl QuotaWindow {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuotaWindow::Daily => "daily",
+            QuotaWindow::Monthly => "monthly",
+        }
+    }
+}
+
+/// Redis holding quota counts shared between instances
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotaRedisConfig {
+    /// `redis://[user][:password@]host[:port][/db]`
+    pub url: String,
+    #[serde(default = "default_quota_redis_prefix")]
+    pub key_prefix: String,
+}
+
+fn default_quota_redis_prefix() -> String {
+    "mizuchi:quota:".into()
+}
+
+/// Storage class routing rule
+///
+/// Matches uploads whose key starts with `prefix` (relative to the bucket,
+/// leading `/` ignored), whose content type matches `content_types` (exact
+/// types or `type/*` wildcards), and whose size lies within
+/// `min_size..=max_size`. Unset conditions match everything.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageClassRule {
+    #[serde(default)]
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub content_types: Vec<String>,
+    #[serde(default)]
+    pub min_size: Option<u64>,
+    #[serde(default)]
+    pub max_size: Option<u64>,
+    pub storage_class: StorageClass,
+}
+
+/// S3 storage class, sent as `x-amz-storage-class`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum StorageClass {
+    Standard,
+    ReducedRedundancy,
+    StandardIa,
+    OnezoneIa,
+    IntelligentTiering,
+    Glacier,
+    GlacierIr,
+    DeepArchive,
+    ExpressOnezone,
+}
+
+impl StorageClass {
+    /// Header value of this storage class
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StorageClass::Standard => "STANDARD",
+            StorageClass::ReducedRedundancy => "REDUCED_REDUNDANCY",
+            StorageClass::StandardIa => "STANDARD_IA",
+            StorageClass::OnezoneIa => "ONEZONE_IA",
+            StorageClass::IntelligentTiering => "INTELLIGENT_TIERING",
+            StorageClass::Glacier => "GLACIER",
+            StorageClass::GlacierIr => "GLACIER_IR",
+            StorageClass::DeepArchive => "DEEP_ARCHIVE",
+            StorageClass::ExpressOnezone => "EXPRESS_ONEZONE",
+        }
+    }
+}
+
+/// Disk-backed spill buffer for request bodies
+///
+/// Bodies without a `Content-Length`, or declaring more than
+/// `memory_limit` bytes, are written to `dir` instead of being collected in
+/// memory, then uploaded from there (as a multipart upload above
+/// `multipart_threshold`). At most `max_disk_bytes` are spilled at once per
+/// bucket. Spill files left behind by a crash are deleted at startup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpillConfig {
+    /// Spill directory (a tmpfs such as `/dev/shm` is recommended)
+    #[serde(default = "default_spill_dir")]
+    pub dir: String,
+    #[serde(default = "default_spill_max_disk_bytes")]
+    pub max_disk_bytes: u64,
+    #[serde(default = "default_spill_memory_limit")]
+    pub memory_limit: u64,
+    /// Encrypt spill files at rest with the per-process spool key
+    #[serde(default)]
+    pub encrypt: bool,
+}
+
+impl Default for SpillConfig {
+    fn default() -> Self {
+        Self {
+            dir: default_spill_dir(),
+            max_disk_bytes: default_spill_max_disk_bytes(),
+            memory_limit: default_spill_memory_limit(),
+            encrypt: false,
+        }
+    }
+}
+
+fn default_spill_dir() -> String {
+    std::env::temp_dir()
+        .join("mizuchi-spill")
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn default_spill_max_disk_bytes() -> u64 {
+    10 * 1024 * 1024 * 1024 // 10GB
+}
+
+fn default_spill_memory_limit() -> u64 {
+    8 * 1024 * 1024 // 8MB
 }
 
-/// Token source configuration
+/// Backpressure for bodies streamed to the backend
+///
+/// Bodies declaring more than `multipart_threshold` bytes are uploaded as
+/// multipart parts while they are still being received. When the bytes
+/// received but not yet stored reach `high_water_mark`, reading from the
+/// client stops until part uploads bring them down to `low_water_mark`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type")]
-pub enum TokenSource {
-    #[serde(rename = "bearer")]
-    Bearer,
-    #[serde(rename = "query")]
-    Query { name: String },
-    #[serde(rename = "header")]
-    Header { name: String },
+pub struct FlowControlConfig {
+    #[serde(default = "default_flow_high_water_mark")]
+    pub high_water_mark: u64,
+    #[serde(default = "default_flow_low_water_mark")]
+    pub low_water_mark: u64,
 }
 
-/// SigV4 configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SigV4Config {
-    pub service: String,
-    pub region: String,
+impl Default for FlowControlConfig {
+    fn default() -> Self {
+        Self {
+            high_water_mark: default_flow_high_water_mark(),
+            low_water_mark: default_flow_low_water_mark(),
+        }
+    }
 }
 
-/// Upload configuration
+fn default_flow_high_water_mark() -> u64 {
+    256 * 1024 * 1024 // 256MB
+}
+
+fn default_flow_low_water_mark() -> u64 {
+    128 * 1024 * 1024 // 128MB
+}
+
+/// Multipart upload reconciliation
+///
+/// Every `interval_secs`, multipart uploads left on the backend that this
+/// proxy is not driving and that were started more than `max_age_secs` ago
+/// are aborted, so their parts stop accruing storage costs.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct UploadConfig {
-    #[serde(default = "default_multipart_threshold")]
-    pub multipart_threshold: usize,
-    #[serde(default = "default_part_size")]
-    pub part_size: usize,
-    #[serde(default = "default_concurrent_parts")]
-    pub concurrent_parts: usize,
+pub struct MultipartReconciliationConfig {
+    #[serde(default = "default_reconciliation_interval")]
+    pub interval_secs: u64,
+    #[serde(default = "default_reconciliation_max_age")]
+    pub max_age_secs: u64,
 }
 
-impl Default for UploadConfig {
+impl Default for MultipartReconciliationConfig {
     fn default() -> Self {
         Self {
-            multipart_threshold: default_multipart_threshold(),
-            part_size: default_part_size(),
-            concurrent_parts: default_concurrent_parts(),
+            interval_secs: default_reconciliation_interval(),
+            max_age_secs: default_reconciliation_max_age(),
         }
     }
 }
 
-fn default_multipart_threshold() -> usize {
-    52428800 // 50MB
-}
-
-fn default_part_size() -> usize {
-    104857600 // 100MB
+fn default_reconciliation_interval() -> u64 {
+    3600 // 1 hour
 }
 
-fn default_concurrent_parts() -> usize {
-    4
+fn default_reconciliation_max_age() -> u64 {
+    86400 // 24 hours
 }
 
 /// Metrics configuration
+///
+/// When enabled, Prometheus metrics are served on `address:port`, on a
+/// listener separate from the data plane.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MetricsConfig {
     #[serde(default = "default_metrics_enabled")]
     pub enabled: bool,
+    /// IP address the metrics listener binds to
+    #[serde(default = "default_metrics_address")]
+    pub address: String,
     #[serde(default = "default_metrics_port")]
     pub port: u16,
+    /// Label upload metrics with the uploader's tenant (no tenant metrics if unset)
+    #[serde(default)]
+    pub tenant_labels: Option<TenantLabelConfig>,
+}
+
+impl MetricsConfig {
+    /// Socket address of the metrics listener (`address:port`)
+    pub fn bind_address(&self) -> Result<SocketAddr, ConfigError> {
+        let ip: IpAddr = self.address.parse().map_err(|e| {
+            ConfigError::ValidationError(format!(
+                "metrics.address {:?} is not an IP address: {}",
+                self.address, e
+            ))
+        })?;
+        Ok(SocketAddr::new(ip, self.port))
+    }
 }
 
 impl Default for MetricsConfig {
     fn default() -> Self {
         Self {
             enabled: default_metrics_enabled(),
+            address: default_metrics_address(),
             port: default_metrics_port(),
+            tenant_labels: None,
         }
     }
 }
@@ -371,10 +3309,183 @@ fn default_metrics_enabled() -> bool {
     true
 }
 
+fn default_metrics_address() -> String {
+    "0.0.0.0".to_string()
+}
+
 fn default_metrics_port() -> u16 {
     9090
 }
 
+/// Tenant label policy for upload metrics
+///
+/// The first `max_tenants` tenants seen get their own label value; uploads of
+/// later tenants are counted under `other`, which bounds the number of
+/// series. Uploads without the claim are counted under `none`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantLabelConfig {
+    /// Claim naming the uploader's tenant (dots reach nested claims)
+    pub claim: String,
+    /// Label with a hash of the tenant instead of its name
+    #[serde(default)]
+    pub hash: bool,
+    /// Tenants labeled individually before falling back to `other`
+    #[serde(default = "default_max_tenant_labels")]
+    pub max_tenants: usize,
+}
+
+fn default_max_tenant_labels() -> usize {
+    100
+}
+
+/// Admin API
+///
+/// Served on its own listener, off the data-plane port, and only when
+/// `address` is set. Requests must send the token as
+/// `Authorization: Bearer <token>`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AdminConfig {
+    /// Bind address of the admin listener (e.g. "127.0.0.1:8081")
+    #[serde(default)]
+    pub address: Option<String>,
+    /// Bearer token for admin requests, required with `address`.
+    #[serde(default)]
+    pub token: Option<String>,
+    /// File holding `token`
+    #[serde(default)]
+    pub token_file: Option<String>,
+}
+
+/// Log output
+///
+/// # Example
+///
+/// ```yaml
+/// logging:
+///   format: "pretty"
+///   filter: "info,mizuchi_uploadr::auth=debug"
+///   filter_file: "/etc/mizuchi/log-filter"
+///   slow_request_threshold_ms: 5000
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// Console output format
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Initial `EnvFilter` directives; the `--log-level` flag takes
+    /// precedence. Default: "info"
+    #[serde(default)]
+    pub filter: Option<String>,
+    /// File holding `EnvFilter` directives, re-read on SIGUSR1
+    #[serde(default)]
+    pub filter_file: Option<String>,
+    /// Log requests taking longer than this at WARN, with their phase
+    /// timings (disabled if unset)
+    #[serde(default)]
+    pub slow_request_threshold_ms: Option<u64>,
+}
+
+/// Log line format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One JSON object per line, for log shippers
+    #[default]
+    Json,
+    /// Human-readable multi-line output, for development
+    Pretty,
+}
+
+// ============================================================================
+// Notification Configuration
+// ============================================================================
+
+/// Upload completion notification configuration.
+///
+/// When enabled, a JSON event is POSTed to every configured webhook after a
+/// successful PutObject or CompleteMultipartUpload. Delivery happens on a
+/// bounded background queue, so a slow or failing webhook never blocks uploads.
+///
+/// # Example
+///
+/// ```yaml
+/// notifications:
+///   enabled: true
+///   queue_capacity: 1024
+///   webhooks:
+///     - url: "https://hooks.example.com/uploads"
+///       secret: "${WEBHOOK_SECRET}"  # Optional: HMAC-SHA256 signing key
+///       max_retries: 3
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationsConfig {
+    /// Enable or disable notifications. Default: false
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Maximum number of pending events before new events are dropped. Default: 1024
+    #[serde(default = "default_notification_queue_capacity")]
+    pub queue_capacity: usize,
+
+    /// Webhook endpoints that receive every event
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            queue_capacity: default_notification_queue_capacity(),
+            webhooks: Vec::new(),
+        }
+    }
+}
+
+fn default_notification_queue_capacity() -> usize {
+    1024
+}
+
+/// A single webhook endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    /// Webhook URL
+    pub url: String,
+
+    /// Optional HMAC-SHA256 key. When set, the payload signature is sent in
+    /// the `X-Mizuchi-Signature` header as `sha256=<hex>`.
+    #[serde(default)]
+    pub secret: Option<String>,
+
+    /// File holding `secret`
+    #[serde(default)]
+    pub secret_file: Option<String>,
+
+    /// Maximum number of retries after the first failed delivery. Default: 3
+    #[serde(default = "default_webhook_max_retries")]
+    pub max_retries: u32,
+
+    /// Initial backoff in milliseconds, doubled after each retry. Default: 200
+    #[serde(default = "default_webhook_initial_backoff")]
+    pub initial_backoff_ms: u64,
+
+    /// Per-request timeout in milliseconds. Default: 5000
+    #[serde(default = "default_webhook_timeout")]
+    pub timeout_ms: u64,
+}
+
+fn default_webhook_max_retries() -> u32 {
+    3
+}
+
+fn default_webhook_initial_backoff() -> u64 {
+    200
+}
+
+fn default_webhook_timeout() -> u64 {
+    5000
+}
+
 // ============================================================================
 // Tracing Configuration
 // ============================================================================
@@ -403,12 +3514,9 @@ pub struct TracingConfig {
     #[serde(default)]
     pub enabled: bool,
 
-    /// Service name for trace identification. Supports ${VAR} and ${VAR:-default} expansion.
+    /// Service name for trace identification.
     /// Default: "mizuchi-uploadr"
-    #[serde(
-        default = "default_service_name",
-        deserialize_with = "deserialize_with_env"
-    )]
+    #[serde(default = "default_service_name")]
     pub service_name: String,
 
     /// OTLP exporter configuration
@@ -447,10 +3555,10 @@ fn default_service_name() -> String {
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OtlpConfig {
-    /// OTLP collector endpoint URL. Supports ${VAR} expansion.
+    /// OTLP collector endpoint URL.
     /// Must start with http:// or https://
     /// Default: "" (empty, must be provided when tracing is enabled)
-    #[serde(default, deserialize_with = "deserialize_with_env")]
+    #[serde(default)]
     pub endpoint: String,
 
     /// Protocol to use: "grpc" or "http/protobuf". Default: "grpc"
@@ -496,6 +3604,10 @@ fn default_otlp_timeout() -> u64 {
 /// - `ratio` - Sample a percentage of traces based on `ratio` field
 /// - `parent_based` - Respect parent span's sampling decision
 ///
+/// The strategy decides when a trace starts. With `rules`, traces are also
+/// sampled once they complete (tail-based sampling), which needs the
+/// `always` strategy so every trace is recorded until the decision.
+///
 /// # Example
 ///
 /// ```yaml
@@ -512,6 +3624,10 @@ pub struct SamplingConfig {
     /// Sampling ratio (0.0 to 1.0). Only used with "ratio" strategy. Default: 1.0
     #[serde(default = "default_sampling_ratio")]
     pub ratio: f64,
+
+    /// Tail-based sampling rules, applied when a request's trace completes
+    #[serde(default)]
+    pub rules: Option<TailSamplingConfig>,
 }
 
 impl Default for SamplingConfig {
@@ -519,6 +3635,130 @@ impl Default for SamplingConfig {
         Self {
             strategy: default_sampling_strategy(),
             ratio: default_sampling_ratio(),
+            rules: None,
+        }
+    }
+}
+
+/// Tail-based sampling rules.
+///
+/// Spans are buffered per trace until the request's root span ends. The
+/// trace is then kept if it contains an error (`errors`), took at least
+/// `slow_threshold_ms`, or is picked by the first matching `custom` rule
+/// (or by `base_rate` if none match). Dropped traces are never exported.
+///
+/// # Example
+///
+/// ```yaml
+/// sampling:
+///   strategy: "always"
+///   rules:
+///     errors: true
+///     slow_threshold_ms: 1000
+///     base_rate: 0.05
+///     custom:
+///       - path: "/uploads/critical/*"
+///         method: "PUT"
+///         sample_rate: 1.0
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TailSamplingConfig {
+    /// Keep every trace containing an error. Default: true
+    #[serde(default = "default_tail_sampling_errors")]
+    pub errors: bool,
+
+    /// Keep every trace whose request took at least this long
+    #[serde(default)]
+    pub slow_threshold_ms: Option<u64>,
+
+    /// Rate (0.0 to 1.0) for traces no other rule keeps. Default: 1.0
+    #[serde(default = "default_sampling_ratio")]
+    pub base_rate: f64,
+
+    /// Rules matched against the request span, first match wins
+    #[serde(default)]
+    pub custom: Vec<SamplingRuleConfig>,
+
+    /// Traces buffered at once; the oldest is dropped when full. Default: 10000
+    #[serde(default = "default_max_pending_traces")]
+    pub max_pending_traces: usize,
+}
+
+impl Default for TailSamplingConfig {
+    fn default() -> Self {
+        Self {
+            errors: default_tail_sampling_errors(),
+            slow_threshold_ms: None,
+            base_rate: default_sampling_ratio(),
+            custom: Vec::new(),
+            max_pending_traces: default_max_pending_traces(),
+        }
+    }
+}
+
+/// Tail sampling rule matched against a request span
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SamplingRuleConfig {
+    /// Request path, exact or a prefix ending in `/*`
+    #[serde(default)]
+    pub path: Option<String>,
+
+    /// HTTP method
+    #[serde(default)]
+    pub method: Option<String>,
+
+    /// Span attributes that must all match
+    #[serde(default)]
+    pub attributes: std::collections::HashMap<String, String>,
+
+    /// Rate (0.0 to 1.0) for matching traces
+    pub sample_rate: f64,
+}
+
+fn default_tail_sampling_errors() -> bool {
+    true
+}
+
+fn default_max_pending_traces() -> usize {
+    10_000
+}
+
+fn validate_tail_sampling(
+    strategy: &str,
+    rules: &TailSamplingConfig,
+    errors: &mut Vec<FieldError>,
+) {
+    if strategy != "always" {
+        errors.push(FieldError::new(
+            "tracing.sampling.rules",
+            format!(
+                "tracing.sampling.rules requires the 'always' strategy, got '{}'",
+                strategy
+            ),
+        ));
+    }
+    if rules.max_pending_traces == 0 {
+        errors.push(FieldError::new(
+            "tracing.sampling.rules.max_pending_traces",
+            "tracing.sampling.rules.max_pending_traces must be greater than 0",
+        ));
+    }
+    let rates = std::iter::once(("base_rate".to_string(), rules.base_rate)).chain(
+        rules
+            .custom
+            .iter()
+            .enumerate()
+            .map(|(i, r)| (format!("custom[{}].sample_rate", i), r.sample_rate)),
+    );
+    for (field, rate) in rates {
+        if !(0.0..=1.0).contains(&rate) {
+            errors.push(FieldError::new(
+                format!("tracing.sampling.rules.{}", field),
+                format!(
+                    "Invalid tail sampling rate {}: must be between 0.0 and 1.0",
+                    rate
+                ),
+            ));
         }
     }
 }
@@ -604,12 +3844,599 @@ mod tests {
             server: ServerConfig {
                 address: "0.0.0.0:8080".into(),
                 zero_copy: ZeroCopyConfig::default(),
+                http: Default::default(),
+                tls: None,
+                listeners: Vec::new(),
+                tcp: Default::default(),
+                buffer_pool: Default::default(),
+                shutdown: Default::default(),
             },
             buckets: vec![],
             metrics: MetricsConfig::default(),
             tracing: None,
+            notifications: Default::default(),
+            admin: Default::default(),
+            logging: Default::default(),
+            state: Default::default(),
         };
 
         assert!(config.validate().is_err());
     }
+
+    #[test]
+    fn test_authz_opa_bundle_config() {
+        let yaml = r#"
+server:
+  address: "0.0.0.0:8080"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: my-bucket
+      region: us-east-1
+    auth:
+      enabled: true
+      jwt:
+        secret: secret
+        algorithm: HS256
+    authz:
+      type: opa
+      policy_path: mizuchi/allow
+      bundle:
+        url: https://bundles.example.com/mizuchi.tar.gz
+        reload_secs: 60
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate().is_ok());
+
+        match config.buckets[0].authz.as_mut().unwrap() {
+            AuthzConfig::Opa(opa) => {
+                let bundle = opa.bundle.as_mut().unwrap();
+                assert_eq!(bundle.reload_secs, Some(60));
+                bundle.path = Some("/etc/mizuchi/bundle.tar.gz".into());
+                bundle.reload_secs = Some(0);
+            }
+            other => panic!("unexpected authz config: {:?}", other),
+        }
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("exactly one of path and url"), "{}", err);
+        assert!(err.contains("authz.bundle.reload_secs"), "{}", err);
+        // No server URL is needed with a bundle
+        assert!(!err.contains("authz.url"), "{}", err);
+    }
+
+    #[test]
+    fn test_config_validation_invalid_webhook_url() {
+        let yaml = r#"
+server:
+  address: "0.0.0.0:8080"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: my-bucket
+      region: us-east-1
+notifications:
+  enabled: true
+  webhooks:
+    - url: "ftp://hooks.example.com"
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_logging_config() {
+        let yaml = r#"
+server:
+  address: "0.0.0.0:8080"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: my-bucket
+      region: us-east-1
+logging:
+  format: pretty
+  filter: "info,mizuchi_uploadr::auth=debug"
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.logging.format, LogFormat::Pretty);
+        assert!(config.validate().is_ok());
+
+        config.logging.filter = Some("mizuchi=loud".into());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_metrics_bind_address() {
+        let yaml = r#"
+server:
+  address: "0.0.0.0:8080"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: my-bucket
+      region: us-east-1
+metrics:
+  address: "::1"
+  port: 9100
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            config.metrics.bind_address().unwrap(),
+            "[::1]:9100".parse::<SocketAddr>().unwrap()
+        );
+        assert!(config.validate().is_ok());
+
+        config.metrics.address = "metrics.local".into();
+        assert!(config.validate().is_err());
+
+        // Not checked when metrics are disabled
+        config.metrics.enabled = false;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_tenant_routing_validation() {
+        let yaml = r#"
+server:
+  address: "0.0.0.0:8080"
+buckets:
+  - name: acme
+    path_prefix: /uploads
+    s3:
+      bucket: acme-uploads
+      region: us-east-1
+    auth:
+      enabled: true
+      jwt:
+        secret: test-secret
+        algorithm: HS256
+    tenant:
+      claim: tenant_id
+      values: [acme]
+  - name: globex
+    path_prefix: /uploads
+    s3:
+      bucket: globex-uploads
+      region: eu-west-1
+    tenant:
+      claim: tenant_id
+      values: [globex]
+  - name: shared
+    path_prefix: /uploads
+    s3:
+      bucket: shared-uploads
+      region: us-east-1
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate().is_ok());
+
+        let mut overlapping = config.clone();
+        overlapping.buckets[1].tenant.as_mut().unwrap().values = vec!["acme".into()];
+        assert!(overlapping.validate().is_err());
+
+        let mut other_claim = config.clone();
+        other_claim.buckets[1].tenant.as_mut().unwrap().claim = "org".into();
+        assert!(other_claim.validate().is_err());
+
+        let mut two_defaults = config.clone();
+        two_defaults.buckets[1].tenant = None;
+        assert!(two_defaults.validate().is_err());
+
+        let mut unauthenticated = config.clone();
+        unauthenticated.buckets[0].auth.enabled = false;
+        assert!(unauthenticated.validate().is_err());
+
+        let mut duplicate_name = config;
+        duplicate_name.buckets[2].name = "acme".into();
+        assert!(duplicate_name.validate().is_err());
+    }
+
+    #[test]
+    fn test_hedging_config() {
+        let yaml = r#"
+server:
+  address: "0.0.0.0:8080"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: my-bucket
+      region: us-east-1
+      hedging:
+        percentile: 95
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let hedging = config.buckets[0].s3.hedging.clone().unwrap();
+        assert_eq!(hedging.percentile, 95);
+        assert_eq!(hedging.max_object_size, 1024 * 1024);
+        assert!(config.validate().is_ok());
+
+        config.buckets[0].s3.hedging.as_mut().unwrap().percentile = 100;
+        assert!(config.validate().is_err());
+
+        let hedging = config.buckets[0].s3.hedging.as_mut().unwrap();
+        hedging.percentile = 99;
+        hedging.min_delay_ms = hedging.max_delay_ms + 1;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_hashing_config() {
+        let yaml = r#"
+server:
+  address: "0.0.0.0:8080"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: my-bucket
+      region: us-east-1
+      hashing:
+        offload_threshold_bytes: 0
+  - name: defaults
+    path_prefix: /defaults
+    s3:
+      bucket: other-bucket
+      region: us-east-1
+      hashing: {}
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let hashing = config.buckets[0].s3.hashing.clone().unwrap();
+        assert_eq!(hashing.offload_threshold_bytes, 0);
+        assert_eq!(
+            config.buckets[1].s3.hashing,
+            Some(S3HashingConfig::default())
+        );
+        assert_eq!(
+            S3HashingConfig::default().offload_threshold_bytes,
+            1024 * 1024
+        );
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_checksum_config() {
+        let yaml = r#"
+server:
+  address: "0.0.0.0:8080"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: my-bucket
+      region: us-east-1
+      checksum:
+        algorithm: none
+  - name: defaults
+    path_prefix: /defaults
+    s3:
+      bucket: other-bucket
+      region: us-east-1
+      checksum: {}
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            config.buckets[0].s3.checksum.unwrap().algorithm,
+            ChecksumAlgorithm::None
+        );
+        assert_eq!(
+            config.buckets[1].s3.checksum.unwrap().algorithm,
+            ChecksumAlgorithm::Crc32c
+        );
+        assert_eq!(
+            S3ChecksumConfig::default().algorithm,
+            ChecksumAlgorithm::Crc32c
+        );
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_debug_log_config() {
+        let yaml = r#"
+server:
+  address: "0.0.0.0:8080"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: my-bucket
+      region: us-east-1
+      debug_log:
+        dir: /var/log/mizuchi
+        rotation: hourly
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let debug_log = config.buckets[0].s3.debug_log.clone().unwrap();
+        assert_eq!(debug_log.dir, "/var/log/mizuchi");
+        assert_eq!(debug_log.rotation, DebugLogRotation::Hourly);
+        assert_eq!(debug_log.max_files, 7);
+        assert_eq!(debug_log.max_body_bytes, 4096);
+        assert!(config.validate().is_ok());
+
+        config.buckets[0].s3.debug_log.as_mut().unwrap().max_files = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_dns_config() {
+        let yaml = r#"
+server:
+  address: "0.0.0.0:8080"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: my-bucket
+      region: us-east-1
+      endpoint: http://minio.internal:9000
+      pool:
+        dns:
+          overrides:
+            minio.internal: ["10.0.0.5"]
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let dns = config.buckets[0].s3.pool.clone().unwrap().dns.unwrap();
+        assert_eq!(dns.cache_ttl_secs, 60);
+        assert!(dns.happy_eyeballs);
+        assert_eq!(
+            dns.overrides["minio.internal"],
+            vec!["10.0.0.5".parse::<IpAddr>().unwrap()]
+        );
+        assert!(config.validate().is_ok());
+
+        let pool = config.buckets[0].s3.pool.as_mut().unwrap();
+        pool.dns
+            .as_mut()
+            .unwrap()
+            .overrides
+            .insert("minio.internal".into(), Vec::new());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_flow_control_config() {
+        let yaml = r#"
+server:
+  address: "0.0.0.0:8080"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: my-bucket
+      region: us-east-1
+    upload:
+      flow_control: {}
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let flow = config.buckets[0].upload.flow_control.clone().unwrap();
+        assert_eq!(flow.high_water_mark, 256 * 1024 * 1024);
+        assert_eq!(flow.low_water_mark, 128 * 1024 * 1024);
+        assert!(config.validate().is_ok());
+
+        let flow = config.buckets[0].upload.flow_control.as_mut().unwrap();
+        flow.low_water_mark = flow.high_water_mark;
+        assert!(config.validate().is_err());
+
+        let upload = &mut config.buckets[0].upload;
+        upload.flow_control.as_mut().unwrap().low_water_mark = 0;
+        upload.part_size = 512 * 1024 * 1024;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_token_exchange_config() {
+        let yaml = r#"
+server:
+  address: "0.0.0.0:8080"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: my-bucket
+      region: us-east-1
+    auth:
+      enabled: true
+      jwt:
+        secret: secret
+        algorithm: HS256
+        token_exchange:
+          token_endpoint: https://idp.example.com/oauth2/token
+          client_id: mizuchi
+          audience: uploads
+          scopes: [upload]
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let jwt = config.buckets[0].auth.jwt.as_mut().unwrap();
+        let exchange = jwt.token_exchange.as_mut().unwrap();
+        assert_eq!(exchange.timeout_ms, 5000);
+        assert_eq!(exchange.scopes, vec!["upload"]);
+        assert!(config.validate().is_ok());
+
+        let jwt = config.buckets[0].auth.jwt.as_mut().unwrap();
+        jwt.token_exchange.as_mut().unwrap().token_endpoint = "idp.example.com".into();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(
+            err.contains("auth.jwt.token_exchange.token_endpoint"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_authz_deny_cache_ttl_config() {
+        let yaml = r#"
+server:
+  address: "0.0.0.0:8080"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: my-bucket
+      region: us-east-1
+    auth:
+      enabled: true
+      jwt:
+        secret: secret
+        algorithm: HS256
+    authz:
+      type: openfga
+      url: http://openfga:8080
+      store_id: store123
+      cache_ttl_secs: 60
+      deny_cache_ttl_secs: 5
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate().is_ok());
+
+        match config.buckets[0].authz.as_mut().unwrap() {
+            AuthzConfig::OpenFga(fga) => {
+                assert_eq!(fga.deny_cache_ttl_secs, Some(5));
+                fga.deny_cache_ttl_secs = Some(120);
+            }
+            other => panic!("unexpected authz config: {:?}", other),
+        }
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("authz.deny_cache_ttl_secs"), "{}", err);
+    }
+
+    #[test]
+    fn test_jwt_revocation_config() {
+        let yaml = r#"
+server:
+  address: "0.0.0.0:8080"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: my-bucket
+      region: us-east-1
+    auth:
+      enabled: true
+      jwt:
+        secret: secret
+        algorithm: HS256
+        revocation:
+          store:
+            type: redis
+            url: redis://redis:6379/0
+"#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate().is_ok());
+        let revocation = config.buckets[0]
+            .auth
+            .jwt
+            .as_mut()
+            .unwrap()
+            .revocation
+            .as_mut()
+            .unwrap();
+        assert_eq!(revocation.refresh_secs, 30);
+        match revocation.store {
+            RevocationStoreConfig::Redis { ref key_prefix, .. } => {
+                assert_eq!(key_prefix, "mizuchi:revoked:")
+            }
+            ref other => panic!("unexpected store: {:?}", other),
+        }
+
+        revocation.refresh_secs = 0;
+        revocation.store = RevocationStoreConfig::Redis {
+            url: "http://redis:6379".into(),
+            key_prefix: "x:".into(),
+        };
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("auth.jwt.revocation.refresh_secs"), "{}", err);
+        assert!(err.contains("auth.jwt.revocation.store.url"), "{}", err);
+    }
+
+    #[test]
+    fn test_listeners_config() {
+        let yaml = r#"
+server:
+  tcp:
+    recv_buffer_size: 4194304
+  listeners:
+    - name: internal
+      address: "127.0.0.1:8080"
+    - name: public
+      address: "0.0.0.0:8443"
+      buckets: [user-uploads]
+      tcp:
+        nodelay: true
+        keepalive_secs: 60
+      tls:
+        cert_path: /etc/mizuchi/tls.crt
+        key_path: /etc/mizuchi/tls.key
+buckets:
+  - name: user-uploads
+    path_prefix: /uploads
+    s3:
+      bucket: user-uploads
+      region: us-east-1
+    auth:
+      enabled: true
+      mtls:
+        ca_path: /etc/mizuchi/ca.pem
+  - name: service
+    path_prefix: /service
+    s3:
+      bucket: service
+      region: us-east-1
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate().is_ok(), "{:?}", config.validate());
+        let listeners = config.server.effective_listeners();
+        assert!(listeners[0].serves("service"));
+        assert!(listeners[1].serves("user-uploads"));
+        assert!(!listeners[1].serves("service"));
+        // Listeners without their own socket options get server.tcp
+        let internal = listeners[0].tcp.as_ref().unwrap();
+        assert_eq!(internal.recv_buffer_size, Some(4 << 20));
+        assert_eq!(internal.backlog, 1024);
+        let public = listeners[1].tcp.as_ref().unwrap();
+        assert_eq!(public.recv_buffer_size, None);
+        assert!(public.nodelay);
+
+        let mut both = config.clone();
+        both.server.address = "0.0.0.0:8080".into();
+        let err = both.validate().unwrap_err().to_string();
+        assert!(err.contains("server.listeners"), "{}", err);
+
+        let mut invalid = config.clone();
+        invalid.server.listeners[1].name = "internal".into();
+        invalid.server.listeners[1].address = "localhost".into();
+        invalid.server.listeners[1].buckets = vec!["typo".into()];
+        let err = invalid.validate().unwrap_err().to_string();
+        assert!(err.contains("server.listeners[1].name"), "{}", err);
+        assert!(err.contains("server.listeners[1].address"), "{}", err);
+        assert!(err.contains("server.listeners[1].buckets[0]"), "{}", err);
+
+        // mTLS needs a TLS listener serving the bucket
+        let mut plaintext = config.clone();
+        plaintext.server.listeners[1].tls = None;
+        let err = plaintext.validate().unwrap_err().to_string();
+        assert!(err.contains("buckets[0].auth.mtls"), "{}", err);
+
+        let mut tuned = config.clone();
+        tuned.server.tcp.keepalive_retries = Some(3);
+        tuned.server.listeners[0].tcp = Some(TcpConfig {
+            recv_buffer_size: Some(0),
+            ..Default::default()
+        });
+        let err = tuned.validate().unwrap_err().to_string();
+        assert!(err.contains("server.tcp.keepalive_secs"), "{}", err);
+        assert!(
+            err.contains("server.listeners[0].tcp.recv_buffer_size"),
+            "{}",
+            err
+        );
+
+        let mut unserved = config;
+        unserved.server.listeners.remove(0);
+        let err = unserved.validate().unwrap_err().to_string();
+        assert!(err.contains("not served by any listener"), "{}", err);
+    }
 }
@@ -0,0 +1,212 @@
+//! Secrets read from files
+//!
+//! Secret fields have a `*_file` variant (`secret_key_file`,
+//! `jwt.secret_file`, ...) naming a file that holds the value, such as a
+//! mounted Kubernetes or Docker secret. [`ConfigLoader::load`] reads them
+//! into their inline counterparts, so a reload picks up rotated secrets.
+//! A trailing newline in the file is dropped.
+//!
+//! [`ConfigLoader::load`]: super::ConfigLoader::load
+
+use super::{Config, ConfigError, FieldError, S3Config, SigV4StoreConfig};
+
+impl Config {
+    /// Read every `*_file` secret into its inline field
+    pub fn resolve_secret_files(&mut self) -> Result<(), ConfigError> {
+        let errors = resolve(self);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(errors))
+        }
+    }
+}
+
+/// Read every `*_file` secret, returning the problems found
+pub(super) fn resolve(config: &mut Config) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+
+    for (i, bucket) in config.buckets.iter_mut().enumerate() {
+        let at = format!("buckets[{}]", i);
+        resolve_s3(&mut bucket.s3, &format!("{}.s3", at), &mut errors);
+        if let Some(ref mut replication) = bucket.replication {
+            let path = format!("{}.replication.target", at);
+            resolve_s3(&mut replication.target, &path, &mut errors);
+        }
+        if let Some(ref mut failover) = bucket.failover {
+            let path = format!("{}.failover.target", at);
+            resolve_s3(&mut failover.target, &path, &mut errors);
+        }
+        if let Some(ref mut regions) = bucket.regions {
+            for (j, target) in regions.targets.iter_mut().enumerate() {
+                let path = format!("{}.regions.targets[{}].s3", at, j);
+                resolve_s3(&mut target.s3, &path, &mut errors);
+            }
+        }
+
+        if let Some(ref mut jwt) = bucket.auth.jwt {
+            let path = format!("{}.auth.jwt.secret", at);
+            resolve_optional(&mut jwt.secret, &jwt.secret_file, &path, &mut errors);
+            if let Some(ref mut exchange) = jwt.token_exchange {
+                let path = format!("{}.auth.jwt.token_exchange.client_secret", at);
+                resolve_optional(
+                    &mut exchange.client_secret,
+                    &exchange.client_secret_file,
+                    &path,
+                    &mut errors,
+                );
+            }
+        }
+        if let Some(ref mut api_key) = bucket.auth.api_key {
+            let path = format!("{}.auth.api_key.pepper", at);
+            resolve_optional(
+                &mut api_key.pepper,
+                &api_key.pepper_file,
+                &path,
+                &mut errors,
+            );
+        }
+        if let Some(SigV4StoreConfig::Vault {
+            token, token_file, ..
+        }) = bucket.auth.sigv4.as_mut().and_then(|s| s.store.as_mut())
+        {
+            let path = format!("{}.auth.sigv4.store.token", at);
+            let mut value = (!token.is_empty()).then(|| std::mem::take(token));
+            resolve_optional(&mut value, token_file, &path, &mut errors);
+            *token = value.unwrap_or_default();
+        }
+    }
+
+    let admin = &mut config.admin;
+    resolve_optional(
+        &mut admin.token,
+        &admin.token_file,
+        "admin.token",
+        &mut errors,
+    );
+
+    for (i, webhook) in config.notifications.webhooks.iter_mut().enumerate() {
+        let path = format!("notifications.webhooks[{}].secret", i);
+        resolve_optional(
+            &mut webhook.secret,
+            &webhook.secret_file,
+            &path,
+            &mut errors,
+        );
+    }
+    if let Some(ref mut webhook) = config.server.shutdown.report_webhook {
+        let path = "server.shutdown.report_webhook.secret";
+        resolve_optional(&mut webhook.secret, &webhook.secret_file, path, &mut errors);
+    }
+
+    errors
+}
+
+fn resolve_s3(s3: &mut S3Config, path: &str, errors: &mut Vec<FieldError>) {
+    let access_key = format!("{}.access_key", path);
+    resolve_optional(&mut s3.access_key, &s3.access_key_file, &access_key, errors);
+    let secret_key = format!("{}.secret_key", path);
+    resolve_optional(&mut s3.secret_key, &s3.secret_key_file, &secret_key, errors);
+}
+
+/// Fill `value` (at YAML `path`) from `file`, if set
+fn resolve_optional(
+    value: &mut Option<String>,
+    file: &Option<String>,
+    path: &str,
+    errors: &mut Vec<FieldError>,
+) {
+    let Some(file) = file else {
+        return;
+    };
+    if value.is_some() {
+        errors.push(FieldError::new(
+            format!("{}_file", path),
+            format!("Only one of {0} and {0}_file can be set", field_name(path)),
+        ));
+        return;
+    }
+    match std::fs::read_to_string(file) {
+        Ok(content) => *value = Some(content.trim_end_matches(['\n', '\r']).to_string()),
+        Err(e) => errors.push(FieldError::new(
+            format!("{}_file", path),
+            format!("Cannot read secret file '{}': {}", file, e),
+        )),
+    }
+}
+
+/// Last segment of a YAML path
+fn field_name(path: &str) -> &str {
+    path.rsplit('.').next().unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn secret_file(content: &str) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        file
+    }
+
+    fn config(s3: &str) -> Config {
+        serde_yaml::from_str(&format!(
+            r#"
+server:
+  address: "0.0.0.0:8080"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: uploads
+      region: us-east-1
+{}
+"#,
+            s3
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_secret_files_read() {
+        let access_key = secret_file("AKIAEXAMPLE\n");
+        let secret_key = secret_file("s3cr3t");
+        let mut config = config(&format!(
+            "      access_key_file: {}\n      secret_key_file: {}",
+            access_key.path().display(),
+            secret_key.path().display()
+        ));
+
+        config.resolve_secret_files().unwrap();
+
+        let s3 = &config.buckets[0].s3;
+        assert_eq!(s3.access_key.as_deref(), Some("AKIAEXAMPLE"));
+        assert_eq!(s3.secret_key.as_deref(), Some("s3cr3t"));
+    }
+
+    #[test]
+    fn test_secret_file_errors() {
+        let secret_key = secret_file("s3cr3t");
+        let mut config = config(&format!(
+            "      access_key_file: /nonexistent/access_key\n      secret_key: inline\n      secret_key_file: {}",
+            secret_key.path().display()
+        ));
+
+        let errors = resolve(&mut config);
+        let paths: Vec<_> = errors.iter().map(|e| e.path.as_str()).collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                "buckets[0].s3.access_key_file",
+                "buckets[0].s3.secret_key_file"
+            ]
+        );
+        assert_eq!(
+            errors[1].message,
+            "Only one of secret_key and secret_key_file can be set"
+        );
+    }
+}
@@ -0,0 +1,433 @@
+//! S3 conformance checks
+//!
+//! The subset of the Ceph [s3-tests](https://github.com/ceph/s3-tests)
+//! that applies to an upload-only proxy, embedded so it can run against any
+//! deployment with `mizuchi-uploadr conformance` and no Python toolchain.
+//! Cases carry the name of the s3-tests function they mirror where there is
+//! one. Whether a case passes also depends on the backend: conditional
+//! writes, for instance, need a backend that implements them.
+//!
+//! Operations the proxy does not implement are checked too, for being
+//! rejected rather than mishandled.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use mizuchi_uploadr::conformance::{self, ConformanceOptions};
+//!
+//! # async fn example() {
+//! let options = ConformanceOptions {
+//!     target: "http://localhost:8080/uploads".into(),
+//!     ..Default::default()
+//! };
+//! let results = conformance::run(&reqwest::Client::new(), &options).await;
+//! print!("{}", conformance::report(&results));
+//! # }
+//! ```
+
+use md5::{Digest, Md5};
+use reqwest::{Method, Response, StatusCode};
+
+/// Where to run the checks
+#[derive(Debug, Clone)]
+pub struct ConformanceOptions {
+    /// URL of a bucket's path prefix (e.g. `http://host/uploads`)
+    pub target: String,
+    /// Headers sent with every request (e.g. `Authorization`)
+    pub headers: Vec<(String, String)>,
+    /// First component of every object key; a random run ID follows
+    pub key_prefix: String,
+}
+
+impl Default for ConformanceOptions {
+    fn default() -> Self {
+        Self {
+            target: String::new(),
+            headers: Vec::new(),
+            key_prefix: "conformance".into(),
+        }
+    }
+}
+
+/// A conformance check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    WriteCheckEtag,
+    WriteEmpty,
+    Overwrite,
+    SpecialKeyNames,
+    IfNoneMatchNew,
+    IfNoneMatchExisting,
+    IfMatchGood,
+    IfMatchFailed,
+    AwsChunkedTrailer,
+    HeadBucket,
+    GetBucketLocation,
+    ReadsRejected,
+    UploadPartRejected,
+    CopyRejected,
+}
+
+impl Case {
+    /// Every case, in the order they run
+    pub const ALL: [Case; 14] = [
+        Case::WriteCheckEtag,
+        Case::WriteEmpty,
+        Case::Overwrite,
+        Case::SpecialKeyNames,
+        Case::IfNoneMatchNew,
+        Case::IfNoneMatchExisting,
+        Case::IfMatchGood,
+        Case::IfMatchFailed,
+        Case::AwsChunkedTrailer,
+        Case::HeadBucket,
+        Case::GetBucketLocation,
+        Case::ReadsRejected,
+        Case::UploadPartRejected,
+        Case::CopyRejected,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Case::WriteCheckEtag => "test_object_write_check_etag",
+            Case::WriteEmpty => "test_object_write_empty",
+            Case::Overwrite => "test_object_overwrite",
+            Case::SpecialKeyNames => "test_bucket_create_special_key_names",
+            Case::IfNoneMatchNew => "test_put_object_ifnonmatch_nonexisted_good",
+            Case::IfNoneMatchExisting => "test_put_object_ifnonmatch_overwrite_existed_failed",
+            Case::IfMatchGood => "test_put_object_ifmatch_good",
+            Case::IfMatchFailed => "test_put_object_ifmatch_failed",
+            Case::AwsChunkedTrailer => "test_object_write_aws_chunked_trailer",
+            Case::HeadBucket => "test_bucket_head",
+            Case::GetBucketLocation => "test_bucket_get_location",
+            Case::ReadsRejected => "test_object_read_rejected",
+            Case::UploadPartRejected => "test_multipart_upload_part_rejected",
+            Case::CopyRejected => "test_object_copy_rejected",
+        }
+    }
+
+    /// Behavior checked
+    pub fn description(self) -> &'static str {
+        match self {
+            Case::WriteCheckEtag => "PutObject returns the MD5 of the body as ETag",
+            Case::WriteEmpty => "Empty objects can be uploaded",
+            Case::Overwrite => "Uploading to an existing key replaces the object",
+            Case::SpecialKeyNames => "Keys with spaces, unicode and reserved characters",
+            Case::IfNoneMatchNew => "If-None-Match: * creates a new object",
+            Case::IfNoneMatchExisting => "If-None-Match: * on an existing key is 412",
+            Case::IfMatchGood => "If-Match with the current ETag overwrites",
+            Case::IfMatchFailed => "If-Match with another ETag is 412",
+            Case::AwsChunkedTrailer => "aws-chunked bodies with a checksum trailer",
+            Case::HeadBucket => "HeadBucket reports x-amz-bucket-region",
+            Case::GetBucketLocation => "GetBucketLocation returns a LocationConstraint",
+            Case::ReadsRejected => "GetObject, DeleteObject and ListObjects are 404",
+            Case::UploadPartRejected => "UploadPart is 501 NotImplemented",
+            Case::CopyRejected => "CopyObject is 501 NotImplemented",
+        }
+    }
+
+    async fn check(self, run: &Run<'_>) -> Result<(), String> {
+        match self {
+            Case::WriteCheckEtag => {
+                let response = run.put("etag", "bar", &[]).await?;
+                expect_etag(&response, b"bar")
+            }
+            Case::WriteEmpty => {
+                let response = run.put("empty", "", &[]).await?;
+                expect_etag(&response, b"")
+            }
+            Case::Overwrite => {
+                run.put("overwrite", "bar", &[]).await?;
+                let response = run.put("overwrite", "baz", &[]).await?;
+                expect_etag(&response, b"baz")
+            }
+            Case::SpecialKeyNames => {
+                for key in ["a%20b.txt", "%E6%97%A5%E6%9C%AC.txt", "a+b=c", "x/y/z", "_"] {
+                    let response = run.put(key, "bar", &[]).await?;
+                    expect_status(&response, StatusCode::OK)
+                        .map_err(|e| format!("{}: {}", key, e))?;
+                }
+                Ok(())
+            }
+            Case::IfNoneMatchNew => {
+                let response = run
+                    .put("ifnonematch-new", "bar", &[("If-None-Match", "*")])
+                    .await?;
+                expect_status(&response, StatusCode::OK)
+            }
+            Case::IfNoneMatchExisting => {
+                run.put("ifnonematch-existing", "bar", &[]).await?;
+                let response = run
+                    .put("ifnonematch-existing", "baz", &[("If-None-Match", "*")])
+                    .await?;
+                expect_error(
+                    response,
+                    StatusCode::PRECONDITION_FAILED,
+                    "PreconditionFailed",
+                )
+                .await
+            }
+            Case::IfMatchGood => {
+                let response = run.put("ifmatch-good", "bar", &[]).await?;
+                let etag = etag(&response)?.to_string();
+                let response = run
+                    .put("ifmatch-good", "baz", &[("If-Match", &etag)])
+                    .await?;
+                expect_etag(&response, b"baz")
+            }
+            Case::IfMatchFailed => {
+                run.put("ifmatch-failed", "bar", &[]).await?;
+                let stale = format!("\"{}\"", md5_hex(b"baz"));
+                let response = run
+                    .put("ifmatch-failed", "bar", &[("If-Match", &stale)])
+                    .await?;
+                expect_error(
+                    response,
+                    StatusCode::PRECONDITION_FAILED,
+                    "PreconditionFailed",
+                )
+                .await
+            }
+            Case::AwsChunkedTrailer => {
+                let headers = [
+                    ("Content-Encoding", "aws-chunked"),
+                    ("x-amz-content-sha256", "STREAMING-UNSIGNED-PAYLOAD-TRAILER"),
+                    ("x-amz-decoded-content-length", "3"),
+                    ("x-amz-trailer", "x-amz-checksum-crc32"),
+                ];
+                let body = "3\r\nbar\r\n0\r\nx-amz-checksum-crc32:dv+Mqg==\r\n\r\n";
+                let response = run.put("aws-chunked", body, &headers).await?;
+                expect_etag(&response, b"bar")
+            }
+            Case::HeadBucket => {
+                let response = run.send(Method::HEAD, "", &[]).await?;
+                expect_status(&response, StatusCode::OK)?;
+                match response.headers().get("x-amz-bucket-region") {
+                    Some(_) => Ok(()),
+                    None => Err("no x-amz-bucket-region header".into()),
+                }
+            }
+            Case::GetBucketLocation => {
+                let response = run.send(Method::GET, "?location", &[]).await?;
+                expect_status(&response, StatusCode::OK)?;
+                let body = response.text().await.map_err(|e| e.to_string())?;
+                if body.contains("<LocationConstraint") {
+                    Ok(())
+                } else {
+                    Err(format!("not a LocationConstraint: {}", body))
+                }
+            }
+            Case::ReadsRejected => {
+                run.put("read", "bar", &[]).await?;
+                let key = format!("/{}", run.key("read"));
+                for (method, path) in [
+                    (Method::GET, key.as_str()),
+                    (Method::DELETE, key.as_str()),
+                    (Method::GET, "?list-type=2"),
+                ] {
+                    let response = run.send(method.clone(), path, &[]).await?;
+                    expect_status(&response, StatusCode::NOT_FOUND)
+                        .map_err(|e| format!("{} {}: {}", method, path, e))?;
+                }
+                Ok(())
+            }
+            Case::UploadPartRejected => {
+                let path = format!("/{}?partNumber=1&uploadId=missing", run.key("part"));
+                let response = run.send_body(Method::PUT, &path, &[], "bar").await?;
+                expect_error(response, StatusCode::NOT_IMPLEMENTED, "NotImplemented").await
+            }
+            Case::CopyRejected => {
+                let source = run.key("etag");
+                let headers = [("x-amz-copy-source", source.as_str())];
+                let path = format!("/{}", run.key("copy"));
+                let response = run.send(Method::PUT, &path, &headers).await?;
+                expect_error(response, StatusCode::NOT_IMPLEMENTED, "NotImplemented").await
+            }
+        }
+    }
+}
+
+/// Outcome of one case
+#[derive(Debug, Clone)]
+pub struct CaseResult {
+    pub case: Case,
+    /// Why the case failed
+    pub error: Option<String>,
+}
+
+impl CaseResult {
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Requests of one conformance run
+struct Run<'a> {
+    client: &'a reqwest::Client,
+    options: &'a ConformanceOptions,
+    id: String,
+}
+
+impl Run<'_> {
+    /// Key of the run's object `name` (unencoded path segments)
+    fn key(&self, name: &str) -> String {
+        format!("{}/{}/{}", self.options.key_prefix, self.id, name)
+    }
+
+    async fn put(
+        &self,
+        name: &str,
+        body: &'static str,
+        headers: &[(&str, &str)],
+    ) -> Result<Response, String> {
+        let path = format!("/{}", self.key(name));
+        self.send_body(Method::PUT, &path, headers, body).await
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<Response, String> {
+        self.send_body(method, path, headers, "").await
+    }
+
+    /// Send a request to `target` followed by `path`
+    async fn send_body(
+        &self,
+        method: Method,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: &'static str,
+    ) -> Result<Response, String> {
+        let url = format!("{}{}", self.options.target.trim_end_matches('/'), path);
+        let mut request = self.client.request(method, &url);
+        for (name, value) in &self.options.headers {
+            request = request.header(name, value);
+        }
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        if !body.is_empty() {
+            request = request.body(body);
+        }
+        request.send().await.map_err(|e| e.to_string())
+    }
+}
+
+/// Run every case against `options.target`, in order
+pub async fn run(client: &reqwest::Client, options: &ConformanceOptions) -> Vec<CaseResult> {
+    let run = Run {
+        client,
+        options,
+        id: uuid::Uuid::new_v4().simple().to_string(),
+    };
+    let mut results = Vec::with_capacity(Case::ALL.len());
+    for case in Case::ALL {
+        let error = case.check(&run).await.err();
+        results.push(CaseResult { case, error });
+    }
+    results
+}
+
+/// Table of `results`, with a summary line
+pub fn report(results: &[CaseResult]) -> String {
+    let width = Case::ALL
+        .iter()
+        .map(|case| case.name().len())
+        .max()
+        .unwrap_or(0);
+    let mut out = format!("{:<width$}  RESULT  BEHAVIOR\n", "CASE");
+    for result in results {
+        out.push_str(&format!(
+            "{:<width$}  {:<6}  {}\n",
+            result.case.name(),
+            if result.passed() { "PASS" } else { "FAIL" },
+            result.case.description()
+        ));
+        if let Some(error) = &result.error {
+            out.push_str(&format!("{:<width$}          {}\n", "", error));
+        }
+    }
+    let passed = results.iter().filter(|result| result.passed()).count();
+    out.push_str(&format!("{}/{} passed\n", passed, results.len()));
+    out
+}
+
+fn md5_hex(body: &[u8]) -> String {
+    hex::encode(Md5::digest(body))
+}
+
+fn expect_status(response: &Response, status: StatusCode) -> Result<(), String> {
+    if response.status() == status {
+        Ok(())
+    } else {
+        Err(format!("expected {}, got {}", status, response.status()))
+    }
+}
+
+fn etag(response: &Response) -> Result<&str, String> {
+    response
+        .headers()
+        .get("etag")
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| "no ETag header".to_string())
+}
+
+/// Successful upload whose ETag is the MD5 of `body`
+fn expect_etag(response: &Response, body: &[u8]) -> Result<(), String> {
+    expect_status(response, StatusCode::OK)?;
+    let expected = format!("\"{}\"", md5_hex(body));
+    match etag(response)? {
+        etag if etag == expected => Ok(()),
+        etag => Err(format!("expected ETag {}, got {}", expected, etag)),
+    }
+}
+
+/// S3 error response with `status` and `code`
+async fn expect_error(response: Response, status: StatusCode, code: &str) -> Result<(), String> {
+    expect_status(&response, status)?;
+    let body = response.text().await.map_err(|e| e.to_string())?;
+    if body.contains(&format!("<Code>{}</Code>", code)) {
+        Ok(())
+    } else {
+        Err(format!("expected error code {}, got: {}", code, body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_case_names_unique() {
+        let mut names: Vec<_> = Case::ALL.iter().map(|case| case.name()).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), Case::ALL.len());
+    }
+
+    #[test]
+    fn test_report() {
+        let results = [
+            CaseResult {
+                case: Case::WriteEmpty,
+                error: None,
+            },
+            CaseResult {
+                case: Case::HeadBucket,
+                error: Some("expected 200 OK, got 404 Not Found".into()),
+            },
+        ];
+
+        let report = report(&results);
+
+        let lines: Vec<_> = report.lines().collect();
+        assert!(lines[1].starts_with("test_object_write_empty"));
+        assert!(lines[1].contains("PASS"));
+        assert!(lines[2].contains("FAIL"));
+        assert!(lines[3].trim() == "expected 200 OK, got 404 Not Found");
+        assert_eq!(lines[4], "1/2 passed");
+    }
+}
@@ -0,0 +1,118 @@
+//! Error categories and context
+//!
+//! Errors of every module fall into one of four [`ErrorCategory`]s: the
+//! client sent a bad request, it failed authentication or authorization,
+//! the storage backend failed, or the proxy itself did. The category gives
+//! the HTTP status of errors without a more specific one, the `type` label
+//! of `mizuchi_errors_total` and the `error.type` field of the request span.
+//!
+//! [`ErrorContext`] names the object an error happened on, so an error
+//! coming out of a multipart upload says which bucket, key and upload it
+//! belongs to.
+
+use hyper::StatusCode;
+use std::fmt;
+
+/// Who an error is attributed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    /// The request is invalid
+    Client,
+    /// The caller failed authentication or is not allowed the operation
+    Auth,
+    /// The storage backend (or an authorization service) failed
+    Backend,
+    /// The proxy failed, e.g. on local IO or a configuration problem
+    Server,
+}
+
+impl ErrorCategory {
+    /// Label of the category in metrics and spans
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Client => "client",
+            Self::Auth => "auth",
+            Self::Backend => "backend",
+            Self::Server => "server",
+        }
+    }
+
+    /// Response status of errors in the category
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::Client => StatusCode::BAD_REQUEST,
+            Self::Auth => StatusCode::FORBIDDEN,
+            Self::Backend => StatusCode::BAD_GATEWAY,
+            Self::Server => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Errors that can say which [`ErrorCategory`] they belong to
+pub trait Categorized {
+    fn category(&self) -> ErrorCategory;
+}
+
+/// Object an error happened on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorContext {
+    pub bucket: String,
+    pub key: String,
+    /// Multipart upload the object was written with, if any
+    pub upload_id: Option<String>,
+}
+
+impl ErrorContext {
+    pub fn new(bucket: impl Into<String>, key: impl Into<String>) -> Self {
+        Self {
+            bucket: bucket.into(),
+            key: key.into(),
+            upload_id: None,
+        }
+    }
+
+    /// The same object, written with multipart upload `upload_id`
+    #[must_use]
+    pub fn with_upload_id(mut self, upload_id: impl Into<String>) -> Self {
+        self.upload_id = Some(upload_id.into());
+        self
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bucket {}, key {}", self.bucket, self.key)?;
+        if let Some(upload_id) = &self.upload_id {
+            write!(f, ", upload {}", upload_id)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_display() {
+        let context = ErrorContext::new("uploads", "a/b.bin");
+        assert_eq!(context.to_string(), "bucket uploads, key a/b.bin");
+        assert_eq!(
+            context.with_upload_id("u-1").to_string(),
+            "bucket uploads, key a/b.bin, upload u-1"
+        );
+    }
+
+    #[test]
+    fn test_category_status() {
+        assert_eq!(ErrorCategory::Client.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(ErrorCategory::Backend.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(ErrorCategory::Server.as_str(), "server");
+    }
+}
@@ -0,0 +1,198 @@
+//! Offline garbage collection of multipart uploads
+//!
+//! `mizuchi-uploadr gc` aborts the multipart uploads left behind on every
+//! configured bucket, for deployments that don't run the in-process
+//! reconciler. It knows nothing about the uploads a running proxy is still
+//! driving, so `older_than` should be well above the longest upload.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use mizuchi_uploadr::config::Config;
+//! use mizuchi_uploadr::gc;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let config = Config::load("config.yaml")?;
+//! let older_than = gc::parse_duration("24h")?;
+//! let results = gc::run(&config, older_than, true).await;
+//! print!("{}", gc::summary_table(&results, true));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::config::Config;
+use crate::upload::backend::from_bucket_config;
+use crate::upload::reconcile::{sweep, ReconcileReport};
+use crate::upload::registry::UploadRegistry;
+use crate::upload::UploadError;
+use std::time::Duration;
+
+/// Outcome of collecting one configured bucket
+#[derive(Debug)]
+pub struct BucketGc {
+    /// Configured bucket name
+    pub name: String,
+    pub result: Result<ReconcileReport, UploadError>,
+}
+
+impl BucketGc {
+    /// Whether the bucket was listed and every abort succeeded
+    pub fn is_ok(&self) -> bool {
+        matches!(&self.result, Ok(report) if report.failed == 0)
+    }
+}
+
+/// Abort the multipart uploads started more than `older_than` ago in every
+/// configured bucket, or with `dry_run` only list them
+///
+/// Buckets are collected one after another; a bucket whose backend can't be
+/// built or listed is reported and skipped.
+pub async fn run(config: &Config, older_than: Duration, dry_run: bool) -> Vec<BucketGc> {
+    // Nothing is tracked: every old upload counts as orphaned
+    let registry = UploadRegistry::new();
+    let mut results = Vec::with_capacity(config.buckets.len());
+    for bucket in &config.buckets {
+        let result = match from_bucket_config(bucket) {
+            Ok(backend) => sweep(backend.as_ref(), &registry, older_than, dry_run).await,
+            Err(e) => Err(e),
+        };
+        results.push(BucketGc {
+            name: bucket.name.clone(),
+            result,
+        });
+    }
+    results
+}
+
+/// One row per bucket, plus a total
+pub fn summary_table(results: &[BucketGc], dry_run: bool) -> String {
+    let action = if dry_run { "ORPHANED" } else { "ABORTED" };
+    let name_width = results
+        .iter()
+        .map(|r| r.name.len())
+        .chain(["BUCKET".len(), "TOTAL".len()])
+        .max()
+        .unwrap_or_default();
+    let row = |name: &str, listed: &str, recent: &str, aborted: &str, failed: &str| {
+        format!(
+            "{:<name_width$}  {:>8}  {:>8}  {:>8}  {:>8}\n",
+            name, listed, recent, aborted, failed
+        )
+    };
+
+    let mut table = row("BUCKET", "LISTED", "RECENT", action, "FAILED");
+    // listed, recent, aborted, failed
+    let mut total = [0usize; 4];
+    let mut errors = Vec::new();
+    for result in results {
+        match &result.result {
+            Ok(report) => {
+                table.push_str(&row(
+                    &result.name,
+                    &report.listed.to_string(),
+                    &report.recent.to_string(),
+                    &report.aborted.len().to_string(),
+                    &report.failed.to_string(),
+                ));
+                let counts = [
+                    report.listed,
+                    report.recent,
+                    report.aborted.len(),
+                    report.failed,
+                ];
+                for (total, count) in total.iter_mut().zip(counts) {
+                    *total += count;
+                }
+            }
+            Err(e) => {
+                table.push_str(&row(&result.name, "-", "-", "-", "-"));
+                errors.push(format!("{}: {}", result.name, e));
+            }
+        }
+    }
+    let [listed, recent, aborted, failed] = total.map(|count| count.to_string());
+    table.push_str(&row("TOTAL", &listed, &recent, &aborted, &failed));
+    if !errors.is_empty() {
+        table.push('\n');
+    }
+    for error in errors {
+        table.push_str(&format!("error: {}\n", error));
+    }
+    table
+}
+
+/// Parse a duration such as `90s`, `30m`, `24h` or `7d` (a bare number is
+/// seconds)
+pub fn parse_duration(duration: &str) -> Result<Duration, String> {
+    let duration = duration.trim();
+    let split = duration
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(duration.len());
+    let (number, unit) = duration.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", duration))?;
+    let multiplier: u64 = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("invalid duration unit in '{}'", duration)),
+    };
+    number
+        .checked_mul(multiplier)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("duration '{}' is too large", duration))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::upload::multipart::PendingUpload;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(1800)));
+        assert_eq!(parse_duration("24h"), Ok(Duration::from_secs(86400)));
+        assert_eq!(parse_duration("7d"), Ok(Duration::from_secs(604800)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("1w").is_err());
+    }
+
+    #[test]
+    fn test_summary_table() {
+        let pending = PendingUpload {
+            key: "a.bin".into(),
+            upload_id: "u1".into(),
+            initiated: None,
+        };
+        let results = vec![
+            BucketGc {
+                name: "uploads".into(),
+                result: Ok(ReconcileReport {
+                    listed: 3,
+                    recent: 1,
+                    aborted: vec![pending.clone(), pending],
+                    ..Default::default()
+                }),
+            },
+            BucketGc {
+                name: "media".into(),
+                result: Err(UploadError::S3Error("access denied".into())),
+            },
+        ];
+
+        let table = summary_table(&results, true);
+
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "BUCKET     LISTED    RECENT  ORPHANED    FAILED");
+        assert_eq!(lines[1], "uploads         3         1         2         0");
+        assert_eq!(lines[2], "media           -         -         -         -");
+        assert_eq!(lines[3], "TOTAL           3         1         2         0");
+        assert!(table.contains("error: media: "));
+        assert!(!results[1].is_ok());
+    }
+}
@@ -9,6 +9,8 @@
 //! - **S3 Compatible**: Works with AWS SDKs and tools
 //! - **Flexible Auth**: JWT, SigV4, JWKS support
 //! - **Fine-Grained AuthZ**: OPA and OpenFGA integration
+//! - **Embeddable**: Mount the proxy in another server as a `tower` service
+//!   with [`layer`]
 //!
 //! # Example
 //!
@@ -26,11 +28,20 @@
 
 pub mod auth;
 pub mod authz;
+pub mod bench;
 pub mod config;
+pub mod conformance;
+pub mod error;
+pub mod gc;
+pub mod logging;
 pub mod metrics;
+pub mod notifications;
+pub mod profiling;
+pub mod redis;
 pub mod router;
 pub mod s3;
 pub mod server;
+pub mod state;
 pub mod upload;
 
 #[cfg(feature = "tracing")]
@@ -38,7 +49,8 @@ pub mod tracing;
 
 // Re-export commonly used types
 pub use config::Config;
-pub use server::Server;
+pub use server::service::{layer, UploadService};
+pub use server::{Server, ServerBuilder};
 
 /// Library version
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
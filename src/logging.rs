@@ -0,0 +1,298 @@
+//! Logging setup and runtime log filter control
+//!
+//! [`init`] installs the process-wide log subscriber described by
+//! `config.logging`, with a reloadable [`EnvFilter`]. The returned
+//! [`LogFilterHandle`] keeps the reload handle behind a type-erased closure,
+//! so the admin API and the SIGUSR1 handler can change the filter without
+//! knowing how the subscriber was assembled. [`init_profiling`] does the
+//! same for `--profile` mode, also recording spans for flamegraphs; the
+//! profiling spans stay enabled whatever the filter is changed to.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use mizuchi_uploadr::config::LoggingConfig;
+//! use mizuchi_uploadr::logging;
+//!
+//! # fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let handle = logging::init(&LoggingConfig::default(), None)?;
+//! handle.set("info,mizuchi_uploadr=debug")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::config::{LogFormat, LoggingConfig};
+use crate::profiling;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use thiserror::Error;
+use tracing_flame::{FlameLayer, FlushGuard};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{reload, EnvFilter};
+
+/// Filter used when neither the CLI nor `logging.filter` sets one
+pub const DEFAULT_FILTER: &str = "info";
+
+/// Logging errors
+#[derive(Error, Debug)]
+pub enum LoggingError {
+    #[error("Invalid log filter: {0}")]
+    InvalidFilter(String),
+
+    #[error("Failed to reload log filter: {0}")]
+    ReloadFailed(String),
+
+    #[error("Failed to read log filter file {path}: {error}")]
+    FilterFile { path: String, error: std::io::Error },
+
+    #[error("Failed to install log subscriber: {0}")]
+    InitFailed(String),
+
+    #[error("Failed to create profile output {path}: {error}")]
+    ProfileOutput { path: String, error: std::io::Error },
+}
+
+/// Install the global log subscriber
+///
+/// `filter` (from the command line) takes precedence over `logging.filter`.
+pub fn init(
+    config: &LoggingConfig,
+    filter: Option<&str>,
+) -> Result<Arc<LogFilterHandle>, LoggingError> {
+    let (handle, _) = install(config, directives(config, filter), None)?;
+    Ok(handle)
+}
+
+/// Install the global log subscriber for profiling mode
+///
+/// Like [`init`], with the [`profiling`](crate::profiling) spans enabled
+/// and every span recorded as folded stacks in `folded`. The stacks are
+/// flushed when the returned guard is dropped.
+pub fn init_profiling(
+    config: &LoggingConfig,
+    filter: Option<&str>,
+    folded: &Path,
+) -> Result<(Arc<LogFilterHandle>, ProfileGuard), LoggingError> {
+    let file = File::create(folded).map_err(|error| LoggingError::ProfileOutput {
+        path: folded.display().to_string(),
+        error,
+    })?;
+    let (handle, guard) = install(
+        config,
+        directives(config, filter),
+        Some(BufWriter::new(file)),
+    )?;
+    Ok((handle, guard.expect("flame layer installed")))
+}
+
+/// Flushes the folded stacks of profiling mode when dropped
+pub type ProfileGuard = FlushGuard<BufWriter<File>>;
+
+fn directives<'a>(config: &'a LoggingConfig, filter: Option<&'a str>) -> &'a str {
+    filter
+        .or(config.filter.as_deref())
+        .unwrap_or(DEFAULT_FILTER)
+        .trim()
+}
+
+/// Install the subscriber, recording spans to `folded` (with the
+/// profiling spans enabled) if given
+fn install(
+    config: &LoggingConfig,
+    directives: &str,
+    folded: Option<BufWriter<File>>,
+) -> Result<(Arc<LogFilterHandle>, Option<ProfileGuard>), LoggingError> {
+    let pinned = folded.as_ref().map(|_| profiling::FILTER);
+    let env_filter = with_pinned(directives, pinned)?;
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(env_filter)
+        .with_target(true)
+        .with_thread_ids(true);
+    let (handle, guard, result) = match config.format {
+        LogFormat::Json => {
+            let builder = builder.json().with_filter_reloading();
+            let handle = LogFilterHandle::new(directives, builder.reload_handle()).pin(pinned);
+            let flame = folded.map(flame_layer);
+            let guard = flame.as_ref().map(FlameLayer::flush_on_drop);
+            (
+                handle,
+                guard,
+                tracing::subscriber::set_global_default(builder.finish().with(flame)),
+            )
+        }
+        LogFormat::Pretty => {
+            let builder = builder.pretty().with_filter_reloading();
+            let handle = LogFilterHandle::new(directives, builder.reload_handle()).pin(pinned);
+            let flame = folded.map(flame_layer);
+            let guard = flame.as_ref().map(FlameLayer::flush_on_drop);
+            (
+                handle,
+                guard,
+                tracing::subscriber::set_global_default(builder.finish().with(flame)),
+            )
+        }
+    };
+    result.map_err(|e| LoggingError::InitFailed(e.to_string()))?;
+
+    Ok((Arc::new(handle), guard))
+}
+
+/// Layer writing spans to `writer` as folded stacks, merging the stacks of
+/// all threads so the same work on different workers adds up
+fn flame_layer<S>(writer: BufWriter<File>) -> FlameLayer<S, BufWriter<File>>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    FlameLayer::new(writer).with_threads_collapsed(true)
+}
+
+/// Apply the filter directives stored in `path`
+///
+/// Lines are joined with commas; blank lines and `#` comments are ignored.
+pub fn reload_from_file(handle: &LogFilterHandle, path: &Path) -> Result<(), LoggingError> {
+    let contents = std::fs::read_to_string(path).map_err(|error| LoggingError::FilterFile {
+        path: path.display().to_string(),
+        error,
+    })?;
+    let directives = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join(",");
+    handle.set(&directives)
+}
+
+/// Re-read `path` into the log filter on every SIGUSR1
+#[cfg(unix)]
+pub fn reload_on_sigusr1(
+    handle: Arc<LogFilterHandle>,
+    path: std::path::PathBuf,
+) -> Result<tokio::task::JoinHandle<()>, LoggingError> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut signals = signal(SignalKind::user_defined1())
+        .map_err(|e| LoggingError::InitFailed(format!("SIGUSR1 handler: {}", e)))?;
+    Ok(tokio::spawn(async move {
+        while signals.recv().await.is_some() {
+            if let Err(e) = reload_from_file(&handle, &path) {
+                tracing::warn!("Log filter not changed: {}", e);
+            }
+        }
+    }))
+}
+
+/// Filter of `directives` followed by `pinned`, if any
+fn with_pinned(directives: &str, pinned: Option<&str>) -> Result<EnvFilter, LoggingError> {
+    let directives = match pinned {
+        Some(pinned) if !directives.is_empty() => format!("{},{}", directives, pinned),
+        Some(pinned) => pinned.to_string(),
+        None => directives.to_string(),
+    };
+    EnvFilter::try_new(directives).map_err(|e| LoggingError::InvalidFilter(e.to_string()))
+}
+
+type Reload = Box<dyn Fn(EnvFilter) -> Result<(), String> + Send + Sync>;
+
+/// Handle for changing the log filter of a running process
+pub struct LogFilterHandle {
+    current: RwLock<String>,
+    pinned: Option<&'static str>,
+    reload: Reload,
+}
+
+impl LogFilterHandle {
+    /// Wrap the reload handle of a subscriber whose filter is `directives`
+    pub fn new<S: 'static>(directives: &str, handle: reload::Handle<EnvFilter, S>) -> Self {
+        Self {
+            current: RwLock::new(directives.to_string()),
+            pinned: None,
+            reload: Box::new(move |filter| handle.reload(filter).map_err(|e| e.to_string())),
+        }
+    }
+
+    /// Keep `directive` in the filter whatever it is [`set`](Self::set) to
+    pub fn pin(mut self, directive: Option<&'static str>) -> Self {
+        self.pinned = directive;
+        self
+    }
+
+    /// Filter directives currently in effect, besides the pinned one
+    pub fn current(&self) -> String {
+        self.current
+            .read()
+            .expect("log filter lock poisoned")
+            .clone()
+    }
+
+    /// Replace the filter with `directives` (`EnvFilter` syntax, e.g.
+    /// `info,mizuchi_uploadr::auth=debug`)
+    pub fn set(&self, directives: &str) -> Result<(), LoggingError> {
+        let directives = directives.trim();
+        let filter = with_pinned(directives, self.pinned)?;
+        let mut current = self.current.write().expect("log filter lock poisoned");
+        (self.reload)(filter).map_err(LoggingError::ReloadFailed)?;
+        *current = directives.to_string();
+        tracing::info!("Log filter changed to {}", directives);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn handle() -> (
+        reload::Layer<EnvFilter, tracing_subscriber::Registry>,
+        LogFilterHandle,
+    ) {
+        let (layer, handle) = reload::Layer::new(EnvFilter::new("info"));
+        (layer, LogFilterHandle::new("info", handle))
+    }
+
+    #[test]
+    fn test_reload_from_file() {
+        let (_layer, filter) = handle();
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "# verbose auth\ninfo\n\nmizuchi_uploadr::auth=debug").unwrap();
+
+        reload_from_file(&filter, file.path()).unwrap();
+        assert_eq!(filter.current(), "info,mizuchi_uploadr::auth=debug");
+
+        assert!(reload_from_file(&filter, Path::new("/nonexistent/filter")).is_err());
+        assert_eq!(filter.current(), "info,mizuchi_uploadr::auth=debug");
+    }
+
+    #[test]
+    fn test_set_filter() {
+        let (_layer, filter) = handle();
+
+        filter.set("debug,hyper=warn").unwrap();
+        assert_eq!(filter.current(), "debug,hyper=warn");
+
+        assert!(filter.set("mizuchi=loud").is_err());
+        assert_eq!(filter.current(), "debug,hyper=warn");
+    }
+
+    #[test]
+    fn test_pinned_directive_survives_set() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let (layer, handle) =
+            reload::Layer::new(with_pinned("info", Some(profiling::FILTER)).unwrap());
+        let filter = LogFilterHandle::new("info", handle).pin(Some(profiling::FILTER));
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            filter.set("warn").unwrap();
+            assert_eq!(filter.current(), "warn");
+            assert!(!profiling::profile_span!("sha256").is_disabled());
+            assert!(!tracing::enabled!(tracing::Level::INFO));
+        });
+    }
+}
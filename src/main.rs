@@ -2,11 +2,19 @@
 //!
 //! A secure, zero-copy S3 proxy that only allows upload operations.
 
-use clap::Parser;
-use mizuchi_uploadr::{config::Config, server::Server};
-use std::path::PathBuf;
-use tracing::{info, Level};
-use tracing_subscriber::FmtSubscriber;
+use clap::{Parser, Subcommand};
+use mizuchi_uploadr::bench::{self, BenchOptions};
+use mizuchi_uploadr::config::{self, CheckOptions, Config};
+use mizuchi_uploadr::conformance::{self, ConformanceOptions};
+use mizuchi_uploadr::{gc, logging, profiling, server::Server};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::info;
+
+/// Counts allocations for `--heap-interval`; passes them through otherwise
+#[cfg(feature = "dhat")]
+#[global_allocator]
+static ALLOC: dhat::Alloc = dhat::Alloc;
 
 /// Mizuchi Uploadr - Upload-only S3 proxy with zero-copy optimization
 #[derive(Parser, Debug)]
@@ -17,43 +25,265 @@ struct Args {
     #[arg(short, long, default_value = "config.yaml")]
     config: PathBuf,
 
-    /// Log level (trace, debug, info, warn, error)
-    #[arg(short, long, default_value = "info")]
-    log_level: String,
+    /// Log filter: a level (trace, debug, info, warn, error) or `EnvFilter`
+    /// directives such as "info,mizuchi_uploadr::auth=debug".
+    /// Overrides `logging.filter` (default: info)
+    #[arg(short, long)]
+    log_level: Option<String>,
+
+    /// Check a configuration file, print every problem found and exit
+    /// (non-zero if there are any)
+    #[arg(long, value_name = "PATH")]
+    check_config: Option<PathBuf>,
+
+    /// With --check-config, also fetch each bucket's JWKS
+    #[arg(long, requires = "check_config")]
+    check_jwks: bool,
+
+    /// Profiling mode: enable fine-grained spans around hashing, signing,
+    /// S3 requests and syscalls, and record them as folded stacks in
+    /// DIR/tracing.folded (default DIR: profile)
+    #[arg(long, value_name = "DIR", num_args = 0..=1, default_missing_value = "profile")]
+    profile: Option<PathBuf>,
+
+    /// With --profile, also write a CPU flamegraph of every interval, e.g.
+    /// 30s or 5m (builds with the pprof feature)
+    #[arg(long, requires = "profile", value_parser = gc::parse_duration)]
+    pprof_interval: Option<Duration>,
+
+    /// With --profile, also profile the heap: append a snapshot of its
+    /// usage to DIR/heap.jsonl every interval, and write allocation sites
+    /// to DIR/dhat-heap.json on exit (builds with the dhat feature)
+    #[arg(long, requires = "profile", value_parser = gc::parse_duration)]
+    heap_interval: Option<Duration>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Upload objects to a running deployment and report throughput and
+    /// latency percentiles (exits non-zero if any upload fails)
+    Bench(BenchArgs),
+    /// Abort multipart uploads left behind in every configured bucket and
+    /// print a summary (exits non-zero if a bucket or an abort fails)
+    Gc(GcArgs),
+    /// Check a running deployment's S3 behaviors against a subset of the
+    /// Ceph s3-tests (exits non-zero if any check fails)
+    Conformance(ConformanceArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct BenchArgs {
+    /// URL prefix to upload under, e.g. http://localhost:8080/uploads
+    #[arg(long)]
+    target: String,
+
+    /// Object size, e.g. 512, 64K, 1M or 1GiB
+    #[arg(long, default_value = "1M", value_parser = bench::parse_size)]
+    size: usize,
+
+    /// Uploads in flight at once
+    #[arg(long, default_value_t = 64)]
+    concurrency: usize,
+
+    /// Total number of uploads
+    #[arg(long, default_value_t = 1000)]
+    requests: usize,
+
+    /// Header sent with every upload, e.g. "Authorization: Bearer <token>"
+    /// (repeatable)
+    #[arg(long = "header", short = 'H', value_parser = parse_header)]
+    headers: Vec<(String, String)>,
+}
+
+#[derive(clap::Args, Debug)]
+struct GcArgs {
+    /// Path to configuration file
+    #[arg(short, long, default_value = "config.yaml")]
+    config: PathBuf,
+
+    /// Only abort uploads started longer ago than this, e.g. 90m, 24h or 7d
+    #[arg(long, default_value = "24h", value_parser = gc::parse_duration)]
+    older_than: Duration,
+
+    /// List the uploads that would be aborted without aborting them
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct ConformanceArgs {
+    /// URL of a bucket's path prefix, e.g. http://localhost:8080/uploads
+    #[arg(long)]
+    target: String,
+
+    /// Header sent with every request, e.g. "Authorization: Bearer <token>"
+    #[arg(long = "header", short = 'H', value_parser = parse_header)]
+    headers: Vec<(String, String)>,
+
+    /// First component of the keys uploaded
+    #[arg(long, default_value = "conformance")]
+    key_prefix: String,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
-    // Initialize logging
-    let level = match args.log_level.to_lowercase().as_str() {
-        "trace" => Level::TRACE,
-        "debug" => Level::DEBUG,
-        "info" => Level::INFO,
-        "warn" => Level::WARN,
-        "error" => Level::ERROR,
-        _ => Level::INFO,
-    };
+    match args.command {
+        Some(Command::Bench(bench_args)) => {
+            let ok = run_bench(bench_args).await;
+            std::process::exit(if ok { 0 } else { 1 });
+        }
+        Some(Command::Gc(gc_args)) => {
+            let ok = run_gc(gc_args).await?;
+            std::process::exit(if ok { 0 } else { 1 });
+        }
+        Some(Command::Conformance(conformance_args)) => {
+            let ok = run_conformance(conformance_args).await;
+            std::process::exit(if ok { 0 } else { 1 });
+        }
+        None => {}
+    }
 
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(level)
-        .with_target(true)
-        .with_thread_ids(true)
-        .json()
-        .finish();
+    if let Some(ref path) = args.check_config {
+        let ok = check_config(path, args.check_jwks).await?;
+        std::process::exit(if ok { 0 } else { 1 });
+    }
 
-    tracing::subscriber::set_global_default(subscriber)?;
+    // Load configuration first: it decides the log format
+    let config = Config::load(&args.config)?;
 
-    info!("Starting Mizuchi Uploadr v{}", env!("CARGO_PKG_VERSION"));
+    // Initialize logging; the filter can be changed later through the admin
+    // API, or by editing logging.filter_file and sending SIGUSR1
+    let (log_filter, _profile_guard, _heap_profiler) = match args.profile {
+        Some(ref dir) => {
+            let folded = profiling::prepare_output(dir)?;
+            let (log_filter, guard) =
+                logging::init_profiling(&config.logging, args.log_level.as_deref(), &folded)?;
+            if let Some(interval) = args.pprof_interval {
+                profiling::spawn_cpu_profiler(dir.clone(), interval)?;
+            }
+            let heap_profiler = match args.heap_interval {
+                Some(interval) => Some(profiling::start_heap_profiler(dir.clone(), interval)?),
+                None => None,
+            };
+            info!("Profiling: recording spans to {}", folded.display());
+            (log_filter, Some(guard), heap_profiler)
+        }
+        None => (
+            logging::init(&config.logging, args.log_level.as_deref())?,
+            None,
+            None,
+        ),
+    };
+    #[cfg(unix)]
+    if let Some(ref path) = config.logging.filter_file {
+        logging::reload_on_sigusr1(log_filter.clone(), path.into())?;
+    }
 
-    // Load configuration
-    let config = Config::load(&args.config)?;
+    info!("Starting Mizuchi Uploadr v{}", env!("CARGO_PKG_VERSION"));
     info!("Loaded configuration from {:?}", args.config);
 
     // Start server
-    let server = Server::new(config)?;
+    let server = Server::new(config)?.with_log_filter(log_filter);
     server.run().await?;
 
     Ok(())
 }
+
+/// Print every problem in the configuration file; true if there are none
+async fn check_config(path: &Path, jwks: bool) -> anyhow::Result<bool> {
+    let options = CheckOptions {
+        jwks,
+        ..Default::default()
+    };
+    let errors = config::check_file(path, &options).await?;
+    if errors.is_empty() {
+        println!("{}: OK", path.display());
+        return Ok(true);
+    }
+
+    println!("{}: {} problem(s)", path.display(), errors.len());
+    for error in &errors {
+        println!("  {}", error);
+    }
+    Ok(false)
+}
+
+/// Run the load generator and print its report; true if every upload succeeded
+async fn run_bench(args: BenchArgs) -> bool {
+    let options = BenchOptions {
+        target: args.target,
+        size: args.size,
+        concurrency: args.concurrency,
+        requests: args.requests,
+        headers: args.headers,
+        ..Default::default()
+    };
+    println!(
+        "Uploading {} x {} bytes to {} ({} concurrent)",
+        options.requests, options.size, options.target, options.concurrency
+    );
+    let metrics = bench::run(&reqwest::Client::new(), &options).await;
+
+    println!("Requests:    {}", metrics.total_requests);
+    println!("Successful:  {}", metrics.successful_requests);
+    println!("Failed:      {}", metrics.failed_requests);
+    println!(
+        "Duration:    {:.2}s",
+        metrics.total_duration_ms as f64 / 1000.0
+    );
+    println!(
+        "Throughput:  {:.2} req/s, {:.2} MB/s",
+        metrics.requests_per_sec(),
+        metrics.throughput_mbps()
+    );
+    println!(
+        "Latency:     p50 {:.2}ms, p95 {:.2}ms, p99 {:.2}ms, max {:.2}ms",
+        metrics.p50_latency_ms(),
+        metrics.p95_latency_ms(),
+        metrics.p99_latency_ms(),
+        metrics.max_latency_ms()
+    );
+    metrics.failed_requests == 0
+}
+
+/// Collect every bucket's orphaned multipart uploads and print a summary;
+/// true if every bucket was listed and every abort succeeded
+async fn run_gc(args: GcArgs) -> anyhow::Result<bool> {
+    let config = Config::load(&args.config)?;
+    println!(
+        "{} multipart uploads older than {}s in {} bucket(s){}",
+        if args.dry_run { "Listing" } else { "Aborting" },
+        args.older_than.as_secs(),
+        config.buckets.len(),
+        if args.dry_run { " (dry run)" } else { "" }
+    );
+    let results = gc::run(&config, args.older_than, args.dry_run).await;
+    print!("{}", gc::summary_table(&results, args.dry_run));
+    Ok(results.iter().all(gc::BucketGc::is_ok))
+}
+
+/// Run the conformance checks and print their results; true if all passed
+async fn run_conformance(args: ConformanceArgs) -> bool {
+    let options = ConformanceOptions {
+        target: args.target,
+        headers: args.headers,
+        key_prefix: args.key_prefix,
+    };
+    println!("Checking S3 conformance of {}", options.target);
+    let results = conformance::run(&reqwest::Client::new(), &options).await;
+    print!("{}", conformance::report(&results));
+    results.iter().all(conformance::CaseResult::passed)
+}
+
+/// Parse a `Name: value` header
+fn parse_header(header: &str) -> Result<(String, String), String> {
+    let (name, value) = header
+        .split_once(':')
+        .ok_or_else(|| format!("expected 'Name: value', got '{}'", header))?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
@@ -2,12 +2,15 @@
 //!
 //! Provides Prometheus metrics and OpenTelemetry tracing.
 
+pub mod process;
 pub mod server;
+pub mod tenant;
 
 use lazy_static::lazy_static;
 use prometheus::{
-    register_counter, register_counter_vec, register_histogram, register_histogram_vec, Counter,
-    CounterVec, Histogram, HistogramVec,
+    exponential_buckets, register_counter, register_counter_vec, register_gauge_vec,
+    register_histogram, register_histogram_vec, register_int_gauge, register_int_gauge_vec,
+    Counter, CounterVec, GaugeVec, Histogram, HistogramVec, IntGauge, IntGaugeVec,
 };
 
 lazy_static! {
@@ -23,6 +26,33 @@ lazy_static! {
         "Total bytes uploaded"
     ).unwrap();
 
+    pub static ref BUCKET_UPLOAD_BYTES: CounterVec = register_counter_vec!(
+        "mizuchi_bucket_upload_bytes_total",
+        "Bytes uploaded per bucket, including multipart parts",
+        &["bucket"]
+    ).unwrap();
+
+    // 1 KiB to 4 GiB in powers of 4
+    pub static ref UPLOAD_SIZE: HistogramVec = register_histogram_vec!(
+        "mizuchi_upload_size_bytes",
+        "Size of single-request uploads in bytes",
+        &["bucket"],
+        exponential_buckets(1024.0, 4.0, 12).unwrap()
+    ).unwrap();
+
+    // Only reported when `metrics.tenant_labels` is set
+    pub static ref TENANT_UPLOADS_TOTAL: CounterVec = register_counter_vec!(
+        "mizuchi_tenant_uploads_total",
+        "Total number of uploads per tenant",
+        &["bucket", "tenant", "status"]
+    ).unwrap();
+
+    pub static ref TENANT_UPLOAD_BYTES: CounterVec = register_counter_vec!(
+        "mizuchi_tenant_upload_bytes_total",
+        "Bytes uploaded per tenant",
+        &["bucket", "tenant"]
+    ).unwrap();
+
     pub static ref UPLOAD_DURATION: HistogramVec = register_histogram_vec!(
         "mizuchi_upload_duration_seconds",
         "Upload duration in seconds",
@@ -30,6 +60,15 @@ lazy_static! {
         vec![0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0]
     ).unwrap();
 
+    // Time spent in each phase of an upload request: "auth", "authz",
+    // "body" (reading, buffering and transforming), "s3" and "total"
+    pub static ref REQUEST_PHASE_DURATION: HistogramVec = register_histogram_vec!(
+        "mizuchi_request_phase_duration_seconds",
+        "Upload request time spent per phase in seconds",
+        &["bucket", "phase"],
+        vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]
+    ).unwrap();
+
     // Zero-copy metrics
     pub static ref ZERO_COPY_BYTES: Counter = register_counter!(
         "mizuchi_zero_copy_bytes_total",
@@ -69,6 +108,223 @@ lazy_static! {
         &["method", "status"]
     ).unwrap();
 
+    pub static ref AUTH_DURATION: HistogramVec = register_histogram_vec!(
+        "mizuchi_auth_duration_seconds",
+        "Time spent in each authenticator",
+        &["method"],
+        vec![0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0]
+    ).unwrap();
+
+    pub static ref JWKS_REFRESH_TOTAL: CounterVec = register_counter_vec!(
+        "mizuchi_jwks_refresh_total",
+        "JWKS fetches by trigger",
+        &["trigger"]  // "scheduled", "expired" or "unknown_kid"
+    ).unwrap();
+
+    pub static ref JWKS_REFRESH_FAILURES: CounterVec = register_counter_vec!(
+        "mizuchi_jwks_refresh_failures_total",
+        "Failed JWKS fetches by trigger",
+        &["trigger"]
+    ).unwrap();
+
+    pub static ref JWT_REVOCATION_REFRESHES: CounterVec = register_counter_vec!(
+        "mizuchi_jwt_revocation_refreshes_total",
+        "JWT revocation list loads",
+        &["status"]  // "success" or "failure"
+    ).unwrap();
+
+    pub static ref JWT_REVOKED: CounterVec = register_counter_vec!(
+        "mizuchi_jwt_revoked_total",
+        "JWTs rejected as revoked",
+        &["reason"]  // "jti" or "not_before"
+    ).unwrap();
+
+    // Authorization metrics
+    pub static ref AUTHZ_DEGRADED_DECISIONS: CounterVec = register_counter_vec!(
+        "mizuchi_authz_degraded_decisions_total",
+        "Decisions made while the authorizer was failing",
+        &["bucket", "outcome"]  // "fail_closed", "fail_open" or "last_known"
+    ).unwrap();
+
+    pub static ref AUTHZ_HEALTHY: IntGaugeVec = register_int_gauge_vec!(
+        "mizuchi_authz_healthy",
+        "Whether the bucket's authorizer passed its last health check (1) or not (0)",
+        &["bucket"]
+    ).unwrap();
+
+    pub static ref AUTHZ_COALESCED_CHECKS: CounterVec = register_counter_vec!(
+        "mizuchi_authz_coalesced_checks_total",
+        "Authorization checks answered by an identical check already in flight",
+        &["authorizer"]  // "opa" or "openfga"
+    ).unwrap();
+
+    pub static ref AUTHZ_SCOPE_MISMATCHES: CounterVec = register_counter_vec!(
+        "mizuchi_authz_scope_mismatches_total",
+        "Requests denied because the token's scopes don't grant the action",
+        &["bucket", "reason"]  // "missing", "action" or "prefix"
+    ).unwrap();
+
+    pub static ref AUTHZ_SHADOW_DECISIONS: CounterVec = register_counter_vec!(
+        "mizuchi_authz_shadow_decisions_total",
+        "Shadow authorizer decisions compared with the enforced ones",
+        &["bucket", "outcome"]  // "agree", "would_allow", "would_deny" or "error"
+    ).unwrap();
+
+    pub static ref OPA_DECISION_LOGS: CounterVec = register_counter_vec!(
+        "mizuchi_opa_decision_logs_total",
+        "OPA decision log events by push status",
+        &["status"]  // "delivered", "failed" or "dropped"
+    ).unwrap();
+
+    // Notification metrics
+    pub static ref NOTIFICATIONS_TOTAL: CounterVec = register_counter_vec!(
+        "mizuchi_notifications_total",
+        "Upload notifications by delivery status",
+        &["status"]  // "delivered", "failed" or "dropped"
+    ).unwrap();
+
+    // Replication metrics
+    pub static ref REPLICATION_LAG: HistogramVec = register_histogram_vec!(
+        "mizuchi_replication_lag_seconds",
+        "Time between the primary write and the replica write",
+        &["bucket"],
+        vec![0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0]
+    ).unwrap();
+
+    pub static ref REPLICATION_FAILURES: CounterVec = register_counter_vec!(
+        "mizuchi_replication_failures_total",
+        "Failed replica writes",
+        &["bucket", "reason"]  // "error" or "queue_full"
+    ).unwrap();
+
+    // Failover metrics
+    pub static ref S3_CIRCUIT_STATE: IntGaugeVec = register_int_gauge_vec!(
+        "mizuchi_s3_circuit_state",
+        "Circuit breaker state of the primary S3 endpoint (0 = closed, 1 = half-open, 2 = open)",
+        &["bucket"]
+    ).unwrap();
+
+    pub static ref S3_FAILOVERS_TOTAL: CounterVec = register_counter_vec!(
+        "mizuchi_s3_failovers_total",
+        "Requests routed to the fallback S3 endpoint",
+        &["bucket"]
+    ).unwrap();
+
+    pub static ref S3_CLOCK_SKEW: GaugeVec = register_gauge_vec!(
+        "mizuchi_s3_clock_skew_seconds",
+        "Measured offset of the S3 endpoint clock from the local clock (positive = server ahead)",
+        &["bucket"]
+    ).unwrap();
+
+    pub static ref S3_HEDGED_REQUESTS: CounterVec = register_counter_vec!(
+        "mizuchi_s3_hedged_requests_total",
+        "Hedged PutObject attempts sent, and which attempt won",
+        &["bucket", "outcome"]  // "sent", "primary_won" or "hedge_won"
+    ).unwrap();
+
+    // Codes without a name of their own are counted as "other"
+    pub static ref S3_ERRORS_TOTAL: CounterVec = register_counter_vec!(
+        "mizuchi_s3_errors_total",
+        "S3 error responses by error code",
+        &["bucket", "code"]
+    ).unwrap();
+
+    // Spill buffer metrics
+    pub static ref SPILL_BYTES: IntGaugeVec = register_int_gauge_vec!(
+        "mizuchi_spill_bytes",
+        "Bytes of request bodies currently held in spill files",
+        &["bucket"]
+    ).unwrap();
+
+    // Buffer pool metrics
+    pub static ref BUFFER_POOL_IDLE_BYTES: IntGauge = register_int_gauge!(
+        "mizuchi_buffer_pool_idle_bytes",
+        "Bytes of upload buffers kept idle for reuse"
+    ).unwrap();
+
+    pub static ref BUFFER_POOL_IDLE_BUFFERS: IntGauge = register_int_gauge!(
+        "mizuchi_buffer_pool_idle_buffers",
+        "Upload buffers kept idle for reuse"
+    ).unwrap();
+
+    pub static ref BUFFER_POOL_IN_USE_BYTES: IntGauge = register_int_gauge!(
+        "mizuchi_buffer_pool_in_use_bytes",
+        "Bytes of pooled upload buffers holding request bodies"
+    ).unwrap();
+
+    pub static ref BUFFER_POOL_ACQUIRES: CounterVec = register_counter_vec!(
+        "mizuchi_buffer_pool_acquires_total",
+        "Upload buffers taken from the pool",
+        &["result"]
+    ).unwrap();
+
+    pub static ref BUFFER_POOL_RELEASES: CounterVec = register_counter_vec!(
+        "mizuchi_buffer_pool_releases_total",
+        "Upload buffers given back to the pool",
+        &["result"]
+    ).unwrap();
+
+    // Flow control metrics
+    pub static ref FLOW_PAUSES: CounterVec = register_counter_vec!(
+        "mizuchi_upload_pauses_total",
+        "Times reading an upload body was paused for the backend to catch up",
+        &["bucket"]
+    ).unwrap();
+
+    pub static ref FLOW_PAUSED_SECONDS: CounterVec = register_counter_vec!(
+        "mizuchi_upload_paused_seconds_total",
+        "Time spent with reading upload bodies paused",
+        &["bucket"]
+    ).unwrap();
+
+    // Region routing metrics
+    pub static ref REGION_UPLOADS: CounterVec = register_counter_vec!(
+        "mizuchi_region_uploads_total",
+        "Uploads routed to each region",
+        &["bucket", "region"]
+    ).unwrap();
+
+    pub static ref REGION_LATENCY: GaugeVec = register_gauge_vec!(
+        "mizuchi_region_latency_seconds",
+        "Moving average of upload latency to each region",
+        &["bucket", "region"]
+    ).unwrap();
+
+    // Body length metrics
+    pub static ref BODY_LENGTH_MISMATCHES: CounterVec = register_counter_vec!(
+        "mizuchi_body_length_mismatches_total",
+        "Upload bodies whose length differs from their declared length",
+        &["bucket", "kind"]  // "truncated" or "overlong"
+    ).unwrap();
+
+    // Content type metrics
+    pub static ref CONTENT_TYPE_VIOLATIONS: CounterVec = register_counter_vec!(
+        "mizuchi_content_type_violations_total",
+        "Uploads whose declared or detected content type is not accepted",
+        &["bucket", "reason"]  // "not_allowed" or "mismatch"
+    ).unwrap();
+
+    // Transform metrics
+    pub static ref TRANSFORMS_TOTAL: CounterVec = register_counter_vec!(
+        "mizuchi_transforms_total",
+        "Upload bodies run through a transform plugin",
+        &["bucket", "outcome"]  // "transformed", "rejected" or "failed"
+    ).unwrap();
+
+    // Compression metrics
+    pub static ref COMPRESSION_BYTES: CounterVec = register_counter_vec!(
+        "mizuchi_compression_bytes_total",
+        "Bytes of compressed upload bodies before and after compression",
+        &["bucket", "stage"]  // "original" or "compressed"
+    ).unwrap();
+
+    // Quota metrics
+    pub static ref QUOTA_REJECTIONS: CounterVec = register_counter_vec!(
+        "mizuchi_quota_rejections_total",
+        "Uploads rejected for exceeding their owner's quota",
+        &["bucket", "window"]  // "daily" or "monthly"
+    ).unwrap();
+
     // Error metrics
     pub static ref ERRORS_TOTAL: CounterVec = register_counter_vec!(
         "mizuchi_errors_total",
@@ -80,7 +336,18 @@ lazy_static! {
 /// Record a successful upload
 pub fn record_upload_success(bucket: &str, bytes: u64) {
     UPLOADS_TOTAL.with_label_values(&[bucket, "success"]).inc();
+    UPLOAD_SIZE
+        .with_label_values(&[bucket])
+        .observe(bytes as f64);
+    record_upload_bytes(bucket, bytes);
+}
+
+/// Record bytes stored for a bucket (an upload or a multipart part)
+pub fn record_upload_bytes(bucket: &str, bytes: u64) {
     UPLOAD_BYTES_TOTAL.inc_by(bytes as f64);
+    BUCKET_UPLOAD_BYTES
+        .with_label_values(&[bucket])
+        .inc_by(bytes as f64);
 }
 
 /// Record a failed upload
@@ -88,6 +355,19 @@ pub fn record_upload_failure(bucket: &str) {
     UPLOADS_TOTAL.with_label_values(&[bucket, "failure"]).inc();
 }
 
+/// Record an upload's outcome under its tenant label (see [`tenant`])
+pub fn record_tenant_upload(bucket: &str, tenant: &str, success: bool, bytes: u64) {
+    let status = if success { "success" } else { "failure" };
+    TENANT_UPLOADS_TOTAL
+        .with_label_values(&[bucket, tenant, status])
+        .inc();
+    if success {
+        TENANT_UPLOAD_BYTES
+            .with_label_values(&[bucket, tenant])
+            .inc_by(bytes as f64);
+    }
+}
+
 /// Record upload duration
 pub fn record_upload_duration(bucket: &str, method: &str, duration_secs: f64) {
     UPLOAD_DURATION
@@ -95,6 +375,13 @@ pub fn record_upload_duration(bucket: &str, method: &str, duration_secs: f64) {
         .observe(duration_secs);
 }
 
+/// Record the time an upload request spent in one phase
+pub fn record_phase_duration(bucket: &str, phase: &str, duration_secs: f64) {
+    REQUEST_PHASE_DURATION
+        .with_label_values(&[bucket, phase])
+        .observe(duration_secs);
+}
+
 /// Record a data transfer with mode tracking
 ///
 /// # Arguments
@@ -125,11 +412,207 @@ pub fn record_auth_attempt(method: &str, success: bool) {
     AUTH_ATTEMPTS.with_label_values(&[method, status]).inc();
 }
 
+/// Record the outcome of one authenticator in an auth chain
+///
+/// `status` is "success", "failure" or "missing" (no credentials for this
+/// authenticator in the request).
+pub fn record_auth_outcome(method: &str, status: &str, duration_secs: f64) {
+    AUTH_ATTEMPTS.with_label_values(&[method, status]).inc();
+    AUTH_DURATION
+        .with_label_values(&[method])
+        .observe(duration_secs);
+}
+
+/// Record a JWKS fetch
+pub fn record_jwks_refresh(trigger: &str, success: bool) {
+    JWKS_REFRESH_TOTAL.with_label_values(&[trigger]).inc();
+    if !success {
+        JWKS_REFRESH_FAILURES.with_label_values(&[trigger]).inc();
+    }
+}
+
+/// Record a load of the JWT revocation list
+pub fn record_jwt_revocation_refresh(success: bool) {
+    let status = if success { "success" } else { "failure" };
+    JWT_REVOCATION_REFRESHES.with_label_values(&[status]).inc();
+}
+
+/// Record a JWT rejected as revoked
+pub fn record_jwt_revoked(reason: &str) {
+    JWT_REVOKED.with_label_values(&[reason]).inc();
+}
+
+/// Record `count` OPA decision log events by push status
+pub fn record_opa_decision_logs(status: &str, count: u64) {
+    OPA_DECISION_LOGS
+        .with_label_values(&[status])
+        .inc_by(count as f64);
+}
+
+/// Record the outcome of a bucket's authorizer health check
+pub fn record_authz_healthy(bucket: &str, healthy: bool) {
+    AUTHZ_HEALTHY
+        .with_label_values(&[bucket])
+        .set(i64::from(healthy));
+}
+
+/// Record a check answered by an identical one already in flight
+pub fn record_authz_coalesced(authorizer: &str) {
+    AUTHZ_COALESCED_CHECKS
+        .with_label_values(&[authorizer])
+        .inc();
+}
+
+/// Record a request denied by scope rules before authorization
+pub fn record_authz_scope_mismatch(bucket: &str, reason: &str) {
+    AUTHZ_SCOPE_MISMATCHES
+        .with_label_values(&[bucket, reason])
+        .inc();
+}
+
+/// Record a decision made without a working authorizer
+pub fn record_authz_degraded(bucket: &str, outcome: &str) {
+    AUTHZ_DEGRADED_DECISIONS
+        .with_label_values(&[bucket, outcome])
+        .inc();
+}
+
+/// Record a shadow authorizer decision compared with the enforced one
+pub fn record_authz_shadow(bucket: &str, outcome: &str) {
+    AUTHZ_SHADOW_DECISIONS
+        .with_label_values(&[bucket, outcome])
+        .inc();
+}
+
 /// Record an error
 pub fn record_error(error_type: &str) {
     ERRORS_TOTAL.with_label_values(&[error_type]).inc();
 }
 
+/// Record a webhook notification outcome
+pub fn record_notification(status: &str) {
+    NOTIFICATIONS_TOTAL.with_label_values(&[status]).inc();
+}
+
+/// Record the lag of a replica write
+pub fn record_replication_lag(bucket: &str, lag_secs: f64) {
+    REPLICATION_LAG
+        .with_label_values(&[bucket])
+        .observe(lag_secs);
+}
+
+/// Record a failed replica write
+pub fn record_replication_failure(bucket: &str, reason: &str) {
+    REPLICATION_FAILURES
+        .with_label_values(&[bucket, reason])
+        .inc();
+}
+
+/// Record the circuit breaker state of a bucket's primary endpoint
+pub fn record_circuit_state(bucket: &str, state: i64) {
+    S3_CIRCUIT_STATE.with_label_values(&[bucket]).set(state);
+}
+
+/// Record a request routed to a bucket's fallback endpoint
+pub fn record_failover(bucket: &str) {
+    S3_FAILOVERS_TOTAL.with_label_values(&[bucket]).inc();
+}
+
+/// Record the clock offset measured against a bucket's S3 endpoint
+pub fn record_clock_skew(bucket: &str, offset_secs: f64) {
+    S3_CLOCK_SKEW.with_label_values(&[bucket]).set(offset_secs);
+}
+
+/// Record a hedged PutObject being sent, or the attempt that won
+pub fn record_s3_hedge(bucket: &str, outcome: &str) {
+    S3_HEDGED_REQUESTS
+        .with_label_values(&[bucket, outcome])
+        .inc();
+}
+
+/// Record an error response from a bucket's S3 endpoint
+pub fn record_s3_error(bucket: &str, code: &str) {
+    S3_ERRORS_TOTAL.with_label_values(&[bucket, code]).inc();
+}
+
+/// Record the bytes a bucket currently holds in spill files
+pub fn record_spill_bytes(bucket: &str, bytes: u64) {
+    SPILL_BYTES
+        .with_label_values(&[bucket])
+        .set(i64::try_from(bytes).unwrap_or(i64::MAX));
+}
+
+/// Record taking a buffer from the pool, `reused` or newly `allocated`
+pub fn record_buffer_pool_acquire(result: &str) {
+    BUFFER_POOL_ACQUIRES.with_label_values(&[result]).inc();
+}
+
+/// Record giving a buffer back to the pool, `pooled` or `discarded`
+pub fn record_buffer_pool_release(result: &str) {
+    BUFFER_POOL_RELEASES.with_label_values(&[result]).inc();
+}
+
+/// Record the buffers the pool holds idle, and the bytes handed out
+pub fn record_buffer_pool_occupancy(idle_buffers: usize, idle_bytes: usize, in_use_bytes: usize) {
+    BUFFER_POOL_IDLE_BUFFERS.set(i64::try_from(idle_buffers).unwrap_or(i64::MAX));
+    BUFFER_POOL_IDLE_BYTES.set(i64::try_from(idle_bytes).unwrap_or(i64::MAX));
+    BUFFER_POOL_IN_USE_BYTES.set(i64::try_from(in_use_bytes).unwrap_or(i64::MAX));
+}
+
+/// Record reading an upload body paused for `seconds`
+pub fn record_flow_pause(bucket: &str, seconds: f64) {
+    FLOW_PAUSES.with_label_values(&[bucket]).inc();
+    FLOW_PAUSED_SECONDS
+        .with_label_values(&[bucket])
+        .inc_by(seconds);
+}
+
+/// Record an upload routed to a region
+pub fn record_region_upload(bucket: &str, region: &str) {
+    REGION_UPLOADS.with_label_values(&[bucket, region]).inc();
+}
+
+/// Record a region's moving average upload latency
+pub fn record_region_latency(bucket: &str, region: &str, seconds: f64) {
+    REGION_LATENCY
+        .with_label_values(&[bucket, region])
+        .set(seconds);
+}
+
+/// Record an upload body shorter or longer than its declared length
+pub fn record_body_length_mismatch(bucket: &str, kind: &str) {
+    BODY_LENGTH_MISMATCHES
+        .with_label_values(&[bucket, kind])
+        .inc();
+}
+
+/// Record an upload with a disallowed or mislabeled content type
+pub fn record_content_type_violation(bucket: &str, reason: &str) {
+    CONTENT_TYPE_VIOLATIONS
+        .with_label_values(&[bucket, reason])
+        .inc();
+}
+
+/// Record the outcome of a transform plugin run
+pub fn record_transform(bucket: &str, outcome: &str) {
+    TRANSFORMS_TOTAL.with_label_values(&[bucket, outcome]).inc();
+}
+
+/// Record an upload body compressed before storage
+pub fn record_compression(bucket: &str, original_bytes: u64, compressed_bytes: u64) {
+    COMPRESSION_BYTES
+        .with_label_values(&[bucket, "original"])
+        .inc_by(original_bytes as f64);
+    COMPRESSION_BYTES
+        .with_label_values(&[bucket, "compressed"])
+        .inc_by(compressed_bytes as f64);
+}
+
+/// Record an upload rejected by a quota
+pub fn record_quota_rejection(bucket: &str, window: &str) {
+    QUOTA_REJECTIONS.with_label_values(&[bucket, window]).inc();
+}
+
 /// Record a successful multipart upload
 pub fn record_multipart_upload_success(bucket: &str, parts_count: usize) {
     MULTIPART_UPLOADS
@@ -145,6 +628,13 @@ pub fn record_multipart_upload_failure(bucket: &str) {
         .inc();
 }
 
+/// Record an orphaned multipart upload aborted by reconciliation
+pub fn record_multipart_upload_orphaned(bucket: &str) {
+    MULTIPART_UPLOADS
+        .with_label_values(&[bucket, "orphaned"])
+        .inc();
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -155,6 +645,25 @@ mod tests {
         // Just verify it doesn't panic
     }
 
+    #[test]
+    fn test_record_upload_bytes_per_bucket() {
+        let before = BUCKET_UPLOAD_BYTES
+            .with_label_values(&["bytes-bucket-a"])
+            .get();
+        record_upload_success("bytes-bucket-a", 4096);
+        record_upload_bytes("bytes-bucket-a", 5 * 1024 * 1024);
+        record_upload_bytes("bytes-bucket-b", 1);
+
+        let after = BUCKET_UPLOAD_BYTES
+            .with_label_values(&["bytes-bucket-a"])
+            .get();
+        assert_eq!(after - before, (4096 + 5 * 1024 * 1024) as f64);
+
+        let sizes = UPLOAD_SIZE.with_label_values(&["bytes-bucket-a"]);
+        assert_eq!(sizes.get_sample_count(), 1);
+        assert_eq!(sizes.get_sample_sum(), 4096.0);
+    }
+
     #[test]
     fn test_record_zero_copy() {
         record_zero_copy_transfer(65536);
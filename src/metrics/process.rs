@@ -0,0 +1,195 @@
+//! Process and runtime metrics
+//!
+//! Gauges describing the proxy process itself (CPU time, resident memory,
+//! open file descriptors) and the tokio runtime it runs on. They are
+//! refreshed by [`update`] right before each scrape rather than on a timer,
+//! so they cost nothing when nobody is scraping.
+//!
+//! Process metrics are read from `/proc/self` and are only reported on
+//! Linux. Metric names follow the Prometheus client conventions
+//! (`process_*`) so the usual dashboards work unchanged.
+
+use lazy_static::lazy_static;
+use prometheus::{register_counter, register_gauge, register_int_gauge, Counter, Gauge, IntGauge};
+
+lazy_static! {
+    pub static ref PROCESS_CPU_SECONDS: Counter = register_counter!(
+        "process_cpu_seconds_total",
+        "Total user and system CPU time spent in seconds"
+    ).unwrap();
+
+    pub static ref PROCESS_RESIDENT_MEMORY: IntGauge = register_int_gauge!(
+        "process_resident_memory_bytes",
+        "Resident memory size in bytes"
+    ).unwrap();
+
+    pub static ref PROCESS_VIRTUAL_MEMORY: IntGauge = register_int_gauge!(
+        "process_virtual_memory_bytes",
+        "Virtual memory size in bytes"
+    ).unwrap();
+
+    pub static ref PROCESS_OPEN_FDS: IntGauge = register_int_gauge!(
+        "process_open_fds",
+        "Number of open file descriptors"
+    ).unwrap();
+
+    pub static ref PROCESS_MAX_FDS: IntGauge = register_int_gauge!(
+        "process_max_fds",
+        "Maximum number of open file descriptors"
+    ).unwrap();
+
+    pub static ref PROCESS_START_TIME: Gauge = register_gauge!(
+        "process_start_time_seconds",
+        "Start time of the process since unix epoch in seconds"
+    ).unwrap();
+
+    // Tokio runtime metrics
+    pub static ref TOKIO_WORKERS: IntGauge = register_int_gauge!(
+        "mizuchi_tokio_workers",
+        "Number of tokio runtime worker threads"
+    ).unwrap();
+
+    pub static ref TOKIO_ALIVE_TASKS: IntGauge = register_int_gauge!(
+        "mizuchi_tokio_alive_tasks",
+        "Number of tasks alive in the tokio runtime"
+    ).unwrap();
+
+    pub static ref TOKIO_GLOBAL_QUEUE_DEPTH: IntGauge = register_int_gauge!(
+        "mizuchi_tokio_global_queue_depth",
+        "Number of tasks waiting in the tokio runtime's global queue"
+    ).unwrap();
+}
+
+/// Refresh the process and runtime gauges
+///
+/// Must be called from within a tokio runtime to report runtime metrics;
+/// outside one only the process metrics are updated.
+pub fn update() {
+    #[cfg(target_os = "linux")]
+    linux::update();
+
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        let runtime = handle.metrics();
+        TOKIO_WORKERS.set(runtime.num_workers() as i64);
+        TOKIO_ALIVE_TASKS.set(runtime.num_alive_tasks() as i64);
+        TOKIO_GLOBAL_QUEUE_DEPTH.set(runtime.global_queue_depth() as i64);
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::*;
+
+    /// Fields of `/proc/self/stat` used here
+    #[derive(Debug, PartialEq)]
+    pub(super) struct Stat {
+        /// User plus system time, in clock ticks
+        pub cpu_ticks: u64,
+        /// Process start time, in clock ticks after boot
+        pub start_ticks: u64,
+        pub vsize: u64,
+        /// Resident set size, in pages
+        pub rss_pages: i64,
+    }
+
+    /// Parse the contents of `/proc/self/stat`
+    ///
+    /// The command name (field 2) may contain spaces and parentheses, so
+    /// fields are counted from the last `)`.
+    pub(super) fn parse_stat(contents: &str) -> Option<Stat> {
+        let rest = &contents[contents.rfind(')')? + 1..];
+        // rest starts at field 3 (state)
+        let fields: Vec<&str> = rest.split_whitespace().collect();
+        let field = |n: usize| fields.get(n - 3).copied();
+        let utime: u64 = field(14)?.parse().ok()?;
+        let stime: u64 = field(15)?.parse().ok()?;
+        Some(Stat {
+            cpu_ticks: utime + stime,
+            start_ticks: field(22)?.parse().ok()?,
+            vsize: field(23)?.parse().ok()?,
+            rss_pages: field(24)?.parse().ok()?,
+        })
+    }
+
+    /// Seconds since the epoch at which the system booted
+    fn boot_time() -> Option<f64> {
+        let stat = std::fs::read_to_string("/proc/stat").ok()?;
+        stat.lines()
+            .find_map(|line| line.strip_prefix("btime "))
+            .and_then(|btime| btime.trim().parse().ok())
+    }
+
+    pub(super) fn update() {
+        // SAFETY: sysconf has no memory safety preconditions
+        let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as f64;
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+
+        if let Some(stat) = std::fs::read_to_string("/proc/self/stat")
+            .ok()
+            .as_deref()
+            .and_then(parse_stat)
+        {
+            if ticks_per_sec > 0.0 {
+                // Counters only go up: add what was used since the last scrape
+                let cpu_secs = stat.cpu_ticks as f64 / ticks_per_sec;
+                let delta = cpu_secs - PROCESS_CPU_SECONDS.get();
+                if delta > 0.0 {
+                    PROCESS_CPU_SECONDS.inc_by(delta);
+                }
+                if let Some(boot) = boot_time() {
+                    PROCESS_START_TIME.set(boot + stat.start_ticks as f64 / ticks_per_sec);
+                }
+            }
+            PROCESS_VIRTUAL_MEMORY.set(stat.vsize as i64);
+            PROCESS_RESIDENT_MEMORY.set(stat.rss_pages * page_size as i64);
+        }
+
+        if let Ok(fds) = std::fs::read_dir("/proc/self/fd") {
+            PROCESS_OPEN_FDS.set(fds.count() as i64);
+        }
+
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: `limit` is a valid rlimit for getrlimit to fill in
+        if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } == 0 {
+            PROCESS_MAX_FDS.set(limit.rlim_cur as i64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_stat() {
+        let contents = "4242 (mizuchi (worker) 1) S 1 4242 4242 0 -1 4194560 1000 0 0 0 \
+                        150 50 0 0 20 0 8 0 12345 104857600 2560 18446744073709551615";
+        let stat = linux::parse_stat(contents).unwrap();
+        assert_eq!(
+            stat,
+            linux::Stat {
+                cpu_ticks: 200,
+                start_ticks: 12345,
+                vsize: 104857600,
+                rss_pages: 2560,
+            }
+        );
+
+        assert!(linux::parse_stat("4242 (truncated").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_update() {
+        update();
+        assert!(TOKIO_WORKERS.get() >= 1);
+        #[cfg(target_os = "linux")]
+        {
+            assert!(PROCESS_RESIDENT_MEMORY.get() > 0);
+            assert!(PROCESS_OPEN_FDS.get() > 0);
+        }
+    }
+}
@@ -4,7 +4,8 @@
 //!
 //! # Features
 //!
-//! - `/metrics` - Prometheus text format metrics
+//! - `/metrics` - Prometheus text format metrics, including process and
+//!   tokio runtime metrics (see [`super::process`])
 //! - `/health` - Health check endpoint for Kubernetes
 //! - Graceful shutdown support
 //! - Builder pattern for configuration
@@ -252,6 +253,8 @@ async fn handle_request(
 
 /// Handle /metrics endpoint - returns Prometheus text format
 fn metrics_handler() -> Response<Full<Bytes>> {
+    super::process::update();
+
     let encoder = TextEncoder::new();
     let metric_families = prometheus::gather();
     let mut buffer = Vec::new();
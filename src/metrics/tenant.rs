@@ -0,0 +1,115 @@
+//! Tenant labels for upload metrics
+//!
+//! Labeling upload metrics with a tenant adds one series per tenant, which
+//! deployments with many tenants cannot afford. [`TenantLabels`] gives the
+//! first `max_tenants` tenants seen their own label value and counts the
+//! rest under [`OTHER_TENANT`]. With `hash` set, the label value is a short
+//! hash of the tenant so tenant names do not leak into monitoring.
+
+use crate::authz::claims;
+use crate::config::TenantLabelConfig;
+use parking_lot::Mutex;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+
+/// Label value of tenants beyond `max_tenants`
+pub const OTHER_TENANT: &str = "other";
+
+/// Label value of uploads without a tenant claim
+pub const NO_TENANT: &str = "none";
+
+/// Maps tenants to label values under a cardinality cap
+pub struct TenantLabels {
+    claim: String,
+    hash: bool,
+    max_tenants: usize,
+    admitted: Mutex<HashSet<String>>,
+}
+
+impl TenantLabels {
+    pub fn new(config: &TenantLabelConfig) -> Self {
+        Self {
+            claim: config.claim.clone(),
+            hash: config.hash,
+            max_tenants: config.max_tenants,
+            admitted: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Label value for an upload authenticated with `claims` (`None` for
+    /// anonymous uploads)
+    pub fn label(&self, claims: Option<&HashMap<String, Value>>) -> String {
+        let tenant = claims.and_then(|claims| match claims::lookup(claims, &self.claim)? {
+            Value::String(value) => Some(value.clone()),
+            Value::Number(value) => Some(value.to_string()),
+            _ => None,
+        });
+        let Some(tenant) = tenant else {
+            return NO_TENANT.to_string();
+        };
+        let label = if self.hash {
+            // 64 bits keeps collisions unlikely well past any sane cap
+            hex::encode(&Sha256::digest(tenant.as_bytes())[..8])
+        } else {
+            tenant
+        };
+
+        let mut admitted = self.admitted.lock();
+        if admitted.contains(&label) {
+            return label;
+        }
+        if admitted.len() < self.max_tenants {
+            admitted.insert(label.clone());
+            return label;
+        }
+        OTHER_TENANT.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(max_tenants: usize, hash: bool) -> TenantLabels {
+        TenantLabels::new(&TenantLabelConfig {
+            claim: "org.id".into(),
+            hash,
+            max_tenants,
+        })
+    }
+
+    fn claims(tenant: &str) -> HashMap<String, Value> {
+        HashMap::from([("org".to_string(), serde_json::json!({ "id": tenant }))])
+    }
+
+    #[test]
+    fn test_cardinality_capped() {
+        let labels = labels(2, false);
+
+        assert_eq!(labels.label(Some(&claims("a"))), "a");
+        assert_eq!(labels.label(Some(&claims("b"))), "b");
+        assert_eq!(labels.label(Some(&claims("c"))), OTHER_TENANT);
+        assert_eq!(labels.label(Some(&claims("a"))), "a");
+    }
+
+    #[test]
+    fn test_missing_tenant() {
+        let labels = labels(2, false);
+
+        assert_eq!(labels.label(None), NO_TENANT);
+        assert_eq!(labels.label(Some(&HashMap::new())), NO_TENANT);
+        assert_eq!(labels.label(Some(&claims("a"))), "a");
+    }
+
+    #[test]
+    fn test_hashed_labels() {
+        let labels = labels(10, true);
+
+        let label = labels.label(Some(&claims("acme")));
+        assert_eq!(label.len(), 16);
+        assert_ne!(label, "acme");
+        assert_eq!(labels.label(Some(&claims("acme"))), label);
+        assert_ne!(labels.label(Some(&claims("globex"))), label);
+    }
+}
@@ -0,0 +1,299 @@
+//! Upload notification module
+//!
+//! Delivers upload completion events to webhooks.
+//!
+//! # Design
+//!
+//! - Events are pushed onto a bounded queue with `try_send`, so the upload
+//!   path never waits on webhook delivery
+//! - A single background worker drains the queue and POSTs each event to
+//!   every configured webhook
+//! - Failed deliveries are retried with exponential backoff
+//! - Payloads are optionally signed with HMAC-SHA256
+//!
+//! # Example
+//!
+//! ```no_run
+//! use mizuchi_uploadr::config::{NotificationsConfig, WebhookConfig};
+//! use mizuchi_uploadr::notifications::{UploadEvent, WebhookNotifier};
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let config = NotificationsConfig {
+//!     enabled: true,
+//!     queue_capacity: 1024,
+//!     webhooks: vec![WebhookConfig {
+//!         url: "https://hooks.example.com/uploads".to_string(),
+//!         secret: Some("signing-key".to_string()),
+//!         secret_file: None,
+//!         max_retries: 3,
+//!         initial_backoff_ms: 200,
+//!         timeout_ms: 5000,
+//!     }],
+//! };
+//!
+//! let notifier = WebhookNotifier::new(&config)?;
+//! notifier.notify(UploadEvent::completed("uploads", "file.txt", "\"abc123\"", 1024, None))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::config::{NotificationsConfig, WebhookConfig};
+use crate::metrics;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+/// Header carrying the HMAC-SHA256 payload signature
+pub const SIGNATURE_HEADER: &str = "X-Mizuchi-Signature";
+
+/// Header carrying the event type
+pub const EVENT_HEADER: &str = "X-Mizuchi-Event";
+
+/// Event type emitted after a successful upload
+pub const UPLOAD_COMPLETED: &str = "upload.completed";
+
+/// Event type of the report emitted when the server shuts down
+pub const SERVER_SHUTDOWN: &str = "server.shutdown";
+
+/// Notification errors
+#[derive(Error, Debug)]
+pub enum NotificationError {
+    #[error("Notification queue is full")]
+    QueueFull,
+
+    #[error("Notification worker has stopped")]
+    Closed,
+
+    #[error("Configuration error: {0}")]
+    ConfigError(String),
+
+    #[error("Delivery failed: {0}")]
+    DeliveryError(String),
+}
+
+/// Upload completion event
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UploadEvent {
+    pub event: String,
+    pub bucket: String,
+    pub key: String,
+    pub etag: String,
+    pub size: u64,
+    pub subject: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl UploadEvent {
+    /// Create an `upload.completed` event stamped with the current time
+    pub fn completed(
+        bucket: &str,
+        key: &str,
+        etag: &str,
+        size: u64,
+        subject: Option<&str>,
+    ) -> Self {
+        Self {
+            event: UPLOAD_COMPLETED.to_string(),
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            etag: etag.to_string(),
+            size,
+            subject: subject.map(|s| s.to_string()),
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// Webhook notifier
+///
+/// Cheap to share behind an `Arc`. Dropping the last handle closes the queue;
+/// the worker delivers whatever is still pending and then exits.
+pub struct WebhookNotifier {
+    sender: mpsc::Sender<UploadEvent>,
+}
+
+impl WebhookNotifier {
+    /// Create a notifier and spawn its delivery worker
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn new(config: &NotificationsConfig) -> Result<Self, NotificationError> {
+        if config.queue_capacity == 0 {
+            return Err(NotificationError::ConfigError(
+                "queue_capacity must be greater than 0".into(),
+            ));
+        }
+
+        let client = reqwest::Client::builder()
+            .build()
+            .map_err(|e| NotificationError::ConfigError(e.to_string()))?;
+
+        let (sender, receiver) = mpsc::channel(config.queue_capacity);
+        tokio::spawn(run_worker(client, config.webhooks.clone(), receiver));
+
+        Ok(Self { sender })
+    }
+
+    /// Queue an event for delivery
+    ///
+    /// Never blocks. If the queue is full the event is dropped and
+    /// `NotificationError::QueueFull` is returned.
+    pub fn notify(&self, event: UploadEvent) -> Result<(), NotificationError> {
+        self.sender.try_send(event).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(event) => {
+                tracing::warn!(
+                    bucket = %event.bucket,
+                    key = %event.key,
+                    "Notification queue full, dropping event"
+                );
+                metrics::record_notification("dropped");
+                NotificationError::QueueFull
+            }
+            mpsc::error::TrySendError::Closed(_) => NotificationError::Closed,
+        })
+    }
+}
+
+/// Deliver a single event to one webhook, bypassing the queue
+///
+/// Used for one-off events such as the shutdown report, where the caller
+/// wants to wait for delivery to finish.
+pub async fn send_event<T: Serialize>(
+    webhook: &WebhookConfig,
+    event_type: &str,
+    event: &T,
+) -> Result<(), NotificationError> {
+    let payload =
+        serde_json::to_vec(event).map_err(|e| NotificationError::DeliveryError(e.to_string()))?;
+    let client = reqwest::Client::builder()
+        .build()
+        .map_err(|e| NotificationError::ConfigError(e.to_string()))?;
+
+    deliver(&client, webhook, event_type, &payload).await
+}
+
+/// Compute the `sha256=<hex>` signature of a payload
+pub fn sign_payload(secret: &str, payload: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(payload);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Drain the queue and deliver each event to every webhook
+async fn run_worker(
+    client: reqwest::Client,
+    webhooks: Vec<WebhookConfig>,
+    mut receiver: mpsc::Receiver<UploadEvent>,
+) {
+    while let Some(event) = receiver.recv().await {
+        let payload = match serde_json::to_vec(&event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to serialize upload event");
+                continue;
+            }
+        };
+
+        let deliveries = webhooks
+            .iter()
+            .map(|webhook| deliver(&client, webhook, &event.event, &payload));
+
+        for (webhook, result) in webhooks
+            .iter()
+            .zip(futures::future::join_all(deliveries).await)
+        {
+            match result {
+                Ok(()) => metrics::record_notification("delivered"),
+                Err(e) => {
+                    tracing::warn!(url = %webhook.url, error = %e, "Webhook delivery failed");
+                    metrics::record_notification("failed");
+                }
+            }
+        }
+    }
+}
+
+/// POST a payload to a webhook, retrying with exponential backoff
+async fn deliver(
+    client: &reqwest::Client,
+    webhook: &WebhookConfig,
+    event_type: &str,
+    payload: &[u8],
+) -> Result<(), NotificationError> {
+    let signature = webhook
+        .secret
+        .as_deref()
+        .map(|secret| sign_payload(secret, payload));
+
+    let mut last_error = None;
+    for attempt in 0..=webhook.max_retries {
+        if attempt > 0 {
+            let backoff = webhook
+                .initial_backoff_ms
+                .saturating_mul(1u64 << (attempt - 1).min(16));
+            tokio::time::sleep(Duration::from_millis(backoff)).await;
+        }
+
+        let mut request = client
+            .post(&webhook.url)
+            .timeout(Duration::from_millis(webhook.timeout_ms))
+            .header("Content-Type", "application/json")
+            .header(EVENT_HEADER, event_type)
+            .body(payload.to_vec());
+
+        if let Some(ref signature) = signature {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+
+        match request.send().await {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => {
+                last_error = Some(NotificationError::DeliveryError(format!(
+                    "HTTP {}",
+                    response.status().as_u16()
+                )));
+            }
+            Err(e) => {
+                last_error = Some(NotificationError::DeliveryError(e.to_string()));
+            }
+        }
+
+        tracing::debug!(url = %webhook.url, attempt = attempt + 1, "Webhook delivery attempt failed");
+    }
+
+    Err(last_error
+        .unwrap_or_else(|| NotificationError::DeliveryError("All retries exhausted".into())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_payload() {
+        // HMAC-SHA256("key", "The quick brown fox jumps over the lazy dog")
+        let signature = sign_payload("key", b"The quick brown fox jumps over the lazy dog");
+        assert_eq!(
+            signature,
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn test_upload_event_serialization() {
+        let event = UploadEvent::completed("uploads", "a/b.txt", "\"etag\"", 42, Some("user1"));
+        let json: serde_json::Value = serde_json::to_value(&event).unwrap();
+
+        assert_eq!(json["event"], "upload.completed");
+        assert_eq!(json["bucket"], "uploads");
+        assert_eq!(json["key"], "a/b.txt");
+        assert_eq!(json["size"], 42);
+        assert_eq!(json["subject"], "user1");
+        assert!(json["timestamp"].is_string());
+    }
+}
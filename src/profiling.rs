@@ -0,0 +1,265 @@
+//! Profiling mode
+//!
+//! `mizuchi-uploadr --profile [DIR]` shows where uploads spend their time
+//! without patching the code. It enables fine-grained spans around SHA-256
+//! and CRC32C hashing, request signing, S3 requests on the wire, and spill
+//! file and splice syscalls. These are TRACE spans with the [`TARGET`]
+//! target, filtered out unless profiling. Every span is recorded in
+//! `DIR/tracing.folded` as folded stacks, ready for `inferno-flamegraph` or
+//! `flamegraph.pl`:
+//!
+//! ```text
+//! mizuchi-uploadr --config config.yaml --profile /tmp/profile
+//! inferno-flamegraph < /tmp/profile/tracing.folded > uploads.svg
+//! ```
+//!
+//! Built with the `pprof` feature, `--pprof-interval 60s` additionally
+//! samples the CPU with `pprof-rs` and writes a flamegraph of each interval
+//! to `DIR/cpu-<unix time>.svg`. Spans show time spent awaiting as well as
+//! computing; the CPU profiles show only the latter, down to the function.
+//!
+//! Built with the `dhat` feature, `--heap-interval 60s` profiles the heap
+//! with `dhat`: a snapshot of heap usage (live and peak bytes and blocks,
+//! totals allocated) is appended to `DIR/heap.jsonl` every interval, and the
+//! allocation sites are written to `DIR/dhat-heap.json` on exit, for DHAT's
+//! viewer (`dh_view.html`). Allocations are slower while profiling.
+
+use std::path::{Path, PathBuf};
+#[cfg(any(feature = "pprof", feature = "dhat"))]
+use std::time::Duration;
+use thiserror::Error;
+
+/// Target of the spans enabled in profiling mode
+pub const TARGET: &str = "mizuchi_uploadr::profile";
+
+/// Filter directive enabling the profiling spans
+pub const FILTER: &str = "mizuchi_uploadr::profile=trace";
+
+/// File of the output directory the folded span stacks are written to
+pub const FOLDED_FILE: &str = "tracing.folded";
+
+/// File of the output directory heap snapshots are appended to
+pub const HEAP_FILE: &str = "heap.jsonl";
+
+/// File of the output directory allocation sites are written to on exit
+pub const DHAT_FILE: &str = "dhat-heap.json";
+
+/// TRACE span with the profiling target, e.g.
+/// `profile_span!("sha256", bytes = data.len())`
+macro_rules! profile_span {
+    ($name:literal $(, $($fields:tt)+)?) => {
+        ::tracing::trace_span!(target: "mizuchi_uploadr::profile", $name $(, $($fields)+)?)
+    };
+}
+pub(crate) use profile_span;
+
+/// Profiling errors
+#[derive(Error, Debug)]
+pub enum ProfilingError {
+    #[error("Failed to create profile directory {path}: {error}")]
+    OutputDir { path: String, error: std::io::Error },
+
+    #[error("CPU profiles need a build with the pprof feature")]
+    PprofUnavailable,
+
+    #[error("Failed to start CPU profiler: {0}")]
+    PprofFailed(String),
+
+    #[error("Heap profiles need a build with the dhat feature")]
+    HeapUnavailable,
+
+    #[error("Failed to start heap profiler: {0}")]
+    HeapFailed(String),
+}
+
+/// Create the output directory `dir` and return the path of the folded
+/// span stacks in it
+pub fn prepare_output(dir: &Path) -> Result<PathBuf, ProfilingError> {
+    std::fs::create_dir_all(dir).map_err(|error| ProfilingError::OutputDir {
+        path: dir.display().to_string(),
+        error,
+    })?;
+    Ok(dir.join(FOLDED_FILE))
+}
+
+/// Write a CPU profile of every `interval` to `dir` until the process exits
+///
+/// Sampling runs at 99 Hz on a thread of its own.
+#[cfg(feature = "pprof")]
+pub fn spawn_cpu_profiler(dir: PathBuf, interval: Duration) -> Result<(), ProfilingError> {
+    std::thread::Builder::new()
+        .name("cpu-profiler".into())
+        .spawn(move || loop {
+            if let Err(e) = cpu_profile(&dir, interval) {
+                tracing::warn!("CPU profile not written: {}", e);
+                std::thread::sleep(interval);
+            }
+        })
+        .map_err(|e| ProfilingError::PprofFailed(e.to_string()))?;
+    Ok(())
+}
+
+/// Without the `pprof` feature there is no CPU profiler to start
+#[cfg(not(feature = "pprof"))]
+pub fn spawn_cpu_profiler(
+    _dir: PathBuf,
+    _interval: std::time::Duration,
+) -> Result<(), ProfilingError> {
+    Err(ProfilingError::PprofUnavailable)
+}
+
+/// Sample the CPU for `interval` and write the flamegraph to `dir`, named
+/// after the time sampling started
+#[cfg(feature = "pprof")]
+fn cpu_profile(dir: &Path, interval: Duration) -> Result<(), String> {
+    let started = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(99)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()
+        .map_err(|e| e.to_string())?;
+    std::thread::sleep(interval);
+    let report = guard.report().build().map_err(|e| e.to_string())?;
+    drop(guard);
+    // An idle interval has nothing to draw
+    if report.data.is_empty() {
+        return Ok(());
+    }
+
+    let path = dir.join(format!("cpu-{}.svg", started));
+    let file = std::fs::File::create(&path).map_err(|e| e.to_string())?;
+    report.flamegraph(file).map_err(|e| e.to_string())?;
+    tracing::info!("Wrote CPU profile {}", path.display());
+    Ok(())
+}
+
+/// Running heap profiler; writes the allocation sites when dropped
+#[cfg(feature = "dhat")]
+pub struct HeapProfiler {
+    /// Whether snapshots may be taken; heap stats can only be read while
+    /// the profiler runs
+    running: std::sync::Arc<std::sync::Mutex<bool>>,
+    profiler: Option<dhat::Profiler>,
+}
+
+/// Without the `dhat` feature no heap profiler can run
+#[cfg(not(feature = "dhat"))]
+pub enum HeapProfiler {}
+
+#[cfg(feature = "dhat")]
+impl Drop for HeapProfiler {
+    fn drop(&mut self) {
+        *self
+            .running
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = false;
+        self.profiler.take();
+    }
+}
+
+/// Profile the heap, appending a snapshot of its usage to `dir` every
+/// `interval` until the returned profiler is dropped
+///
+/// Only allocations through `dhat::Alloc`, the binary's global allocator
+/// with the `dhat` feature, are seen.
+#[cfg(feature = "dhat")]
+pub fn start_heap_profiler(
+    dir: PathBuf,
+    interval: Duration,
+) -> Result<HeapProfiler, ProfilingError> {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex, PoisonError};
+
+    let path = dir.join(HEAP_FILE);
+    let mut snapshots = std::fs::File::create(&path)
+        .map_err(|e| ProfilingError::HeapFailed(format!("{}: {}", path.display(), e)))?;
+    let profiler = dhat::Profiler::builder()
+        .file_name(dir.join(DHAT_FILE))
+        .build();
+    let running = Arc::new(Mutex::new(true));
+
+    let sampling = running.clone();
+    std::thread::Builder::new()
+        .name("heap-profiler".into())
+        .spawn(move || loop {
+            std::thread::sleep(interval);
+            let stats = {
+                let running = sampling.lock().unwrap_or_else(PoisonError::into_inner);
+                if !*running {
+                    return;
+                }
+                dhat::HeapStats::get()
+            };
+            if let Err(e) = writeln!(snapshots, "{}", heap_snapshot(&stats)) {
+                tracing::warn!("Heap snapshot not written: {}", e);
+            }
+        })
+        .map_err(|e| ProfilingError::HeapFailed(e.to_string()))?;
+
+    Ok(HeapProfiler {
+        running,
+        profiler: Some(profiler),
+    })
+}
+
+/// Without the `dhat` feature there is no heap profiler to start
+#[cfg(not(feature = "dhat"))]
+pub fn start_heap_profiler(
+    _dir: PathBuf,
+    _interval: std::time::Duration,
+) -> Result<HeapProfiler, ProfilingError> {
+    Err(ProfilingError::HeapUnavailable)
+}
+
+/// One line of the heap snapshots file
+#[cfg(feature = "dhat")]
+fn heap_snapshot(stats: &dhat::HeapStats) -> serde_json::Value {
+    let time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    serde_json::json!({
+        "time": time,
+        "curr_bytes": stats.curr_bytes,
+        "curr_blocks": stats.curr_blocks,
+        "max_bytes": stats.max_bytes,
+        "max_blocks": stats.max_blocks,
+        "total_bytes": stats.total_bytes,
+        "total_blocks": stats.total_blocks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prepare_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("profile/run-1");
+
+        let folded = prepare_output(&nested).unwrap();
+        assert!(nested.is_dir());
+        assert_eq!(folded, nested.join(FOLDED_FILE));
+    }
+
+    #[test]
+    fn test_spans_enabled_only_by_filter() {
+        use tracing_subscriber::layer::SubscriberExt;
+        use tracing_subscriber::EnvFilter;
+
+        let enabled = |directives: &str| {
+            let subscriber = tracing_subscriber::registry().with(EnvFilter::new(directives));
+            tracing::subscriber::with_default(subscriber, || {
+                !profile_span!("sha256", bytes = 3).is_disabled()
+            })
+        };
+
+        assert!(!enabled("info"));
+        assert!(enabled(&format!("info,{}", FILTER)));
+        assert_eq!(FILTER, format!("{}=trace", TARGET));
+    }
+}
@@ -0,0 +1,96 @@
+//! Host patterns for Host-header routing
+//!
+//! A pattern is either an exact host name (`tenant-a.upload.example.com`)
+//! or a wildcard whose first label is `*` (`*.upload.example.com`), which
+//! matches exactly one label in its place, as TLS wildcard certificates do.
+//! Matching ignores case, the port and a trailing dot.
+
+/// Host pattern of a bucket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostPattern {
+    /// Exact host name
+    Exact(String),
+    /// Any single label followed by this suffix (including its leading `.`)
+    Wildcard(String),
+}
+
+impl HostPattern {
+    /// Parse a pattern, or `None` if it is not a valid host pattern
+    pub fn new(pattern: &str) -> Option<Self> {
+        let pattern = normalize_host(pattern);
+        if let Some(suffix) = pattern.strip_prefix('*') {
+            return (suffix.len() > 1 && suffix.starts_with('.') && !suffix.contains('*'))
+                .then(|| HostPattern::Wildcard(suffix.to_string()));
+        }
+        (!pattern.is_empty() && !pattern.contains('*')).then_some(HostPattern::Exact(pattern))
+    }
+
+    /// Whether `host` (as sent in the `Host` header) matches
+    pub fn matches(&self, host: &str) -> bool {
+        let host = normalize_host(host);
+        match self {
+            HostPattern::Exact(name) => host == *name,
+            HostPattern::Wildcard(suffix) => host
+                .strip_suffix(suffix.as_str())
+                .is_some_and(|label| !label.is_empty() && !label.contains('.')),
+        }
+    }
+
+    /// Sort key putting more specific patterns first
+    pub(crate) fn specificity(&self) -> (bool, usize) {
+        match self {
+            HostPattern::Exact(name) => (true, name.len()),
+            HostPattern::Wildcard(suffix) => (false, suffix.len()),
+        }
+    }
+}
+
+/// Lowercase a host, dropping its port and trailing dot
+fn normalize_host(host: &str) -> String {
+    let host = host.trim();
+    let host = match host.strip_prefix('[') {
+        // IPv6 literal, e.g. `[::1]:8080`
+        Some(rest) => rest.split(']').next().unwrap_or(rest),
+        None => host.split(':').next().unwrap_or(host),
+    };
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exact_pattern() {
+        let pattern = HostPattern::new("Tenant-A.upload.example.com").unwrap();
+
+        assert!(pattern.matches("tenant-a.upload.example.com"));
+        assert!(pattern.matches("TENANT-A.upload.example.com:8443"));
+        assert!(pattern.matches("tenant-a.upload.example.com."));
+        assert!(!pattern.matches("tenant-b.upload.example.com"));
+    }
+
+    #[test]
+    fn test_wildcard_pattern() {
+        let pattern = HostPattern::new("*.upload.example.com").unwrap();
+
+        assert!(pattern.matches("tenant-a.upload.example.com"));
+        assert!(pattern.matches("tenant-b.upload.example.com:443"));
+        assert!(!pattern.matches("upload.example.com"));
+        assert!(!pattern.matches("a.b.upload.example.com"));
+        assert!(!pattern.matches("tenant-a.upload.example.org"));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        assert_eq!(HostPattern::new(""), None);
+        assert_eq!(HostPattern::new("*"), None);
+        assert_eq!(HostPattern::new("*."), None);
+        assert_eq!(HostPattern::new("tenant-*.example.com"), None);
+        assert_eq!(HostPattern::new("*.*.example.com"), None);
+        assert_eq!(
+            HostPattern::new("[::1]:8080"),
+            Some(HostPattern::Exact("::1".into()))
+        );
+    }
+}
@@ -5,12 +5,35 @@
 //!
 //! # Performance
 //!
-//! The `BucketResolver` uses a HashMap for O(1) average-case lookup performance.
-//! Path prefixes are normalized and stored as keys for fast resolution.
+//! The `BucketResolver` stores path prefixes in a segment tree, so lookups take
+//! O(path length) whatever the number of buckets. Prefixes may span several
+//! segments and nest (`/uploads` and `/uploads/images`), and a `*` segment
+//! matches any single path segment (`/tenants/*/uploads`); the longest
+//! matching prefix wins.
+//!
+//! # Hosts
+//!
+//! Buckets with `hosts` only serve requests whose `Host` header matches one
+//! of their [`HostPattern`]s, so each tenant can get its own subdomain.
+//! Host-restricted buckets are tried first, most specific pattern first
+//! (exact hosts, then wildcards with longer suffixes), before buckets
+//! serving any host.
+//!
+//! # Tenants
+//!
+//! Several buckets may share a path prefix and serve different tenants
+//! (see [`TenantConfig`](crate::config::TenantConfig)); resolution on
+//! `(prefix, tenant)` picks among them with [`select_tenant_bucket`].
+
+mod host;
+mod tree;
+
+pub use host::HostPattern;
 
 use crate::config::{BucketConfig, Config};
-use std::collections::HashMap;
+use std::cmp::Reverse;
 use thiserror::Error;
+use tree::PrefixTree;
 
 /// Router errors
 #[derive(Error, Debug)]
@@ -163,12 +186,12 @@ impl S3RequestParser {
 
 /// Bucket Resolver
 ///
-/// Maps incoming request paths to configured S3 buckets using a HashMap for O(1) lookup.
+/// Maps incoming request paths to configured S3 buckets by longest matching prefix.
 ///
 /// # Performance
 ///
-/// Uses a HashMap to map path prefixes to bucket configurations, providing O(1)
-/// average-case lookup performance instead of O(n) linear search.
+/// Uses a segment tree to map path prefixes to bucket configurations, providing
+/// O(path length) lookups instead of O(n) linear search over buckets.
 ///
 /// # Example
 ///
@@ -182,6 +205,12 @@ impl S3RequestParser {
 ///     server: ServerConfig {
 ///         address: "127.0.0.1:8080".to_string(),
 ///         zero_copy: ZeroCopyConfig::default(),
+///         http: Default::default(),
+///         tls: None,
+///         listeners: Vec::new(),
+///         tcp: Default::default(),
+///         buffer_pool: Default::default(),
+///         shutdown: Default::default(),
 ///     },
 ///     buckets: vec![
 ///         BucketConfig {
@@ -193,13 +222,35 @@ impl S3RequestParser {
 ///                 endpoint: None,
 ///                 access_key: None,
 ///                 secret_key: None,
+///                 pool: None,
+///                 sigv4a_region_set: None,
+///                 hedging: None,
+///                 hashing: None,
+///                 checksum: None,
+///                 debug_log: None,
+///                 access_key_file: None,
+///                 secret_key_file: None,
 ///             },
 ///             auth: AuthConfig::default(),
 ///             upload: UploadConfig::default(),
+///             failover: None,
+///             replication: None,
+///             storage: Default::default(),
+///             authz: None,
+///             authz_failure: Default::default(),
+///             tenant: None,
+///             hosts: Vec::new(),
+///             key_prefix: None,
+///             regions: None,
+///             authz_shadow: None,
 ///         },
 ///     ],
 ///     metrics: MetricsConfig::default(),
 ///     tracing: None,
+///     notifications: Default::default(),
+///     admin: Default::default(),
+///     logging: Default::default(),
+///     state: Default::default(),
 /// };
 ///
 /// let resolver = BucketResolver::new(&config);
@@ -215,16 +266,18 @@ impl S3RequestParser {
 /// # }
 /// ```
 pub struct BucketResolver {
-    /// HashMap mapping path prefixes to bucket configurations
-    /// Key: normalized path prefix (e.g., "/uploads")
-    /// Value: bucket configuration
-    prefix_map: HashMap<String, BucketConfig>,
+    /// Tree mapping path prefixes to bucket configurations
+    /// Key: path prefix segments (e.g., "uploads", "images")
+    /// Value: bucket configurations sharing the prefix, in declaration order
+    prefixes: PrefixTree<Vec<BucketConfig>>,
+    /// Trees of buckets restricted to hosts, most specific pattern first
+    hosts: Vec<(HostPattern, PrefixTree<Vec<BucketConfig>>)>,
 }
 
 impl BucketResolver {
     /// Create a new bucket resolver from configuration
     ///
-    /// Builds a HashMap from the bucket configurations for fast O(1) lookup.
+    /// Builds a prefix tree from the bucket configurations for fast lookup.
     ///
     /// # Arguments
     ///
@@ -236,55 +289,54 @@ impl BucketResolver {
     /// # use mizuchi_uploadr::config::{Config, BucketConfig, S3Config, ServerConfig, ZeroCopyConfig, AuthConfig, UploadConfig, MetricsConfig};
     /// # use mizuchi_uploadr::router::BucketResolver;
     /// # let config = Config {
-    /// #     server: ServerConfig { address: "127.0.0.1:8080".to_string(), zero_copy: ZeroCopyConfig::default() },
+    /// #     server: ServerConfig { address: "127.0.0.1:8080".to_string(), zero_copy: ZeroCopyConfig::default(), tls: None, http: Default::default(), shutdown: Default::default(), listeners: Vec::new(), tcp: Default::default(), buffer_pool: Default::default() },
     /// #     buckets: vec![],
     /// #     metrics: MetricsConfig::default(),
     /// #     tracing: None,
+    /// #     notifications: Default::default(),
+    /// #     admin: Default::default(),
+    /// #     logging: Default::default(),
+    /// #     state: Default::default(),
     /// # };
     /// let resolver = BucketResolver::new(&config);
     /// ```
     pub fn new(config: &Config) -> Self {
-        let mut prefix_map = HashMap::new();
+        let mut prefixes: PrefixTree<Vec<BucketConfig>> = PrefixTree::new();
+        let mut hosts: Vec<(HostPattern, PrefixTree<Vec<BucketConfig>>)> = Vec::new();
 
         for bucket in &config.buckets {
-            // Normalize prefix (ensure it starts with / and doesn't end with /)
-            let normalized_prefix = Self::normalize_prefix(&bucket.path_prefix);
-            prefix_map.insert(normalized_prefix, bucket.clone());
-        }
-
-        Self { prefix_map }
-    }
-
-    /// Normalize a path prefix
-    ///
-    /// Ensures the prefix starts with / and doesn't end with / (unless it's just "/")
-    fn normalize_prefix(prefix: &str) -> String {
-        let mut normalized = prefix.to_string();
-
-        // Ensure starts with /
-        if !normalized.starts_with('/') {
-            normalized.insert(0, '/');
-        }
-
-        // Remove trailing / (unless it's the root)
-        if normalized.len() > 1 && normalized.ends_with('/') {
-            normalized.pop();
+            if bucket.hosts.is_empty() {
+                prefixes.entry(&bucket.path_prefix).push(bucket.clone());
+            }
+            // Invalid patterns are rejected by config validation
+            for pattern in bucket
+                .hosts
+                .iter()
+                .filter_map(|host| HostPattern::new(host))
+            {
+                let index = match hosts.iter().position(|(known, _)| *known == pattern) {
+                    Some(index) => index,
+                    None => {
+                        hosts.push((pattern, PrefixTree::new()));
+                        hosts.len() - 1
+                    }
+                };
+                hosts[index]
+                    .1
+                    .entry(&bucket.path_prefix)
+                    .push(bucket.clone());
+            }
         }
+        hosts.sort_by_key(|(pattern, _)| Reverse(pattern.specificity()));
 
-        normalized
-    }
-
-    /// Extract the first path segment from a path
-    ///
-    /// Used to determine which bucket prefix to look up.
-    fn extract_first_segment(path: &str) -> Option<String> {
-        let trimmed = path.trim_start_matches('/');
-        trimmed.split('/').next().map(|s| format!("/{}", s))
+        Self { prefixes, hosts }
     }
 
     /// Resolve a path to a bucket configuration
     ///
-    /// Looks up the bucket configuration based on the path prefix using O(1) HashMap lookup.
+    /// Looks up the bucket configuration with the longest prefix matching the path.
+    /// When several buckets share the prefix, the first declared is returned
+    /// (the one authenticating uploads to it).
     ///
     /// # Arguments
     ///
@@ -302,18 +354,32 @@ impl BucketResolver {
     /// # use mizuchi_uploadr::router::BucketResolver;
     /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
     /// # let config = Config {
-    /// #     server: ServerConfig { address: "127.0.0.1:8080".to_string(), zero_copy: ZeroCopyConfig::default() },
+    /// #     server: ServerConfig { address: "127.0.0.1:8080".to_string(), zero_copy: ZeroCopyConfig::default(), tls: None, http: Default::default(), shutdown: Default::default(), listeners: Vec::new(), tcp: Default::default(), buffer_pool: Default::default() },
     /// #     buckets: vec![
     /// #         BucketConfig {
     /// #             name: "uploads".to_string(),
     /// #             path_prefix: "/uploads".to_string(),
-    /// #             s3: S3Config { bucket: "my-bucket".to_string(), region: "us-east-1".to_string(), endpoint: None, access_key: None, secret_key: None },
+    /// #             s3: S3Config { bucket: "my-bucket".to_string(), region: "us-east-1".to_string(), endpoint: None, access_key: None, secret_key: None, pool: None, sigv4a_region_set: None, hedging: None, hashing: None, checksum: None, debug_log: None, access_key_file: None, secret_key_file: None },
     /// #             auth: AuthConfig::default(),
     /// #             upload: UploadConfig::default(),
+    /// #             failover: None,
+    /// #             replication: None,
+    /// #             storage: Default::default(),
+    /// #             authz: None,
+    /// #             authz_failure: Default::default(),
+    /// #             tenant: None,
+    /// #             hosts: Vec::new(),
+    /// #             key_prefix: None,
+    /// #             regions: None,
+    /// #             authz_shadow: None,
     /// #         },
     /// #     ],
     /// #     metrics: MetricsConfig::default(),
     /// #     tracing: None,
+    /// #     notifications: Default::default(),
+    /// #     admin: Default::default(),
+    /// #     logging: Default::default(),
+    /// #     state: Default::default(),
     /// # };
     /// let resolver = BucketResolver::new(&config);
     /// let bucket = resolver.resolve_bucket("/uploads/file.txt")?;
@@ -322,6 +388,62 @@ impl BucketResolver {
     /// # }
     /// ```
     pub fn resolve_bucket(&self, path: &str) -> Result<&BucketConfig, RouterError> {
+        Ok(&self.resolve_buckets_and_key(path)?.0[0])
+    }
+
+    /// Resolve a path and the caller's tenant to a bucket configuration
+    ///
+    /// Among the buckets sharing the path prefix, returns the one serving
+    /// `tenant` (see [`select_tenant_bucket`]).
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(&BucketConfig)` - The bucket serving the tenant
+    /// * `Err(RouterError)` - If no bucket matches the path, or none serves the tenant
+    pub fn resolve_bucket_for_tenant(
+        &self,
+        path: &str,
+        tenant: Option<&str>,
+    ) -> Result<&BucketConfig, RouterError> {
+        let buckets: Vec<&BucketConfig> = self.resolve_buckets_and_key(path)?.0.iter().collect();
+        select_tenant_bucket(&buckets, tenant).ok_or_else(|| {
+            RouterError::BucketNotFound(format!(
+                "No bucket serves tenant {} for path: {}",
+                tenant.unwrap_or("(none)"),
+                path
+            ))
+        })
+    }
+
+    /// Resolve a path to the buckets sharing its longest matching prefix
+    /// (in declaration order) and the S3 key after that prefix
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((&[BucketConfig], String))` - The bucket configs and extracted S3 key
+    /// * `Err(RouterError)` - If no bucket matches or path is invalid
+    pub fn resolve_buckets_and_key(
+        &self,
+        path: &str,
+    ) -> Result<(&[BucketConfig], String), RouterError> {
+        self.resolve_buckets_and_key_for_host(None, path)
+    }
+
+    /// Resolve a request's `Host` header and path to the buckets sharing
+    /// the longest matching prefix and the S3 key after that prefix
+    ///
+    /// Buckets restricted to hosts matching `host` are tried first; buckets
+    /// serving any host are the fallback.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((&[BucketConfig], String))` - The bucket configs and extracted S3 key
+    /// * `Err(RouterError)` - If no bucket matches or path is invalid
+    pub fn resolve_buckets_and_key_for_host(
+        &self,
+        host: Option<&str>,
+        path: &str,
+    ) -> Result<(&[BucketConfig], String), RouterError> {
         // Handle empty or root path
         if path.is_empty() || path == "/" {
             return Err(RouterError::InvalidPath("Empty or root path".into()));
@@ -332,16 +454,24 @@ impl BucketResolver {
             return Err(RouterError::InvalidPath("Path must start with /".into()));
         }
 
-        // Extract first segment and look up in HashMap
-        let first_segment = Self::extract_first_segment(path)
-            .ok_or_else(|| RouterError::InvalidPath("Invalid path format".into()))?;
-
-        self.prefix_map.get(&first_segment).ok_or_else(|| {
-            RouterError::BucketNotFound(format!(
-                "No bucket configured for path prefix: {}",
-                first_segment
-            ))
-        })
+        let (buckets, matched) = host
+            .into_iter()
+            .flat_map(|host| {
+                self.hosts
+                    .iter()
+                    .filter(move |(pattern, _)| pattern.matches(host))
+            })
+            .map(|(_, tree)| tree)
+            .chain(std::iter::once(&self.prefixes))
+            .find_map(|tree| tree.longest_match(path))
+            .ok_or_else(|| {
+                RouterError::BucketNotFound(format!("No bucket configured for path: {}", path))
+            })?;
+
+        // Extract the S3 key (path after the matched prefix)
+        let key = path[matched..].trim_start_matches('/').to_string();
+
+        Ok((buckets, key))
     }
 
     /// Resolve a path to a bucket configuration and extract the S3 key
@@ -364,18 +494,32 @@ impl BucketResolver {
     /// # use mizuchi_uploadr::router::BucketResolver;
     /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
     /// # let config = Config {
-    /// #     server: ServerConfig { address: "127.0.0.1:8080".to_string(), zero_copy: ZeroCopyConfig::default() },
+    /// #     server: ServerConfig { address: "127.0.0.1:8080".to_string(), zero_copy: ZeroCopyConfig::default(), tls: None, http: Default::default(), shutdown: Default::default(), listeners: Vec::new(), tcp: Default::default(), buffer_pool: Default::default() },
     /// #     buckets: vec![
     /// #         BucketConfig {
     /// #             name: "uploads".to_string(),
     /// #             path_prefix: "/uploads".to_string(),
-    /// #             s3: S3Config { bucket: "my-bucket".to_string(), region: "us-east-1".to_string(), endpoint: None, access_key: None, secret_key: None },
+    /// #             s3: S3Config { bucket: "my-bucket".to_string(), region: "us-east-1".to_string(), endpoint: None, access_key: None, secret_key: None, pool: None, sigv4a_region_set: None, hedging: None, hashing: None, checksum: None, debug_log: None, access_key_file: None, secret_key_file: None },
     /// #             auth: AuthConfig::default(),
     /// #             upload: UploadConfig::default(),
+    /// #             failover: None,
+    /// #             replication: None,
+    /// #             storage: Default::default(),
+    /// #             authz: None,
+    /// #             authz_failure: Default::default(),
+    /// #             tenant: None,
+    /// #             hosts: Vec::new(),
+    /// #             key_prefix: None,
+    /// #             regions: None,
+    /// #             authz_shadow: None,
     /// #         },
     /// #     ],
     /// #     metrics: MetricsConfig::default(),
     /// #     tracing: None,
+    /// #     notifications: Default::default(),
+    /// #     admin: Default::default(),
+    /// #     logging: Default::default(),
+    /// #     state: Default::default(),
     /// # };
     /// let resolver = BucketResolver::new(&config);
     /// let (bucket, key) = resolver.resolve_bucket_and_key("/uploads/folder/file.txt")?;
@@ -388,15 +532,44 @@ impl BucketResolver {
         &self,
         path: &str,
     ) -> Result<(&BucketConfig, String), RouterError> {
-        let bucket = self.resolve_bucket(path)?;
+        let (buckets, key) = self.resolve_buckets_and_key(path)?;
+        Ok((&buckets[0], key))
+    }
+}
 
-        // Extract the S3 key (path after bucket prefix)
-        let key = path[bucket.path_prefix.len()..]
-            .trim_start_matches('/')
-            .to_string();
+/// Claim naming the caller's tenant for buckets sharing a path prefix
+///
+/// Returns `None` if none of the buckets routes by tenant.
+pub fn tenant_claim<'a>(buckets: &[&'a BucketConfig]) -> Option<&'a str> {
+    buckets
+        .iter()
+        .find_map(|bucket| bucket.tenant.as_ref())
+        .map(|tenant| tenant.claim.as_str())
+}
 
-        Ok((bucket, key))
+/// Pick the bucket serving `tenant` among buckets sharing a path prefix
+///
+/// Without tenant routing the first bucket is returned. Otherwise the
+/// bucket listing the tenant wins, then the bucket without a `tenant`
+/// section; `None` if neither exists.
+pub fn select_tenant_bucket<'a>(
+    buckets: &[&'a BucketConfig],
+    tenant: Option<&str>,
+) -> Option<&'a BucketConfig> {
+    if tenant_claim(buckets).is_none() {
+        return buckets.first().copied();
     }
+    tenant
+        .and_then(|tenant| {
+            buckets.iter().find(|bucket| {
+                bucket
+                    .tenant
+                    .as_ref()
+                    .is_some_and(|config| config.values.iter().any(|value| value == tenant))
+            })
+        })
+        .or_else(|| buckets.iter().find(|bucket| bucket.tenant.is_none()))
+        .copied()
 }
 
 #[cfg(test)]
@@ -0,0 +1,169 @@
+//! Segment tree for longest-prefix path matching
+//!
+//! Prefixes are split on `/` into segments, one tree level per segment, so
+//! nested and multi-segment prefixes (`/uploads/images`, `/uploads/video`)
+//! coexist. A `*` segment matches any single path segment.
+//!
+//! Lookups walk the path segment by segment, so they take O(path length)
+//! unless a node has both a literal and a wildcard child for a segment, in
+//! which case both branches are tried. On equal length a literal match wins.
+
+use std::collections::HashMap;
+
+/// Wildcard segment, matching any single path segment
+pub const WILDCARD: &str = "*";
+
+/// Prefix tree mapping path prefixes to values
+#[derive(Debug)]
+pub struct PrefixTree<T> {
+    root: Node<T>,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    children: HashMap<String, Node<T>>,
+    wildcard: Option<Box<Node<T>>>,
+    value: Option<T>,
+}
+
+impl<T> Default for Node<T> {
+    fn default() -> Self {
+        Self {
+            children: HashMap::new(),
+            wildcard: None,
+            value: None,
+        }
+    }
+}
+
+impl<T> Default for PrefixTree<T> {
+    fn default() -> Self {
+        Self {
+            root: Node::default(),
+        }
+    }
+}
+
+impl<T> PrefixTree<T> {
+    /// Create an empty tree
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Value stored at `prefix`, inserting `T::default()` if there is none
+    pub fn entry(&mut self, prefix: &str) -> &mut T
+    where
+        T: Default,
+    {
+        let mut node = &mut self.root;
+        for segment in prefix.split('/').filter(|s| !s.is_empty()) {
+            node = if segment == WILDCARD {
+                node.wildcard.get_or_insert_with(Box::default)
+            } else {
+                node.children.entry(segment.to_string()).or_default()
+            };
+        }
+        node.value.get_or_insert_with(T::default)
+    }
+
+    /// Value of the longest prefix matching `path`, with the byte length of
+    /// `path` that the prefix covers
+    pub fn longest_match(&self, path: &str) -> Option<(&T, usize)> {
+        // Non-empty segments with the offset just past each
+        let mut segments = Vec::new();
+        let mut start = 0;
+        for segment in path.split('/') {
+            let end = start + segment.len();
+            if !segment.is_empty() {
+                segments.push((segment, end));
+            }
+            start = end + 1;
+        }
+        self.root.longest_match(&segments, 0)
+    }
+}
+
+impl<T> Node<T> {
+    fn longest_match(&self, segments: &[(&str, usize)], matched: usize) -> Option<(&T, usize)> {
+        let mut best = self.value.as_ref().map(|value| (value, matched));
+        let Some(((segment, end), rest)) = segments.split_first() else {
+            return best;
+        };
+
+        // Literal first, so it wins ties with the wildcard
+        let literal = self
+            .children
+            .get(*segment)
+            .and_then(|child| child.longest_match(rest, *end));
+        let wildcard = self
+            .wildcard
+            .as_ref()
+            .and_then(|child| child.longest_match(rest, *end));
+        for candidate in [literal, wildcard].into_iter().flatten() {
+            if best.is_none_or(|(_, len)| candidate.1 > len) {
+                best = Some(candidate);
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(prefixes: &[&'static str]) -> PrefixTree<&'static str> {
+        let mut tree = PrefixTree::new();
+        for &prefix in prefixes {
+            *tree.entry(prefix) = prefix;
+        }
+        tree
+    }
+
+    #[test]
+    fn test_nested_prefixes() {
+        let tree = tree(&["/uploads", "/uploads/images", "/uploads/video/raw"]);
+
+        assert_eq!(
+            tree.longest_match("/uploads/images/a.png"),
+            Some((&"/uploads/images", 15))
+        );
+        assert_eq!(
+            tree.longest_match("/uploads/video/a.mp4"),
+            Some((&"/uploads", 8))
+        );
+        assert_eq!(
+            tree.longest_match("/uploads/video/raw/a.mov"),
+            Some((&"/uploads/video/raw", 18))
+        );
+        assert_eq!(tree.longest_match("/uploads2/a.png"), None);
+    }
+
+    #[test]
+    fn test_wildcard_segments() {
+        let tree = tree(&["/tenants/*/uploads", "/tenants/acme/uploads", "/tenants"]);
+
+        assert_eq!(
+            tree.longest_match("/tenants/globex/uploads/a.txt"),
+            Some((&"/tenants/*/uploads", 23))
+        );
+        assert_eq!(
+            tree.longest_match("/tenants/acme/uploads/a.txt"),
+            Some((&"/tenants/acme/uploads", 21))
+        );
+        assert_eq!(
+            tree.longest_match("/tenants/globex/other/a.txt"),
+            Some((&"/tenants", 8))
+        );
+    }
+
+    #[test]
+    fn test_empty_segments_ignored() {
+        let tree = tree(&["/uploads/images/"]);
+
+        assert_eq!(
+            tree.longest_match("//uploads//images/a.png"),
+            Some((&"/uploads/images/", 17))
+        );
+    }
+}
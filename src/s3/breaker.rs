@@ -0,0 +1,180 @@
+//! Circuit breaker for S3 endpoints
+//!
+//! Tracks consecutive transient failures (5xx, timeouts, connection errors)
+//! against an endpoint and stops sending it traffic once a threshold is hit.
+//!
+//! # States
+//!
+//! - **Closed** - Requests go to the endpoint; failures are counted
+//! - **Open** - Requests are diverted until the probe interval elapses
+//! - **Half-open** - A single request is let through as a probe. Success
+//!   closes the breaker, failure re-opens it.
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Circuit breaker state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl CircuitState {
+    /// Numeric value exported as a metric (0 = closed, 1 = half-open, 2 = open)
+    pub fn as_metric(&self) -> i64 {
+        match self {
+            CircuitState::Closed => 0,
+            CircuitState::HalfOpen => 1,
+            CircuitState::Open => 2,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probe_in_flight: bool,
+}
+
+/// Consecutive-failure circuit breaker
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    probe_interval: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    /// Create a closed breaker
+    ///
+    /// # Arguments
+    ///
+    /// * `failure_threshold` - Consecutive failures that trip the breaker
+    /// * `probe_interval` - Time to wait before probing an open endpoint
+    pub fn new(failure_threshold: u32, probe_interval: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            probe_interval,
+            inner: Mutex::new(Inner {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                probe_in_flight: false,
+            }),
+        }
+    }
+
+    /// Current state
+    ///
+    /// An open breaker whose probe interval has elapsed reports `HalfOpen`.
+    pub fn state(&self) -> CircuitState {
+        let inner = self.inner.lock();
+        match inner.state {
+            CircuitState::Open if self.probe_due(&inner) => CircuitState::HalfOpen,
+            state => state,
+        }
+    }
+
+    /// Whether a request may be sent to the endpoint
+    ///
+    /// In the half-open state only one probe is allowed at a time.
+    pub fn allow_request(&self) -> bool {
+        let mut inner = self.inner.lock();
+        match inner.state {
+            CircuitState::Closed => true,
+            CircuitState::Open => {
+                if self.probe_due(&inner) {
+                    inner.state = CircuitState::HalfOpen;
+                    inner.probe_in_flight = true;
+                    true
+                } else {
+                    false
+                }
+            }
+            CircuitState::HalfOpen => {
+                if inner.probe_in_flight {
+                    false
+                } else {
+                    inner.probe_in_flight = true;
+                    true
+                }
+            }
+        }
+    }
+
+    /// Record a request that reached the endpoint successfully
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock();
+        inner.state = CircuitState::Closed;
+        inner.consecutive_failures = 0;
+        inner.opened_at = None;
+        inner.probe_in_flight = false;
+    }
+
+    /// Record a transient failure
+    pub fn record_failure(&self) {
+        let mut inner = self.inner.lock();
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        inner.probe_in_flight = false;
+
+        let trip = inner.state == CircuitState::HalfOpen
+            || inner.consecutive_failures >= self.failure_threshold;
+        if trip {
+            inner.state = CircuitState::Open;
+            inner.opened_at = Some(Instant::now());
+        }
+    }
+
+    fn probe_due(&self, inner: &Inner) -> bool {
+        inner
+            .opened_at
+            .map(|opened| opened.elapsed() >= self.probe_interval)
+            .unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trips_after_threshold() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+        breaker.record_failure();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.allow_request());
+    }
+
+    #[test]
+    fn test_success_resets_failure_count() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_half_open_allows_single_probe() {
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+        breaker.record_failure();
+
+        assert!(breaker.allow_request(), "Probe should be allowed");
+        assert!(!breaker.allow_request(), "Only one probe at a time");
+
+        breaker.record_failure();
+        assert!(breaker.allow_request(), "Re-opened breaker probes again");
+        breaker.record_success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
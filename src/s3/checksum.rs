@@ -0,0 +1,107 @@
+//! CRC checksums of payloads
+//!
+//! S3 verifies an `x-amz-checksum-*` header against the body it receives,
+//! so a part corrupted between the proxy and the backend is refused rather
+//! than stored. Multipart parts carry a CRC32C by default (see
+//! `s3.checksum`): S3 checks each part as it arrives and the checksums are
+//! listed again when the upload completes.
+//!
+//! CRCs are computed with `crc-fast`, which uses SSE4.2/PCLMULQDQ on x86_64
+//! and the CRC and PMULL instructions on ARMv8, at tens of GB/s; even the
+//! largest parts are checksummed inline. Compare with SHA-256 in
+//! `benches/hashing_benchmark.rs`.
+
+use crate::profiling::profile_span;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crc_fast::{CrcAlgorithm, Digest};
+
+/// Header carrying the CRC32C of a payload
+pub const CRC32C_HEADER: &str = "x-amz-checksum-crc32c";
+
+/// Base64 of the big-endian CRC32C of `data`, as S3 expects it
+pub fn crc32c_base64(data: &[u8]) -> String {
+    let _span = profile_span!("crc32c", bytes = data.len()).entered();
+    STANDARD.encode(crc_fast::crc32_iscsi(data).to_be_bytes())
+}
+
+/// Incremental CRC of a payload, for data checksummed as it streams
+pub struct Crc {
+    digest: Digest,
+    width: CrcWidth,
+}
+
+/// Width of a CRC, which sets the length of its encoding
+#[derive(Clone, Copy)]
+enum CrcWidth {
+    Bits32,
+    Bits64,
+}
+
+impl Crc {
+    /// CRC-32 (ISO-HDLC), as in `x-amz-checksum-crc32`
+    pub fn crc32() -> Self {
+        Self::new(CrcAlgorithm::Crc32IsoHdlc, CrcWidth::Bits32)
+    }
+
+    /// CRC32C (Castagnoli), as in `x-amz-checksum-crc32c`
+    pub fn crc32c() -> Self {
+        Self::new(CrcAlgorithm::Crc32Iscsi, CrcWidth::Bits32)
+    }
+
+    /// CRC-64/NVME, as in `x-amz-checksum-crc64nvme`
+    pub fn crc64nvme() -> Self {
+        Self::new(CrcAlgorithm::Crc64Nvme, CrcWidth::Bits64)
+    }
+
+    fn new(algorithm: CrcAlgorithm, width: CrcWidth) -> Self {
+        Self {
+            digest: Digest::new(algorithm),
+            width,
+        }
+    }
+
+    /// Checksum `data` after what was checksummed so far
+    pub fn update(&mut self, data: &[u8]) {
+        self.digest.update(data);
+    }
+
+    /// Base64 of the big-endian CRC of everything checksummed
+    pub fn finish_base64(&self) -> String {
+        let crc = self.digest.finalize();
+        match self.width {
+            CrcWidth::Bits32 => STANDARD.encode((crc as u32).to_be_bytes()),
+            CrcWidth::Bits64 => STANDARD.encode(crc.to_be_bytes()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_values() {
+        // The CRC catalogue's check values of "123456789"
+        let check = |mut crc: Crc, expected: &[u8]| {
+            crc.update(b"1234");
+            crc.update(b"56789");
+            assert_eq!(crc.finish_base64(), STANDARD.encode(expected));
+        };
+        check(Crc::crc32(), &0xCBF4_3926u32.to_be_bytes());
+        check(Crc::crc32c(), &0xE306_9283u32.to_be_bytes());
+        check(Crc::crc64nvme(), &0xAE8B_1486_0A79_9888u64.to_be_bytes());
+
+        assert_eq!(crc32c_base64(b"123456789"), "4waSgw==");
+        assert_eq!(crc32c_base64(b""), "AAAAAA==");
+    }
+
+    #[test]
+    fn test_large_payload_matches_streamed() {
+        let data: Vec<u8> = (0..3 * 1024 * 1024 + 11).map(|i| (i % 251) as u8).collect();
+        let mut crc = Crc::crc32c();
+        for chunk in data.chunks(64 * 1024 + 3) {
+            crc.update(chunk);
+        }
+        assert_eq!(crc.finish_base64(), crc32c_base64(&data));
+    }
+}
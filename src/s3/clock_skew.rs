@@ -0,0 +1,111 @@
+//! Clock-skew correction for request signing
+//!
+//! S3 rejects signatures whose timestamp is more than 15 minutes off its own
+//! clock with `RequestTimeTooSkewed`. When that happens, [`ClockSkew`] reads
+//! the server's time from the error (the `ServerTime` element, or the `Date`
+//! header), remembers the offset from the local clock and applies it to every
+//! later signing timestamp, so a proxy on a host with a bad clock keeps
+//! working.
+
+use chrono::{DateTime, Utc};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{Duration, SystemTime};
+
+/// S3 error code for a signature timestamp outside the allowed skew
+const SKEW_ERROR_CODE: &str = "<Code>RequestTimeTooSkewed</Code>";
+
+/// Measured offset of the server clock from the local clock
+#[derive(Debug, Default)]
+pub struct ClockSkew {
+    offset_ms: AtomicI64,
+}
+
+impl ClockSkew {
+    /// Current offset in milliseconds (positive when the server is ahead)
+    pub fn offset_ms(&self) -> i64 {
+        self.offset_ms.load(Ordering::Relaxed)
+    }
+
+    /// Local time corrected by the measured offset
+    pub fn now(&self) -> SystemTime {
+        let offset = self.offset_ms();
+        let now = SystemTime::now();
+        if offset >= 0 {
+            now + Duration::from_millis(offset as u64)
+        } else {
+            now - Duration::from_millis(offset.unsigned_abs())
+        }
+    }
+
+    /// Update the offset from a `RequestTimeTooSkewed` error response
+    ///
+    /// Returns the new offset in milliseconds, or `None` if the response is
+    /// not a skew error or carries no usable server time.
+    pub fn correct(&self, date_header: Option<&str>, error_body: &str) -> Option<i64> {
+        if !error_body.contains(SKEW_ERROR_CODE) {
+            return None;
+        }
+        let server_time = server_time(date_header, error_body)?;
+        let offset = (server_time - Utc::now()).num_milliseconds();
+        self.offset_ms.store(offset, Ordering::Relaxed);
+        Some(offset)
+    }
+}
+
+/// Server time from a skew error's `ServerTime` element or `Date` header
+fn server_time(date_header: Option<&str>, error_body: &str) -> Option<DateTime<Utc>> {
+    let from_body = error_body
+        .split_once("<ServerTime>")
+        .and_then(|(_, rest)| rest.split_once("</ServerTime>"))
+        .and_then(|(time, _)| DateTime::parse_from_rfc3339(time.trim()).ok());
+    let from_header = || date_header.and_then(|date| DateTime::parse_from_rfc2822(date).ok());
+    from_body
+        .or_else(from_header)
+        .map(|time| time.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn skew_error(server_time: &str) -> String {
+        format!(
+            "<Error><Code>RequestTimeTooSkewed</Code>\
+             <Message>The difference between the request time and the current time is too large.</Message>\
+             <ServerTime>{}</ServerTime></Error>",
+            server_time
+        )
+    }
+
+    #[test]
+    fn test_correct_from_server_time() {
+        let skew = ClockSkew::default();
+        let ahead = (Utc::now() + chrono::Duration::hours(1)).to_rfc3339();
+
+        let offset = skew.correct(None, &skew_error(&ahead)).unwrap();
+        assert!((offset - 3_600_000).abs() < 5_000, "offset {}", offset);
+        let corrected = skew.now().duration_since(SystemTime::now()).unwrap();
+        assert!(corrected > Duration::from_secs(3590));
+    }
+
+    #[test]
+    fn test_correct_from_date_header() {
+        let skew = ClockSkew::default();
+        let behind = (Utc::now() - chrono::Duration::minutes(30)).to_rfc2822();
+        let body = "<Error><Code>RequestTimeTooSkewed</Code></Error>";
+
+        let offset = skew.correct(Some(&behind), body).unwrap();
+        assert!((offset + 1_800_000).abs() < 5_000, "offset {}", offset);
+        assert!(skew.now() < SystemTime::now());
+    }
+
+    #[test]
+    fn test_other_errors_ignored() {
+        let skew = ClockSkew::default();
+        let body =
+            "<Error><Code>AccessDenied</Code><ServerTime>2030-01-01T00:00:00Z</ServerTime></Error>";
+
+        assert_eq!(skew.correct(None, body), None);
+        assert_eq!(skew.offset_ms(), 0);
+    }
+}
@@ -230,6 +230,14 @@ mod tests {
             endpoint: None,
             access_key: None,
             secret_key: Some("secret".into()),
+            pool: None,
+            sigv4a_region_set: None,
+            hedging: None,
+            hashing: None,
+            checksum: None,
+            debug_log: None,
+            access_key_file: None,
+            secret_key_file: None,
         };
 
         let result = CredentialsProvider::from_config(&config);
@@ -244,6 +252,14 @@ mod tests {
             endpoint: None,
             access_key: Some("access".into()),
             secret_key: None,
+            pool: None,
+            sigv4a_region_set: None,
+            hedging: None,
+            hashing: None,
+            checksum: None,
+            debug_log: None,
+            access_key_file: None,
+            secret_key_file: None,
         };
 
         let result = CredentialsProvider::from_config(&config);
@@ -258,6 +274,14 @@ mod tests {
             endpoint: None,
             access_key: Some("config-access".into()),
             secret_key: Some("config-secret".into()),
+            pool: None,
+            sigv4a_region_set: None,
+            hedging: None,
+            hashing: None,
+            checksum: None,
+            debug_log: None,
+            access_key_file: None,
+            secret_key_file: None,
         };
 
         let result = CredentialsProvider::from_config(&config);
@@ -0,0 +1,89 @@
+//! Deadlines of inbound requests, bounding the S3 calls made for them
+//!
+//! A client that only waits so long for an upload can say so with
+//! [`DEADLINE_HEADER`], in milliseconds from when the proxy receives the
+//! request. The server runs the request in a [`scope`] with that deadline,
+//! and every S3 call made by the request's task is cut short when it
+//! passes: no request or retry is started after it, and requests in flight
+//! time out with it. Calls made from other tasks (such as asynchronous
+//! replication) are not bound by the deadline. OpenFGA checks sent over gRPC
+//! carry it as their `grpc-timeout`.
+
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Request header carrying the client's deadline in milliseconds
+pub const DEADLINE_HEADER: &str = "x-mizuchi-deadline-ms";
+
+tokio::task_local! {
+    static DEADLINE: Instant;
+}
+
+/// Deadline requested by `headers`, counted from now
+///
+/// `Ok(None)` without the header; an error if it is not a number of
+/// milliseconds.
+pub fn from_headers(headers: &hyper::HeaderMap) -> Result<Option<Instant>, String> {
+    let Some(value) = headers.get(DEADLINE_HEADER) else {
+        return Ok(None);
+    };
+    value
+        .to_str()
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(|ms| Some(Instant::now() + Duration::from_millis(ms)))
+        .ok_or_else(|| format!("{} must be a number of milliseconds", DEADLINE_HEADER))
+}
+
+/// Run `future` with `deadline` bounding its S3 calls
+pub async fn scope<F: Future>(deadline: Option<Instant>, future: F) -> F::Output {
+    match deadline {
+        Some(deadline) => DEADLINE.scope(deadline, future).await,
+        None => future.await,
+    }
+}
+
+/// Deadline of the current task, if any
+pub fn current() -> Option<Instant> {
+    DEADLINE.try_with(|deadline| *deadline).ok()
+}
+
+/// Time left until the current task's deadline
+///
+/// `Some(Duration::ZERO)` once it has passed; `None` without a deadline.
+pub fn remaining() -> Option<Duration> {
+    current().map(|deadline| deadline.saturating_duration_since(Instant::now()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    #[test]
+    fn test_from_headers() {
+        let mut headers = hyper::HeaderMap::new();
+        assert_eq!(from_headers(&headers), Ok(None));
+
+        headers.insert(DEADLINE_HEADER, HeaderValue::from_static("1500"));
+        let deadline = from_headers(&headers).unwrap().unwrap();
+        assert!(deadline > Instant::now() + Duration::from_millis(1000));
+
+        headers.insert(DEADLINE_HEADER, HeaderValue::from_static("soon"));
+        assert!(from_headers(&headers).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scope() {
+        assert_eq!(remaining(), None);
+
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let left = scope(Some(deadline), async { remaining() }).await.unwrap();
+        assert!(left > Duration::from_secs(59));
+
+        let passed = Instant::now() - Duration::from_millis(1);
+        let left = scope(Some(passed), async { remaining() }).await;
+        assert_eq!(left, Some(Duration::ZERO));
+    }
+}
@@ -0,0 +1,236 @@
+//! Debug log of the traffic between a bucket's client and its backend
+//!
+//! An S3-compatible backend answering `SignatureDoesNotMatch` rarely says
+//! what it signed; comparing the request as sent with what it expected is
+//! the quickest way to find out. With `s3.debug_log` set, every request of
+//! the bucket is written to a rolling file of its own: the operation and
+//! request line, the headers as sent, then the response status, headers and
+//! body (up to `max_body_bytes`), or the error the request failed with.
+//!
+//! The signature in `Authorization` and session tokens are redacted; the
+//! credential scope and signed header list are kept, since they are what
+//! tells a mismatch apart. Request payloads are never logged.
+//!
+//! ```text
+//! 2026-01-07T10:12:31.274Z #3 UploadPart > PUT http://minio:9000/uploads/big.bin?partNumber=2&uploadId=3f1c
+//!   x-amz-content-sha256: 9d0bba8bb7d4ba0ba5b8f9ba4e8b5a2bdbb4bce5f9d7d1e3e8c1a6c1a2f6a0f3
+//!   x-amz-date: 20260107T101231Z
+//!   authorization: AWS4-HMAC-SHA256 Credential=AKIA.../20260107/us-east-1/s3/aws4_request, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature=<redacted>
+//!   traceparent: 00-fbd44dda3a7e45e587351bc3d2bb8e39-8ef7e6b5c2824f01-00
+//!   (body: 5242880 bytes)
+//! 2026-01-07T10:12:31.301Z #3 < 403 Forbidden
+//!   content-type: application/xml
+//!   <Error><Code>SignatureDoesNotMatch</Code>...</Error>
+//! ```
+//!
+//! Entries are written to the file by a thread of their own, so requests
+//! never wait on the disk. Should the disk fall too far behind, entries are
+//! dropped rather than holding up uploads.
+
+use crate::config::{DebugLogRotation, S3DebugLogConfig};
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+
+/// Replaces redacted values
+const REDACTED: &str = "<redacted>";
+
+/// Rolling log file of a bucket's backend traffic
+///
+/// Entries still queued are written when it is dropped.
+pub struct DebugLog {
+    file: NonBlocking,
+    _writer: WorkerGuard,
+    max_body_bytes: usize,
+    next_id: AtomicU64,
+}
+
+impl DebugLog {
+    /// Open the log of `bucket` in the directory `config` names, creating
+    /// the directory if needed
+    pub fn open(bucket: &str, config: &S3DebugLogConfig) -> io::Result<Self> {
+        let rotation = match config.rotation {
+            DebugLogRotation::Hourly => Rotation::HOURLY,
+            DebugLogRotation::Daily => Rotation::DAILY,
+            DebugLogRotation::Never => Rotation::NEVER,
+        };
+        let file = RollingFileAppender::builder()
+            .rotation(rotation)
+            .filename_prefix(format!("s3-{}", bucket))
+            .filename_suffix("log")
+            .max_log_files(config.max_files.max(1))
+            .build(&config.dir)
+            .map_err(io::Error::other)?;
+        let (file, writer) = tracing_appender::non_blocking(file);
+        Ok(Self {
+            file,
+            _writer: writer,
+            max_body_bytes: config.max_body_bytes,
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    /// Log `request`, sent for `operation`, returning the ID that ties its
+    /// response to it
+    pub fn request(&self, operation: &str, request: &reqwest::Request) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut entry = format!(
+            "{} #{} {} > {} {}\n",
+            timestamp(),
+            id,
+            operation,
+            request.method(),
+            request.url()
+        );
+        write_headers(&mut entry, request.headers());
+        if let Some(len) = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .map(<[u8]>::len)
+        {
+            let _ = writeln!(entry, "  (body: {} bytes)", len);
+        }
+        self.write(&entry);
+        id
+    }
+
+    /// Log the response to request `id`
+    pub fn response(&self, id: u64, status: StatusCode, headers: &HeaderMap, body: &[u8]) {
+        let mut entry = format!("{} #{} < {}\n", timestamp(), id, status);
+        write_headers(&mut entry, headers);
+        if !body.is_empty() {
+            let shown = &body[..body.len().min(self.max_body_bytes)];
+            let _ = write!(entry, "  {}", String::from_utf8_lossy(shown));
+            if shown.len() < body.len() {
+                let _ = write!(entry, "... ({} more bytes)", body.len() - shown.len());
+            }
+            entry.push('\n');
+        }
+        self.write(&entry);
+    }
+
+    /// Log that request `id` failed without a response
+    pub fn error(&self, id: u64, error: &dyn std::fmt::Display) {
+        self.write(&format!("{} #{} ! {}\n", timestamp(), id, error));
+    }
+
+    /// Queue `entry` as a single write, so entries of concurrent requests
+    /// do not interleave
+    fn write(&self, entry: &str) {
+        if let Err(e) = self.file.clone().write_all(entry.as_bytes()) {
+            tracing::warn!("S3 debug log not written: {}", e);
+        }
+    }
+}
+
+fn timestamp() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+fn write_headers(entry: &mut String, headers: &HeaderMap) {
+    for (name, value) in headers {
+        let value = String::from_utf8_lossy(value.as_bytes());
+        let _ = writeln!(entry, "  {}: {}", name, redact(name.as_str(), &value));
+    }
+}
+
+/// `value` of header `name` with secrets replaced
+///
+/// SigV4 and SigV4A `Authorization` headers keep everything but the
+/// signature; other schemes and session tokens are dropped whole.
+pub fn redact<'a>(name: &str, value: &'a str) -> Cow<'a, str> {
+    match name.to_ascii_lowercase().as_str() {
+        "authorization" if value.starts_with("AWS4-") => match value.find("Signature=") {
+            Some(at) => {
+                let start = at + "Signature=".len();
+                let end = value[start..]
+                    .find(',')
+                    .map_or(value.len(), |len| start + len);
+                Cow::Owned(format!("{}{}{}", &value[..start], REDACTED, &value[end..]))
+            }
+            None => Cow::Borrowed(value),
+        },
+        "authorization" | "proxy-authorization" | "x-amz-security-token" => Cow::Borrowed(REDACTED),
+        _ => Cow::Borrowed(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        let sigv4 = "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20260107/us-east-1/s3/aws4_request, \
+                     SignedHeaders=host;x-amz-date, Signature=fe5f80f77d5fa3beca038a248ff027d0445342fe2855ddc963176630326f1024";
+        assert_eq!(
+            redact("Authorization", sigv4),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20260107/us-east-1/s3/aws4_request, \
+             SignedHeaders=host;x-amz-date, Signature=<redacted>"
+        );
+        assert_eq!(
+            redact(
+                "authorization",
+                "AWS4-ECDSA-P256-SHA256 Signature=3045, Credential=AK"
+            ),
+            "AWS4-ECDSA-P256-SHA256 Signature=<redacted>, Credential=AK"
+        );
+        assert_eq!(redact("authorization", "Bearer abc"), REDACTED);
+        assert_eq!(redact("x-amz-security-token", "FwoGZXIvYXdz"), REDACTED);
+        assert_eq!(redact("x-amz-date", "20260107T101231Z"), "20260107T101231Z");
+    }
+
+    #[test]
+    fn test_log_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = DebugLog::open(
+            "uploads",
+            &S3DebugLogConfig {
+                dir: dir.path().display().to_string(),
+                rotation: DebugLogRotation::Never,
+                max_files: 1,
+                max_body_bytes: 16,
+            },
+        )
+        .unwrap();
+
+        let request = reqwest::Client::new()
+            .put("http://minio:9000/uploads/a.txt")
+            .header("authorization", "AWS4-HMAC-SHA256 Signature=abcdef")
+            .body("hello")
+            .build()
+            .unwrap();
+        let id = log.request("PutObject", &request);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-amz-request-id", "4442587FB7D0A2F9".parse().unwrap());
+        log.response(
+            id,
+            StatusCode::FORBIDDEN,
+            &headers,
+            b"<Error><Code>SignatureDoesNotMatch</Code></Error>",
+        );
+        log.error(log.request("UploadPart", &request), &"connection reset");
+        // Dropping the log writes the queued entries
+        drop(log);
+
+        let written = std::fs::read_to_string(dir.path().join("s3-uploads.log")).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        assert!(lines[0].ends_with("#1 PutObject > PUT http://minio:9000/uploads/a.txt"));
+        assert_eq!(
+            lines[1],
+            "  authorization: AWS4-HMAC-SHA256 Signature=<redacted>"
+        );
+        assert_eq!(lines[2], "  (body: 5 bytes)");
+        assert!(lines[3].ends_with("#1 < 403 Forbidden"));
+        assert_eq!(lines[4], "  x-amz-request-id: 4442587FB7D0A2F9");
+        assert_eq!(lines[5], "  <Error><Code>Sig... (33 more bytes)");
+        assert!(lines[6].ends_with("#2 UploadPart > PUT http://minio:9000/uploads/a.txt"));
+        assert!(lines[9].ends_with("#2 ! connection reset"));
+        assert!(!written.contains("abcdef"));
+    }
+}
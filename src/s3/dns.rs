@@ -0,0 +1,175 @@
+//! DNS resolution for S3 endpoints
+//!
+//! [`Resolver`] replaces the system lookup reqwest does for every new
+//! connection. Answers are cached for `cache_ttl_secs`, so a burst of new
+//! connections to an endpoint costs one lookup; `overrides` pin hostnames
+//! to fixed addresses without touching `/etc/hosts` (e.g. an air-gapped
+//! MinIO). With `happy_eyeballs`, IPv6 and IPv4 addresses are both handed
+//! to the connector, which races the families (RFC 8305); without it only
+//! the family of the first address is used and connections are not raced.
+
+use crate::config::S3DnsConfig;
+use parking_lot::Mutex;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Resolved addresses by host, with when they were looked up
+type Cache = Mutex<HashMap<String, (Instant, Vec<IpAddr>)>>;
+
+/// Caching resolver with static overrides
+///
+/// Clones share the cache.
+#[derive(Debug, Clone)]
+pub struct Resolver {
+    ttl: Duration,
+    overrides: Arc<BTreeMap<String, Vec<IpAddr>>>,
+    happy_eyeballs: bool,
+    cache: Arc<Cache>,
+}
+
+impl Resolver {
+    /// Create a resolver from its configuration
+    pub fn new(config: &S3DnsConfig) -> Self {
+        Self {
+            ttl: Duration::from_secs(config.cache_ttl_secs),
+            overrides: Arc::new(
+                config
+                    .overrides
+                    .iter()
+                    .map(|(host, addrs)| (host.to_ascii_lowercase(), addrs.clone()))
+                    .collect(),
+            ),
+            happy_eyeballs: config.happy_eyeballs,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Addresses of `host`, from the overrides, the cache or a lookup
+    pub async fn lookup(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        let host = host.to_ascii_lowercase();
+        if let Some(addrs) = self.overrides.get(&host) {
+            return Ok(addrs.clone());
+        }
+        if let Some(addrs) = self.cached(&host) {
+            return Ok(addrs);
+        }
+
+        let addrs = order(
+            tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .map(|addr| addr.ip())
+                .collect(),
+            self.happy_eyeballs,
+        );
+        if addrs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no addresses for {}", host),
+            ));
+        }
+        if !self.ttl.is_zero() {
+            self.cache
+                .lock()
+                .insert(host, (Instant::now() + self.ttl, addrs.clone()));
+        }
+        Ok(addrs)
+    }
+
+    fn cached(&self, host: &str) -> Option<Vec<IpAddr>> {
+        let mut cache = self.cache.lock();
+        match cache.get(host) {
+            Some((expires_at, addrs)) if *expires_at > Instant::now() => Some(addrs.clone()),
+            Some(_) => {
+                cache.remove(host);
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+impl Resolve for Resolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs = resolver.lookup(&host).await?;
+            // Port 0 is replaced with the URL's port by the connector
+            let addrs: Addrs = Box::new(addrs.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+            Ok(addrs)
+        })
+    }
+}
+
+/// Order resolved addresses for the connector
+///
+/// For Happy Eyeballs, IPv6 addresses come first and IPv4 ones follow as
+/// the fallback family; otherwise only the family of the first address is
+/// kept.
+fn order(addrs: Vec<IpAddr>, happy_eyeballs: bool) -> Vec<IpAddr> {
+    if happy_eyeballs {
+        let (mut v6, v4): (Vec<_>, Vec<_>) = addrs.into_iter().partition(IpAddr::is_ipv6);
+        v6.extend(v4);
+        v6
+    } else {
+        let Some(first) = addrs.first() else {
+            return addrs;
+        };
+        let ipv6 = first.is_ipv6();
+        addrs
+            .into_iter()
+            .filter(|ip| ip.is_ipv6() == ipv6)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolver(ttl_secs: u64) -> Resolver {
+        Resolver::new(&S3DnsConfig {
+            cache_ttl_secs: ttl_secs,
+            overrides: BTreeMap::from([(
+                "MinIO.internal".to_string(),
+                vec!["10.0.0.5".parse().unwrap()],
+            )]),
+            happy_eyeballs: true,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_overrides() {
+        let addrs = resolver(60).lookup("minio.internal").await.unwrap();
+
+        assert_eq!(addrs, vec!["10.0.0.5".parse::<IpAddr>().unwrap()]);
+    }
+
+    #[tokio::test]
+    async fn test_lookups_cached() {
+        let resolver = resolver(60);
+
+        let addrs = resolver.lookup("localhost").await.unwrap();
+        assert!(!addrs.is_empty());
+        assert_eq!(resolver.cached("localhost"), Some(addrs));
+
+        let uncached = self::resolver(0);
+        uncached.lookup("localhost").await.unwrap();
+        assert_eq!(uncached.cached("localhost"), None);
+    }
+
+    #[test]
+    fn test_order() {
+        let v4: IpAddr = "192.0.2.1".parse().unwrap();
+        let v6: IpAddr = "2001:db8::1".parse().unwrap();
+
+        assert_eq!(order(vec![v4, v6], true), vec![v6, v4]);
+        assert_eq!(order(vec![v4, v6], false), vec![v4]);
+        assert_eq!(order(vec![v6, v4], false), vec![v6]);
+        assert!(order(Vec::new(), false).is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+//! S3 error responses
+//!
+//! S3 reports failures as an `<Error>` document with a `Code`, a `Message`
+//! and a `RequestId`. [`S3ServiceError`] keeps those apart (with the HTTP
+//! status) so callers can act on the error code instead of matching on the
+//! response text, and [`S3ErrorCode`] names the codes the proxy handles.
+
+use std::fmt;
+
+/// Error code of an S3 error response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum S3ErrorCode {
+    /// The bucket does not exist
+    NoSuchBucket,
+    /// The multipart upload does not exist (aborted or completed)
+    NoSuchUpload,
+    /// The credentials may not perform the operation
+    AccessDenied,
+    /// Request rate too high, back off
+    SlowDown,
+    /// A part of a multipart completion was not found or its ETag differs
+    InvalidPart,
+    /// The parts of a multipart completion are not in ascending order
+    InvalidPartOrder,
+    /// A part other than the last is below the 5 MiB minimum
+    EntityTooSmall,
+    /// The signature timestamp is too far from the server's clock
+    RequestTimeTooSkewed,
+    /// Internal error of the S3 service
+    InternalError,
+    /// A request argument is out of range, e.g. a part number above 10000
+    InvalidArgument,
+    /// A request body is not the XML document the operation expects
+    MalformedXML,
+    /// Any other code (empty if the response had no error document)
+    Other(String),
+}
+
+impl S3ErrorCode {
+    /// Code named by the `Code` element of an error document
+    pub fn parse(code: &str) -> Self {
+        match code {
+            "NoSuchBucket" => Self::NoSuchBucket,
+            "NoSuchUpload" => Self::NoSuchUpload,
+            "AccessDenied" => Self::AccessDenied,
+            "SlowDown" => Self::SlowDown,
+            "InvalidPart" => Self::InvalidPart,
+            "InvalidPartOrder" => Self::InvalidPartOrder,
+            "EntityTooSmall" => Self::EntityTooSmall,
+            "RequestTimeTooSkewed" => Self::RequestTimeTooSkewed,
+            "InternalError" => Self::InternalError,
+            "InvalidArgument" => Self::InvalidArgument,
+            "MalformedXML" => Self::MalformedXML,
+            other => Self::Other(other.to_string()),
+        }
+    }
+
+    /// The code as S3 spells it
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::NoSuchBucket => "NoSuchBucket",
+            Self::NoSuchUpload => "NoSuchUpload",
+            Self::AccessDenied => "AccessDenied",
+            Self::SlowDown => "SlowDown",
+            Self::InvalidPart => "InvalidPart",
+            Self::InvalidPartOrder => "InvalidPartOrder",
+            Self::EntityTooSmall => "EntityTooSmall",
+            Self::RequestTimeTooSkewed => "RequestTimeTooSkewed",
+            Self::InternalError => "InternalError",
+            Self::InvalidArgument => "InvalidArgument",
+            Self::MalformedXML => "MalformedXML",
+            Self::Other(code) => code,
+        }
+    }
+
+    /// Metrics label of the code
+    ///
+    /// Codes without a variant share the `other` label, so a backend
+    /// returning unusual codes cannot grow the label set.
+    pub fn metric_label(&self) -> &str {
+        match self {
+            Self::Other(_) => "other",
+            code => code.as_str(),
+        }
+    }
+}
+
+impl fmt::Display for S3ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Error response of the S3 service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3ServiceError {
+    /// HTTP status of the response
+    pub status: u16,
+    pub code: S3ErrorCode,
+    pub message: String,
+    /// `RequestId` of the failed request, for S3 support cases
+    pub request_id: Option<String>,
+}
+
+impl S3ServiceError {
+    /// Error from a failure response's status and body
+    ///
+    /// Bodies that are not an error document (HEAD responses have none)
+    /// give an empty code, with the body as the message.
+    pub fn from_response(status: u16, body: &str) -> Self {
+        match xml_tag(body, "Code") {
+            Some(code) => Self {
+                status,
+                code: S3ErrorCode::parse(code.trim()),
+                message: xml_tag(body, "Message")
+                    .map(|message| super::unescape_xml(message.to_string()))
+                    .unwrap_or_default(),
+                request_id: xml_tag(body, "RequestId").map(str::to_string),
+            },
+            None => Self {
+                status,
+                code: S3ErrorCode::Other(String::new()),
+                message: body.trim().to_string(),
+                request_id: None,
+            },
+        }
+    }
+
+    /// Whether the error is a server-side (5xx) failure
+    pub fn is_server_error(&self) -> bool {
+        self.status >= 500
+    }
+}
+
+impl fmt::Display for S3ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HTTP {}", self.status)?;
+        if !self.code.as_str().is_empty() {
+            write!(f, " {}", self.code)?;
+        }
+        if !self.message.is_empty() {
+            write!(f, ": {}", self.message)?;
+        }
+        if let Some(request_id) = &self.request_id {
+            write!(f, " (request {})", request_id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Text of the first `<tag>` element in `xml`
+fn xml_tag<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start_tag = format!("<{}>", tag);
+    let end_tag = format!("</{}>", tag);
+    let start = xml.find(&start_tag)? + start_tag.len();
+    let end = xml[start..].find(&end_tag)? + start;
+    Some(&xml[start..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_error_document() {
+        let error = S3ServiceError::from_response(
+            404,
+            "<?xml version=\"1.0\"?><Error><Code>NoSuchBucket</Code>\
+             <Message>The specified bucket does not exist</Message>\
+             <RequestId>4442587FB7D0A2F9</RequestId></Error>",
+        );
+        assert_eq!(error.code, S3ErrorCode::NoSuchBucket);
+        assert_eq!(error.message, "The specified bucket does not exist");
+        assert_eq!(error.request_id.as_deref(), Some("4442587FB7D0A2F9"));
+        assert_eq!(
+            error.to_string(),
+            "HTTP 404 NoSuchBucket: The specified bucket does not exist (request 4442587FB7D0A2F9)"
+        );
+    }
+
+    #[test]
+    fn test_parse_without_error_document() {
+        let error = S3ServiceError::from_response(404, "");
+        assert_eq!(error.code, S3ErrorCode::Other(String::new()));
+        assert_eq!(error.to_string(), "HTTP 404");
+    }
+
+    #[test]
+    fn test_metric_label() {
+        assert_eq!(S3ErrorCode::SlowDown.metric_label(), "SlowDown");
+        assert_eq!(
+            S3ErrorCode::parse("KeyTooLongError").metric_label(),
+            "other"
+        );
+    }
+}
@@ -0,0 +1,253 @@
+//! Failover between a primary and a fallback S3 endpoint
+//!
+//! [`FailoverClient`] sends requests to the primary endpoint while its
+//! [`CircuitBreaker`] is closed. Transient failures (5xx, timeouts) are
+//! retried once on the fallback endpoint and counted against the breaker.
+//! Once the breaker trips, uploads go straight to the fallback, and the
+//! primary is probed with a single live request every probe interval until
+//! it recovers.
+//!
+//! Multipart uploads stay on the endpoint they were created on: parts and
+//! completion for an upload ID are never moved to the other endpoint.
+
+use super::breaker::{CircuitBreaker, CircuitState};
+use super::{S3Client, S3ClientError, S3CompletedPart};
+use crate::metrics;
+use crate::upload::backend::{StorageBackend, StoredObject};
+use crate::upload::multipart::{CompletedPart, PendingUpload};
+use crate::upload::UploadError;
+use async_trait::async_trait;
+use bytes::Bytes;
+use dashmap::DashMap;
+use std::future::Future;
+use std::time::Duration;
+
+/// Endpoint a request was sent to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Endpoint {
+    Primary,
+    Fallback,
+}
+
+/// S3 client with circuit-breaker failover to a secondary endpoint
+pub struct FailoverClient {
+    /// Logical bucket name used as the metrics label
+    name: String,
+    primary: S3Client,
+    fallback: S3Client,
+    breaker: CircuitBreaker,
+    /// Endpoint of each in-progress multipart upload, keyed by upload ID
+    sessions: DashMap<String, Endpoint>,
+}
+
+impl FailoverClient {
+    /// Create a failover client
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Logical bucket name (metrics label)
+    /// * `primary` - Preferred endpoint
+    /// * `fallback` - Endpoint used while the primary is unhealthy
+    /// * `failure_threshold` - Consecutive transient failures that trip the breaker
+    /// * `probe_interval` - How often to probe the primary while tripped
+    pub fn new(
+        name: &str,
+        primary: S3Client,
+        fallback: S3Client,
+        failure_threshold: u32,
+        probe_interval: Duration,
+    ) -> Self {
+        metrics::record_circuit_state(name, CircuitState::Closed.as_metric());
+        Self {
+            name: name.to_string(),
+            primary,
+            fallback,
+            breaker: CircuitBreaker::new(failure_threshold, probe_interval),
+            sessions: DashMap::new(),
+        }
+    }
+
+    /// Circuit breaker state of the primary endpoint
+    pub fn state(&self) -> CircuitState {
+        self.breaker.state()
+    }
+
+    fn client(&self, endpoint: Endpoint) -> &S3Client {
+        match endpoint {
+            Endpoint::Primary => &self.primary,
+            Endpoint::Fallback => &self.fallback,
+        }
+    }
+
+    /// Feed the outcome of a primary request into the breaker
+    fn observe<T>(&self, result: &Result<T, S3ClientError>) {
+        match result {
+            Err(e) if e.is_transient() => self.breaker.record_failure(),
+            // Any other response means the endpoint is reachable and serving
+            _ => self.breaker.record_success(),
+        }
+        metrics::record_circuit_state(&self.name, self.breaker.state().as_metric());
+    }
+
+    /// Run `op` on the primary, failing over to the fallback when needed
+    async fn run<'a, T, F, Fut>(&'a self, op: F) -> Result<(T, Endpoint), S3ClientError>
+    where
+        F: Fn(&'a S3Client) -> Fut,
+        Fut: Future<Output = Result<T, S3ClientError>>,
+    {
+        if self.breaker.allow_request() {
+            let result = op(&self.primary).await;
+            self.observe(&result);
+            match result {
+                Err(e) if e.is_transient() => {
+                    tracing::warn!(
+                        bucket = %self.name,
+                        error = %e,
+                        "Primary S3 endpoint failed, retrying on fallback"
+                    );
+                }
+                other => return other.map(|value| (value, Endpoint::Primary)),
+            }
+        }
+
+        metrics::record_failover(&self.name);
+        op(&self.fallback)
+            .await
+            .map(|value| (value, Endpoint::Fallback))
+    }
+
+    /// Run `op` on the endpoint a multipart upload was created on
+    async fn run_pinned<'a, T, F, Fut>(&'a self, upload_id: &str, op: F) -> Result<T, S3ClientError>
+    where
+        F: FnOnce(&'a S3Client) -> Fut,
+        Fut: Future<Output = Result<T, S3ClientError>>,
+    {
+        let endpoint = self
+            .sessions
+            .get(upload_id)
+            .map(|e| *e)
+            .unwrap_or(Endpoint::Primary);
+
+        let result = op(self.client(endpoint)).await;
+        if endpoint == Endpoint::Primary {
+            self.observe(&result);
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl StorageBackend for FailoverClient {
+    fn bucket(&self) -> &str {
+        self.primary.bucket()
+    }
+
+    fn circuit_state(&self) -> Option<CircuitState> {
+        Some(self.state())
+    }
+
+    async fn put_object(
+        &self,
+        key: &str,
+        body: Bytes,
+        content_type: Option<&str>,
+    ) -> Result<StoredObject, UploadError> {
+        self.put_object_with_headers(key, body, content_type, &[])
+            .await
+    }
+
+    async fn put_object_with_headers(
+        &self,
+        key: &str,
+        body: Bytes,
+        content_type: Option<&str>,
+        headers: &[(String, String)],
+    ) -> Result<StoredObject, UploadError> {
+        let (response, _) = self
+            .run(|client| client.put_object_with_headers(key, body.clone(), content_type, headers))
+            .await?;
+        Ok(response.into())
+    }
+
+    async fn create_multipart_upload(&self, key: &str) -> Result<String, UploadError> {
+        self.create_multipart_upload_with_headers(key, &[]).await
+    }
+
+    async fn create_multipart_upload_with_headers(
+        &self,
+        key: &str,
+        headers: &[(String, String)],
+    ) -> Result<String, UploadError> {
+        let (response, endpoint) = self
+            .run(|client| client.create_multipart_upload_with_headers(key, headers))
+            .await?;
+        self.sessions.insert(response.upload_id.clone(), endpoint);
+        Ok(response.upload_id)
+    }
+
+    async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: u32,
+        body: Bytes,
+    ) -> Result<StoredObject, UploadError> {
+        let response = self
+            .run_pinned(upload_id, |client| {
+                client.upload_part(key, upload_id, part_number, body)
+            })
+            .await?;
+        Ok(response.into())
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[CompletedPart],
+    ) -> Result<StoredObject, UploadError> {
+        self.complete_multipart_upload_with_headers(key, upload_id, parts, &[])
+            .await
+    }
+
+    async fn complete_multipart_upload_with_headers(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[CompletedPart],
+        headers: &[(String, String)],
+    ) -> Result<StoredObject, UploadError> {
+        let s3_parts = parts
+            .iter()
+            .map(|p| S3CompletedPart {
+                part_number: p.part_number,
+                etag: p.etag.clone(),
+                checksum_crc32c: p.checksum_crc32c.clone(),
+            })
+            .collect();
+
+        let response = self
+            .run_pinned(upload_id, |client| {
+                client.complete_multipart_upload_with_headers(key, upload_id, s3_parts, headers)
+            })
+            .await?;
+        self.sessions.remove(upload_id);
+        Ok(response.into())
+    }
+
+    async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> Result<(), UploadError> {
+        self.run_pinned(upload_id, |client| {
+            client.abort_multipart_upload(key, upload_id)
+        })
+        .await?;
+        self.sessions.remove(upload_id);
+        Ok(())
+    }
+
+    /// Lists the primary endpoint only, where uploads are normally created
+    async fn list_multipart_uploads(&self) -> Result<Vec<PendingUpload>, UploadError> {
+        let result = self.primary.list_multipart_uploads().await;
+        self.observe(&result);
+        Ok(result?.into_iter().map(PendingUpload::from).collect())
+    }
+}
@@ -0,0 +1,116 @@
+//! SHA-256 of request payloads
+//!
+//! SigV4 signs the SHA-256 of the whole payload, so every PutObject hashes
+//! its body before it is sent. Hashing a 100MB body takes a few hundred
+//! milliseconds, which on a runtime worker stalls every other task queued
+//! on it; bodies of at least `s3.hashing.offload_threshold_bytes` are
+//! therefore hashed on the blocking thread pool instead (see
+//! [`sha256_hex_offloaded`]).
+//!
+//! The `sha2` crate uses SHA extensions where the CPU has them, and portable
+//! code elsewhere. With the `simd-sha256` feature hashing uses `ring`
+//! instead, whose assembly also covers CPUs with only AVX2 or NEON. See
+//! `benches/hashing_benchmark.rs` for both.
+
+use crate::profiling::profile_span;
+use bytes::Bytes;
+
+/// Smallest body hashed on the blocking thread pool unless configured
+pub const DEFAULT_OFFLOAD_THRESHOLD: u64 = 1024 * 1024;
+
+/// Hex-encoded SHA-256 of `data`
+pub fn sha256_hex(data: &[u8]) -> String {
+    let _span = profile_span!("sha256", bytes = data.len()).entered();
+    let mut hasher = Sha256Hasher::new();
+    hasher.update(data);
+    hasher.finish_hex()
+}
+
+/// Hex-encoded SHA-256 of `body`, hashed on the blocking thread pool if it
+/// is at least `threshold` bytes
+pub async fn sha256_hex_offloaded(body: &Bytes, threshold: u64) -> String {
+    if (body.len() as u64) < threshold {
+        return sha256_hex(body);
+    }
+    let owned = body.clone();
+    match tokio::task::spawn_blocking(move || sha256_hex(&owned)).await {
+        Ok(hash) => hash,
+        // Only fails if the runtime is shutting down; hash here instead
+        Err(_) => sha256_hex(body),
+    }
+}
+
+/// Incremental SHA-256, for payloads hashed as they are written
+pub struct Sha256Hasher {
+    #[cfg(feature = "simd-sha256")]
+    context: ring::digest::Context,
+    #[cfg(not(feature = "simd-sha256"))]
+    hasher: sha2::Sha256,
+}
+
+impl Sha256Hasher {
+    /// Start hashing an empty payload
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "simd-sha256")]
+            context: ring::digest::Context::new(&ring::digest::SHA256),
+            #[cfg(not(feature = "simd-sha256"))]
+            hasher: <sha2::Sha256 as sha2::Digest>::new(),
+        }
+    }
+
+    /// Hash `data` after what was hashed so far
+    pub fn update(&mut self, data: &[u8]) {
+        #[cfg(feature = "simd-sha256")]
+        self.context.update(data);
+        #[cfg(not(feature = "simd-sha256"))]
+        sha2::Digest::update(&mut self.hasher, data);
+    }
+
+    /// Hex-encoded SHA-256 of everything hashed
+    pub fn finish_hex(self) -> String {
+        #[cfg(feature = "simd-sha256")]
+        return hex::encode(self.context.finish());
+        #[cfg(not(feature = "simd-sha256"))]
+        return hex::encode(sha2::Digest::finalize(self.hasher));
+    }
+}
+
+impl Default for Sha256Hasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+    const HELLO: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(sha256_hex(b""), EMPTY);
+        assert_eq!(sha256_hex(b"hello"), HELLO);
+
+        let mut hasher = Sha256Hasher::new();
+        hasher.update(b"hel");
+        hasher.update(b"lo");
+        assert_eq!(hasher.finish_hex(), HELLO);
+    }
+
+    #[tokio::test]
+    async fn test_offloaded_matches_inline() {
+        let body = Bytes::from(vec![0x5a; 3 * 1024 * 1024 + 7]);
+        let inline = sha256_hex(&body);
+        // Below, at and above the threshold
+        for threshold in [u64::MAX, body.len() as u64, 1] {
+            assert_eq!(sha256_hex_offloaded(&body, threshold).await, inline);
+        }
+        assert_eq!(
+            sha256_hex_offloaded(&Bytes::from_static(b"hello"), 0).await,
+            HELLO
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! Hedged requests for small PutObjects
+//!
+//! A few PutObjects land on a slow S3 front end and take many times the
+//! usual latency. For small objects it is cheaper to send the request again
+//! than to wait: once an attempt has been running for longer than the
+//! configured percentile of recent PutObject latencies, [`Hedging`] says a
+//! second attempt should start, and the client keeps whichever completes
+//! first. Overwriting a key with the same body is idempotent, so the
+//! duplicate write is harmless.
+
+use crate::config::S3HedgingConfig;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Latencies kept for estimating the percentile
+const MAX_SAMPLES: usize = 512;
+
+/// Latencies needed before the percentile replaces `max_delay_ms`
+const MIN_SAMPLES: usize = 20;
+
+/// Hedging policy and the recent PutObject latencies it is based on
+#[derive(Debug)]
+pub struct Hedging {
+    config: S3HedgingConfig,
+    samples: Mutex<VecDeque<Duration>>,
+}
+
+impl Hedging {
+    /// Create a policy with no latencies measured yet
+    pub fn new(config: S3HedgingConfig) -> Self {
+        Self {
+            config,
+            samples: Mutex::new(VecDeque::with_capacity(MAX_SAMPLES)),
+        }
+    }
+
+    /// Whether a PutObject of `size` bytes is hedged
+    pub fn applies(&self, size: u64) -> bool {
+        size <= self.config.max_object_size
+    }
+
+    /// How long the first attempt may run before a second one starts
+    pub fn delay(&self) -> Duration {
+        let min = Duration::from_millis(self.config.min_delay_ms);
+        let max = Duration::from_millis(self.config.max_delay_ms);
+
+        let samples = self.samples.lock();
+        if samples.len() < MIN_SAMPLES {
+            return max;
+        }
+        let mut sorted: Vec<Duration> = samples.iter().copied().collect();
+        drop(samples);
+        sorted.sort_unstable();
+
+        let rank = (sorted.len() * usize::from(self.config.percentile)).div_ceil(100);
+        sorted[rank.saturating_sub(1)].clamp(min, max)
+    }
+
+    /// Record the latency of a successful attempt
+    pub fn observe(&self, latency: Duration) {
+        let mut samples = self.samples.lock();
+        if samples.len() == MAX_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(latency);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hedging() -> Hedging {
+        Hedging::new(S3HedgingConfig {
+            max_object_size: 1024,
+            percentile: 90,
+            min_delay_ms: 10,
+            max_delay_ms: 500,
+        })
+    }
+
+    #[test]
+    fn test_applies_to_small_objects() {
+        let hedging = hedging();
+
+        assert!(hedging.applies(1024));
+        assert!(!hedging.applies(1025));
+    }
+
+    #[test]
+    fn test_delay_waits_for_samples() {
+        let hedging = hedging();
+        for _ in 0..MIN_SAMPLES - 1 {
+            hedging.observe(Duration::from_millis(20));
+        }
+
+        assert_eq!(hedging.delay(), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_delay_uses_percentile() {
+        let hedging = hedging();
+        for ms in 1..=100 {
+            hedging.observe(Duration::from_millis(ms));
+        }
+
+        assert_eq!(hedging.delay(), Duration::from_millis(90));
+    }
+
+    #[test]
+    fn test_delay_clamped() {
+        let hedging = hedging();
+        for _ in 0..MIN_SAMPLES {
+            hedging.observe(Duration::from_millis(1));
+        }
+        assert_eq!(hedging.delay(), Duration::from_millis(10));
+
+        for _ in 0..MAX_SAMPLES {
+            hedging.observe(Duration::from_secs(5));
+        }
+        assert_eq!(hedging.delay(), Duration::from_millis(500));
+    }
+}
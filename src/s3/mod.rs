@@ -8,8 +8,16 @@
 //! - **Distributed Tracing**: All S3 operations create spans with OpenTelemetry
 //! - **W3C Trace Context**: Automatic traceparent header injection for distributed tracing
 //! - **XML Parsing**: Parses S3 XML responses for multipart uploads
-//! - **Error Handling**: Comprehensive HTTP error handling with S3 error messages
-//! - **SigV4 Signing**: AWS Signature Version 4 authentication
+//! - **Error Handling**: S3 error responses are parsed into typed error codes
+//!   (see [`S3ServiceError`])
+//! - **Retries**: Transient failures of every operation are retried with
+//!   full-jitter backoff, honoring `Retry-After`, within a retry budget
+//! - **SigV4 Signing**: AWS Signature Version 4 authentication, or SigV4A
+//!   for Multi-Region Access Points
+//! - **Clock-Skew Correction**: `RequestTimeTooSkewed` errors adjust the
+//!   signing clock to the server's
+//! - **Hedged Requests**: optionally resend slow PutObjects of small objects
+//!   and keep the first response
 //! - **Connection Pool**: S3ClientPool for managing multiple bucket clients
 //! - **Credentials**: Flexible credential loading from environment or config
 //!
@@ -28,6 +36,7 @@
 //!     secret_key: Some("minioadmin".to_string()),
 //!     retry: None,   // Use default retry config (3 retries with exponential backoff)
 //!     timeout: None, // Use default timeouts (5s connect, 30s request)
+//!     pool: None,
 //! };
 //!
 //! let client = S3Client::new(config)?;
@@ -55,6 +64,7 @@
 //! #     secret_key: Some("minioadmin".to_string()),
 //! #     retry: None,
 //! #     timeout: None,
+//! #     pool: None,
 //! # };
 //! let client = S3Client::new(config)?;
 //!
@@ -72,8 +82,8 @@
 //!
 //! // 3. Complete multipart upload
 //! let parts = vec![
-//!     S3CompletedPart { part_number: 1, etag: part1_response.etag },
-//!     S3CompletedPart { part_number: 2, etag: part2_response.etag },
+//!     S3CompletedPart::from_response(1, &part1_response),
+//!     S3CompletedPart::from_response(2, &part2_response),
 //! ];
 //! let complete_response = client.complete_multipart_upload(key, &upload_id, parts).await?;
 //! println!("Upload complete! ETag: {}", complete_response.etag);
@@ -98,38 +108,67 @@
 //!
 //! ```text
 //! traceparent: 00-{trace-id}-{span-id}-{flags}
-//! Example: 00-0000000000000000000001234567890a-0000000012345678-01
+//! Example: 00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01
 //! ```
 //!
-//! This enables end-to-end tracing across service boundaries, allowing you to track
-//! requests from the upload proxy through to S3 and back.
+//! With the `tracing` feature, the header carries the current OpenTelemetry span
+//! (plus `tracestate`), so S3 requests join the trace of the client request
+//! that caused them. This enables end-to-end tracing across service boundaries,
+//! allowing you to track requests from the upload proxy through to S3 and back.
 //!
 //! # Implementation Notes
 //!
-//! - **No SigV4 signing yet**: Currently sends unsigned requests (works with MinIO in dev mode)
-//! - **W3C Trace Context**: Automatic traceparent injection (TODO: extract from OpenTelemetry span)
+//! - **SigV4 signing**: Every request is signed when the client has credentials
+//!   (SigV4A with a region set); without them requests are sent unsigned
+//! - **W3C Trace Context**: Automatic traceparent injection from the current span
 //! - **Simple XML parsing**: Uses basic string matching - consider using quick-xml for complex responses
 //! - **Key parameter**: All multipart operations now accept key parameter for flexible object naming
 
 // Sub-modules
+pub mod breaker;
+pub mod checksum;
+pub mod clock_skew;
 pub mod credentials;
+pub mod deadline;
+pub mod debug_log;
+pub mod dns;
+pub mod error;
+pub mod failover;
+pub mod hashing;
+pub mod hedge;
 pub mod pool;
+pub mod retry;
 
 // Re-exports for convenience
+pub use breaker::{CircuitBreaker, CircuitState};
+pub use clock_skew::ClockSkew;
 pub use credentials::{
     Credentials, CredentialsError, CredentialsProvider, CredentialsProviderTrait,
     EnvironmentCredentials, StaticCredentials,
 };
-pub use pool::{S3ClientPool, S3ClientPoolError};
-
+pub use debug_log::DebugLog;
+pub use error::{S3ErrorCode, S3ServiceError};
+pub use failover::FailoverClient;
+pub use hedge::Hedging;
+pub use pool::{ClientHealth, S3ClientPool, S3ClientPoolError};
+pub use retry::RetryBudget;
+
+use crate::config::{
+    ChecksumAlgorithm, S3ChecksumConfig, S3DebugLogConfig, S3HashingConfig, S3HedgingConfig,
+    S3PoolConfig,
+};
+use crate::error::{Categorized, ErrorCategory};
+use crate::profiling::profile_span;
 use aws_sigv4::http_request::{
     sign, SignableBody, SignableRequest, SigningParams, SigningSettings,
 };
-use aws_sigv4::sign::v4;
+use aws_sigv4::sign::{v4, v4a};
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
-use std::time::SystemTime;
+use std::time::Instant;
 use thiserror::Error;
+use tracing::Instrument;
 
 /// Characters that must be percent-encoded in S3 object keys per RFC 3986.
 /// S3 allows alphanumeric, hyphen, underscore, period, and tilde unencoded.
@@ -143,10 +182,18 @@ const S3_KEY_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
 
 /// Percent-encode an S3 object key for use in URLs.
 /// Preserves '/' for path structure, encodes all other special characters.
-fn encode_s3_key(key: &str) -> String {
+pub(crate) fn encode_s3_key(key: &str) -> String {
     utf8_percent_encode(key, S3_KEY_ENCODE_SET).to_string()
 }
 
+/// Resolve XML entities (`&amp;`, `&lt;`, ...) in a text value
+fn unescape_xml(value: String) -> String {
+    match quick_xml::escape::unescape(&value) {
+        Ok(std::borrow::Cow::Owned(unescaped)) => unescaped,
+        _ => value,
+    }
+}
+
 /// S3 client errors
 #[derive(Error, Debug)]
 pub enum S3ClientError {
@@ -156,11 +203,63 @@ pub enum S3ClientError {
     #[error("Request error: {0}")]
     RequestError(String),
 
+    #[error("Connect error: {0}")]
+    ConnectError(String),
+
+    #[error("Request timed out: {0}")]
+    Timeout(String),
+
+    #[error("Deadline of the client request exceeded")]
+    DeadlineExceeded,
+
     #[error("Response error: {0}")]
     ResponseError(String),
 
+    #[error("S3 error: {0}")]
+    ServiceError(S3ServiceError),
+
     #[error("Signing error: {0}")]
     SigningError(String),
+
+    #[error("Precondition failed: {0}")]
+    PreconditionFailed(String),
+}
+
+impl S3ClientError {
+    /// Whether the error points at an unhealthy endpoint
+    ///
+    /// Network errors, timeouts and 5xx responses are transient; client
+    /// errors (4xx), local configuration problems and a client deadline
+    /// running out are not.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            S3ClientError::RequestError(_)
+            | S3ClientError::ConnectError(_)
+            | S3ClientError::Timeout(_) => true,
+            S3ClientError::ServiceError(error) => error.is_server_error(),
+            S3ClientError::ResponseError(_)
+            | S3ClientError::ConfigError(_)
+            | S3ClientError::DeadlineExceeded
+            | S3ClientError::SigningError(_)
+            | S3ClientError::PreconditionFailed(_) => false,
+        }
+    }
+}
+
+impl Categorized for S3ClientError {
+    fn category(&self) -> ErrorCategory {
+        match self {
+            S3ClientError::ConfigError(_) | S3ClientError::SigningError(_) => ErrorCategory::Server,
+            S3ClientError::DeadlineExceeded | S3ClientError::PreconditionFailed(_) => {
+                ErrorCategory::Client
+            }
+            S3ClientError::RequestError(_)
+            | S3ClientError::ConnectError(_)
+            | S3ClientError::Timeout(_)
+            | S3ClientError::ResponseError(_)
+            | S3ClientError::ServiceError(_) => ErrorCategory::Backend,
+        }
+    }
 }
 
 /// Retry configuration for S3 operations
@@ -174,6 +273,9 @@ pub struct RetryConfig {
     pub max_backoff_ms: u64,
     /// Backoff multiplier (default: 2.0)
     pub backoff_multiplier: f64,
+    /// Retry budget in tokens; a retry spends 5 (10 after a timeout) and
+    /// a success earns 1 back (default: 500, 0 = unlimited)
+    pub budget: u32,
 }
 
 impl Default for RetryConfig {
@@ -183,17 +285,25 @@ impl Default for RetryConfig {
             initial_backoff_ms: 100,
             max_backoff_ms: 10_000,
             backoff_multiplier: 2.0,
+            budget: 500,
         }
     }
 }
 
 /// Timeout configuration for S3 operations
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct TimeoutConfig {
     /// Connection timeout in milliseconds (default: 5000ms)
     pub connect_timeout_ms: u64,
     /// Request timeout in milliseconds (default: 30000ms)
     pub request_timeout_ms: u64,
+    /// PutObject timeout in milliseconds (default: `request_timeout_ms`)
+    pub put_object_timeout_ms: Option<u64>,
+    /// UploadPart timeout in milliseconds (default: `request_timeout_ms`)
+    pub upload_part_timeout_ms: Option<u64>,
+    /// CompleteMultipartUpload timeout in milliseconds (default:
+    /// `request_timeout_ms`)
+    pub complete_timeout_ms: Option<u64>,
 }
 
 impl Default for TimeoutConfig {
@@ -201,10 +311,26 @@ impl Default for TimeoutConfig {
         Self {
             connect_timeout_ms: 5_000,
             request_timeout_ms: 30_000,
+            put_object_timeout_ms: None,
+            upload_part_timeout_ms: None,
+            complete_timeout_ms: None,
         }
     }
 }
 
+impl TimeoutConfig {
+    /// Timeout of one request of `operation`
+    fn for_operation(&self, operation: &str) -> std::time::Duration {
+        let override_ms = match operation {
+            "PutObject" => self.put_object_timeout_ms,
+            "UploadPart" => self.upload_part_timeout_ms,
+            "CompleteMultipartUpload" => self.complete_timeout_ms,
+            _ => None,
+        };
+        std::time::Duration::from_millis(override_ms.unwrap_or(self.request_timeout_ms))
+    }
+}
+
 /// S3 Client configuration
 #[derive(Debug, Clone)]
 pub struct S3ClientConfig {
@@ -217,6 +343,25 @@ pub struct S3ClientConfig {
     pub retry: Option<RetryConfig>,
     /// Timeout configuration (optional, uses defaults if not specified)
     pub timeout: Option<TimeoutConfig>,
+    /// Connection pool configuration (optional, uses defaults if not specified)
+    pub pool: Option<S3PoolConfig>,
+}
+
+/// Endpoint URL for a client config (normalized without trailing slash)
+fn endpoint_url(config: &S3ClientConfig) -> String {
+    let endpoint = config
+        .endpoint
+        .clone()
+        .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", config.region));
+    // Strip trailing slash to avoid double slashes when constructing URLs
+    endpoint.trim_end_matches('/').to_string()
+}
+
+/// PutObject body with its hex-encoded SHA256
+#[derive(Clone, Copy)]
+struct Payload<'a> {
+    body: &'a Bytes,
+    content_hash: &'a str,
 }
 
 /// S3 Client
@@ -224,6 +369,14 @@ pub struct S3Client {
     config: S3ClientConfig,
     http_client: reqwest::Client,
     retry_config: RetryConfig,
+    timeout_config: TimeoutConfig,
+    retry_budget: RetryBudget,
+    sigv4a_region_set: Option<String>,
+    hedging: Option<Hedging>,
+    hashing: S3HashingConfig,
+    checksum: ChecksumAlgorithm,
+    debug_log: Option<DebugLog>,
+    clock_skew: ClockSkew,
 }
 
 impl S3Client {
@@ -231,24 +384,95 @@ impl S3Client {
     pub fn new(config: S3ClientConfig) -> Result<Self, S3ClientError> {
         let timeout_config = config.timeout.clone().unwrap_or_default();
         let retry_config = config.retry.clone().unwrap_or_default();
+        let pool_config = config.pool.clone().unwrap_or_default();
 
-        let http_client = reqwest::Client::builder()
-            .connect_timeout(std::time::Duration::from_millis(
-                timeout_config.connect_timeout_ms,
-            ))
-            .timeout(std::time::Duration::from_millis(
-                timeout_config.request_timeout_ms,
-            ))
-            .build()
-            .map_err(|e| S3ClientError::ConfigError(e.to_string()))?;
+        // Clients for the same endpoint share one connection pool
+        let http_client =
+            pool::shared_http_client(&endpoint_url(&config), timeout_config.clone(), pool_config)?;
 
         Ok(Self {
             config,
             http_client,
+            retry_budget: RetryBudget::new(retry_config.budget),
             retry_config,
+            timeout_config,
+            sigv4a_region_set: None,
+            hedging: None,
+            hashing: S3HashingConfig::default(),
+            checksum: ChecksumAlgorithm::default(),
+            debug_log: None,
+            clock_skew: ClockSkew::default(),
         })
     }
 
+    /// Sign requests with SigV4A (ECDSA) for `region_set` instead of SigV4
+    ///
+    /// Needed for S3 Multi-Region Access Points, where the region set is
+    /// usually `*`.
+    pub fn with_sigv4a(mut self, region_set: impl Into<String>) -> Self {
+        self.sigv4a_region_set = Some(region_set.into());
+        self
+    }
+
+    /// Hedge PutObjects of small objects that outlast recent latencies
+    ///
+    /// See [`hedge`] for how the delay before the second attempt is chosen.
+    pub fn with_hedging(mut self, config: S3HedgingConfig) -> Self {
+        self.hedging = Some(Hedging::new(config));
+        self
+    }
+
+    /// Hash PutObject and UploadPart bodies as `config` says, instead of
+    /// the defaults
+    pub fn with_hashing(mut self, config: S3HashingConfig) -> Self {
+        self.hashing = config;
+        self
+    }
+
+    /// How request bodies are hashed
+    pub fn hashing(&self) -> &S3HashingConfig {
+        &self.hashing
+    }
+
+    /// Checksum multipart parts as `config` says, instead of with CRC32C
+    pub fn with_checksum(mut self, config: S3ChecksumConfig) -> Self {
+        self.checksum = config.algorithm;
+        self
+    }
+
+    /// Log every request and its response as `config` says
+    ///
+    /// See [`debug_log`] for what is logged. Fails if the log directory
+    /// cannot be created.
+    pub fn with_debug_log(mut self, config: &S3DebugLogConfig) -> Result<Self, S3ClientError> {
+        let log = DebugLog::open(&self.config.bucket, config).map_err(|e| {
+            S3ClientError::ConfigError(format!("Debug log in {}: {}", config.dir, e))
+        })?;
+        self.debug_log = Some(log);
+        Ok(self)
+    }
+
+    /// Measured offset of the endpoint's clock applied when signing
+    pub fn clock_skew(&self) -> &ClockSkew {
+        &self.clock_skew
+    }
+
+    /// Adjust the signing clock if `error_body` is a `RequestTimeTooSkewed`
+    /// error, returning whether the request should be re-signed and retried
+    fn correct_clock_skew(&self, date_header: Option<&str>, error_body: &str) -> bool {
+        let Some(offset_ms) = self.clock_skew.correct(date_header, error_body) else {
+            return false;
+        };
+        let offset_secs = offset_ms as f64 / 1000.0;
+        crate::metrics::record_clock_skew(&self.config.bucket, offset_secs);
+        tracing::warn!(
+            bucket = %self.config.bucket,
+            offset_secs = offset_secs,
+            "S3 reported RequestTimeTooSkewed, correcting signing clock"
+        );
+        true
+    }
+
     /// Check if an error is retryable
     fn is_retryable_error(status: reqwest::StatusCode) -> bool {
         // Retry on server errors and throttling
@@ -257,6 +481,19 @@ impl S3Client {
             || status == reqwest::StatusCode::REQUEST_TIMEOUT // 408
     }
 
+    /// Error for a failure response, counted by its S3 error code
+    ///
+    /// `412 Precondition Failed` (a conditional write that did not apply)
+    /// is kept apart so callers can pass it on to the client.
+    fn error_response(&self, status: reqwest::StatusCode, body: String) -> S3ClientError {
+        if status == reqwest::StatusCode::PRECONDITION_FAILED {
+            return S3ClientError::PreconditionFailed(body);
+        }
+        let error = S3ServiceError::from_response(status.as_u16(), &body);
+        crate::metrics::record_s3_error(&self.config.bucket, error.code.metric_label());
+        S3ClientError::ServiceError(error)
+    }
+
     /// Calculate backoff delay for a retry attempt
     fn calculate_backoff(&self, attempt: u32) -> std::time::Duration {
         let delay_ms = (self.retry_config.initial_backoff_ms as f64
@@ -266,12 +503,141 @@ impl S3Client {
         std::time::Duration::from_millis(delay_ms)
     }
 
+    /// Delay before retry `attempt`: a random time up to the backoff, but
+    /// at least what `Retry-After` asked for (capped at the maximum backoff)
+    fn retry_delay(
+        &self,
+        attempt: u32,
+        retry_after: Option<std::time::Duration>,
+    ) -> std::time::Duration {
+        let backoff = retry::full_jitter(self.calculate_backoff(attempt));
+        let max = std::time::Duration::from_millis(self.retry_config.max_backoff_ms);
+        match retry_after {
+            Some(retry_after) => retry_after.min(max).max(backoff),
+            None => backoff,
+        }
+    }
+
+    /// Error for a request that got no response
+    fn request_error(error: reqwest::Error) -> S3ClientError {
+        if error.is_timeout() {
+            S3ClientError::Timeout(error.to_string())
+        } else if error.is_connect() {
+            S3ClientError::ConnectError(error.to_string())
+        } else {
+            S3ClientError::RequestError(error.to_string())
+        }
+    }
+
+    /// Send the request made by `build`, retrying transient failures
+    ///
+    /// `build` is called for every attempt, so each one is signed afresh.
+    /// Network errors, throttling and server errors are retried after
+    /// [`S3Client::retry_delay`] while attempts and the retry budget last;
+    /// a `RequestTimeTooSkewed` error corrects the signing clock and is
+    /// retried at once. Returns the first successful response.
+    ///
+    /// Each attempt times out after the operation's timeout, or earlier at
+    /// the [`deadline`] of the client request; once the deadline has passed
+    /// no attempt is started and `DeadlineExceeded` is returned.
+    async fn send_with_retry<F>(
+        &self,
+        operation: &str,
+        build: F,
+    ) -> Result<reqwest::Response, S3ClientError>
+    where
+        F: Fn() -> Result<reqwest::RequestBuilder, S3ClientError>,
+    {
+        let mut attempt = 0;
+        let mut retry_cost = 0;
+        loop {
+            let operation_timeout = self.timeout_config.for_operation(operation);
+            let (timeout, at_deadline) = match deadline::remaining() {
+                Some(remaining) if remaining.is_zero() => {
+                    return Err(S3ClientError::DeadlineExceeded);
+                }
+                Some(remaining) if remaining < operation_timeout => (remaining, true),
+                _ => (operation_timeout, false),
+            };
+
+            let request = self.inject_trace_context(build()?).timeout(timeout);
+            let sent = self
+                .send(operation, request)
+                .instrument(profile_span!("s3.send", operation, attempt))
+                .await;
+            let (error, retry_after) = match sent {
+                Ok(response) if response.status().is_success() => {
+                    self.retry_budget.release(retry_cost);
+                    return Ok(response);
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let retry_after = retry::retry_after(response.headers());
+                    let date_header = response
+                        .headers()
+                        .get("Date")
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string);
+                    let error_body = response
+                        .text()
+                        .await
+                        .unwrap_or_else(|_| "Unknown error".to_string());
+
+                    if !Self::is_retryable_error(status) {
+                        // Our clock is off: re-sign with the server's time and retry
+                        if self.has_credentials()
+                            && attempt < self.retry_config.max_retries
+                            && self.correct_clock_skew(date_header.as_deref(), &error_body)
+                        {
+                            attempt += 1;
+                            continue;
+                        }
+                        return Err(self.error_response(status, error_body));
+                    }
+                    (self.error_response(status, error_body), retry_after)
+                }
+                Err(e) if e.is_timeout() && at_deadline => {
+                    return Err(S3ClientError::DeadlineExceeded);
+                }
+                Err(e) => (Self::request_error(e), None),
+            };
+
+            if attempt >= self.retry_config.max_retries {
+                return Err(error);
+            }
+            let cost = match error {
+                S3ClientError::Timeout(_) => retry::TIMEOUT_RETRY_COST,
+                _ => retry::RETRY_COST,
+            };
+            if !self.retry_budget.acquire(cost) {
+                tracing::warn!(
+                    operation = operation,
+                    error = %error,
+                    "S3 retry budget exhausted, not retrying"
+                );
+                return Err(error);
+            }
+            retry_cost = cost;
+
+            let delay = self.retry_delay(attempt, retry_after);
+            if deadline::remaining().is_some_and(|remaining| remaining <= delay) {
+                return Err(error);
+            }
+            tracing::warn!(
+                operation = operation,
+                attempt = attempt + 1,
+                delay_ms = delay.as_millis(),
+                error = %error,
+                "Transient S3 error, will retry"
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
     /// Compute SHA256 hash of body for x-amz-content-sha256 header
     fn compute_content_hash(body: &[u8]) -> String {
-        use sha2::{Digest, Sha256};
-        let mut hasher = Sha256::new();
-        hasher.update(body);
-        hex::encode(hasher.finalize())
+        hashing::sha256_hex(body)
     }
 
     /// Get the bucket name
@@ -286,13 +652,7 @@ impl S3Client {
 
     /// Get the endpoint URL (normalized without trailing slash)
     pub fn endpoint(&self) -> String {
-        let endpoint = self
-            .config
-            .endpoint
-            .clone()
-            .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", self.config.region));
-        // Strip trailing slash to avoid double slashes when constructing URLs
-        endpoint.trim_end_matches('/').to_string()
+        endpoint_url(&self.config)
     }
 
     /// Get the host from the endpoint URL
@@ -321,29 +681,64 @@ impl S3Client {
 
     /// Inject W3C Trace Context into HTTP request
     ///
-    /// Generates a traceparent header from a generated trace context.
+    /// Propagates the context of the current OpenTelemetry span (with its
+    /// `tracestate`), so the S3 request joins the caller's trace. Without an
+    /// active trace (or without the `tracing` feature) a new, unsampled trace
+    /// with random IDs is started, so S3 access logs can still be correlated.
     /// Format: 00-{trace-id}-{span-id}-{flags}
-    ///
-    /// For now, we generate a simple trace context using timestamp-based IDs.
-    /// In the future, this should extract the actual trace context from the
-    /// current OpenTelemetry span.
     fn inject_trace_context(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
-        // Generate a trace context using timestamp
-        // TODO: Extract from current OpenTelemetry span context
-        use std::time::UNIX_EPOCH;
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos();
-
-        // Generate trace_id (32 hex chars) and span_id (16 hex chars) from timestamp
-        let trace_id = format!("{:032x}", now);
-        let span_id = format!("{:016x}", now as u64);
-        let trace_flags = 0x01; // Sampled
+        #[cfg(feature = "tracing")]
+        if let Some(context) = crate::tracing::propagation::current_trace_context() {
+            let request = request.header("traceparent", context.to_traceparent());
+            return match context.tracestate {
+                Some(tracestate) => request.header("tracestate", tracestate),
+                None => request,
+            };
+        }
 
-        let traceparent = format!("00-{}-{}-{:02x}", trace_id, span_id, trace_flags);
+        let trace_id = uuid::Uuid::new_v4().simple();
+        let (span_id, _) = uuid::Uuid::new_v4().as_u64_pair();
+        request.header(
+            "traceparent",
+            format!("00-{}-{:016x}-00", trace_id, span_id),
+        )
+    }
 
-        request.header("traceparent", traceparent)
+    /// Send `request`, through the debug log if there is one
+    ///
+    /// A logged response is read whole to log its body, and handed on
+    /// rebuilt from what was read.
+    async fn send(
+        &self,
+        operation: &str,
+        request: reqwest::RequestBuilder,
+    ) -> reqwest::Result<reqwest::Response> {
+        let Some(log) = &self.debug_log else {
+            return request.send().await;
+        };
+        let request = request.build()?;
+        let id = log.request(operation, &request);
+        let result = async {
+            let response = self.http_client.execute(request).await?;
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = response.bytes().await?;
+            Ok((status, headers, body))
+        }
+        .await;
+        match result {
+            Ok((status, headers, body)) => {
+                log.response(id, status, &headers, &body);
+                let mut response = hyper::Response::new(body);
+                *response.status_mut() = status;
+                *response.headers_mut() = headers;
+                Ok(response.into())
+            }
+            Err(e) => {
+                log.error(id, &e);
+                Err(e)
+            }
+        }
     }
 
     /// Sign a request with AWS SigV4
@@ -367,8 +762,10 @@ impl S3Client {
         method: &str,
         uri: &str,
         headers: &[(String, String)],
-        body: &[u8],
+        content_hash: &str,
     ) -> Result<Vec<(String, String)>, S3ClientError> {
+        let _span = profile_span!("sign", method).entered();
+
         // Get credentials from config
         let access_key = self
             .config
@@ -400,19 +797,31 @@ impl S3Client {
         let settings = SigningSettings::default();
 
         // Create signing params
-        let signing_params = v4::SigningParams::builder()
-            .identity(&identity)
-            .region(&self.config.region)
-            .name("s3")
-            .time(SystemTime::now())
-            .settings(settings)
-            .build()
-            .map_err(|e| S3ClientError::SigningError(e.to_string()))?;
-
-        let signing_params = SigningParams::V4(signing_params);
-
-        // Create signable request
-        let signable_body = SignableBody::Bytes(body);
+        let signing_params = match &self.sigv4a_region_set {
+            Some(region_set) => SigningParams::V4a(
+                v4a::SigningParams::builder()
+                    .identity(&identity)
+                    .region_set(region_set)
+                    .name("s3")
+                    .time(self.clock_skew.now())
+                    .settings(settings)
+                    .build()
+                    .map_err(|e| S3ClientError::SigningError(e.to_string()))?,
+            ),
+            None => SigningParams::V4(
+                v4::SigningParams::builder()
+                    .identity(&identity)
+                    .region(&self.config.region)
+                    .name("s3")
+                    .time(self.clock_skew.now())
+                    .settings(settings)
+                    .build()
+                    .map_err(|e| S3ClientError::SigningError(e.to_string()))?,
+            ),
+        };
+
+        // Create signable request; the payload was hashed once by the caller
+        let signable_body = SignableBody::Precomputed(content_hash.to_string());
         let signable_request = SignableRequest::new(
             method,
             uri,
@@ -470,6 +879,7 @@ impl S3Client {
     /// #     secret_key: None,
     /// #     retry: None,
     /// #     timeout: None,
+    /// #     pool: None,
     /// # };
     /// let client = S3Client::new(config)?;
     /// let body = Bytes::from("Hello, World!");
@@ -488,9 +898,24 @@ impl S3Client {
     /// - `upload.bytes` - Size of object
     /// - `s3.etag` - ETag from response (recorded after upload)
     /// - `http.status_code` - HTTP status code (recorded after upload)
+    pub async fn put_object(
+        &self,
+        key: &str,
+        body: Bytes,
+        content_type: Option<&str>,
+    ) -> Result<S3PutObjectResponse, S3ClientError> {
+        self.put_object_with_headers(key, body, content_type, &[])
+            .await
+    }
+
+    /// Upload an object to S3 with additional request headers
+    ///
+    /// Like [`S3Client::put_object`], but also sends and signs `headers`
+    /// (lowercase names), such as `content-encoding` or `x-amz-meta-*`
+    /// user metadata.
     #[tracing::instrument(
         name = "s3.put_object",
-        skip(self, body),
+        skip(self, body, extra_headers),
         fields(
             s3.bucket = %self.config.bucket,
             s3.key = %key,
@@ -501,158 +926,177 @@ impl S3Client {
         ),
         err
     )]
-    pub async fn put_object(
+    pub async fn put_object_with_headers(
         &self,
         key: &str,
         body: Bytes,
         content_type: Option<&str>,
+        extra_headers: &[(String, String)],
+    ) -> Result<S3PutObjectResponse, S3ClientError> {
+        // Hashed once for every attempt; large bodies off the runtime workers
+        let content_hash =
+            hashing::sha256_hex_offloaded(&body, self.hashing.offload_threshold_bytes).await;
+        let payload = Payload {
+            body: &body,
+            content_hash: &content_hash,
+        };
+        match self.hedging {
+            Some(ref hedging) if hedging.applies(body.len() as u64) => {
+                self.put_object_hedged(hedging, key, payload, content_type, extra_headers)
+                    .await
+            }
+            _ => {
+                self.send_put_object(key, payload, content_type, extra_headers)
+                    .await
+            }
+        }
+    }
+
+    /// Send a PutObject, and a second one if the first is slower than the
+    /// hedging delay, returning the first success
+    ///
+    /// A failed attempt only fails the upload once the other one has failed
+    /// too; the attempt that loses is cancelled.
+    async fn put_object_hedged(
+        &self,
+        hedging: &Hedging,
+        key: &str,
+        payload: Payload<'_>,
+        content_type: Option<&str>,
+        extra_headers: &[(String, String)],
+    ) -> Result<S3PutObjectResponse, S3ClientError> {
+        let started = Instant::now();
+        let primary = self.send_put_object(key, payload, content_type, extra_headers);
+        tokio::pin!(primary);
+
+        tokio::select! {
+            result = &mut primary => {
+                if result.is_ok() {
+                    hedging.observe(started.elapsed());
+                }
+                return result;
+            }
+            _ = tokio::time::sleep(hedging.delay()) => {}
+        }
+
+        tracing::debug!(
+            elapsed_ms = started.elapsed().as_millis(),
+            "PutObject slower than hedging delay, sending hedge"
+        );
+        crate::metrics::record_s3_hedge(&self.config.bucket, "sent");
+        let hedge_started = Instant::now();
+        let hedge = self.send_put_object(key, payload, content_type, extra_headers);
+        tokio::pin!(hedge);
+
+        let (result, outcome, latency) = tokio::select! {
+            result = &mut primary => match result {
+                Ok(response) => (Ok(response), "primary_won", started.elapsed()),
+                Err(_) => (hedge.await, "hedge_won", hedge_started.elapsed()),
+            },
+            result = &mut hedge => match result {
+                Ok(response) => (Ok(response), "hedge_won", hedge_started.elapsed()),
+                Err(_) => (primary.await, "primary_won", started.elapsed()),
+            },
+        };
+        if result.is_ok() {
+            hedging.observe(latency);
+            crate::metrics::record_s3_hedge(&self.config.bucket, outcome);
+        }
+        result
+    }
+
+    /// Send a PutObject, retrying transient failures
+    async fn send_put_object(
+        &self,
+        key: &str,
+        payload: Payload<'_>,
+        content_type: Option<&str>,
+        extra_headers: &[(String, String)],
     ) -> Result<S3PutObjectResponse, S3ClientError> {
         // Build the request URL (path-style: /bucket/key)
         // Encode the key to handle special characters per RFC 3986
         let encoded_key = encode_s3_key(key);
         let url = format!("{}/{}/{}", self.endpoint(), self.config.bucket, encoded_key);
 
-        // Compute content hash for x-amz-content-sha256
-        let content_hash = Self::compute_content_hash(&body);
+        let Payload { body, content_hash } = payload;
 
         // Build headers list for signing (including content hash)
         let mut headers = vec![
             ("host".to_string(), self.get_host()),
-            ("x-amz-content-sha256".to_string(), content_hash.clone()),
+            ("x-amz-content-sha256".to_string(), content_hash.to_string()),
         ];
         if let Some(ct) = content_type {
             headers.push(("content-type".to_string(), ct.to_string()));
         }
+        headers.extend(extra_headers.iter().cloned());
 
-        // Sign the request if credentials are available
-        let signed_headers = if self.has_credentials() {
-            self.sign_request("PUT", &url, &headers, &body)?
-        } else {
-            vec![]
-        };
-
-        // Retry loop with exponential backoff
-        let mut last_error = None;
-        for attempt in 0..=self.retry_config.max_retries {
-            if attempt > 0 {
-                // Backoff before retry
-                let backoff = self.calculate_backoff(attempt - 1);
-                tracing::debug!(
-                    attempt = attempt,
-                    backoff_ms = backoff.as_millis(),
-                    "Retrying S3 PutObject after backoff"
-                );
-                tokio::time::sleep(backoff).await;
-            }
-
-            // Build the HTTP request (need to rebuild each time for retry)
-            let mut request = self.http_client.put(&url).body(body.clone());
-
-            // Add Content-Type header if provided
-            if let Some(ct) = content_type {
-                request = request.header("Content-Type", ct);
-            }
-
-            // Add x-amz-content-sha256 header
-            request = request.header("x-amz-content-sha256", &content_hash);
+        let response = self
+            .send_with_retry("PutObject", || {
+                let mut request = self.http_client.put(&url).body(body.clone());
 
-            // Add signed headers (Authorization, x-amz-date, etc.)
-            for (name, value) in &signed_headers {
-                request = request.header(name, value);
-            }
-
-            // Inject W3C Trace Context
-            request = self.inject_trace_context(request);
+                // Add Content-Type header if provided
+                if let Some(ct) = content_type {
+                    request = request.header("Content-Type", ct);
+                }
 
-            // Send the request
-            let result = request.send().await;
+                // Add x-amz-content-sha256 header
+                request = request.header("x-amz-content-sha256", content_hash);
 
-            match result {
-                Ok(response) => {
-                    let status = response.status();
+                // Add caller-supplied headers (Content-Encoding, user metadata, etc.)
+                for (name, value) in extra_headers {
+                    request = request.header(name, value);
+                }
 
-                    if status.is_success() {
-                        // Success - extract ETag and return
-                        let etag = response
-                            .headers()
-                            .get("ETag")
-                            .and_then(|v| v.to_str().ok())
-                            .ok_or_else(|| {
-                                S3ClientError::ResponseError("Missing ETag header".to_string())
-                            })?
-                            .to_string();
-
-                        // Record response attributes in span
-                        let span = tracing::Span::current();
-                        span.record("s3.etag", etag.as_str());
-                        span.record("http.status_code", status.as_u16());
-
-                        tracing::info!(
-                            etag = %etag,
-                            status = status.as_u16(),
-                            attempts = attempt + 1,
-                            "PutObject completed"
-                        );
-
-                        return Ok(S3PutObjectResponse { etag });
+                // Sign the request if credentials are available
+                if self.has_credentials() {
+                    for (name, value) in self.sign_request("PUT", &url, &headers, content_hash)? {
+                        request = request.header(name, value);
                     }
+                }
+                Ok(request)
+            })
+            .await?;
 
-                    // Check if error is retryable
-                    if Self::is_retryable_error(status) && attempt < self.retry_config.max_retries {
-                        let error_body = response
-                            .text()
-                            .await
-                            .unwrap_or_else(|_| "Unknown error".to_string());
-                        tracing::warn!(
-                            status = status.as_u16(),
-                            attempt = attempt + 1,
-                            error = %error_body,
-                            "Retryable S3 error, will retry"
-                        );
-                        last_error = Some(S3ClientError::ResponseError(format!(
-                            "HTTP {}: {}",
-                            status.as_u16(),
-                            error_body
-                        )));
-                        continue;
-                    }
+        let status = response.status();
+        let etag = response
+            .headers()
+            .get("ETag")
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| S3ClientError::ResponseError("Missing ETag header".to_string()))?
+            .to_string();
 
-                    // Non-retryable error
-                    let error_body = response
-                        .text()
-                        .await
-                        .unwrap_or_else(|_| "Unknown error".to_string());
-                    return Err(S3ClientError::ResponseError(format!(
-                        "HTTP {}: {}",
-                        status.as_u16(),
-                        error_body
-                    )));
-                }
-                Err(e) => {
-                    // Network error - these are typically retryable
-                    if attempt < self.retry_config.max_retries {
-                        tracing::warn!(
-                            attempt = attempt + 1,
-                            error = %e,
-                            "Network error, will retry"
-                        );
-                        last_error = Some(S3ClientError::RequestError(e.to_string()));
-                        continue;
-                    }
-                    return Err(S3ClientError::RequestError(e.to_string()));
-                }
-            }
-        }
+        // Record response attributes in span
+        let span = tracing::Span::current();
+        span.record("s3.etag", etag.as_str());
+        span.record("http.status_code", status.as_u16());
+
+        tracing::info!(
+            etag = %etag,
+            status = status.as_u16(),
+            "PutObject completed"
+        );
 
-        // If we get here, all retries failed
-        Err(last_error
-            .unwrap_or_else(|| S3ClientError::RequestError("All retries exhausted".to_string())))
+        let metadata = ObjectMetadata::from_headers(response.headers());
+        Ok(S3PutObjectResponse { etag, metadata })
     }
 
     /// Create a multipart upload
+    pub async fn create_multipart_upload(
+        &self,
+        key: &str,
+    ) -> Result<S3CreateMultipartUploadResponse, S3ClientError> {
+        self.create_multipart_upload_with_headers(key, &[]).await
+    }
+
+    /// Create a multipart upload with additional request headers
+    ///
+    /// `headers` set metadata of the final object, such as
+    /// `x-amz-tagging` or `content-encoding`. Unless part checksums are
+    /// disabled, the upload is created for CRC32C checksums, which
+    /// [`upload_part`](Self::upload_part) then sends with every part.
     #[tracing::instrument(
         name = "s3.create_multipart_upload",
-        skip(self),
+        skip(self, extra_headers),
         fields(
             s3.bucket = %self.config.bucket,
             s3.key = %key,
@@ -662,39 +1106,49 @@ impl S3Client {
         ),
         err
     )]
-    pub async fn create_multipart_upload(
+    pub async fn create_multipart_upload_with_headers(
         &self,
         key: &str,
+        extra_headers: &[(String, String)],
     ) -> Result<S3CreateMultipartUploadResponse, S3ClientError> {
         // Build the request URL with ?uploads query parameter (path-style: /bucket/key?uploads)
         let encoded_key = encode_s3_key(key);
-        let url = format!("{}/{}/{}?uploads", self.endpoint(), self.config.bucket, encoded_key);
+        let url = format!(
+            "{}/{}/{}?uploads",
+            self.endpoint(),
+            self.config.bucket,
+            encoded_key
+        );
 
-        // Build POST request with trace context
-        let request = self.http_client.post(&url);
-        let request = self.inject_trace_context(request);
+        let content_hash = Self::compute_content_hash(&[]);
+        let mut headers = vec![
+            ("host".to_string(), self.get_host()),
+            ("x-amz-content-sha256".to_string(), content_hash.clone()),
+        ];
+        if self.checksum == ChecksumAlgorithm::Crc32c {
+            headers.push(("x-amz-checksum-algorithm".to_string(), "CRC32C".to_string()));
+        }
+        headers.extend(extra_headers.iter().cloned());
 
         // Send POST request
-        let response = request
-            .send()
-            .await
-            .map_err(|e| S3ClientError::RequestError(e.to_string()))?;
+        let response = self
+            .send_with_retry("CreateMultipartUpload", || {
+                let mut request = self.http_client.post(&url);
+                // The headers signed, but for the host reqwest sets
+                for (name, value) in &headers[1..] {
+                    request = request.header(name, value);
+                }
+                if self.has_credentials() {
+                    for (name, value) in self.sign_request("POST", &url, &headers, &content_hash)? {
+                        request = request.header(name, value);
+                    }
+                }
+                Ok(request)
+            })
+            .await?;
 
         let status = response.status();
 
-        // Check for errors
-        if !status.is_success() {
-            let error_body = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(S3ClientError::ResponseError(format!(
-                "HTTP {}: {}",
-                status.as_u16(),
-                error_body
-            )));
-        }
-
         // Parse XML response
         let body = response
             .text()
@@ -721,6 +1175,11 @@ impl S3Client {
     }
 
     /// Upload a part in a multipart upload
+    ///
+    /// The part is sent with its CRC32C in `x-amz-checksum-crc32c` unless
+    /// part checksums are disabled, and S3 refuses it with `BadDigest` if
+    /// the body it received differs. The checksum is returned among the
+    /// response's checksums, for completing the upload.
     #[tracing::instrument(
         name = "s3.upload_part",
         skip(self, body),
@@ -754,31 +1213,41 @@ impl S3Client {
             upload_id
         );
 
-        // Build PUT request with trace context
-        let request = self.http_client.put(&url).body(body);
-        let request = self.inject_trace_context(request);
+        let crc32c = match self.checksum {
+            ChecksumAlgorithm::Crc32c => Some(checksum::crc32c_base64(&body)),
+            ChecksumAlgorithm::None => None,
+        };
+
+        // Hashed once for every attempt; large parts off the runtime workers
+        let content_hash =
+            hashing::sha256_hex_offloaded(&body, self.hashing.offload_threshold_bytes).await;
+        let mut headers = vec![
+            ("host".to_string(), self.get_host()),
+            ("x-amz-content-sha256".to_string(), content_hash.clone()),
+        ];
+        if let Some(crc32c) = &crc32c {
+            headers.push((checksum::CRC32C_HEADER.to_string(), crc32c.clone()));
+        }
 
         // Send PUT request
-        let response = request
-            .send()
-            .await
-            .map_err(|e| S3ClientError::RequestError(e.to_string()))?;
+        let response = self
+            .send_with_retry("UploadPart", || {
+                let mut request = self.http_client.put(&url).body(body.clone());
+                // The headers signed, but for the host reqwest sets
+                for (name, value) in &headers[1..] {
+                    request = request.header(name, value);
+                }
+                if self.has_credentials() {
+                    for (name, value) in self.sign_request("PUT", &url, &headers, &content_hash)? {
+                        request = request.header(name, value);
+                    }
+                }
+                Ok(request)
+            })
+            .await?;
 
         let status = response.status();
 
-        // Check for errors
-        if !status.is_success() {
-            let error_body = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(S3ClientError::ResponseError(format!(
-                "HTTP {}: {}",
-                status.as_u16(),
-                error_body
-            )));
-        }
-
         // Extract ETag from response headers
         let etag = response
             .headers()
@@ -787,6 +1256,16 @@ impl S3Client {
             .ok_or_else(|| S3ClientError::ResponseError("Missing ETag header".to_string()))?
             .to_string();
 
+        let mut metadata = ObjectMetadata::from_headers(response.headers());
+        // Backends that verified the checksum don't always echo it
+        if let Some(crc32c) = crc32c {
+            if metadata.checksum(checksum::CRC32C_HEADER).is_none() {
+                metadata
+                    .checksums
+                    .push((checksum::CRC32C_HEADER.to_string(), crc32c));
+            }
+        }
+
         // Record response attributes in span
         let span = tracing::Span::current();
         span.record("s3.etag", etag.as_str());
@@ -799,13 +1278,26 @@ impl S3Client {
             "UploadPart completed"
         );
 
-        Ok(S3UploadPartResponse { etag })
+        Ok(S3UploadPartResponse { etag, metadata })
     }
 
     /// Complete a multipart upload
+    pub async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: Vec<S3CompletedPart>,
+    ) -> Result<S3CompleteMultipartUploadResponse, S3ClientError> {
+        self.complete_multipart_upload_with_headers(key, upload_id, parts, &[])
+            .await
+    }
+
+    /// Complete a multipart upload with additional request headers
+    ///
+    /// Used for conditional completion (`if-match`, `if-none-match`).
     #[tracing::instrument(
         name = "s3.complete_multipart_upload",
-        skip(self, parts),
+        skip(self, parts, extra_headers),
         fields(
             s3.bucket = %self.config.bucket,
             s3.key = %key,
@@ -817,11 +1309,12 @@ impl S3Client {
         ),
         err
     )]
-    pub async fn complete_multipart_upload(
+    pub async fn complete_multipart_upload_with_headers(
         &self,
         key: &str,
         upload_id: &str,
         parts: Vec<S3CompletedPart>,
+        extra_headers: &[(String, String)],
     ) -> Result<S3CompleteMultipartUploadResponse, S3ClientError> {
         // Build the request URL with uploadId query parameter (path-style: /bucket/key?...)
         let encoded_key = encode_s3_key(key);
@@ -837,43 +1330,54 @@ impl S3Client {
         let mut xml_parts = String::new();
         for part in &parts {
             xml_parts.push_str(&format!(
-                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag>",
                 part.part_number, part.etag
             ));
+            if let Some(crc32c) = &part.checksum_crc32c {
+                xml_parts.push_str(&format!("<ChecksumCRC32C>{}</ChecksumCRC32C>", crc32c));
+            }
+            xml_parts.push_str("</Part>");
         }
         let xml_body = format!(
             "<CompleteMultipartUpload>{}</CompleteMultipartUpload>",
             xml_parts
         );
 
-        // Build POST request with trace context
-        let request = self
-            .http_client
-            .post(&url)
-            .body(xml_body)
-            .header("Content-Type", "application/xml");
-        let request = self.inject_trace_context(request);
+        let content_hash = Self::compute_content_hash(xml_body.as_bytes());
+        let mut headers = vec![
+            ("host".to_string(), self.get_host()),
+            ("x-amz-content-sha256".to_string(), content_hash.clone()),
+            ("content-type".to_string(), "application/xml".to_string()),
+        ];
+        headers.extend(extra_headers.iter().cloned());
 
         // Send POST request
-        let response = request
-            .send()
-            .await
-            .map_err(|e| S3ClientError::RequestError(e.to_string()))?;
+        let result = self
+            .send_with_retry("CompleteMultipartUpload", || {
+                let mut request = self.http_client.post(&url).body(xml_body.clone());
+                // The headers signed, but for the host reqwest sets
+                for (name, value) in &headers[1..] {
+                    request = request.header(name, value);
+                }
+                if self.has_credentials() {
+                    for (name, value) in self.sign_request("POST", &url, &headers, &content_hash)? {
+                        request = request.header(name, value);
+                    }
+                }
+                Ok(request)
+            })
+            .await;
+        let response = match result {
+            Ok(response) => response,
+            Err(error) if error.is_transient() || is_no_such_upload(&error) => {
+                return self.verify_completed(key, &parts, error).await;
+            }
+            Err(error) => return Err(error),
+        };
 
         let status = response.status();
 
-        // Check for errors
-        if !status.is_success() {
-            let error_body = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(S3ClientError::ResponseError(format!(
-                "HTTP {}: {}",
-                status.as_u16(),
-                error_body
-            )));
-        }
+        let mut metadata = ObjectMetadata::from_headers(response.headers());
 
         // Parse XML response
         let body = response
@@ -881,6 +1385,13 @@ impl S3Client {
             .await
             .map_err(|e| S3ClientError::ResponseError(e.to_string()))?;
 
+        // Checksums of the assembled object are reported in the body
+        for (tag, header) in CHECKSUM_XML_TAGS {
+            if let Some(value) = Self::extract_xml_tag(&body, tag) {
+                metadata.checksums.push((header.to_string(), value));
+            }
+        }
+
         // Extract ETag from XML
         let etag = Self::extract_xml_tag(&body, "ETag")
             .ok_or_else(|| S3ClientError::ResponseError("Missing ETag in response".to_string()))?;
@@ -897,7 +1408,111 @@ impl S3Client {
             "CompleteMultipartUpload completed"
         );
 
-        Ok(S3CompleteMultipartUploadResponse { etag })
+        Ok(S3CompleteMultipartUploadResponse { etag, metadata })
+    }
+
+    /// Recover from a CompleteMultipartUpload that failed after S3 may have
+    /// completed the upload
+    ///
+    /// A completion can go through on the backend while its response is
+    /// lost, and its retry then fails with `NoSuchUpload`. If the object now
+    /// exists with the composite ETag of `parts`, the upload did complete;
+    /// otherwise `error` is returned.
+    async fn verify_completed(
+        &self,
+        key: &str,
+        parts: &[S3CompletedPart],
+        error: S3ClientError,
+    ) -> Result<S3CompleteMultipartUploadResponse, S3ClientError> {
+        // Parts encrypted with SSE-KMS or SSE-C have no MD5 ETags to check
+        let Some(expected) = composite_etag(parts) else {
+            return Err(error);
+        };
+        match self.head_object(key).await {
+            Ok(Some(object)) if object.etag.trim_matches('"') == expected => {
+                tracing::warn!(
+                    etag = %object.etag,
+                    error = %error,
+                    "CompleteMultipartUpload failed but the object is complete"
+                );
+                Ok(S3CompleteMultipartUploadResponse {
+                    etag: object.etag,
+                    metadata: object.metadata,
+                })
+            }
+            Ok(_) => Err(error),
+            Err(head_error) => {
+                tracing::debug!(
+                    error = %head_error,
+                    "Could not check whether the multipart upload completed"
+                );
+                Err(error)
+            }
+        }
+    }
+
+    /// Look up an object's ETag and metadata (HeadObject)
+    ///
+    /// Returns `None` if the object does not exist.
+    #[tracing::instrument(
+        name = "s3.head_object",
+        skip(self),
+        fields(
+            s3.bucket = %self.config.bucket,
+            s3.key = %key,
+            http.method = "HEAD",
+            http.status_code = tracing::field::Empty
+        ),
+        err
+    )]
+    pub async fn head_object(
+        &self,
+        key: &str,
+    ) -> Result<Option<S3HeadObjectResponse>, S3ClientError> {
+        let encoded_key = encode_s3_key(key);
+        let url = format!("{}/{}/{}", self.endpoint(), self.config.bucket, encoded_key);
+
+        let content_hash = Self::compute_content_hash(&[]);
+        let headers = vec![
+            ("host".to_string(), self.get_host()),
+            ("x-amz-content-sha256".to_string(), content_hash.clone()),
+        ];
+
+        let result = self
+            .send_with_retry("HeadObject", || {
+                let mut request = self
+                    .http_client
+                    .head(&url)
+                    .header("x-amz-content-sha256", &content_hash);
+                if self.has_credentials() {
+                    for (name, value) in self.sign_request("HEAD", &url, &headers, &content_hash)? {
+                        request = request.header(name, value);
+                    }
+                }
+                Ok(request)
+            })
+            .await;
+        let response = match result {
+            Ok(response) => response,
+            Err(S3ClientError::ServiceError(error)) if error.status == 404 => {
+                tracing::Span::current().record("http.status_code", 404);
+                return Ok(None);
+            }
+            Err(error) => return Err(error),
+        };
+
+        let status = response.status();
+        tracing::Span::current().record("http.status_code", status.as_u16());
+
+        let etag = response
+            .headers()
+            .get("ETag")
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| S3ClientError::ResponseError("Missing ETag header".to_string()))?
+            .to_string();
+        let metadata = ObjectMetadata::from_headers(response.headers());
+
+        Ok(Some(S3HeadObjectResponse { etag, metadata }))
     }
 
     /// Abort a multipart upload
@@ -928,31 +1543,32 @@ impl S3Client {
             upload_id
         );
 
-        // Build DELETE request with trace context
-        let request = self.http_client.delete(&url);
-        let request = self.inject_trace_context(request);
+        let content_hash = Self::compute_content_hash(&[]);
+        let headers = vec![
+            ("host".to_string(), self.get_host()),
+            ("x-amz-content-sha256".to_string(), content_hash.clone()),
+        ];
 
-        // Send DELETE request
-        let response = request
-            .send()
-            .await
-            .map_err(|e| S3ClientError::RequestError(e.to_string()))?;
+        // Send DELETE request (204 No Content is success for abort)
+        let response = self
+            .send_with_retry("AbortMultipartUpload", || {
+                let mut request = self
+                    .http_client
+                    .delete(&url)
+                    .header("x-amz-content-sha256", &content_hash);
+                if self.has_credentials() {
+                    for (name, value) in
+                        self.sign_request("DELETE", &url, &headers, &content_hash)?
+                    {
+                        request = request.header(name, value);
+                    }
+                }
+                Ok(request)
+            })
+            .await?;
 
         let status = response.status();
 
-        // Check for errors (204 No Content is success for abort)
-        if !status.is_success() {
-            let error_body = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(S3ClientError::ResponseError(format!(
-                "HTTP {}: {}",
-                status.as_u16(),
-                error_body
-            )));
-        }
-
         // Record response attributes in span
         let span = tracing::Span::current();
         span.record("http.status_code", status.as_u16());
@@ -966,6 +1582,119 @@ impl S3Client {
         Ok(())
     }
 
+    /// List multipart uploads in progress in the bucket (ListMultipartUploads)
+    ///
+    /// Follows `NextKeyMarker`/`NextUploadIdMarker` until the listing is no
+    /// longer truncated, so every in-progress upload is returned.
+    #[tracing::instrument(
+        name = "s3.list_multipart_uploads",
+        skip(self),
+        fields(
+            s3.bucket = %self.config.bucket,
+            http.method = "GET",
+            s3.uploads = tracing::field::Empty,
+            http.status_code = tracing::field::Empty
+        ),
+        err
+    )]
+    pub async fn list_multipart_uploads(&self) -> Result<Vec<S3MultipartUpload>, S3ClientError> {
+        let mut uploads = Vec::new();
+        let mut markers: Option<(String, String)> = None;
+
+        let content_hash = Self::compute_content_hash(&[]);
+        let headers = vec![
+            ("host".to_string(), self.get_host()),
+            ("x-amz-content-sha256".to_string(), content_hash.clone()),
+        ];
+
+        loop {
+            // Build the request URL with ?uploads query parameter (path-style: /bucket?uploads)
+            let mut url = format!("{}/{}?uploads", self.endpoint(), self.config.bucket);
+            if let Some((ref key_marker, ref upload_id_marker)) = markers {
+                url.push_str(&format!(
+                    "&key-marker={}&upload-id-marker={}",
+                    utf8_percent_encode(key_marker, NON_ALPHANUMERIC),
+                    utf8_percent_encode(upload_id_marker, NON_ALPHANUMERIC)
+                ));
+            }
+
+            // Send GET request
+            let response = self
+                .send_with_retry("ListMultipartUploads", || {
+                    let mut request = self
+                        .http_client
+                        .get(&url)
+                        .header("x-amz-content-sha256", &content_hash);
+                    if self.has_credentials() {
+                        for (name, value) in
+                            self.sign_request("GET", &url, &headers, &content_hash)?
+                        {
+                            request = request.header(name, value);
+                        }
+                    }
+                    Ok(request)
+                })
+                .await?;
+
+            let status = response.status();
+
+            tracing::Span::current().record("http.status_code", status.as_u16());
+
+            // Parse XML response
+            let body = response
+                .text()
+                .await
+                .map_err(|e| S3ClientError::ResponseError(e.to_string()))?;
+            uploads.extend(Self::parse_multipart_uploads(&body)?);
+
+            if Self::extract_xml_tag(&body, "IsTruncated").as_deref() != Some("true") {
+                break;
+            }
+            let key_marker = Self::extract_xml_tag(&body, "NextKeyMarker");
+            let upload_id_marker = Self::extract_xml_tag(&body, "NextUploadIdMarker");
+            match (key_marker, upload_id_marker) {
+                (Some(key_marker), Some(upload_id_marker)) => {
+                    markers = Some((unescape_xml(key_marker), upload_id_marker));
+                }
+                _ => {
+                    return Err(S3ClientError::ResponseError(
+                        "Truncated listing without next markers".to_string(),
+                    ))
+                }
+            }
+        }
+
+        tracing::Span::current().record("s3.uploads", uploads.len());
+
+        tracing::info!(uploads = uploads.len(), "ListMultipartUploads completed");
+
+        Ok(uploads)
+    }
+
+    /// Parse the `<Upload>` entries of a ListMultipartUploads response
+    fn parse_multipart_uploads(xml: &str) -> Result<Vec<S3MultipartUpload>, S3ClientError> {
+        xml.split("<Upload>")
+            .skip(1)
+            .map(|entry| {
+                let entry = entry.split("</Upload>").next().unwrap_or(entry);
+                let key = Self::extract_xml_tag(entry, "Key").ok_or_else(|| {
+                    S3ClientError::ResponseError("Missing Key in upload entry".to_string())
+                })?;
+                let upload_id = Self::extract_xml_tag(entry, "UploadId").ok_or_else(|| {
+                    S3ClientError::ResponseError("Missing UploadId in upload entry".to_string())
+                })?;
+                let initiated = Self::extract_xml_tag(entry, "Initiated")
+                    .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+                    .map(|t| t.with_timezone(&Utc));
+                Ok(S3MultipartUpload {
+                    key: unescape_xml(key),
+                    upload_id,
+                    initiated,
+                })
+            })
+            .collect()
+    }
+
     /// Upload an object from a temp file (zero-copy optimized)
     ///
     /// Uses the pre-computed content hash from TempFileUpload for SigV4 signing,
@@ -999,6 +1728,7 @@ impl S3Client {
     /// #     secret_key: None,
     /// #     retry: None,
     /// #     timeout: None,
+    /// #     pool: None,
     /// # };
     /// let client = S3Client::new(config)?;
     ///
@@ -1034,17 +1764,20 @@ impl S3Client {
     ) -> Result<S3PutObjectResponse, S3ClientError> {
         use std::io::Read;
 
-        // Read file content into memory
+        // Read file content into memory (decrypting if the spool file is encrypted)
         // Note: For large files, this could be optimized with streaming
         // In REFACTOR phase, we can use reqwest's Body::wrap_stream
-        let mut file = std::fs::File::open(temp_file.path())
-            .map_err(|e| S3ClientError::RequestError(format!("Failed to open temp file: {}", e)))?;
-
-        let mut body = Vec::with_capacity(temp_file.size() as usize);
-        file.read_to_end(&mut body)
-            .map_err(|e| S3ClientError::RequestError(format!("Failed to read temp file: {}", e)))?;
-
-        let body = Bytes::from(body);
+        let body = {
+            let mut file = temp_file.reader().map_err(|e| {
+                S3ClientError::RequestError(format!("Failed to open temp file: {}", e))
+            })?;
+
+            let mut body = Vec::with_capacity(temp_file.size() as usize);
+            file.read_to_end(&mut body).map_err(|e| {
+                S3ClientError::RequestError(format!("Failed to read temp file: {}", e))
+            })?;
+            Bytes::from(body)
+        };
 
         // Use pre-computed content hash (avoids re-hashing)
         let content_hash = temp_file.content_hash().to_string();
@@ -1066,126 +1799,155 @@ impl S3Client {
             headers.push(("content-type".to_string(), ct.to_string()));
         }
 
-        // Sign the request if credentials are available
-        let signed_headers = if self.has_credentials() {
-            self.sign_request("PUT", &url, &headers, &body)?
-        } else {
-            vec![]
-        };
+        let response = self
+            .send_with_retry("PutObject", || {
+                let mut request = self.http_client.put(&url).body(body.clone());
 
-        // Retry loop with exponential backoff
-        let mut last_error = None;
-        for attempt in 0..=self.retry_config.max_retries {
-            if attempt > 0 {
-                let backoff = self.calculate_backoff(attempt - 1);
-                tracing::debug!(
-                    attempt = attempt,
-                    backoff_ms = backoff.as_millis(),
-                    "Retrying S3 PutObject from file after backoff"
-                );
-                tokio::time::sleep(backoff).await;
-            }
+                // Add Content-Type header if provided
+                if let Some(ct) = content_type {
+                    request = request.header("Content-Type", ct);
+                }
 
-            // Build the HTTP request
-            let mut request = self.http_client.put(&url).body(body.clone());
+                // Add x-amz-content-sha256 header (pre-computed)
+                request = request.header("x-amz-content-sha256", &content_hash);
 
-            // Add Content-Type header if provided
-            if let Some(ct) = content_type {
-                request = request.header("Content-Type", ct);
-            }
+                // Sign the request if credentials are available
+                if self.has_credentials() {
+                    for (name, value) in self.sign_request("PUT", &url, &headers, &content_hash)? {
+                        request = request.header(name, value);
+                    }
+                }
+                Ok(request)
+            })
+            .await?;
 
-            // Add x-amz-content-sha256 header (pre-computed)
-            request = request.header("x-amz-content-sha256", &content_hash);
+        let status = response.status();
+        let etag = response
+            .headers()
+            .get("ETag")
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| S3ClientError::ResponseError("Missing ETag header".to_string()))?
+            .to_string();
 
-            // Add signed headers
-            for (name, value) in &signed_headers {
-                request = request.header(name, value);
-            }
+        // Record response attributes in span
+        let span = tracing::Span::current();
+        span.record("s3.etag", etag.as_str());
+        span.record("http.status_code", status.as_u16());
 
-            // Inject W3C Trace Context
-            request = self.inject_trace_context(request);
+        tracing::info!(
+            etag = %etag,
+            status = status.as_u16(),
+            mode = "temp_file",
+            "PutObject from file completed"
+        );
 
-            // Send the request
-            let result = request.send().await;
+        let metadata = ObjectMetadata::from_headers(response.headers());
+        Ok(S3PutObjectResponse { etag, metadata })
+    }
+}
 
-            match result {
-                Ok(response) => {
-                    let status = response.status();
+/// Whether a request failed because the multipart upload is gone
+fn is_no_such_upload(error: &S3ClientError) -> bool {
+    matches!(
+        error,
+        S3ClientError::ServiceError(S3ServiceError {
+            code: S3ErrorCode::NoSuchUpload,
+            ..
+        })
+    )
+}
 
-                    if status.is_success() {
-                        let etag = response
-                            .headers()
-                            .get("ETag")
-                            .and_then(|v| v.to_str().ok())
-                            .ok_or_else(|| {
-                                S3ClientError::ResponseError("Missing ETag header".to_string())
-                            })?
-                            .to_string();
-
-                        // Record response attributes in span
-                        let span = tracing::Span::current();
-                        span.record("s3.etag", etag.as_str());
-                        span.record("http.status_code", status.as_u16());
-
-                        tracing::info!(
-                            etag = %etag,
-                            status = status.as_u16(),
-                            attempts = attempt + 1,
-                            mode = "temp_file",
-                            "PutObject from file completed"
-                        );
-
-                        return Ok(S3PutObjectResponse { etag });
-                    }
+/// ETag S3 gives an object assembled from `parts` (without quotes)
+///
+/// The MD5 of the parts' binary MD5s, followed by the number of parts.
+/// `None` if a part ETag is not an MD5.
+fn composite_etag(parts: &[S3CompletedPart]) -> Option<String> {
+    use md5::{Digest, Md5};
+
+    let mut digests = Vec::with_capacity(parts.len() * 16);
+    for part in parts {
+        let digest = hex::decode(part.etag.trim_matches('"')).ok()?;
+        if digest.len() != 16 {
+            return None;
+        }
+        digests.extend_from_slice(&digest);
+    }
+    Some(format!(
+        "{}-{}",
+        hex::encode(Md5::digest(&digests)),
+        parts.len()
+    ))
+}
 
-                    // Check if error is retryable
-                    if Self::is_retryable_error(status) && attempt < self.retry_config.max_retries {
-                        let error_body = response
-                            .text()
-                            .await
-                            .unwrap_or_else(|_| "Unknown error".to_string());
-                        tracing::warn!(
-                            status = status.as_u16(),
-                            attempt = attempt + 1,
-                            error = %error_body,
-                            "Retryable S3 error, will retry"
-                        );
-                        last_error = Some(S3ClientError::ResponseError(format!(
-                            "HTTP {}: {}",
-                            status.as_u16(),
-                            error_body
-                        )));
-                        continue;
-                    }
+/// Checksum elements of a CompleteMultipartUpload result and their headers
+const CHECKSUM_XML_TAGS: [(&str, &str); 6] = [
+    ("ChecksumCRC32", "x-amz-checksum-crc32"),
+    ("ChecksumCRC32C", "x-amz-checksum-crc32c"),
+    ("ChecksumCRC64NVME", "x-amz-checksum-crc64nvme"),
+    ("ChecksumSHA1", "x-amz-checksum-sha1"),
+    ("ChecksumSHA256", "x-amz-checksum-sha256"),
+    ("ChecksumType", "x-amz-checksum-type"),
+];
+
+/// Response metadata of an S3 write that clients may rely on
+///
+/// Passed through to the proxy's own responses alongside the ETag.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjectMetadata {
+    /// `x-amz-version-id` (versioned buckets only)
+    pub version_id: Option<String>,
+    /// `x-amz-request-id` of the backend request
+    pub request_id: Option<String>,
+    /// `x-amz-checksum-*` values, as (lowercase header name, value)
+    pub checksums: Vec<(String, String)>,
+}
 
-                    // Non-retryable error
-                    let error_body = response
-                        .text()
-                        .await
-                        .unwrap_or_else(|_| "Unknown error".to_string());
-                    return Err(S3ClientError::ResponseError(format!(
-                        "HTTP {}: {}",
-                        status.as_u16(),
-                        error_body
-                    )));
-                }
-                Err(e) => {
-                    if attempt < self.retry_config.max_retries {
-                        tracing::warn!(
-                            attempt = attempt + 1,
-                            error = %e,
-                            "Network error, will retry"
-                        );
-                        last_error = Some(S3ClientError::RequestError(e.to_string()));
-                        continue;
-                    }
-                    return Err(S3ClientError::RequestError(e.to_string()));
-                }
-            }
+impl ObjectMetadata {
+    /// Collect the pass-through headers of an S3 response
+    pub fn from_headers(headers: &reqwest::header::HeaderMap) -> Self {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let checksums = headers
+            .iter()
+            .filter(|(name, _)| name.as_str().starts_with("x-amz-checksum-"))
+            .filter_map(|(name, value)| {
+                Some((name.as_str().to_string(), value.to_str().ok()?.to_string()))
+            })
+            .collect();
+        Self {
+            version_id: header("x-amz-version-id"),
+            request_id: header("x-amz-request-id"),
+            checksums,
         }
+    }
 
-        Err(last_error
-            .unwrap_or_else(|| S3ClientError::RequestError("All retries exhausted".to_string())))
+    /// Value of the `x-amz-checksum-*` header `name`, if reported
+    pub fn checksum(&self, name: &str) -> Option<&str> {
+        self.checksums
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Headers to add to a client response
+    pub fn response_headers(&self) -> Vec<(&str, &str)> {
+        let mut headers = Vec::new();
+        if let Some(version_id) = &self.version_id {
+            headers.push(("x-amz-version-id", version_id.as_str()));
+        }
+        if let Some(request_id) = &self.request_id {
+            headers.push(("x-amz-request-id", request_id.as_str()));
+        }
+        headers.extend(
+            self.checksums
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        );
+        headers
     }
 }
 
@@ -1193,6 +1955,14 @@ impl S3Client {
 #[derive(Debug, Clone)]
 pub struct S3PutObjectResponse {
     pub etag: String,
+    pub metadata: ObjectMetadata,
+}
+
+/// S3 HeadObject response
+#[derive(Debug, Clone)]
+pub struct S3HeadObjectResponse {
+    pub etag: String,
+    pub metadata: ObjectMetadata,
 }
 
 /// S3 CreateMultipartUpload response
@@ -1205,19 +1975,65 @@ pub struct S3CreateMultipartUploadResponse {
 #[derive(Debug, Clone)]
 pub struct S3UploadPartResponse {
     pub etag: String,
+    pub metadata: ObjectMetadata,
 }
 
 /// S3 CompleteMultipartUpload response
 #[derive(Debug, Clone)]
 pub struct S3CompleteMultipartUploadResponse {
     pub etag: String,
+    pub metadata: ObjectMetadata,
 }
 
 /// S3 completed part
+///
+/// Built with [`new`](Self::new) or [`from_response`](Self::from_response),
+/// so fields can be added without breaking callers.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct S3CompletedPart {
     pub part_number: u32,
     pub etag: String,
+    /// Base64 CRC32C the part was uploaded with, if any
+    pub checksum_crc32c: Option<String>,
+}
+
+impl S3CompletedPart {
+    /// Part `part_number`, uploaded without a checksum
+    pub fn new(part_number: u32, etag: impl Into<String>) -> Self {
+        Self {
+            part_number,
+            etag: etag.into(),
+            checksum_crc32c: None,
+        }
+    }
+
+    /// The part, uploaded with the base64 CRC32C `checksum`
+    pub fn with_checksum_crc32c(mut self, checksum: impl Into<String>) -> Self {
+        self.checksum_crc32c = Some(checksum.into());
+        self
+    }
+
+    /// Part `part_number`, as uploaded with `response`
+    pub fn from_response(part_number: u32, response: &S3UploadPartResponse) -> Self {
+        Self {
+            part_number,
+            etag: response.etag.clone(),
+            checksum_crc32c: response
+                .metadata
+                .checksum(checksum::CRC32C_HEADER)
+                .map(str::to_string),
+        }
+    }
+}
+
+/// In-progress multipart upload from a ListMultipartUploads response
+#[derive(Debug, Clone)]
+pub struct S3MultipartUpload {
+    pub key: String,
+    pub upload_id: String,
+    /// When the upload was started, if S3 reported a parseable time
+    pub initiated: Option<DateTime<Utc>>,
 }
 
 #[cfg(test)]
@@ -1234,6 +2050,7 @@ mod tests {
             secret_key: None,
             retry: None,
             timeout: None,
+            pool: None,
         };
 
         let client = S3Client::new(config).unwrap();
@@ -1251,6 +2068,7 @@ mod tests {
             secret_key: None,
             retry: None,
             timeout: None,
+            pool: None,
         };
 
         let client = S3Client::new(config).unwrap();
@@ -1267,6 +2085,7 @@ mod tests {
             secret_key: None,
             retry: None,
             timeout: None,
+            pool: None,
         };
 
         let client = S3Client::new(config).unwrap();
@@ -1302,8 +2121,10 @@ mod tests {
                 initial_backoff_ms: 200,
                 max_backoff_ms: 20_000,
                 backoff_multiplier: 3.0,
+                budget: 500,
             }),
             timeout: None,
+            pool: None,
         };
 
         let client = S3Client::new(config).unwrap();
@@ -1366,8 +2187,10 @@ mod tests {
                 initial_backoff_ms: 100,
                 max_backoff_ms: 10_000,
                 backoff_multiplier: 2.0,
+                budget: 500,
             }),
             timeout: None,
+            pool: None,
         };
 
         let client = S3Client::new(config).unwrap();
@@ -1397,6 +2220,72 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_object_metadata_response_headers() {
+        let metadata = ObjectMetadata {
+            version_id: Some("v1".into()),
+            request_id: None,
+            checksums: vec![("x-amz-checksum-sha256".into(), "abc=".into())],
+        };
+        assert_eq!(
+            metadata.response_headers(),
+            vec![
+                ("x-amz-version-id", "v1"),
+                ("x-amz-checksum-sha256", "abc=")
+            ]
+        );
+        assert!(ObjectMetadata::default().response_headers().is_empty());
+    }
+
+    #[test]
+    fn test_parse_multipart_uploads() {
+        let xml = r#"<ListMultipartUploadsResult>
+            <Bucket>b</Bucket>
+            <IsTruncated>false</IsTruncated>
+            <Upload>
+                <Key>a &amp; b.bin</Key>
+                <UploadId>u1</UploadId>
+                <Initiated>2024-01-02T03:04:05.000Z</Initiated>
+            </Upload>
+            <Upload>
+                <Key>c.bin</Key>
+                <UploadId>u2</UploadId>
+            </Upload>
+        </ListMultipartUploadsResult>"#;
+
+        let uploads = S3Client::parse_multipart_uploads(xml).unwrap();
+        assert_eq!(uploads.len(), 2);
+        assert_eq!(uploads[0].key, "a & b.bin");
+        assert_eq!(uploads[0].upload_id, "u1");
+        assert_eq!(
+            uploads[0].initiated.unwrap().to_rfc3339(),
+            "2024-01-02T03:04:05+00:00"
+        );
+        assert_eq!(uploads[1].upload_id, "u2");
+        assert!(uploads[1].initiated.is_none());
+    }
+
+    #[test]
+    fn test_composite_etag() {
+        use md5::{Digest, Md5};
+
+        let hello = Md5::digest(b"hello ");
+        let world = Md5::digest(b"world");
+        let parts = vec![
+            S3CompletedPart::new(1, format!("\"{}\"", hex::encode(hello))),
+            S3CompletedPart::new(2, hex::encode(world)),
+        ];
+
+        let expected = format!(
+            "{}-2",
+            hex::encode(Md5::digest([hello.as_slice(), world.as_slice()].concat()))
+        );
+        assert_eq!(composite_etag(&parts), Some(expected));
+
+        let kms_part = S3CompletedPart::new(3, "\"not-an-md5\"");
+        assert_eq!(composite_etag(&[kms_part]), None);
+    }
+
     // Note: HTTP integration tests are in tests/s3_http_api_test.rs
     // These tests use wiremock to mock S3 responses
 }
@@ -4,9 +4,16 @@
 //!
 //! # Design
 //!
-//! - One AWS SDK client per configured bucket
-//! - Clients are created at pool initialization
-//! - Thread-safe access via HashMap with cloned Arc references
+//! - One S3 client per registered bucket, keyed by bucket name; buckets
+//!   sharing a path prefix for different tenants (accounts) get their own
+//! - Clients are created lazily on first use of their bucket
+//! - Buckets can be registered and removed at runtime (configuration reload)
+//! - Thread-safe access via an `RwLock`'d map handing out `Arc` clones
+//! - Per-client health: uninitialized, healthy, or unhealthy with the
+//!   creation error (retried on next use)
+//! - HTTP connections are pooled per endpoint: clients for buckets on the
+//!   same endpoint (with the same timeout and pool settings) share one
+//!   `reqwest::Client`, so 100 buckets on one MinIO use a single pool
 //!
 //! # Example
 //!
@@ -29,12 +36,92 @@
 //! # }
 //! ```
 
-use crate::config::Config;
+use crate::config::{BucketConfig, Config, S3Config, S3PoolConfig};
 use crate::s3::credentials::{CredentialsError, CredentialsProvider};
-use crate::s3::{S3Client, S3ClientConfig, S3ClientError};
+use crate::s3::dns::Resolver;
+use crate::s3::{S3Client, S3ClientConfig, S3ClientError, TimeoutConfig};
+use lazy_static::lazy_static;
+use parking_lot::{Mutex, RwLock};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
+use tracing::warn;
+
+/// Key identifying a shareable HTTP connection pool
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct HttpClientKey {
+    endpoint: String,
+    timeout: TimeoutConfig,
+    pool: S3PoolConfig,
+}
+
+lazy_static! {
+    /// HTTP clients shared by S3 clients, keyed by endpoint and settings
+    static ref SHARED_HTTP_CLIENTS: Mutex<HashMap<HttpClientKey, reqwest::Client>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Get (or create) the HTTP client for an endpoint
+///
+/// `reqwest::Client` is a handle to a connection pool, so returning a clone
+/// of a cached client shares the pool with every other S3 client on the
+/// same endpoint.
+pub(crate) fn shared_http_client(
+    endpoint: &str,
+    timeout: TimeoutConfig,
+    pool: S3PoolConfig,
+) -> Result<reqwest::Client, S3ClientError> {
+    let key = HttpClientKey {
+        endpoint: endpoint.to_string(),
+        timeout,
+        pool,
+    };
+
+    let mut clients = SHARED_HTTP_CLIENTS.lock();
+    if let Some(client) = clients.get(&key) {
+        return Ok(client.clone());
+    }
+
+    let client = build_http_client(&key)?;
+    clients.insert(key, client.clone());
+    Ok(client)
+}
+
+/// Number of distinct HTTP connection pools in use
+pub fn shared_http_client_count() -> usize {
+    SHARED_HTTP_CLIENTS.lock().len()
+}
+
+fn build_http_client(key: &HttpClientKey) -> Result<reqwest::Client, S3ClientError> {
+    let pool = &key.pool;
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(Duration::from_millis(key.timeout.connect_timeout_ms))
+        .timeout(Duration::from_millis(key.timeout.request_timeout_ms))
+        .pool_max_idle_per_host(pool.max_idle_per_host)
+        .pool_idle_timeout(Duration::from_millis(pool.idle_timeout_ms))
+        .tcp_keepalive(pool.tcp_keepalive_ms.map(Duration::from_millis))
+        .tcp_keepalive_interval(pool.tcp_keepalive_interval_ms.map(Duration::from_millis))
+        .tcp_keepalive_retries(pool.tcp_keepalive_retries)
+        .tcp_nodelay(pool.tcp_nodelay);
+
+    builder = if !pool.http2 {
+        builder.http1_only()
+    } else if key.endpoint.starts_with("http://") {
+        // No ALPN without TLS: plaintext endpoints need prior knowledge
+        builder.http2_prior_knowledge()
+    } else {
+        builder
+    };
+
+    if let Some(ref dns) = pool.dns {
+        builder = builder.dns_resolver(Arc::new(Resolver::new(dns)));
+    }
+
+    builder
+        .build()
+        .map_err(|e| S3ClientError::ConfigError(e.to_string()))
+}
 
 /// S3 Client Pool errors
 #[derive(Error, Debug)]
@@ -44,26 +131,60 @@ pub enum S3ClientPoolError {
 
     #[error("Client creation error: {0}")]
     ClientCreationError(#[from] S3ClientError),
+
+    #[error("Unknown bucket: {0}")]
+    UnknownBucket(String),
+}
+
+/// Health of a bucket's client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientHealth {
+    /// Registered; the client is created on first use
+    Uninitialized,
+    /// Client created and ready
+    Healthy,
+    /// Client creation failed; retried on next use
+    Unhealthy(String),
+}
+
+/// Registered bucket and its client, once created
+struct PoolEntry {
+    s3: S3Config,
+    client: Option<Arc<S3Client>>,
+    health: ClientHealth,
+}
+
+impl PoolEntry {
+    fn new(s3: S3Config) -> Self {
+        Self {
+            s3,
+            client: None,
+            health: ClientHealth::Uninitialized,
+        }
+    }
 }
 
 /// S3 Client Pool
 ///
-/// Manages a pool of S3 clients, one per configured bucket.
-/// Clients are created during pool initialization and reused for all requests.
+/// Manages S3 clients, one per registered bucket. Clients are created on
+/// first use and reused for all later requests; buckets can be registered
+/// and removed at runtime, e.g. on configuration reload.
 ///
 /// # Thread Safety
 ///
-/// The pool is thread-safe. Clients are wrapped in `Arc` for shared access.
+/// The pool is thread-safe. Clients are handed out as `Arc` clones, so a
+/// removed or replaced client stays usable by requests already holding it.
 pub struct S3ClientPool {
-    /// Map from bucket name to S3 client
-    clients: HashMap<String, Arc<S3Client>>,
+    /// Map from bucket name to its S3 configuration and client
+    entries: RwLock<HashMap<String, PoolEntry>>,
 }
 
 impl S3ClientPool {
     /// Create a new S3 client pool from configuration
     ///
-    /// Creates one S3 client per configured bucket. Each client is configured
-    /// with the appropriate credentials, region, and endpoint.
+    /// Registers every configured bucket. No client is created yet, so
+    /// credential or endpoint problems surface on first use of a bucket
+    /// (see [`S3ClientPool::health`]).
     ///
     /// # Arguments
     ///
@@ -71,8 +192,8 @@ impl S3ClientPool {
     ///
     /// # Returns
     ///
-    /// * `Ok(S3ClientPool)` - Pool with initialized clients
-    /// * `Err(S3ClientPoolError)` - If client creation fails
+    /// * `Ok(S3ClientPool)` - Pool with every bucket registered
+    /// * `Err(S3ClientPoolError)` - If the pool cannot be created
     ///
     /// # Example
     ///
@@ -83,42 +204,100 @@ impl S3ClientPool {
     /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
     /// let config = Config::load("config.yaml")?;
     /// let pool = S3ClientPool::new(&config).await?;
-    /// assert!(pool.client_count() >= 0);
+    /// assert_eq!(pool.client_count(), config.buckets.len());
     /// # Ok(())
     /// # }
     /// ```
     pub async fn new(config: &Config) -> Result<Self, S3ClientPoolError> {
-        let mut clients = HashMap::new();
+        let pool = Self {
+            entries: RwLock::new(HashMap::new()),
+        };
+        for bucket_config in &config.buckets {
+            pool.register(bucket_config);
+        }
+        Ok(pool)
+    }
+
+    /// Register a bucket, replacing any bucket of the same name
+    ///
+    /// A bucket re-registered with an unchanged S3 configuration keeps its
+    /// client; otherwise the client is recreated on next use.
+    pub fn register(&self, bucket_config: &BucketConfig) {
+        let mut entries = self.entries.write();
+        match entries.get(&bucket_config.name) {
+            Some(entry) if entry.s3 == bucket_config.s3 => {}
+            _ => {
+                entries.insert(
+                    bucket_config.name.clone(),
+                    PoolEntry::new(bucket_config.s3.clone()),
+                );
+            }
+        }
+    }
+
+    /// Remove a bucket, returning whether it was registered
+    pub fn remove(&self, bucket_name: &str) -> bool {
+        self.entries.write().remove(bucket_name).is_some()
+    }
 
+    /// Bring the pool in line with a reloaded configuration
+    ///
+    /// Registers every configured bucket and removes buckets no longer
+    /// configured. Clients of unchanged buckets are kept.
+    pub fn reload(&self, config: &Config) {
         for bucket_config in &config.buckets {
-            // Load credentials from config
-            let credentials = CredentialsProvider::from_config(&bucket_config.s3)?;
-
-            // Create S3 client config
-            let client_config = S3ClientConfig {
-                bucket: bucket_config.s3.bucket.clone(),
-                region: bucket_config.s3.region.clone(),
-                endpoint: bucket_config.s3.endpoint.clone(),
-                access_key: Some(credentials.access_key_id().to_string()),
-                secret_key: Some(credentials.secret_access_key().to_string()),
-                retry: None,   // Use defaults
-                timeout: None, // Use defaults
-            };
-
-            // Create client
-            let client = S3Client::new(client_config)?;
-
-            // Store in pool
-            clients.insert(bucket_config.name.clone(), Arc::new(client));
+            self.register(bucket_config);
+        }
+        self.entries
+            .write()
+            .retain(|name, _| config.buckets.iter().any(|bucket| &bucket.name == name));
+    }
+
+    /// Get the client for a bucket, creating it on first use
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Arc<S3Client>)` - The bucket's client
+    /// * `Err(S3ClientPoolError::UnknownBucket)` - If the bucket is not registered
+    /// * `Err(S3ClientPoolError)` - If client creation fails
+    pub fn client(&self, bucket_name: &str) -> Result<Arc<S3Client>, S3ClientPoolError> {
+        if let Some(client) = self
+            .entries
+            .read()
+            .get(bucket_name)
+            .and_then(|entry| entry.client.clone())
+        {
+            return Ok(client);
+        }
+
+        let mut entries = self.entries.write();
+        let entry = entries
+            .get_mut(bucket_name)
+            .ok_or_else(|| S3ClientPoolError::UnknownBucket(bucket_name.to_string()))?;
+        // Another request may have created the client while we waited
+        if let Some(ref client) = entry.client {
+            return Ok(Arc::clone(client));
         }
 
-        Ok(Self { clients })
+        match create_client(&entry.s3) {
+            Ok(client) => {
+                let client = Arc::new(client);
+                entry.client = Some(Arc::clone(&client));
+                entry.health = ClientHealth::Healthy;
+                Ok(client)
+            }
+            Err(e) => {
+                entry.health = ClientHealth::Unhealthy(e.to_string());
+                Err(e)
+            }
+        }
     }
 
     /// Get a client for a specific bucket
     ///
-    /// Returns a reference to the client for the given bucket name.
-    /// Returns `None` if no client exists for that bucket.
+    /// Returns the client for the given bucket name, creating it on first
+    /// use. Returns `None` if no bucket of that name is registered or its
+    /// client cannot be created.
     ///
     /// # Arguments
     ///
@@ -126,8 +305,8 @@ impl S3ClientPool {
     ///
     /// # Returns
     ///
-    /// * `Some(&S3Client)` - Reference to the client
-    /// * `None` - If no client exists for this bucket
+    /// * `Some(Arc<S3Client>)` - The client
+    /// * `None` - If no client is available for this bucket
     ///
     /// # Example
     ///
@@ -144,29 +323,96 @@ impl S3ClientPool {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn get_client(&self, bucket_name: &str) -> Option<&S3Client> {
-        self.clients.get(bucket_name).map(|arc| arc.as_ref())
+    pub fn get_client(&self, bucket_name: &str) -> Option<Arc<S3Client>> {
+        match self.client(bucket_name) {
+            Ok(client) => Some(client),
+            Err(S3ClientPoolError::UnknownBucket(_)) => None,
+            Err(e) => {
+                warn!(
+                    "Failed to create S3 client for bucket {}: {}",
+                    bucket_name, e
+                );
+                None
+            }
+        }
+    }
+
+    /// Health of a bucket's client, or `None` if the bucket is not registered
+    pub fn health(&self, bucket_name: &str) -> Option<ClientHealth> {
+        self.entries
+            .read()
+            .get(bucket_name)
+            .map(|entry| entry.health.clone())
     }
 
-    /// Get the number of clients in the pool
+    /// Get the number of registered buckets
     ///
     /// # Returns
     ///
-    /// The number of configured S3 clients
+    /// The number of buckets with a (possibly not yet created) S3 client
     pub fn client_count(&self) -> usize {
-        self.clients.len()
+        self.entries.read().len()
     }
 
-    /// Get all bucket names in the pool
+    /// Get the number of clients created so far
+    pub fn initialized_count(&self) -> usize {
+        self.entries
+            .read()
+            .values()
+            .filter(|entry| entry.client.is_some())
+            .count()
+    }
+
+    /// Get all registered bucket names
     ///
     /// # Returns
     ///
-    /// Iterator over bucket names
-    pub fn bucket_names(&self) -> impl Iterator<Item = &String> {
-        self.clients.keys()
+    /// Iterator over a snapshot of the bucket names
+    pub fn bucket_names(&self) -> impl Iterator<Item = String> {
+        self.entries
+            .read()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>()
+            .into_iter()
     }
 }
 
+/// Create the client for a bucket's S3 configuration
+fn create_client(s3: &S3Config) -> Result<S3Client, S3ClientPoolError> {
+    // Load credentials from config
+    let credentials = CredentialsProvider::from_config(s3)?;
+
+    let client_config = S3ClientConfig {
+        bucket: s3.bucket.clone(),
+        region: s3.region.clone(),
+        endpoint: s3.endpoint.clone(),
+        access_key: Some(credentials.access_key_id().to_string()),
+        secret_key: Some(credentials.secret_access_key().to_string()),
+        retry: None,   // Use defaults
+        timeout: None, // Use defaults
+        pool: s3.pool.clone(),
+    };
+
+    let mut client = S3Client::new(client_config)?;
+    if let Some(region_set) = &s3.sigv4a_region_set {
+        client = client.with_sigv4a(region_set);
+    }
+    if let Some(hedging) = &s3.hedging {
+        client = client.with_hedging(hedging.clone());
+    }
+    if let Some(hashing) = &s3.hashing {
+        client = client.with_hashing(hashing.clone());
+    }
+    if let Some(checksum) = s3.checksum {
+        client = client.with_checksum(checksum);
+    }
+    if let Some(debug_log) = &s3.debug_log {
+        client = client.with_debug_log(debug_log)?;
+    }
+    Ok(client)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -180,10 +426,20 @@ mod tests {
             server: ServerConfig {
                 address: "127.0.0.1:8080".to_string(),
                 zero_copy: ZeroCopyConfig::default(),
+                http: Default::default(),
+                tls: None,
+                listeners: Vec::new(),
+                tcp: Default::default(),
+                buffer_pool: Default::default(),
+                shutdown: Default::default(),
             },
             buckets,
             metrics: MetricsConfig::default(),
             tracing: None,
+            notifications: Default::default(),
+            admin: Default::default(),
+            logging: Default::default(),
+            state: Default::default(),
         }
     }
 
@@ -197,9 +453,27 @@ mod tests {
                 endpoint: Some("http://localhost:9000".to_string()),
                 access_key: Some("test-access".to_string()),
                 secret_key: Some("test-secret".to_string()),
+                pool: None,
+                sigv4a_region_set: None,
+                hedging: None,
+                hashing: None,
+                checksum: None,
+                debug_log: None,
+                access_key_file: None,
+                secret_key_file: None,
             },
             auth: AuthConfig::default(),
             upload: UploadConfig::default(),
+            failover: None,
+            replication: None,
+            storage: Default::default(),
+            authz: None,
+            authz_failure: Default::default(),
+            tenant: None,
+            hosts: Vec::new(),
+            key_prefix: None,
+            regions: None,
+            authz_shadow: None,
         }
     }
 
@@ -268,7 +542,155 @@ mod tests {
 
         let names: Vec<_> = pool.bucket_names().collect();
         assert_eq!(names.len(), 2);
-        assert!(names.contains(&&"uploads".to_string()));
-        assert!(names.contains(&&"attachments".to_string()));
+        assert!(names.contains(&"uploads".to_string()));
+        assert!(names.contains(&"attachments".to_string()));
+    }
+
+    fn pools_for(endpoint: &str) -> usize {
+        SHARED_HTTP_CLIENTS
+            .lock()
+            .keys()
+            .filter(|key| key.endpoint == endpoint)
+            .count()
+    }
+
+    fn bucket_on(name: &str, endpoint: &str, pool: Option<S3PoolConfig>) -> BucketConfig {
+        let mut bucket = create_bucket_config(name, name, "us-east-1");
+        bucket.s3.endpoint = Some(endpoint.to_string());
+        bucket.s3.pool = pool;
+        bucket
+    }
+
+    #[tokio::test]
+    async fn test_buckets_on_same_endpoint_share_http_pool() {
+        let endpoint = "http://shared-pool.test:9000";
+        let buckets = (0..100)
+            .map(|i| bucket_on(&format!("bucket-{}", i), endpoint, None))
+            .collect();
+        let pool = S3ClientPool::new(&create_test_config(buckets))
+            .await
+            .unwrap();
+        for name in pool.bucket_names() {
+            pool.client(&name).unwrap();
+        }
+
+        assert_eq!(pool.initialized_count(), 100);
+        assert_eq!(pools_for(endpoint), 1);
+    }
+
+    #[tokio::test]
+    async fn test_different_pool_settings_get_separate_http_pools() {
+        let endpoint = "http://split-pool.test:9000";
+        let http2 = S3PoolConfig {
+            http2: true,
+            ..Default::default()
+        };
+        let config = create_test_config(vec![
+            bucket_on("h1", endpoint, None),
+            bucket_on("h2", endpoint, Some(http2)),
+            bucket_on("other", "http://other-pool.test:9000", None),
+        ]);
+        let pool = S3ClientPool::new(&config).await.unwrap();
+        for name in pool.bucket_names() {
+            pool.client(&name).unwrap();
+        }
+
+        assert_eq!(pools_for(endpoint), 2);
+        assert_eq!(pools_for("http://other-pool.test:9000"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_clients_created_on_first_use() {
+        let config = create_test_config(vec![
+            create_bucket_config("uploads", "uploads-bucket", "us-east-1"),
+            create_bucket_config("attachments", "attachments-bucket", "us-west-2"),
+        ]);
+        let pool = S3ClientPool::new(&config).await.unwrap();
+        assert_eq!(pool.initialized_count(), 0);
+        assert_eq!(pool.health("uploads"), Some(ClientHealth::Uninitialized));
+
+        let first = pool.get_client("uploads").unwrap();
+        let second = pool.get_client("uploads").unwrap();
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(pool.initialized_count(), 1);
+        assert_eq!(pool.health("uploads"), Some(ClientHealth::Healthy));
+        assert_eq!(
+            pool.health("attachments"),
+            Some(ClientHealth::Uninitialized)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_creation_marks_unhealthy() {
+        let mut bucket = create_bucket_config("uploads", "my-bucket", "us-east-1");
+        bucket.s3.secret_key = None;
+        let pool = S3ClientPool::new(&create_test_config(vec![bucket.clone()]))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            pool.client("uploads"),
+            Err(S3ClientPoolError::CredentialsError(_))
+        ));
+        assert!(matches!(
+            pool.health("uploads"),
+            Some(ClientHealth::Unhealthy(_))
+        ));
+
+        // Fixed configuration is picked up on next use
+        bucket.s3.secret_key = Some("test-secret".to_string());
+        pool.register(&bucket);
+        assert!(pool.get_client("uploads").is_some());
+        assert_eq!(pool.health("uploads"), Some(ClientHealth::Healthy));
+    }
+
+    #[tokio::test]
+    async fn test_register_and_remove() {
+        let pool = S3ClientPool::new(&create_test_config(vec![]))
+            .await
+            .unwrap();
+        assert!(matches!(
+            pool.client("uploads"),
+            Err(S3ClientPoolError::UnknownBucket(_))
+        ));
+
+        pool.register(&create_bucket_config("uploads", "my-bucket", "us-east-1"));
+        assert_eq!(pool.get_client("uploads").unwrap().bucket(), "my-bucket");
+
+        assert!(pool.remove("uploads"));
+        assert!(!pool.remove("uploads"));
+        assert!(pool.get_client("uploads").is_none());
+        assert_eq!(pool.health("uploads"), None);
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_unchanged_clients() {
+        let pool = S3ClientPool::new(&create_test_config(vec![
+            create_bucket_config("uploads", "uploads-bucket", "us-east-1"),
+            create_bucket_config("attachments", "attachments-bucket", "us-west-2"),
+            create_bucket_config("legacy", "legacy-bucket", "us-east-1"),
+        ]))
+        .await
+        .unwrap();
+        let uploads = pool.get_client("uploads").unwrap();
+        pool.get_client("attachments").unwrap();
+
+        pool.reload(&create_test_config(vec![
+            create_bucket_config("uploads", "uploads-bucket", "us-east-1"),
+            create_bucket_config("attachments", "attachments-v2", "us-west-2"),
+        ]));
+
+        assert_eq!(pool.client_count(), 2);
+        assert!(Arc::ptr_eq(&uploads, &pool.get_client("uploads").unwrap()));
+        assert_eq!(
+            pool.health("attachments"),
+            Some(ClientHealth::Uninitialized)
+        );
+        assert_eq!(
+            pool.get_client("attachments").unwrap().bucket(),
+            "attachments-v2"
+        );
+        assert!(pool.get_client("legacy").is_none());
     }
 }
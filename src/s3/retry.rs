@@ -0,0 +1,169 @@
+//! Retry pacing for S3 requests
+//!
+//! Retries of transient failures wait a random time up to the exponential
+//! backoff ("full jitter"), so clients that failed together do not retry
+//! together, and at least as long as a `Retry-After` header asks. A
+//! [`RetryBudget`] caps how many retries a client makes while the backend
+//! keeps failing: each retry spends tokens, each success earns some back,
+//! and once the budget is spent failures are returned without retrying, so
+//! a backend brownout does not turn into a retry storm.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+/// Tokens a retry spends
+pub const RETRY_COST: u32 = 5;
+
+/// Tokens a retry after a timeout spends
+///
+/// Timeouts point at an overloaded backend more strongly than other errors.
+pub const TIMEOUT_RETRY_COST: u32 = 10;
+
+/// Tokens a success earns when it needed no retry
+const SUCCESS_REWARD: u32 = 1;
+
+/// Token bucket limiting retries
+#[derive(Debug)]
+pub struct RetryBudget {
+    capacity: u32,
+    tokens: AtomicU32,
+}
+
+impl RetryBudget {
+    /// Create a full budget of `capacity` tokens (0 for no limit)
+    pub fn new(capacity: u32) -> Self {
+        Self {
+            capacity,
+            tokens: AtomicU32::new(capacity),
+        }
+    }
+
+    /// Tokens left
+    pub fn available(&self) -> u32 {
+        self.tokens.load(Ordering::Relaxed)
+    }
+
+    /// Spend `cost` tokens for a retry, returning whether the retry may
+    /// go ahead
+    pub fn acquire(&self, cost: u32) -> bool {
+        if self.capacity == 0 {
+            return true;
+        }
+        self.tokens
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |tokens| {
+                tokens.checked_sub(cost)
+            })
+            .is_ok()
+    }
+
+    /// Return tokens after a success
+    ///
+    /// A success that needed retries refunds the cost of the last retry;
+    /// one that needed none (`retry_cost == 0`) earns a token.
+    pub fn release(&self, retry_cost: u32) {
+        if self.capacity == 0 {
+            return;
+        }
+        let amount = if retry_cost == 0 {
+            SUCCESS_REWARD
+        } else {
+            retry_cost
+        };
+        let _ = self
+            .tokens
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |tokens| {
+                Some(tokens.saturating_add(amount).min(self.capacity))
+            });
+    }
+}
+
+/// Random duration between zero and `backoff`
+pub fn full_jitter(backoff: Duration) -> Duration {
+    backoff.mul_f64(random_fraction())
+}
+
+/// Uniform random number in `[0, 1)`
+fn random_fraction() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Delay requested by a `Retry-After` header (seconds or an HTTP date)
+pub fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let value = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (at.with_timezone(&chrono::Utc) - chrono::Utc::now())
+        .to_std()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+
+    #[test]
+    fn test_budget_spent_and_refilled() {
+        let budget = RetryBudget::new(12);
+
+        assert!(budget.acquire(RETRY_COST));
+        assert!(budget.acquire(RETRY_COST));
+        assert!(!budget.acquire(RETRY_COST));
+        assert_eq!(budget.available(), 2);
+
+        budget.release(RETRY_COST);
+        assert_eq!(budget.available(), 7);
+        budget.release(0);
+        assert_eq!(budget.available(), 8);
+
+        budget.release(TIMEOUT_RETRY_COST);
+        assert_eq!(budget.available(), 12);
+    }
+
+    #[test]
+    fn test_unlimited_budget() {
+        let budget = RetryBudget::new(0);
+
+        for _ in 0..100 {
+            assert!(budget.acquire(TIMEOUT_RETRY_COST));
+        }
+    }
+
+    #[test]
+    fn test_full_jitter_within_backoff() {
+        let backoff = Duration::from_millis(100);
+
+        for _ in 0..100 {
+            assert!(full_jitter(backoff) <= backoff);
+        }
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("3"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(3)));
+
+        let at = chrono::Utc::now() + chrono::Duration::seconds(30);
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_str(&at.to_rfc2822()).unwrap(),
+        );
+        let delay = retry_after(&headers).unwrap();
+        assert!(delay > Duration::from_secs(25) && delay <= Duration::from_secs(30));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+        assert_eq!(retry_after(&headers), None);
+    }
+}
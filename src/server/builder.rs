@@ -0,0 +1,231 @@
+//! Programmatic server construction
+//!
+//! [`ServerBuilder`] builds a [`Server`] (or an embeddable
+//! [`UploadService`]) from a configuration assembled in code, and lets
+//! library code supply its own components per bucket:
+//!
+//! - an [`Authenticator`], tried after the bucket's configured authenticators
+//!   (and enabling authentication for the bucket)
+//! - an [`Authorizer`], used instead of the bucket's `authz` section and
+//!   still subject to its `authz_failure` and `authz_shadow` settings
+//! - a [`StorageBackend`], used instead of the one built from `s3`/`storage`
+//!
+//! Authenticators and authorizers can also be registered by name, for the
+//! configuration to refer to (`auth.custom` and `authz: {type: custom}`).
+//!
+//! # Example
+//!
+//! ```no_run
+//! use mizuchi_uploadr::auth::jwt::JwtAuthenticator;
+//! use mizuchi_uploadr::config::{BucketConfig, S3Config};
+//! use mizuchi_uploadr::server::Server;
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let server = Server::builder()
+//!     .address("0.0.0.0:8080")
+//!     .add_bucket(BucketConfig::new(
+//!         "uploads",
+//!         "/uploads",
+//!         S3Config::new("my-bucket", "us-east-1"),
+//!     ))
+//!     .with_authenticator("uploads", Arc::new(JwtAuthenticator::new_hs256("secret")))
+//!     .build()?;
+//! server.run().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use super::service::UploadService;
+use super::{Server, ServerError};
+use crate::auth::Authenticator;
+use crate::authz::Authorizer;
+use crate::config::{BucketConfig, Config, ConfigBuilder};
+use crate::upload::backend::StorageBackend;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Components supplied in code, by bucket name or by the name the
+/// configuration refers to them with
+///
+/// Applied by `ServerState::from_config_with_overrides` in place of (or, for
+/// authenticators, in addition to) those built from the configuration.
+#[derive(Clone, Default)]
+pub(crate) struct Overrides {
+    pub(crate) authenticators: HashMap<String, Vec<Arc<dyn Authenticator>>>,
+    pub(crate) authorizers: HashMap<String, Arc<dyn Authorizer>>,
+    pub(crate) backends: HashMap<String, Arc<dyn StorageBackend>>,
+    /// Authenticators by the name buckets list in `auth.custom`
+    pub(crate) registered_authenticators: HashMap<String, Arc<dyn Authenticator>>,
+    /// Authorizers by the name `type: custom` authz sections refer to
+    pub(crate) registered_authorizers: HashMap<String, Arc<dyn Authorizer>>,
+}
+
+/// Builder for [`Server`], created by [`Server::builder`]
+#[derive(Default)]
+pub struct ServerBuilder {
+    config: ConfigBuilder,
+    overrides: Overrides,
+}
+
+impl ServerBuilder {
+    /// Start from an existing configuration; later calls add to it
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = ConfigBuilder::from_config(config);
+        self
+    }
+
+    /// Set the address the server listens on
+    pub fn address(mut self, address: &str) -> Self {
+        self.config = self.config.address(address);
+        self
+    }
+
+    /// Add a bucket; buckets are matched in the order they were added
+    pub fn add_bucket(mut self, bucket: BucketConfig) -> Self {
+        self.config = self.config.add_bucket(bucket);
+        self
+    }
+
+    /// Authenticate uploads to `bucket` with `authenticator`
+    ///
+    /// It is tried after the bucket's configured authenticators, following
+    /// the bucket's `auth.chain.mode`. Authentication is enabled for the
+    /// bucket even if `auth.enabled` is false.
+    pub fn with_authenticator(
+        mut self,
+        bucket: &str,
+        authenticator: Arc<dyn Authenticator>,
+    ) -> Self {
+        self.overrides
+            .authenticators
+            .entry(bucket.to_string())
+            .or_default()
+            .push(authenticator);
+        self
+    }
+
+    /// Authorize uploads to `bucket` with `authorizer` instead of its `authz`
+    /// section
+    pub fn with_authorizer(mut self, bucket: &str, authorizer: Arc<dyn Authorizer>) -> Self {
+        self.overrides
+            .authorizers
+            .insert(bucket.to_string(), authorizer);
+        self
+    }
+
+    /// Store uploads to `bucket` with `backend` instead of the backend its
+    /// `s3` and `storage` sections describe
+    pub fn with_storage_backend(mut self, bucket: &str, backend: Arc<dyn StorageBackend>) -> Self {
+        self.overrides.backends.insert(bucket.to_string(), backend);
+        self
+    }
+
+    /// Make `authenticator` available to buckets listing `name` in
+    /// `auth.custom`
+    pub fn register_authenticator(
+        mut self,
+        name: &str,
+        authenticator: Arc<dyn Authenticator>,
+    ) -> Self {
+        self.overrides
+            .registered_authenticators
+            .insert(name.to_string(), authenticator);
+        self
+    }
+
+    /// Make `authorizer` available to `type: custom` authz sections naming it
+    pub fn register_authorizer(mut self, name: &str, authorizer: Arc<dyn Authorizer>) -> Self {
+        self.overrides
+            .registered_authorizers
+            .insert(name.to_string(), authorizer);
+        self
+    }
+
+    /// Validate the configuration and build the server
+    ///
+    /// Fails if the configuration is invalid or a component was supplied
+    /// for a bucket that is not configured.
+    pub fn build(self) -> Result<Server, ServerError> {
+        let (config, overrides) = self.finish()?;
+        Ok(Server::new(config)?.with_overrides(overrides))
+    }
+
+    /// Validate the configuration and build an embeddable service instead
+    /// of a standalone server
+    pub async fn build_service(self) -> Result<UploadService, ServerError> {
+        let (config, overrides) = self.finish()?;
+        UploadService::with_overrides(config, overrides).await
+    }
+
+    fn finish(self) -> Result<(Config, Overrides), ServerError> {
+        let mut config = self.config.build_unchecked();
+        let overrides = self.overrides;
+
+        let buckets = overrides
+            .authenticators
+            .keys()
+            .chain(overrides.authorizers.keys())
+            .chain(overrides.backends.keys());
+        for name in buckets {
+            if !config.buckets.iter().any(|b| &b.name == name) {
+                return Err(ServerError::RuntimeError(format!(
+                    "Component supplied for unknown bucket '{}'",
+                    name
+                )));
+            }
+        }
+
+        // Supplied authenticators make a bucket authenticated; enabling it
+        // here also satisfies validation of an `authz` section
+        for bucket in &mut config.buckets {
+            if overrides.authenticators.contains_key(&bucket.name) {
+                bucket.auth.enabled = true;
+            }
+        }
+
+        config
+            .validate()
+            .map_err(|e| ServerError::RuntimeError(e.to_string()))?;
+        Ok((config, overrides))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::chain::AnonymousAuthenticator;
+    use crate::config::S3Config;
+
+    fn bucket(name: &str) -> BucketConfig {
+        BucketConfig::new(name, "/uploads", S3Config::new("bucket", "us-east-1"))
+    }
+
+    #[test]
+    fn test_authenticator_enables_auth() {
+        let (config, overrides) = Server::builder()
+            .add_bucket(bucket("uploads"))
+            .with_authenticator("uploads", Arc::new(AnonymousAuthenticator))
+            .finish()
+            .unwrap();
+
+        assert!(config.buckets[0].auth.enabled);
+        assert_eq!(overrides.authenticators["uploads"].len(), 1);
+    }
+
+    #[test]
+    fn test_unknown_bucket_rejected() {
+        let result = Server::builder()
+            .add_bucket(bucket("uploads"))
+            .with_authenticator("other", Arc::new(AnonymousAuthenticator))
+            .build();
+
+        let err = result.err().unwrap();
+        assert!(err.to_string().contains("'other'"), "{err}");
+    }
+
+    #[test]
+    fn test_invalid_config_rejected() {
+        assert!(Server::builder().build().is_err());
+    }
+}
@@ -5,10 +5,27 @@
 #[cfg(feature = "tracing")]
 pub mod http_tracing;
 
+pub mod admin;
+pub mod builder;
 pub mod pingora;
+pub mod response_headers;
+pub mod service;
+pub mod shutdown;
+pub mod signing;
+mod slow_log;
+pub mod systemd;
+pub mod tcp;
+pub mod tls;
 
+pub use builder::ServerBuilder;
+
+use crate::auth::Authenticator;
+use crate::authz::Authorizer;
 use crate::config::Config;
+use crate::logging::LogFilterHandle;
+use crate::metrics::server::{MetricsServer, MetricsServerConfig};
 use std::net::SocketAddr;
+use std::sync::Arc;
 use thiserror::Error;
 use tracing::info;
 
@@ -20,29 +37,88 @@ pub enum ServerError {
 
     #[error("Server error: {0}")]
     RuntimeError(String),
+
+    #[error("TLS error: {0}")]
+    TlsError(String),
 }
 
 /// HTTP Server
 pub struct Server {
     config: Config,
-    addr: SocketAddr,
+    addrs: Vec<SocketAddr>,
+    log_filter: Option<Arc<LogFilterHandle>>,
+    overrides: builder::Overrides,
 }
 
 impl Server {
     /// Create a new server instance
     pub fn new(config: Config) -> Result<Self, ServerError> {
-        let addr: SocketAddr = config
+        let addrs = config
             .server
-            .address
-            .parse()
+            .effective_listeners()
+            .iter()
+            .map(|listener| listener.address.parse())
+            .collect::<Result<Vec<SocketAddr>, _>>()
             .map_err(|e| ServerError::BindError(format!("{}", e)))?;
 
-        Ok(Self { config, addr })
+        Ok(Self {
+            config,
+            addrs,
+            log_filter: None,
+            overrides: builder::Overrides::default(),
+        })
+    }
+
+    /// Configure a server in code, optionally with custom authenticators,
+    /// authorizers and storage backends
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::default()
+    }
+
+    /// Make `authenticator` available to buckets listing `name` in
+    /// `auth.custom`
+    ///
+    /// Buckets referring to a name that was not registered fail to start.
+    pub fn register_authenticator(
+        mut self,
+        name: &str,
+        authenticator: Arc<dyn Authenticator>,
+    ) -> Self {
+        self.overrides
+            .registered_authenticators
+            .insert(name.to_string(), authenticator);
+        self
+    }
+
+    /// Make `authorizer` available to `type: custom` authz sections naming it
+    ///
+    /// Buckets referring to a name that was not registered fail to start.
+    pub fn register_authorizer(mut self, name: &str, authorizer: Arc<dyn Authorizer>) -> Self {
+        self.overrides
+            .registered_authorizers
+            .insert(name.to_string(), authorizer);
+        self
+    }
+
+    fn with_overrides(mut self, overrides: builder::Overrides) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    /// Let the admin API change the log filter through `handle`
+    pub fn with_log_filter(mut self, handle: Arc<LogFilterHandle>) -> Self {
+        self.log_filter = Some(handle);
+        self
     }
 
     /// Run the server
+    ///
+    /// Binds every configured listener and serves them until a shutdown
+    /// signal; failing to bind any of them fails startup.
     pub async fn run(&self) -> Result<(), ServerError> {
-        info!("Starting server on {}", self.addr);
+        for addr in &self.addrs {
+            info!("Starting server on {}", addr);
+        }
         info!(
             "Zero-copy: {}",
             if self.config.server.zero_copy.enabled && crate::zero_copy_available() {
@@ -52,18 +128,71 @@ impl Server {
             }
         );
 
-        // TODO: Implement actual server logic
-        // This is a placeholder for the TDD approach
-        // RED: Tests will be written first
-        // GREEN: Implementation will follow
+        let mut server =
+            pingora::PingoraServer::with_overrides(self.config.clone(), self.overrides.clone())
+                .await?;
+        if let Some(ref log_filter) = self.log_filter {
+            server = server.with_log_filter(Arc::clone(log_filter));
+        }
+
+        // Metrics stay up while in-flight uploads drain
+        let mut metrics = if self.config.metrics.enabled {
+            Some(self.start_metrics().await?)
+        } else {
+            None
+        };
 
-        tokio::signal::ctrl_c()
-            .await
-            .map_err(|e| ServerError::RuntimeError(e.to_string()))?;
+        // Buckets' S3 clients and authenticators are set up by now
+        let addrs: Vec<String> = self
+            .config
+            .server
+            .effective_listeners()
+            .iter()
+            .filter_map(|listener| server.listener_addr(&listener.name))
+            .map(|addr| addr.to_string())
+            .collect();
+        systemd::notify(&format!("READY=1\nSTATUS=Serving on {}", addrs.join(", ")));
+        let watchdog = systemd::spawn_watchdog();
+
+        let result = server
+            .run_until(async {
+                shutdown::shutdown_signal().await;
+                systemd::notify("STOPPING=1");
+            })
+            .await;
 
-        info!("Shutting down server");
+        if let Some(watchdog) = watchdog {
+            watchdog.abort();
+        }
+
+        if let Some(ref mut metrics) = metrics {
+            metrics.shutdown().await;
+        }
+        result?;
+
+        info!("Server stopped");
         Ok(())
     }
+
+    /// Start the Prometheus metrics listener on `metrics.address:metrics.port`
+    async fn start_metrics(&self) -> Result<MetricsServer, ServerError> {
+        let address = self
+            .config
+            .metrics
+            .bind_address()
+            .map_err(|e| ServerError::BindError(e.to_string()))?;
+        let mut metrics = MetricsServer::new(MetricsServerConfig {
+            address: address.to_string(),
+        });
+        let bound = metrics.start().await.map_err(|e| {
+            ServerError::BindError(format!(
+                "Failed to bind metrics server to {}: {}",
+                address, e
+            ))
+        })?;
+        info!("Metrics server listening on {}", bound);
+        Ok(metrics)
+    }
 }
 
 #[cfg(test)]
@@ -76,6 +205,12 @@ mod tests {
             server: ServerConfig {
                 address: "127.0.0.1:0".into(),
                 zero_copy: ZeroCopyConfig::default(),
+                http: Default::default(),
+                tls: None,
+                listeners: Vec::new(),
+                tcp: Default::default(),
+                buffer_pool: Default::default(),
+                shutdown: Default::default(),
             },
             buckets: vec![BucketConfig {
                 name: "test".into(),
@@ -86,12 +221,34 @@ mod tests {
                     endpoint: None,
                     access_key: None,
                     secret_key: None,
+                    pool: None,
+                    sigv4a_region_set: None,
+                    hedging: None,
+                    hashing: None,
+                    checksum: None,
+                    debug_log: None,
+                    access_key_file: None,
+                    secret_key_file: None,
                 },
                 auth: Default::default(),
                 upload: Default::default(),
+                failover: None,
+                replication: None,
+                storage: Default::default(),
+                authz: None,
+                authz_failure: Default::default(),
+                tenant: None,
+                hosts: Vec::new(),
+                key_prefix: None,
+                regions: None,
+                authz_shadow: None,
             }],
             metrics: MetricsConfig::default(),
             tracing: None,
+            notifications: Default::default(),
+            admin: Default::default(),
+            logging: Default::default(),
+            state: Default::default(),
         }
     }
 
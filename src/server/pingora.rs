@@ -6,7 +6,8 @@
 //!
 //! The server is built on top of `hyper` and `tokio`, providing:
 //! - Async I/O for high concurrency
-//! - HTTP/1.1 support
+//! - HTTP/1.1 and HTTP/2 (ALPN over TLS, prior-knowledge h2c in plaintext)
+//! - Several listeners, each serving some or all buckets with its own TLS
 //! - Graceful shutdown
 //! - Health check endpoint
 //!
@@ -34,10 +35,20 @@
 //!     server: mizuchi_uploadr::config::ServerConfig {
 //!         address: "127.0.0.1:0".to_string(),
 //!         zero_copy: mizuchi_uploadr::config::ZeroCopyConfig::default(),
+//!         http: Default::default(),
+//!         tls: None,
+//!         listeners: Vec::new(),
+//!         tcp: Default::default(),
+//!         buffer_pool: Default::default(),
+//!         shutdown: Default::default(),
 //!     },
 //!     buckets: vec![],
 //!     metrics: mizuchi_uploadr::config::MetricsConfig::default(),
 //!     tracing: None,
+//!     notifications: Default::default(),
+//!     admin: Default::default(),
+//!     logging: Default::default(),
+//!     state: Default::default(),
 //! };
 //! let server = PingoraServer::new(config).await?;
 //! server.run().await?;
@@ -46,21 +57,74 @@
 //! ```
 //!
 
-use crate::auth::jwt::JwtAuthenticator;
-use crate::auth::{AuthError, AuthRequest, Authenticator};
-use crate::config::{BucketConfig, Config};
-use crate::s3::{S3Client, S3ClientConfig};
+use crate::auth::anonymous::{AnonymousError, AnonymousPolicy};
+use crate::auth::chain::{AuthChain, ANONYMOUS_SUBJECT};
+use crate::auth::mtls::MtlsAuthenticator;
+use crate::auth::{AuthError, AuthRequest, AuthResult, Authenticator};
+use crate::authz::cache::DecisionCache;
+use crate::authz::claims::{self, ClaimMapper};
+use crate::authz::failure::FailurePolicyAuthorizer;
+use crate::authz::health::AuthzHealth;
+use crate::authz::opa::decision_log;
+use crate::authz::shadow::ShadowAuthorizer;
+use crate::authz::{self, Authorizer, AuthzRequest, ObjectContext};
+use crate::config::{
+    Config, ContentTypeEnforcement, FlowControlConfig, HttpConfig, ListenerConfig, TcpConfig,
+};
+use crate::error::{Categorized, ErrorCategory};
+use crate::logging::LogFilterHandle;
+use crate::metrics;
+use crate::metrics::tenant::TenantLabels;
+use crate::notifications::{self, UploadEvent, WebhookNotifier, SERVER_SHUTDOWN};
+use crate::router::{self, BucketResolver};
+use crate::s3::{deadline, CircuitState, S3ErrorCode, S3ServiceError};
+use crate::server::admin::{self, AdminState};
+use crate::server::builder::Overrides;
+use crate::server::response_headers::{ResponseHeaders, UploadedObject};
+use crate::server::shutdown::{DrainTracker, ShutdownReport};
+use crate::server::signing::{ResponseSigner, SignedObject, SIGNATURE_HEADER};
+use crate::server::slow_log::RequestTimings;
+use crate::server::systemd;
+use crate::server::tcp;
+use crate::server::tls::{self, PeerCertificates};
 use crate::server::ServerError;
-use http_body_util::BodyExt;
-use hyper::server::conn::http1;
+use crate::state;
+use crate::upload::aws_chunked::{self, AwsChunkedBody, AwsChunkedError};
+use crate::upload::backend::{self, StorageBackend};
+use crate::upload::buffer_pool::BufferPool;
+use crate::upload::compression::Compressor;
+use crate::upload::content_length::{ContentLengthError, LengthCheckedBody};
+use crate::upload::content_type::{ContentTypeError, ContentTypePolicy};
+use crate::upload::flow::{FlowControl, ForwardError};
+use crate::upload::key_prefix::{KeyPrefix, KeyPrefixError};
+use crate::upload::multipart::{MAX_OBJECT_SIZE, MAX_PUT_SIZE};
+use crate::upload::progress::{ProgressBody, UploadProgress, UploadSessions, UPLOAD_ID_HEADER};
+use crate::upload::quota::{Quota, QuotaError};
+use crate::upload::receipts::{Receipt, ReceiptStore};
+use crate::upload::reconcile;
+use crate::upload::region::RegionRouter;
+use crate::upload::registry::UploadRegistry;
+use crate::upload::spill::{SpillBuffer, SpillError};
+use crate::upload::storage_class::StorageClassRouter;
+use crate::upload::transform::{Transform, TransformOutcome};
+use crate::upload::UploadError;
+use bytes::Bytes;
+use futures::StreamExt;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, LengthLimitError, Limited};
 use hyper::service::service_fn;
 use hyper::{body::Incoming, Request, Response, StatusCode};
-use hyper_util::rt::TokioIo;
-use std::collections::HashMap;
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto;
+use hyper_util::server::graceful::{GracefulShutdown, Watcher};
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::net::TcpListener;
-use tracing::{error, info, warn};
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, error, info, warn, Instrument};
 
 /// HTTP Server for Mizuchi Uploadr
 ///
@@ -69,18 +133,114 @@ use tracing::{error, info, warn};
 /// # Fields
 ///
 /// * `config` - Server configuration (shared across connections)
-/// * `listener` - TCP listener for accepting connections
-/// * `local_addr` - The actual address the server is bound to
+/// * `state` - Buckets, backends and policies shared by every request
+/// * `listeners` - Bound listeners (`server.address` or `server.listeners`)
+/// * `admin_listener` - Admin API listener (if `admin.address` is configured)
+/// * `log_filter` - Handle for changing the log filter through the admin API
+/// * `http` - HTTP/1.1 and HTTP/2 connection builder shared by all connections
 pub struct PingoraServer {
     config: Arc<Config>,
+    state: ServerState,
+    listeners: Vec<Listener>,
+    admin_listener: Option<TcpListener>,
+    log_filter: Option<Arc<LogFilterHandle>>,
+    http: Arc<auto::Builder<TokioExecutor>>,
+}
+
+/// A bound listener
+///
+/// # Fields
+///
+/// * `name` - Listener name from the configuration
+/// * `listener` - TCP listener for accepting connections
+/// * `local_addr` - The actual address the listener is bound to
+/// * `tls` - TLS acceptor (if the listener has TLS)
+/// * `tcp` - Socket options of accepted connections
+/// * `state` - Request state routing only to the buckets the listener serves
+struct Listener {
+    name: String,
     listener: TcpListener,
     local_addr: SocketAddr,
+    tls: Option<TlsAcceptor>,
+    tcp: TcpConfig,
+    state: ServerState,
+}
+
+/// State shared by every request handled by the server
+///
+/// # Fields
+///
+/// * `resolver` - Bucket lookup by longest matching path prefix
+/// * `notifier` - Webhook notifier for upload completion events (if enabled)
+/// * `drain` - Upload counters used for the shutdown report
+/// * `backends` - Storage backend per bucket name, built once at startup
+/// * `region_routers` - Regional backends per bucket name (buckets with `regions`)
+/// * `receipts` - Receipt store per bucket name (buckets with `upload.receipts`)
+/// * `auth` - Authenticator chain per bucket name (buckets with auth enabled)
+/// * `anonymous` - Anonymous upload constraints per bucket name
+/// * `claim_mappers` - Claim-based key scoping per bucket name
+/// * `key_prefixes` - Prefix jailing every key per bucket name (buckets with `key_prefix`)
+/// * `authorizers` - Authorizer per bucket name (buckets with `authz` or a supplied authorizer)
+/// * `spill` - Disk spill buffer per bucket name (buckets with `upload.spill`)
+/// * `flow_controls` - Body forwarding flow control per bucket name (buckets with
+///   `upload.flow_control`)
+/// * `content_types` - Content type policy per bucket name (buckets with `upload.content_type`)
+/// * `transforms` - WASM transform per bucket name (buckets with `upload.transform`)
+/// * `compressors` - Compression stage per bucket name (buckets with `upload.compression`)
+/// * `storage_classes` - Storage class rules per bucket name (buckets with `upload.storage_class_rules`)
+/// * `quotas` - Upload quota per bucket name (buckets with `upload.quota`)
+/// * `signers` - Response signer per bucket name (buckets with `upload.signing`)
+/// * `response_headers` - Configured response headers per bucket name (buckets with
+///   `upload.response_headers`)
+/// * `buffers` - Buffers single-PUT upload bodies are received into
+/// * `sessions` - Progress of uploads sent with an upload ID
+/// * `authz_cache` - Decision cache shared by every authorizer
+/// * `authz_health` - Health check results of every bucket's authorizer
+/// * `uploads` - Multipart uploads in progress, checked by reconciliation
+/// * `tenant_labels` - Tenant label policy of upload metrics (if `metrics.tenant_labels` is set)
+/// * `slow_request_threshold` - Duration above which requests are logged as slow (if set)
+#[derive(Clone)]
+pub(crate) struct ServerState {
+    resolver: Arc<BucketResolver>,
+    notifier: Option<Arc<WebhookNotifier>>,
+    drain: Arc<DrainTracker>,
+    backends: Arc<HashMap<String, Arc<dyn StorageBackend>>>,
+    region_routers: Arc<HashMap<String, Arc<RegionRouter>>>,
+    receipts: Arc<HashMap<String, Arc<ReceiptStore>>>,
+    auth: Arc<HashMap<String, Arc<AuthChain>>>,
+    anonymous: Arc<HashMap<String, Arc<AnonymousPolicy>>>,
+    claim_mappers: Arc<HashMap<String, Arc<ClaimMapper>>>,
+    key_prefixes: Arc<HashMap<String, Arc<KeyPrefix>>>,
+    authorizers: Arc<HashMap<String, Arc<dyn Authorizer>>>,
+    spill: Arc<HashMap<String, Arc<SpillBuffer>>>,
+    flow_controls: Arc<HashMap<String, Arc<FlowControl>>>,
+    content_types: Arc<HashMap<String, Arc<ContentTypePolicy>>>,
+    transforms: Arc<HashMap<String, Arc<Transform>>>,
+    compressors: Arc<HashMap<String, Arc<Compressor>>>,
+    signers: Arc<HashMap<String, Arc<ResponseSigner>>>,
+    response_headers: Arc<HashMap<String, Arc<ResponseHeaders>>>,
+    storage_classes: Arc<HashMap<String, Arc<StorageClassRouter>>>,
+    quotas: Arc<HashMap<String, Arc<Quota>>>,
+    buffers: Arc<BufferPool>,
+    sessions: Arc<UploadSessions>,
+    authz_cache: Arc<DecisionCache>,
+    authz_health: Arc<AuthzHealth>,
+    uploads: Arc<UploadRegistry>,
+    tenant_labels: Option<Arc<TenantLabels>>,
+    slow_request_threshold: Option<Duration>,
 }
 
+/// Request body as seen by the handlers
+///
+/// Bodies are boxed so the standalone server and embedding applications
+/// (see [`crate::server::service`]) share one handler; read errors are
+/// wrapped in `io::Error`.
+pub(crate) type RequestBody = BoxBody<Bytes, std::io::Error>;
+
 impl PingoraServer {
     /// Create a new HTTP server instance
     ///
-    /// This method binds to the configured address immediately. If port 0 is specified,
+    /// This method binds to the configured addresses immediately. If port 0 is specified,
     /// the OS will assign an available port.
     ///
     /// # Arguments
@@ -102,10 +262,20 @@ impl PingoraServer {
     ///     server: mizuchi_uploadr::config::ServerConfig {
     ///         address: "127.0.0.1:0".to_string(),
     ///         zero_copy: mizuchi_uploadr::config::ZeroCopyConfig::default(),
+    ///         http: Default::default(),
+    ///         tls: None,
+    ///         listeners: Vec::new(),
+    ///         tcp: Default::default(),
+    ///         buffer_pool: Default::default(),
+    ///         shutdown: Default::default(),
     ///     },
     ///     buckets: vec![],
     ///     metrics: mizuchi_uploadr::config::MetricsConfig::default(),
     ///     tracing: None,
+    ///     notifications: Default::default(),
+    ///     admin: Default::default(),
+    ///     logging: Default::default(),
+    ///     state: Default::default(),
     /// };
     /// let server = PingoraServer::new(config).await?;
     /// println!("Server bound to: {:?}", server.local_addr()?);
@@ -113,47 +283,152 @@ impl PingoraServer {
     /// # }
     /// ```
     pub async fn new(config: Config) -> Result<Self, ServerError> {
-        // Parse address
-        let addr: SocketAddr = config
-            .server
-            .address
-            .parse()
-            .map_err(|e| ServerError::BindError(format!("Invalid address: {}", e)))?;
-
-        // Bind to address
-        let listener = TcpListener::bind(addr)
-            .await
-            .map_err(|e| ServerError::BindError(format!("Failed to bind to {}: {}", addr, e)))?;
+        Self::with_overrides(config, Overrides::default()).await
+    }
 
-        // Get actual bound address (important for port 0)
-        let local_addr = listener
-            .local_addr()
-            .map_err(|e| ServerError::BindError(format!("Failed to get local address: {}", e)))?;
+    /// Like [`new`](Self::new), with components supplied in code
+    pub(crate) async fn with_overrides(
+        config: Config,
+        overrides: Overrides,
+    ) -> Result<Self, ServerError> {
+        // Sockets passed by systemd socket activation replace binding
+        let mut inherited = systemd::take_listeners();
+        let inherited_admin = inherited
+            .iter()
+            .position(|(name, _)| name == systemd::ADMIN_SOCKET_NAME)
+            .map(|i| inherited.remove(i).1);
+
+        let mut bound = Vec::new();
+        for listener_config in config.server.effective_listeners() {
+            // Named listeners take the socket of the same name, the default
+            // listener the first one
+            let socket = if config.server.listeners.is_empty() {
+                (!inherited.is_empty()).then(|| inherited.remove(0))
+            } else {
+                inherited
+                    .iter()
+                    .position(|(name, _)| *name == listener_config.name)
+                    .map(|i| inherited.remove(i))
+            };
+            let listener = bind(&listener_config, socket)?;
+
+            // Get actual bound address (important for port 0)
+            let local_addr = listener.local_addr().map_err(|e| {
+                ServerError::BindError(format!("Failed to get local address: {}", e))
+            })?;
+            info!(
+                "Listener '{}' bound to {}",
+                listener_config.name, local_addr
+            );
+            bound.push((listener_config, listener, local_addr));
+        }
+        for (name, _) in inherited {
+            warn!(
+                "Ignoring socket '{}' passed by systemd: no listener is named so",
+                name
+            );
+        }
+
+        let state = ServerState::from_config_with_overrides(&config, &overrides)?;
+        state.check_authz_health().await;
+
+        let mut listeners = Vec::with_capacity(bound.len());
+        for (listener_config, listener, local_addr) in bound {
+            let tls = match listener_config.tls {
+                Some(ref tls_config) => Some(tls::build_acceptor(tls_config, &config)?),
+                None => None,
+            };
+            listeners.push(Listener {
+                state: state.for_listener(&config, &listener_config),
+                tcp: listener_config.tcp.unwrap_or_default(),
+                name: listener_config.name,
+                listener,
+                local_addr,
+                tls,
+            });
+        }
+
+        // The admin API gets its own listener, off the data-plane port
+        let admin_listener = match (&config.admin.address, inherited_admin) {
+            (Some(_), Some(listener)) => {
+                let listener = from_std_listener(listener)?;
+                if let Ok(addr) = listener.local_addr() {
+                    info!("Admin API using socket {} passed by systemd", addr);
+                }
+                Some(listener)
+            }
+            (None, Some(_)) => {
+                warn!("Ignoring admin socket passed by systemd: admin.address is not set");
+                None
+            }
+            (Some(address), None) => {
+                let listener = TcpListener::bind(address).await.map_err(|e| {
+                    ServerError::BindError(format!(
+                        "Failed to bind admin API to {}: {}",
+                        address, e
+                    ))
+                })?;
+                if let Ok(addr) = listener.local_addr() {
+                    info!("Admin API bound to {}", addr);
+                }
+                Some(listener)
+            }
+            (None, None) => None,
+        };
 
-        info!("Server bound to {}", local_addr);
+        let http = Arc::new(connection_builder(&config.server.http));
 
         Ok(Self {
             config: Arc::new(config),
-            listener,
-            local_addr,
+            state,
+            listeners,
+            admin_listener,
+            log_filter: None,
+            http,
         })
     }
 
     /// Get the local address the server is bound to
     ///
     /// This is useful when binding to port 0 (OS-assigned port) to discover
-    /// which port was actually assigned.
+    /// which port was actually assigned. With several listeners, this is
+    /// the address of the first one.
     ///
     /// # Returns
     ///
     /// The socket address (IP + port) the server is listening on
     pub fn local_addr(&self) -> Result<SocketAddr, ServerError> {
-        Ok(self.local_addr)
+        self.listeners
+            .first()
+            .map(|listener| listener.local_addr)
+            .ok_or_else(|| ServerError::BindError("No listener is bound".to_string()))
+    }
+
+    /// Get the address the listener named `name` is bound to
+    pub fn listener_addr(&self, name: &str) -> Option<SocketAddr> {
+        self.listeners
+            .iter()
+            .find(|listener| listener.name == name)
+            .map(|listener| listener.local_addr)
+    }
+
+    /// Get the address the admin API is bound to, if it is enabled
+    pub fn admin_addr(&self) -> Option<SocketAddr> {
+        self.admin_listener
+            .as_ref()
+            .and_then(|listener| listener.local_addr().ok())
+    }
+
+    /// Let the admin API change the log filter through `handle`
+    pub fn with_log_filter(mut self, handle: Arc<LogFilterHandle>) -> Self {
+        self.log_filter = Some(handle);
+        self
     }
 
     /// Run the server
     ///
-    /// Accepts incoming connections and spawns a task to handle each one.
+    /// Accepts incoming connections on every listener and spawns a task to
+    /// handle each one.
     /// This method runs indefinitely until the server is shut down (e.g., via SIGTERM).
     ///
     /// # Behavior
@@ -177,10 +452,20 @@ impl PingoraServer {
     ///     server: mizuchi_uploadr::config::ServerConfig {
     ///         address: "127.0.0.1:0".to_string(),
     ///         zero_copy: mizuchi_uploadr::config::ZeroCopyConfig::default(),
+    ///         http: Default::default(),
+    ///         tls: None,
+    ///         listeners: Vec::new(),
+    ///         tcp: Default::default(),
+    ///         buffer_pool: Default::default(),
+    ///         shutdown: Default::default(),
     ///     },
     ///     buckets: vec![],
     ///     metrics: mizuchi_uploadr::config::MetricsConfig::default(),
     ///     tracing: None,
+    ///     notifications: Default::default(),
+    ///     admin: Default::default(),
+    ///     logging: Default::default(),
+    ///     state: Default::default(),
     /// };
     /// let server = PingoraServer::new(config).await?;
     ///
@@ -190,66 +475,806 @@ impl PingoraServer {
     /// # }
     /// ```
     pub async fn run(self) -> Result<(), ServerError> {
-        info!("Starting Pingora server on {}", self.local_addr);
+        self.run_until(std::future::pending()).await.map(|_| ())
+    }
+
+    /// Run the server until `shutdown` resolves, then drain
+    ///
+    /// After the shutdown future completes the listeners are closed and
+    /// in-flight requests are given up to `server.shutdown.drain_timeout_secs`
+    /// to finish. The resulting [`ShutdownReport`] is logged, sent to the
+    /// configured report webhook (if any), and returned.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mizuchi_uploadr::config::Config;
+    /// # use mizuchi_uploadr::server::pingora::PingoraServer;
+    /// # use mizuchi_uploadr::server::shutdown::shutdown_signal;
+    /// # async fn example(config: Config) -> Result<(), Box<dyn std::error::Error>> {
+    /// let server = PingoraServer::new(config).await?;
+    /// let report = server.run_until(shutdown_signal()).await?;
+    /// println!("Aborted uploads: {}", report.uploads_aborted);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn run_until<F>(mut self, shutdown: F) -> Result<ShutdownReport, ServerError>
+    where
+        F: Future<Output = ()>,
+    {
+        for listener in &self.listeners {
+            info!(
+                "Starting Pingora server on {} (listener '{}')",
+                listener.local_addr, listener.name
+            );
+        }
+
+        let graceful = GracefulShutdown::new();
+
+        let admin = self.admin_listener.take().map(|listener| {
+            tokio::spawn(admin::serve(
+                listener,
+                AdminState {
+                    config: Arc::clone(&self.config),
+                    drain: Arc::clone(&self.state.drain),
+                    backends: Arc::clone(&self.state.backends),
+                    receipts: Arc::clone(&self.state.receipts),
+                    authz_cache: Arc::clone(&self.state.authz_cache),
+                    uploads: Arc::clone(&self.state.uploads),
+                    log_filter: self.log_filter.clone(),
+                },
+            ))
+        });
+
+        // Abort orphaned multipart uploads in the background
+        let reconcilers: Vec<_> = self
+            .config
+            .buckets
+            .iter()
+            .filter_map(|bucket| {
+                let reconciliation = bucket.upload.reconciliation.as_ref()?;
+                let backend = Arc::clone(self.state.backends.get(&bucket.name)?);
+                Some(reconcile::spawn(
+                    bucket.name.clone(),
+                    backend,
+                    Arc::clone(&self.state.uploads),
+                    reconciliation,
+                ))
+            })
+            .collect();
+        let authz_health =
+            (!self.state.authz_health.is_empty()).then(|| self.state.authz_health.spawn());
+        tokio::pin!(shutdown);
+
+        // One stream of connections across all listeners, which own their
+        // socket until the stream is dropped
+        let mut served = Vec::with_capacity(self.listeners.len());
+        let mut accepting = futures::stream::select_all(
+            std::mem::take(&mut self.listeners)
+                .into_iter()
+                .enumerate()
+                .map(|(i, listener)| {
+                    served.push((listener.name, listener.tls, listener.tcp, listener.state));
+                    futures::stream::unfold(listener.listener, move |listener| async move {
+                        let conn = listener.accept().await;
+                        Some(((i, conn), listener))
+                    })
+                    .boxed()
+                }),
+        );
 
         loop {
             // Accept connection
-            let (stream, peer_addr) = match self.listener.accept().await {
+            let (i, conn) = tokio::select! {
+                Some(accepted) = accepting.next() => accepted,
+                _ = &mut shutdown => break,
+            };
+            let (ref name, ref tls, ref tcp, ref state) = served[i];
+            let (stream, peer_addr) = match conn {
                 Ok(conn) => conn,
                 Err(e) => {
-                    error!("Failed to accept connection: {}", e);
+                    error!("Failed to accept connection on listener '{}': {}", name, e);
                     continue;
                 }
             };
+            if let Err(e) = tcp::tune_stream(&stream, tcp) {
+                warn!("Failed to set socket options for {}: {}", peer_addr, e);
+            }
+
+            let state = state.clone();
+            let watcher = graceful.watcher();
+            let http = Arc::clone(&self.http);
+
+            match tls {
+                Some(ref acceptor) => {
+                    let acceptor = acceptor.clone();
+                    tokio::spawn(async move {
+                        let stream = match acceptor.accept(stream).await {
+                            Ok(stream) => stream,
+                            Err(e) => {
+                                warn!("TLS handshake with {} failed: {}", peer_addr, e);
+                                return;
+                            }
+                        };
+                        let peer_certificates = stream
+                            .get_ref()
+                            .1
+                            .peer_certificates()
+                            .map(|chain| chain.iter().map(|c| c.to_vec()).collect())
+                            .unwrap_or_default();
+                        let peer = PeerCertificates(Arc::new(peer_certificates));
+                        serve_connection(
+                            &http,
+                            TokioIo::new(stream),
+                            state,
+                            peer,
+                            watcher,
+                            peer_addr,
+                        )
+                        .await;
+                    });
+                }
+                None => {
+                    // Serve connection, tracked so it can be drained on shutdown
+                    tokio::spawn(async move {
+                        serve_connection(
+                            &http,
+                            TokioIo::new(stream),
+                            state,
+                            PeerCertificates::default(),
+                            watcher,
+                            peer_addr,
+                        )
+                        .await;
+                    });
+                }
+            }
+        }
 
-            let config = Arc::clone(&self.config);
+        // Stop accepting and drain in-flight requests
+        for task in reconcilers.into_iter().chain(admin).chain(authz_health) {
+            task.abort();
+        }
+        let started = Instant::now();
+        self.state.drain.begin_drain();
+        drop(accepting);
 
-            // Spawn task to handle connection
-            tokio::spawn(async move {
-                let io = TokioIo::new(stream);
+        let drain_timeout = Duration::from_secs(self.config.server.shutdown.drain_timeout_secs);
+        info!(
+            "Draining {} in-flight uploads (timeout {:?})",
+            self.state.drain.in_flight(),
+            drain_timeout
+        );
+        let timed_out = tokio::time::timeout(drain_timeout, graceful.shutdown())
+            .await
+            .is_err();
 
-                // Create service
-                let service = service_fn(move |req| {
-                    let config = Arc::clone(&config);
-                    async move { handle_request(req, config).await }
-                });
+        let report = self.state.drain.report(started.elapsed(), timed_out);
+        report.log();
+
+        if let Some(webhook) = &self.config.server.shutdown.report_webhook {
+            if let Err(e) = notifications::send_event(webhook, SERVER_SHUTDOWN, &report).await {
+                warn!(
+                    "Failed to deliver shutdown report to {}: {}",
+                    webhook.url, e
+                );
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// Bind a listener, or take over the socket systemd passed for it
+fn bind(
+    config: &ListenerConfig,
+    inherited: Option<(String, std::net::TcpListener)>,
+) -> Result<TcpListener, ServerError> {
+    let tcp = config.tcp.clone().unwrap_or_default();
+    if let Some((name, listener)) = inherited {
+        info!("Using socket '{}' passed by systemd", name);
+        let listener = from_std_listener(listener)?;
+        tcp::tune_listener(&listener, &tcp)
+            .map_err(|e| ServerError::BindError(format!("Failed to set socket options: {}", e)))?;
+        return Ok(listener);
+    }
+
+    let addr: SocketAddr = config
+        .address
+        .parse()
+        .map_err(|e| ServerError::BindError(format!("Invalid address: {}", e)))?;
+    tcp::bind(addr, &tcp)
+        .map_err(|e| ServerError::BindError(format!("Failed to bind to {}: {}", addr, e)))
+}
+
+impl ServerState {
+    /// State for requests on `listener`, routing only to its buckets
+    fn for_listener(&self, config: &Config, listener: &ListenerConfig) -> Self {
+        if listener.buckets.is_empty() {
+            return self.clone();
+        }
+        let mut served = config.clone();
+        served
+            .buckets
+            .retain(|bucket| listener.serves(&bucket.name));
+        Self {
+            resolver: Arc::new(BucketResolver::new(&served)),
+            ..self.clone()
+        }
+    }
+
+    /// Check every authorizer once, so misconfigured ones are logged (and
+    /// reported by `/ready`) before the first upload
+    pub(crate) async fn check_authz_health(&self) {
+        self.authz_health.check().await;
+    }
+
+    /// Build the request handling state for `config`, using the components
+    /// in `overrides` where supplied
+    ///
+    /// Starts the webhook notification worker, if notifications are enabled.
+    pub(crate) fn from_config_with_overrides(
+        config: &Config,
+        overrides: &Overrides,
+    ) -> Result<Self, ServerError> {
+        // Start the notification worker if webhooks are configured
+        let notifier = if config.notifications.enabled {
+            let notifier = WebhookNotifier::new(&config.notifications)
+                .map_err(|e| ServerError::RuntimeError(e.to_string()))?;
+            info!(
+                "Upload notifications enabled ({} webhooks)",
+                config.notifications.webhooks.len()
+            );
+            Some(Arc::new(notifier))
+        } else {
+            None
+        };
+
+        // Rate limits, replay cache and quotas are shared through Redis, if configured
+        let shared_state = state::from_config(&config.state).map_err(|e| {
+            ServerError::RuntimeError(format!("Failed to create shared state store: {}", e))
+        })?;
+
+        // Build storage backends once so clients and replication workers are shared
+        let mut backends = HashMap::new();
+        for bucket in &config.buckets {
+            let backend = match overrides.backends.get(&bucket.name) {
+                Some(backend) => Arc::clone(backend),
+                None => backend::from_bucket_config(bucket).map_err(|e| {
+                    ServerError::RuntimeError(format!(
+                        "Failed to create storage backend for bucket '{}': {}",
+                        bucket.name, e
+                    ))
+                })?,
+            };
+            backends.insert(bucket.name.clone(), backend);
+        }
+
+        // The bucket's own backend is the home region of its router
+        let mut region_routers = HashMap::new();
+        for bucket in config.buckets.iter().filter(|b| b.regions.is_some()) {
+            let home = Arc::clone(&backends[&bucket.name]);
+            let router = RegionRouter::from_bucket_config(bucket, home).map_err(|e| {
+                ServerError::RuntimeError(format!(
+                    "Failed to create region router for bucket '{}': {}",
+                    bucket.name, e
+                ))
+            })?;
+            region_routers.insert(bucket.name.clone(), Arc::new(router));
+        }
+
+        // Build authenticator chains, loading client CA bundles and CRLs for mTLS buckets
+        let mut auth = HashMap::new();
+        for bucket in config.buckets.iter().filter(|b| b.auth.enabled) {
+            let mtls = match bucket.auth.mtls {
+                Some(ref mtls_config) => {
+                    let authenticator =
+                        MtlsAuthenticator::from_config(mtls_config).map_err(|e| {
+                            ServerError::TlsError(format!(
+                                "Failed to create mTLS authenticator for bucket '{}': {}",
+                                bucket.name, e
+                            ))
+                        })?;
+                    Some(Arc::new(authenticator))
+                }
+                None => None,
+            };
+            let mut chain = AuthChain::from_config_with_state(bucket, mtls, shared_state.as_ref())
+                .map_err(|e| {
+                    ServerError::RuntimeError(format!(
+                        "Failed to create authenticators for bucket '{}': {}",
+                        bucket.name, e
+                    ))
+                })?;
+            for name in &bucket.auth.custom {
+                let authenticator =
+                    overrides
+                        .registered_authenticators
+                        .get(name)
+                        .ok_or_else(|| {
+                            ServerError::RuntimeError(format!(
+                                "Authenticator '{}' of bucket '{}' is not registered",
+                                name, bucket.name
+                            ))
+                        })?;
+                chain = chain.with(name, Arc::clone(authenticator));
+            }
+            for authenticator in overrides
+                .authenticators
+                .get(&bucket.name)
+                .into_iter()
+                .flatten()
+            {
+                chain = chain.with("custom", Arc::clone(authenticator));
+            }
+            // Buckets that only allow anonymous uploads have nothing to chain
+            if !chain.is_empty() || bucket.auth.allow_anonymous.is_none() {
+                auth.insert(bucket.name.clone(), Arc::new(chain));
+            }
+        }
 
-                // Serve connection
-                if let Err(e) = http1::Builder::new().serve_connection(io, service).await {
-                    error!("Error serving connection from {}: {}", peer_addr, e);
+        let anonymous = config
+            .buckets
+            .iter()
+            .filter_map(|bucket| {
+                let mut policy = AnonymousPolicy::new(bucket.auth.allow_anonymous.as_ref()?);
+                if let Some(ref store) = shared_state {
+                    policy = policy.with_store(Arc::clone(store), &bucket.name);
                 }
+                Some((bucket.name.clone(), Arc::new(policy)))
+            })
+            .collect();
+
+        let mut claim_mappers = HashMap::new();
+        for bucket in config.buckets.iter().filter(|b| b.auth.enabled) {
+            if let Some(ref mapping) = bucket.auth.claim_mapping {
+                let mapper = ClaimMapper::new(mapping).map_err(|e| {
+                    ServerError::RuntimeError(format!(
+                        "Failed to create claim mapping for bucket '{}': {}",
+                        bucket.name, e
+                    ))
+                })?;
+                claim_mappers.insert(bucket.name.clone(), Arc::new(mapper));
+            }
+        }
+
+        let mut key_prefixes = HashMap::new();
+        for bucket in &config.buckets {
+            if let Some(ref template) = bucket.key_prefix {
+                let prefix = KeyPrefix::new(template).map_err(|e| {
+                    ServerError::RuntimeError(format!(
+                        "Failed to create key prefix for bucket '{}': {}",
+                        bucket.name, e
+                    ))
+                })?;
+                key_prefixes.insert(bucket.name.clone(), Arc::new(prefix));
+            }
+        }
+
+        let authz_cache = Arc::new(DecisionCache::default());
+        let mut authorizers = HashMap::new();
+        for bucket in &config.buckets {
+            let authorizer = match (overrides.authorizers.get(&bucket.name), &bucket.authz) {
+                (Some(authorizer), _) => Some(Arc::clone(authorizer)),
+                (None, Some(authz_config)) => Some(
+                    authz::from_config_with_registry(
+                        authz_config,
+                        &authz_cache,
+                        &overrides.registered_authorizers,
+                    )
+                    .map_err(|e| {
+                        ServerError::RuntimeError(format!(
+                            "Failed to create authorizer for bucket '{}': {}",
+                            bucket.name, e
+                        ))
+                    })?,
+                ),
+                (None, None) => None,
+            };
+            if let Some(authorizer) = authorizer {
+                let authorizer = Arc::new(FailurePolicyAuthorizer::new(
+                    authorizer,
+                    &bucket.name,
+                    &bucket.authz_failure,
+                    Arc::clone(&authz_cache),
+                )) as Arc<dyn Authorizer>;
+                let authorizer = match bucket.authz_shadow {
+                    Some(ref shadow_config) => {
+                        let shadow = authz::from_config_with_registry(
+                            shadow_config,
+                            &authz_cache,
+                            &overrides.registered_authorizers,
+                        )
+                        .map_err(|e| {
+                            ServerError::RuntimeError(format!(
+                                "Failed to create shadow authorizer for bucket '{}': {}",
+                                bucket.name, e
+                            ))
+                        })?;
+                        Arc::new(ShadowAuthorizer::new(authorizer, shadow, &bucket.name))
+                    }
+                    None => authorizer,
+                };
+                authorizers.insert(bucket.name.clone(), authorizer);
+            }
+        }
+
+        let authz_health = config
+            .buckets
+            .iter()
+            .filter_map(|bucket| Some((bucket, authorizers.get(&bucket.name)?)))
+            .fold(AuthzHealth::new(), |health, (bucket, authorizer)| {
+                health.with(
+                    &bucket.name,
+                    Arc::clone(authorizer),
+                    bucket.authz_failure.mode,
+                )
             });
+
+        // Spill buffers delete files left behind by a previous crash
+        let mut spill = HashMap::new();
+        for bucket in &config.buckets {
+            if let Some(ref spill_config) = bucket.upload.spill {
+                let buffer = SpillBuffer::new(&bucket.name, spill_config).map_err(|e| {
+                    ServerError::RuntimeError(format!(
+                        "Failed to create spill buffer for bucket '{}': {}",
+                        bucket.name, e
+                    ))
+                })?;
+                spill.insert(bucket.name.clone(), Arc::new(buffer));
+            }
+        }
+
+        let flow_controls = config
+            .buckets
+            .iter()
+            .filter_map(|bucket| {
+                let flow = FlowControl::new(&bucket.name, bucket.upload.flow_control.as_ref()?);
+                Some((bucket.name.clone(), Arc::new(flow)))
+            })
+            .collect();
+
+        let content_types = config
+            .buckets
+            .iter()
+            .filter_map(|bucket| {
+                let policy = ContentTypePolicy::new(bucket.upload.content_type.as_ref()?);
+                Some((bucket.name.clone(), Arc::new(policy)))
+            })
+            .collect();
+
+        // Transform plugins are compiled once and instantiated per upload
+        let mut transforms = HashMap::new();
+        for bucket in &config.buckets {
+            if let Some(ref transform_config) = bucket.upload.transform {
+                let transform = Transform::from_config(transform_config).map_err(|e| {
+                    ServerError::RuntimeError(format!(
+                        "Failed to load transform for bucket '{}': {}",
+                        bucket.name, e
+                    ))
+                })?;
+                transforms.insert(bucket.name.clone(), Arc::new(transform));
+            }
+        }
+
+        let compressors = config
+            .buckets
+            .iter()
+            .filter_map(|bucket| {
+                let compressor = Compressor::new(bucket.upload.compression.as_ref()?);
+                Some((bucket.name.clone(), Arc::new(compressor)))
+            })
+            .collect();
+
+        let storage_classes = config
+            .buckets
+            .iter()
+            .filter(|bucket| !bucket.upload.storage_class_rules.is_empty())
+            .map(|bucket| {
+                let router = StorageClassRouter::new(&bucket.upload.storage_class_rules);
+                (bucket.name.clone(), Arc::new(router))
+            })
+            .collect();
+
+        let mut signers = HashMap::new();
+        for bucket in &config.buckets {
+            if let Some(ref signing) = bucket.upload.signing {
+                let signer = ResponseSigner::new(signing).map_err(|e| {
+                    ServerError::RuntimeError(format!(
+                        "Failed to create response signer for bucket '{}': {}",
+                        bucket.name, e
+                    ))
+                })?;
+                signers.insert(bucket.name.clone(), Arc::new(signer));
+            }
+        }
+
+        let mut response_headers = HashMap::new();
+        for bucket in &config.buckets {
+            if bucket.upload.response_headers.is_empty() {
+                continue;
+            }
+            let headers = ResponseHeaders::new(&bucket.upload.response_headers).map_err(|e| {
+                ServerError::RuntimeError(format!(
+                    "Failed to create response headers for bucket '{}': {}",
+                    bucket.name, e
+                ))
+            })?;
+            response_headers.insert(bucket.name.clone(), Arc::new(headers));
+        }
+
+        // Quotas pick up the counts persisted by a previous run
+        let mut quotas = HashMap::new();
+        for bucket in &config.buckets {
+            if let Some(ref quota_config) = bucket.upload.quota {
+                let quota = Quota::from_config_with_state(
+                    &bucket.name,
+                    quota_config,
+                    shared_state.as_ref(),
+                )
+                .map_err(|e| {
+                    ServerError::RuntimeError(format!(
+                        "Failed to create quota for bucket '{}': {}",
+                        bucket.name, e
+                    ))
+                })?;
+                quotas.insert(bucket.name.clone(), Arc::new(quota));
+            }
+        }
+
+        // Buckets recording to the same file share its store
+        let mut receipt_files: HashMap<&str, Arc<ReceiptStore>> = HashMap::new();
+        let mut receipts = HashMap::new();
+        for bucket in &config.buckets {
+            let Some(ref receipt_config) = bucket.upload.receipts else {
+                continue;
+            };
+            let store = match receipt_files.get(receipt_config.path.as_str()) {
+                Some(store) => Arc::clone(store),
+                None => {
+                    let store =
+                        ReceiptStore::open(&receipt_config.path, receipt_config.retention_days)
+                            .map_err(|e| {
+                                ServerError::RuntimeError(format!(
+                                    "Failed to open receipt store for bucket '{}': {}",
+                                    bucket.name, e
+                                ))
+                            })?;
+                    let store = Arc::new(store);
+                    receipt_files.insert(&receipt_config.path, Arc::clone(&store));
+                    store
+                }
+            };
+            receipts.insert(bucket.name.clone(), store);
         }
+
+        Ok(Self {
+            resolver: Arc::new(BucketResolver::new(config)),
+            notifier,
+            drain: Arc::new(DrainTracker::new()),
+            backends: Arc::new(backends),
+            region_routers: Arc::new(region_routers),
+            receipts: Arc::new(receipts),
+            auth: Arc::new(auth),
+            anonymous: Arc::new(anonymous),
+            claim_mappers: Arc::new(claim_mappers),
+            key_prefixes: Arc::new(key_prefixes),
+            authorizers: Arc::new(authorizers),
+            spill: Arc::new(spill),
+            flow_controls: Arc::new(flow_controls),
+            content_types: Arc::new(content_types),
+            transforms: Arc::new(transforms),
+            compressors: Arc::new(compressors),
+            signers: Arc::new(signers),
+            response_headers: Arc::new(response_headers),
+            storage_classes: Arc::new(storage_classes),
+            quotas: Arc::new(quotas),
+            buffers: Arc::new(BufferPool::new(
+                config.server.zero_copy.pipe_buffer_size,
+                &config.server.buffer_pool,
+            )),
+            sessions: Arc::new(UploadSessions::new()),
+            authz_cache,
+            authz_health: Arc::new(authz_health),
+            uploads: Arc::new(UploadRegistry::new()),
+            tenant_labels: config
+                .metrics
+                .tenant_labels
+                .as_ref()
+                .map(|labels| Arc::new(TenantLabels::new(labels))),
+            slow_request_threshold: config
+                .logging
+                .slow_request_threshold_ms
+                .map(Duration::from_millis),
+        })
     }
 }
 
-/// Find a bucket configuration that matches the request path
+/// Build the inbound connection builder from `server.http`
 ///
-/// This function matches on path prefix boundaries and returns the longest matching prefix
-/// to avoid mis-routing (e.g., `/uploads2/...` should not match `/uploads`).
-fn find_bucket_for_path<'a>(config: &'a Config, path: &str) -> Option<&'a BucketConfig> {
-    config
-        .buckets
-        .iter()
-        .filter(|bucket| {
-            // Match on prefix boundary: path must either equal prefix exactly,
-            // or continue with '/' after prefix
-            if path == bucket.path_prefix {
-                true
-            } else if path.starts_with(&bucket.path_prefix) {
-                // Check that the character after prefix is '/' to ensure boundary match
-                let after_prefix = &path[bucket.path_prefix.len()..];
-                after_prefix.starts_with('/')
-            } else {
-                false
+/// With HTTP/2 enabled the builder detects the protocol from the connection
+/// preface, so TLS clients that negotiated `h2` and plaintext clients using
+/// prior knowledge are both served over HTTP/2.
+fn connection_builder(config: &HttpConfig) -> auto::Builder<TokioExecutor> {
+    let mut builder = auto::Builder::new(TokioExecutor::new());
+    builder.http1().keep_alive(config.keep_alive);
+
+    let http2 = &config.http2;
+    if !http2.enabled {
+        return builder.http1_only();
+    }
+
+    builder
+        .http2()
+        .timer(TokioTimer::new())
+        .max_concurrent_streams(http2.max_concurrent_streams)
+        .initial_stream_window_size(http2.initial_stream_window_size)
+        .initial_connection_window_size(http2.initial_connection_window_size)
+        .adaptive_window(http2.adaptive_window)
+        .keep_alive_interval(http2.keep_alive_interval_secs.map(Duration::from_secs))
+        .keep_alive_timeout(Duration::from_secs(http2.keep_alive_timeout_secs));
+    builder
+}
+
+/// Register a listening socket inherited from systemd with the runtime
+fn from_std_listener(listener: std::net::TcpListener) -> Result<TcpListener, ServerError> {
+    listener
+        .set_nonblocking(true)
+        .and_then(|_| TcpListener::from_std(listener))
+        .map_err(|e| ServerError::BindError(format!("Unusable inherited socket: {}", e)))
+}
+
+/// Serve HTTP on an accepted (and possibly TLS-wrapped) connection
+///
+/// The connection is registered with the graceful shutdown watcher so it can
+/// be drained. Client certificates are attached to every request.
+async fn serve_connection<I>(
+    http: &auto::Builder<TokioExecutor>,
+    io: I,
+    state: ServerState,
+    peer_certificates: PeerCertificates,
+    watcher: Watcher,
+    peer_addr: SocketAddr,
+) where
+    I: hyper::rt::Read + hyper::rt::Write + Unpin + Send + 'static,
+{
+    let service = service_fn(move |req: Request<Incoming>| {
+        let mut req = req.map(|body| body.map_err(std::io::Error::other).boxed());
+        req.extensions_mut().insert(peer_certificates.clone());
+        req.extensions_mut().insert(peer_addr);
+        serve_request(req, state.clone())
+    });
+
+    let conn = watcher.watch(http.serve_connection(io, service));
+    if let Err(e) = conn.await {
+        error!("Error serving connection from {}: {}", peer_addr, e);
+    }
+}
+
+/// Serve one request under its server span and the client's deadline
+///
+/// The client address and certificates are taken from the request
+/// extensions (`SocketAddr` and [`PeerCertificates`]), if present.
+pub(crate) async fn serve_request(
+    req: Request<RequestBody>,
+    state: ServerState,
+) -> Result<Response<String>, hyper::Error> {
+    let span = request_span(&req);
+    let started = Instant::now();
+    let slow_threshold = state.slow_request_threshold;
+    let (method, path) = (req.method().clone(), req.uri().path().to_string());
+    let timings = RequestTimings::default();
+    // S3 calls made for the request stop at the client's deadline
+    let response = match deadline::from_headers(req.headers()) {
+        Ok(deadline) => {
+            deadline::scope(deadline, handle_request(req, state, &timings))
+                .instrument(span.clone())
+                .await
+        }
+        Err(message) => Ok(Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .header("Content-Type", "text/plain")
+            .body(message)
+            .expect("Failed to build 400 response")),
+    };
+    record_response(&span, &response);
+    if let Some(threshold) = slow_threshold {
+        let _enter = span.enter();
+        timings.log_if_slow(
+            method.as_str(),
+            &path,
+            response.as_ref().ok().map(|r| r.status().as_u16()),
+            started.elapsed(),
+            threshold,
+        );
+    }
+    response
+}
+
+/// Server span covering one request
+///
+/// Fields follow the OpenTelemetry HTTP semantic conventions. `http.route` is
+/// filled in once the request is routed and the response fields by
+/// [`record_response`]; auth, authorization and S3 spans nest underneath.
+///
+/// With the `tracing` feature, a valid inbound `traceparent` makes the span
+/// (and everything under it, down to the S3 requests) part of the caller's
+/// trace.
+fn request_span(req: &Request<RequestBody>) -> tracing::Span {
+    let peer_addr = req.extensions().get::<SocketAddr>();
+    let request_content_length = req
+        .headers()
+        .get(hyper::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    let span = tracing::info_span!(
+        "http.request",
+        otel.kind = "server",
+        otel.status_code = tracing::field::Empty,
+        otel.status_message = tracing::field::Empty,
+        http.method = %req.method(),
+        http.target = %req.uri().path(),
+        http.route = tracing::field::Empty,
+        http.status_code = tracing::field::Empty,
+        http.request_content_length = request_content_length,
+        http.response_content_length = tracing::field::Empty,
+        error.type = tracing::field::Empty,
+        client.address = peer_addr.map(|addr| tracing::field::display(addr.ip())),
+        client.port = peer_addr.map(|addr| addr.port()),
+    );
+    #[cfg(feature = "tracing")]
+    {
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+        span.set_parent(crate::tracing::propagation::extract_context(req.headers()));
+    }
+    span
+}
+
+/// Record the outcome of a request on its span
+///
+/// 5xx responses and connection errors mark the span as failed and add an
+/// `exception` event; 4xx responses are the client's fault and are not
+/// errors of a server span. Responses to a categorized error set
+/// `error.type` and count the error by category.
+fn record_response(span: &tracing::Span, response: &Result<Response<String>, hyper::Error>) {
+    match response {
+        Ok(response) => {
+            span.record("http.status_code", response.status().as_u16());
+            span.record("http.response_content_length", response.body().len() as u64);
+            if let Some(category) = response.extensions().get::<ErrorCategory>() {
+                span.record("error.type", category.as_str());
+                metrics::record_error(category.as_str());
             }
-        })
-        // Return the longest matching prefix to handle overlapping prefixes correctly
-        .max_by_key(|bucket| bucket.path_prefix.len())
+            if response.status().is_server_error() {
+                record_exception(span, response.body());
+            }
+        }
+        Err(e) => record_exception(span, &e.to_string()),
+    }
+}
+
+fn record_exception(span: &tracing::Span, message: &str) {
+    span.record("otel.status_code", "ERROR");
+    span.record("otel.status_message", message);
+    tracing::error!(parent: span, exception.message = %message, "exception");
+}
+
+/// Set `http.route` on the current request span
+fn record_route(route: &str) {
+    tracing::Span::current().record("http.route", route);
+}
+
+/// Host the request was sent to (`Host` header, or the HTTP/2 `:authority`)
+fn request_host(req: &Request<RequestBody>) -> Option<String> {
+    req.headers()
+        .get(hyper::header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| req.uri().authority().map(|authority| authority.as_str()))
+        .map(str::to_string)
 }
 
 /// Build AuthRequest from hyper Request headers
-fn build_auth_request(req: &Request<Incoming>) -> AuthRequest {
+fn build_auth_request(req: &Request<RequestBody>) -> AuthRequest {
     let mut headers = HashMap::new();
     for (name, value) in req.headers() {
         if let Ok(v) = value.to_str() {
@@ -262,170 +1287,1144 @@ fn build_auth_request(req: &Request<Incoming>) -> AuthRequest {
         query: req.uri().query().map(|q| q.to_string()),
         method: req.method().to_string(),
         path: req.uri().path().to_string(),
+        peer_certificates: req
+            .extensions()
+            .get::<PeerCertificates>()
+            .map(|certs| certs.0.as_ref().clone())
+            .unwrap_or_default(),
     }
 }
 
-/// Handle HTTP request
-///
-/// Routes incoming requests to appropriate handlers based on path and method.
-///
-/// # Supported Endpoints
-///
-/// * `GET /health` - Health check endpoint (returns "ok")
-/// * `PUT /{path_prefix}/*` - Upload endpoint (forwards to S3 backend)
-/// * All other requests return 404 Not Found
-///
-/// # Authentication
-///
-/// If a bucket has `auth.enabled = true` and JWT config, the request must include
-/// a valid JWT token in the `Authorization: Bearer <token>` header.
-///
-/// # Arguments
+/// Map an authentication failure to a response
 ///
-/// * `req` - The incoming HTTP request
-/// * `config` - Server configuration with bucket definitions
+/// Bearer challenges are only sent when the chain accepts JWTs.
+fn auth_error_response(chain: &AuthChain, path: &str, error: AuthError) -> Response<String> {
+    let category = error.category();
+    let bearer = chain.names().any(|name| name == "jwt");
+    let unauthorized = |body: &str, challenge: Option<&str>| {
+        let mut builder = Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .header("Content-Type", "text/plain");
+        if let Some(challenge) = challenge.filter(|_| bearer) {
+            builder = builder.header("WWW-Authenticate", challenge);
+        }
+        builder
+            .body(body.to_string())
+            .expect("Failed to build 401 response")
+    };
+
+    let response = match error {
+        AuthError::MissingAuth => {
+            warn!("Missing authentication for {}", path);
+            unauthorized("Missing authentication", Some("Bearer"))
+        }
+        AuthError::TokenExpired => {
+            warn!("Expired token for {}", path);
+            unauthorized(
+                "Token expired",
+                Some("Bearer error=\"invalid_token\", error_description=\"Token expired\""),
+            )
+        }
+        AuthError::TokenRevoked => {
+            warn!("Revoked token for {}", path);
+            unauthorized(
+                "Token revoked",
+                Some("Bearer error=\"invalid_token\", error_description=\"Token revoked\""),
+            )
+        }
+        AuthError::InvalidSignature | AuthError::InvalidToken(_) => {
+            warn!("Invalid token for {}", path);
+            unauthorized("Invalid token", Some("Bearer error=\"invalid_token\""))
+        }
+        AuthError::InvalidCertificate(e) => {
+            warn!("Client certificate rejected for {}: {}", path, e);
+            unauthorized("Invalid client certificate", None)
+        }
+        AuthError::Forbidden(e) => {
+            warn!("Forbidden upload to {}: {}", path, e);
+            Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .header("Content-Type", "text/plain")
+                .body("Forbidden".to_string())
+                .expect("Failed to build 403 response")
+        }
+        AuthError::TokenExchangeError(e) => {
+            error!("Token exchange failed for {}: {}", path, e);
+            Response::builder()
+                .status(StatusCode::BAD_GATEWAY)
+                .header("Content-Type", "text/plain")
+                .body("Token exchange failed".to_string())
+                .expect("Failed to build 502 response")
+        }
+        AuthError::ConfigError(e) => {
+            // Fail-closed: auth enabled without a usable authenticator
+            error!("Authentication misconfigured for {}: {}", path, e);
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .header("Content-Type", "text/plain")
+                .body("Server configuration error".to_string())
+                .expect("Failed to build error response")
+        }
+        e => {
+            error!("Authentication error: {}", e);
+            unauthorized(&format!("Authentication failed: {}", e), None)
+        }
+    };
+    categorized(response, category)
+}
+
+/// Check an anonymous upload against its bucket's constraints
 ///
-/// # Returns
+/// The body size is checked against `Content-Length` here and enforced again
+/// while the body is read, since chunked uploads declare no length.
+async fn check_anonymous_request(
+    policy: &AnonymousPolicy,
+    req: &Request<RequestBody>,
+    key: &str,
+) -> Result<(), AnonymousError> {
+    if let Some(client) = req.extensions().get::<SocketAddr>() {
+        policy.check_shared_rate(client.ip()).await?;
+    }
+    policy.check_key(key)?;
+    policy.check_content_type(
+        req.headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok()),
+    )?;
+    if let Some(length) = content_length(req) {
+        policy.check_size(length)?;
+    }
+    Ok(())
+}
+
+/// Build the authorization request for an upload
 ///
-/// An HTTP response with appropriate status code and body
-async fn handle_request(
-    req: Request<Incoming>,
-    config: Arc<Config>,
-) -> Result<Response<String>, hyper::Error> {
-    let path = req.uri().path().to_string();
-    let method = req.method().clone();
+/// Requests without an authentication result are authorized as the
+/// anonymous subject. Claims are mapped into the context when the bucket
+/// configures `auth.claim_mapping`, the party acting for a delegated subject
+/// is added as `actor`, and the upload itself is described under `object`
+/// (see [`ObjectContext`]).
+fn build_authz_request(
+    auth_result: Option<&AuthResult>,
+    mapper: Option<&ClaimMapper>,
+    bucket: &str,
+    object: &ObjectContext,
+) -> AuthzRequest {
+    let resource = format!("bucket/{}/{}", bucket, object.key);
+    let mut request = match (auth_result, mapper) {
+        (Some(result), Some(mapper)) => mapper.authz_request(result, "upload", &resource),
+        (result, _) => AuthzRequest {
+            subject: result
+                .map_or(ANONYMOUS_SUBJECT, |r| r.subject.as_str())
+                .to_string(),
+            action: "upload".to_string(),
+            resource,
+            context: HashMap::new(),
+        },
+    };
+    if let Some(actor) = auth_result.and_then(AuthResult::actor) {
+        request
+            .context
+            .insert("actor".to_string(), serde_json::json!(actor));
+    }
+    request.with_object(object)
+}
 
-    info!("Handling {} {}", method, path);
+/// Declared length of a request's payload, if any
+///
+/// For `aws-chunked` bodies this is `x-amz-decoded-content-length`, the
+/// length without the chunk framing.
+fn content_length(req: &Request<RequestBody>) -> Option<u64> {
+    if aws_chunked::is_aws_chunked(req.headers()) {
+        return aws_chunked::decoded_content_length(req.headers());
+    }
+    req.headers()
+        .get("content-length")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+}
 
-    // Health check endpoint
-    if path == "/health" && method == hyper::Method::GET {
-        return Ok(Response::builder()
-            .status(StatusCode::OK)
+/// Map a failure to read an upload body to a response
+///
+/// Hitting the body cap (`limit`) means the upload was too large; with
+/// `anonymous_cap`, the cap is the anonymous size limit. Bodies shorter or
+/// longer than their declared length are counted per `bucket`.
+fn body_error_response(
+    path: &str,
+    bucket: &str,
+    error: Box<dyn std::error::Error + Send + Sync>,
+    limit: u64,
+    anonymous_cap: bool,
+) -> Response<String> {
+    let mismatch = match error.downcast_ref::<ContentLengthError>() {
+        Some(e) => Some(e.kind()),
+        None => match error.downcast_ref() {
+            Some(AwsChunkedError::Truncated) => Some("truncated"),
+            Some(AwsChunkedError::LengthMismatch { expected, actual }) if actual < expected => {
+                Some("truncated")
+            }
+            Some(AwsChunkedError::LengthMismatch { .. }) => Some("overlong"),
+            _ => None,
+        },
+    };
+    if let Some(kind) = mismatch {
+        warn!("Upload to {} rejected: {}", path, error);
+        metrics::record_body_length_mismatch(bucket, kind);
+        return Response::builder()
+            .status(StatusCode::BAD_REQUEST)
             .header("Content-Type", "text/plain")
-            .body("ok".to_string())
-            .expect("Failed to build health check response"));
+            .body(format!("Incomplete body: {}", error))
+            .expect("Failed to build 400 response");
+    }
+    if error.is::<LengthLimitError>() && anonymous_cap {
+        let e = AnonymousError::TooLarge { max_size: limit };
+        warn!("Anonymous upload to {} rejected: {}", path, e);
+        return anonymous_error_response(e);
+    }
+    if error.is::<LengthLimitError>() {
+        warn!(
+            "Upload to {} exceeds the size limit of {} bytes",
+            path, limit
+        );
+        return Response::builder()
+            .status(StatusCode::PAYLOAD_TOO_LARGE)
+            .header("Content-Type", "text/plain")
+            .body(format!("Upload exceeds the size limit of {} bytes", limit))
+            .expect("Failed to build 413 response");
     }
+    error!("Failed to read upload body: {}", error);
+    Response::builder()
+        .status(StatusCode::BAD_REQUEST)
+        .header("Content-Type", "text/plain")
+        .body(format!("Failed to read body: {}", error))
+        .expect("Failed to build error response")
+}
 
-    // Find matching bucket for the path
-    let bucket = match find_bucket_for_path(&config, &path) {
-        Some(b) => b,
-        None => {
-            info!("No bucket configured for path: {}", path);
-            return Ok(Response::builder()
-                .status(StatusCode::NOT_FOUND)
-                .header("Content-Type", "text/plain")
-                .body("Not Found".to_string())
+/// Handle the outcome of a content type check
+///
+/// Violations are logged and counted. Buckets enforcing with `reject` get
+/// an S3-style `InvalidArgument` error response back; with `log` the upload
+/// proceeds.
+fn content_type_violation(
+    policy: &ContentTypePolicy,
+    bucket: &str,
+    path: &str,
+    result: Result<(), ContentTypeError>,
+) -> Option<Response<String>> {
+    let error = result.err()?;
+    let reason = match error {
+        ContentTypeError::NotAllowed(_) => "not_allowed",
+        ContentTypeError::Mismatch { .. } => "mismatch",
+    };
+    metrics::record_content_type_violation(bucket, reason);
+    if policy.enforce() == ContentTypeEnforcement::Log {
+        warn!(
+            "Upload to {} has a disallowed content type: {}",
+            path, error
+        );
+        return None;
+    }
+    warn!("Upload to {} rejected: {}", path, error);
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <Error><Code>InvalidArgument</Code><Message>{}</Message>\
+         <ArgumentName>Content-Type</ArgumentName></Error>",
+        quick_xml::escape::escape(error.to_string())
+    );
+    Some(
+        Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .header("Content-Type", "application/xml")
+            .body(body)
+            .expect("Failed to build content type error response"),
+    )
+}
+
+/// Conditional write headers (`If-Match`, `If-None-Match`) of a request
+fn conditional_headers(headers: &hyper::HeaderMap) -> Vec<(String, String)> {
+    backend::CONDITIONAL_HEADERS
+        .iter()
+        .filter_map(|name| {
+            let value = headers.get(*name)?.to_str().ok()?;
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// `x-amz-storage-class` header for an upload, if a bucket rule matches it
+fn storage_class_header(
+    router: Option<&Arc<StorageClassRouter>>,
+    key: &str,
+    content_type: Option<&str>,
+    size: u64,
+) -> Option<(String, String)> {
+    let class = router?.route(key, content_type, size)?;
+    Some((
+        "x-amz-storage-class".to_string(),
+        class.as_str().to_string(),
+    ))
+}
+
+/// S3-style response to a conditional write whose precondition failed
+fn precondition_failed_response() -> Response<String> {
+    Response::builder()
+        .status(StatusCode::PRECONDITION_FAILED)
+        .header("Content-Type", "application/xml")
+        .body(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <Error><Code>PreconditionFailed</Code>\
+             <Message>At least one of the pre-conditions you specified did not hold</Message>\
+             </Error>"
+                .to_string(),
+        )
+        .expect("Failed to build 412 response")
+}
+
+/// Response to a failed upload
+///
+/// Failed preconditions, the client's deadline and backend errors have
+/// responses of their own; any other error answers with the status of its
+/// [`ErrorCategory`].
+fn upload_error_response(path: &str, bucket: &str, error: &UploadError) -> Response<String> {
+    let response = match error.root() {
+        UploadError::PreconditionFailed(e) => {
+            warn!("Conditional upload to {} not applied: {}", path, e);
+            precondition_failed_response()
+        }
+        UploadError::DeadlineExceeded => {
+            warn!("Upload to {} stopped at the client's deadline", path);
+            metrics::record_upload_failure(bucket);
+            Response::builder()
+                .status(StatusCode::GATEWAY_TIMEOUT)
+                .header("Content-Type", "text/plain")
+                .body("Deadline exceeded".to_string())
+                .expect("Failed to build 504 response")
+        }
+        UploadError::Backend(backend_error) => {
+            error!("Upload to {} failed at the backend: {}", path, error);
+            metrics::record_upload_failure(bucket);
+            backend_error_response(backend_error)
+        }
+        UploadError::InvalidParts { code, message } => {
+            warn!("Upload to {} has invalid parts: {}", path, error);
+            metrics::record_upload_failure(bucket);
+            invalid_parts_response(code, message)
+        }
+        _ => {
+            error!("Upload failed: {}", error);
+            metrics::record_upload_failure(bucket);
+            Response::builder()
+                .status(error.category().status())
+                .header("Content-Type", "text/plain")
+                .body(format!("Upload failed: {}", error))
+                .expect("Failed to build error response")
+        }
+    };
+    categorized(response, error.category())
+}
+
+/// Mark an error response with the category of its error
+///
+/// [`record_response`] labels the request span and `mizuchi_errors_total`
+/// with it.
+fn categorized(mut response: Response<String>, category: ErrorCategory) -> Response<String> {
+    response.extensions_mut().insert(category);
+    response
+}
+
+/// Response to an upload the S3 backend failed, by its error code
+///
+/// Throttling is passed on as `503` with `Retry-After`, and invalid
+/// multipart parts as the client's error. A missing bucket or denied
+/// access are proxy misconfigurations and answer `502` without the
+/// backend's message, as does any other backend error.
+fn backend_error_response(error: &S3ServiceError) -> Response<String> {
+    let (status, message) = match error.code {
+        S3ErrorCode::SlowDown => (StatusCode::SERVICE_UNAVAILABLE, "Slow down".to_string()),
+        S3ErrorCode::NoSuchBucket => (
+            StatusCode::BAD_GATEWAY,
+            "Storage bucket not found".to_string(),
+        ),
+        S3ErrorCode::AccessDenied => (StatusCode::BAD_GATEWAY, "Storage access denied".to_string()),
+        S3ErrorCode::InvalidPart | S3ErrorCode::InvalidPartOrder | S3ErrorCode::EntityTooSmall => (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid part: {}", error.message),
+        ),
+        S3ErrorCode::NoSuchUpload => (StatusCode::NOT_FOUND, "No such upload".to_string()),
+        _ => (StatusCode::BAD_GATEWAY, format!("Upload failed: {}", error)),
+    };
+    let mut response = Response::builder()
+        .status(status)
+        .header("Content-Type", "text/plain");
+    if status == StatusCode::SERVICE_UNAVAILABLE {
+        response = response.header("Retry-After", "1");
+    }
+    response
+        .body(message)
+        .expect("Failed to build backend error response")
+}
+
+/// S3-style response to a multipart upload with parts S3 would refuse
+fn invalid_parts_response(code: &S3ErrorCode, message: &str) -> Response<String> {
+    Response::builder()
+        .status(StatusCode::BAD_REQUEST)
+        .header("Content-Type", "application/xml")
+        .body(format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <Error><Code>{}</Code><Message>{}</Message></Error>",
+            code, message
+        ))
+        .expect("Failed to build 400 response")
+}
+
+/// S3-style response to an upload over its owner's quota
+fn quota_exceeded_response(error: &QuotaError) -> Response<String> {
+    let mut response = Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .header("Content-Type", "application/xml");
+    if let QuotaError::Exceeded { resets_at, .. } = error {
+        let retry_after = (*resets_at - chrono::Utc::now()).num_seconds().max(1);
+        response = response.header("Retry-After", retry_after.to_string());
+    }
+    response
+        .body(format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <Error><Code>QuotaExceeded</Code><Message>{}</Message></Error>",
+            error
+        ))
+        .expect("Failed to build 403 response")
+}
+
+/// Longest a progress request may wait for the upload to change
+const MAX_PROGRESS_WAIT: Duration = Duration::from_secs(30);
+
+/// Subject making a read request to `bucket`, authenticated like uploads
+///
+/// `Ok(None)` if the bucket has no authentication; the anonymous subject
+/// for requests without credentials where the bucket allows anonymous
+/// uploads. On failure, the response to send.
+async fn read_subject(
+    req: &Request<RequestBody>,
+    bucket: &str,
+    auth: &HashMap<String, Arc<AuthChain>>,
+    anonymous: &HashMap<String, Arc<AnonymousPolicy>>,
+) -> Result<Option<String>, Response<String>> {
+    read_auth(req, bucket, auth, anonymous)
+        .await
+        .map(|result| result.map(|result| result.subject))
+}
+
+/// Like [`read_subject`], keeping the claims; anonymous requests have none
+async fn read_auth(
+    req: &Request<RequestBody>,
+    bucket: &str,
+    auth: &HashMap<String, Arc<AuthChain>>,
+    anonymous: &HashMap<String, Arc<AnonymousPolicy>>,
+) -> Result<Option<AuthResult>, Response<String>> {
+    let Some(chain) = auth.get(bucket) else {
+        return Ok(None);
+    };
+    match chain.authenticate(&build_auth_request(req)).await {
+        Ok(result) => Ok(Some(result)),
+        Err(AuthError::MissingAuth) if anonymous.contains_key(bucket) => Ok(Some(AuthResult {
+            subject: ANONYMOUS_SUBJECT.to_string(),
+            claims: HashMap::new(),
+        })),
+        Err(e) => Err(auth_error_response(chain, req.uri().path(), e)),
+    }
+}
+
+/// Whether `query` has the parameter `name`, with or without a value
+fn has_query_flag(query: Option<&str>, name: &str) -> bool {
+    query.is_some_and(|query| {
+        query
+            .split('&')
+            .any(|pair| pair.split('=').next() == Some(name))
+    })
+}
+
+/// S3 operation of a PUT the proxy does not implement, if it is one
+///
+/// UploadPart (and UploadPartCopy) carry `uploadId` and `partNumber`, and
+/// CopyObject an `x-amz-copy-source`. Storing their bodies as objects would
+/// silently break the client's upload.
+fn unsupported_put(req: &Request<RequestBody>) -> Option<&'static str> {
+    let query = req.uri().query();
+    if has_query_flag(query, "uploadId") || has_query_flag(query, "partNumber") {
+        Some("UploadPart")
+    } else if req.headers().contains_key("x-amz-copy-source") {
+        Some("CopyObject")
+    } else {
+        None
+    }
+}
+
+/// S3 `NotImplemented` error for `operation`
+fn not_implemented_response(operation: &str) -> Response<String> {
+    Response::builder()
+        .status(StatusCode::NOT_IMPLEMENTED)
+        .header("Content-Type", "application/xml")
+        .body(format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <Error><Code>NotImplemented</Code>\
+             <Message>{} is not supported by this proxy</Message></Error>",
+            operation
+        ))
+        .expect("Failed to build 501 response")
+}
+
+/// Why a body declaring `declared_length` bytes can't be stored, if it can't
+///
+/// Bodies over [`MAX_PUT_SIZE`] have to be stored as multipart parts while
+/// they arrive, which rules out transforming or sniffing them as a whole.
+fn oversized_refusal(
+    declared_length: Option<u64>,
+    transformed: bool,
+    sniffed: bool,
+) -> Option<String> {
+    let length = declared_length.filter(|&length| length > MAX_PUT_SIZE)?;
+    if length > MAX_OBJECT_SIZE {
+        return Some(format!(
+            "Body of {} bytes exceeds the maximum object size of 5TB",
+            length
+        ));
+    }
+    let reason = if transformed {
+        "this bucket transforms upload bodies"
+    } else if sniffed {
+        "this bucket sniffs upload content types"
+    } else {
+        return None;
+    };
+    Some(format!(
+        "Body of {} bytes exceeds the 5GB single upload limit and can't be \
+         split into parts because {}",
+        length, reason
+    ))
+}
+
+/// S3 `EntityTooLarge` error with `message`
+fn entity_too_large_response(message: &str) -> Response<String> {
+    Response::builder()
+        .status(StatusCode::BAD_REQUEST)
+        .header("Content-Type", "application/xml")
+        .body(format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <Error><Code>EntityTooLarge</Code>\
+             <Message>{}</Message></Error>",
+            message
+        ))
+        .expect("Failed to build 400 response")
+}
+
+/// Response to HeadBucket, or to GetBucketLocation if `location`
+///
+/// Both carry the bucket's region in `x-amz-bucket-region`, which SDKs use
+/// to pick the signing region. As in S3, the location of a `us-east-1`
+/// bucket is empty.
+fn bucket_response(location: bool, region: &str) -> Response<String> {
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header("x-amz-bucket-region", region);
+    if !location {
+        return response
+            .body(String::new())
+            .expect("Failed to build HeadBucket response");
+    }
+    let constraint = if region == "us-east-1" { "" } else { region };
+    response
+        .header("Content-Type", "application/xml")
+        .body(format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <LocationConstraint xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">{}</LocationConstraint>",
+            constraint
+        ))
+        .expect("Failed to build GetBucketLocation response")
+}
+
+/// Most uploads a ListMultipartUploads response lists, as in S3
+const MAX_LIST_UPLOADS: usize = 1000;
+
+/// Page of a ListMultipartUploads request
+struct ListUploadsPage<'a> {
+    prefix: &'a str,
+    key_marker: &'a str,
+    upload_id_marker: &'a str,
+    max_uploads: usize,
+}
+
+/// ListMultipartUploads result listing the `page` of `uploads`
+///
+/// Uploads are ordered by key, then upload ID, and listed after the
+/// markers. Upload IDs are the proxy's upload session IDs, which the caller
+/// can query for progress.
+fn list_uploads_response(
+    path_prefix: &str,
+    page: &ListUploadsPage<'_>,
+    mut uploads: Vec<UploadProgress>,
+) -> Response<String> {
+    use quick_xml::escape::escape;
+
+    uploads.sort_by(|a, b| (&a.key, &a.upload_id).cmp(&(&b.key, &b.upload_id)));
+    uploads.retain(|upload| {
+        let key = upload.key.as_str();
+        // Without an upload ID marker, every upload of the marker key is skipped
+        key > page.key_marker
+            || (key == page.key_marker
+                && !page.upload_id_marker.is_empty()
+                && upload.upload_id.as_str() > page.upload_id_marker)
+    });
+    // An empty page can never be continued, so it is not truncated either
+    let truncated = page.max_uploads > 0 && uploads.len() > page.max_uploads;
+    uploads.truncate(page.max_uploads);
+
+    let mut body = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <ListMultipartUploadsResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\
+         <Bucket>{}</Bucket><Prefix>{}</Prefix><KeyMarker>{}</KeyMarker>\
+         <UploadIdMarker>{}</UploadIdMarker><MaxUploads>{}</MaxUploads>\
+         <IsTruncated>{}</IsTruncated>",
+        escape(path_prefix.trim_matches('/')),
+        escape(page.prefix),
+        escape(page.key_marker),
+        escape(page.upload_id_marker),
+        page.max_uploads,
+        truncated
+    );
+    if let Some(last) = uploads.last().filter(|_| truncated) {
+        body.push_str(&format!(
+            "<NextKeyMarker>{}</NextKeyMarker><NextUploadIdMarker>{}</NextUploadIdMarker>",
+            escape(last.key.as_str()),
+            escape(last.upload_id.as_str())
+        ));
+    }
+    for upload in &uploads {
+        body.push_str(&format!(
+            "<Upload><Key>{}</Key><UploadId>{}</UploadId><Initiated>{}</Initiated>\
+             <StorageClass>STANDARD</StorageClass></Upload>",
+            escape(upload.key.as_str()),
+            escape(upload.upload_id.as_str()),
+            upload
+                .started_at
+                .to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+        ));
+    }
+    body.push_str("</ListMultipartUploadsResult>");
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/xml")
+        .header("Cache-Control", "no-store")
+        .body(body)
+        .expect("Failed to build ListMultipartUploads response")
+}
+
+/// Progress of an upload as JSON, or 404 if unknown to the caller
+fn upload_progress_response(progress: Option<UploadProgress>) -> Response<String> {
+    let Some(body) = progress.and_then(|progress| serde_json::to_string(&progress).ok()) else {
+        return Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .header("Content-Type", "text/plain")
+            .body("Upload not found".to_string())
+            .expect("Failed to build 404 response");
+    };
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .header("Cache-Control", "no-store")
+        .body(body)
+        .expect("Failed to build progress response")
+}
+
+/// Map a rejected anonymous upload to a response
+fn anonymous_error_response(error: AnonymousError) -> Response<String> {
+    let mut builder = Response::builder().header("Content-Type", "text/plain");
+    builder = match error {
+        AnonymousError::RateLimited { retry_after_secs } => builder
+            .status(StatusCode::TOO_MANY_REQUESTS)
+            .header("Retry-After", retry_after_secs.to_string()),
+        AnonymousError::TooLarge { .. } => builder.status(StatusCode::PAYLOAD_TOO_LARGE),
+        AnonymousError::ContentTypeNotAllowed(_) => {
+            builder.status(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+        }
+        AnonymousError::KeyOutsidePrefix(_) => builder.status(StatusCode::FORBIDDEN),
+    };
+    builder
+        .body(error.to_string())
+        .expect("Failed to build anonymous upload error response")
+}
+
+/// Build the readiness response
+///
+/// Returns 503 while draining, and while the authorizer of a `fail_closed`
+/// bucket fails its health check. A bucket whose primary S3 endpoint is
+/// tripped is still served by its fallback, and a failing `fail_open`
+/// authorizer still lets uploads through, so the server reports `degraded`
+/// with 200 for those.
+fn readiness_response(
+    drain: &DrainTracker,
+    backends: &HashMap<String, Arc<dyn StorageBackend>>,
+    authz_health: &AuthzHealth,
+) -> Response<String> {
+    let circuits: BTreeMap<&str, CircuitState> = backends
+        .iter()
+        .filter_map(|(name, backend)| Some((name.as_str(), backend.circuit_state()?)))
+        .collect();
+    let authz = authz_health.failures();
+
+    let (status_code, status) = if drain.is_draining() {
+        (StatusCode::SERVICE_UNAVAILABLE, "draining")
+    } else if authz_health.is_blocking() {
+        (StatusCode::SERVICE_UNAVAILABLE, "unready")
+    } else if circuits.values().any(|s| *s != CircuitState::Closed) || !authz.is_empty() {
+        (StatusCode::OK, "degraded")
+    } else {
+        (StatusCode::OK, "ready")
+    };
+
+    let body = serde_json::json!({
+        "status": status,
+        "circuits": circuits,
+        "authz": authz,
+    });
+
+    Response::builder()
+        .status(status_code)
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .expect("Failed to build readiness response")
+}
+
+/// Handle HTTP request
+///
+/// Routes incoming requests to appropriate handlers based on path and method.
+///
+/// # Supported Endpoints
+///
+/// * `GET /health` - Health check endpoint (returns "ok")
+/// * `GET /ready` - Readiness endpoint (drain, circuit breaker and authorizer state)
+/// * `PUT /{path_prefix}/*` - Upload endpoint (forwards to S3 backend)
+/// * All other requests return 404 Not Found
+///
+/// # Authentication
+///
+/// If a bucket has `auth.enabled = true`, the request must be accepted by the
+/// bucket's [`AuthChain`]: by default a client certificate (mTLS), a SigV4
+/// signature or a JWT in the `Authorization: Bearer <token>` header, in that
+/// order, for whichever of them are configured.
+/// Buckets with `auth.claim_mapping.key_prefix` additionally reject (403)
+/// authenticated uploads outside the prefix derived from the token claims,
+/// and buckets with `auth.claim_mapping.scopes` uploads whose token scopes
+/// don't grant `upload` on the key.
+///
+/// # Authorization
+///
+/// Buckets with `authz` then ask their [`Authorizer`] whether the subject
+/// may `upload` to `bucket/{bucket}/{key}`; denials get 403. Authorizer
+/// errors get 500 unless the bucket's `authz_failure.mode` is `fail_open`.
+///
+/// # Arguments
+///
+/// * `req` - The incoming HTTP request
+/// * `state` - Shared server state (configuration, notifier, drain tracker)
+///
+/// # Returns
+///
+/// An HTTP response with appropriate status code and body
+async fn handle_request(
+    req: Request<RequestBody>,
+    state: ServerState,
+    timings: &RequestTimings,
+) -> Result<Response<String>, hyper::Error> {
+    let ServerState {
+        resolver,
+        notifier,
+        drain,
+        backends,
+        region_routers,
+        receipts,
+        auth,
+        anonymous,
+        claim_mappers,
+        key_prefixes,
+        authorizers,
+        spill,
+        flow_controls,
+        content_types,
+        transforms,
+        compressors,
+        signers,
+        response_headers,
+        storage_classes,
+        quotas,
+        buffers,
+        sessions,
+        tenant_labels,
+        authz_health,
+        ..
+    } = state;
+    let path = req.uri().path().to_string();
+    let method = req.method().clone();
+
+    info!("Handling {} {}", method, path);
+
+    // Health check endpoint
+    if path == "/health" && method == hyper::Method::GET {
+        record_route("/health");
+        return Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "text/plain")
+            .body("ok".to_string())
+            .expect("Failed to build health check response"));
+    }
+
+    // Readiness endpoint: drain status, per-bucket circuit breaker and authorizer state
+    if path == "/ready" && method == hyper::Method::GET {
+        record_route("/ready");
+        return Ok(readiness_response(&drain, &backends, &authz_health));
+    }
+
+    // Find matching buckets for the host and path; the first one authenticates uploads
+    let host = request_host(&req);
+    let resolved = resolver.resolve_buckets_and_key_for_host(host.as_deref(), &path);
+    let (buckets, s3_key) = match resolved {
+        Ok((buckets, key)) => (buckets.iter().collect::<Vec<_>>(), key),
+        Err(_) => {
+            info!("No bucket configured for path: {}", path);
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .header("Content-Type", "text/plain")
+                .body("Not Found".to_string())
                 .expect("Failed to build 404 response"));
         }
     };
+    let bucket = buckets[0];
+    record_route(&format!("{}/{{key}}", bucket.path_prefix));
+    timings.set_bucket(&bucket.name);
+
+    // Progress of an upload sent with an upload ID, for the uploader only
+    if method == hyper::Method::GET {
+        if let Some(upload_id) = admin::query_param(req.uri().query(), "uploadId") {
+            let subject = match read_subject(&req, &bucket.name, &auth, &anonymous).await {
+                Ok(subject) => subject,
+                Err(response) => return Ok(response),
+            };
+            let wait = admin::query_param(req.uri().query(), "wait")
+                .and_then(|secs| secs.parse().ok())
+                .map(|secs| Duration::from_secs(secs).min(MAX_PROGRESS_WAIT));
+            let progress = match wait {
+                Some(timeout) => sessions.wait(&upload_id, timeout).await,
+                None => sessions.get(&upload_id),
+            };
+            let progress = progress.filter(|progress| {
+                progress.key == s3_key
+                    && buckets.iter().any(|b| b.name == progress.bucket)
+                    && (auth.get(&bucket.name).is_none() || progress.subject == subject)
+            });
+            return Ok(upload_progress_response(progress));
+        }
+    }
+
+    // HeadBucket and GetBucketLocation, which SDKs send before uploading
+    let is_location = method == hyper::Method::GET && has_query_flag(req.uri().query(), "location");
+    if s3_key.is_empty() && (method == hyper::Method::HEAD || is_location) {
+        if let Err(response) = read_subject(&req, &bucket.name, &auth, &anonymous).await {
+            return Ok(response);
+        }
+        return Ok(bucket_response(is_location, &bucket.s3.region));
+    }
+
+    // ListMultipartUploads, limited to the caller's own uploads in progress
+    let query = req.uri().query();
+    if s3_key.is_empty()
+        && method == hyper::Method::GET
+        && has_query_flag(query, "uploads")
+        && bucket.upload.list_uploads
+    {
+        let auth_result = match read_auth(&req, &bucket.name, &auth, &anonymous).await {
+            Ok(result) => result,
+            Err(response) => return Ok(response),
+        };
+        // Only the bucket the caller's uploads are routed to is listed, so
+        // tenants sharing the prefix never see each other's uploads
+        let tenant = router::tenant_claim(&buckets)
+            .zip(auth_result.as_ref())
+            .and_then(|(claim, result)| claims::lookup(&result.claims, claim)?.as_str());
+        let subject = auth_result.as_ref().map(|result| result.subject.as_str());
+        let prefix = admin::query_param(query, "prefix").unwrap_or_default();
+        let uploads = router::select_tenant_bucket(&buckets, tenant)
+            .map(|listed| sessions.list(&listed.name))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|progress| {
+                progress.key.starts_with(prefix.as_ref())
+                    && (auth.get(&bucket.name).is_none() || progress.subject.as_deref() == subject)
+            })
+            .collect();
+        let page = ListUploadsPage {
+            prefix: &prefix,
+            key_marker: &admin::query_param(query, "key-marker").unwrap_or_default(),
+            upload_id_marker: &admin::query_param(query, "upload-id-marker").unwrap_or_default(),
+            max_uploads: admin::query_param(query, "max-uploads")
+                .and_then(|max| max.parse().ok())
+                .map_or(MAX_LIST_UPLOADS, |max: usize| max.min(MAX_LIST_UPLOADS)),
+        };
+        return Ok(list_uploads_response(&bucket.path_prefix, &page, uploads));
+    }
 
     // Handle upload requests (PUT)
     if method == hyper::Method::PUT {
-        // Authenticate if auth is enabled for this bucket
-        if bucket.auth.enabled {
-            if let Some(ref jwt_config) = bucket.auth.jwt {
-                // Create JWT authenticator from config
-                let secret = match &jwt_config.secret {
-                    Some(s) => s,
-                    None => {
-                        error!(
-                            "JWT auth enabled but no secret configured for bucket {}",
-                            bucket.name
-                        );
-                        return Ok(Response::builder()
-                            .status(StatusCode::INTERNAL_SERVER_ERROR)
-                            .header("Content-Type", "text/plain")
-                            .body("Server configuration error".to_string())
-                            .expect("Failed to build error response"));
-                    }
-                };
+        let started = Instant::now();
 
-                // Create JWT authenticator based on configured algorithm
-                let authenticator = match jwt_config.algorithm.to_uppercase().as_str() {
-                    "HS256" => JwtAuthenticator::new_hs256(secret),
-                    "RS256" => match JwtAuthenticator::new_rs256(secret) {
-                        Ok(auth) => auth,
-                        Err(e) => {
-                            error!("Failed to create RS256 authenticator: {}", e);
-                            return Ok(Response::builder()
-                                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                                .header("Content-Type", "text/plain")
-                                .body("Server configuration error".to_string())
-                                .expect("Failed to build error response"));
-                        }
-                    },
-                    "ES256" => match JwtAuthenticator::new_es256(secret) {
-                        Ok(auth) => auth,
-                        Err(e) => {
-                            error!("Failed to create ES256 authenticator: {}", e);
-                            return Ok(Response::builder()
-                                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                                .header("Content-Type", "text/plain")
-                                .body("Server configuration error".to_string())
-                                .expect("Failed to build error response"));
-                        }
-                    },
-                    alg => {
-                        error!("Unsupported JWT algorithm configured: {}", alg);
-                        return Ok(Response::builder()
-                            .status(StatusCode::INTERNAL_SERVER_ERROR)
-                            .header("Content-Type", "text/plain")
-                            .body("Server configuration error".to_string())
-                            .expect("Failed to build error response"));
-                    }
-                };
-                let auth_request = build_auth_request(&req);
+        // Authentication result (if any); the subject is reported in upload notifications
+        let mut auth_result: Option<AuthResult> = None;
 
-                match authenticator.authenticate(&auth_request).await {
+        // Authenticate if auth is enabled for this bucket. Requests without
+        // credentials fall back to anonymous access where the bucket allows it.
+        let anonymous_policy = anonymous.get(&bucket.name);
+        let mut is_anonymous = false;
+        match auth.get(&bucket.name) {
+            Some(chain) => {
+                let auth_request = build_auth_request(&req);
+                let auth_started = Instant::now();
+                let outcome = chain.authenticate(&auth_request).await;
+                timings.phase(&bucket.name, "auth", auth_started.elapsed());
+                match outcome {
                     Ok(result) => {
                         info!("Authenticated user: {}", result.subject);
+                        auth_result = Some(result);
                     }
-                    Err(AuthError::MissingAuth) => {
-                        warn!("Missing authentication for {}", path);
-                        return Ok(Response::builder()
-                            .status(StatusCode::UNAUTHORIZED)
-                            .header("Content-Type", "text/plain")
-                            .header("WWW-Authenticate", "Bearer")
-                            .body("Missing authentication".to_string())
-                            .expect("Failed to build 401 response"));
+                    Err(AuthError::MissingAuth) if anonymous_policy.is_some() => {
+                        is_anonymous = true;
                     }
-                    Err(AuthError::TokenExpired) => {
-                        warn!("Expired token for {}", path);
-                        return Ok(Response::builder()
-                            .status(StatusCode::UNAUTHORIZED)
-                            .header("Content-Type", "text/plain")
-                            .header("WWW-Authenticate", "Bearer error=\"invalid_token\", error_description=\"Token expired\"")
-                            .body("Token expired".to_string())
-                            .expect("Failed to build 401 response"));
+                    Err(e) => return Ok(auth_error_response(chain, &path, e)),
+                }
+            }
+            None => is_anonymous = anonymous_policy.is_some(),
+        }
+
+        // Buckets sharing the prefix route to different S3 accounts by tenant
+        let tenant = router::tenant_claim(&buckets)
+            .zip(auth_result.as_ref())
+            .and_then(|(claim, result)| claims::lookup(&result.claims, claim)?.as_str());
+        let bucket = match router::select_tenant_bucket(&buckets, tenant) {
+            Some(b) => b,
+            None => {
+                warn!(
+                    "No bucket serves tenant {} for path: {}",
+                    tenant.unwrap_or("(none)"),
+                    path
+                );
+                return Ok(Response::builder()
+                    .status(StatusCode::FORBIDDEN)
+                    .header("Content-Type", "text/plain")
+                    .body("Forbidden".to_string())
+                    .expect("Failed to build 403 response"));
+            }
+        };
+
+        timings.set_bucket(&bucket.name);
+
+        // Validate S3 key is not empty
+        if s3_key.is_empty() {
+            warn!("Empty S3 key for path: {}", path);
+            return Ok(Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .header("Content-Type", "text/plain")
+                .body("Invalid key: object key cannot be empty".to_string())
+                .expect("Failed to build error response"));
+        }
+
+        // Authenticated uploads stay under the claim-derived prefix and within
+        // their token scopes, if configured
+        if let (Some(result), Some(mapper)) = (&auth_result, claim_mappers.get(&bucket.name)) {
+            if mapper.check_key(&result.claims, &s3_key).is_err() {
+                warn!(
+                    "Upload to {} by {} is outside its claim-scoped key prefix",
+                    path, result.subject
+                );
+                return Ok(Response::builder()
+                    .status(StatusCode::FORBIDDEN)
+                    .header("Content-Type", "text/plain")
+                    .body("Forbidden".to_string())
+                    .expect("Failed to build 403 response"));
+            }
+            // Denied by scope without asking the authorizer
+            if let Err(mismatch) = mapper.check_scopes(&result.claims, "upload", &s3_key) {
+                warn!(
+                    "Upload to {} by {} is not granted by its token scopes ({})",
+                    path,
+                    result.subject,
+                    mismatch.as_str()
+                );
+                metrics::record_authz_scope_mismatch(&bucket.name, mismatch.as_str());
+                return Ok(Response::builder()
+                    .status(StatusCode::FORBIDDEN)
+                    .header("Content-Type", "text/plain")
+                    .body("Forbidden".to_string())
+                    .expect("Failed to build 403 response"));
+            }
+        }
+
+        let mut subject = auth_result.as_ref().map(|result| result.subject.clone());
+        let anonymous_policy = anonymous_policy.filter(|_| is_anonymous);
+        if let Some(policy) = anonymous_policy {
+            if let Err(e) = check_anonymous_request(policy, &req, &s3_key).await {
+                warn!("Anonymous upload to {} rejected: {}", path, e);
+                metrics::record_auth_attempt("anonymous", false);
+                return Ok(anonymous_error_response(e));
+            }
+            metrics::record_auth_attempt("anonymous", true);
+            subject = Some(ANONYMOUS_SUBJECT.to_string());
+        }
+        if let Some(ref subject) = subject {
+            timings.set_subject(subject);
+        }
+
+        // Keys are stored under the bucket's key prefix, whatever the client sent
+        let s3_key = match key_prefixes.get(&bucket.name) {
+            Some(prefix) => {
+                let claims = auth_result.as_ref().map(|result| &result.claims);
+                match prefix.apply(&s3_key, subject.as_deref(), claims) {
+                    Ok(key) => key,
+                    Err(e) => {
+                        warn!("Upload to {} rejected: {}", path, e);
+                        let (status, message) = match e {
+                            KeyPrefixError::Escape(_) => (
+                                StatusCode::BAD_REQUEST,
+                                "Invalid key: key leaves its prefix",
+                            ),
+                            _ => (StatusCode::FORBIDDEN, "Forbidden"),
+                        };
+                        return Ok(categorized(
+                            Response::builder()
+                                .status(status)
+                                .header("Content-Type", "text/plain")
+                                .body(message.to_string())
+                                .expect("Failed to build error response"),
+                            e.category(),
+                        ));
                     }
-                    Err(AuthError::InvalidSignature) | Err(AuthError::InvalidToken(_)) => {
-                        warn!("Invalid token for {}", path);
-                        return Ok(Response::builder()
-                            .status(StatusCode::UNAUTHORIZED)
+                }
+            }
+            None => s3_key,
+        };
+
+        // Authorize the upload against the bucket's policy, if any
+        let mut decision_ids = Vec::new();
+        if let Some(authorizer) = authorizers.get(&bucket.name) {
+            let object = ObjectContext::new(
+                &s3_key,
+                content_length(&req),
+                req.headers()
+                    .get("content-type")
+                    .and_then(|v| v.to_str().ok()),
+                req.uri().query(),
+            );
+            let authz_request = build_authz_request(
+                auth_result.as_ref(),
+                claim_mappers.get(&bucket.name).map(Arc::as_ref),
+                &bucket.name,
+                &object,
+            );
+            let authz_started = Instant::now();
+            let (decision, ids) = decision_log::collect(authorizer.authorize(&authz_request)).await;
+            decision_ids = ids;
+            timings.phase(&bucket.name, "authz", authz_started.elapsed());
+            match decision {
+                Ok(true) => {}
+                Ok(false) => {
+                    // Decision IDs join the denial with the policy's decision log
+                    let decisions = match decision_ids.as_slice() {
+                        [] => String::new(),
+                        ids => format!(" (decisions {})", ids.join(", ")),
+                    };
+                    warn!(
+                        "Upload to {} by {} denied by policy{}",
+                        path, authz_request.subject, decisions
+                    );
+                    return Ok(categorized(
+                        Response::builder()
+                            .status(StatusCode::FORBIDDEN)
                             .header("Content-Type", "text/plain")
-                            .header("WWW-Authenticate", "Bearer error=\"invalid_token\"")
-                            .body("Invalid token".to_string())
-                            .expect("Failed to build 401 response"));
-                    }
-                    Err(e) => {
-                        error!("Authentication error: {}", e);
+                            .body("Forbidden".to_string())
+                            .expect("Failed to build 403 response"),
+                        ErrorCategory::Auth,
+                    ));
+                }
+                Err(e) => {
+                    // Fail-closed: the bucket's failure mode rejects the upload
+                    error!("Authorization of upload to {} failed: {}", path, e);
+                    return Ok(categorized(
+                        Response::builder()
+                            .status(StatusCode::INTERNAL_SERVER_ERROR)
+                            .header("Content-Type", "text/plain")
+                            .body("Authorization error".to_string())
+                            .expect("Failed to build error response"),
+                        e.category(),
+                    ));
+                }
+            }
+        }
+
+        // Authorized, but not an operation the proxy implements
+        if let Some(operation) = unsupported_put(&req) {
+            warn!("{} to {} rejected: not implemented", operation, path);
+            return Ok(not_implemented_response(operation));
+        }
+
+        // Uploads over their owner's quota are refused before the body is read
+        let quota = quotas.get(&bucket.name).map(|quota| {
+            let claims = auth_result.as_ref().map(|result| &result.claims);
+            (quota, quota.owner(subject.as_deref(), claims))
+        });
+        let mut quota_remaining = None;
+        if let Some((quota, owner)) = &quota {
+            match quota.check(owner, content_length(&req)).await {
+                Ok(remaining) => quota_remaining = remaining,
+                Err(e @ QuotaError::Exceeded { window, .. }) => {
+                    warn!("Upload to {} by {} rejected: {}", path, owner, e);
+                    metrics::record_quota_rejection(&bucket.name, window.as_str());
+                    return Ok(quota_exceeded_response(&e));
+                }
+                Err(e) => {
+                    // Fail-closed: usage cannot be counted without the store
+                    error!("Quota check of upload to {} failed: {}", path, e);
+                    return Ok(Response::builder()
+                        .status(StatusCode::SERVICE_UNAVAILABLE)
+                        .header("Content-Type", "text/plain")
+                        .body("Quota unavailable".to_string())
+                        .expect("Failed to build 503 response"));
+                }
+            }
+        }
+
+        // Uploads sent with an ID report their progress until they finish
+        let session = match req.headers().get(UPLOAD_ID_HEADER) {
+            Some(upload_id) => {
+                let started = upload_id.to_str().ok().and_then(|upload_id| {
+                    sessions.start(
+                        upload_id,
+                        &bucket.name,
+                        &s3_key,
+                        subject.as_deref(),
+                        content_length(&req),
+                    )
+                });
+                match started {
+                    Some(session) => Some(session),
+                    None => {
+                        warn!("Upload to {} has an invalid or reused upload ID", path);
                         return Ok(Response::builder()
-                            .status(StatusCode::UNAUTHORIZED)
+                            .status(StatusCode::CONFLICT)
                             .header("Content-Type", "text/plain")
-                            .body(format!("Authentication failed: {}", e))
-                            .expect("Failed to build 401 response"));
+                            .body("Upload ID is invalid or already in use".to_string())
+                            .expect("Failed to build 409 response"));
                     }
                 }
-            } else {
-                // Fail-closed: auth enabled but no JWT config means deny access
-                error!("Auth enabled but no JWT config for bucket {}", bucket.name);
-                return Ok(Response::builder()
-                    .status(StatusCode::INTERNAL_SERVER_ERROR)
-                    .header("Content-Type", "text/plain")
-                    .body("Server configuration error".to_string())
-                    .expect("Failed to build error response"));
             }
-        }
+            None => None,
+        };
 
         // Extract content type from request
         let content_type = req
@@ -434,91 +2433,455 @@ async fn handle_request(
             .and_then(|v| v.to_str().ok())
             .map(|s| s.to_string());
 
-        // Collect the request body
-        let body = req.into_body();
-        let bytes_result = body.collect().await;
-        let body_bytes = match bytes_result {
-            Ok(collected) => collected.to_bytes(),
+        // Bodies the client already encoded are never compressed again;
+        // aws-chunked is only transfer framing, removed as the body is read
+        let content_encoded = req
+            .headers()
+            .get_all("content-encoding")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .any(|encoding| !encoding.trim().eq_ignore_ascii_case("aws-chunked"));
+        let chunked_decoder = match aws_chunked::Decoder::from_headers(req.headers()) {
+            Ok(decoder) => decoder,
             Err(e) => {
-                error!("Failed to read upload body: {}", e);
+                warn!("Upload to {} rejected: {}", path, e);
                 return Ok(Response::builder()
                     .status(StatusCode::BAD_REQUEST)
                     .header("Content-Type", "text/plain")
-                    .body(format!("Failed to read body: {}", e))
-                    .expect("Failed to build error response"));
+                    .body(e.to_string())
+                    .expect("Failed to build 400 response"));
             }
         };
 
-        info!(
-            "Upload request to {}: {} bytes received",
-            path,
-            body_bytes.len()
-        );
-
-        // Create S3 client and upload
-        let s3_config = S3ClientConfig {
-            bucket: bucket.s3.bucket.clone(),
-            region: bucket.s3.region.clone(),
-            endpoint: bucket.s3.endpoint.clone(),
-            access_key: bucket.s3.access_key.clone(),
-            secret_key: bucket.s3.secret_key.clone(),
-            retry: None,
-            timeout: None,
-        };
+        // Conditional write headers are forwarded for the backend to decide;
+        // object tags come from the caller's claims, never from the request
+        let mut upload_headers = conditional_headers(req.headers());
+        let tagging = auth_result
+            .as_ref()
+            .zip(claim_mappers.get(&bucket.name))
+            .and_then(|(result, mapper)| mapper.tagging(&result.subject, &result.claims));
+        if let Some(tagging) = tagging {
+            upload_headers.push(("x-amz-tagging".to_string(), tagging));
+        }
 
-        let s3_client = match S3Client::new(s3_config) {
-            Ok(client) => client,
-            Err(e) => {
-                error!("Failed to create S3 client: {}", e);
-                return Ok(Response::builder()
-                    .status(StatusCode::INTERNAL_SERVER_ERROR)
-                    .header("Content-Type", "text/plain")
-                    .body("Failed to create S3 client".to_string())
-                    .expect("Failed to build error response"));
+        // Refuse content types the bucket does not accept before reading the body
+        let content_type_policy = content_types.get(&bucket.name);
+        if let Some(policy) = content_type_policy {
+            let result = policy.check_declared(content_type.as_deref());
+            if let Some(response) = content_type_violation(policy, &bucket.name, &path, result) {
+                return Ok(response);
             }
-        };
+        }
 
-        // Extract the S3 key from the path (remove the path prefix)
-        let s3_key = path
-            .strip_prefix(&bucket.path_prefix)
-            .unwrap_or(&path)
-            .trim_start_matches('/');
+        let storage_class_router = storage_classes.get(&bucket.name);
 
-        // Validate S3 key is not empty
-        if s3_key.is_empty() {
-            warn!("Empty S3 key for path: {}", path);
+        let Some(backend) = backends.get(&bucket.name) else {
+            error!("No storage backend for bucket {}", bucket.name);
             return Ok(Response::builder()
-                .status(StatusCode::BAD_REQUEST)
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
                 .header("Content-Type", "text/plain")
-                .body("Invalid key: object key cannot be empty".to_string())
+                .body("Storage backend unavailable".to_string())
                 .expect("Failed to build error response"));
+        };
+
+        // Buckets with regions store the upload in the region picked for the
+        // client, tagged with its name
+        let client_ip = req.extensions().get::<SocketAddr>().map(|addr| addr.ip());
+        let routed = region_routers
+            .get(&bucket.name)
+            .map(|router| (router, router.select(client_ip)));
+        let backend = match routed {
+            Some((router, region)) => {
+                let tagging = router.tagging(region);
+                match upload_headers
+                    .iter_mut()
+                    .find(|(name, _)| name == "x-amz-tagging")
+                {
+                    Some((_, tags)) => *tags = format!("{}&{}", tags, tagging),
+                    None => upload_headers.push(("x-amz-tagging".to_string(), tagging)),
+                }
+                metrics::record_region_upload(&bucket.name, region.name());
+                region.backend()
+            }
+            None => backend,
+        };
+
+        // Track the upload so the shutdown report can account for it
+        let upload = drain.start_upload();
+
+        // Unsized or large bodies go to the bucket's spill buffer, if any.
+        // Transforms need the whole body in memory, so they are never spilled.
+        let transform = transforms.get(&bucket.name);
+        let declared_length = content_length(&req);
+        let spill_buffer = spill
+            .get(&bucket.name)
+            .filter(|buffer| transform.is_none() && buffer.should_spill(declared_length));
+
+        // Large bodies of buckets with flow control are stored as they
+        // arrive instead, unless their content has to be sniffed first
+        let sniffed = content_type_policy.is_some_and(|policy| policy.sniff_bytes().is_some());
+        if let Some(message) = oversized_refusal(declared_length, transform.is_some(), sniffed) {
+            warn!("Upload to {} rejected: {}", path, message);
+            return Ok(entity_too_large_response(&message));
         }
 
-        // Upload to S3
-        match s3_client
-            .put_object(
-                s3_key,
-                body_bytes,
-                content_type.as_deref(),
-            )
-            .await
-        {
-            Ok(response) => {
-                info!("Upload successful, ETag: {}", response.etag);
-                return Ok(Response::builder()
+        // S3 stores at most 5GB with a single PUT, so larger bodies are
+        // always split into a multipart upload as they arrive
+        let default_flow;
+        let flow_control = match flow_controls.get(&bucket.name) {
+            Some(flow) => Some(flow.as_ref()),
+            None if declared_length.is_some_and(|length| length > MAX_PUT_SIZE) => {
+                default_flow = FlowControl::new(&bucket.name, &FlowControlConfig::default());
+                Some(&default_flow)
+            }
+            None => None,
+        }
+        .filter(|flow| {
+            transform.is_none() && !sniffed && flow.applies(declared_length, &bucket.upload)
+        });
+
+        // Read the request body, capped for anonymous uploads, transforms,
+        // quotas and single PUTs, and checked against its declared length
+        let anonymous_limit = anonymous_policy.map(|policy| policy.max_size());
+        let single_put = flow_control.is_none() && spill_buffer.is_none();
+        let limit = anonymous_limit
+            .into_iter()
+            .chain(transform.map(|transform| transform.max_input_bytes()))
+            .chain(quota_remaining)
+            .chain(single_put.then_some(MAX_PUT_SIZE))
+            .min()
+            .unwrap_or(u64::MAX);
+        let anonymous_cap = anonymous_limit == Some(limit);
+        let body = Limited::new(
+            ProgressBody::new(
+                LengthCheckedBody::new(
+                    AwsChunkedBody::new(req.into_body(), chunked_decoder),
+                    declared_length,
+                ),
+                session.as_ref(),
+            ),
+            usize::try_from(limit).unwrap_or(usize::MAX),
+        );
+        let body_started = Instant::now();
+        let (result, body_len, s3_elapsed) = match (flow_control, spill_buffer) {
+            (Some(flow), _) => {
+                let body_len = declared_length.unwrap_or_default();
+                info!(
+                    "Upload request to {}: forwarding {} bytes as they arrive",
+                    path, body_len
+                );
+                let mut headers = upload_headers.clone();
+                headers.extend(storage_class_header(
+                    storage_class_router,
+                    &s3_key,
+                    content_type.as_deref(),
+                    body_len,
+                ));
+                let result = flow
+                    .forward(
+                        body,
+                        backend.as_ref(),
+                        &s3_key,
+                        content_type.as_deref(),
+                        &headers,
+                        &bucket.upload,
+                        session.as_ref(),
+                    )
+                    .await;
+                // Receiving and storing overlap, so it all counts as S3 time
+                let s3_elapsed = body_started.elapsed();
+                timings.phase(&bucket.name, "s3", s3_elapsed);
+                match result {
+                    Ok((object, size)) => (Ok(object), size, s3_elapsed),
+                    Err(ForwardError::Body(e)) => {
+                        return Ok(body_error_response(
+                            &path,
+                            &bucket.name,
+                            e,
+                            limit,
+                            anonymous_cap,
+                        ))
+                    }
+                    Err(ForwardError::Upload(e)) => (Err(e), body_len, s3_elapsed),
+                }
+            }
+            (None, Some(buffer)) => {
+                let spilled = match buffer.spill(body).await {
+                    Ok(spilled) => spilled,
+                    Err(SpillError::Body(e)) => {
+                        return Ok(body_error_response(
+                            &path,
+                            &bucket.name,
+                            e,
+                            limit,
+                            anonymous_cap,
+                        ))
+                    }
+                    Err(e @ SpillError::QuotaExceeded { .. }) => {
+                        warn!("Upload to {} rejected: {}", path, e);
+                        return Ok(Response::builder()
+                            .status(StatusCode::SERVICE_UNAVAILABLE)
+                            .header("Content-Type", "text/plain")
+                            .header("Retry-After", "1")
+                            .body("Spill buffer full".to_string())
+                            .expect("Failed to build 503 response"));
+                    }
+                    Err(e) => {
+                        error!("Failed to spill upload body: {}", e);
+                        return Ok(Response::builder()
+                            .status(StatusCode::INTERNAL_SERVER_ERROR)
+                            .header("Content-Type", "text/plain")
+                            .body(format!("Failed to buffer body: {}", e))
+                            .expect("Failed to build error response"));
+                    }
+                };
+                info!(
+                    "Upload request to {}: {} bytes spilled to disk",
+                    path,
+                    spilled.size()
+                );
+                if let Some(session) = &session {
+                    session.uploading();
+                }
+                if let Some(policy) = content_type_policy {
+                    if let Some(limit) = policy.sniff_bytes() {
+                        let result = match spilled.head(limit) {
+                            Ok(head) => policy.check_content(content_type.as_deref(), &head),
+                            Err(e) => {
+                                error!("Failed to read spilled upload body: {}", e);
+                                return Ok(Response::builder()
+                                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                                    .header("Content-Type", "text/plain")
+                                    .body(format!("Failed to buffer body: {}", e))
+                                    .expect("Failed to build error response"));
+                            }
+                        };
+                        if let Some(response) =
+                            content_type_violation(policy, &bucket.name, &path, result)
+                        {
+                            return Ok(response);
+                        }
+                    }
+                }
+                let mut headers = upload_headers.clone();
+                headers.extend(storage_class_header(
+                    storage_class_router,
+                    &s3_key,
+                    content_type.as_deref(),
+                    spilled.size(),
+                ));
+                timings.phase(&bucket.name, "body", body_started.elapsed());
+                let s3_started = Instant::now();
+                let result = spilled
+                    .upload_with_progress(
+                        backend.as_ref(),
+                        &s3_key,
+                        content_type.as_deref(),
+                        &headers,
+                        &bucket.upload,
+                        session.as_ref(),
+                    )
+                    .await;
+                let s3_elapsed = s3_started.elapsed();
+                timings.phase(&bucket.name, "s3", s3_elapsed);
+                (result, spilled.size(), s3_elapsed)
+            }
+            (None, None) => {
+                // Received into a pooled buffer, reused once the upload is done
+                let received = match buffers.collect(body, declared_length).await {
+                    Ok(received) => received,
+                    Err(e) => {
+                        return Ok(body_error_response(
+                            &path,
+                            &bucket.name,
+                            e,
+                            limit,
+                            anonymous_cap,
+                        ))
+                    }
+                };
+                let body_bytes = received.bytes();
+                info!(
+                    "Upload request to {}: {} bytes received",
+                    path,
+                    body_bytes.len()
+                );
+                if let Some(session) = &session {
+                    session.uploading();
+                }
+                if let Some(policy) = content_type_policy {
+                    let result = policy.check_content(content_type.as_deref(), &body_bytes);
+                    if let Some(response) =
+                        content_type_violation(policy, &bucket.name, &path, result)
+                    {
+                        return Ok(response);
+                    }
+                }
+                let body_bytes = match transform {
+                    Some(transform) => match transform.apply(body_bytes).await {
+                        Ok(TransformOutcome::Body(body)) => {
+                            metrics::record_transform(&bucket.name, "transformed");
+                            body
+                        }
+                        Ok(TransformOutcome::Rejected(reason)) => {
+                            warn!("Upload to {} rejected by transform: {}", path, reason);
+                            metrics::record_transform(&bucket.name, "rejected");
+                            return Ok(Response::builder()
+                                .status(StatusCode::UNPROCESSABLE_ENTITY)
+                                .header("Content-Type", "text/plain")
+                                .body(format!("Rejected: {}", reason))
+                                .expect("Failed to build 422 response"));
+                        }
+                        Err(e) => {
+                            error!("Transform of upload to {} failed: {}", path, e);
+                            metrics::record_transform(&bucket.name, "failed");
+                            return Ok(Response::builder()
+                                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                                .header("Content-Type", "text/plain")
+                                .body(format!("Transform failed: {}", e))
+                                .expect("Failed to build error response"));
+                        }
+                    },
+                    None => body_bytes,
+                };
+                let body_len = body_bytes.len() as u64;
+                let compressor = compressors.get(&bucket.name).filter(|compressor| {
+                    !content_encoded
+                        && compressor.should_compress(content_type.as_deref(), body_len)
+                });
+                let (body_bytes, mut headers) = match compressor {
+                    Some(compressor) => match compressor.compress(body_bytes.clone()).await {
+                        Ok(compressed) if (compressed.len() as u64) < body_len => {
+                            debug!(
+                                "Compressed upload to {} from {} to {} bytes",
+                                path,
+                                body_len,
+                                compressed.len()
+                            );
+                            metrics::record_compression(
+                                &bucket.name,
+                                body_len,
+                                compressed.len() as u64,
+                            );
+                            (compressed, compressor.headers(body_len))
+                        }
+                        Ok(_) => (body_bytes, Vec::new()),
+                        Err(e) => {
+                            // Compression only saves storage; store the body as sent
+                            warn!("Compression of upload to {} failed: {}", path, e);
+                            (body_bytes, Vec::new())
+                        }
+                    },
+                    None => (body_bytes, Vec::new()),
+                };
+                headers.extend_from_slice(&upload_headers);
+                headers.extend(storage_class_header(
+                    storage_class_router,
+                    &s3_key,
+                    content_type.as_deref(),
+                    body_len,
+                ));
+                timings.phase(&bucket.name, "body", body_started.elapsed());
+                let s3_started = Instant::now();
+                let result = backend
+                    .put_object_with_headers(&s3_key, body_bytes, content_type.as_deref(), &headers)
+                    .await;
+                let s3_elapsed = s3_started.elapsed();
+                timings.phase(&bucket.name, "s3", s3_elapsed);
+                (result, body_len, s3_elapsed)
+            }
+        };
+
+        // Report the storage backend's outcome
+        if let Some((router, region)) = routed {
+            router.observe(region, s3_elapsed, result.is_ok());
+        }
+        metrics::record_phase_duration(&bucket.name, "total", started.elapsed().as_secs_f64());
+        timings.set_size(body_len);
+        let tenant = tenant_labels
+            .as_ref()
+            .map(|labels| labels.label(auth_result.as_ref().map(|result| &result.claims)));
+        if let Some(ref tenant) = tenant {
+            metrics::record_tenant_upload(&bucket.name, tenant, result.is_ok(), body_len);
+        }
+        match result {
+            Ok(object) => {
+                info!("Upload successful, ETag: {}", object.etag);
+                upload.complete(body_len);
+                if let Some(session) = session {
+                    session.complete();
+                }
+                if let Some((quota, owner)) = &quota {
+                    if let Err(e) = quota.record(owner, body_len).await {
+                        warn!(
+                            "Failed to count upload to {} against its quota: {}",
+                            path, e
+                        );
+                    }
+                }
+                metrics::record_upload_success(&bucket.name, body_len);
+                if let Some(store) = receipts.get(&bucket.name) {
+                    store
+                        .record(
+                            Receipt::new(
+                                &bucket.name,
+                                &s3_key,
+                                &object.etag,
+                                body_len,
+                                subject.as_deref(),
+                            )
+                            .with_actor(auth_result.as_ref().and_then(AuthResult::actor))
+                            .with_decision_ids(decision_ids),
+                        )
+                        .await;
+                }
+                if let Some(notifier) = &notifier {
+                    // Delivery failures are logged by the notifier and never fail the upload
+                    let _ = notifier.notify(UploadEvent::completed(
+                        &bucket.name,
+                        &s3_key,
+                        &object.etag,
+                        body_len,
+                        subject.as_deref(),
+                    ));
+                }
+                // Pass through the backend headers S3 clients rely on
+                let mut response = Response::builder()
                     .status(StatusCode::OK)
                     .header("Content-Type", "text/plain")
-                    .header("ETag", &response.etag)
+                    .header("ETag", &object.etag);
+                for (name, value) in object.metadata.response_headers() {
+                    response = response.header(name, value);
+                }
+                if let Some(signer) = signers.get(&bucket.name) {
+                    let signature = signer.sign(&SignedObject {
+                        bucket: &bucket.s3.bucket,
+                        key: &s3_key,
+                        etag: &object.etag,
+                        size: body_len,
+                        timestamp: chrono::Utc::now().timestamp(),
+                    });
+                    response = response.header(SIGNATURE_HEADER, signature);
+                }
+                if let Some(headers) = response_headers.get(&bucket.name) {
+                    let object = UploadedObject {
+                        bucket: &bucket.s3.bucket,
+                        key: &s3_key,
+                        etag: &object.etag,
+                        size: body_len,
+                    };
+                    for (name, value) in headers.render(&object) {
+                        response = response.header(name, value);
+                    }
+                }
+                return Ok(response
                     .body("Upload successful".to_string())
                     .expect("Failed to build upload response"));
             }
             Err(e) => {
-                error!("S3 upload failed: {}", e);
-                return Ok(Response::builder()
-                    .status(StatusCode::INTERNAL_SERVER_ERROR)
-                    .header("Content-Type", "text/plain")
-                    .body(format!("Upload failed: {}", e))
-                    .expect("Failed to build error response"));
+                upload.fail();
+                return Ok(upload_error_response(&path, &bucket.name, &e));
             }
         }
     }
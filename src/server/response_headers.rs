@@ -0,0 +1,227 @@
+//! Configured upload response headers
+//!
+//! `upload.response_headers` adds headers to successful upload responses,
+//! so clients don't have to rebuild URLs of the stored object themselves.
+//! Values may use placeholders in braces, like `key_prefix` templates:
+//!
+//! - `{bucket}` - backend bucket the object was stored in
+//! - `{key}` - object key (with any `key_prefix`), percent-encoded with
+//!   `/` kept
+//! - `{etag}` - ETag of the object, without quotes
+//! - `{size}` - object size in bytes
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::server::response_headers::{ResponseHeaders, UploadedObject};
+//! use std::collections::HashMap;
+//!
+//! let config = HashMap::from([(
+//!     "X-Object-Url".to_string(),
+//!     "https://cdn.example.com/{key}".to_string(),
+//! )]);
+//! let headers = ResponseHeaders::new(&config).unwrap();
+//!
+//! let rendered = headers.render(&UploadedObject {
+//!     bucket: "uploads",
+//!     key: "photos/summer 2024.jpg",
+//!     etag: "\"5d41402abc4b2a76b9719d911017c592\"",
+//!     size: 5,
+//! });
+//! assert_eq!(rendered[0].0, "x-object-url");
+//! assert_eq!(rendered[0].1, "https://cdn.example.com/photos/summer%202024.jpg");
+//! ```
+
+use crate::authz::claims::{parse_template, Segment};
+use crate::authz::AuthzError;
+use crate::s3::encode_s3_key;
+use hyper::header::{HeaderName, HeaderValue};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Headers the proxy sets itself, which the configuration may not replace
+const RESERVED: &[&str] = &[
+    "content-type",
+    "content-length",
+    "etag",
+    crate::server::signing::SIGNATURE_HEADER,
+];
+
+/// Response header configuration errors
+#[derive(Error, Debug, PartialEq)]
+pub enum ResponseHeaderError {
+    #[error("Invalid header name '{0}'")]
+    InvalidName(String),
+
+    #[error("Header '{0}' is set by the proxy and can't be configured")]
+    Reserved(String),
+
+    #[error("Invalid template for header '{name}': {message}")]
+    InvalidTemplate { name: String, message: String },
+}
+
+/// Stored object the headers describe
+#[derive(Debug, Clone, Copy)]
+pub struct UploadedObject<'a> {
+    pub bucket: &'a str,
+    pub key: &'a str,
+    pub etag: &'a str,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Variable {
+    Bucket,
+    Key,
+    Etag,
+    Size,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Variable(Variable),
+}
+
+/// Response headers of one bucket
+#[derive(Debug, Clone)]
+pub struct ResponseHeaders {
+    headers: Vec<(HeaderName, Vec<Part>)>,
+}
+
+impl ResponseHeaders {
+    /// Parse `upload.response_headers`
+    ///
+    /// Fails on invalid or reserved header names, unbalanced braces and
+    /// unknown placeholders.
+    pub fn new(config: &HashMap<String, String>) -> Result<Self, ResponseHeaderError> {
+        let mut headers = Vec::with_capacity(config.len());
+        for (name, template) in config {
+            let header = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| ResponseHeaderError::InvalidName(name.clone()))?;
+            if RESERVED.contains(&header.as_str()) {
+                return Err(ResponseHeaderError::Reserved(name.clone()));
+            }
+            let parts =
+                parse(template).map_err(|message| ResponseHeaderError::InvalidTemplate {
+                    name: name.clone(),
+                    message,
+                })?;
+            headers.push((header, parts));
+        }
+        // Deterministic order, whatever the map's
+        headers.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+        Ok(Self { headers })
+    }
+
+    /// Headers for `object`
+    ///
+    /// A value that isn't a valid header value once filled in (e.g. a
+    /// literal with control characters) is left out.
+    pub fn render(&self, object: &UploadedObject<'_>) -> Vec<(HeaderName, HeaderValue)> {
+        self.headers
+            .iter()
+            .filter_map(|(name, parts)| {
+                let value: String = parts
+                    .iter()
+                    .map(|part| match part {
+                        Part::Literal(text) => text.clone(),
+                        Part::Variable(Variable::Bucket) => object.bucket.to_string(),
+                        Part::Variable(Variable::Key) => encode_s3_key(object.key),
+                        Part::Variable(Variable::Etag) => object.etag.trim_matches('"').to_string(),
+                        Part::Variable(Variable::Size) => object.size.to_string(),
+                    })
+                    .collect();
+                let value = HeaderValue::from_str(&value).ok()?;
+                Some((name.clone(), value))
+            })
+            .collect()
+    }
+}
+
+/// Split a template into literals and `{placeholders}`
+fn parse(template: &str) -> Result<Vec<Part>, String> {
+    let segments = parse_template(template).map_err(|e| match e {
+        AuthzError::ConfigError(message) => message,
+        e => e.to_string(),
+    })?;
+    segments
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Literal(text) => Ok(Part::Literal(text)),
+            Segment::Claim(name) => match name.as_str() {
+                "bucket" => Ok(Part::Variable(Variable::Bucket)),
+                "key" => Ok(Part::Variable(Variable::Key)),
+                "etag" => Ok(Part::Variable(Variable::Etag)),
+                "size" => Ok(Part::Variable(Variable::Size)),
+                other => Err(format!("unknown placeholder '{{{}}}'", other)),
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> Result<ResponseHeaders, ResponseHeaderError> {
+        let config = pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        ResponseHeaders::new(&config)
+    }
+
+    const OBJECT: UploadedObject<'static> = UploadedObject {
+        bucket: "uploads",
+        key: "a/b c.txt",
+        etag: "\"abc\"",
+        size: 42,
+    };
+
+    #[test]
+    fn test_render_placeholders() {
+        let headers = headers(&[
+            ("X-Object-Url", "https://cdn.example.com/{ bucket }/{key}"),
+            ("X-Upload-Info", "etag={etag};size={size}"),
+            ("Cache-Control", "no-store"),
+        ])
+        .unwrap();
+
+        let rendered = headers.render(&OBJECT);
+
+        let rendered: Vec<(&str, &str)> = rendered
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.to_str().unwrap()))
+            .collect();
+        assert_eq!(
+            rendered,
+            vec![
+                ("cache-control", "no-store"),
+                (
+                    "x-object-url",
+                    "https://cdn.example.com/uploads/a/b%20c.txt"
+                ),
+                ("x-upload-info", "etag=abc;size=42"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        assert_eq!(
+            headers(&[("bad name", "x")]).unwrap_err(),
+            ResponseHeaderError::InvalidName("bad name".into())
+        );
+        assert_eq!(
+            headers(&[("ETag", "x")]).unwrap_err(),
+            ResponseHeaderError::Reserved("ETag".into())
+        );
+        for template in ["{owner}", "{key", "key}", "{}"] {
+            assert!(matches!(
+                headers(&[("X-A", template)]),
+                Err(ResponseHeaderError::InvalidTemplate { .. })
+            ));
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! Embedded mode: the upload proxy as a `tower` service
+//!
+//! [`UploadService`] handles requests exactly like the standalone server
+//! (bucket routing, authentication, authorization, upload policies and
+//! storage backends, all built from the same [`Config`]) so applications
+//! can mount it inside their own HTTP server instead of running the binary.
+//!
+//! Only request handling is embedded. The listener, TLS, the admin API,
+//! the metrics server, orphaned multipart reconciliation and graceful
+//! draining stay with the host application; `server.*`, `admin` and
+//! `metrics` settings are ignored.
+//!
+//! The client address is read from a `SocketAddr` request extension (used
+//! for anonymous upload rate limits and the request span) and client
+//! certificates from a [`PeerCertificates`] extension (needed for mTLS
+//! authentication); insert them from the host server if required.
+//!
+//! # Example
+//!
+//! With axum, route the bucket prefixes to the service. `route_service`
+//! and `fallback_service` keep the full request path, which bucket routing
+//! needs (`nest_service` strips the prefix).
+//!
+//! ```ignore
+//! let uploads = mizuchi_uploadr::layer(config).await?;
+//! let app = axum::Router::new()
+//!     .route("/api/health", axum::routing::get(|| async { "ok" }))
+//!     .route_service("/uploads/{*key}", uploads);
+//! ```
+//!
+//! [`PeerCertificates`]: crate::server::tls::PeerCertificates
+
+use crate::config::Config;
+use crate::server::builder::{Overrides, ServerBuilder};
+use crate::server::pingora::{self, ServerState};
+use crate::server::ServerError;
+use bytes::Bytes;
+use http_body_util::BodyExt;
+use hyper::body::{Body, Frame, SizeHint};
+use hyper::{Request, Response, StatusCode};
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Mutex, PoisonError};
+use std::task::{Context, Poll};
+use tracing::error;
+
+/// Build an [`UploadService`] from `config`
+///
+/// Shorthand for [`UploadService::new`].
+pub async fn layer(config: Config) -> Result<UploadService, ServerError> {
+    UploadService::new(config).await
+}
+
+/// The upload proxy as a `tower` service
+///
+/// Clones share buckets, backends and caches.
+#[derive(Clone)]
+pub struct UploadService {
+    state: ServerState,
+}
+
+impl UploadService {
+    /// Build the service's backends, authenticators and policies from `config`
+    pub async fn new(config: Config) -> Result<Self, ServerError> {
+        Self::with_overrides(config, Overrides::default()).await
+    }
+
+    /// Configure the service in code, optionally with custom
+    /// authenticators, authorizers and storage backends
+    ///
+    /// The builder's listener settings are ignored, as with [`new`](Self::new).
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::default()
+    }
+
+    pub(crate) async fn with_overrides(
+        config: Config,
+        overrides: Overrides,
+    ) -> Result<Self, ServerError> {
+        let state = ServerState::from_config_with_overrides(&config, &overrides)?;
+        state.check_authz_health().await;
+        Ok(Self { state })
+    }
+}
+
+impl<B> tower_service::Service<Request<B>> for UploadService
+where
+    B: Body<Data = Bytes> + Send + 'static,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    type Response = Response<String>;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<String>, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let state = self.state.clone();
+        let req = req.map(|body| SyncBody::new(body).boxed());
+        Box::pin(async move {
+            Ok(match pingora::serve_request(req, state).await {
+                Ok(response) => response,
+                Err(e) => {
+                    error!("Failed to handle embedded request: {}", e);
+                    Response::builder()
+                        .status(StatusCode::INTERNAL_SERVER_ERROR)
+                        .header("Content-Type", "text/plain")
+                        .body("Internal Server Error".to_string())
+                        .expect("Failed to build error response")
+                }
+            })
+        })
+    }
+}
+
+/// Make a `Send` body `Sync`
+///
+/// Handlers keep references to the request across awaits, so the boxed
+/// body must be `Sync`; bodies of other frameworks (such as axum's) often
+/// are not. Polling goes through `Mutex::get_mut` and never locks.
+struct SyncBody<B> {
+    inner: Mutex<Pin<Box<B>>>,
+}
+
+impl<B> SyncBody<B> {
+    fn new(body: B) -> Self {
+        Self {
+            inner: Mutex::new(Box::pin(body)),
+        }
+    }
+}
+
+impl<B> Body for SyncBody<B>
+where
+    B: Body<Data = Bytes>,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    type Data = Bytes;
+    type Error = std::io::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        let inner = self
+            .get_mut()
+            .inner
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        inner
+            .as_mut()
+            .poll_frame(cx)
+            .map_err(|e| std::io::Error::other(e.into()))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner
+            .lock()
+            .map(|inner| inner.is_end_stream())
+            .unwrap_or(false)
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner
+            .lock()
+            .map(|inner| inner.size_hint())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,267 @@
+//! Graceful shutdown and drain reporting
+//!
+//! When the server receives a shutdown signal it stops accepting connections
+//! and drains in-flight requests. [`DrainTracker`] counts what happens to
+//! uploads during that window, and [`ShutdownReport`] summarizes it so
+//! operators can confirm a deploy didn't drop customer uploads.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::server::shutdown::DrainTracker;
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! let tracker = Arc::new(DrainTracker::new());
+//! let upload = tracker.start_upload();
+//!
+//! tracker.begin_drain();
+//! upload.complete(1024);
+//!
+//! let report = tracker.report(Duration::from_millis(12), false);
+//! assert_eq!(report.uploads_completed, 1);
+//! assert_eq!(report.bytes_flushed, 1024);
+//! ```
+
+use crate::notifications::SERVER_SHUTDOWN;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Upload activity counters for the drain window
+///
+/// Uploads are always tracked; only outcomes after [`DrainTracker::begin_drain`]
+/// are counted in the report.
+#[derive(Debug, Default)]
+pub struct DrainTracker {
+    draining: AtomicBool,
+    in_flight: AtomicU64,
+    completed: AtomicU64,
+    aborted: AtomicU64,
+    bytes_flushed: AtomicU64,
+    multipart_persisted: AtomicU64,
+    multipart_aborted: AtomicU64,
+}
+
+impl DrainTracker {
+    /// Create a tracker in the serving (not draining) state
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark the start of the drain window
+    pub fn begin_drain(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the server is draining
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Number of uploads currently in progress
+    pub fn in_flight(&self) -> u64 {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Register an upload; the returned guard records its outcome
+    pub fn start_upload(self: &Arc<Self>) -> UploadGuard {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        UploadGuard {
+            tracker: Arc::clone(self),
+            finished: false,
+        }
+    }
+
+    /// Record a multipart session saved for resumption during drain
+    pub fn record_multipart_persisted(&self) {
+        if self.is_draining() {
+            self.multipart_persisted.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Record a multipart session aborted during drain
+    pub fn record_multipart_aborted(&self) {
+        if self.is_draining() {
+            self.multipart_aborted.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Build the shutdown report
+    ///
+    /// Uploads still in flight are counted as aborted, since the process is
+    /// about to exit underneath them.
+    pub fn report(&self, elapsed: Duration, drain_timed_out: bool) -> ShutdownReport {
+        ShutdownReport {
+            event: SERVER_SHUTDOWN.to_string(),
+            uploads_completed: self.completed.load(Ordering::SeqCst),
+            uploads_aborted: self.aborted.load(Ordering::SeqCst) + self.in_flight(),
+            bytes_flushed: self.bytes_flushed.load(Ordering::SeqCst),
+            multipart_persisted: self.multipart_persisted.load(Ordering::SeqCst),
+            multipart_aborted: self.multipart_aborted.load(Ordering::SeqCst),
+            drain_timed_out,
+            duration_ms: elapsed.as_millis() as u64,
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn finish(&self, bytes: Option<u64>) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        if !self.is_draining() {
+            return;
+        }
+        match bytes {
+            Some(bytes) => {
+                self.completed.fetch_add(1, Ordering::SeqCst);
+                self.bytes_flushed.fetch_add(bytes, Ordering::SeqCst);
+            }
+            None => {
+                self.aborted.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+/// Tracks a single in-flight upload
+///
+/// Dropping the guard without calling [`UploadGuard::complete`] counts the
+/// upload as aborted (e.g. the connection was cut mid-request).
+#[must_use = "dropping the guard immediately records the upload as aborted"]
+pub struct UploadGuard {
+    tracker: Arc<DrainTracker>,
+    finished: bool,
+}
+
+impl UploadGuard {
+    /// Record a successful upload of `bytes` bytes
+    pub fn complete(mut self, bytes: u64) {
+        self.finished = true;
+        self.tracker.finish(Some(bytes));
+    }
+
+    /// Record a failed upload
+    pub fn fail(mut self) {
+        self.finished = true;
+        self.tracker.finish(None);
+    }
+}
+
+impl Drop for UploadGuard {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.tracker.finish(None);
+        }
+    }
+}
+
+/// Summary of what happened while the server drained
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ShutdownReport {
+    pub event: String,
+    /// Uploads that finished successfully during drain
+    pub uploads_completed: u64,
+    /// Uploads that failed, were cut off, or were still running at exit
+    pub uploads_aborted: u64,
+    /// Bytes written to storage by uploads completed during drain
+    pub bytes_flushed: u64,
+    /// Multipart sessions saved for resumption
+    pub multipart_persisted: u64,
+    /// Multipart sessions aborted
+    pub multipart_aborted: u64,
+    /// Whether the drain timeout expired before all requests finished
+    pub drain_timed_out: bool,
+    /// Time from shutdown signal to end of drain
+    pub duration_ms: u64,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ShutdownReport {
+    /// Write the report to the log
+    pub fn log(&self) {
+        if self.uploads_aborted > 0 || self.drain_timed_out {
+            tracing::warn!(
+                uploads_completed = self.uploads_completed,
+                uploads_aborted = self.uploads_aborted,
+                bytes_flushed = self.bytes_flushed,
+                multipart_persisted = self.multipart_persisted,
+                multipart_aborted = self.multipart_aborted,
+                drain_timed_out = self.drain_timed_out,
+                duration_ms = self.duration_ms,
+                "Shutdown report: uploads were aborted during drain"
+            );
+        } else {
+            tracing::info!(
+                uploads_completed = self.uploads_completed,
+                uploads_aborted = self.uploads_aborted,
+                bytes_flushed = self.bytes_flushed,
+                multipart_persisted = self.multipart_persisted,
+                multipart_aborted = self.multipart_aborted,
+                drain_timed_out = self.drain_timed_out,
+                duration_ms = self.duration_ms,
+                "Shutdown report"
+            );
+        }
+    }
+}
+
+/// Wait for SIGINT (Ctrl+C) or, on Unix, SIGTERM
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!(error = %e, "Failed to listen for Ctrl+C");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("Received Ctrl+C, shutting down"),
+        _ = terminate => tracing::info!("Received SIGTERM, shutting down"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uploads_before_drain_not_counted() {
+        let tracker = Arc::new(DrainTracker::new());
+        tracker.start_upload().complete(100);
+        tracker.begin_drain();
+
+        let report = tracker.report(Duration::ZERO, false);
+        assert_eq!(report.uploads_completed, 0);
+        assert_eq!(report.bytes_flushed, 0);
+    }
+
+    #[test]
+    fn test_dropped_and_in_flight_uploads_are_aborted() {
+        let tracker = Arc::new(DrainTracker::new());
+        let dropped = tracker.start_upload();
+        let _still_running = tracker.start_upload();
+        tracker.begin_drain();
+        drop(dropped);
+
+        let report = tracker.report(Duration::ZERO, true);
+        assert_eq!(report.uploads_aborted, 2);
+        assert!(report.drain_timed_out);
+    }
+}
@@ -0,0 +1,107 @@
+//! Slow request log
+//!
+//! Requests taking longer than `logging.slow_request_threshold_ms` are
+//! logged at WARN with the time spent in each phase, for deployments
+//! without a tracing collector. Handlers report phases, the bucket, the
+//! subject and the object size to the request's [`RequestTimings`] as they
+//! go; the log line is written once the response is ready.
+
+use crate::metrics;
+use parking_lot::Mutex;
+use std::time::Duration;
+use tracing::warn;
+
+/// Phase timings and details of one request
+#[derive(Default)]
+pub(crate) struct RequestTimings {
+    inner: Mutex<Details>,
+}
+
+#[derive(Default)]
+struct Details {
+    bucket: Option<String>,
+    subject: Option<String>,
+    size: Option<u64>,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl RequestTimings {
+    /// Record time spent in `phase` of an upload to `bucket`, in the phase
+    /// metrics and for the slow request log
+    pub(crate) fn phase(&self, bucket: &str, phase: &'static str, duration: Duration) {
+        metrics::record_phase_duration(bucket, phase, duration.as_secs_f64());
+        self.inner.lock().phases.push((phase, duration));
+    }
+
+    pub(crate) fn set_bucket(&self, bucket: &str) {
+        self.inner.lock().bucket = Some(bucket.to_string());
+    }
+
+    pub(crate) fn set_subject(&self, subject: &str) {
+        self.inner.lock().subject = Some(subject.to_string());
+    }
+
+    pub(crate) fn set_size(&self, size: u64) {
+        self.inner.lock().size = Some(size);
+    }
+
+    /// Log the request if it took longer than `threshold`
+    pub(crate) fn log_if_slow(
+        &self,
+        method: &str,
+        path: &str,
+        status: Option<u16>,
+        elapsed: Duration,
+        threshold: Duration,
+    ) {
+        if elapsed <= threshold {
+            return;
+        }
+        let details = self.inner.lock();
+        let phase_ms = |name: &str| {
+            details
+                .phases
+                .iter()
+                .find(|(phase, _)| *phase == name)
+                .map(|(_, duration)| duration.as_millis() as u64)
+        };
+        warn!(
+            method,
+            path,
+            status,
+            bucket = details.bucket.as_deref(),
+            subject = details.subject.as_deref(),
+            size = details.size,
+            total_ms = elapsed.as_millis() as u64,
+            auth_ms = phase_ms("auth"),
+            authz_ms = phase_ms("authz"),
+            body_ms = phase_ms("body"),
+            s3_ms = phase_ms("s3"),
+            threshold_ms = threshold.as_millis() as u64,
+            "Slow request"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phases_collected() {
+        let timings = RequestTimings::default();
+        timings.set_bucket("slow-log");
+        timings.phase("slow-log", "auth", Duration::from_millis(5));
+        timings.phase("slow-log", "s3", Duration::from_millis(20));
+
+        let details = timings.inner.lock();
+        assert_eq!(details.bucket.as_deref(), Some("slow-log"));
+        assert_eq!(
+            details.phases,
+            [
+                ("auth", Duration::from_millis(5)),
+                ("s3", Duration::from_millis(20))
+            ]
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! systemd integration: socket activation, readiness and watchdog
+//!
+//! When started by a socket unit, systemd passes the listening sockets as
+//! file descriptors from 3 on (`LISTEN_FDS`, named by `FileDescriptorName=`
+//! in `LISTEN_FDNAMES`), and the server uses them instead of binding
+//! `server.address` (or, with `server.listeners`, the socket named after
+//! each listener); the socket named `admin` is used for the admin API.
+//! With `Type=notify`, [`notify`] tells systemd when the server is ready
+//! and when it starts stopping, and [`spawn_watchdog`] pings it at half of
+//! `WatchdogSec=`. Outside systemd none of the variables are set and all
+//! of this does nothing.
+
+use std::net::TcpListener;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// First file descriptor passed by systemd
+#[cfg(unix)]
+const LISTEN_FDS_START: i32 = 3;
+
+/// Name of the inherited socket used for the admin API
+pub const ADMIN_SOCKET_NAME: &str = "admin";
+
+/// Take the listening sockets passed by systemd, with their names
+///
+/// The activation variables are removed, so sockets are taken once and
+/// child processes do not see them. Descriptors that are not sockets are
+/// skipped.
+#[cfg(unix)]
+pub fn take_listeners() -> Vec<(String, TcpListener)> {
+    use std::os::fd::FromRawFd;
+
+    let fds = listen_fds(
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::env::var("LISTEN_FDNAMES").ok().as_deref(),
+        std::process::id(),
+    );
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+
+    fds.into_iter()
+        .filter_map(|(fd, name)| {
+            // SAFETY: fstat and fcntl only inspect and flag the descriptor
+            let is_socket = unsafe {
+                let mut stat: libc::stat = std::mem::zeroed();
+                libc::fstat(fd, &mut stat) == 0 && stat.st_mode & libc::S_IFMT == libc::S_IFSOCK
+            };
+            if !is_socket {
+                warn!(fd, name = %name, "Ignoring inherited descriptor that is not a socket");
+                return None;
+            }
+            // SAFETY: as above
+            unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+            // SAFETY: systemd hands the descriptor over to this process,
+            // and the variables are removed so it is taken only once
+            Some((name, unsafe { TcpListener::from_raw_fd(fd) }))
+        })
+        .collect()
+}
+
+/// Take the listening sockets passed by systemd (none outside Unix)
+#[cfg(not(unix))]
+pub fn take_listeners() -> Vec<(String, TcpListener)> {
+    Vec::new()
+}
+
+/// Descriptors and names described by the socket activation variables
+///
+/// Empty unless `LISTEN_PID` is this process; unnamed sockets are called
+/// `unknown`, as systemd does.
+#[cfg(unix)]
+fn listen_fds(
+    listen_pid: Option<&str>,
+    listen_fds: Option<&str>,
+    names: Option<&str>,
+    pid: u32,
+) -> Vec<(i32, String)> {
+    if listen_pid.and_then(|p| p.parse::<u32>().ok()) != Some(pid) {
+        return Vec::new();
+    }
+    let count = listen_fds.and_then(|n| n.parse::<i32>().ok()).unwrap_or(0);
+    let mut names = names.unwrap_or_default().split(':');
+    (0..count.max(0))
+        .map(|i| {
+            let name = names.next().filter(|n| !n.is_empty()).unwrap_or("unknown");
+            (LISTEN_FDS_START + i, name.to_string())
+        })
+        .collect()
+}
+
+/// Send `state` (e.g. `READY=1`) to systemd, if it asked for notifications
+pub fn notify(state: &str) {
+    let Some(socket) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    match notify_to(&socket, state) {
+        Ok(()) => debug!(state, "Notified systemd"),
+        Err(e) => warn!(error = %e, state, "Failed to notify systemd"),
+    }
+}
+
+#[cfg(unix)]
+fn notify_to(socket: &std::ffi::OsStr, state: &str) -> std::io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::UnixDatagram;
+
+    let sender = UnixDatagram::unbound()?;
+    let path = socket.as_bytes();
+    #[cfg(target_os = "linux")]
+    if let Some(name) = path.strip_prefix(b"@") {
+        use std::os::linux::net::SocketAddrExt;
+        let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+        return sender.send_to_addr(state.as_bytes(), &addr).map(|_| ());
+    }
+    sender.send_to(state.as_bytes(), socket).map(|_| ())
+}
+
+#[cfg(not(unix))]
+fn notify_to(_socket: &std::ffi::OsStr, _state: &str) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// How often to ping the watchdog, if systemd enabled it for this process
+pub fn watchdog_interval() -> Option<Duration> {
+    watchdog_interval_from(
+        std::env::var("WATCHDOG_USEC").ok().as_deref(),
+        std::env::var("WATCHDOG_PID").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+fn watchdog_interval_from(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    if let Some(pid) = pid {
+        if pid.parse::<u32>().ok() != Some(own_pid) {
+            return None;
+        }
+    }
+    let usec = usec?.parse::<u64>().ok().filter(|usec| *usec > 0)?;
+    // Ping at half the timeout so one late tick does not trip it
+    Some(Duration::from_micros(usec / 2))
+}
+
+/// Ping the systemd watchdog from a background task, if it is enabled
+///
+/// Pings come from the runtime, so a wedged runtime stops them and systemd
+/// restarts the process.
+pub fn spawn_watchdog() -> Option<JoinHandle<()>> {
+    let interval = watchdog_interval()?;
+    Some(tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            notify("WATCHDOG=1");
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_listen_fds() {
+        assert!(listen_fds(None, Some("2"), None, 42).is_empty());
+        assert!(listen_fds(Some("41"), Some("2"), None, 42).is_empty());
+
+        assert_eq!(
+            listen_fds(Some("42"), Some("2"), Some("http:admin"), 42),
+            vec![(3, "http".to_string()), (4, "admin".to_string())]
+        );
+        assert_eq!(
+            listen_fds(Some("42"), Some("1"), None, 42),
+            vec![(3, "unknown".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_watchdog_interval() {
+        assert_eq!(watchdog_interval_from(None, None, 42), None);
+        assert_eq!(
+            watchdog_interval_from(Some("30000000"), None, 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            watchdog_interval_from(Some("30000000"), Some("42"), 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            watchdog_interval_from(Some("30000000"), Some("7"), 42),
+            None
+        );
+        assert_eq!(watchdog_interval_from(Some("0"), None, 42), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_notify_to() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let receiver = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+
+        notify_to(path.as_os_str(), "READY=1").unwrap();
+
+        let mut buf = [0u8; 64];
+        let n = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+    }
+}
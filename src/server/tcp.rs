@@ -0,0 +1,129 @@
+//! TCP socket options
+//!
+//! Applies `server.tcp` (or a listener's own `tcp`) to listening sockets
+//! and accepted connections. Buffer sizes are set on the listening socket,
+//! before `listen`, so accepted connections inherit them from the handshake
+//! on and can advertise a large enough window scale; `TCP_NODELAY` and
+//! keepalive are set on every accepted connection.
+
+use crate::config::TcpConfig;
+use socket2::{SockRef, TcpKeepalive};
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+
+/// Bind a listening socket on `addr` with `tcp`'s buffer sizes and backlog
+pub fn bind(addr: SocketAddr, tcp: &TcpConfig) -> io::Result<TcpListener> {
+    let socket = match addr {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+    // As `TcpListener::bind` does, so restarts can rebind at once
+    #[cfg(unix)]
+    socket.set_reuseaddr(true)?;
+    if let Some(size) = tcp.recv_buffer_size {
+        socket.set_recv_buffer_size(size)?;
+    }
+    if let Some(size) = tcp.send_buffer_size {
+        socket.set_send_buffer_size(size)?;
+    }
+    socket.bind(addr)?;
+    socket.listen(tcp.backlog)
+}
+
+/// Apply `tcp`'s buffer sizes to a socket bound elsewhere (e.g. by systemd)
+///
+/// The backlog was fixed when the socket started listening.
+pub fn tune_listener(listener: &TcpListener, tcp: &TcpConfig) -> io::Result<()> {
+    let socket = SockRef::from(listener);
+    if let Some(size) = tcp.recv_buffer_size {
+        socket.set_recv_buffer_size(size as usize)?;
+    }
+    if let Some(size) = tcp.send_buffer_size {
+        socket.set_send_buffer_size(size as usize)?;
+    }
+    Ok(())
+}
+
+/// Apply `tcp`'s per-connection options to an accepted connection
+pub fn tune_stream(stream: &TcpStream, tcp: &TcpConfig) -> io::Result<()> {
+    if tcp.nodelay {
+        stream.set_nodelay(true)?;
+    }
+    if let Some(keepalive) = keepalive(tcp) {
+        SockRef::from(stream).set_tcp_keepalive(&keepalive)?;
+    }
+    Ok(())
+}
+
+/// Keepalive settings, if keepalive is enabled
+fn keepalive(tcp: &TcpConfig) -> Option<TcpKeepalive> {
+    let mut keepalive = TcpKeepalive::new().with_time(Duration::from_secs(tcp.keepalive_secs?));
+    if let Some(interval) = tcp.keepalive_interval_secs {
+        keepalive = keepalive.with_interval(Duration::from_secs(interval));
+    }
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+    if let Some(retries) = tcp.keepalive_retries {
+        keepalive = keepalive.with_retries(retries);
+    }
+    Some(keepalive)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tuned() -> TcpConfig {
+        TcpConfig {
+            recv_buffer_size: Some(1 << 20),
+            send_buffer_size: Some(256 << 10),
+            nodelay: true,
+            keepalive_secs: Some(30),
+            keepalive_interval_secs: Some(5),
+            keepalive_retries: Some(3),
+            backlog: 16,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bind_sets_buffer_sizes() {
+        let listener = bind("127.0.0.1:0".parse().unwrap(), &tuned()).unwrap();
+        let socket = SockRef::from(&listener);
+        // Linux doubles the requested size for bookkeeping
+        assert!(socket.recv_buffer_size().unwrap() >= 1 << 20);
+        assert!(socket.send_buffer_size().unwrap() >= 256 << 10);
+    }
+
+    #[tokio::test]
+    async fn test_tune_stream() {
+        let listener = bind("127.0.0.1:0".parse().unwrap(), &tuned()).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (_client, accepted) = tokio::join!(TcpStream::connect(addr), listener.accept());
+        let (stream, _) = accepted.unwrap();
+
+        tune_stream(&stream, &tuned()).unwrap();
+        assert!(stream.nodelay().unwrap());
+        let socket = SockRef::from(&stream);
+        assert!(socket.keepalive().unwrap());
+        #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+        {
+            assert_eq!(
+                socket.tcp_keepalive_time().unwrap(),
+                Duration::from_secs(30)
+            );
+            assert_eq!(socket.tcp_keepalive_retries().unwrap(), 3);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_defaults_leave_stream_alone() {
+        let listener = bind("127.0.0.1:0".parse().unwrap(), &TcpConfig::default()).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (_client, accepted) = tokio::join!(TcpStream::connect(addr), listener.accept());
+        let (stream, _) = accepted.unwrap();
+
+        tune_stream(&stream, &TcpConfig::default()).unwrap();
+        assert!(!SockRef::from(&stream).keepalive().unwrap());
+    }
+}
@@ -0,0 +1,74 @@
+//! TLS termination
+//!
+//! Builds the rustls acceptor for the HTTPS listener. Client certificates
+//! are requested but optional: plain TLS clients (e.g. JWT-authenticated
+//! browsers) can still connect, and buckets that use mTLS re-verify the
+//! presented chain in [`MtlsAuthenticator`](crate::auth::mtls::MtlsAuthenticator).
+
+use crate::config::{Config, TlsConfig};
+use crate::server::ServerError;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::RootCertStore;
+use std::sync::Arc;
+use tokio_rustls::TlsAcceptor;
+
+/// DER-encoded client certificate chain of a TLS connection, leaf first
+///
+/// Inserted into request extensions for every request on an mTLS connection.
+#[derive(Debug, Clone, Default)]
+pub struct PeerCertificates(pub Arc<Vec<Vec<u8>>>);
+
+/// Build the TLS acceptor for the listener
+///
+/// Client certificates are verified against the union of all bucket mTLS CA
+/// bundles. Without any mTLS buckets, client certificates are not requested.
+pub fn build_acceptor(tls: &TlsConfig, config: &Config) -> Result<TlsAcceptor, ServerError> {
+    let tls_error = |msg: String| ServerError::TlsError(msg);
+    let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+
+    let certs = CertificateDer::pem_file_iter(&tls.cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| tls_error(format!("Failed to read {}: {}", tls.cert_path, e)))?;
+    let key = PrivateKeyDer::from_pem_file(&tls.key_path)
+        .map_err(|e| tls_error(format!("Failed to read {}: {}", tls.key_path, e)))?;
+
+    let mut client_roots = RootCertStore::empty();
+    for mtls in config.buckets.iter().filter_map(|b| b.auth.mtls.as_ref()) {
+        let cas = CertificateDer::pem_file_iter(&mtls.ca_path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| tls_error(format!("Failed to read {}: {}", mtls.ca_path, e)))?;
+        for ca in cas {
+            client_roots
+                .add(ca)
+                .map_err(|e| tls_error(format!("Invalid CA in {}: {}", mtls.ca_path, e)))?;
+        }
+    }
+
+    let builder = rustls::ServerConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| tls_error(e.to_string()))?;
+
+    let builder = if client_roots.is_empty() {
+        builder.with_no_client_auth()
+    } else {
+        let verifier =
+            WebPkiClientVerifier::builder_with_provider(Arc::new(client_roots), provider)
+                .allow_unauthenticated()
+                .build()
+                .map_err(|e| tls_error(format!("Failed to build client verifier: {}", e)))?;
+        builder.with_client_cert_verifier(verifier)
+    };
+
+    let mut server_config = builder
+        .with_single_cert(certs, key)
+        .map_err(|e| tls_error(format!("Invalid server certificate or key: {}", e)))?;
+    server_config.alpn_protocols = if config.server.http.http2.enabled {
+        vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+    } else {
+        vec![b"http/1.1".to_vec()]
+    };
+
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
@@ -0,0 +1,330 @@
+//! State shared between proxy instances
+//!
+//! Rate limits, the SigV4 replay cache and quota counts are kept per process
+//! unless they are given a [`Store`]. With `state.redis` configured, every
+//! instance behind a load balancer uses the same [`RedisStore`], so a client
+//! cannot get around a limit by landing on another instance.
+//! [`MemoryStore`] keeps the same semantics in process, for single instances
+//! and tests.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::state::{MemoryStore, Store};
+//! use std::time::Duration;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let store = MemoryStore::new();
+//! let ttl = Duration::from_secs(60);
+//!
+//! assert!(store.set_nx("seen:abc", b"1", ttl).await?);
+//! assert!(!store.set_nx("seen:abc", b"1", ttl).await?);
+//! assert_eq!(store.incr_by("count", 5, ttl).await?, 5);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::config::StateConfig;
+use crate::redis::{RedisClient, RedisError, RedisValue};
+use async_trait::async_trait;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Entries kept by a [`MemoryStore`] before expired ones are pruned
+const MAX_MEMORY_ENTRIES: usize = 100_000;
+
+/// Shared state errors
+#[derive(Error, Debug)]
+pub enum StateError {
+    #[error("State store unavailable: {0}")]
+    Redis(#[from] RedisError),
+
+    #[error("Value of '{0}' is not an integer")]
+    NotAnInteger(String),
+}
+
+/// Key-value store with expiring entries
+#[async_trait]
+pub trait Store: Send + Sync {
+    /// Value of `key`, if set and not expired
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StateError>;
+
+    /// Set `key`, expiring after `ttl` if given
+    async fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> Result<(), StateError>;
+
+    /// Set `key` unless it is set, returning whether it was
+    async fn set_nx(&self, key: &str, value: &[u8], ttl: Duration) -> Result<bool, StateError>;
+
+    /// Add `delta` to the integer at `key`, returning the new value
+    ///
+    /// A missing key starts at 0 and expires after `ttl`; adding to an
+    /// existing key keeps its expiry.
+    async fn incr_by(&self, key: &str, delta: i64, ttl: Duration) -> Result<i64, StateError>;
+
+    /// Remove `key`
+    async fn delete(&self, key: &str) -> Result<(), StateError>;
+}
+
+/// Build the store shared by every subsystem, or `None` without `state.redis`
+pub fn from_config(config: &StateConfig) -> Result<Option<Arc<dyn Store>>, StateError> {
+    Ok(match config.redis {
+        Some(ref redis) => {
+            let client = RedisClient::from_url(&redis.url)?;
+            Some(Arc::new(RedisStore::new(client, &redis.key_prefix)))
+        }
+        None => None,
+    })
+}
+
+struct MemoryEntry {
+    value: Vec<u8>,
+    expires_at: Option<Instant>,
+}
+
+impl MemoryEntry {
+    fn is_live(&self, now: Instant) -> bool {
+        self.expires_at.is_none_or(|at| at > now)
+    }
+}
+
+/// Store kept in this process
+#[derive(Default)]
+pub struct MemoryStore {
+    entries: DashMap<String, MemoryEntry>,
+}
+
+impl MemoryStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn prune(&self, now: Instant) {
+        if self.entries.len() > MAX_MEMORY_ENTRIES {
+            self.entries.retain(|_, entry| entry.is_live(now));
+        }
+    }
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StateError> {
+        let now = Instant::now();
+        Ok(self
+            .entries
+            .get(key)
+            .filter(|entry| entry.is_live(now))
+            .map(|entry| entry.value.clone()))
+    }
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> Result<(), StateError> {
+        let now = Instant::now();
+        self.prune(now);
+        self.entries.insert(
+            key.to_string(),
+            MemoryEntry {
+                value: value.to_vec(),
+                expires_at: ttl.map(|ttl| now + ttl),
+            },
+        );
+        Ok(())
+    }
+
+    async fn set_nx(&self, key: &str, value: &[u8], ttl: Duration) -> Result<bool, StateError> {
+        let now = Instant::now();
+        self.prune(now);
+        let entry = MemoryEntry {
+            value: value.to_vec(),
+            expires_at: Some(now + ttl),
+        };
+        match self.entries.entry(key.to_string()) {
+            Entry::Occupied(existing) if existing.get().is_live(now) => Ok(false),
+            Entry::Occupied(mut expired) => {
+                expired.insert(entry);
+                Ok(true)
+            }
+            Entry::Vacant(vacant) => {
+                vacant.insert(entry);
+                Ok(true)
+            }
+        }
+    }
+
+    async fn incr_by(&self, key: &str, delta: i64, ttl: Duration) -> Result<i64, StateError> {
+        let now = Instant::now();
+        self.prune(now);
+        let mut entry = self
+            .entries
+            .entry(key.to_string())
+            .or_insert_with(|| MemoryEntry {
+                value: b"0".to_vec(),
+                expires_at: Some(now + ttl),
+            });
+        if !entry.is_live(now) {
+            *entry = MemoryEntry {
+                value: b"0".to_vec(),
+                expires_at: Some(now + ttl),
+            };
+        }
+        let value = std::str::from_utf8(&entry.value)
+            .ok()
+            .and_then(|value| value.parse::<i64>().ok())
+            .ok_or_else(|| StateError::NotAnInteger(key.to_string()))?
+            .saturating_add(delta);
+        entry.value = value.to_string().into_bytes();
+        Ok(value)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StateError> {
+        self.entries.remove(key);
+        Ok(())
+    }
+}
+
+/// Store kept in Redis, shared between instances
+///
+/// Every key is prefixed with `key_prefix`.
+pub struct RedisStore {
+    client: RedisClient,
+    key_prefix: String,
+}
+
+impl RedisStore {
+    pub fn new(client: RedisClient, key_prefix: &str) -> Self {
+        Self {
+            client,
+            key_prefix: key_prefix.to_string(),
+        }
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{}", self.key_prefix, key)
+    }
+}
+
+#[async_trait]
+impl Store for RedisStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StateError> {
+        Ok(self.client.get(&self.key(key)).await?)
+    }
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> Result<(), StateError> {
+        let key = self.key(key);
+        match ttl {
+            Some(ttl) => {
+                let millis = millis(ttl);
+                self.client
+                    .command(&[b"SET", key.as_bytes(), value, b"PX", millis.as_bytes()])
+                    .await?
+            }
+            None => {
+                self.client
+                    .command(&[b"SET", key.as_bytes(), value])
+                    .await?
+            }
+        };
+        Ok(())
+    }
+
+    async fn set_nx(&self, key: &str, value: &[u8], ttl: Duration) -> Result<bool, StateError> {
+        let key = self.key(key);
+        let millis = millis(ttl);
+        match self
+            .client
+            .command(&[
+                b"SET",
+                key.as_bytes(),
+                value,
+                b"NX",
+                b"PX",
+                millis.as_bytes(),
+            ])
+            .await?
+        {
+            RedisValue::Simple(ok) if ok == "OK" => Ok(true),
+            RedisValue::Nil => Ok(false),
+            other => Err(unexpected("SET", other)),
+        }
+    }
+
+    async fn incr_by(&self, key: &str, delta: i64, ttl: Duration) -> Result<i64, StateError> {
+        let key = self.key(key);
+        let delta_arg = delta.to_string();
+        let value = match self
+            .client
+            .command(&[b"INCRBY", key.as_bytes(), delta_arg.as_bytes()])
+            .await?
+        {
+            RedisValue::Int(value) => value,
+            other => return Err(unexpected("INCRBY", other)),
+        };
+        // A key INCRBY just created has no expiry yet
+        if value == delta {
+            let millis = millis(ttl);
+            self.client
+                .command(&[b"PEXPIRE", key.as_bytes(), millis.as_bytes()])
+                .await?;
+        }
+        Ok(value)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StateError> {
+        let key = self.key(key);
+        self.client.command(&[b"DEL", key.as_bytes()]).await?;
+        Ok(())
+    }
+}
+
+/// TTL argument in milliseconds, at least 1
+fn millis(ttl: Duration) -> String {
+    ttl.as_millis().max(1).to_string()
+}
+
+fn unexpected(command: &str, reply: RedisValue) -> StateError {
+    StateError::Redis(RedisError::Protocol(format!(
+        "unexpected {} reply: {:?}",
+        command, reply
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_set_nx_expires() {
+        let store = MemoryStore::new();
+
+        assert!(store.set_nx("k", b"1", Duration::ZERO).await.unwrap());
+        assert!(store
+            .set_nx("k", b"1", Duration::from_secs(60))
+            .await
+            .unwrap());
+        assert!(!store
+            .set_nx("k", b"1", Duration::from_secs(60))
+            .await
+            .unwrap());
+        assert_eq!(store.get("k").await.unwrap(), Some(b"1".to_vec()));
+
+        store.delete("k").await.unwrap();
+        assert_eq!(store.get("k").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_memory_incr_by() {
+        let store = MemoryStore::new();
+        let ttl = Duration::from_secs(60);
+
+        assert_eq!(store.incr_by("n", 2, ttl).await.unwrap(), 2);
+        assert_eq!(store.incr_by("n", 3, ttl).await.unwrap(), 5);
+
+        store.set("s", b"text", None).await.unwrap();
+        assert!(matches!(
+            store.incr_by("s", 1, ttl).await,
+            Err(StateError::NotAnInteger(_))
+        ));
+    }
+}
@@ -3,9 +3,10 @@
 //! This module handles the initialization of the OpenTelemetry tracer provider,
 //! OTLP exporter configuration, and graceful shutdown with span flushing.
 
+use super::tail_sampling::{TailSampler, TailSamplingProcessor};
 use crate::config::TracingConfig;
 use opentelemetry::global;
-use opentelemetry_sdk::trace::{Sampler, TracerProvider};
+use opentelemetry_sdk::trace::{Builder, Sampler, SpanProcessor, TracerProvider};
 use opentelemetry_sdk::Resource;
 use std::sync::Arc;
 use thiserror::Error;
@@ -113,6 +114,45 @@ pub fn init_tracing(config: &TracingConfig) -> Result<TracingGuard, TracingError
         return Ok(TracingGuard::inactive());
     }
 
+    // TODO: Add OTLP exporter integration (requires Tokio runtime context)
+    // For now, we create a basic provider that validates configuration
+    // and sets up the sampling/resource correctly
+    Ok(install(provider_builder(config)?.build()))
+}
+
+/// Initialize tracing, handing finished spans to `processor`
+///
+/// `processor` is normally the exporting (batch) span processor. With
+/// `sampling.rules` configured it only receives the traces kept by the
+/// [`TailSamplingProcessor`].
+pub fn init_tracing_with_processor<P: SpanProcessor + 'static>(
+    config: &TracingConfig,
+    processor: P,
+) -> Result<TracingGuard, TracingError> {
+    if !config.enabled {
+        return Ok(TracingGuard::inactive());
+    }
+
+    let builder = provider_builder(config)?;
+    let builder = match config.sampling.rules {
+        Some(ref rules) => builder.with_span_processor(TailSamplingProcessor::new(
+            processor,
+            TailSampler::new(rules),
+            rules.max_pending_traces,
+        )),
+        None => builder.with_span_processor(processor),
+    };
+    Ok(install(builder.build()))
+}
+
+/// Set `provider` as the global tracer provider
+fn install(provider: TracerProvider) -> TracingGuard {
+    global::set_tracer_provider(provider.clone());
+    TracingGuard::new(provider)
+}
+
+/// Validate `config` and prepare a provider with its sampler and resource
+fn provider_builder(config: &TracingConfig) -> Result<Builder, TracingError> {
     // Validate endpoint format
     let endpoint = &config.otlp.endpoint;
     if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
@@ -139,22 +179,12 @@ pub fn init_tracing(config: &TracingConfig) -> Result<TracingGuard, TracingError
         config.service_name.clone(),
     )]);
 
-    // Build tracer provider with configured sampler and resource
-    // TODO: Add OTLP exporter integration (requires Tokio runtime context)
-    // For now, we create a basic provider that validates configuration
-    // and sets up the sampling/resource correctly
-    let provider = TracerProvider::builder()
-        .with_config(
-            opentelemetry_sdk::trace::config()
-                .with_sampler(sampler)
-                .with_resource(resource),
-        )
-        .build();
-
-    // Set as global provider
-    global::set_tracer_provider(provider.clone());
-
-    Ok(TracingGuard::new(provider))
+    // Tracer provider with configured sampler and resource
+    Ok(TracerProvider::builder().with_config(
+        opentelemetry_sdk::trace::config()
+            .with_sampler(sampler)
+            .with_resource(resource),
+    ))
 }
 
 /// Explicitly shutdown tracing and flush all pending spans
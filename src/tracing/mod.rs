@@ -6,7 +6,7 @@
 //! # Features
 //!
 //! - OTLP gRPC and HTTP export
-//! - Configurable sampling strategies
+//! - Configurable sampling strategies, including tail-based sampling
 //! - Batch span processing for performance
 //! - Graceful shutdown with span flushing
 //! - W3C Trace Context propagation
@@ -39,6 +39,7 @@ pub mod instrumentation;
 pub mod propagation;
 pub mod sampling;
 pub mod subscriber;
+pub mod tail_sampling;
 
 pub use init::{init_tracing, shutdown_tracing, TracingGuard};
 pub use subscriber::init_subscriber;
@@ -59,6 +59,13 @@
 //! assert!(headers.contains_key("traceparent"));
 //! ```
 //!
+//! ### OpenTelemetry Spans
+//!
+//! [`extract_context`] turns inbound request headers into an OpenTelemetry
+//! parent context, so the proxy's request span joins the caller's trace.
+//! [`current_trace_context`] reads the context of the current span back out,
+//! which is what gets injected into outgoing S3 requests.
+//!
 //! # Performance Characteristics
 //!
 //! This module is optimized for high-performance distributed tracing with minimal overhead.
@@ -102,7 +109,12 @@
 //! Our implementation is **4-18x faster** than typical tracing libraries due to
 //! aggressive optimizations and zero-copy techniques.
 
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::trace::TraceContextExt;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
 use std::collections::HashMap;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 /// W3C Trace Context
 ///
@@ -285,9 +297,86 @@ pub fn inject_trace_context(context: &TraceContext, headers: &mut HashMap<String
     }
 }
 
+/// Trace context of the current span
+///
+/// Returns `None` when the current span is not recorded by OpenTelemetry
+/// (no OpenTelemetry layer installed, or outside any span).
+pub fn current_trace_context() -> Option<TraceContext> {
+    let context = Span::current().context();
+    let span = context.span();
+    let span_context = span.span_context();
+    if !span_context.is_valid() {
+        return None;
+    }
+
+    let tracestate = span_context.trace_state().header();
+    Some(TraceContext {
+        trace_id: span_context.trace_id().to_string(),
+        span_id: span_context.span_id().to_string(),
+        trace_flags: span_context.trace_flags().to_u8(),
+        tracestate: (!tracestate.is_empty()).then_some(tracestate),
+    })
+}
+
+/// Extract the caller's trace context from inbound request headers
+///
+/// The result is meant for `Span::set_parent`. Without a valid `traceparent`
+/// header it carries no span, and the request span starts a new trace.
+pub fn extract_context(headers: &hyper::HeaderMap) -> opentelemetry::Context {
+    TraceContextPropagator::new().extract(&HeaderExtractor(headers))
+}
+
+/// Read-only view of hyper headers for the OpenTelemetry propagator
+struct HeaderExtractor<'a>(&'a hyper::HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use opentelemetry::trace::TracerProvider as _;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_current_trace_context_joins_inbound_trace() {
+        let provider = opentelemetry_sdk::trace::TracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"
+                .parse()
+                .unwrap(),
+        );
+        headers.insert("tracestate", "congo=t61rcWkgMzE".parse().unwrap());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("http.request");
+            span.set_parent(extract_context(&headers));
+            let _entered = span.enter();
+
+            let context = current_trace_context().unwrap();
+            assert_eq!(context.trace_id, "0af7651916cd43dd8448eb211c80319c");
+            assert_ne!(context.span_id, "b7ad6b7169203331");
+            assert!(context.is_sampled());
+            assert_eq!(context.tracestate.as_deref(), Some("congo=t61rcWkgMzE"));
+        });
+    }
+
+    #[test]
+    fn test_current_trace_context_without_span() {
+        assert!(current_trace_context().is_none());
+    }
 
     #[test]
     fn test_parse_valid_traceparent() {
@@ -314,47 +314,44 @@ impl AdvancedSampler {
         method: &str,
         attributes: &HashMap<String, String>,
     ) -> SamplingDecision {
-        // Check rules in order (first match wins)
-        for rule in &self.rules {
-            if rule.matches(path)
-                && rule.matches_method(method)
-                && rule.matches_attributes(attributes)
-            {
-                let rate = rule.sample_rate();
-                if rate >= 1.0 {
-                    return SamplingDecision::Sample;
-                } else if rate <= 0.0 {
-                    return SamplingDecision::Drop;
-                }
-                // For simplicity, use deterministic sampling based on path hash
-                let hash = path
-                    .bytes()
-                    .fold(0u64, |acc, b| acc.wrapping_mul(31).wrapping_add(b as u64));
-                let threshold = (rate * u64::MAX as f64) as u64;
-                return if hash <= threshold {
-                    SamplingDecision::Sample
-                } else {
-                    SamplingDecision::Drop
-                };
-            }
-        }
+        // For simplicity, use deterministic sampling based on path hash
+        let hash = path
+            .bytes()
+            .fold(0u64, |acc, b| acc.wrapping_mul(31).wrapping_add(b as u64));
+        self.should_sample_trace(path, method, attributes, hash)
+    }
 
-        // No rules matched, use base rate
-        if self.base_rate >= 1.0 {
+    /// Determine if a trace should be sampled
+    ///
+    /// Like [`should_sample`](Self::should_sample), but partial rates are
+    /// applied to `trace_id`, so requests to the same path are sampled
+    /// independently.
+    pub fn should_sample_trace(
+        &self,
+        path: &str,
+        method: &str,
+        attributes: &HashMap<String, String>,
+        trace_id: u64,
+    ) -> SamplingDecision {
+        // Check rules in order (first match wins), falling back to base rate
+        let rate = self
+            .rules
+            .iter()
+            .find(|rule| {
+                rule.matches(path)
+                    && rule.matches_method(method)
+                    && rule.matches_attributes(attributes)
+            })
+            .map_or(self.base_rate, SamplingRule::sample_rate);
+
+        if rate >= 1.0 {
             SamplingDecision::Sample
-        } else if self.base_rate <= 0.0 {
+        } else if rate <= 0.0 {
             SamplingDecision::Drop
+        } else if trace_id <= (rate * u64::MAX as f64) as u64 {
+            SamplingDecision::Sample
         } else {
-            // Use deterministic sampling based on path hash
-            let hash = path
-                .bytes()
-                .fold(0u64, |acc, b| acc.wrapping_mul(31).wrapping_add(b as u64));
-            let threshold = (self.base_rate * u64::MAX as f64) as u64;
-            if hash <= threshold {
-                SamplingDecision::Sample
-            } else {
-                SamplingDecision::Drop
-            }
+            SamplingDecision::Drop
         }
     }
 }
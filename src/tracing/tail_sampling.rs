@@ -0,0 +1,416 @@
+//! Tail-based sampling
+//!
+//! Head sampling decides whether to record a trace before anything has
+//! happened, so it cannot favour the traces operators care about: failed or
+//! slow uploads. [`TailSamplingProcessor`] buffers the spans of each trace
+//! until the request's root span ends, then applies the samplers from
+//! [`super::sampling`] to the complete trace and forwards the kept traces to
+//! the exporting span processor.
+//!
+//! A trace is kept when, in order:
+//!
+//! 1. it contains an error ([`ErrorBasedSampler`]), or
+//! 2. the request took at least the slow threshold ([`SlowRequestSampler`]), or
+//! 3. the first matching custom rule, or the base rate, picks it
+//!    ([`AdvancedSampler`]).
+//!
+//! The root span is the first span of the trace in this process: one without
+//! a parent, or whose parent came from the caller's `traceparent`. Spans that
+//! end after their root follow the decision already made for the trace.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use mizuchi_uploadr::config::TailSamplingConfig;
+//! use mizuchi_uploadr::tracing::tail_sampling::{TailSampler, TailSamplingProcessor};
+//! use opentelemetry_sdk::trace::{SpanProcessor, TracerProvider};
+//!
+//! # fn example(exporting: impl SpanProcessor + 'static) {
+//! let config = TailSamplingConfig {
+//!     slow_threshold_ms: Some(1000),
+//!     base_rate: 0.05,
+//!     ..Default::default()
+//! };
+//! let processor = TailSamplingProcessor::new(exporting, TailSampler::new(&config), 10_000);
+//! let provider = TracerProvider::builder()
+//!     .with_span_processor(processor)
+//!     .build();
+//! # }
+//! ```
+
+use super::sampling::{
+    AdvancedSampler, ErrorBasedSampler, SamplingDecision, SamplingRule, SlowRequestSampler,
+};
+use crate::config::TailSamplingConfig;
+use lru::LruCache;
+use opentelemetry::trace::{Span as _, SpanId, Status, TraceContextExt, TraceId, TraceResult};
+use opentelemetry::Context;
+use opentelemetry_sdk::export::trace::SpanData;
+use opentelemetry_sdk::trace::{Span, SpanProcessor};
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+
+/// Attributes holding the request path, newest semantic conventions first
+const PATH_ATTRIBUTES: &[&str] = &["url.path", "http.target", "http.route"];
+
+/// Attributes holding the request method
+const METHOD_ATTRIBUTES: &[&str] = &["http.request.method", "http.method"];
+
+/// Attributes holding the response status code
+const STATUS_ATTRIBUTES: &[&str] = &["http.response.status_code", "http.status_code"];
+
+/// Sampling decision for a complete trace
+pub struct TailSampler {
+    errors: Option<ErrorBasedSampler>,
+    slow: Option<SlowRequestSampler>,
+    rules: AdvancedSampler,
+}
+
+impl TailSampler {
+    /// Build the samplers described by `tracing.sampling.rules`
+    pub fn new(config: &TailSamplingConfig) -> Self {
+        let mut rules = AdvancedSampler::new(config.base_rate);
+        for rule in &config.custom {
+            let mut sampling_rule = SamplingRule::new().with_sample_rate(rule.sample_rate);
+            if let Some(ref path) = rule.path {
+                sampling_rule = sampling_rule.with_path_pattern(path);
+            }
+            if let Some(ref method) = rule.method {
+                sampling_rule = sampling_rule.with_method(method);
+            }
+            for (key, value) in &rule.attributes {
+                sampling_rule = sampling_rule.with_attribute(key, value);
+            }
+            rules.add_rule(sampling_rule);
+        }
+
+        Self {
+            // Base rates of 0: these only ever keep traces, the rules decide the rest
+            errors: config.errors.then(|| ErrorBasedSampler::new(0.0)),
+            slow: config
+                .slow_threshold_ms
+                .map(|threshold| SlowRequestSampler::new(threshold, 0.0)),
+            rules,
+        }
+    }
+
+    /// Decide on a trace given its root span
+    pub fn decide(&self, root: &SpanData, has_error: bool) -> SamplingDecision {
+        let trace_id = root.span_context.trace_id().to_bytes();
+        let trace_hash = u64::from_be_bytes(trace_id[8..].try_into().expect("8 bytes"));
+
+        if let Some(ref errors) = self.errors {
+            if errors.should_sample(has_error, trace_hash) == SamplingDecision::Sample {
+                return SamplingDecision::Sample;
+            }
+        }
+
+        if let Some(ref slow) = self.slow {
+            let duration = root
+                .end_time
+                .duration_since(root.start_time)
+                .unwrap_or_default();
+            let duration_ms = u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+            if slow.should_sample(duration_ms) == SamplingDecision::Sample {
+                return SamplingDecision::Sample;
+            }
+        }
+
+        let attributes: HashMap<String, String> = root
+            .attributes
+            .iter()
+            .map(|kv| (kv.key.as_str().to_string(), kv.value.as_str().into_owned()))
+            .collect();
+        let first = |keys: &[&str]| {
+            keys.iter()
+                .find_map(|key| attributes.get(*key))
+                .map_or("", String::as_str)
+        };
+        self.rules.should_sample_trace(
+            first(PATH_ATTRIBUTES),
+            first(METHOD_ATTRIBUTES),
+            &attributes,
+            trace_hash,
+        )
+    }
+}
+
+/// Spans of a trace whose root has not ended yet
+#[derive(Default)]
+struct PendingTrace {
+    spans: Vec<SpanData>,
+    has_error: bool,
+}
+
+struct State {
+    /// Local roots that have started but not ended
+    roots: HashSet<SpanId>,
+    pending: LruCache<TraceId, PendingTrace>,
+    /// Recent decisions, for spans ending after their root
+    decided: LruCache<TraceId, SamplingDecision>,
+}
+
+/// Span processor applying a [`TailSampler`] before `inner` sees any span
+pub struct TailSamplingProcessor {
+    inner: Box<dyn SpanProcessor>,
+    sampler: TailSampler,
+    state: Mutex<State>,
+}
+
+impl std::fmt::Debug for TailSamplingProcessor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state.lock();
+        f.debug_struct("TailSamplingProcessor")
+            .field("inner", &self.inner)
+            .field("pending_traces", &state.pending.len())
+            .finish()
+    }
+}
+
+impl TailSamplingProcessor {
+    /// Wrap `inner`, buffering up to `max_pending_traces` traces
+    ///
+    /// When the buffer is full, the least recently active trace is dropped.
+    pub fn new<P: SpanProcessor + 'static>(
+        inner: P,
+        sampler: TailSampler,
+        max_pending_traces: usize,
+    ) -> Self {
+        let capacity = NonZeroUsize::new(max_pending_traces).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            inner: Box::new(inner),
+            sampler,
+            state: Mutex::new(State {
+                roots: HashSet::new(),
+                pending: LruCache::new(capacity),
+                decided: LruCache::new(capacity),
+            }),
+        }
+    }
+
+    /// Number of traces waiting for their root span to end
+    pub fn pending_traces(&self) -> usize {
+        self.state.lock().pending.len()
+    }
+}
+
+impl SpanProcessor for TailSamplingProcessor {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        let parent = cx.span();
+        let parent = parent.span_context();
+        if span.is_recording() && (!parent.is_valid() || parent.is_remote()) {
+            self.state
+                .lock()
+                .roots
+                .insert(span.span_context().span_id());
+        }
+        self.inner.on_start(span, cx);
+    }
+
+    fn on_end(&self, span: SpanData) {
+        let trace_id = span.span_context.trace_id();
+        let mut state = self.state.lock();
+        let is_root = state.roots.remove(&span.span_context.span_id());
+        if !span.span_context.is_sampled() {
+            return;
+        }
+
+        if let Some(&decision) = state.decided.get(&trace_id) {
+            drop(state);
+            if decision == SamplingDecision::Sample {
+                self.inner.on_end(span);
+            }
+            return;
+        }
+
+        let has_error = is_error(&span);
+        if !is_root {
+            let trace = state
+                .pending
+                .get_or_insert_mut(trace_id, PendingTrace::default);
+            trace.has_error |= has_error;
+            trace.spans.push(span);
+            return;
+        }
+
+        let trace = state.pending.pop(&trace_id).unwrap_or_default();
+        let decision = self.sampler.decide(&span, trace.has_error || has_error);
+        state.decided.put(trace_id, decision);
+        drop(state);
+
+        if decision == SamplingDecision::Sample {
+            for child in trace.spans {
+                self.inner.on_end(child);
+            }
+            self.inner.on_end(span);
+        }
+    }
+
+    fn force_flush(&self) -> TraceResult<()> {
+        // Incomplete traces stay buffered: they can't be decided yet
+        self.inner.force_flush()
+    }
+
+    fn shutdown(&mut self) -> TraceResult<()> {
+        self.inner.shutdown()
+    }
+}
+
+/// Whether a span failed: error status or a 5xx response
+fn is_error(span: &SpanData) -> bool {
+    matches!(span.status, Status::Error { .. })
+        || span.attributes.iter().any(|kv| {
+            STATUS_ATTRIBUTES.contains(&kv.key.as_str())
+                && kv
+                    .value
+                    .as_str()
+                    .parse::<u16>()
+                    .is_ok_and(|status| status >= 500)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SamplingRuleConfig;
+    use opentelemetry::trace::{Tracer, TracerProvider as _};
+    use opentelemetry::KeyValue;
+    use opentelemetry_sdk::trace::TracerProvider;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    /// Processor recording the spans it receives
+    #[derive(Debug, Clone, Default)]
+    struct Collected(Arc<Mutex<Vec<SpanData>>>);
+
+    impl Collected {
+        fn names(&self) -> Vec<String> {
+            self.0.lock().iter().map(|s| s.name.to_string()).collect()
+        }
+    }
+
+    impl SpanProcessor for Collected {
+        fn on_start(&self, _span: &mut Span, _cx: &Context) {}
+
+        fn on_end(&self, span: SpanData) {
+            self.0.lock().push(span);
+        }
+
+        fn force_flush(&self) -> TraceResult<()> {
+            Ok(())
+        }
+
+        fn shutdown(&mut self) -> TraceResult<()> {
+            Ok(())
+        }
+    }
+
+    fn provider(config: &TailSamplingConfig) -> (TracerProvider, Collected) {
+        let collected = Collected::default();
+        let processor = TailSamplingProcessor::new(collected.clone(), TailSampler::new(config), 16);
+        let provider = TracerProvider::builder()
+            .with_span_processor(processor)
+            .build();
+        (provider, collected)
+    }
+
+    /// Run a request trace: a root span with one child
+    fn request(provider: &TracerProvider, name: &str, attributes: Vec<KeyValue>, error: bool) {
+        let tracer = provider.tracer("test");
+        let start = SystemTime::now();
+        let root = tracer
+            .span_builder(name.to_string())
+            .with_attributes(attributes)
+            .with_start_time(start)
+            .start(&tracer);
+        let cx = Context::current_with_span(root);
+
+        let mut child = tracer.start_with_context(format!("{}.s3", name), &cx);
+        if error {
+            child.set_status(Status::error("S3 unavailable"));
+        }
+        child.end();
+        cx.span()
+            .end_with_timestamp(start + Duration::from_millis(5));
+    }
+
+    #[test]
+    fn test_keeps_error_traces_only() {
+        let config = TailSamplingConfig {
+            base_rate: 0.0,
+            ..Default::default()
+        };
+        let (provider, collected) = provider(&config);
+
+        request(&provider, "ok", vec![], false);
+        request(&provider, "failed", vec![], true);
+
+        assert_eq!(collected.names(), ["failed.s3", "failed"]);
+    }
+
+    #[test]
+    fn test_keeps_slow_traces() {
+        let config = TailSamplingConfig {
+            errors: false,
+            slow_threshold_ms: Some(1),
+            base_rate: 0.0,
+            ..Default::default()
+        };
+        let (provider, collected) = provider(&config);
+
+        request(&provider, "slow", vec![], false);
+
+        assert_eq!(collected.names(), ["slow.s3", "slow"]);
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let config = TailSamplingConfig {
+            base_rate: 0.0,
+            custom: vec![SamplingRuleConfig {
+                path: Some("/critical/*".into()),
+                method: Some("PUT".into()),
+                attributes: HashMap::new(),
+                sample_rate: 1.0,
+            }],
+            ..Default::default()
+        };
+        let (provider, collected) = provider(&config);
+
+        let attributes = |path: &'static str| {
+            vec![
+                KeyValue::new("http.method", "PUT"),
+                KeyValue::new("http.target", path),
+            ]
+        };
+        request(&provider, "critical", attributes("/critical/a.bin"), false);
+        request(&provider, "other", attributes("/other/a.bin"), false);
+
+        assert_eq!(collected.names(), ["critical.s3", "critical"]);
+    }
+
+    #[test]
+    fn test_server_errors_count_as_errors() {
+        let config = TailSamplingConfig {
+            base_rate: 0.0,
+            ..Default::default()
+        };
+        let (provider, collected) = provider(&config);
+
+        request(
+            &provider,
+            "bad-gateway",
+            vec![KeyValue::new("http.status_code", 502)],
+            false,
+        );
+        request(
+            &provider,
+            "not-found",
+            vec![KeyValue::new("http.status_code", 404)],
+            false,
+        );
+
+        assert_eq!(collected.names(), ["bad-gateway.s3", "bad-gateway"]);
+    }
+}
@@ -0,0 +1,547 @@
+//! Decoding of `aws-chunked` request bodies
+//!
+//! AWS SDKs stream uploads they cannot hash up front as `aws-chunked`
+//! bodies (`Content-Encoding: aws-chunked`, `x-amz-content-sha256:
+//! STREAMING-...`): the payload is split into chunks, each preceded by its
+//! size in hex and an optional chunk signature, and may be followed by
+//! trailers carrying a checksum of the whole payload (`x-amz-trailer`).
+//! Stored as sent, the object would contain the framing.
+//!
+//! [`AwsChunkedBody`] removes the framing as the body is read, and at the
+//! end checks the payload length against `x-amz-decoded-content-length` and
+//! the payload checksum against the trailer. Chunk signatures are dropped
+//! without being verified; the request itself is still authenticated by
+//! its seed signature.
+//!
+//! # Example
+//!
+//! ```
+//! use http_body_util::{BodyExt, Full};
+//! use hyper::HeaderMap;
+//! use mizuchi_uploadr::upload::aws_chunked::{AwsChunkedBody, Decoder};
+//!
+//! # tokio_test::block_on(async {
+//! let mut headers = HeaderMap::new();
+//! headers.insert("content-encoding", "aws-chunked".parse().unwrap());
+//! headers.insert("x-amz-decoded-content-length", "5".parse().unwrap());
+//!
+//! let decoder = Decoder::from_headers(&headers).unwrap();
+//! let body = AwsChunkedBody::new(Full::new(&b"5\r\nhello\r\n0\r\n\r\n"[..]), decoder);
+//! let payload = body.collect().await.unwrap().to_bytes();
+//! assert_eq!(payload, "hello");
+//! # });
+//! ```
+
+use crate::s3::checksum::Crc;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use bytes::{Buf, Bytes, BytesMut};
+use hyper::body::{Body, Frame, SizeHint};
+use hyper::HeaderMap;
+use sha2::{Digest, Sha256};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use thiserror::Error;
+
+/// Longest chunk header or trailer line accepted
+const MAX_LINE: usize = 4096;
+
+/// Errors decoding an `aws-chunked` body
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum AwsChunkedError {
+    #[error("Malformed aws-chunked body: {0}")]
+    Malformed(String),
+
+    #[error("aws-chunked body ended before its final chunk")]
+    Truncated,
+
+    #[error("Decoded length {actual} does not match x-amz-decoded-content-length {expected}")]
+    LengthMismatch { expected: u64, actual: u64 },
+
+    #[error("Unsupported trailer checksum: {0}")]
+    UnsupportedChecksum(String),
+
+    #[error("Trailer {0} is missing")]
+    MissingChecksum(String),
+
+    #[error("Payload does not match the {name} trailer")]
+    ChecksumMismatch { name: String },
+}
+
+/// Whether the request body is `aws-chunked`
+pub fn is_aws_chunked(headers: &HeaderMap) -> bool {
+    let encoded = headers
+        .get_all("content-encoding")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|encoding| encoding.trim().eq_ignore_ascii_case("aws-chunked"));
+    let streaming = headers
+        .get("x-amz-content-sha256")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("STREAMING-"));
+    encoded || streaming
+}
+
+/// Payload length of an `aws-chunked` body, from `x-amz-decoded-content-length`
+pub fn decoded_content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get("x-amz-decoded-content-length")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+}
+
+/// Checksum of the payload announced in `x-amz-trailer`
+enum Checksum {
+    Crc(Crc),
+    Sha256(Sha256),
+}
+
+impl Checksum {
+    fn for_trailer(name: &str) -> Result<Self, AwsChunkedError> {
+        match name {
+            "x-amz-checksum-crc32" => Ok(Checksum::Crc(Crc::crc32())),
+            "x-amz-checksum-crc32c" => Ok(Checksum::Crc(Crc::crc32c())),
+            "x-amz-checksum-crc64nvme" => Ok(Checksum::Crc(Crc::crc64nvme())),
+            "x-amz-checksum-sha256" => Ok(Checksum::Sha256(Sha256::new())),
+            other => Err(AwsChunkedError::UnsupportedChecksum(other.to_string())),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Checksum::Crc(crc) => crc.update(data),
+            Checksum::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Base64 of the big-endian checksum, as sent in the trailer
+    fn finish(&self) -> String {
+        match self {
+            Checksum::Crc(crc) => crc.finish_base64(),
+            Checksum::Sha256(hasher) => STANDARD.encode(hasher.clone().finalize()),
+        }
+    }
+}
+
+/// Where the decoder is in the chunk framing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Expecting a chunk header line
+    Header,
+    /// Inside a chunk's data, with this many bytes left
+    Data(usize),
+    /// Expecting the CRLF after a chunk's data
+    DataEnd,
+    /// After the final chunk, expecting trailers or the closing blank line
+    Trailers,
+    Done,
+}
+
+/// Incremental decoder of the `aws-chunked` framing
+pub struct Decoder {
+    buf: BytesMut,
+    state: State,
+    decoded: u64,
+    expected_length: Option<u64>,
+    trailer: Option<(String, Checksum)>,
+    trailer_value: Option<String>,
+}
+
+impl Decoder {
+    /// Decoder for a request body, `None` if it is not `aws-chunked`
+    pub fn from_headers(headers: &HeaderMap) -> Result<Option<Self>, AwsChunkedError> {
+        if !is_aws_chunked(headers) {
+            return Ok(None);
+        }
+        let trailer = match headers.get("x-amz-trailer").and_then(|v| v.to_str().ok()) {
+            Some(name) => {
+                let name = name.trim().to_ascii_lowercase();
+                let checksum = Checksum::for_trailer(&name)?;
+                Some((name, checksum))
+            }
+            None => None,
+        };
+        Ok(Some(Self {
+            buf: BytesMut::new(),
+            state: State::Header,
+            decoded: 0,
+            expected_length: decoded_content_length(headers),
+            trailer,
+            trailer_value: None,
+        }))
+    }
+
+    /// Add body bytes as received
+    fn push(&mut self, mut data: impl Buf) {
+        while data.has_remaining() {
+            let chunk = data.chunk();
+            self.buf.extend_from_slice(chunk);
+            let n = chunk.len();
+            data.advance(n);
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// Next piece of payload decodable from the bytes received so far
+    ///
+    /// `Ok(None)` when more input is needed or the body is complete.
+    fn next_data(&mut self) -> Result<Option<Bytes>, AwsChunkedError> {
+        loop {
+            match self.state {
+                State::Header => {
+                    let Some(line) = self.take_line()? else {
+                        return Ok(None);
+                    };
+                    let size = line.split(';').next().unwrap_or_default().trim();
+                    let size = usize::from_str_radix(size, 16).map_err(|_| {
+                        AwsChunkedError::Malformed(format!("invalid chunk size '{}'", size))
+                    })?;
+                    self.state = if size == 0 {
+                        State::Trailers
+                    } else {
+                        State::Data(size)
+                    };
+                }
+                State::Data(remaining) => {
+                    if self.buf.is_empty() {
+                        return Ok(None);
+                    }
+                    let n = remaining.min(self.buf.len());
+                    let data = self.buf.split_to(n).freeze();
+                    self.state = match remaining - n {
+                        0 => State::DataEnd,
+                        left => State::Data(left),
+                    };
+                    self.decoded += n as u64;
+                    if let Some((_, checksum)) = self.trailer.as_mut() {
+                        checksum.update(&data);
+                    }
+                    return Ok(Some(data));
+                }
+                State::DataEnd => {
+                    if self.buf.len() < 2 {
+                        return Ok(None);
+                    }
+                    if &self.buf[..2] != b"\r\n" {
+                        return Err(AwsChunkedError::Malformed(
+                            "chunk data longer than its size".into(),
+                        ));
+                    }
+                    self.buf.advance(2);
+                    self.state = State::Header;
+                }
+                State::Trailers => {
+                    let Some(line) = self.take_line()? else {
+                        return Ok(None);
+                    };
+                    if line.is_empty() {
+                        self.state = State::Done;
+                        continue;
+                    }
+                    let (name, value) = line.split_once(':').ok_or_else(|| {
+                        AwsChunkedError::Malformed(format!("invalid trailer '{}'", line))
+                    })?;
+                    let name = name.trim().to_ascii_lowercase();
+                    if self.trailer.as_ref().is_some_and(|(n, _)| *n == name) {
+                        self.trailer_value = Some(value.trim().to_string());
+                    }
+                }
+                State::Done => {
+                    if !self.buf.is_empty() {
+                        return Err(AwsChunkedError::Malformed(
+                            "data after the final chunk".into(),
+                        ));
+                    }
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    /// Take a CRLF-terminated line from the buffer, if complete
+    fn take_line(&mut self) -> Result<Option<String>, AwsChunkedError> {
+        let Some(end) = self.buf.windows(2).position(|w| w == b"\r\n") else {
+            if self.buf.len() > MAX_LINE {
+                return Err(AwsChunkedError::Malformed("line too long".into()));
+            }
+            return Ok(None);
+        };
+        let line = self.buf.split_to(end + 2);
+        String::from_utf8(line[..end].to_vec())
+            .map(Some)
+            .map_err(|_| AwsChunkedError::Malformed("line is not UTF-8".into()))
+    }
+
+    /// Check the decoded payload once the body has ended
+    fn finish(&self) -> Result<(), AwsChunkedError> {
+        if !self.is_done() {
+            return Err(AwsChunkedError::Truncated);
+        }
+        if let Some(expected) = self.expected_length {
+            if expected != self.decoded {
+                return Err(AwsChunkedError::LengthMismatch {
+                    expected,
+                    actual: self.decoded,
+                });
+            }
+        }
+        if let Some((name, checksum)) = &self.trailer {
+            let value = self
+                .trailer_value
+                .as_ref()
+                .ok_or_else(|| AwsChunkedError::MissingChecksum(name.clone()))?;
+            if *value != checksum.finish() {
+                return Err(AwsChunkedError::ChecksumMismatch { name: name.clone() });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Request body with `aws-chunked` framing removed
+///
+/// Without a decoder the body is passed through unchanged.
+pub struct AwsChunkedBody<B> {
+    inner: B,
+    decoder: Option<Decoder>,
+    ended: bool,
+}
+
+impl<B> AwsChunkedBody<B> {
+    /// Decode `inner` with `decoder`, if any
+    pub fn new(inner: B, decoder: Option<Decoder>) -> Self {
+        Self {
+            inner,
+            decoder,
+            ended: false,
+        }
+    }
+}
+
+impl<B> Body for AwsChunkedBody<B>
+where
+    B: Body + Unpin,
+    B::Data: Buf,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    type Data = Bytes;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = &mut *self;
+        let Some(decoder) = this.decoder.as_mut() else {
+            return Pin::new(&mut this.inner).poll_frame(cx).map(|frame| {
+                frame.map(|frame| {
+                    frame
+                        .map(|frame| {
+                            frame.map_data(|mut data| data.copy_to_bytes(data.remaining()))
+                        })
+                        .map_err(Into::into)
+                })
+            });
+        };
+
+        loop {
+            if this.ended {
+                return Poll::Ready(None);
+            }
+            match decoder.next_data() {
+                Ok(Some(data)) => return Poll::Ready(Some(Ok(Frame::data(data)))),
+                Ok(None) => {}
+                Err(e) => {
+                    this.ended = true;
+                    return Poll::Ready(Some(Err(e.into())));
+                }
+            }
+            match std::task::ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+                Some(Ok(frame)) => {
+                    // HTTP trailers are not part of the aws-chunked framing
+                    if let Ok(data) = frame.into_data() {
+                        decoder.push(data);
+                    }
+                }
+                Some(Err(e)) => {
+                    this.ended = true;
+                    return Poll::Ready(Some(Err(e.into())));
+                }
+                None => {
+                    this.ended = true;
+                    return Poll::Ready(decoder.finish().err().map(|e| Err(e.into())));
+                }
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        match self.decoder {
+            Some(_) => self.ended,
+            None => self.inner.is_end_stream(),
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        match self.decoder {
+            Some(ref decoder) => match decoder.expected_length {
+                Some(length) => SizeHint::with_exact(length),
+                None => SizeHint::default(),
+            },
+            None => self.inner.size_hint(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::{BodyExt, StreamBody};
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    /// Decode `frames` as one body sent with `headers`
+    async fn decode(
+        headers: &HeaderMap,
+        frames: &[&'static [u8]],
+    ) -> Result<Bytes, Box<dyn std::error::Error + Send + Sync>> {
+        let frames: Vec<Result<Frame<Bytes>, std::convert::Infallible>> = frames
+            .iter()
+            .map(|f| Ok(Frame::data(Bytes::from_static(f))))
+            .collect();
+        let inner = StreamBody::new(futures::stream::iter(frames));
+        let decoder = Decoder::from_headers(headers).unwrap();
+        let body = AwsChunkedBody::new(inner, decoder);
+        Ok(body.collect().await?.to_bytes())
+    }
+
+    #[test]
+    fn test_checksums() {
+        let check = |name: &str| {
+            let mut checksum = Checksum::for_trailer(name).unwrap();
+            checksum.update(b"1234");
+            checksum.update(b"56789");
+            checksum.finish()
+        };
+
+        assert_eq!(
+            check("x-amz-checksum-crc32"),
+            STANDARD.encode(0xCBF4_3926u32.to_be_bytes())
+        );
+        assert_eq!(
+            check("x-amz-checksum-crc32c"),
+            STANDARD.encode(0xE306_9283u32.to_be_bytes())
+        );
+        assert_eq!(
+            check("x-amz-checksum-crc64nvme"),
+            STANDARD.encode(0xAE8B_1486_0A79_9888u64.to_be_bytes())
+        );
+        assert_eq!(
+            check("x-amz-checksum-sha256"),
+            STANDARD.encode(Sha256::digest(b"123456789"))
+        );
+        assert!(Checksum::for_trailer("x-amz-checksum-sha1").is_err());
+    }
+
+    #[test]
+    fn test_detection() {
+        assert!(!is_aws_chunked(&HeaderMap::new()));
+        assert!(is_aws_chunked(&headers(&[(
+            "content-encoding",
+            "gzip, aws-chunked"
+        )])));
+        assert!(is_aws_chunked(&headers(&[(
+            "x-amz-content-sha256",
+            "STREAMING-UNSIGNED-PAYLOAD-TRAILER"
+        )])));
+        assert!(!is_aws_chunked(&headers(&[(
+            "x-amz-content-sha256",
+            "UNSIGNED-PAYLOAD"
+        )])));
+    }
+
+    #[tokio::test]
+    async fn test_signed_chunks_split_across_frames() {
+        let headers = headers(&[
+            ("content-encoding", "aws-chunked"),
+            ("x-amz-decoded-content-length", "11"),
+        ]);
+
+        let payload = decode(
+            &headers,
+            &[
+                b"6;chunk-signature=abc\r\nhel",
+                b"lo \r\n5;chunk-signature=def\r",
+                b"\nworld\r\n0;chunk-signature=0f0\r\n\r\n",
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(payload, "hello world");
+    }
+
+    #[tokio::test]
+    async fn test_trailer_checksum() {
+        let headers = headers(&[
+            ("content-encoding", "aws-chunked"),
+            ("x-amz-trailer", "x-amz-checksum-crc32"),
+        ]);
+
+        let payload = decode(
+            &headers,
+            &[b"9\r\n123456789\r\n0\r\nx-amz-checksum-crc32:y/Q5Jg==\r\n\r\n"],
+        )
+        .await
+        .unwrap();
+        assert_eq!(payload, "123456789");
+
+        let error = decode(
+            &headers,
+            &[b"9\r\n123456780\r\n0\r\nx-amz-checksum-crc32:y/Q5Jg==\r\n\r\n"],
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("does not match"), "{}", error);
+
+        let error = decode(&headers, &[b"9\r\n123456789\r\n0\r\n\r\n"])
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("missing"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_bodies() {
+        let headers = headers(&[
+            ("content-encoding", "aws-chunked"),
+            ("x-amz-decoded-content-length", "5"),
+        ]);
+
+        for body in [
+            &b"5\r\nhello\r\n"[..],
+            b"5\r\nhello!\r\n0\r\n\r\n",
+            b"x\r\nhello\r\n0\r\n\r\n",
+            b"4\r\nhell\r\n0\r\n\r\n",
+            b"5\r\nhello\r\n0\r\n\r\nmore",
+        ] {
+            assert!(decode(&headers, &[body]).await.is_err(), "{:?}", body);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plain_body_passed_through() {
+        let payload = decode(&HeaderMap::new(), &[b"5\r\nhello\r\n"])
+            .await
+            .unwrap();
+
+        assert_eq!(payload, "5\r\nhello\r\n");
+    }
+}
@@ -0,0 +1,378 @@
+//! Storage backends
+//!
+//! Upload handlers talk to storage through the [`StorageBackend`] trait
+//! instead of `S3Client` directly. This keeps the upload orchestration
+//! independent of where objects end up, and lets it be tested without an
+//! S3 mock server.
+//!
+//! # Implementations
+//!
+//! - [`S3Client`] - Production backend (any S3-compatible service)
+//! - [`LocalFsBackend`](super::local::LocalFsBackend) - Local filesystem, for development and tests
+//! - [`ReplicatedBackend`](super::replication::ReplicatedBackend) - Mirrors writes to a secondary backend
+//! - [`FailoverClient`] - Fails over to a secondary S3 endpoint behind a circuit breaker
+//!
+//! # Example
+//!
+//! ```no_run
+//! use mizuchi_uploadr::upload::backend::StorageBackend;
+//! use mizuchi_uploadr::upload::local::LocalFsBackend;
+//! use mizuchi_uploadr::upload::put_object::PutObjectHandler;
+//! use mizuchi_uploadr::upload::UploadHandler;
+//! use bytes::Bytes;
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let backend = Arc::new(LocalFsBackend::new("uploads", "/var/lib/mizuchi/uploads")?);
+//! let handler = PutObjectHandler::with_backend(backend);
+//!
+//! let result = handler
+//!     .upload("uploads", "hello.txt", Bytes::from("Hello"), Some("text/plain"))
+//!     .await?;
+//! println!("Stored with ETag: {}", result.etag);
+//! # Ok(())
+//! # }
+//! ```
+
+use super::local::LocalFsBackend;
+use super::multipart::{CompletedPart, PendingUpload};
+use super::replication::{DeadLetterLog, ReplicatedBackend};
+use super::UploadError;
+use crate::config::{BucketConfig, ReplicationMode, S3Config, StorageConfig};
+use crate::s3::{
+    CircuitState, FailoverClient, ObjectMetadata, S3Client, S3ClientConfig,
+    S3CompleteMultipartUploadResponse, S3CompletedPart, S3PutObjectResponse, S3UploadPartResponse,
+};
+use async_trait::async_trait;
+use bytes::Bytes;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Request headers of a conditional write (lowercase)
+///
+/// `if-none-match: *` only creates the object if the key is free;
+/// `if-match: <etag>` only overwrites the object with that ETag.
+pub const CONDITIONAL_HEADERS: [&str; 2] = ["if-match", "if-none-match"];
+
+/// An object or part written by a [`StorageBackend`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StoredObject {
+    pub etag: String,
+    /// Backend response metadata (version ID, request ID, checksums)
+    pub metadata: ObjectMetadata,
+}
+
+impl StoredObject {
+    /// An object known only by its ETag
+    pub fn new(etag: impl Into<String>) -> Self {
+        Self {
+            etag: etag.into(),
+            metadata: ObjectMetadata::default(),
+        }
+    }
+}
+
+impl From<S3PutObjectResponse> for StoredObject {
+    fn from(response: S3PutObjectResponse) -> Self {
+        Self {
+            etag: response.etag,
+            metadata: response.metadata,
+        }
+    }
+}
+
+impl From<S3UploadPartResponse> for StoredObject {
+    fn from(response: S3UploadPartResponse) -> Self {
+        Self {
+            etag: response.etag,
+            metadata: response.metadata,
+        }
+    }
+}
+
+impl From<S3CompleteMultipartUploadResponse> for StoredObject {
+    fn from(response: S3CompleteMultipartUploadResponse) -> Self {
+        Self {
+            etag: response.etag,
+            metadata: response.metadata,
+        }
+    }
+}
+
+/// Object storage backend used by the upload handlers
+///
+/// Keys are relative to the backend's bucket. Every method returns the
+/// identifier the caller needs for the next step (ETag or upload ID),
+/// writes along with the backend's response metadata.
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// Bucket this backend writes to
+    fn bucket(&self) -> &str;
+
+    /// Store an object in a single request
+    async fn put_object(
+        &self,
+        key: &str,
+        body: Bytes,
+        content_type: Option<&str>,
+    ) -> Result<StoredObject, UploadError>;
+
+    /// Store an object along with additional request headers
+    ///
+    /// `headers` carries lowercase S3 request headers such as
+    /// `content-encoding`, `x-amz-meta-*` user metadata, and the
+    /// [`CONDITIONAL_HEADERS`] of a conditional write, which fail with
+    /// [`UploadError::PreconditionFailed`] when they do not hold. Backends
+    /// that keep no object metadata store the body alone.
+    async fn put_object_with_headers(
+        &self,
+        key: &str,
+        body: Bytes,
+        content_type: Option<&str>,
+        _headers: &[(String, String)],
+    ) -> Result<StoredObject, UploadError> {
+        self.put_object(key, body, content_type).await
+    }
+
+    /// Start a multipart upload, returning its upload ID
+    async fn create_multipart_upload(&self, key: &str) -> Result<String, UploadError>;
+
+    /// Start a multipart upload whose object is stored with `headers`
+    ///
+    /// Object metadata (`content-encoding`, `x-amz-meta-*`, `x-amz-tagging`)
+    /// is set when a multipart upload starts, not when it completes.
+    async fn create_multipart_upload_with_headers(
+        &self,
+        key: &str,
+        _headers: &[(String, String)],
+    ) -> Result<String, UploadError> {
+        self.create_multipart_upload(key).await
+    }
+
+    /// Store one part of a multipart upload
+    async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: u32,
+        body: Bytes,
+    ) -> Result<StoredObject, UploadError>;
+
+    /// Assemble the uploaded parts into the final object
+    async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[CompletedPart],
+    ) -> Result<StoredObject, UploadError>;
+
+    /// Assemble the uploaded parts, subject to the [`CONDITIONAL_HEADERS`]
+    /// among `headers`
+    async fn complete_multipart_upload_with_headers(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[CompletedPart],
+        _headers: &[(String, String)],
+    ) -> Result<StoredObject, UploadError> {
+        self.complete_multipart_upload(key, upload_id, parts).await
+    }
+
+    /// Abort a multipart upload and discard its parts
+    async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> Result<(), UploadError>;
+
+    /// List multipart uploads that were started but not completed or aborted
+    ///
+    /// Backends that don't keep multipart state server-side have nothing to
+    /// list.
+    async fn list_multipart_uploads(&self) -> Result<Vec<PendingUpload>, UploadError> {
+        Ok(Vec::new())
+    }
+
+    /// Circuit breaker state of the backend's primary endpoint, if it has one
+    fn circuit_state(&self) -> Option<CircuitState> {
+        None
+    }
+}
+
+#[async_trait]
+impl StorageBackend for S3Client {
+    fn bucket(&self) -> &str {
+        S3Client::bucket(self)
+    }
+
+    async fn put_object(
+        &self,
+        key: &str,
+        body: Bytes,
+        content_type: Option<&str>,
+    ) -> Result<StoredObject, UploadError> {
+        let response = S3Client::put_object(self, key, body, content_type).await?;
+        Ok(response.into())
+    }
+
+    async fn put_object_with_headers(
+        &self,
+        key: &str,
+        body: Bytes,
+        content_type: Option<&str>,
+        headers: &[(String, String)],
+    ) -> Result<StoredObject, UploadError> {
+        let response =
+            S3Client::put_object_with_headers(self, key, body, content_type, headers).await?;
+        Ok(response.into())
+    }
+
+    async fn create_multipart_upload(&self, key: &str) -> Result<String, UploadError> {
+        let response = S3Client::create_multipart_upload(self, key).await?;
+        Ok(response.upload_id)
+    }
+
+    async fn create_multipart_upload_with_headers(
+        &self,
+        key: &str,
+        headers: &[(String, String)],
+    ) -> Result<String, UploadError> {
+        let response = S3Client::create_multipart_upload_with_headers(self, key, headers).await?;
+        Ok(response.upload_id)
+    }
+
+    async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: u32,
+        body: Bytes,
+    ) -> Result<StoredObject, UploadError> {
+        let response = S3Client::upload_part(self, key, upload_id, part_number, body).await?;
+        Ok(response.into())
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[CompletedPart],
+    ) -> Result<StoredObject, UploadError> {
+        StorageBackend::complete_multipart_upload_with_headers(self, key, upload_id, parts, &[])
+            .await
+    }
+
+    async fn complete_multipart_upload_with_headers(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[CompletedPart],
+        headers: &[(String, String)],
+    ) -> Result<StoredObject, UploadError> {
+        let s3_parts = parts
+            .iter()
+            .map(|p| S3CompletedPart {
+                part_number: p.part_number,
+                etag: p.etag.clone(),
+                checksum_crc32c: p.checksum_crc32c.clone(),
+            })
+            .collect();
+
+        let response = S3Client::complete_multipart_upload_with_headers(
+            self, key, upload_id, s3_parts, headers,
+        )
+        .await?;
+        Ok(response.into())
+    }
+
+    async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> Result<(), UploadError> {
+        S3Client::abort_multipart_upload(self, key, upload_id).await?;
+        Ok(())
+    }
+
+    async fn list_multipart_uploads(&self) -> Result<Vec<PendingUpload>, UploadError> {
+        let uploads = S3Client::list_multipart_uploads(self).await?;
+        Ok(uploads.into_iter().map(PendingUpload::from).collect())
+    }
+}
+
+/// Build the storage backend configured for a bucket
+///
+/// Wraps the backend in a [`ReplicatedBackend`] when replication is
+/// configured. Async replication spawns a worker, so this must be called
+/// from within a tokio runtime in that case.
+pub fn from_bucket_config(bucket: &BucketConfig) -> Result<Arc<dyn StorageBackend>, UploadError> {
+    let primary: Arc<dyn StorageBackend> = match &bucket.storage {
+        StorageConfig::S3 => match &bucket.failover {
+            Some(failover) => Arc::new(FailoverClient::new(
+                &bucket.name,
+                s3_client(&bucket.s3)?,
+                s3_client(&failover.target)?,
+                failover.failure_threshold,
+                Duration::from_secs(failover.probe_interval_secs),
+            )),
+            None => s3_backend(&bucket.s3)?,
+        },
+        StorageConfig::Local { root } => Arc::new(LocalFsBackend::new(&bucket.s3.bucket, root)?),
+    };
+
+    let Some(replication) = &bucket.replication else {
+        return Ok(primary);
+    };
+
+    let secondary = s3_backend(&replication.target)?;
+    let backend = match replication.mode {
+        ReplicationMode::Sync => ReplicatedBackend::sync(primary, secondary),
+        ReplicationMode::Async => {
+            let path = replication.dead_letter_path.as_deref().ok_or_else(|| {
+                UploadError::S3Error(format!(
+                    "Bucket '{}' uses async replication without a dead_letter_path",
+                    bucket.name
+                ))
+            })?;
+            ReplicatedBackend::asynchronous(
+                primary,
+                secondary,
+                replication.queue_capacity,
+                replication.max_retries,
+                Arc::new(DeadLetterLog::new(path)),
+            )
+        }
+    };
+
+    Ok(Arc::new(backend))
+}
+
+/// Build an S3 backend for a single target, without failover or replication
+pub fn s3_backend(config: &S3Config) -> Result<Arc<dyn StorageBackend>, UploadError> {
+    Ok(Arc::new(s3_client(config)?))
+}
+
+fn s3_client(config: &S3Config) -> Result<S3Client, UploadError> {
+    let client = S3Client::new(S3ClientConfig {
+        bucket: config.bucket.clone(),
+        region: config.region.clone(),
+        endpoint: config.endpoint.clone(),
+        access_key: config.access_key.clone(),
+        secret_key: config.secret_key.clone(),
+        retry: None,
+        timeout: None,
+        pool: config.pool.clone(),
+    })?;
+    let client = match &config.sigv4a_region_set {
+        Some(region_set) => client.with_sigv4a(region_set),
+        None => client,
+    };
+    let client = match &config.hedging {
+        Some(hedging) => client.with_hedging(hedging.clone()),
+        None => client,
+    };
+    let client = match &config.hashing {
+        Some(hashing) => client.with_hashing(hashing.clone()),
+        None => client,
+    };
+    let client = match config.checksum {
+        Some(checksum) => client.with_checksum(checksum),
+        None => client,
+    };
+    Ok(match &config.debug_log {
+        Some(debug_log) => client.with_debug_log(debug_log)?,
+        None => client,
+    })
+}
@@ -0,0 +1,367 @@
+//! Reusable buffers for upload bodies
+//!
+//! Bodies uploaded with a single PUT are received whole before they are
+//! signed and sent. Allocating a buffer per request churns the allocator,
+//! and under many concurrent uploads freed buffers are not handed back to
+//! the OS quickly, so RSS spikes. A [`BufferPool`] instead hands out buffers
+//! sized in whole chunks of `server.zero_copy.pipe_buffer_size` and takes
+//! them back when the upload is done, keeping up to
+//! `server.buffer_pool.max_idle_bytes` of them for the next bodies of the
+//! same size.
+//!
+//! Only bodies with a declared length small enough to be kept are pooled;
+//! others are read as before. A buffer is reused only if nothing else still
+//! holds its bytes when the upload finishes (e.g. a hedged request still in
+//! flight); otherwise it is freed as usual.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use mizuchi_uploadr::config::BufferPoolConfig;
+//! use mizuchi_uploadr::upload::buffer_pool::BufferPool;
+//! use bytes::Bytes;
+//! use http_body_util::Full;
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> Result<(), std::convert::Infallible> {
+//! let pool = Arc::new(BufferPool::new(1024 * 1024, &BufferPoolConfig::default()));
+//!
+//! let body = pool.collect(Full::new(Bytes::from("Hello")), Some(5)).await?;
+//! assert_eq!(body.bytes(), "Hello");
+//! // Dropping `body` gives its buffer back to the pool
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::config::BufferPoolConfig;
+use crate::metrics;
+use bytes::{Bytes, BytesMut};
+use http_body_util::BodyExt;
+use hyper::body::Body;
+use std::collections::HashMap;
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+
+/// Buffers of upload bodies, reused across requests
+#[derive(Debug)]
+pub struct BufferPool {
+    enabled: bool,
+    chunk_size: usize,
+    max_idle_bytes: usize,
+    state: Mutex<PoolState>,
+}
+
+/// Idle buffers by size in chunks, and the bytes handed out
+#[derive(Debug, Default)]
+struct PoolState {
+    idle: HashMap<usize, Vec<BytesMut>>,
+    idle_buffers: usize,
+    idle_bytes: usize,
+    in_use_bytes: usize,
+}
+
+impl PoolState {
+    fn record(&self) {
+        metrics::record_buffer_pool_occupancy(
+            self.idle_buffers,
+            self.idle_bytes,
+            self.in_use_bytes,
+        );
+    }
+}
+
+impl BufferPool {
+    /// Create a pool of buffers sized in chunks of `chunk_size` bytes
+    pub fn new(chunk_size: usize, config: &BufferPoolConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            chunk_size: chunk_size.max(1),
+            max_idle_bytes: config.max_idle_bytes,
+            state: Mutex::new(PoolState::default()),
+        }
+    }
+
+    /// Read `body` whole, into a pooled buffer if `length` is small enough
+    /// for one to be kept
+    pub async fn collect<B>(
+        self: &Arc<Self>,
+        body: B,
+        length: Option<u64>,
+    ) -> Result<PooledBytes, B::Error>
+    where
+        B: Body<Data = Bytes>,
+    {
+        let Some(capacity) = length.and_then(|length| self.capacity_for(length)) else {
+            let bytes = body.collect().await?.to_bytes();
+            return Ok(PooledBytes {
+                bytes,
+                reserved: 0,
+                pool: None,
+            });
+        };
+
+        let mut buffer = self.acquire(capacity);
+        let mut body = pin!(body);
+        while let Some(frame) = body.frame().await {
+            match frame {
+                Ok(frame) => {
+                    if let Ok(data) = frame.into_data() {
+                        buffer.extend_from_slice(&data);
+                    }
+                }
+                Err(e) => {
+                    buffer.clear();
+                    self.put(buffer, capacity);
+                    return Err(e);
+                }
+            }
+        }
+
+        // Bodies longer than declared grow the buffer; count what it holds
+        let reserved = buffer.capacity();
+        if reserved > capacity {
+            let mut state = self.state.lock().unwrap();
+            state.in_use_bytes += reserved - capacity;
+            state.record();
+        }
+        Ok(PooledBytes {
+            bytes: buffer.freeze(),
+            reserved,
+            pool: Some(self.clone()),
+        })
+    }
+
+    /// Capacity of the buffer for a body of `length` bytes, if it can be
+    /// pooled
+    fn capacity_for(&self, length: u64) -> Option<usize> {
+        if !self.enabled {
+            return None;
+        }
+        let chunks = usize::try_from(length)
+            .ok()?
+            .div_ceil(self.chunk_size)
+            .max(1);
+        let capacity = chunks.checked_mul(self.chunk_size)?;
+        (capacity <= self.max_idle_bytes).then_some(capacity)
+    }
+
+    /// Take an idle buffer of `capacity` bytes, or allocate one
+    fn acquire(&self, capacity: usize) -> BytesMut {
+        let mut state = self.state.lock().unwrap();
+        let reused = state
+            .idle
+            .get_mut(&(capacity / self.chunk_size))
+            .and_then(Vec::pop);
+        let buffer = match reused {
+            Some(buffer) => {
+                state.idle_buffers -= 1;
+                state.idle_bytes -= capacity;
+                metrics::record_buffer_pool_acquire("reused");
+                buffer
+            }
+            None => {
+                metrics::record_buffer_pool_acquire("allocated");
+                BytesMut::with_capacity(capacity)
+            }
+        };
+        state.in_use_bytes += capacity;
+        state.record();
+        buffer
+    }
+
+    /// Take back the bytes of a buffer handed out with `reserved` bytes
+    fn release(&self, bytes: Bytes, reserved: usize) {
+        match bytes.try_into_mut() {
+            Ok(mut buffer) => {
+                buffer.clear();
+                self.put(buffer, reserved);
+            }
+            Err(_) => {
+                let mut state = self.state.lock().unwrap();
+                state.in_use_bytes -= reserved;
+                state.record();
+                metrics::record_buffer_pool_release("discarded");
+            }
+        }
+    }
+
+    /// Keep an empty `buffer` handed out with `reserved` bytes, if there is
+    /// room for it
+    fn put(&self, buffer: BytesMut, reserved: usize) {
+        // Grown buffers are kept with the bodies they can hold in full chunks
+        let chunks = buffer.capacity() / self.chunk_size;
+        let capacity = chunks * self.chunk_size;
+        let mut state = self.state.lock().unwrap();
+        state.in_use_bytes -= reserved;
+        let kept = chunks > 0 && state.idle_bytes + capacity <= self.max_idle_bytes;
+        if kept {
+            state.idle.entry(chunks).or_default().push(buffer);
+            state.idle_buffers += 1;
+            state.idle_bytes += capacity;
+        }
+        state.record();
+        metrics::record_buffer_pool_release(if kept { "pooled" } else { "discarded" });
+    }
+}
+
+/// A body read by [`BufferPool::collect`]
+///
+/// Gives its buffer back to the pool when dropped.
+#[derive(Debug)]
+pub struct PooledBytes {
+    bytes: Bytes,
+    reserved: usize,
+    pool: Option<Arc<BufferPool>>,
+}
+
+impl PooledBytes {
+    /// The body, sharing the pooled buffer
+    ///
+    /// The buffer is only reused if every copy is dropped before this.
+    pub fn bytes(&self) -> Bytes {
+        self.bytes.clone()
+    }
+
+    /// Length of the body in bytes
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Whether the body is empty
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+impl Drop for PooledBytes {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            pool.release(std::mem::take(&mut self.bytes), self.reserved);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::{Full, StreamBody};
+    use hyper::body::Frame;
+
+    const CHUNK: usize = 1024;
+
+    fn pool(max_idle_bytes: usize) -> Arc<BufferPool> {
+        Arc::new(BufferPool::new(
+            CHUNK,
+            &BufferPoolConfig {
+                enabled: true,
+                max_idle_bytes,
+            },
+        ))
+    }
+
+    fn body(len: usize) -> Full<Bytes> {
+        Full::new(Bytes::from(vec![7u8; len]))
+    }
+
+    fn idle(pool: &BufferPool) -> (usize, usize, usize) {
+        let state = pool.state.lock().unwrap();
+        (state.idle_buffers, state.idle_bytes, state.in_use_bytes)
+    }
+
+    #[tokio::test]
+    async fn test_buffers_sized_in_chunks_and_reused() {
+        let pool = pool(16 * CHUNK);
+
+        let first = pool.collect(body(1500), Some(1500)).await.unwrap();
+        assert_eq!(first.bytes(), Bytes::from(vec![7u8; 1500]));
+        assert_eq!(idle(&pool), (0, 0, 2 * CHUNK));
+        let address = first.bytes().as_ptr();
+        drop(first);
+        assert_eq!(idle(&pool), (1, 2 * CHUNK, 0));
+
+        // A body needing as many chunks gets the same buffer back
+        let second = pool.collect(body(2000), Some(2000)).await.unwrap();
+        assert_eq!(second.bytes().as_ptr(), address);
+        assert_eq!(second.len(), 2000);
+        assert_eq!(idle(&pool), (0, 0, 2 * CHUNK));
+
+        // One needing more chunks does not
+        let third = pool.collect(body(3000), Some(3000)).await.unwrap();
+        assert_ne!(third.bytes().as_ptr(), address);
+        drop((second, third));
+        assert_eq!(idle(&pool), (2, 5 * CHUNK, 0));
+    }
+
+    #[tokio::test]
+    async fn test_shared_buffers_not_reused() {
+        let pool = pool(16 * CHUNK);
+        let received = pool.collect(body(100), Some(100)).await.unwrap();
+        let still_sending = received.bytes();
+        drop(received);
+        assert_eq!(idle(&pool), (0, 0, 0));
+        assert_eq!(still_sending.len(), 100);
+    }
+
+    #[tokio::test]
+    async fn test_idle_bytes_capped() {
+        let pool = pool(2 * CHUNK);
+        let bodies = [
+            pool.collect(body(100), Some(100)).await.unwrap(),
+            pool.collect(body(100), Some(100)).await.unwrap(),
+            pool.collect(body(100), Some(100)).await.unwrap(),
+        ];
+        drop(bodies);
+        assert_eq!(idle(&pool), (2, 2 * CHUNK, 0));
+    }
+
+    #[tokio::test]
+    async fn test_large_unsized_or_disabled_bodies_not_pooled() {
+        let pool = pool(2 * CHUNK);
+        for length in [Some(3 * CHUNK as u64), None] {
+            let len = length.unwrap_or(10) as usize;
+            let received = pool.collect(body(len), length).await.unwrap();
+            assert_eq!(received.len(), len);
+            assert_eq!(idle(&pool), (0, 0, 0));
+            drop(received);
+            assert_eq!(idle(&pool), (0, 0, 0));
+        }
+
+        let disabled = Arc::new(BufferPool::new(
+            CHUNK,
+            &BufferPoolConfig {
+                enabled: false,
+                max_idle_bytes: 2 * CHUNK,
+            },
+        ));
+        drop(disabled.collect(body(100), Some(100)).await.unwrap());
+        assert_eq!(idle(&disabled), (0, 0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_overlong_body_grows_buffer() {
+        let pool = pool(16 * CHUNK);
+        let received = pool.collect(body(3 * CHUNK), Some(10)).await.unwrap();
+        assert_eq!(received.len(), 3 * CHUNK);
+        let (_, _, in_use) = idle(&pool);
+        assert!(in_use >= 3 * CHUNK);
+        drop(received);
+        let (buffers, idle_bytes, in_use) = idle(&pool);
+        assert_eq!((buffers, in_use), (1, 0));
+        assert!(idle_bytes >= 3 * CHUNK);
+    }
+
+    #[tokio::test]
+    async fn test_read_error_returns_buffer() {
+        let pool = pool(16 * CHUNK);
+        let frames: Vec<Result<Frame<Bytes>, &str>> = vec![
+            Ok(Frame::data(Bytes::from_static(b"partial"))),
+            Err("connection reset"),
+        ];
+        let body = StreamBody::new(futures::stream::iter(frames));
+        assert_eq!(
+            pool.collect(body, Some(100)).await.unwrap_err(),
+            "connection reset"
+        );
+        assert_eq!(idle(&pool), (1, CHUNK, 0));
+    }
+}
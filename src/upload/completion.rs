@@ -0,0 +1,148 @@
+//! CompleteMultipartUpload request bodies
+//!
+//! A client completes a multipart upload by POSTing the parts it wants
+//! assembled, in order:
+//!
+//! ```xml
+//! <CompleteMultipartUpload>
+//!   <Part><PartNumber>1</PartNumber><ETag>"a54357aff0632cce46d942af68356b38"</ETag></Part>
+//!   <Part><PartNumber>2</PartNumber><ETag>"0c78aef83f66abc1fa1e8477f296d394"</ETag></Part>
+//! </CompleteMultipartUpload>
+//! ```
+//!
+//! [`parse_completion`] reads the parts out of such a body; other elements
+//! (checksums) are ignored. The parts are then checked against the uploaded
+//! ones by [`MultipartHandler::complete_parts`](super::multipart::MultipartHandler::complete_parts),
+//! which completes them with the checksums they were stored with.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::upload::completion::parse_completion;
+//!
+//! let parts = parse_completion(
+//!     b"<CompleteMultipartUpload><Part><PartNumber>1</PartNumber>\
+//!       <ETag>\"etag-1\"</ETag></Part></CompleteMultipartUpload>",
+//! )
+//! .unwrap();
+//! assert_eq!(parts[0].part_number, 1);
+//! assert_eq!(parts[0].etag, "\"etag-1\"");
+//! ```
+
+use super::multipart::{validate_part_number, CompletedPart};
+use super::UploadError;
+use crate::s3::S3ErrorCode;
+use serde::Deserialize;
+
+/// `CompleteMultipartUpload` document
+#[derive(Debug, Deserialize)]
+struct CompleteMultipartUpload {
+    #[serde(rename = "Part", default)]
+    parts: Vec<Part>,
+}
+
+/// `Part` element of a `CompleteMultipartUpload` document
+#[derive(Debug, Deserialize)]
+struct Part {
+    #[serde(rename = "PartNumber")]
+    part_number: u32,
+    #[serde(rename = "ETag")]
+    etag: String,
+}
+
+/// Parts listed by a CompleteMultipartUpload request body
+///
+/// Fails with `MalformedXML` when the body is not such a document, lists no
+/// parts or has a part without an ETag, and with `InvalidArgument` for part
+/// numbers outside 1 to 10000.
+pub fn parse_completion(body: &[u8]) -> Result<Vec<CompletedPart>, UploadError> {
+    let body = std::str::from_utf8(body).map_err(|e| malformed(e.to_string()))?;
+    let document: CompleteMultipartUpload =
+        quick_xml::de::from_str(body).map_err(|e| malformed(e.to_string()))?;
+    if document.parts.is_empty() {
+        return Err(malformed("At least one part must be specified".into()));
+    }
+
+    document
+        .parts
+        .into_iter()
+        .map(|part| {
+            validate_part_number(part.part_number)?;
+            let etag = part.etag.trim();
+            if etag.trim_matches('"').is_empty() {
+                return Err(malformed(format!("Part {} has no ETag", part.part_number)));
+            }
+            Ok(CompletedPart {
+                part_number: part.part_number,
+                etag: etag.to_string(),
+                checksum_crc32c: None,
+            })
+        })
+        .collect()
+}
+
+fn malformed(message: String) -> UploadError {
+    UploadError::InvalidParts {
+        code: S3ErrorCode::MalformedXML,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(body: &str) -> Option<S3ErrorCode> {
+        match parse_completion(body.as_bytes()) {
+            Err(UploadError::InvalidParts { code, .. }) => Some(code),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_parse_parts() {
+        let parts = parse_completion(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+            <CompleteMultipartUpload xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+                <Part>
+                    <ChecksumCRC32>AAAAAA==</ChecksumCRC32>
+                    <ETag>"etag-1"</ETag>
+                    <PartNumber>1</PartNumber>
+                </Part>
+                <Part><PartNumber>3</PartNumber><ETag>&quot;etag-3&quot;</ETag></Part>
+            </CompleteMultipartUpload>"#,
+        )
+        .unwrap();
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].part_number, 1);
+        assert_eq!(parts[0].etag, "\"etag-1\"");
+        assert_eq!(parts[1].part_number, 3);
+        assert_eq!(parts[1].etag, "\"etag-3\"");
+    }
+
+    #[test]
+    fn test_malformed_bodies() {
+        assert_eq!(code("not xml"), Some(S3ErrorCode::MalformedXML));
+        assert_eq!(
+            code("<CompleteMultipartUpload></CompleteMultipartUpload>"),
+            Some(S3ErrorCode::MalformedXML)
+        );
+        assert_eq!(
+            code("<CompleteMultipartUpload><Part><PartNumber>x</PartNumber><ETag>e</ETag></Part></CompleteMultipartUpload>"),
+            Some(S3ErrorCode::MalformedXML)
+        );
+        assert_eq!(
+            code("<CompleteMultipartUpload><Part><PartNumber>1</PartNumber><ETag></ETag></Part></CompleteMultipartUpload>"),
+            Some(S3ErrorCode::MalformedXML)
+        );
+    }
+
+    #[test]
+    fn test_part_number_out_of_range() {
+        assert_eq!(
+            code("<CompleteMultipartUpload><Part><PartNumber>10001</PartNumber><ETag>e</ETag></Part></CompleteMultipartUpload>"),
+            Some(S3ErrorCode::InvalidArgument)
+        );
+    }
+}
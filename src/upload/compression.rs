@@ -0,0 +1,183 @@
+//! Compression of uploads before storage
+//!
+//! Buckets with `upload.compression` compress upload bodies with gzip or
+//! zstd before forwarding them, trading proxy CPU for storage cost on
+//! compressible data such as logs. Compressed objects are stored with a
+//! `Content-Encoding` matching the algorithm and their original size in
+//! [`ORIGINAL_SIZE_HEADER`] user metadata, so readers can decode them
+//! transparently.
+//!
+//! Small bodies, already-compressed content types, bodies the client sent
+//! with its own `Content-Encoding`, and bodies that would not shrink are
+//! stored as sent.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::config::CompressionConfig;
+//! use mizuchi_uploadr::upload::compression::Compressor;
+//!
+//! let compressor = Compressor::new(&CompressionConfig::default());
+//!
+//! assert!(compressor.should_compress(Some("text/plain"), 4096));
+//! assert!(!compressor.should_compress(Some("image/png"), 4096));
+//! assert!(!compressor.should_compress(Some("text/plain"), 16));
+//! ```
+
+use super::content_type::{essence, matches_any};
+use crate::config::{CompressionAlgorithm, CompressionConfig};
+use bytes::Bytes;
+use std::io::{self, Write};
+
+/// User metadata header holding an object's size before compression
+pub const ORIGINAL_SIZE_HEADER: &str = "x-amz-meta-mizuchi-original-size";
+
+/// Compression stage of one bucket
+#[derive(Debug, Clone)]
+pub struct Compressor {
+    algorithm: CompressionAlgorithm,
+    level: Option<i32>,
+    min_size: u64,
+    exclude: Vec<String>,
+}
+
+impl Compressor {
+    /// Create a compressor from bucket configuration
+    pub fn new(config: &CompressionConfig) -> Self {
+        Self {
+            algorithm: config.algorithm,
+            level: config.level,
+            min_size: config.min_size,
+            exclude: config
+                .exclude_content_types
+                .iter()
+                .map(|t| t.trim().to_ascii_lowercase())
+                .collect(),
+        }
+    }
+
+    /// `Content-Encoding` of compressed objects
+    pub fn content_encoding(&self) -> &'static str {
+        match self.algorithm {
+            CompressionAlgorithm::Gzip => "gzip",
+            CompressionAlgorithm::Zstd => "zstd",
+        }
+    }
+
+    /// Whether a body of `size` bytes with `content_type` is worth compressing
+    pub fn should_compress(&self, content_type: Option<&str>, size: u64) -> bool {
+        size >= self.min_size
+            && !content_type
+                .map(essence)
+                .is_some_and(|mime| matches_any(&self.exclude, &mime))
+    }
+
+    /// Compress `body` on the blocking thread pool
+    pub async fn compress(&self, body: Bytes) -> io::Result<Bytes> {
+        let (algorithm, level) = (self.algorithm, self.level);
+        tokio::task::spawn_blocking(move || compress(algorithm, level, &body))
+            .await
+            .map_err(io::Error::other)?
+    }
+
+    /// Headers to store a compressed object of `original_size` bytes with
+    pub fn headers(&self, original_size: u64) -> Vec<(String, String)> {
+        vec![
+            (
+                "content-encoding".to_string(),
+                self.content_encoding().to_string(),
+            ),
+            (ORIGINAL_SIZE_HEADER.to_string(), original_size.to_string()),
+        ]
+    }
+}
+
+fn compress(algorithm: CompressionAlgorithm, level: Option<i32>, body: &[u8]) -> io::Result<Bytes> {
+    let compressed = match algorithm {
+        CompressionAlgorithm::Gzip => {
+            let level = level
+                .map(|level| flate2::Compression::new(level.clamp(0, 9) as u32))
+                .unwrap_or_default();
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), level);
+            encoder.write_all(body)?;
+            encoder.finish()?
+        }
+        CompressionAlgorithm::Zstd => {
+            zstd::encode_all(body, level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL))?
+        }
+    };
+    Ok(Bytes::from(compressed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn compressor(algorithm: CompressionAlgorithm) -> Compressor {
+        Compressor::new(&CompressionConfig {
+            algorithm,
+            ..Default::default()
+        })
+    }
+
+    fn log_lines() -> Bytes {
+        Bytes::from("GET /index.html 200 1024\n".repeat(200))
+    }
+
+    #[tokio::test]
+    async fn test_gzip_round_trip() {
+        let body = log_lines();
+
+        let compressed = compressor(CompressionAlgorithm::Gzip)
+            .compress(body.clone())
+            .await
+            .unwrap();
+
+        assert!(compressed.len() < body.len());
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(&compressed[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, body);
+    }
+
+    #[tokio::test]
+    async fn test_zstd_round_trip() {
+        let body = log_lines();
+
+        let compressed = compressor(CompressionAlgorithm::Zstd)
+            .compress(body.clone())
+            .await
+            .unwrap();
+
+        assert!(compressed.len() < body.len());
+        assert_eq!(zstd::decode_all(&compressed[..]).unwrap(), body);
+    }
+
+    #[test]
+    fn test_should_compress() {
+        let compressor = Compressor::new(&CompressionConfig {
+            min_size: 100,
+            exclude_content_types: vec!["image/*".into(), "Application/Zip".into()],
+            ..Default::default()
+        });
+
+        assert!(compressor.should_compress(Some("text/plain; charset=utf-8"), 100));
+        assert!(compressor.should_compress(None, 100));
+        assert!(!compressor.should_compress(Some("text/plain"), 99));
+        assert!(!compressor.should_compress(Some("image/jpeg"), 1000));
+        assert!(!compressor.should_compress(Some("application/zip"), 1000));
+    }
+
+    #[test]
+    fn test_headers() {
+        assert_eq!(
+            compressor(CompressionAlgorithm::Zstd).headers(4096),
+            vec![
+                ("content-encoding".to_string(), "zstd".to_string()),
+                (ORIGINAL_SIZE_HEADER.to_string(), "4096".to_string()),
+            ]
+        );
+    }
+}
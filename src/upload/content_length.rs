@@ -0,0 +1,155 @@
+//! Content-Length enforcement
+//!
+//! A connection closed mid-body, an HTTP/2 stream or a body handed to the
+//! embedded service can deliver fewer or more bytes than the request's
+//! `Content-Length` declares. Storing such a body would turn a broken
+//! upload into a partial object, so [`LengthCheckedBody`] counts the bytes
+//! read and fails the body as soon as they cannot match the declared
+//! length.
+
+use bytes::Buf;
+use hyper::body::{Body, Frame, SizeHint};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use thiserror::Error;
+
+/// Body length differing from its `Content-Length`
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ContentLengthError {
+    #[error("Body ended after {received} of the {expected} bytes declared by Content-Length")]
+    Truncated { expected: u64, received: u64 },
+
+    #[error("Body exceeds the {expected} bytes declared by Content-Length")]
+    Overlong { expected: u64, received: u64 },
+}
+
+impl ContentLengthError {
+    /// Metrics label of the mismatch
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ContentLengthError::Truncated { .. } => "truncated",
+            ContentLengthError::Overlong { .. } => "overlong",
+        }
+    }
+}
+
+/// Request body checked against its declared length
+pub struct LengthCheckedBody<B> {
+    inner: B,
+    expected: Option<u64>,
+    received: u64,
+    failed: bool,
+}
+
+impl<B> LengthCheckedBody<B> {
+    /// Check `inner` against `expected` bytes, if a length was declared
+    pub fn new(inner: B, expected: Option<u64>) -> Self {
+        Self {
+            inner,
+            expected,
+            received: 0,
+            failed: false,
+        }
+    }
+
+    fn fail(&mut self, error: ContentLengthError) -> Box<dyn std::error::Error + Send + Sync> {
+        self.failed = true;
+        Box::new(error)
+    }
+}
+
+impl<B> Body for LengthCheckedBody<B>
+where
+    B: Body + Unpin,
+    B::Data: Buf,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    type Data = B::Data;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        if self.failed {
+            return Poll::Ready(None);
+        }
+        let poll = std::task::ready!(Pin::new(&mut self.inner).poll_frame(cx));
+        let Some(expected) = self.expected else {
+            return Poll::Ready(poll.map(|frame| frame.map_err(Into::into)));
+        };
+        match poll {
+            Some(Ok(frame)) => {
+                if let Some(data) = frame.data_ref() {
+                    self.received += data.remaining() as u64;
+                }
+                if self.received > expected {
+                    let received = self.received;
+                    return Poll::Ready(Some(Err(
+                        self.fail(ContentLengthError::Overlong { expected, received })
+                    )));
+                }
+                Poll::Ready(Some(Ok(frame)))
+            }
+            Some(Err(e)) => Poll::Ready(Some(Err(e.into()))),
+            None if self.received < expected => {
+                let received = self.received;
+                Poll::Ready(Some(Err(
+                    self.fail(ContentLengthError::Truncated { expected, received })
+                )))
+            }
+            None => Poll::Ready(None),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        // A short body must still be polled to report it
+        self.failed
+            || (self.inner.is_end_stream()
+                && self
+                    .expected
+                    .is_none_or(|expected| self.received >= expected))
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use http_body_util::{BodyExt, Full};
+
+    async fn read(body: &'static str, expected: Option<u64>) -> Result<Bytes, ContentLengthError> {
+        LengthCheckedBody::new(Full::new(Bytes::from(body)), expected)
+            .collect()
+            .await
+            .map(|collected| collected.to_bytes())
+            .map_err(|e| *e.downcast::<ContentLengthError>().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_matching_length() {
+        assert_eq!(read("hello", Some(5)).await.unwrap(), "hello");
+        assert_eq!(read("hello", None).await.unwrap(), "hello");
+    }
+
+    #[tokio::test]
+    async fn test_truncated_body() {
+        assert_eq!(
+            read("hel", Some(5)).await,
+            Err(ContentLengthError::Truncated {
+                expected: 5,
+                received: 3
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_overlong_body() {
+        let error = read("hello world", Some(5)).await.unwrap_err();
+        assert_eq!(error.kind(), "overlong");
+    }
+}
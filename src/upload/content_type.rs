@@ -0,0 +1,240 @@
+//! Content type allow-list and sniffing
+//!
+//! Buckets with `upload.content_type` only accept the configured content
+//! types. The declared `Content-Type` is checked before the body is read;
+//! with sniffing enabled, the start of the body is matched against known
+//! file signatures (via the `infer` crate) once it has arrived, so an
+//! executable labelled `image/png` is caught before it reaches storage.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::config::ContentTypeConfig;
+//! use mizuchi_uploadr::upload::content_type::ContentTypePolicy;
+//!
+//! let policy = ContentTypePolicy::new(&ContentTypeConfig {
+//!     allowed_content_types: vec!["image/*".into()],
+//!     sniff: true,
+//!     ..Default::default()
+//! });
+//! let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+//! let mut elf = vec![0u8; 64];
+//! elf[..4].copy_from_slice(b"\x7fELF");
+//!
+//! assert!(policy.check_declared(Some("image/png")).is_ok());
+//! assert!(policy.check_content(Some("image/png"), png).is_ok());
+//! assert!(policy.check_content(Some("image/png"), &elf).is_err());
+//! ```
+
+use crate::config::{ContentTypeConfig, ContentTypeEnforcement};
+use thiserror::Error;
+
+/// Reasons an upload's content type is refused
+#[derive(Error, Debug, PartialEq)]
+pub enum ContentTypeError {
+    #[error("Content type not allowed: {0}")]
+    NotAllowed(String),
+
+    #[error("Content declared as {declared} looks like {detected}")]
+    Mismatch { declared: String, detected: String },
+}
+
+/// Check a MIME type against a list of exact types and `type/*` wildcards
+///
+/// Both sides are expected in lowercase without parameters.
+pub fn matches_any(patterns: &[String], mime: &str) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| match pattern.strip_suffix("/*") {
+            Some(top_level) => top_level_type(mime) == Some(top_level),
+            None => pattern == mime,
+        })
+}
+
+/// Lowercase MIME type of a `Content-Type` value, without parameters
+pub fn essence(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/// Declared type that says nothing about the content
+const GENERIC_CONTENT_TYPE: &str = "application/octet-stream";
+
+fn top_level_type(mime: &str) -> Option<&str> {
+    mime.split_once('/').map(|(top_level, _)| top_level)
+}
+
+/// Content type constraints of one bucket
+#[derive(Debug)]
+pub struct ContentTypePolicy {
+    allowed: Vec<String>,
+    sniff_bytes: Option<usize>,
+    enforce: ContentTypeEnforcement,
+}
+
+impl ContentTypePolicy {
+    /// Create a policy from bucket configuration
+    pub fn new(config: &ContentTypeConfig) -> Self {
+        Self {
+            allowed: config
+                .allowed_content_types
+                .iter()
+                .map(|t| t.trim().to_ascii_lowercase())
+                .collect(),
+            sniff_bytes: config.sniff.then_some(config.sniff_bytes),
+            enforce: config.enforce,
+        }
+    }
+
+    /// Bytes of the body to sniff, or `None` if sniffing is disabled
+    pub fn sniff_bytes(&self) -> Option<usize> {
+        self.sniff_bytes
+    }
+
+    /// What to do with a violation
+    pub fn enforce(&self) -> ContentTypeEnforcement {
+        self.enforce
+    }
+
+    /// Check the declared content type against the allow-list
+    ///
+    /// A missing content type is rejected unless sniffing may still
+    /// determine it from the body.
+    pub fn check_declared(&self, content_type: Option<&str>) -> Result<(), ContentTypeError> {
+        match content_type.map(essence).filter(|mime| !mime.is_empty()) {
+            Some(mime) => self.check_allowed(&mime),
+            None if self.sniff_bytes.is_some() || self.allowed.is_empty() => Ok(()),
+            None => Err(ContentTypeError::NotAllowed("(none)".to_string())),
+        }
+    }
+
+    /// Check the content type detected from the start of the body
+    ///
+    /// `head` is the first [`ContentTypePolicy::sniff_bytes`] bytes of the
+    /// body. Content that matches no known signature (such as plain text)
+    /// is judged by its declared type alone, and content declared as
+    /// `application/octet-stream` by its detected type alone. Always passes
+    /// when sniffing is disabled.
+    pub fn check_content(
+        &self,
+        content_type: Option<&str>,
+        head: &[u8],
+    ) -> Result<(), ContentTypeError> {
+        let Some(limit) = self.sniff_bytes else {
+            return Ok(());
+        };
+        let declared = content_type.map(essence).filter(|mime| !mime.is_empty());
+        let Some(detected) = infer::get(&head[..head.len().min(limit)]) else {
+            return match declared {
+                Some(_) => Ok(()),
+                None if self.allowed.is_empty() => Ok(()),
+                None => Err(ContentTypeError::NotAllowed("(none)".to_string())),
+            };
+        };
+
+        let detected = detected.mime_type();
+        self.check_allowed(detected)?;
+        match declared {
+            Some(declared)
+                if declared != GENERIC_CONTENT_TYPE
+                    && top_level_type(&declared) != top_level_type(detected) =>
+            {
+                Err(ContentTypeError::Mismatch {
+                    declared,
+                    detected: detected.to_string(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_allowed(&self, mime: &str) -> Result<(), ContentTypeError> {
+        if self.allowed.is_empty() || matches_any(&self.allowed, mime) {
+            Ok(())
+        } else {
+            Err(ContentTypeError::NotAllowed(mime.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    /// An ELF header, padded to the length signature matching requires
+    fn elf() -> Vec<u8> {
+        let mut elf = vec![0u8; 64];
+        elf[..4].copy_from_slice(b"\x7fELF");
+        elf
+    }
+
+    fn policy(allowed: &[&str], sniff: bool) -> ContentTypePolicy {
+        ContentTypePolicy::new(&ContentTypeConfig {
+            allowed_content_types: allowed.iter().map(|t| t.to_string()).collect(),
+            sniff,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_declared_allow_list() {
+        let policy = policy(&["image/*", "Text/Plain"], false);
+
+        assert!(policy.check_declared(Some("image/png")).is_ok());
+        assert!(policy
+            .check_declared(Some("text/plain; charset=utf-8"))
+            .is_ok());
+        assert_eq!(
+            policy.check_declared(Some("application/x-msdownload")),
+            Err(ContentTypeError::NotAllowed(
+                "application/x-msdownload".into()
+            ))
+        );
+        assert!(policy.check_declared(None).is_err());
+        // Without sniffing, the body is never inspected
+        assert!(policy.check_content(Some("image/png"), &elf()).is_ok());
+    }
+
+    #[test]
+    fn test_sniffed_content() {
+        let policy = policy(&["image/*", "text/plain"], true);
+
+        assert!(policy.check_content(Some("image/png"), PNG).is_ok());
+        // Undetectable content is judged by its declared type
+        assert!(policy.check_content(Some("text/plain"), b"hello").is_ok());
+        // Missing types are decided by sniffing
+        assert!(policy.check_declared(None).is_ok());
+        assert!(policy.check_content(None, PNG).is_ok());
+        assert!(policy.check_content(None, b"hello").is_err());
+        assert_eq!(
+            policy.check_content(Some("image/png"), &elf()),
+            Err(ContentTypeError::NotAllowed(
+                "application/x-executable".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_mislabeled_content() {
+        let policy = policy(&[], true);
+
+        assert_eq!(
+            policy.check_content(Some("text/plain"), PNG),
+            Err(ContentTypeError::Mismatch {
+                declared: "text/plain".into(),
+                detected: "image/png".into(),
+            })
+        );
+        // Subtypes may differ within the same top-level type
+        assert!(policy.check_content(Some("image/jpeg"), PNG).is_ok());
+        assert!(policy
+            .check_content(Some("application/octet-stream"), PNG)
+            .is_ok());
+    }
+}
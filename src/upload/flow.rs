@@ -0,0 +1,488 @@
+//! Backpressure-aware body forwarding
+//!
+//! Collecting or spilling a body before uploading it holds the whole object
+//! at once. A [`FlowControl`] instead uploads large bodies as multipart
+//! parts while they are still arriving, so only the bytes received but not
+//! yet stored are held in memory.
+//!
+//! When the backend is slower than the client those bytes pile up. Once
+//! they reach the high-water mark, reading from the client stops, so TCP
+//! flow control slows the sender down, until part uploads bring them down
+//! to the low-water mark. Pauses are counted in
+//! `mizuchi_upload_pauses_total` and `mizuchi_upload_paused_seconds_total`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use mizuchi_uploadr::config::{FlowControlConfig, UploadConfig};
+//! use mizuchi_uploadr::upload::flow::FlowControl;
+//! use mizuchi_uploadr::upload::local::LocalFsBackend;
+//! use bytes::Bytes;
+//! use http_body_util::Full;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let flow = FlowControl::new("uploads", &FlowControlConfig::default());
+//! let backend = LocalFsBackend::new("uploads", "/var/lib/mizuchi/uploads")?;
+//!
+//! let body = Full::new(Bytes::from(vec![0u8; 64 * 1024 * 1024]));
+//! let (object, size) = flow
+//!     .forward(body, &backend, "large.bin", None, &[], &UploadConfig::default(), None)
+//!     .await?;
+//! println!("Stored {} bytes with ETag: {}", size, object.etag);
+//! # Ok(())
+//! # }
+//! ```
+
+use super::backend::{StorageBackend, StoredObject, CONDITIONAL_HEADERS};
+use super::multipart::{part_size_for, validate_part_number, CompletedPart, MAX_PUT_SIZE};
+use super::progress::SessionHandle;
+use super::UploadError;
+use crate::config::{FlowControlConfig, UploadConfig};
+use crate::error::ErrorContext;
+use crate::metrics;
+use bytes::{Bytes, BytesMut};
+use futures::stream::{FuturesUnordered, StreamExt};
+use http_body_util::BodyExt;
+use hyper::body::Body;
+use std::collections::VecDeque;
+use std::time::Instant;
+use thiserror::Error;
+use tracing::{debug, warn};
+
+/// Forwarding errors
+#[derive(Error, Debug)]
+pub enum ForwardError {
+    #[error("Failed to read body: {0}")]
+    Body(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error(transparent)]
+    Upload(UploadError),
+}
+
+/// Flow control of one bucket
+#[derive(Debug, Clone)]
+pub struct FlowControl {
+    bucket: String,
+    high_water_mark: u64,
+    low_water_mark: u64,
+}
+
+impl FlowControl {
+    /// Create the flow control described by `config`
+    pub fn new(bucket: &str, config: &FlowControlConfig) -> Self {
+        Self {
+            bucket: bucket.to_string(),
+            high_water_mark: config.high_water_mark,
+            low_water_mark: config.low_water_mark,
+        }
+    }
+
+    /// Check if a body with the declared length should be forwarded
+    ///
+    /// Only bodies declared larger than `config.multipart_threshold`, or
+    /// than a single PUT can store, are; unsized bodies might turn out small
+    /// enough for a single request.
+    pub fn applies(&self, content_length: Option<u64>, config: &UploadConfig) -> bool {
+        content_length
+            .is_some_and(|length| length > (config.multipart_threshold as u64).min(MAX_PUT_SIZE))
+    }
+
+    /// Upload `body` to `backend` as a multipart upload while reading it
+    ///
+    /// Up to `config.concurrent_parts` parts of `config.part_size` bytes
+    /// (more if the body's size hint shows that many would be too many) are
+    /// stored at once. Object metadata in `headers` goes with the creation
+    /// of the upload and the [`CONDITIONAL_HEADERS`] with its completion.
+    /// The upload is aborted if reading the body or storing a part fails.
+    ///
+    /// Returns the stored object and the size of the body.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn forward<B>(
+        &self,
+        body: B,
+        backend: &dyn StorageBackend,
+        key: &str,
+        content_type: Option<&str>,
+        headers: &[(String, String)],
+        config: &UploadConfig,
+        session: Option<&SessionHandle>,
+    ) -> Result<(StoredObject, u64), ForwardError>
+    where
+        B: Body<Data = Bytes> + Unpin,
+        B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let (conditions, mut metadata): (Vec<_>, Vec<_>) = headers
+            .iter()
+            .cloned()
+            .partition(|(name, _)| CONDITIONAL_HEADERS.contains(&name.as_str()));
+        if let Some(content_type) = content_type {
+            metadata.push(("content-type".to_string(), content_type.to_string()));
+        }
+        let upload_id = backend
+            .create_multipart_upload_with_headers(key, &metadata)
+            .await
+            .map_err(|e| {
+                ForwardError::Upload(e.in_object(ErrorContext::new(backend.bucket(), key)))
+            })?;
+
+        let result = match self
+            .forward_parts(body, backend, key, &upload_id, config, session)
+            .await
+        {
+            Ok((parts, size)) => backend
+                .complete_multipart_upload_with_headers(key, &upload_id, &parts, &conditions)
+                .await
+                .map(|object| (object, size))
+                .map_err(ForwardError::Upload),
+            Err(e) => Err(e),
+        };
+        // Also abort when completion is refused, e.g. by a failed precondition
+        if result.is_err() {
+            if let Err(abort_error) = backend.abort_multipart_upload(key, &upload_id).await {
+                warn!(
+                    key = key,
+                    upload_id = %upload_id,
+                    error = %abort_error,
+                    "Failed to abort forwarded multipart upload"
+                );
+            }
+        }
+        result.map_err(|e| match e {
+            ForwardError::Upload(e) => ForwardError::Upload(
+                e.in_object(ErrorContext::new(backend.bucket(), key).with_upload_id(upload_id)),
+            ),
+            e => e,
+        })
+    }
+
+    /// Read `body` into parts and store them, pausing between the water
+    /// marks
+    async fn forward_parts<B>(
+        &self,
+        mut body: B,
+        backend: &dyn StorageBackend,
+        key: &str,
+        upload_id: &str,
+        config: &UploadConfig,
+        session: Option<&SessionHandle>,
+    ) -> Result<(Vec<CompletedPart>, u64), ForwardError>
+    where
+        B: Body<Data = Bytes> + Unpin,
+        B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        // Sized bodies get parts large enough to fit within the part limit
+        let part_size = match body.size_hint().upper() {
+            Some(size) => part_size_for(size, config.part_size),
+            None => config.part_size.max(1),
+        };
+        let concurrency = config.concurrent_parts.max(1);
+
+        let mut pending = BytesMut::new();
+        let mut queued: VecDeque<(u32, Bytes)> = VecDeque::new();
+        let mut in_flight = FuturesUnordered::new();
+        let mut completed = Vec::new();
+        let mut next_part = 1;
+        let mut size = 0u64;
+        // Bytes received and not stored yet
+        let mut buffered = 0u64;
+        let mut eof = false;
+        let mut pause = Pause {
+            bucket: &self.bucket,
+            since: None,
+        };
+
+        loop {
+            while in_flight.len() < concurrency {
+                let Some((part_number, data)) = queued.pop_front() else {
+                    break;
+                };
+                let len = data.len() as u64;
+                in_flight.push(async move {
+                    backend
+                        .upload_part(key, upload_id, part_number, data)
+                        .await
+                        .map(|part| (part_number, len, part))
+                });
+            }
+            if eof && in_flight.is_empty() {
+                break;
+            }
+
+            if pause.since.is_none() && buffered >= self.high_water_mark && !in_flight.is_empty() {
+                debug!(key = key, buffered = buffered, "Pausing upload body");
+                pause.since = Some(Instant::now());
+            } else if pause.since.is_some()
+                && (buffered <= self.low_water_mark || in_flight.is_empty())
+            {
+                debug!(key = key, buffered = buffered, "Resuming upload body");
+                pause.resume();
+            }
+
+            tokio::select! {
+                frame = body.frame(), if !eof && pause.since.is_none() => match frame {
+                    Some(Ok(frame)) => {
+                        // Trailers carry no data
+                        let Ok(data) = frame.into_data() else {
+                            continue;
+                        };
+                        size += data.len() as u64;
+                        buffered += data.len() as u64;
+                        pending.extend_from_slice(&data);
+                        while pending.len() >= part_size {
+                            validate_part_number(next_part).map_err(ForwardError::Upload)?;
+                            queued.push_back((next_part, pending.split_to(part_size).freeze()));
+                            next_part += 1;
+                        }
+                    }
+                    Some(Err(e)) => return Err(ForwardError::Body(e.into())),
+                    None => {
+                        eof = true;
+                        // The last part may be short; an empty body is one empty part
+                        if !pending.is_empty() || next_part == 1 {
+                            validate_part_number(next_part).map_err(ForwardError::Upload)?;
+                            queued.push_back((next_part, pending.split().freeze()));
+                            next_part += 1;
+                        }
+                    }
+                },
+                Some(result) = in_flight.next(), if !in_flight.is_empty() => {
+                    let (part_number, len, part) = result.map_err(ForwardError::Upload)?;
+                    buffered -= len;
+                    completed.push(CompletedPart::stored(part_number, &part));
+                    if let Some(session) = session {
+                        session.part_completed();
+                    }
+                }
+            }
+        }
+
+        completed.sort_by_key(|part| part.part_number);
+        Ok((completed, size))
+    }
+}
+
+/// Pause in reading a body, recorded when it ends (or the upload does)
+struct Pause<'a> {
+    bucket: &'a str,
+    since: Option<Instant>,
+}
+
+impl Pause<'_> {
+    fn resume(&mut self) {
+        if let Some(since) = self.since.take() {
+            metrics::record_flow_pause(self.bucket, since.elapsed().as_secs_f64());
+        }
+    }
+}
+
+impl Drop for Pause<'_> {
+    fn drop(&mut self) {
+        self.resume();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use http_body_util::StreamBody;
+    use hyper::body::Frame;
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// In-memory backend taking `delay` to store each part
+    #[derive(Default)]
+    struct SlowBackend {
+        delay: Duration,
+        parts: Mutex<BTreeMap<u32, Bytes>>,
+        object: Mutex<Option<Bytes>>,
+        aborted: Mutex<bool>,
+        /// Bytes of the parts stored so far
+        stored: Arc<AtomicU64>,
+    }
+
+    #[async_trait]
+    impl StorageBackend for SlowBackend {
+        fn bucket(&self) -> &str {
+            "flow"
+        }
+
+        async fn put_object(
+            &self,
+            _key: &str,
+            _body: Bytes,
+            _content_type: Option<&str>,
+        ) -> Result<StoredObject, UploadError> {
+            unimplemented!()
+        }
+
+        async fn create_multipart_upload(&self, _key: &str) -> Result<String, UploadError> {
+            Ok("upload-1".to_string())
+        }
+
+        async fn upload_part(
+            &self,
+            _key: &str,
+            _upload_id: &str,
+            part_number: u32,
+            body: Bytes,
+        ) -> Result<StoredObject, UploadError> {
+            tokio::time::sleep(self.delay).await;
+            self.stored.fetch_add(body.len() as u64, Ordering::SeqCst);
+            self.parts.lock().unwrap().insert(part_number, body);
+            Ok(StoredObject::new(format!("\"part-{}\"", part_number)))
+        }
+
+        async fn complete_multipart_upload(
+            &self,
+            _key: &str,
+            _upload_id: &str,
+            parts: &[CompletedPart],
+        ) -> Result<StoredObject, UploadError> {
+            let stored = self.parts.lock().unwrap();
+            let mut object = BytesMut::new();
+            for (expected, part) in parts.iter().enumerate() {
+                assert_eq!(part.part_number as usize, expected + 1);
+                object.extend_from_slice(&stored[&part.part_number]);
+            }
+            *self.object.lock().unwrap() = Some(object.freeze());
+            Ok(StoredObject::new(format!("\"object-{}\"", parts.len())))
+        }
+
+        async fn abort_multipart_upload(
+            &self,
+            _key: &str,
+            _upload_id: &str,
+        ) -> Result<(), UploadError> {
+            *self.aborted.lock().unwrap() = true;
+            Ok(())
+        }
+    }
+
+    fn config() -> UploadConfig {
+        UploadConfig {
+            multipart_threshold: 0,
+            part_size: 1024,
+            concurrent_parts: 2,
+            ..Default::default()
+        }
+    }
+
+    fn flow(bucket: &str) -> FlowControl {
+        FlowControl::new(
+            bucket,
+            &FlowControlConfig {
+                high_water_mark: 4096,
+                low_water_mark: 2048,
+            },
+        )
+    }
+
+    type Chunks = Vec<Result<Frame<Bytes>, std::io::Error>>;
+
+    /// `count` frames of 256 bytes
+    fn chunks(count: usize) -> Chunks {
+        (0..count)
+            .map(|i| Ok(Frame::data(Bytes::from(vec![i as u8; 256]))))
+            .collect()
+    }
+
+    #[test]
+    fn test_applies() {
+        let flow = FlowControl::new("b", &FlowControlConfig::default());
+        let config = UploadConfig::default();
+        let threshold = config.multipart_threshold as u64;
+
+        assert!(flow.applies(Some(threshold + 1), &config));
+        assert!(!flow.applies(Some(threshold), &config));
+        assert!(!flow.applies(None, &config));
+
+        let config = UploadConfig {
+            multipart_threshold: usize::MAX,
+            ..Default::default()
+        };
+        assert!(flow.applies(Some(MAX_PUT_SIZE + 1), &config));
+        assert!(!flow.applies(Some(MAX_PUT_SIZE), &config));
+    }
+
+    #[tokio::test]
+    async fn test_forward_pauses_for_slow_backend() {
+        let backend = SlowBackend {
+            delay: Duration::from_millis(5),
+            ..Default::default()
+        };
+        let frames = chunks(64);
+        let expected: Vec<u8> = frames
+            .iter()
+            .flat_map(|frame| frame.as_ref().unwrap().data_ref().unwrap().to_vec())
+            .collect();
+
+        // Count how far reading gets ahead of the backend
+        let stored = Arc::clone(&backend.stored);
+        let read = Arc::new(AtomicU64::new(0));
+        let ahead = Arc::new(AtomicU64::new(0));
+        let (read_, ahead_) = (Arc::clone(&read), Arc::clone(&ahead));
+        let body = StreamBody::new(futures::stream::iter(frames).inspect(move |frame| {
+            let len = frame.as_ref().unwrap().data_ref().unwrap().len() as u64;
+            let read = read_.fetch_add(len, Ordering::SeqCst) + len;
+            ahead_.fetch_max(read - stored.load(Ordering::SeqCst), Ordering::SeqCst);
+        }));
+
+        let pauses = || metrics::FLOW_PAUSES.with_label_values(&["flow-slow"]).get();
+        let (object, size) = flow("flow-slow")
+            .forward(body, &backend, "k", None, &[], &config(), None)
+            .await
+            .unwrap();
+
+        assert_eq!(size, 64 * 256);
+        assert_eq!(object.etag, "\"object-16\"");
+        assert_eq!(
+            backend.object.lock().unwrap().as_deref(),
+            Some(expected.as_slice())
+        );
+        // Never more than the high-water mark plus one frame held
+        assert!(ahead.load(Ordering::SeqCst) <= 4096 + 256);
+        assert!(pauses() > 0.0);
+        assert!(
+            metrics::FLOW_PAUSED_SECONDS
+                .with_label_values(&["flow-slow"])
+                .get()
+                > 0.0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forward_short_last_part() {
+        let backend = SlowBackend::default();
+        let mut frames = chunks(5);
+        frames.push(Ok(Frame::data(Bytes::from_static(b"tail"))));
+        let body = StreamBody::new(futures::stream::iter(frames));
+
+        let (object, size) = flow("flow-short")
+            .forward(body, &backend, "k", None, &[], &config(), None)
+            .await
+            .unwrap();
+
+        assert_eq!(size, 5 * 256 + 4);
+        assert_eq!(object.etag, "\"object-2\"");
+        assert_eq!(backend.parts.lock().unwrap()[&2].len(), 256 + 4);
+    }
+
+    #[tokio::test]
+    async fn test_forward_aborts_on_body_error() {
+        let backend = SlowBackend::default();
+        let mut frames = chunks(8);
+        frames.push(Err(std::io::Error::other("client went away")));
+        let body = StreamBody::new(futures::stream::iter(frames));
+
+        let result = flow("flow-error")
+            .forward(body, &backend, "k", None, &[], &config(), None)
+            .await;
+
+        assert!(matches!(result, Err(ForwardError::Body(_))));
+        assert!(*backend.aborted.lock().unwrap());
+        assert!(backend.object.lock().unwrap().is_none());
+    }
+}
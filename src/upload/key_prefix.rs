@@ -0,0 +1,165 @@
+//! Key prefix jail
+//!
+//! Buckets with a `key_prefix` store every upload under that prefix: the
+//! proxy prepends it to the key the client sent, so clients cannot write
+//! anywhere else. Placeholders in braces are filled from the caller's
+//! claims (`{sub}` falls back to the authenticated subject), giving each
+//! user or tenant a folder of their own, e.g. `incoming/{sub}/`.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::upload::key_prefix::KeyPrefix;
+//! use std::collections::HashMap;
+//!
+//! let prefix = KeyPrefix::new("incoming/{sub}/").unwrap();
+//! let claims = HashMap::new();
+//!
+//! assert_eq!(
+//!     prefix.apply("report.csv", Some("alice"), Some(&claims)).unwrap(),
+//!     "incoming/alice/report.csv"
+//! );
+//! // The prefix is prepended, not checked: other folders are out of reach
+//! assert_eq!(
+//!     prefix.apply("incoming/bob/report.csv", Some("alice"), Some(&claims)).unwrap(),
+//!     "incoming/alice/incoming/bob/report.csv"
+//! );
+//! assert!(prefix.apply("../bob/report.csv", Some("alice"), Some(&claims)).is_err());
+//! ```
+
+use crate::authz::claims::{lookup, parse_template, path_segment, Segment};
+use crate::error::{Categorized, ErrorCategory};
+use serde_json::Value;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Key prefix errors
+#[derive(Error, Debug, PartialEq)]
+pub enum KeyPrefixError {
+    #[error("{0}")]
+    Template(String),
+
+    #[error("Claim '{0}' of the key prefix is missing or not a single path segment")]
+    MissingClaim(String),
+
+    #[error("Key leaves its prefix: {0}")]
+    Escape(String),
+}
+
+impl Categorized for KeyPrefixError {
+    fn category(&self) -> ErrorCategory {
+        match self {
+            KeyPrefixError::Template(_) => ErrorCategory::Server,
+            KeyPrefixError::MissingClaim(_) => ErrorCategory::Auth,
+            KeyPrefixError::Escape(_) => ErrorCategory::Client,
+        }
+    }
+}
+
+/// Key prefix of one bucket
+#[derive(Debug, Clone)]
+pub struct KeyPrefix {
+    template: Vec<Segment>,
+}
+
+impl KeyPrefix {
+    /// Parse a `key_prefix` template
+    ///
+    /// Fails on unbalanced braces or an empty placeholder.
+    pub fn new(template: &str) -> Result<Self, KeyPrefixError> {
+        let template = parse_template(template.trim_start_matches('/'))
+            .map_err(|e| KeyPrefixError::Template(e.to_string()))?;
+        Ok(Self { template })
+    }
+
+    /// Key an upload of `key` is stored under
+    ///
+    /// Fails when a placeholder claim is missing or is not a single path
+    /// segment, and when `key` has a `..` segment (which a filesystem
+    /// backend would resolve out of the prefix).
+    pub fn apply(
+        &self,
+        key: &str,
+        subject: Option<&str>,
+        claims: Option<&HashMap<String, Value>>,
+    ) -> Result<String, KeyPrefixError> {
+        let key = key.trim_start_matches('/');
+        if key.split('/').any(|segment| segment == "..") {
+            return Err(KeyPrefixError::Escape(key.to_string()));
+        }
+
+        let mut prefixed = String::new();
+        for segment in &self.template {
+            match segment {
+                Segment::Literal(text) => prefixed.push_str(text),
+                Segment::Claim(claim) => {
+                    let value = claims
+                        .and_then(|claims| lookup(claims, claim))
+                        .and_then(path_segment)
+                        .or_else(|| {
+                            subject
+                                .filter(|_| claim == "sub")
+                                .and_then(|subject| path_segment(&Value::from(subject)))
+                        })
+                        .ok_or_else(|| KeyPrefixError::MissingClaim(claim.clone()))?;
+                    prefixed.push_str(&value);
+                }
+            }
+        }
+        prefixed.push_str(key);
+        Ok(prefixed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn claims(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_static_prefix() {
+        let prefix = KeyPrefix::new("/incoming/").unwrap();
+        assert_eq!(
+            prefix.apply("/a/b.txt", None, None).unwrap(),
+            "incoming/a/b.txt"
+        );
+    }
+
+    #[test]
+    fn test_claim_prefix() {
+        let prefix = KeyPrefix::new("tenant/{org.id}/{sub}/").unwrap();
+        let claims = claims(json!({"org": {"id": "acme"}, "sub": "alice"}));
+        assert_eq!(
+            prefix
+                .apply("a.txt", Some("ignored"), Some(&claims))
+                .unwrap(),
+            "tenant/acme/alice/a.txt"
+        );
+    }
+
+    #[test]
+    fn test_missing_or_unsafe_claim() {
+        let prefix = KeyPrefix::new("tenant/{tenant}/").unwrap();
+        let unsafe_claims = claims(json!({"tenant": "../other"}));
+        assert_eq!(
+            prefix.apply("a.txt", Some("alice"), Some(&unsafe_claims)),
+            Err(KeyPrefixError::MissingClaim("tenant".into()))
+        );
+        assert_eq!(
+            prefix.apply("a.txt", Some("alice"), None),
+            Err(KeyPrefixError::MissingClaim("tenant".into()))
+        );
+    }
+
+    #[test]
+    fn test_invalid_template() {
+        assert!(matches!(
+            KeyPrefix::new("incoming/{sub"),
+            Err(KeyPrefixError::Template(_))
+        ));
+    }
+}
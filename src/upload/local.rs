@@ -0,0 +1,336 @@
+//! Local filesystem storage backend
+//!
+//! Stores objects as plain files under a root directory. Intended for
+//! development and tests, where running MinIO or mocking S3 is overkill.
+//!
+//! # Layout
+//!
+//! ```text
+//! <root>/<key>                                  - Completed objects
+//! <root>/.mizuchi-multipart/<upload_id>/<part>  - Parts of in-flight multipart uploads
+//! ```
+//!
+//! ETags follow S3 conventions: the hex MD5 of the object for single-part
+//! uploads, and `<md5 of part md5s>-<part count>` for multipart uploads.
+//! Content types and other object metadata are not persisted. Conditional
+//! writes (`if-match`, `if-none-match: *`) are checked against the file
+//! before it is replaced, but not atomically with the write.
+
+use super::backend::{StorageBackend, StoredObject};
+use super::multipart::CompletedPart;
+use super::UploadError;
+use async_trait::async_trait;
+use bytes::Bytes;
+use md5::{Digest, Md5};
+use std::path::{Component, Path, PathBuf};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+/// Directory under the root holding in-flight multipart uploads
+const MULTIPART_DIR: &str = ".mizuchi-multipart";
+
+/// Local filesystem backend
+#[derive(Debug, Clone)]
+pub struct LocalFsBackend {
+    bucket: String,
+    root: PathBuf,
+}
+
+impl LocalFsBackend {
+    /// Create a backend storing objects under `root`
+    ///
+    /// The root directory is created if it does not exist.
+    pub fn new(bucket: &str, root: impl AsRef<Path>) -> Result<Self, UploadError> {
+        let root = root.as_ref().to_path_buf();
+        std::fs::create_dir_all(&root)?;
+        Ok(Self {
+            bucket: bucket.to_string(),
+            root,
+        })
+    }
+
+    /// Root directory objects are stored under
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolve an object key to a path under the root
+    ///
+    /// Rejects keys that could escape the root directory or collide with
+    /// the multipart staging area.
+    pub fn object_path(&self, key: &str) -> Result<PathBuf, UploadError> {
+        let relative = Path::new(key);
+        let mut components = relative.components().peekable();
+
+        if components.peek().is_none() {
+            return Err(UploadError::InvalidKey(key.to_string()));
+        }
+        if components.any(|c| !matches!(c, Component::Normal(_))) {
+            return Err(UploadError::InvalidKey(key.to_string()));
+        }
+        if relative.starts_with(MULTIPART_DIR) {
+            return Err(UploadError::InvalidKey(key.to_string()));
+        }
+
+        Ok(self.root.join(relative))
+    }
+
+    /// Staging directory for a multipart upload
+    fn upload_dir(&self, upload_id: &str) -> Result<PathBuf, UploadError> {
+        // Upload IDs are generated by us; anything else is not ours to touch
+        uuid::Uuid::parse_str(upload_id).map_err(|_| {
+            UploadError::MultipartError(format!("Unknown upload ID: {}", upload_id))
+        })?;
+        Ok(self.root.join(MULTIPART_DIR).join(upload_id))
+    }
+
+    fn part_path(upload_dir: &Path, part_number: u32) -> PathBuf {
+        upload_dir.join(format!("{:05}", part_number))
+    }
+
+    /// Write data to `path` atomically via a temp file and rename
+    async fn write_atomic(path: &Path, data: &[u8]) -> Result<(), UploadError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let tmp = tmp_path(path);
+        let result = async {
+            let mut file = fs::File::create(&tmp).await?;
+            file.write_all(data).await?;
+            file.sync_all().await?;
+            fs::rename(&tmp, path).await
+        }
+        .await;
+
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp).await;
+        }
+        Ok(result?)
+    }
+
+    /// Check the conditions of a conditional write of `path`
+    ///
+    /// `if-match` ETags are compared with the MD5 of the current file, so
+    /// only single-part ETags match.
+    async fn check_conditions(
+        path: &Path,
+        headers: &[(String, String)],
+    ) -> Result<(), UploadError> {
+        for (name, value) in headers {
+            let value = value.trim();
+            let holds = match name.as_str() {
+                "if-none-match" => value != "*" || !fs::try_exists(path).await?,
+                "if-match" => match fs::read(path).await {
+                    Ok(data) => {
+                        let etag = hex::encode(Md5::digest(&data));
+                        value == "*"
+                            || value
+                                .split(',')
+                                .any(|candidate| candidate.trim().trim_matches('"') == etag)
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+                    Err(e) => return Err(e.into()),
+                },
+                _ => true,
+            };
+            if !holds {
+                return Err(UploadError::PreconditionFailed(format!(
+                    "{}: {}",
+                    name, value
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sibling temp path used while writing `path`
+fn tmp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", name, uuid::Uuid::new_v4()))
+}
+
+fn quoted_md5(data: &[u8]) -> String {
+    format!("\"{}\"", hex::encode(Md5::digest(data)))
+}
+
+#[async_trait]
+impl StorageBackend for LocalFsBackend {
+    fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    async fn put_object(
+        &self,
+        key: &str,
+        body: Bytes,
+        _content_type: Option<&str>,
+    ) -> Result<StoredObject, UploadError> {
+        let path = self.object_path(key)?;
+        Self::write_atomic(&path, &body).await?;
+        Ok(StoredObject::new(quoted_md5(&body)))
+    }
+
+    async fn put_object_with_headers(
+        &self,
+        key: &str,
+        body: Bytes,
+        content_type: Option<&str>,
+        headers: &[(String, String)],
+    ) -> Result<StoredObject, UploadError> {
+        Self::check_conditions(&self.object_path(key)?, headers).await?;
+        self.put_object(key, body, content_type).await
+    }
+
+    async fn create_multipart_upload(&self, key: &str) -> Result<String, UploadError> {
+        // Validate the key up front so bad keys fail before any parts are sent
+        self.object_path(key)?;
+
+        let upload_id = uuid::Uuid::new_v4().to_string();
+        fs::create_dir_all(self.upload_dir(&upload_id)?).await?;
+        Ok(upload_id)
+    }
+
+    async fn upload_part(
+        &self,
+        _key: &str,
+        upload_id: &str,
+        part_number: u32,
+        body: Bytes,
+    ) -> Result<StoredObject, UploadError> {
+        let dir = self.upload_dir(upload_id)?;
+        if !fs::try_exists(&dir).await? {
+            return Err(UploadError::MultipartError(format!(
+                "Unknown upload ID: {}",
+                upload_id
+            )));
+        }
+
+        Self::write_atomic(&Self::part_path(&dir, part_number), &body).await?;
+        Ok(StoredObject::new(quoted_md5(&body)))
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[CompletedPart],
+    ) -> Result<StoredObject, UploadError> {
+        let path = self.object_path(key)?;
+        let dir = self.upload_dir(upload_id)?;
+
+        let mut object = Vec::new();
+        let mut part_digests = Vec::with_capacity(parts.len() * 16);
+        for part in parts {
+            let data = fs::read(Self::part_path(&dir, part.part_number))
+                .await
+                .map_err(|_| {
+                    UploadError::MultipartError(format!("Missing part {}", part.part_number))
+                })?;
+
+            let digest = Md5::digest(&data);
+            if part.etag.trim_matches('"') != hex::encode(digest) {
+                return Err(UploadError::MultipartError(format!(
+                    "ETag mismatch for part {}",
+                    part.part_number
+                )));
+            }
+            part_digests.extend_from_slice(&digest);
+            object.extend_from_slice(&data);
+        }
+
+        Self::write_atomic(&path, &object).await?;
+        fs::remove_dir_all(&dir).await?;
+
+        Ok(StoredObject::new(format!(
+            "\"{}-{}\"",
+            hex::encode(Md5::digest(&part_digests)),
+            parts.len()
+        )))
+    }
+
+    async fn complete_multipart_upload_with_headers(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[CompletedPart],
+        headers: &[(String, String)],
+    ) -> Result<StoredObject, UploadError> {
+        Self::check_conditions(&self.object_path(key)?, headers).await?;
+        self.complete_multipart_upload(key, upload_id, parts).await
+    }
+
+    async fn abort_multipart_upload(&self, _key: &str, upload_id: &str) -> Result<(), UploadError> {
+        let dir = self.upload_dir(upload_id)?;
+        match fs::remove_dir_all(&dir).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_path_rejects_traversal() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = LocalFsBackend::new("bucket", dir.path()).unwrap();
+
+        assert!(backend.object_path("a/b.txt").is_ok());
+        assert!(backend.object_path("../escape").is_err());
+        assert!(backend.object_path("a/../../escape").is_err());
+        assert!(backend.object_path("/etc/passwd").is_err());
+        assert!(backend.object_path("").is_err());
+        assert!(backend.object_path(".mizuchi-multipart/x").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_put_object_writes_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = LocalFsBackend::new("bucket", dir.path()).unwrap();
+
+        let object = backend
+            .put_object("nested/hello.txt", Bytes::from("hello"), None)
+            .await
+            .unwrap();
+
+        assert_eq!(object.etag, "\"5d41402abc4b2a76b9719d911017c592\"");
+        let stored = std::fs::read(dir.path().join("nested/hello.txt")).unwrap();
+        assert_eq!(stored, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_conditional_put() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = LocalFsBackend::new("bucket", dir.path()).unwrap();
+        let create_only = [("if-none-match".to_string(), "*".to_string())];
+
+        let object = backend
+            .put_object_with_headers("a.txt", Bytes::from("hello"), None, &create_only)
+            .await
+            .unwrap();
+        let result = backend
+            .put_object_with_headers("a.txt", Bytes::from("again"), None, &create_only)
+            .await;
+        assert!(matches!(result, Err(UploadError::PreconditionFailed(_))));
+
+        let stale = [("if-match".to_string(), "\"0123\"".to_string())];
+        let result = backend
+            .put_object_with_headers("a.txt", Bytes::from("again"), None, &stale)
+            .await;
+        assert!(matches!(result, Err(UploadError::PreconditionFailed(_))));
+
+        let current = [("if-match".to_string(), object.etag)];
+        backend
+            .put_object_with_headers("a.txt", Bytes::from("again"), None, &current)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(dir.path().join("a.txt")).unwrap(), b"again");
+    }
+}
@@ -2,12 +2,35 @@
 //!
 //! Handles S3 upload operations with zero-copy optimization on Linux.
 
-use crate::s3::S3ClientError;
+use crate::error::{Categorized, ErrorCategory, ErrorContext};
+use crate::s3::{S3ClientError, S3ErrorCode, S3ServiceError};
 use thiserror::Error;
 
+pub mod aws_chunked;
+pub mod backend;
+pub mod buffer_pool;
+pub mod completion;
+pub mod compression;
+pub mod content_length;
+pub mod content_type;
+pub mod flow;
+pub mod key_prefix;
+pub mod local;
 pub mod multipart;
+pub mod progress;
 pub mod put_object;
+pub mod quota;
+pub mod receipts;
+pub mod reconcile;
+pub mod region;
+pub mod registry;
+pub mod replication;
+pub mod resume;
+pub mod spill;
+pub mod spool_crypto;
+pub mod storage_class;
 pub mod temp_file;
+pub mod transform;
 pub mod zero_copy;
 
 /// Upload errors
@@ -16,6 +39,14 @@ pub enum UploadError {
     #[error("S3 error: {0}")]
     S3Error(String),
 
+    /// Error response of the S3 backend, with its error code
+    #[error("S3 error: {0}")]
+    Backend(S3ServiceError),
+
+    /// Any other failure of the S3 client
+    #[error("S3 error: {0}")]
+    S3Client(#[source] S3ClientError),
+
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 
@@ -31,13 +62,92 @@ pub enum UploadError {
     #[error("Multipart upload error: {0}")]
     MultipartError(String),
 
+    /// Parts S3 would refuse, with the error code it would refuse them with
+    #[error("{code}: {message}")]
+    InvalidParts { code: S3ErrorCode, message: String },
+
+    #[error("Invalid object key: {0}")]
+    InvalidKey(String),
+
     #[error("Bucket mismatch: expected {expected}, got {actual}")]
     BucketMismatch { expected: String, actual: String },
+
+    #[error("Precondition failed: {0}")]
+    PreconditionFailed(String),
+
+    #[error("Deadline of the client request exceeded")]
+    DeadlineExceeded,
+
+    /// An error on the object named by `context`
+    #[error("{source} ({context})")]
+    Object {
+        context: ErrorContext,
+        #[source]
+        source: Box<UploadError>,
+    },
+}
+
+impl UploadError {
+    /// Attach the object the error happened on
+    ///
+    /// An error that already names its object keeps the first context.
+    #[must_use]
+    pub fn in_object(self, context: ErrorContext) -> Self {
+        match self {
+            UploadError::Object { .. } => self,
+            source => UploadError::Object {
+                context,
+                source: Box::new(source),
+            },
+        }
+    }
+
+    /// The error without the object context
+    pub fn root(&self) -> &UploadError {
+        match self {
+            UploadError::Object { source, .. } => source.root(),
+            error => error,
+        }
+    }
+
+    /// Object the error happened on, if known
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            UploadError::Object { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+}
+
+impl Categorized for UploadError {
+    fn category(&self) -> ErrorCategory {
+        match self {
+            UploadError::S3Client(error) => error.category(),
+            UploadError::Object { source, .. } => source.category(),
+            UploadError::S3Error(_) | UploadError::Backend(_) | UploadError::MultipartError(_) => {
+                ErrorCategory::Backend
+            }
+            UploadError::IoError(_)
+            | UploadError::ZeroCopyUnavailable
+            | UploadError::BucketMismatch { .. } => ErrorCategory::Server,
+            UploadError::InvalidContentLength
+            | UploadError::PartTooSmall
+            | UploadError::InvalidParts { .. }
+            | UploadError::InvalidKey(_)
+            | UploadError::PreconditionFailed(_)
+            | UploadError::DeadlineExceeded => ErrorCategory::Client,
+        }
+    }
 }
 
 impl From<S3ClientError> for UploadError {
     fn from(err: S3ClientError) -> Self {
-        UploadError::S3Error(err.to_string())
+        match err {
+            S3ClientError::PreconditionFailed(message) => UploadError::PreconditionFailed(message),
+            S3ClientError::DeadlineExceeded => UploadError::DeadlineExceeded,
+            S3ClientError::ServiceError(error) => UploadError::Backend(error),
+            err => UploadError::S3Client(err),
+        }
     }
 }
 
@@ -46,9 +156,31 @@ impl From<S3ClientError> for UploadError {
 pub struct UploadResult {
     pub etag: String,
     pub version_id: Option<String>,
+    /// Request ID of the backend write, if the backend reports one
+    pub request_id: Option<String>,
+    /// `x-amz-checksum-*` values reported by the backend
+    pub checksums: Vec<(String, String)>,
     pub bytes_written: u64,
 }
 
+impl UploadResult {
+    /// Result of a write the backend reported as `object`
+    pub fn stored(object: backend::StoredObject, bytes_written: u64) -> Self {
+        Self {
+            etag: object.etag,
+            version_id: object.metadata.version_id,
+            request_id: object.metadata.request_id,
+            checksums: object.metadata.checksums,
+            bytes_written,
+        }
+    }
+
+    /// Result of a write with only a (placeholder) ETag
+    fn placeholder(etag: String, bytes_written: u64) -> Self {
+        Self::stored(backend::StoredObject::new(etag), bytes_written)
+    }
+}
+
 /// Upload handler trait
 #[async_trait::async_trait]
 pub trait UploadHandler: Send + Sync {
@@ -71,8 +203,35 @@ mod tests {
         let result = UploadResult {
             etag: "abc123".into(),
             version_id: None,
+            request_id: None,
+            checksums: Vec::new(),
             bytes_written: 1024,
         };
         assert_eq!(result.bytes_written, 1024);
     }
+
+    #[test]
+    fn test_error_in_object() {
+        let context = ErrorContext::new("uploads", "big.bin").with_upload_id("u-1");
+        let error = UploadError::PreconditionFailed("etag".into())
+            .in_object(context.clone())
+            .in_object(ErrorContext::new("other", "key"));
+
+        assert!(matches!(error.root(), UploadError::PreconditionFailed(_)));
+        assert_eq!(error.context(), Some(&context));
+        assert_eq!(error.category(), ErrorCategory::Client);
+        assert_eq!(
+            error.to_string(),
+            "Precondition failed: etag (bucket uploads, key big.bin, upload u-1)"
+        );
+        assert!(std::error::Error::source(&error).is_some());
+    }
+
+    #[test]
+    fn test_s3_client_error_keeps_source() {
+        let error = UploadError::from(S3ClientError::ConnectError("refused".into()));
+        assert_eq!(error.category(), ErrorCategory::Backend);
+        let source = std::error::Error::source(&error).unwrap();
+        assert_eq!(source.to_string(), "Connect error: refused");
+    }
 }
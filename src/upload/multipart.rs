@@ -19,6 +19,7 @@
 //!     secret_key: Some("secret-key".to_string()),
 //!     retry: None,
 //!     timeout: None,
+//!     pool: None,
 //! };
 //! let s3_client = S3Client::new(config)?;
 //!
@@ -39,37 +40,257 @@
 //! # }
 //! ```
 
+use super::backend::{StorageBackend, StoredObject};
+use super::completion::parse_completion;
+use super::registry::UploadRegistry;
+use super::resume::{ResumeState, ResumeStore};
 use super::{UploadError, UploadResult};
-use crate::metrics::{record_multipart_upload_failure, record_multipart_upload_success};
-use crate::s3::{S3Client, S3CompletedPart};
+use crate::config::S3HashingConfig;
+use crate::error::ErrorContext;
+use crate::metrics::{
+    record_multipart_upload_failure, record_multipart_upload_success, record_upload_bytes,
+};
+use crate::notifications::{UploadEvent, WebhookNotifier};
+use crate::s3::checksum;
+use crate::s3::hashing;
+use crate::s3::{S3Client, S3ErrorCode, S3MultipartUpload};
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Minimum part size (5MB) - S3 requirement
 pub const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
 
-/// Maximum parts allowed
+/// Maximum parts allowed, which is also the highest part number
 pub const MAX_PARTS: usize = 10000;
 
+/// Largest object S3 stores with a single PUT (5GB); larger objects have to
+/// be multipart uploads
+pub const MAX_PUT_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+
+/// Largest object S3 stores (5TB)
+pub const MAX_OBJECT_SIZE: u64 = 5 * 1024 * 1024 * 1024 * 1024;
+
+/// Part size for storing `size` bytes in at most [`MAX_PARTS`] parts
+///
+/// This is `part_size` unless that would take too many parts, in which case
+/// parts are made just large enough.
+pub fn part_size_for(size: u64, part_size: usize) -> usize {
+    let needed = usize::try_from(size.div_ceil(MAX_PARTS as u64)).unwrap_or(usize::MAX);
+    part_size.max(needed).max(1)
+}
+
 /// Multipart upload state
 #[derive(Debug)]
 pub struct MultipartUpload {
     pub upload_id: String,
     pub bucket: String,
     pub key: String,
+    /// Parts uploaded so far, a re-uploaded part replacing the earlier one
     pub parts: Vec<CompletedPart>,
+    /// Sizes of the uploaded parts, by part number
+    pub part_sizes: HashMap<u32, usize>,
+    /// Hex SHA-256 of the uploaded parts, by part number, kept when the
+    /// handler has a [`ResumeStore`]
+    pub part_hashes: HashMap<u32, String>,
+}
+
+impl MultipartUpload {
+    /// Context naming this upload in errors
+    pub fn error_context(&self) -> ErrorContext {
+        ErrorContext::new(&self.bucket, &self.key).with_upload_id(&self.upload_id)
+    }
+
+    /// Uploaded parts in part number order, as a completion lists them
+    pub fn sorted_parts(&self) -> Vec<CompletedPart> {
+        let mut parts = self.parts.clone();
+        parts.sort_by_key(|part| part.part_number);
+        parts
+    }
+
+    /// Check the parts of a completion against the parts uploaded
+    ///
+    /// Fails like S3 would: with `InvalidPartOrder` unless part numbers
+    /// ascend, `InvalidPart` for parts not uploaded or with another ETag,
+    /// and `EntityTooSmall` for a part other than the last below
+    /// [`MIN_PART_SIZE`].
+    pub fn validate_completion(&self, parts: &[CompletedPart]) -> Result<(), UploadError> {
+        if parts.len() > MAX_PARTS {
+            return Err(invalid_parts(
+                S3ErrorCode::InvalidArgument,
+                format!(
+                    "At most {} parts are allowed, got {}",
+                    MAX_PARTS,
+                    parts.len()
+                ),
+            ));
+        }
+        if parts
+            .windows(2)
+            .any(|pair| pair[0].part_number >= pair[1].part_number)
+        {
+            return Err(invalid_parts(
+                S3ErrorCode::InvalidPartOrder,
+                "Part numbers must be in ascending order".into(),
+            ));
+        }
+        for part in parts {
+            let uploaded = self
+                .parts
+                .iter()
+                .find(|uploaded| uploaded.part_number == part.part_number);
+            if uploaded.map(|uploaded| uploaded.etag.trim_matches('"'))
+                != Some(part.etag.trim_matches('"'))
+            {
+                return Err(invalid_parts(
+                    S3ErrorCode::InvalidPart,
+                    format!(
+                        "Part {} was not uploaded or its ETag differs",
+                        part.part_number
+                    ),
+                ));
+            }
+        }
+        // The last part may be of any size
+        let leading = parts.split_last().map_or(&[][..], |(_, leading)| leading);
+        if let Some(part) = leading.iter().find(|part| {
+            self.part_sizes
+                .get(&part.part_number)
+                .is_some_and(|&size| size < MIN_PART_SIZE)
+        }) {
+            return Err(invalid_parts(
+                S3ErrorCode::EntityTooSmall,
+                format!(
+                    "Part {} is smaller than the 5 MiB minimum",
+                    part.part_number
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// `parts` with the checksums the uploaded parts were stored with
+    ///
+    /// Clients list parts by number and ETag only.
+    fn with_checksums(&self, parts: &[CompletedPart]) -> Vec<CompletedPart> {
+        parts
+            .iter()
+            .map(|part| CompletedPart {
+                checksum_crc32c: self
+                    .parts
+                    .iter()
+                    .find(|uploaded| uploaded.part_number == part.part_number)
+                    .and_then(|uploaded| uploaded.checksum_crc32c.clone()),
+                ..part.clone()
+            })
+            .collect()
+    }
+
+    /// Remember an uploaded part of `size` bytes
+    fn record_part(&mut self, part: CompletedPart, size: usize) {
+        self.parts.retain(|p| p.part_number != part.part_number);
+        self.part_sizes.insert(part.part_number, size);
+        self.part_hashes.remove(&part.part_number);
+        self.parts.push(part);
+    }
+
+    /// The uploaded part `part_number`, if its body hashed to `hash`
+    fn stored_part(&self, part_number: u32, hash: &str) -> Option<&CompletedPart> {
+        self.part_hashes
+            .get(&part_number)
+            .filter(|stored| stored.as_str() == hash)?;
+        self.parts
+            .iter()
+            .find(|part| part.part_number == part_number)
+    }
+}
+
+/// Check `part_number` is within 1 to [`MAX_PARTS`]
+pub fn validate_part_number(part_number: u32) -> Result<(), UploadError> {
+    if part_number == 0 || part_number as usize > MAX_PARTS {
+        return Err(invalid_parts(
+            S3ErrorCode::InvalidArgument,
+            format!(
+                "Part number must be between 1 and {}, got {}",
+                MAX_PARTS, part_number
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn invalid_parts(code: S3ErrorCode, message: String) -> UploadError {
+    UploadError::InvalidParts { code, message }
 }
 
 /// Completed part info
+///
+/// Built with [`new`](Self::new) or [`stored`](Self::stored), so fields can
+/// be added without breaking callers.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct CompletedPart {
     pub part_number: u32,
     pub etag: String,
+    /// Base64 CRC32C the part was stored with, if the backend checksums
+    /// parts
+    pub checksum_crc32c: Option<String>,
+}
+
+impl CompletedPart {
+    /// Part `part_number`, stored without a checksum
+    pub fn new(part_number: u32, etag: impl Into<String>) -> Self {
+        Self {
+            part_number,
+            etag: etag.into(),
+            checksum_crc32c: None,
+        }
+    }
+
+    /// The part, stored with the base64 CRC32C `checksum`
+    pub fn with_checksum_crc32c(mut self, checksum: impl Into<String>) -> Self {
+        self.checksum_crc32c = Some(checksum.into());
+        self
+    }
+
+    /// Part `part_number`, as stored by a backend
+    pub fn stored(part_number: u32, object: &StoredObject) -> Self {
+        Self {
+            part_number,
+            etag: object.etag.clone(),
+            checksum_crc32c: object
+                .metadata
+                .checksum(checksum::CRC32C_HEADER)
+                .map(str::to_string),
+        }
+    }
+}
+
+/// Multipart upload started on a backend but not completed or aborted yet
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingUpload {
+    pub key: String,
+    pub upload_id: String,
+    /// When the upload was started, if the backend reports it
+    pub initiated: Option<DateTime<Utc>>,
+}
+
+impl From<S3MultipartUpload> for PendingUpload {
+    fn from(upload: S3MultipartUpload) -> Self {
+        Self {
+            key: upload.key,
+            upload_id: upload.upload_id,
+            initiated: upload.initiated,
+        }
+    }
 }
 
 /// Multipart upload handler
 pub struct MultipartHandler {
-    /// S3 client for making upload requests
-    client: Option<S3Client>,
+    /// Storage backend for making upload requests
+    backend: Option<Arc<dyn StorageBackend>>,
     /// Bucket name (used when client is not provided)
     #[allow(dead_code)]
     bucket: String,
@@ -82,20 +303,32 @@ pub struct MultipartHandler {
     /// Number of concurrent part uploads
     #[allow(dead_code)]
     concurrent_parts: usize,
+    /// Optional notifier for upload completion events
+    notifier: Option<Arc<WebhookNotifier>>,
+    /// Optional registry of uploads in progress, used by reconciliation
+    registry: Option<Arc<UploadRegistry>>,
+    /// Optional store of upload state, for resuming after a restart
+    resume: Option<Arc<dyn ResumeStore>>,
+    /// How parts are hashed to recognize those already stored
+    hashing: S3HashingConfig,
 }
 
 impl MultipartHandler {
     /// Create a new multipart handler (legacy constructor)
     ///
-    /// This constructor creates a handler without an S3 client.
-    /// Use `with_client` for production use.
+    /// This constructor creates a handler without a storage backend.
+    /// Use `with_client` or `with_backend` for production use.
     pub fn new(bucket: &str, region: &str, part_size: usize, concurrent_parts: usize) -> Self {
         Self {
-            client: None,
+            backend: None,
             bucket: bucket.to_string(),
             region: region.to_string(),
             part_size: std::cmp::max(part_size, MIN_PART_SIZE),
             concurrent_parts,
+            notifier: None,
+            registry: None,
+            resume: None,
+            hashing: S3HashingConfig::default(),
         }
     }
 
@@ -107,15 +340,100 @@ impl MultipartHandler {
     ///
     /// * `client` - Configured S3 client for making upload requests
     pub fn with_client(client: S3Client) -> Self {
+        let region = client.region().to_string();
+        let hashing = client.hashing().clone();
         Self {
-            bucket: client.bucket().to_string(),
-            region: client.region().to_string(),
-            client: Some(client),
+            region,
+            hashing,
+            ..Self::with_backend(Arc::new(client))
+        }
+    }
+
+    /// Create a new multipart handler with any storage backend
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - Storage backend objects are written to
+    pub fn with_backend(backend: Arc<dyn StorageBackend>) -> Self {
+        Self {
+            bucket: backend.bucket().to_string(),
+            region: String::new(),
+            backend: Some(backend),
             part_size: MIN_PART_SIZE,
             concurrent_parts: 4,
+            notifier: None,
+            registry: None,
+            resume: None,
+            hashing: S3HashingConfig::default(),
         }
     }
 
+    /// Attach a notifier that receives an event after each completed upload
+    pub fn with_notifier(mut self, notifier: Arc<WebhookNotifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// Track uploads in `registry` while they are in progress
+    pub fn with_registry(mut self, registry: Arc<UploadRegistry>) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Save the state of uploads in `store`, so they can be
+    /// [resumed](Self::resume) after a restart
+    pub fn with_resume_store(mut self, store: Arc<dyn ResumeStore>) -> Self {
+        self.resume = Some(store);
+        self
+    }
+
+    /// Hash parts as `config` says, instead of the defaults
+    ///
+    /// [`with_client`](Self::with_client) takes the client's settings.
+    pub fn with_hashing(mut self, config: S3HashingConfig) -> Self {
+        self.hashing = config;
+        self
+    }
+
+    /// Pick up an upload saved in the resume store, e.g. after a restart
+    ///
+    /// Fails with `NoSuchUpload` if the upload is unknown to the store (or
+    /// no store is attached). The upload is tracked in the registry again.
+    pub async fn resume(
+        &self,
+        bucket: &str,
+        upload_id: &str,
+    ) -> Result<MultipartUpload, UploadError> {
+        if let Some(backend) = &self.backend {
+            if bucket != backend.bucket() {
+                return Err(UploadError::BucketMismatch {
+                    expected: backend.bucket().to_string(),
+                    actual: bucket.to_string(),
+                });
+            }
+        }
+        let state = match &self.resume {
+            Some(store) => store.load(bucket, upload_id).await?,
+            None => None,
+        };
+        let Some(state) = state else {
+            return Err(invalid_parts(
+                S3ErrorCode::NoSuchUpload,
+                format!("Upload {} is not known to this proxy", upload_id),
+            ));
+        };
+        let upload = state.into_upload();
+        if let Some(registry) = &self.registry {
+            registry.register(&upload.bucket, &upload.key, &upload.upload_id);
+        }
+        tracing::info!(
+            upload_id = %upload.upload_id,
+            parts = upload.parts.len(),
+            "Resumed multipart upload"
+        );
+        Ok(upload)
+    }
+
     /// Check if zero-copy transfer is supported on this platform
     ///
     /// Returns `true` on Linux where splice(2)/sendfile(2) are available,
@@ -136,33 +454,47 @@ impl MultipartHandler {
         err
     )]
     pub async fn create(&self, bucket: &str, key: &str) -> Result<MultipartUpload, UploadError> {
-        // Use S3Client if available
-        if let Some(client) = &self.client {
-            // Validate bucket matches client configuration
-            if bucket != client.bucket() {
+        // Use storage backend if available
+        if let Some(backend) = &self.backend {
+            // Validate bucket matches backend configuration
+            if bucket != backend.bucket() {
                 return Err(UploadError::BucketMismatch {
-                    expected: client.bucket().to_string(),
+                    expected: backend.bucket().to_string(),
                     actual: bucket.to_string(),
                 });
             }
 
-            // Call S3 CreateMultipartUpload API
-            let response = client.create_multipart_upload(key).await?;
+            let upload_id = backend
+                .create_multipart_upload(key)
+                .await
+                .map_err(|e| e.in_object(ErrorContext::new(bucket, key)))?;
+            if let Some(registry) = &self.registry {
+                registry.register(bucket, key, &upload_id);
+            }
 
             // Record upload_id in span
-            tracing::Span::current().record("upload_id", response.upload_id.as_str());
+            tracing::Span::current().record("upload_id", upload_id.as_str());
 
             tracing::info!(
-                upload_id = %response.upload_id,
+                upload_id = %upload_id,
                 "Created multipart upload"
             );
 
-            return Ok(MultipartUpload {
-                upload_id: response.upload_id,
+            let upload = MultipartUpload {
+                upload_id,
                 bucket: bucket.to_string(),
                 key: key.to_string(),
                 parts: Vec::new(),
-            });
+                part_sizes: HashMap::new(),
+                part_hashes: HashMap::new(),
+            };
+            if let Err(e) = self.save(&upload).await {
+                // An upload that can't be resumed is not handed out
+                let _ = backend.abort_multipart_upload(key, &upload.upload_id).await;
+                self.forget(&upload);
+                return Err(e.in_object(upload.error_context()));
+            }
+            return Ok(upload);
         }
 
         // Legacy mode: generate UUID (for backward compatibility)
@@ -181,6 +513,8 @@ impl MultipartHandler {
             bucket: bucket.to_string(),
             key: key.to_string(),
             parts: Vec::new(),
+            part_sizes: HashMap::new(),
+            part_hashes: HashMap::new(),
         })
     }
 
@@ -202,34 +536,50 @@ impl MultipartHandler {
         part_number: u32,
         body: Bytes,
     ) -> Result<CompletedPart, UploadError> {
-        if body.len() < MIN_PART_SIZE && part_number < MAX_PARTS as u32 {
-            // Allow smaller final part
-            tracing::warn!(
-                part_number = part_number,
-                size = body.len(),
-                "Part may be too small (< 5MB)"
-            );
-        }
+        validate_part_number(part_number).map_err(|e| e.in_object(upload.error_context()))?;
+
+        // Use storage backend if available
+        if let Some(backend) = &self.backend {
+            // A part sent again unchanged, e.g. by a client retrying after a
+            // restart, is already stored
+            let hash = match self.resume {
+                Some(_) => Some(
+                    hashing::sha256_hex_offloaded(&body, self.hashing.offload_threshold_bytes)
+                        .await,
+                ),
+                None => None,
+            };
+            if let Some(part) = hash
+                .as_deref()
+                .and_then(|hash| upload.stored_part(part_number, hash))
+            {
+                return Ok(part.clone());
+            }
 
-        // Use S3Client if available
-        if let Some(client) = &self.client {
-            let response = client
-                .upload_part(&upload.key, &upload.upload_id, part_number, body.clone())
-                .await?;
+            let size = body.len();
+            let stored = backend
+                .upload_part(&upload.key, &upload.upload_id, part_number, body)
+                .await
+                .map_err(|e| e.in_object(upload.error_context()))?;
 
-            let part = CompletedPart {
-                part_number,
-                etag: response.etag.clone(),
-            };
+            let part = CompletedPart::stored(part_number, &stored);
 
-            upload.parts.push(part.clone());
+            upload.record_part(part.clone(), size);
+            if let Some(hash) = hash {
+                upload.part_hashes.insert(part_number, hash);
+            }
+            // The part is only acknowledged once it would survive a restart
+            self.save(upload)
+                .await
+                .map_err(|e| e.in_object(upload.error_context()))?;
+            record_upload_bytes(&upload.bucket, size as u64);
 
             // Record etag in span
             tracing::Span::current().record("s3.etag", part.etag.as_str());
 
             tracing::info!(
                 etag = %part.etag,
-                size = body.len(),
+                size = size,
                 "Uploaded part"
             );
 
@@ -242,9 +592,10 @@ impl MultipartHandler {
         let part = CompletedPart {
             part_number,
             etag: etag.clone(),
+            checksum_crc32c: None,
         };
 
-        upload.parts.push(part.clone());
+        upload.record_part(part.clone(), body.len());
 
         // Record etag in span
         tracing::Span::current().record("s3.etag", part.etag.as_str());
@@ -258,81 +609,150 @@ impl MultipartHandler {
         Ok(part)
     }
 
-    /// Complete a multipart upload
+    /// Complete a multipart upload with all of its uploaded parts
+    pub async fn complete(&self, upload: &MultipartUpload) -> Result<UploadResult, UploadError> {
+        self.complete_parts(upload, &upload.sorted_parts()).await
+    }
+
+    /// Complete a multipart upload with the parts listed by a client's
+    /// CompleteMultipartUpload request `body`
+    ///
+    /// See [`parse_completion`] for the errors of malformed bodies.
+    pub async fn complete_request(
+        &self,
+        upload: &MultipartUpload,
+        body: &[u8],
+    ) -> Result<UploadResult, UploadError> {
+        let parts = parse_completion(body).map_err(|e| e.in_object(upload.error_context()))?;
+        self.complete_parts(upload, &parts).await
+    }
+
+    /// Complete a multipart upload with `parts`, as a client's completion
+    /// request lists them
+    ///
+    /// The parts are checked with [`MultipartUpload::validate_completion`]
+    /// before the backend is asked to complete the upload.
     #[tracing::instrument(
         name = "upload.multipart.complete",
-        skip(self, upload),
+        skip(self, upload, parts),
         fields(
             upload_id = %upload.upload_id,
-            parts_count = upload.parts.len(),
+            parts_count = parts.len(),
             s3.etag = tracing::field::Empty
         ),
         err
     )]
-    pub async fn complete(&self, upload: &MultipartUpload) -> Result<UploadResult, UploadError> {
-        if upload.parts.is_empty() {
+    pub async fn complete_parts(
+        &self,
+        upload: &MultipartUpload,
+        parts: &[CompletedPart],
+    ) -> Result<UploadResult, UploadError> {
+        if parts.is_empty() {
             return Err(UploadError::MultipartError("No parts uploaded".into()));
         }
-
-        // Use S3Client if available
-        if let Some(client) = &self.client {
-            // Convert our CompletedPart to S3CompletedPart
-            let s3_parts: Vec<S3CompletedPart> = upload
-                .parts
-                .iter()
-                .map(|p| S3CompletedPart {
-                    part_number: p.part_number,
-                    etag: p.etag.clone(),
-                })
-                .collect();
-
-            let response = client
-                .complete_multipart_upload(&upload.key, &upload.upload_id, s3_parts)
-                .await?;
-
-            let result = UploadResult {
-                etag: response.etag,
-                version_id: None,
-                bytes_written: 0, // S3 doesn't return this in CompleteMultipartUpload
-            };
+        upload
+            .validate_completion(parts)
+            .map_err(|e| e.in_object(upload.error_context()))?;
+
+        // Use storage backend if available
+        if let Some(backend) = &self.backend {
+            let object = backend
+                .complete_multipart_upload(
+                    &upload.key,
+                    &upload.upload_id,
+                    &upload.with_checksums(parts),
+                )
+                .await
+                .map_err(|e| e.in_object(upload.error_context()))?;
+            self.forget(upload);
+            self.discard(upload).await;
+
+            // S3 doesn't return the object size in CompleteMultipartUpload
+            let result = UploadResult::stored(object, 0);
 
             // Record etag in span
             tracing::Span::current().record("s3.etag", result.etag.as_str());
 
             tracing::info!(
                 etag = %result.etag,
-                parts = upload.parts.len(),
+                parts = parts.len(),
                 "Completed multipart upload"
             );
 
             // Record success metrics
-            record_multipart_upload_success(&upload.bucket, upload.parts.len());
+            record_multipart_upload_success(&upload.bucket, parts.len());
+
+            self.notify_completed(upload, &result);
 
             return Ok(result);
         }
 
         // Legacy mode: generate fake final ETag
-        let result = UploadResult {
-            etag: format!("\"{}-{}\"", uuid::Uuid::new_v4(), upload.parts.len()),
-            version_id: None,
-            bytes_written: 0,
-        };
+        let result =
+            UploadResult::placeholder(format!("\"{}-{}\"", uuid::Uuid::new_v4(), parts.len()), 0);
 
         // Record etag in span
         tracing::Span::current().record("s3.etag", result.etag.as_str());
 
         tracing::info!(
             etag = %result.etag,
-            parts = upload.parts.len(),
+            parts = parts.len(),
             "Completed multipart upload (legacy mode)"
         );
 
         // Record success metrics (legacy mode)
-        record_multipart_upload_success(&upload.bucket, upload.parts.len());
+        record_multipart_upload_success(&upload.bucket, parts.len());
+
+        self.notify_completed(upload, &result);
 
         Ok(result)
     }
 
+    /// Remove a finished upload from the registry, if one is attached
+    fn forget(&self, upload: &MultipartUpload) {
+        if let Some(registry) = &self.registry {
+            registry.unregister(&upload.bucket, &upload.upload_id);
+        }
+    }
+
+    /// Save the state of `upload`, if a resume store is attached
+    async fn save(&self, upload: &MultipartUpload) -> Result<(), UploadError> {
+        match &self.resume {
+            Some(store) => store.save(&ResumeState::of(upload)).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Remove the saved state of a finished upload
+    ///
+    /// The upload is already finished, so failures are only logged; state
+    /// left behind is never resumed successfully.
+    async fn discard(&self, upload: &MultipartUpload) {
+        if let Some(store) = &self.resume {
+            if let Err(e) = store.remove(&upload.bucket, &upload.upload_id).await {
+                tracing::warn!(
+                    upload_id = %upload.upload_id,
+                    error = %e,
+                    "Failed to remove multipart upload state"
+                );
+            }
+        }
+    }
+
+    /// Queue a completion event if a notifier is attached
+    fn notify_completed(&self, upload: &MultipartUpload, result: &UploadResult) {
+        if let Some(notifier) = &self.notifier {
+            // Delivery failures are logged by the notifier and never fail the upload
+            let _ = notifier.notify(UploadEvent::completed(
+                &upload.bucket,
+                &upload.key,
+                &result.etag,
+                result.bytes_written,
+                None,
+            ));
+        }
+    }
+
     /// Abort a multipart upload
     #[tracing::instrument(
         name = "upload.multipart.abort",
@@ -344,11 +764,14 @@ impl MultipartHandler {
         err
     )]
     pub async fn abort(&self, upload: &MultipartUpload) -> Result<(), UploadError> {
-        // Use S3Client if available
-        if let Some(client) = &self.client {
-            client
+        // Use storage backend if available
+        if let Some(backend) = &self.backend {
+            backend
                 .abort_multipart_upload(&upload.key, &upload.upload_id)
-                .await?;
+                .await
+                .map_err(|e| e.in_object(upload.error_context()))?;
+            self.forget(upload);
+            self.discard(upload).await;
 
             tracing::info!(
                 upload_id = %upload.upload_id,
@@ -408,4 +831,116 @@ mod tests {
         let result = handler.complete(&upload).await;
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_part_size_for() {
+        assert_eq!(part_size_for(1024, MIN_PART_SIZE), MIN_PART_SIZE);
+        assert_eq!(part_size_for(0, 0), 1);
+
+        // 5TB in 100MB parts would take 52429 parts
+        let part_size = part_size_for(MAX_OBJECT_SIZE, 100 * 1024 * 1024);
+        assert_eq!(part_size, 549_755_814);
+        assert!(MAX_OBJECT_SIZE.div_ceil(part_size as u64) <= MAX_PARTS as u64);
+        assert!(part_size as u64 <= MAX_PUT_SIZE);
+    }
+
+    #[tokio::test]
+    async fn test_part_number_out_of_range() {
+        let handler = MultipartHandler::new("bucket", "us-east-1", MIN_PART_SIZE, 4);
+        let mut upload = handler.create("bucket", "key").await.unwrap();
+
+        for part_number in [0, MAX_PARTS as u32 + 1] {
+            let error = handler
+                .upload_part(&mut upload, part_number, Bytes::from("x"))
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                error.root(),
+                UploadError::InvalidParts {
+                    code: S3ErrorCode::InvalidArgument,
+                    ..
+                }
+            ));
+        }
+        assert!(upload.parts.is_empty());
+    }
+
+    #[test]
+    fn test_with_client_takes_hashing() {
+        let client = S3Client::new(crate::s3::S3ClientConfig {
+            bucket: "bucket".into(),
+            region: "us-east-1".into(),
+            endpoint: None,
+            access_key: None,
+            secret_key: None,
+            retry: None,
+            timeout: None,
+            pool: None,
+        })
+        .unwrap()
+        .with_hashing(S3HashingConfig {
+            offload_threshold_bytes: 64,
+        });
+
+        let handler = MultipartHandler::with_client(client);
+        assert_eq!(handler.hashing.offload_threshold_bytes, 64);
+    }
+
+    #[tokio::test]
+    async fn test_reuploaded_part_replaces_earlier() {
+        let handler = MultipartHandler::new("bucket", "us-east-1", MIN_PART_SIZE, 4);
+        let mut upload = handler.create("bucket", "key").await.unwrap();
+
+        handler
+            .upload_part(&mut upload, 2, Bytes::from("last"))
+            .await
+            .unwrap();
+        handler
+            .upload_part(&mut upload, 1, Bytes::from("small"))
+            .await
+            .unwrap();
+        handler
+            .upload_part(&mut upload, 1, Bytes::from(vec![0u8; MIN_PART_SIZE]))
+            .await
+            .unwrap();
+
+        let parts = upload.sorted_parts();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].part_number, 1);
+        assert!(handler.complete(&upload).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_completion_validated() {
+        let handler = MultipartHandler::new("bucket", "us-east-1", MIN_PART_SIZE, 4);
+        let mut upload = handler.create("bucket", "key").await.unwrap();
+        let first = handler
+            .upload_part(&mut upload, 1, Bytes::from("small"))
+            .await
+            .unwrap();
+        let second = handler
+            .upload_part(&mut upload, 2, Bytes::from("last"))
+            .await
+            .unwrap();
+
+        let code = |parts: &[CompletedPart]| match upload.validate_completion(parts) {
+            Err(UploadError::InvalidParts { code, .. }) => Some(code),
+            _ => None,
+        };
+        let forged = CompletedPart::new(2, "\"forged\"");
+        assert_eq!(
+            code(&[second.clone(), first.clone()]),
+            Some(S3ErrorCode::InvalidPartOrder)
+        );
+        assert_eq!(
+            code(&[first.clone(), forged]),
+            Some(S3ErrorCode::InvalidPart)
+        );
+        assert_eq!(
+            code(&[first.clone(), second]),
+            Some(S3ErrorCode::EntityTooSmall)
+        );
+        // The last part may be small
+        assert_eq!(code(&[first]), None);
+    }
 }
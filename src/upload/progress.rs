@@ -0,0 +1,430 @@
+//! Progress of uploads in flight, for frontend progress bars
+//!
+//! A client that sends [`UPLOAD_ID_HEADER`] with a PUT can follow the upload
+//! with `GET <same path>?uploadId=<id>` while it runs: the bytes the proxy
+//! has actually received, the multipart parts stored on the backend and an
+//! ETA from the rate so far. Adding `&wait=<secs>` long-polls until the
+//! progress changes. Finished sessions are kept for [`FINISHED_RETENTION`]
+//! so the last poll sees the outcome.
+//!
+//! Sessions live in memory on the proxy instance serving the upload.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::upload::progress::{UploadSessions, UploadState};
+//!
+//! let sessions = UploadSessions::new();
+//! let session = sessions
+//!     .start("7d1c", "uploads", "videos/a.mp4", Some("alice"), Some(1024))
+//!     .unwrap();
+//! session.add_received(512);
+//!
+//! let progress = sessions.get("7d1c").unwrap();
+//! assert_eq!(progress.bytes_received, 512);
+//! assert_eq!(progress.state, UploadState::Receiving);
+//!
+//! session.complete();
+//! assert_eq!(sessions.get("7d1c").unwrap().state, UploadState::Completed);
+//! ```
+
+use bytes::Buf;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use hyper::body::{Body, Frame, SizeHint};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Request header carrying the client-chosen upload ID
+pub const UPLOAD_ID_HEADER: &str = "x-mizuchi-upload-id";
+
+/// How long finished sessions can still be queried
+pub const FINISHED_RETENTION: Duration = Duration::from_secs(60);
+
+/// Longest accepted upload ID
+const MAX_UPLOAD_ID_LEN: usize = 128;
+
+/// Stage of an upload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadState {
+    /// Reading the request body
+    Receiving,
+    /// Body received, storing it on the backend
+    Uploading,
+    Completed,
+    Failed,
+}
+
+impl UploadState {
+    /// Whether the upload is over
+    pub fn is_finished(&self) -> bool {
+        matches!(self, UploadState::Completed | UploadState::Failed)
+    }
+}
+
+/// Snapshot of an upload's progress
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadProgress {
+    pub upload_id: String,
+    pub bucket: String,
+    pub key: String,
+    /// Authenticated uploader, who alone may query the session
+    #[serde(skip)]
+    pub subject: Option<String>,
+    pub state: UploadState,
+    /// Declared `Content-Length`, absent for chunked uploads
+    pub total_bytes: Option<u64>,
+    pub bytes_received: u64,
+    /// Multipart parts stored on the backend (large spilled bodies only)
+    pub parts_completed: u32,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Seconds until the body is received, at the rate so far
+    pub eta_secs: Option<u64>,
+}
+
+struct Session {
+    progress: Mutex<UploadProgress>,
+    started: Instant,
+    finished_at: Mutex<Option<Instant>>,
+    changed: Notify,
+}
+
+impl Session {
+    fn update(&self, f: impl FnOnce(&mut UploadProgress)) {
+        {
+            let mut progress = self.progress.lock();
+            f(&mut progress);
+            progress.updated_at = Utc::now();
+            if progress.state.is_finished() {
+                self.finished_at.lock().get_or_insert_with(Instant::now);
+            }
+        }
+        self.changed.notify_waiters();
+    }
+
+    fn snapshot(&self) -> UploadProgress {
+        let mut progress = self.progress.lock().clone();
+        progress.eta_secs = eta(&progress, self.started.elapsed());
+        progress
+    }
+
+    fn expired(&self) -> bool {
+        self.finished_at
+            .lock()
+            .is_some_and(|at| at.elapsed() > FINISHED_RETENTION)
+    }
+}
+
+/// Seconds left to receive the body, if its size is known
+fn eta(progress: &UploadProgress, elapsed: Duration) -> Option<u64> {
+    if progress.state != UploadState::Receiving {
+        return None;
+    }
+    let total = progress.total_bytes?;
+    let received = progress.bytes_received;
+    if received == 0 || elapsed.is_zero() {
+        return None;
+    }
+    let rate = received as f64 / elapsed.as_secs_f64();
+    Some((total.saturating_sub(received) as f64 / rate).ceil() as u64)
+}
+
+/// Upload sessions, keyed by upload ID
+#[derive(Default)]
+pub struct UploadSessions {
+    sessions: DashMap<String, Arc<Session>>,
+}
+
+impl UploadSessions {
+    /// Create an empty session store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start tracking an upload
+    ///
+    /// Returns `None` if the ID is invalid or used by another upload that
+    /// is still running or retained.
+    pub fn start(
+        &self,
+        upload_id: &str,
+        bucket: &str,
+        key: &str,
+        subject: Option<&str>,
+        total_bytes: Option<u64>,
+    ) -> Option<SessionHandle> {
+        if !valid_upload_id(upload_id) {
+            return None;
+        }
+        self.sessions.retain(|_, session| !session.expired());
+
+        let now = Utc::now();
+        let session = Arc::new(Session {
+            progress: Mutex::new(UploadProgress {
+                upload_id: upload_id.to_string(),
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+                subject: subject.map(str::to_string),
+                state: UploadState::Receiving,
+                total_bytes,
+                bytes_received: 0,
+                parts_completed: 0,
+                started_at: now,
+                updated_at: now,
+                eta_secs: None,
+            }),
+            started: Instant::now(),
+            finished_at: Mutex::new(None),
+            changed: Notify::new(),
+        });
+        match self.sessions.entry(upload_id.to_string()) {
+            dashmap::mapref::entry::Entry::Occupied(_) => None,
+            dashmap::mapref::entry::Entry::Vacant(entry) => {
+                entry.insert(Arc::clone(&session));
+                Some(SessionHandle {
+                    session,
+                    finished: false,
+                })
+            }
+        }
+    }
+
+    /// Current progress of an upload
+    pub fn get(&self, upload_id: &str) -> Option<UploadProgress> {
+        let session = self.sessions.get(upload_id)?;
+        (!session.expired()).then(|| session.snapshot())
+    }
+
+    /// Progress of an upload once it changes, or after `timeout`
+    ///
+    /// Returns at once if the upload is already finished.
+    pub async fn wait(&self, upload_id: &str, timeout: Duration) -> Option<UploadProgress> {
+        let session = Arc::clone(self.sessions.get(upload_id)?.value());
+        if session.expired() {
+            return None;
+        }
+        let changed = session.changed.notified();
+        tokio::pin!(changed);
+        changed.as_mut().enable();
+        let finished = session.progress.lock().state.is_finished();
+        if !finished {
+            let _ = tokio::time::timeout(timeout, changed).await;
+        }
+        Some(session.snapshot())
+    }
+
+    /// Uploads in progress in `bucket`
+    pub fn list(&self, bucket: &str) -> Vec<UploadProgress> {
+        self.sessions
+            .iter()
+            .map(|session| session.snapshot())
+            .filter(|progress| progress.bucket == bucket && !progress.state.is_finished())
+            .collect()
+    }
+
+    /// Number of sessions, including finished ones still retained
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Whether there are no sessions
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+}
+
+/// IDs are opaque to the proxy but must be printable and reasonably short
+fn valid_upload_id(upload_id: &str) -> bool {
+    !upload_id.is_empty()
+        && upload_id.len() <= MAX_UPLOAD_ID_LEN
+        && upload_id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_.".contains(&b))
+}
+
+/// Handle for reporting the progress of one upload
+///
+/// Dropping the handle without [`complete`](Self::complete) marks the
+/// upload failed, so every early return is accounted for.
+pub struct SessionHandle {
+    session: Arc<Session>,
+    finished: bool,
+}
+
+impl SessionHandle {
+    /// Record body bytes received from the client
+    pub fn add_received(&self, bytes: u64) {
+        self.session.update(|p| p.bytes_received += bytes);
+    }
+
+    /// The body is received and is being stored
+    pub fn uploading(&self) {
+        self.session.update(|p| p.state = UploadState::Uploading);
+    }
+
+    /// A multipart part was stored on the backend
+    pub fn part_completed(&self) {
+        self.session.update(|p| p.parts_completed += 1);
+    }
+
+    /// The upload succeeded
+    pub fn complete(mut self) {
+        self.finished = true;
+        self.session.update(|p| p.state = UploadState::Completed);
+    }
+}
+
+impl Drop for SessionHandle {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.session.update(|p| p.state = UploadState::Failed);
+        }
+    }
+}
+
+/// Request body counting the bytes read into an upload session
+pub struct ProgressBody<'a, B> {
+    inner: B,
+    session: Option<&'a SessionHandle>,
+}
+
+impl<'a, B> ProgressBody<'a, B> {
+    /// Count the bytes of `inner` into `session`, if any
+    pub fn new(inner: B, session: Option<&'a SessionHandle>) -> Self {
+        Self { inner, session }
+    }
+}
+
+impl<B> Body for ProgressBody<'_, B>
+where
+    B: Body + Unpin,
+    B::Data: Buf,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        if let (Poll::Ready(Some(Ok(frame))), Some(session)) = (&poll, self.session) {
+            if let Some(data) = frame.data_ref() {
+                session.add_received(data.remaining() as u64);
+            }
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::{BodyExt, Full};
+
+    #[test]
+    fn test_session_lifecycle() {
+        let sessions = UploadSessions::new();
+        let session = sessions
+            .start("u1", "uploads", "a.bin", None, Some(100))
+            .unwrap();
+
+        assert!(sessions
+            .start("u1", "uploads", "b.bin", None, None)
+            .is_none());
+
+        session.add_received(100);
+        session.uploading();
+        session.part_completed();
+        let progress = sessions.get("u1").unwrap();
+        assert_eq!(progress.state, UploadState::Uploading);
+        assert_eq!(progress.parts_completed, 1);
+        assert_eq!(progress.eta_secs, None);
+
+        drop(session);
+        assert_eq!(sessions.get("u1").unwrap().state, UploadState::Failed);
+    }
+
+    #[test]
+    fn test_list_in_progress() {
+        let sessions = UploadSessions::new();
+        let _running = sessions.start("u1", "uploads", "a.bin", None, None);
+        let _other = sessions.start("u2", "videos", "b.bin", None, None);
+        sessions
+            .start("u3", "uploads", "c.bin", None, None)
+            .unwrap()
+            .complete();
+
+        let listed = sessions.list("uploads");
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].upload_id, "u1");
+    }
+
+    #[test]
+    fn test_invalid_upload_ids() {
+        let sessions = UploadSessions::new();
+
+        assert!(sessions.start("", "b", "k", None, None).is_none());
+        assert!(sessions.start("a b", "b", "k", None, None).is_none());
+        assert!(sessions
+            .start(&"x".repeat(MAX_UPLOAD_ID_LEN + 1), "b", "k", None, None)
+            .is_none());
+    }
+
+    #[test]
+    fn test_eta() {
+        let mut progress = UploadSessions::new()
+            .start("u1", "b", "k", None, Some(1000))
+            .unwrap()
+            .session
+            .snapshot();
+        progress.bytes_received = 250;
+
+        assert_eq!(eta(&progress, Duration::from_secs(1)), Some(3));
+
+        progress.total_bytes = None;
+        assert_eq!(eta(&progress, Duration::from_secs(1)), None);
+    }
+
+    #[tokio::test]
+    async fn test_progress_body_counts_bytes() {
+        let sessions = UploadSessions::new();
+        let session = sessions.start("u1", "b", "k", None, Some(5)).unwrap();
+
+        let body = ProgressBody::new(Full::new(bytes::Bytes::from("hello")), Some(&session));
+        body.collect().await.unwrap();
+
+        assert_eq!(sessions.get("u1").unwrap().bytes_received, 5);
+    }
+
+    #[tokio::test]
+    async fn test_wait_returns_on_change() {
+        let sessions = Arc::new(UploadSessions::new());
+        let session = sessions.start("u1", "b", "k", None, None).unwrap();
+
+        let waiter = {
+            let sessions = Arc::clone(&sessions);
+            tokio::spawn(async move { sessions.wait("u1", Duration::from_secs(10)).await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        session.add_received(42);
+
+        let progress = waiter.await.unwrap().unwrap();
+        assert_eq!(progress.bytes_received, 42);
+    }
+}
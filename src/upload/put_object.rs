@@ -2,6 +2,9 @@
 //!
 //! Handles simple object uploads (< multipart threshold).
 //!
+//! The handler writes through a [`StorageBackend`]; `with_client` is a
+//! shorthand for using an `S3Client` as the backend.
+//!
 //! # Example
 //!
 //! ```no_run
@@ -20,6 +23,7 @@
 //!     secret_key: Some("secret-key".to_string()),
 //!     retry: None,
 //!     timeout: None,
+//!     pool: None,
 //! };
 //! let s3_client = S3Client::new(config)?;
 //!
@@ -34,38 +38,44 @@
 //! # }
 //! ```
 
+use super::backend::{StorageBackend, StoredObject};
 use super::{UploadError, UploadHandler, UploadResult};
 use crate::metrics;
+use crate::notifications::{UploadEvent, WebhookNotifier};
 use crate::s3::S3Client;
 use async_trait::async_trait;
 use bytes::Bytes;
+use std::sync::Arc;
 use std::time::Instant;
 
 /// Simple upload handler
 ///
 /// Handles single-part uploads for files under the multipart threshold (default 50MB).
-/// Uses an S3 client for actual uploads.
+/// Uses a storage backend for actual uploads.
 pub struct PutObjectHandler {
-    /// S3 client for making upload requests
-    client: Option<S3Client>,
+    /// Storage backend for making upload requests
+    backend: Option<Arc<dyn StorageBackend>>,
     /// Bucket name (used when client is not provided)
     #[allow(dead_code)]
     bucket: String,
     /// Region (used when client is not provided)
     #[allow(dead_code)]
     region: String,
+    /// Optional notifier for upload completion events
+    notifier: Option<Arc<WebhookNotifier>>,
 }
 
 impl PutObjectHandler {
     /// Create a new PutObject handler (legacy constructor)
     ///
-    /// This constructor creates a handler without an S3 client.
-    /// Use `with_client` for production use.
+    /// This constructor creates a handler without a storage backend.
+    /// Use `with_client` or `with_backend` for production use.
     pub fn new(bucket: &str, region: &str) -> Self {
         Self {
-            client: None,
+            backend: None,
             bucket: bucket.to_string(),
             region: region.to_string(),
+            notifier: None,
         }
     }
 
@@ -77,13 +87,33 @@ impl PutObjectHandler {
     ///
     /// * `client` - Configured S3 client for making upload requests
     pub fn with_client(client: S3Client) -> Self {
+        let region = client.region().to_string();
         Self {
-            bucket: client.bucket().to_string(),
-            region: client.region().to_string(),
-            client: Some(client),
+            region,
+            ..Self::with_backend(Arc::new(client))
         }
     }
 
+    /// Create a new PutObject handler with any storage backend
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - Storage backend objects are written to
+    pub fn with_backend(backend: Arc<dyn StorageBackend>) -> Self {
+        Self {
+            bucket: backend.bucket().to_string(),
+            region: String::new(),
+            backend: Some(backend),
+            notifier: None,
+        }
+    }
+
+    /// Attach a notifier that receives an event after each successful upload
+    pub fn with_notifier(mut self, notifier: Arc<WebhookNotifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
     /// Check if zero-copy transfer is supported on this platform
     ///
     /// Returns `true` on Linux where splice(2)/sendfile(2) are available,
@@ -119,36 +149,32 @@ impl UploadHandler for PutObjectHandler {
         let bytes_written = body.len() as u64;
         let start_time = Instant::now();
 
-        // Use S3 client if available, otherwise use placeholder for legacy behavior
-        let upload_result = if let Some(client) = &self.client {
-            // Validate bucket matches client configuration
-            if bucket != client.bucket() {
+        // Use storage backend if available, otherwise use placeholder for legacy behavior
+        let upload_result = if let Some(backend) = &self.backend {
+            // Validate bucket matches backend configuration
+            if bucket != backend.bucket() {
                 tracing::error!(
-                    expected_bucket = %client.bucket(),
+                    expected_bucket = %backend.bucket(),
                     actual_bucket = %bucket,
                     "Bucket mismatch: upload requested for different bucket than client configured"
                 );
                 return Err(UploadError::S3Error(format!(
                     "Bucket mismatch: client configured for '{}' but upload requested for '{}'",
-                    client.bucket(),
+                    backend.bucket(),
                     bucket
                 )));
             }
-            // Real S3 upload via client
-            client
-                .put_object(key, body, content_type)
-                .await
-                .map(|response| response.etag)
-                .map_err(|e| UploadError::S3Error(e.to_string()))
+            // Real upload via storage backend
+            backend.put_object(key, body, content_type).await
         } else {
             // Legacy placeholder behavior (for backward compatibility with existing tests)
             tracing::warn!(
                 bucket = %bucket,
                 key = %key,
-                "Using legacy placeholder path: no S3 client configured, returning fake ETag. \
+                "Using legacy placeholder path: no storage backend configured, returning fake ETag. \
                  This should only happen in tests."
             );
-            Ok(format!("\"{}\"", uuid::Uuid::new_v4()))
+            Ok(StoredObject::new(format!("\"{}\"", uuid::Uuid::new_v4())))
         };
 
         // Record metrics
@@ -156,14 +182,10 @@ impl UploadHandler for PutObjectHandler {
         metrics::record_upload_duration(bucket, "put_object", duration.as_secs_f64());
 
         match upload_result {
-            Ok(etag) => {
+            Ok(object) => {
                 metrics::record_upload_success(bucket, bytes_written);
 
-                let result = UploadResult {
-                    etag,
-                    version_id: None,
-                    bytes_written,
-                };
+                let result = UploadResult::stored(object, bytes_written);
 
                 // Record result in span
                 let span = tracing::Span::current();
@@ -177,6 +199,17 @@ impl UploadHandler for PutObjectHandler {
                     "PutObject upload completed"
                 );
 
+                if let Some(notifier) = &self.notifier {
+                    // Delivery failures are logged by the notifier and never fail the upload
+                    let _ = notifier.notify(UploadEvent::completed(
+                        bucket,
+                        key,
+                        &result.etag,
+                        bytes_written,
+                        None,
+                    ));
+                }
+
                 Ok(result)
             }
             Err(e) => {
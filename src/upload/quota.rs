@@ -0,0 +1,490 @@
+//! Upload quotas per subject or tenant
+//!
+//! A [`Quota`] counts the bytes and objects each subject (or tenant, by
+//! claim) uploads to a bucket in daily or monthly windows and rejects uploads
+//! that would go over a limit. Uploads are checked before their body is read
+//! against their declared size; bodies without a `Content-Length` are capped
+//! at the bytes left. Concurrent uploads by the same owner are checked
+//! against the same count, so together they may go over a limit by the size
+//! of the uploads in flight.
+//!
+//! [`LocalQuotaStore`] keeps counts on one instance, optionally persisted to
+//! a JSON file. Behind a load balancer, use a [`SharedQuotaStore`] over
+//! Redis (the quota's own `redis` setting or the shared `state.redis`) so
+//! every instance counts against the same quota.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::config::{QuotaConfig, QuotaLimit, QuotaWindow};
+//! use mizuchi_uploadr::upload::quota::Quota;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let config = QuotaConfig {
+//!     claim: None,
+//!     limits: vec![QuotaLimit {
+//!         window: QuotaWindow::Daily,
+//!         max_bytes: Some(1024),
+//!         max_objects: None,
+//!     }],
+//!     redis: None,
+//!     path: None,
+//! };
+//! let quota = Quota::from_config("uploads", &config)?;
+//!
+//! quota.check("alice", Some(1000)).await?;
+//! quota.record("alice", 1000).await?;
+//! assert!(quota.check("alice", Some(100)).await.is_err());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::auth::chain::ANONYMOUS_SUBJECT;
+use crate::authz::claims;
+use crate::config::{QuotaConfig, QuotaLimit, QuotaWindow};
+use crate::redis::RedisClient;
+use crate::state::{RedisStore, StateError, Store};
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Days, Months, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+/// Prefix of quota counts in the shared `state` store
+const SHARED_KEY_PREFIX: &str = "quota:";
+
+/// Quota errors
+#[derive(Error, Debug)]
+pub enum QuotaError {
+    #[error(
+        "{} upload quota of {max} {unit} exceeded, resets at {}",
+        .window.as_str(),
+        .resets_at.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+    )]
+    Exceeded {
+        window: QuotaWindow,
+        unit: &'static str,
+        max: u64,
+        resets_at: DateTime<Utc>,
+    },
+
+    #[error("Quota store unavailable: {0}")]
+    Store(String),
+
+    #[error("Quota store I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// What an owner uploaded in one window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub bytes: u64,
+    pub objects: u64,
+}
+
+/// Store of quota usage counts
+#[async_trait]
+pub trait QuotaStore: Send + Sync {
+    /// Usage counted under `key`
+    async fn usage(&self, key: &str) -> Result<Usage, QuotaError>;
+
+    /// Count an upload of `bytes` under `key`, forgotten after `expires_at`
+    async fn add(&self, key: &str, bytes: u64, expires_at: DateTime<Utc>)
+        -> Result<(), QuotaError>;
+}
+
+/// Quota enforced on one bucket
+pub struct Quota {
+    bucket: String,
+    claim: Option<String>,
+    limits: Vec<QuotaLimit>,
+    store: Arc<dyn QuotaStore>,
+}
+
+impl Quota {
+    /// Build the quota described by `config`, loading persisted counts
+    pub fn from_config(bucket: &str, config: &QuotaConfig) -> Result<Self, QuotaError> {
+        Self::from_config_with_state(bucket, config, None)
+    }
+
+    /// Like [`from_config`](Self::from_config), counting in the shared
+    /// `state` store unless the quota has its own Redis or file
+    pub fn from_config_with_state(
+        bucket: &str,
+        config: &QuotaConfig,
+        state: Option<&Arc<dyn Store>>,
+    ) -> Result<Self, QuotaError> {
+        let store: Arc<dyn QuotaStore> = match (&config.redis, &config.path, state) {
+            (Some(redis), _, _) => {
+                let client = RedisClient::from_url(&redis.url)
+                    .map_err(|e| QuotaError::Store(e.to_string()))?;
+                let redis = Arc::new(RedisStore::new(client, &redis.key_prefix));
+                Arc::new(SharedQuotaStore::new(redis, ""))
+            }
+            (None, None, Some(state)) => {
+                Arc::new(SharedQuotaStore::new(Arc::clone(state), SHARED_KEY_PREFIX))
+            }
+            (None, path, _) => Arc::new(LocalQuotaStore::open(path.as_deref().map(Path::new))?),
+        };
+        Ok(Self::with_store(bucket, config, store))
+    }
+
+    /// Enforce `config` with counts kept in `store`
+    pub fn with_store(bucket: &str, config: &QuotaConfig, store: Arc<dyn QuotaStore>) -> Self {
+        Self {
+            bucket: bucket.to_string(),
+            claim: config.claim.clone(),
+            limits: config.limits.clone(),
+            store,
+        }
+    }
+
+    /// Whom an upload counts against: the quota claim's value if the caller
+    /// has one, else the subject
+    pub fn owner(&self, subject: Option<&str>, claims: Option<&HashMap<String, Value>>) -> String {
+        let claimed = self
+            .claim
+            .as_deref()
+            .zip(claims)
+            .and_then(|(claim, claims)| match claims::lookup(claims, claim)? {
+                Value::String(value) => Some(value.clone()),
+                Value::Number(value) => Some(value.to_string()),
+                _ => None,
+            });
+        claimed.unwrap_or_else(|| subject.unwrap_or(ANONYMOUS_SUBJECT).to_string())
+    }
+
+    /// Check that `owner` may upload `size` bytes (unknown for chunked
+    /// uploads), returning the bytes left under the tightest byte limit
+    pub async fn check(&self, owner: &str, size: Option<u64>) -> Result<Option<u64>, QuotaError> {
+        let now = Utc::now();
+        let mut remaining: Option<u64> = None;
+        for limit in &self.limits {
+            let (period, resets_at) = period(limit.window, now);
+            let usage = self.store.usage(&self.key(owner, limit, &period)).await?;
+            let exceeded = |unit, max| QuotaError::Exceeded {
+                window: limit.window,
+                unit,
+                max,
+                resets_at,
+            };
+
+            if let Some(max) = limit.max_objects {
+                if usage.objects >= max {
+                    return Err(exceeded("objects", max));
+                }
+            }
+            if let Some(max) = limit.max_bytes {
+                let left = max.saturating_sub(usage.bytes);
+                if size.map_or(left == 0, |size| size > left) {
+                    return Err(exceeded("bytes", max));
+                }
+                remaining = Some(remaining.map_or(left, |r| r.min(left)));
+            }
+        }
+        Ok(remaining)
+    }
+
+    /// Count a stored upload of `bytes` against `owner`
+    pub async fn record(&self, owner: &str, bytes: u64) -> Result<(), QuotaError> {
+        let now = Utc::now();
+        for limit in &self.limits {
+            let (period, resets_at) = period(limit.window, now);
+            self.store
+                .add(&self.key(owner, limit, &period), bytes, resets_at)
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn key(&self, owner: &str, limit: &QuotaLimit, period: &str) -> String {
+        format!(
+            "{}:{}:{}:{}",
+            self.bucket,
+            owner,
+            limit.window.as_str(),
+            period
+        )
+    }
+}
+
+/// Window of `window` containing `now`: its label and when it ends
+fn period(window: QuotaWindow, now: DateTime<Utc>) -> (String, DateTime<Utc>) {
+    let today = now.date_naive();
+    let (start, label) = match window {
+        QuotaWindow::Daily => (today, today.format("%Y-%m-%d").to_string()),
+        QuotaWindow::Monthly => {
+            let start = today.with_day(1).unwrap_or(today);
+            (start, start.format("%Y-%m").to_string())
+        }
+    };
+    let end = match window {
+        QuotaWindow::Daily => start + Days::new(1),
+        QuotaWindow::Monthly => start + Months::new(1),
+    };
+    (label, end.and_time(NaiveTime::MIN).and_utc())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LocalEntry {
+    #[serde(flatten)]
+    usage: Usage,
+    expires_at: DateTime<Utc>,
+}
+
+/// Quota counts kept by this instance
+///
+/// With a path, counts are loaded at startup and the file is rewritten
+/// after every upload, so they survive restarts.
+pub struct LocalQuotaStore {
+    path: Option<PathBuf>,
+    entries: Mutex<HashMap<String, LocalEntry>>,
+}
+
+impl LocalQuotaStore {
+    /// Open the store, loading counts from `path` if it exists
+    pub fn open(path: Option<&Path>) -> Result<Self, QuotaError> {
+        let mut entries: HashMap<String, LocalEntry> = match path {
+            Some(path) => match std::fs::read(path) {
+                Ok(content) => serde_json::from_slice(&content).map_err(|e| {
+                    QuotaError::Store(format!("Invalid quota file {}: {}", path.display(), e))
+                })?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+                Err(e) => return Err(e.into()),
+            },
+            None => HashMap::new(),
+        };
+        let now = Utc::now();
+        entries.retain(|_, entry| entry.expires_at > now);
+
+        Ok(Self {
+            path: path.map(Path::to_path_buf),
+            entries: Mutex::new(entries),
+        })
+    }
+}
+
+#[async_trait]
+impl QuotaStore for LocalQuotaStore {
+    async fn usage(&self, key: &str) -> Result<Usage, QuotaError> {
+        let entries = self.entries.lock().await;
+        Ok(entries
+            .get(key)
+            .filter(|entry| entry.expires_at > Utc::now())
+            .map(|entry| entry.usage)
+            .unwrap_or_default())
+    }
+
+    async fn add(
+        &self,
+        key: &str,
+        bytes: u64,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), QuotaError> {
+        // The lock is held while writing so the file never goes back in time
+        let mut entries = self.entries.lock().await;
+        let now = Utc::now();
+        entries.retain(|_, entry| entry.expires_at > now);
+        let entry = entries.entry(key.to_string()).or_insert(LocalEntry {
+            usage: Usage::default(),
+            expires_at,
+        });
+        entry.usage.bytes = entry.usage.bytes.saturating_add(bytes);
+        entry.usage.objects += 1;
+
+        if let Some(ref path) = self.path {
+            let content =
+                serde_json::to_vec(&*entries).map_err(|e| QuotaError::Store(e.to_string()))?;
+            let tmp = path.with_extension("tmp");
+            tokio::fs::write(&tmp, content).await?;
+            tokio::fs::rename(&tmp, path).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Quota counts kept in a [`Store`], shared between instances when the
+/// store is
+pub struct SharedQuotaStore {
+    store: Arc<dyn Store>,
+    key_prefix: String,
+}
+
+impl SharedQuotaStore {
+    pub fn new(store: Arc<dyn Store>, key_prefix: &str) -> Self {
+        Self {
+            store,
+            key_prefix: key_prefix.to_string(),
+        }
+    }
+
+    async fn count(&self, key: &str) -> Result<u64, QuotaError> {
+        match self.store.get(key).await.map_err(store_error)? {
+            Some(value) => std::str::from_utf8(&value)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| QuotaError::Store(format!("invalid quota count at '{}'", key))),
+            None => Ok(0),
+        }
+    }
+}
+
+#[async_trait]
+impl QuotaStore for SharedQuotaStore {
+    async fn usage(&self, key: &str) -> Result<Usage, QuotaError> {
+        let key = format!("{}{}", self.key_prefix, key);
+        Ok(Usage {
+            bytes: self.count(&format!("{}:bytes", key)).await?,
+            objects: self.count(&format!("{}:objects", key)).await?,
+        })
+    }
+
+    async fn add(
+        &self,
+        key: &str,
+        bytes: u64,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), QuotaError> {
+        let key = format!("{}{}", self.key_prefix, key);
+        let ttl = (expires_at - Utc::now()).to_std().unwrap_or_default();
+        let bytes = i64::try_from(bytes).unwrap_or(i64::MAX);
+        self.store
+            .incr_by(&format!("{}:bytes", key), bytes, ttl)
+            .await
+            .map_err(store_error)?;
+        self.store
+            .incr_by(&format!("{}:objects", key), 1, ttl)
+            .await
+            .map_err(store_error)?;
+        Ok(())
+    }
+}
+
+fn store_error(error: StateError) -> QuotaError {
+    QuotaError::Store(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::MemoryStore;
+    use chrono::TimeZone;
+
+    fn config(limits: Vec<QuotaLimit>) -> QuotaConfig {
+        QuotaConfig {
+            claim: Some("tenant".into()),
+            limits,
+            redis: None,
+            path: None,
+        }
+    }
+
+    fn limit(window: QuotaWindow, max_bytes: Option<u64>, max_objects: Option<u64>) -> QuotaLimit {
+        QuotaLimit {
+            window,
+            max_bytes,
+            max_objects,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_limits() {
+        let quota = Quota::from_config(
+            "uploads",
+            &config(vec![
+                limit(QuotaWindow::Daily, Some(100), None),
+                limit(QuotaWindow::Monthly, Some(1000), Some(2)),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(quota.check("alice", Some(60)).await.unwrap(), Some(100));
+        quota.record("alice", 60).await.unwrap();
+        assert_eq!(quota.check("alice", None).await.unwrap(), Some(40));
+        assert!(matches!(
+            quota.check("alice", Some(50)).await,
+            Err(QuotaError::Exceeded {
+                unit: "bytes",
+                max: 100,
+                ..
+            })
+        ));
+
+        quota.record("alice", 10).await.unwrap();
+        assert!(matches!(
+            quota.check("alice", Some(1)).await,
+            Err(QuotaError::Exceeded {
+                unit: "objects",
+                max: 2,
+                ..
+            })
+        ));
+
+        // Other owners have their own counts
+        assert!(quota.check("bob", Some(100)).await.is_ok());
+    }
+
+    #[test]
+    fn test_owner() {
+        let quota = Quota::from_config("uploads", &config(vec![])).unwrap();
+        let claims: HashMap<String, Value> = serde_json::from_str(r#"{"tenant": "acme"}"#).unwrap();
+
+        assert_eq!(quota.owner(Some("alice"), Some(&claims)), "acme");
+        assert_eq!(quota.owner(Some("alice"), Some(&HashMap::new())), "alice");
+        assert_eq!(quota.owner(None, None), ANONYMOUS_SUBJECT);
+    }
+
+    #[test]
+    fn test_period() {
+        let now = Utc.with_ymd_and_hms(2026, 12, 31, 18, 30, 0).unwrap();
+
+        let (label, end) = period(QuotaWindow::Daily, now);
+        assert_eq!(label, "2026-12-31");
+        assert_eq!(end, Utc.with_ymd_and_hms(2027, 1, 1, 0, 0, 0).unwrap());
+
+        let (label, end) = period(QuotaWindow::Monthly, now);
+        assert_eq!(label, "2026-12");
+        assert_eq!(end, Utc.with_ymd_and_hms(2027, 1, 1, 0, 0, 0).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_shared_store() {
+        let store = SharedQuotaStore::new(Arc::new(MemoryStore::new()), "quota:");
+        let expires_at = Utc::now() + Days::new(1);
+
+        store.add("k", 10, expires_at).await.unwrap();
+        store.add("k", 5, expires_at).await.unwrap();
+
+        assert_eq!(
+            store.usage("k").await.unwrap(),
+            Usage {
+                bytes: 15,
+                objects: 2
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_local_store_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("quota.json");
+        let expires_at = Utc::now() + Days::new(1);
+
+        let store = LocalQuotaStore::open(Some(&path)).unwrap();
+        store.add("k", 10, expires_at).await.unwrap();
+        store.add("k", 5, expires_at).await.unwrap();
+
+        let store = LocalQuotaStore::open(Some(&path)).unwrap();
+        assert_eq!(
+            store.usage("k").await.unwrap(),
+            Usage {
+                bytes: 15,
+                objects: 2
+            }
+        );
+    }
+}
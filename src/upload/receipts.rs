@@ -0,0 +1,319 @@
+//! Upload receipts
+//!
+//! The proxy can't read objects back, so "did user X upload file Y?" would
+//! otherwise need S3 list permissions. A [`ReceiptStore`] appends a
+//! [`Receipt`] for every completed upload to a JSON lines file and keeps
+//! them in memory for the admin API's `GET /receipts`.
+//!
+//! Receipts older than the retention are dropped, and the file rewritten
+//! without them, when the store is opened.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use mizuchi_uploadr::upload::receipts::{Receipt, ReceiptQuery, ReceiptStore};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let store = ReceiptStore::open("/var/lib/mizuchi/receipts.jsonl", 30)?;
+//! store
+//!     .record(Receipt::new("uploads", "reports/q3.pdf", "\"d41d8cd9\"", 1024, Some("alice")))
+//!     .await;
+//!
+//! let receipts = store.query(&ReceiptQuery {
+//!     subject: Some("alice"),
+//!     ..Default::default()
+//! });
+//! assert_eq!(receipts[0].key, "reports/q3.pdf");
+//! # Ok(())
+//! # }
+//! ```
+
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+/// Record of a completed upload
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Receipt {
+    /// Configured bucket name
+    pub bucket: String,
+    /// Stored key, with any `key_prefix`
+    pub key: String,
+    pub etag: String,
+    pub size: u64,
+    /// Authenticated subject, if the upload was authenticated
+    pub subject: Option<String>,
+    /// Party that uploaded on behalf of the subject (token exchange)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    /// IDs of the OPA decisions that authorized the upload
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub decision_ids: Vec<String>,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+impl Receipt {
+    /// Receipt of an upload completed now
+    pub fn new(bucket: &str, key: &str, etag: &str, size: u64, subject: Option<&str>) -> Self {
+        Self {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            etag: etag.to_string(),
+            size,
+            subject: subject.map(str::to_string),
+            actor: None,
+            decision_ids: Vec::new(),
+            uploaded_at: Utc::now(),
+        }
+    }
+
+    /// Record the party acting on behalf of the subject
+    #[must_use]
+    pub fn with_actor(mut self, actor: Option<&str>) -> Self {
+        self.actor = actor.map(str::to_string);
+        self
+    }
+
+    /// Record the OPA decisions that authorized the upload
+    #[must_use]
+    pub fn with_decision_ids(mut self, decision_ids: Vec<String>) -> Self {
+        self.decision_ids = decision_ids;
+        self
+    }
+}
+
+/// Filter of [`ReceiptStore::query`]; unset fields match anything
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReceiptQuery<'a> {
+    pub bucket: Option<&'a str>,
+    pub subject: Option<&'a str>,
+    /// Exact stored key
+    pub key: Option<&'a str>,
+    pub key_prefix: Option<&'a str>,
+    /// Most receipts returned (unlimited if 0)
+    pub limit: usize,
+}
+
+impl ReceiptQuery<'_> {
+    fn matches(&self, receipt: &Receipt) -> bool {
+        self.bucket.is_none_or(|bucket| receipt.bucket == bucket)
+            && self
+                .subject
+                .is_none_or(|subject| receipt.subject.as_deref() == Some(subject))
+            && self.key.is_none_or(|key| receipt.key == key)
+            && self
+                .key_prefix
+                .is_none_or(|prefix| receipt.key.starts_with(prefix))
+    }
+}
+
+/// Receipts of completed uploads, kept in a JSON lines file
+#[derive(Debug)]
+pub struct ReceiptStore {
+    path: PathBuf,
+    receipts: RwLock<Vec<Receipt>>,
+    /// Serializes appends to the file
+    file: Mutex<()>,
+}
+
+impl ReceiptStore {
+    /// Open the store at `path`, loading the receipts of the last
+    /// `retention_days` days
+    ///
+    /// Lines that aren't receipts are skipped with a warning.
+    pub fn open(path: impl Into<PathBuf>, retention_days: u32) -> std::io::Result<Self> {
+        let path = path.into();
+        let cutoff = Utc::now() - Duration::days(i64::from(retention_days));
+        let (receipts, dropped) = match std::fs::File::open(&path) {
+            Ok(file) => load(&path, file, cutoff)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (Vec::new(), false),
+            Err(e) => return Err(e),
+        };
+        if dropped {
+            rewrite(&path, &receipts)?;
+        }
+
+        Ok(Self {
+            path,
+            receipts: RwLock::new(receipts),
+            file: Mutex::new(()),
+        })
+    }
+
+    /// Append a receipt
+    ///
+    /// Errors writing the file are logged rather than returned: the upload
+    /// already succeeded. The receipt stays queryable until restart.
+    pub async fn record(&self, receipt: Receipt) {
+        let _guard = self.file.lock().await;
+        let result = async {
+            let mut line = serde_json::to_vec(&receipt).map_err(std::io::Error::other)?;
+            line.push(b'\n');
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await?;
+            file.write_all(&line).await?;
+            file.flush().await
+        }
+        .await;
+
+        if let Err(e) = result {
+            tracing::error!(
+                path = %self.path.display(),
+                key = %receipt.key,
+                error = %e,
+                "Failed to write upload receipt"
+            );
+        }
+        self.receipts.write().push(receipt);
+    }
+
+    /// Receipts matching `query`, newest first
+    pub fn query(&self, query: &ReceiptQuery<'_>) -> Vec<Receipt> {
+        let limit = match query.limit {
+            0 => usize::MAX,
+            limit => limit,
+        };
+        self.receipts
+            .read()
+            .iter()
+            .rev()
+            .filter(|receipt| query.matches(receipt))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Number of receipts held
+    pub fn len(&self) -> usize {
+        self.receipts.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Receipts newer than `cutoff`, and whether any line was left out
+fn load(
+    path: &Path,
+    file: std::fs::File,
+    cutoff: DateTime<Utc>,
+) -> std::io::Result<(Vec<Receipt>, bool)> {
+    let mut receipts = Vec::new();
+    let mut dropped = false;
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Receipt>(&line) {
+            Ok(receipt) if receipt.uploaded_at >= cutoff => receipts.push(receipt),
+            Ok(_) => dropped = true,
+            Err(e) => {
+                tracing::warn!(
+                    path = %path.display(),
+                    line = number + 1,
+                    error = %e,
+                    "Skipping invalid upload receipt"
+                );
+                dropped = true;
+            }
+        }
+    }
+    Ok((receipts, dropped))
+}
+
+/// Replace the file with `receipts`
+fn rewrite(path: &Path, receipts: &[Receipt]) -> std::io::Result<()> {
+    let mut content = Vec::new();
+    for receipt in receipts {
+        serde_json::to_writer(&mut content, receipt).map_err(std::io::Error::other)?;
+        content.push(b'\n');
+    }
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, content)?;
+    std::fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_query() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ReceiptStore::open(dir.path().join("receipts.jsonl"), 30).unwrap();
+        store
+            .record(Receipt::new("docs", "a/1.txt", "\"e1\"", 1, Some("alice")))
+            .await;
+        store
+            .record(Receipt::new("docs", "b/2.txt", "\"e2\"", 2, Some("bob")))
+            .await;
+        store
+            .record(Receipt::new("media", "a/3.txt", "\"e3\"", 3, Some("alice")))
+            .await;
+
+        let keys = |query: ReceiptQuery<'_>| -> Vec<String> {
+            store.query(&query).into_iter().map(|r| r.key).collect()
+        };
+
+        assert_eq!(
+            keys(ReceiptQuery {
+                subject: Some("alice"),
+                ..Default::default()
+            }),
+            vec!["a/3.txt", "a/1.txt"]
+        );
+        assert_eq!(
+            keys(ReceiptQuery {
+                bucket: Some("docs"),
+                key_prefix: Some("a/"),
+                ..Default::default()
+            }),
+            vec!["a/1.txt"]
+        );
+        assert_eq!(
+            keys(ReceiptQuery {
+                limit: 1,
+                ..Default::default()
+            }),
+            vec!["a/3.txt"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reopen_drops_expired() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("receipts.jsonl");
+        let store = ReceiptStore::open(&path, 30).unwrap();
+        store
+            .record(Receipt::new("docs", "new.txt", "\"e\"", 1, None))
+            .await;
+        let mut old = Receipt::new("docs", "old.txt", "\"e\"", 1, None);
+        old.uploaded_at = Utc::now() - Duration::days(31);
+        store.record(old).await;
+        drop(store);
+        std::fs::write(
+            &path,
+            std::fs::read_to_string(&path).unwrap() + "not a receipt\n",
+        )
+        .unwrap();
+
+        let store = ReceiptStore::open(&path, 30).unwrap();
+
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.query(&ReceiptQuery::default())[0].key, "new.txt");
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 1);
+    }
+}
@@ -0,0 +1,290 @@
+//! Multipart upload reconciliation
+//!
+//! Clients that disappear mid-upload leave multipart uploads behind on the
+//! backend, and their parts are billed until someone aborts them. The
+//! reconciler lists the uploads in progress on a bucket's backend, compares
+//! them with the proxy's [`UploadRegistry`], and aborts the ones the proxy is
+//! not driving that were started more than `max_age` ago.
+//!
+//! Uploads whose start time the backend doesn't report are never aborted.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use mizuchi_uploadr::upload::local::LocalFsBackend;
+//! use mizuchi_uploadr::upload::reconcile::reconcile;
+//! use mizuchi_uploadr::upload::registry::UploadRegistry;
+//! use std::time::Duration;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let backend = LocalFsBackend::new("uploads", "/var/lib/mizuchi/uploads")?;
+//! let registry = UploadRegistry::new();
+//! let report = reconcile(&backend, &registry, Duration::from_secs(86400)).await?;
+//! println!("Aborted {} orphaned uploads", report.aborted.len());
+//! # Ok(())
+//! # }
+//! ```
+
+use super::backend::StorageBackend;
+use super::multipart::PendingUpload;
+use super::registry::UploadRegistry;
+use super::UploadError;
+use crate::config::MultipartReconciliationConfig;
+use crate::metrics;
+use chrono::Utc;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{interval_at, Instant, MissedTickBehavior};
+
+/// Outcome of reconciling one bucket
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReconcileReport {
+    /// Uploads in progress on the backend
+    pub listed: usize,
+    /// Uploads the proxy is still driving
+    pub tracked: usize,
+    /// Untracked uploads younger than the threshold (or of unknown age)
+    pub recent: usize,
+    /// Orphaned uploads that were aborted (or would be, in a dry run)
+    pub aborted: Vec<PendingUpload>,
+    /// Orphaned uploads whose abort failed
+    pub failed: usize,
+}
+
+/// Abort the orphaned multipart uploads of a backend
+///
+/// An upload is orphaned when it is not in `registry` and was started more
+/// than `max_age` ago. Failed aborts are logged and counted, and retried on
+/// the next run.
+pub async fn reconcile(
+    backend: &dyn StorageBackend,
+    registry: &UploadRegistry,
+    max_age: Duration,
+) -> Result<ReconcileReport, UploadError> {
+    sweep(backend, registry, max_age, false).await
+}
+
+/// Like [`reconcile`], but with `dry_run` the orphaned uploads are only
+/// reported in [`ReconcileReport::aborted`], not aborted
+pub async fn sweep(
+    backend: &dyn StorageBackend,
+    registry: &UploadRegistry,
+    max_age: Duration,
+    dry_run: bool,
+) -> Result<ReconcileReport, UploadError> {
+    let bucket = backend.bucket();
+    let uploads = backend.list_multipart_uploads().await?;
+    // No cutoff (nothing is old enough) if max_age is out of chrono's range
+    let cutoff = chrono::Duration::from_std(max_age)
+        .ok()
+        .and_then(|max_age| Utc::now().checked_sub_signed(max_age));
+
+    let mut report = ReconcileReport {
+        listed: uploads.len(),
+        ..Default::default()
+    };
+    for upload in uploads {
+        if registry.contains(bucket, &upload.upload_id) {
+            report.tracked += 1;
+            continue;
+        }
+        let expired = match (upload.initiated, cutoff) {
+            (Some(initiated), Some(cutoff)) => initiated < cutoff,
+            _ => false,
+        };
+        if !expired {
+            report.recent += 1;
+            continue;
+        }
+        if dry_run {
+            report.aborted.push(upload);
+            continue;
+        }
+
+        match backend
+            .abort_multipart_upload(&upload.key, &upload.upload_id)
+            .await
+        {
+            Ok(()) => {
+                tracing::info!(
+                    bucket = %bucket,
+                    key = %upload.key,
+                    upload_id = %upload.upload_id,
+                    "Aborted orphaned multipart upload"
+                );
+                metrics::record_multipart_upload_orphaned(bucket);
+                report.aborted.push(upload);
+            }
+            Err(e) => {
+                tracing::warn!(
+                    bucket = %bucket,
+                    upload_id = %upload.upload_id,
+                    error = %e,
+                    "Failed to abort orphaned multipart upload"
+                );
+                report.failed += 1;
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Reconcile a bucket every `config.interval_secs` in the background
+///
+/// The first run happens one interval after the task is spawned.
+pub fn spawn(
+    bucket: String,
+    backend: Arc<dyn StorageBackend>,
+    registry: Arc<UploadRegistry>,
+    config: &MultipartReconciliationConfig,
+) -> JoinHandle<()> {
+    let period = Duration::from_secs(config.interval_secs);
+    let max_age = Duration::from_secs(config.max_age_secs);
+
+    tokio::spawn(async move {
+        let mut ticker = interval_at(Instant::now() + period, period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            match reconcile(backend.as_ref(), &registry, max_age).await {
+                Ok(report) => tracing::debug!(
+                    bucket = %bucket,
+                    listed = report.listed,
+                    aborted = report.aborted.len(),
+                    failed = report.failed,
+                    "Multipart reconciliation finished"
+                ),
+                Err(e) => tracing::warn!(
+                    bucket = %bucket,
+                    error = %e,
+                    "Multipart reconciliation failed"
+                ),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::upload::backend::StoredObject;
+    use crate::upload::multipart::CompletedPart;
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use std::sync::Mutex;
+
+    /// Backend holding a fixed set of pending uploads
+    struct Pending {
+        uploads: Vec<PendingUpload>,
+        aborted: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl StorageBackend for Pending {
+        fn bucket(&self) -> &str {
+            "b"
+        }
+
+        async fn put_object(
+            &self,
+            _key: &str,
+            _body: Bytes,
+            _content_type: Option<&str>,
+        ) -> Result<StoredObject, UploadError> {
+            unimplemented!()
+        }
+
+        async fn create_multipart_upload(&self, _key: &str) -> Result<String, UploadError> {
+            unimplemented!()
+        }
+
+        async fn upload_part(
+            &self,
+            _key: &str,
+            _upload_id: &str,
+            _part_number: u32,
+            _body: Bytes,
+        ) -> Result<StoredObject, UploadError> {
+            unimplemented!()
+        }
+
+        async fn complete_multipart_upload(
+            &self,
+            _key: &str,
+            _upload_id: &str,
+            _parts: &[CompletedPart],
+        ) -> Result<StoredObject, UploadError> {
+            unimplemented!()
+        }
+
+        async fn abort_multipart_upload(
+            &self,
+            _key: &str,
+            upload_id: &str,
+        ) -> Result<(), UploadError> {
+            self.aborted.lock().unwrap().push(upload_id.to_string());
+            Ok(())
+        }
+
+        async fn list_multipart_uploads(&self) -> Result<Vec<PendingUpload>, UploadError> {
+            Ok(self.uploads.clone())
+        }
+    }
+
+    fn pending(upload_id: &str, age_secs: Option<i64>) -> PendingUpload {
+        PendingUpload {
+            key: format!("{}.bin", upload_id),
+            upload_id: upload_id.to_string(),
+            initiated: age_secs.map(|age| Utc::now() - chrono::Duration::seconds(age)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_aborts_only_old_untracked_uploads() {
+        let backend = Pending {
+            uploads: vec![
+                pending("old", Some(7200)),
+                pending("tracked", Some(7200)),
+                pending("young", Some(60)),
+                pending("unknown", None),
+            ],
+            aborted: Mutex::new(Vec::new()),
+        };
+        let registry = UploadRegistry::new();
+        registry.register("b", "tracked.bin", "tracked");
+
+        let report = reconcile(&backend, &registry, Duration::from_secs(3600))
+            .await
+            .unwrap();
+
+        assert_eq!(report.listed, 4);
+        assert_eq!(report.tracked, 1);
+        assert_eq!(report.recent, 2);
+        assert_eq!(report.aborted.len(), 1);
+        assert_eq!(*backend.aborted.lock().unwrap(), vec!["old".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_aborts_nothing() {
+        let backend = Pending {
+            uploads: vec![pending("old", Some(7200)), pending("young", Some(60))],
+            aborted: Mutex::new(Vec::new()),
+        };
+
+        let report = sweep(
+            &backend,
+            &UploadRegistry::new(),
+            Duration::from_secs(3600),
+            true,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(report.aborted.len(), 1);
+        assert_eq!(report.aborted[0].upload_id, "old");
+        assert!(backend.aborted.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,278 @@
+//! Multi-region upload routing
+//!
+//! A bucket with `regions` stores each upload in one of several regional
+//! S3 targets: its own `s3` target (the home region) or one of
+//! `regions.targets`. [`RegionRouter`] picks the region for an upload
+//! either from the client's address, matched against each target's
+//! networks, or by the lowest moving average of observed upload latency.
+//! Objects are tagged with the name of the region they were stored in.
+//!
+//! With the latency strategy, regions nothing was uploaded to yet are tried
+//! first, so every region gets measured. Failed uploads count as
+//! [`FAILURE_PENALTY`], steering traffic away from a failing region.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use mizuchi_uploadr::config::RegionStrategy;
+//! use mizuchi_uploadr::upload::local::LocalFsBackend;
+//! use mizuchi_uploadr::upload::region::RegionRouter;
+//! use std::sync::Arc;
+//!
+//! # fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let router = RegionRouter::new("uploads", RegionStrategy::Cidr, "origin-region")
+//!     .region("us-east-1", Arc::new(LocalFsBackend::new("us", "/srv/us")?), &[])?
+//!     .region(
+//!         "eu-west-1",
+//!         Arc::new(LocalFsBackend::new("eu", "/srv/eu")?),
+//!         &["10.20.0.0/16".to_string()],
+//!     )?;
+//!
+//! let region = router.select(Some("10.20.1.2".parse()?));
+//! assert_eq!(region.name(), "eu-west-1");
+//! # Ok(())
+//! # }
+//! ```
+
+use super::backend::{s3_backend, StorageBackend};
+use super::UploadError;
+use crate::authz::claims::TAG_ENCODE_SET;
+use crate::config::{BucketConfig, RegionStrategy};
+use crate::metrics;
+use ipnet::IpNet;
+use percent_encoding::utf8_percent_encode;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Latency a failed upload counts as
+pub const FAILURE_PENALTY: Duration = Duration::from_secs(10);
+
+/// Weight of the newest sample in the moving average
+const SMOOTHING: f64 = 0.2;
+
+/// Regional storage target
+pub struct Region {
+    name: String,
+    backend: Arc<dyn StorageBackend>,
+    networks: Vec<IpNet>,
+    /// Moving average upload latency in microseconds, 0 until measured
+    latency_us: AtomicU64,
+}
+
+impl Region {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn backend(&self) -> &Arc<dyn StorageBackend> {
+        &self.backend
+    }
+
+    /// Moving average upload latency, if any upload was measured
+    pub fn latency(&self) -> Option<Duration> {
+        match self.latency_us.load(Ordering::Relaxed) {
+            0 => None,
+            us => Some(Duration::from_micros(us)),
+        }
+    }
+}
+
+/// Region selection of one bucket
+pub struct RegionRouter {
+    bucket: String,
+    strategy: RegionStrategy,
+    tag: String,
+    /// Home region first
+    regions: Vec<Region>,
+}
+
+impl RegionRouter {
+    /// Router without regions; the first region added is the home region
+    ///
+    /// # Arguments
+    ///
+    /// * `bucket` - Logical bucket name (metrics label)
+    /// * `strategy` - How uploads pick a region
+    /// * `tag` - Object tag holding the region name
+    pub fn new(bucket: &str, strategy: RegionStrategy, tag: &str) -> Self {
+        Self {
+            bucket: bucket.to_string(),
+            strategy,
+            tag: tag.to_string(),
+            regions: Vec::new(),
+        }
+    }
+
+    /// Add a region serving the clients of `cidrs`
+    pub fn region(
+        mut self,
+        name: &str,
+        backend: Arc<dyn StorageBackend>,
+        cidrs: &[String],
+    ) -> Result<Self, UploadError> {
+        let networks = cidrs
+            .iter()
+            .map(|cidr| {
+                cidr.parse().map_err(|_| {
+                    UploadError::S3Error(format!(
+                        "Bucket '{}' region '{}' has an invalid CIDR '{}'",
+                        self.bucket, name, cidr
+                    ))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        self.regions.push(Region {
+            name: name.to_string(),
+            backend,
+            networks,
+            latency_us: AtomicU64::new(0),
+        });
+        Ok(self)
+    }
+
+    /// Router of a bucket's `regions`, with `home` (the backend of its `s3`
+    /// target) as the home region
+    pub fn from_bucket_config(
+        bucket: &BucketConfig,
+        home: Arc<dyn StorageBackend>,
+    ) -> Result<Self, UploadError> {
+        let config = bucket.regions.as_ref().ok_or_else(|| {
+            UploadError::S3Error(format!("Bucket '{}' has no regions", bucket.name))
+        })?;
+        let mut router = Self::new(&bucket.name, config.strategy, &config.tag).region(
+            &bucket.s3.region,
+            home,
+            &[],
+        )?;
+        for target in &config.targets {
+            router = router.region(&target.name, s3_backend(&target.s3)?, &target.cidrs)?;
+        }
+        Ok(router)
+    }
+
+    /// Region an upload from `client` goes to
+    ///
+    /// # Panics
+    ///
+    /// If no region was added.
+    pub fn select(&self, client: Option<IpAddr>) -> &Region {
+        let home = &self.regions[0];
+        match self.strategy {
+            RegionStrategy::Cidr => client
+                .and_then(|ip| {
+                    self.regions[1..]
+                        .iter()
+                        .find(|region| region.networks.iter().any(|net| net.contains(&ip)))
+                })
+                .unwrap_or(home),
+            RegionStrategy::Latency => self
+                .regions
+                .iter()
+                .min_by_key(|region| region.latency_us.load(Ordering::Relaxed))
+                .unwrap_or(home),
+        }
+    }
+
+    /// Fold the latency of an upload to `region` into its moving average
+    pub fn observe(&self, region: &Region, latency: Duration, succeeded: bool) {
+        let latency = if succeeded {
+            latency
+        } else {
+            latency.max(FAILURE_PENALTY)
+        };
+        // At least 1µs, so a measured region never looks unmeasured
+        let sample = (latency.as_micros() as u64).max(1);
+        let mut average = sample;
+        let _ = region
+            .latency_us
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+                average = match current {
+                    0 => sample,
+                    current => {
+                        let next = current as f64 + SMOOTHING * (sample as f64 - current as f64);
+                        (next as u64).max(1)
+                    }
+                };
+                Some(average)
+            });
+        metrics::record_region_latency(&self.bucket, &region.name, average as f64 / 1e6);
+    }
+
+    /// `x-amz-tagging` pair naming `region`
+    pub fn tagging(&self, region: &Region) -> String {
+        format!(
+            "{}={}",
+            utf8_percent_encode(&self.tag, TAG_ENCODE_SET),
+            utf8_percent_encode(&region.name, TAG_ENCODE_SET)
+        )
+    }
+
+    /// Every region, home first
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::upload::local::LocalFsBackend;
+
+    fn router(strategy: RegionStrategy) -> RegionRouter {
+        let dir = std::env::temp_dir();
+        let backend = || Arc::new(LocalFsBackend::new("b", &dir).unwrap());
+        RegionRouter::new("b", strategy, "origin-region")
+            .region("home", backend(), &[])
+            .unwrap()
+            .region("eu", backend(), &["10.20.0.0/16".into()])
+            .unwrap()
+            .region("v6", backend(), &["2001:db8::/32".into()])
+            .unwrap()
+    }
+
+    #[test]
+    fn test_cidr_selection() {
+        let router = router(RegionStrategy::Cidr);
+
+        let select = |ip: &str| router.select(Some(ip.parse().unwrap())).name().to_string();
+
+        assert_eq!(select("10.20.3.4"), "eu");
+        assert_eq!(select("2001:db8::1"), "v6");
+        assert_eq!(select("192.168.1.1"), "home");
+        assert_eq!(router.select(None).name(), "home");
+    }
+
+    #[test]
+    fn test_latency_selection() {
+        let router = router(RegionStrategy::Latency);
+        let [home, eu, v6] = router.regions() else {
+            unreachable!()
+        };
+
+        // Unmeasured regions go first
+        assert_eq!(router.select(None).name(), "home");
+        router.observe(home, Duration::from_millis(300), true);
+        router.observe(eu, Duration::from_millis(100), true);
+        router.observe(v6, Duration::from_millis(200), true);
+        assert_eq!(router.select(None).name(), "eu");
+
+        // A failure makes a region look slow
+        router.observe(eu, Duration::from_millis(50), false);
+        assert_eq!(router.select(None).name(), "v6");
+        assert!(eu.latency().unwrap() > Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_tagging_and_invalid_cidr() {
+        let router = router(RegionStrategy::Cidr);
+        assert_eq!(router.tagging(&router.regions()[1]), "origin-region=eu");
+
+        let dir = std::env::temp_dir();
+        let backend = Arc::new(LocalFsBackend::new("b", &dir).unwrap());
+        assert!(RegionRouter::new("b", RegionStrategy::Cidr, "t")
+            .region("x", backend, &["10.0.0.0/33".into()])
+            .is_err());
+    }
+}
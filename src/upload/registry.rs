@@ -0,0 +1,105 @@
+//! Registry of multipart uploads in progress through this proxy
+//!
+//! [`MultipartHandler`](super::multipart::MultipartHandler) registers every
+//! upload it creates and forgets it once completed or aborted. Reconciliation
+//! uses the registry to tell uploads the proxy is still driving apart from
+//! ones abandoned on the backend.
+//!
+//! The registry is in memory: after a restart every upload left on the
+//! backend is unknown until it is
+//! [resumed](super::multipart::MultipartHandler::resume), and only the
+//! reconciliation age threshold protects uploads driven by other proxy
+//! instances.
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::Serialize;
+
+/// Multipart upload being driven by this proxy
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackedUpload {
+    pub bucket: String,
+    pub key: String,
+    pub upload_id: String,
+    pub started_at: DateTime<Utc>,
+}
+
+/// In-progress multipart uploads, keyed by bucket and upload ID
+#[derive(Debug, Default)]
+pub struct UploadRegistry {
+    uploads: DashMap<(String, String), TrackedUpload>,
+}
+
+impl UploadRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an upload started in `bucket`
+    pub fn register(&self, bucket: &str, key: &str, upload_id: &str) {
+        self.uploads.insert(
+            (bucket.to_string(), upload_id.to_string()),
+            TrackedUpload {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+                upload_id: upload_id.to_string(),
+                started_at: Utc::now(),
+            },
+        );
+    }
+
+    /// Forget a completed or aborted upload
+    pub fn unregister(&self, bucket: &str, upload_id: &str) -> Option<TrackedUpload> {
+        self.uploads
+            .remove(&(bucket.to_string(), upload_id.to_string()))
+            .map(|(_, upload)| upload)
+    }
+
+    /// Whether the upload is in progress through this proxy
+    pub fn contains(&self, bucket: &str, upload_id: &str) -> bool {
+        self.uploads
+            .contains_key(&(bucket.to_string(), upload_id.to_string()))
+    }
+
+    /// Uploads in progress in `bucket`
+    pub fn list(&self, bucket: &str) -> Vec<TrackedUpload> {
+        self.uploads
+            .iter()
+            .filter(|entry| entry.bucket == bucket)
+            .map(|entry| entry.value().clone())
+            .collect()
+    }
+
+    /// Number of uploads in progress
+    pub fn len(&self) -> usize {
+        self.uploads.len()
+    }
+
+    /// Whether no upload is in progress
+    pub fn is_empty(&self) -> bool {
+        self.uploads.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_and_unregister() {
+        let registry = UploadRegistry::new();
+        registry.register("a", "k1", "u1");
+        registry.register("b", "k2", "u1");
+
+        assert!(registry.contains("a", "u1"));
+        assert!(!registry.contains("a", "u2"));
+        assert_eq!(registry.list("b").len(), 1);
+
+        let upload = registry.unregister("a", "u1").unwrap();
+        assert_eq!(upload.key, "k1");
+        assert!(!registry.contains("a", "u1"));
+        assert!(registry.contains("b", "u1"));
+        assert_eq!(registry.len(), 1);
+    }
+}
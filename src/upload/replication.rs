@@ -0,0 +1,604 @@
+//! Replication to a secondary bucket
+//!
+//! [`ReplicatedBackend`] wraps a primary [`StorageBackend`] and mirrors every
+//! object to a secondary one.
+//!
+//! # Modes
+//!
+//! - **Sync** - Both writes run concurrently and the upload fails if either
+//!   fails. The primary may still hold the object in that case.
+//! - **Async** - The upload returns after the primary write. Replica writes go
+//!   through a bounded queue drained by a background worker, with retries.
+//!   Objects that cannot be replicated (queue full or retries exhausted) are
+//!   appended to a dead-letter file as JSON lines for later reconciliation.
+//!
+//! Multipart uploads are mirrored part by part in both modes, since part
+//! bodies are not kept around until completion. In async mode a failure on
+//! the secondary only marks the session as broken; the object is dead-lettered
+//! when the primary upload completes.
+//!
+//! Conditional writes (`if-match`, `if-none-match`) are decided by the
+//! primary alone: the replica write only happens once the primary accepted
+//! the object, and carries no conditions.
+
+use super::backend::{StorageBackend, StoredObject, CONDITIONAL_HEADERS};
+use super::multipart::{CompletedPart, PendingUpload};
+use super::UploadError;
+use crate::config::ReplicationMode;
+use crate::metrics;
+use crate::s3::CircuitState;
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, Mutex};
+
+/// Delay before the first retry of an async replica write
+const INITIAL_RETRY_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Record of an object that failed to replicate
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeadLetterRecord {
+    pub bucket: String,
+    pub key: String,
+    pub size: u64,
+    pub error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// Append-only JSON lines file of failed replications
+#[derive(Debug)]
+pub struct DeadLetterLog {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl DeadLetterLog {
+    /// Create a log appending to `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Append a record
+    ///
+    /// Errors writing the log are logged rather than returned: there is
+    /// nowhere left to report them.
+    pub async fn append(&self, record: &DeadLetterRecord) {
+        let _guard = self.lock.lock().await;
+        let result = async {
+            let mut line = serde_json::to_vec(record).map_err(std::io::Error::other)?;
+            line.push(b'\n');
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await?;
+            file.write_all(&line).await?;
+            file.flush().await
+        }
+        .await;
+
+        if let Err(e) = result {
+            tracing::error!(
+                path = %self.path.display(),
+                key = %record.key,
+                error = %e,
+                "Failed to write replication dead-letter record"
+            );
+        }
+    }
+}
+
+/// Object waiting to be written to the secondary
+struct ReplicationJob {
+    key: String,
+    body: Bytes,
+    content_type: Option<String>,
+    headers: Vec<(String, String)>,
+    enqueued_at: Instant,
+}
+
+/// Secondary side of a multipart upload
+#[derive(Default)]
+struct SecondarySession {
+    upload_id: Option<String>,
+    parts: Vec<CompletedPart>,
+    /// First error seen on the secondary (async mode only)
+    error: Option<String>,
+}
+
+/// Storage backend that mirrors writes to a secondary backend
+pub struct ReplicatedBackend {
+    primary: Arc<dyn StorageBackend>,
+    secondary: Arc<dyn StorageBackend>,
+    mode: ReplicationMode,
+    queue: Option<mpsc::Sender<ReplicationJob>>,
+    dead_letter: Option<Arc<DeadLetterLog>>,
+    /// Secondary multipart sessions keyed by primary upload ID
+    sessions: DashMap<String, SecondarySession>,
+}
+
+impl ReplicatedBackend {
+    /// Create a synchronously replicated backend
+    pub fn sync(primary: Arc<dyn StorageBackend>, secondary: Arc<dyn StorageBackend>) -> Self {
+        Self {
+            primary,
+            secondary,
+            mode: ReplicationMode::Sync,
+            queue: None,
+            dead_letter: None,
+            sessions: DashMap::new(),
+        }
+    }
+
+    /// Create an asynchronously replicated backend and spawn its worker
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn asynchronous(
+        primary: Arc<dyn StorageBackend>,
+        secondary: Arc<dyn StorageBackend>,
+        queue_capacity: usize,
+        max_retries: u32,
+        dead_letter: Arc<DeadLetterLog>,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel(queue_capacity.max(1));
+        tokio::spawn(run_worker(
+            Arc::clone(&secondary),
+            primary.bucket().to_string(),
+            max_retries,
+            Arc::clone(&dead_letter),
+            receiver,
+        ));
+
+        Self {
+            primary,
+            secondary,
+            mode: ReplicationMode::Async,
+            queue: Some(sender),
+            dead_letter: Some(dead_letter),
+            sessions: DashMap::new(),
+        }
+    }
+
+    /// Replication mode
+    pub fn mode(&self) -> ReplicationMode {
+        self.mode
+    }
+
+    async fn dead_letter(&self, key: &str, size: u64, reason: &str, error: String) {
+        metrics::record_replication_failure(self.primary.bucket(), reason);
+        if let Some(log) = &self.dead_letter {
+            log.append(&DeadLetterRecord {
+                bucket: self.primary.bucket().to_string(),
+                key: key.to_string(),
+                size,
+                error,
+                failed_at: Utc::now(),
+            })
+            .await;
+        }
+    }
+
+    /// Handle a secondary failure according to the replication mode
+    ///
+    /// Returns the error in sync mode; in async mode records it on the
+    /// session so completion can dead-letter the object.
+    fn secondary_failed(&self, upload_id: &str, error: UploadError) -> Result<(), UploadError> {
+        tracing::warn!(upload_id = %upload_id, error = %error, "Replica multipart operation failed");
+        match self.mode {
+            ReplicationMode::Sync => {
+                metrics::record_replication_failure(self.primary.bucket(), "error");
+                Err(replica_error(error))
+            }
+            ReplicationMode::Async => {
+                if let Some(mut session) = self.sessions.get_mut(upload_id) {
+                    session.error.get_or_insert_with(|| error.to_string());
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn replica_error(error: UploadError) -> UploadError {
+    UploadError::S3Error(format!("Replication failed: {}", error))
+}
+
+/// Whether `headers` make a write conditional
+fn is_conditional(headers: &[(String, String)]) -> bool {
+    headers
+        .iter()
+        .any(|(name, _)| CONDITIONAL_HEADERS.contains(&name.as_str()))
+}
+
+/// `headers` without the conditions, which only apply to the primary
+fn replica_headers(headers: &[(String, String)]) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter(|(name, _)| !CONDITIONAL_HEADERS.contains(&name.as_str()))
+        .cloned()
+        .collect()
+}
+
+#[async_trait]
+impl StorageBackend for ReplicatedBackend {
+    fn bucket(&self) -> &str {
+        self.primary.bucket()
+    }
+
+    fn circuit_state(&self) -> Option<CircuitState> {
+        self.primary.circuit_state()
+    }
+
+    async fn put_object(
+        &self,
+        key: &str,
+        body: Bytes,
+        content_type: Option<&str>,
+    ) -> Result<StoredObject, UploadError> {
+        self.put_object_with_headers(key, body, content_type, &[])
+            .await
+    }
+
+    async fn put_object_with_headers(
+        &self,
+        key: &str,
+        body: Bytes,
+        content_type: Option<&str>,
+        headers: &[(String, String)],
+    ) -> Result<StoredObject, UploadError> {
+        match self.mode {
+            ReplicationMode::Sync => {
+                let started = Instant::now();
+                let replica_headers = replica_headers(headers);
+                let (primary, secondary) = if is_conditional(headers) {
+                    // The replica must not be written if the condition fails
+                    let object = self
+                        .primary
+                        .put_object_with_headers(key, body.clone(), content_type, headers)
+                        .await?;
+                    let secondary = self
+                        .secondary
+                        .put_object_with_headers(key, body, content_type, &replica_headers)
+                        .await;
+                    (Ok(object), secondary)
+                } else {
+                    tokio::join!(
+                        self.primary.put_object_with_headers(
+                            key,
+                            body.clone(),
+                            content_type,
+                            headers
+                        ),
+                        self.secondary.put_object_with_headers(
+                            key,
+                            body,
+                            content_type,
+                            &replica_headers
+                        )
+                    )
+                };
+                let object = primary?;
+                if let Err(e) = secondary {
+                    metrics::record_replication_failure(self.primary.bucket(), "error");
+                    return Err(replica_error(e));
+                }
+                metrics::record_replication_lag(
+                    self.primary.bucket(),
+                    started.elapsed().as_secs_f64(),
+                );
+                Ok(object)
+            }
+            ReplicationMode::Async => {
+                let object = self
+                    .primary
+                    .put_object_with_headers(key, body.clone(), content_type, headers)
+                    .await?;
+
+                let size = body.len() as u64;
+                let job = ReplicationJob {
+                    key: key.to_string(),
+                    body,
+                    content_type: content_type.map(|s| s.to_string()),
+                    headers: replica_headers(headers),
+                    enqueued_at: Instant::now(),
+                };
+                let queued = self
+                    .queue
+                    .as_ref()
+                    .map(|queue| queue.try_send(job).is_ok())
+                    .unwrap_or(false);
+
+                if !queued {
+                    tracing::warn!(key = %key, "Replication queue full, dead-lettering object");
+                    self.dead_letter(key, size, "queue_full", "Replication queue full".into())
+                        .await;
+                }
+
+                Ok(object)
+            }
+        }
+    }
+
+    async fn create_multipart_upload(&self, key: &str) -> Result<String, UploadError> {
+        self.create_multipart_upload_with_headers(key, &[]).await
+    }
+
+    async fn create_multipart_upload_with_headers(
+        &self,
+        key: &str,
+        headers: &[(String, String)],
+    ) -> Result<String, UploadError> {
+        let upload_id = self
+            .primary
+            .create_multipart_upload_with_headers(key, headers)
+            .await?;
+        self.sessions
+            .insert(upload_id.clone(), SecondarySession::default());
+
+        match self
+            .secondary
+            .create_multipart_upload_with_headers(key, headers)
+            .await
+        {
+            Ok(secondary_id) => {
+                if let Some(mut session) = self.sessions.get_mut(&upload_id) {
+                    session.upload_id = Some(secondary_id);
+                }
+            }
+            Err(e) => {
+                if let Err(e) = self.secondary_failed(&upload_id, e) {
+                    self.sessions.remove(&upload_id);
+                    let _ = self.primary.abort_multipart_upload(key, &upload_id).await;
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(upload_id)
+    }
+
+    async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: u32,
+        body: Bytes,
+    ) -> Result<StoredObject, UploadError> {
+        let secondary_id = self
+            .sessions
+            .get(upload_id)
+            .filter(|session| session.error.is_none())
+            .and_then(|session| session.upload_id.clone());
+
+        let Some(secondary_id) = secondary_id else {
+            // Secondary session already broken (async) or unknown
+            return self
+                .primary
+                .upload_part(key, upload_id, part_number, body)
+                .await;
+        };
+
+        let (primary, secondary) = tokio::join!(
+            self.primary
+                .upload_part(key, upload_id, part_number, body.clone()),
+            self.secondary
+                .upload_part(key, &secondary_id, part_number, body)
+        );
+        let part = primary?;
+
+        match secondary {
+            Ok(secondary_part) => {
+                if let Some(mut session) = self.sessions.get_mut(upload_id) {
+                    session.parts.retain(|p| p.part_number != part_number);
+                    session
+                        .parts
+                        .push(CompletedPart::stored(part_number, &secondary_part));
+                }
+            }
+            Err(e) => self.secondary_failed(upload_id, e)?,
+        }
+
+        Ok(part)
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[CompletedPart],
+    ) -> Result<StoredObject, UploadError> {
+        self.complete_multipart_upload_with_headers(key, upload_id, parts, &[])
+            .await
+    }
+
+    async fn complete_multipart_upload_with_headers(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[CompletedPart],
+        headers: &[(String, String)],
+    ) -> Result<StoredObject, UploadError> {
+        let started = Instant::now();
+        let object = self
+            .primary
+            .complete_multipart_upload_with_headers(key, upload_id, parts, headers)
+            .await?;
+
+        let Some((_, mut session)) = self.sessions.remove(upload_id) else {
+            return Ok(object);
+        };
+
+        let result = match (&session.upload_id, session.error.take()) {
+            (Some(secondary_id), None) => {
+                // Complete the replica with the same parts, in the same order
+                let secondary_parts: Option<Vec<CompletedPart>> = parts
+                    .iter()
+                    .map(|part| {
+                        session
+                            .parts
+                            .iter()
+                            .find(|p| p.part_number == part.part_number)
+                            .cloned()
+                    })
+                    .collect();
+
+                match secondary_parts {
+                    Some(secondary_parts) => self
+                        .secondary
+                        .complete_multipart_upload(key, secondary_id, &secondary_parts)
+                        .await
+                        .map(|_| ()),
+                    None => {
+                        let _ = self
+                            .secondary
+                            .abort_multipart_upload(key, secondary_id)
+                            .await;
+                        Err(UploadError::MultipartError(
+                            "Replica is missing parts".into(),
+                        ))
+                    }
+                }
+            }
+            (Some(secondary_id), Some(error)) => {
+                let _ = self
+                    .secondary
+                    .abort_multipart_upload(key, secondary_id)
+                    .await;
+                Err(UploadError::MultipartError(error))
+            }
+            (None, error) => Err(UploadError::MultipartError(
+                error.unwrap_or_else(|| "Replica upload was not started".into()),
+            )),
+        };
+
+        match result {
+            Ok(()) => {
+                metrics::record_replication_lag(
+                    self.primary.bucket(),
+                    started.elapsed().as_secs_f64(),
+                );
+                Ok(object)
+            }
+            Err(e) => match self.mode {
+                ReplicationMode::Sync => {
+                    metrics::record_replication_failure(self.primary.bucket(), "error");
+                    Err(replica_error(e))
+                }
+                ReplicationMode::Async => {
+                    // Object size is not tracked across multipart sessions
+                    self.dead_letter(key, 0, "error", e.to_string()).await;
+                    Ok(object)
+                }
+            },
+        }
+    }
+
+    async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> Result<(), UploadError> {
+        if let Some((_, session)) = self.sessions.remove(upload_id) {
+            if let Some(secondary_id) = session.upload_id {
+                if let Err(e) = self
+                    .secondary
+                    .abort_multipart_upload(key, &secondary_id)
+                    .await
+                {
+                    tracing::warn!(upload_id = %upload_id, error = %e, "Failed to abort replica multipart upload");
+                }
+            }
+        }
+
+        self.primary.abort_multipart_upload(key, upload_id).await
+    }
+
+    async fn list_multipart_uploads(&self) -> Result<Vec<PendingUpload>, UploadError> {
+        self.primary.list_multipart_uploads().await
+    }
+}
+
+/// Drain the async queue, retrying each replica write with backoff
+async fn run_worker(
+    secondary: Arc<dyn StorageBackend>,
+    bucket: String,
+    max_retries: u32,
+    dead_letter: Arc<DeadLetterLog>,
+    mut receiver: mpsc::Receiver<ReplicationJob>,
+) {
+    while let Some(job) = receiver.recv().await {
+        let mut last_error = None;
+        for attempt in 0..=max_retries {
+            if attempt > 0 {
+                tokio::time::sleep(INITIAL_RETRY_BACKOFF * (1u32 << (attempt - 1).min(10))).await;
+            }
+
+            match secondary
+                .put_object_with_headers(
+                    &job.key,
+                    job.body.clone(),
+                    job.content_type.as_deref(),
+                    &job.headers,
+                )
+                .await
+            {
+                Ok(_) => {
+                    last_error = None;
+                    break;
+                }
+                Err(e) => {
+                    tracing::debug!(key = %job.key, attempt = attempt + 1, error = %e, "Replica write failed");
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        match last_error {
+            None => {
+                metrics::record_replication_lag(&bucket, job.enqueued_at.elapsed().as_secs_f64());
+            }
+            Some(e) => {
+                tracing::warn!(key = %job.key, error = %e, "Replication failed, dead-lettering object");
+                metrics::record_replication_failure(&bucket, "error");
+                dead_letter
+                    .append(&DeadLetterRecord {
+                        bucket: bucket.clone(),
+                        key: job.key.clone(),
+                        size: job.body.len() as u64,
+                        error: e.to_string(),
+                        failed_at: Utc::now(),
+                    })
+                    .await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::upload::local::LocalFsBackend;
+
+    #[tokio::test]
+    async fn test_sync_put_writes_both() {
+        let primary_dir = tempfile::tempdir().unwrap();
+        let secondary_dir = tempfile::tempdir().unwrap();
+        let backend = ReplicatedBackend::sync(
+            Arc::new(LocalFsBackend::new("bucket", primary_dir.path()).unwrap()),
+            Arc::new(LocalFsBackend::new("replica", secondary_dir.path()).unwrap()),
+        );
+
+        backend
+            .put_object("a.txt", Bytes::from("data"), None)
+            .await
+            .unwrap();
+
+        assert!(primary_dir.path().join("a.txt").exists());
+        assert!(secondary_dir.path().join("a.txt").exists());
+    }
+}
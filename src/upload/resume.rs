@@ -0,0 +1,430 @@
+//! Multipart upload state that survives restarts
+//!
+//! A [`MultipartHandler`](super::multipart::MultipartHandler) keeps the
+//! parts of an upload in its [`MultipartUpload`](super::multipart::MultipartUpload),
+//! so a restart in the middle of an upload would lose them and the upload
+//! could only be abandoned. With a [`ResumeStore`] attached, the handler
+//! saves a [`ResumeState`] when the upload is created and after every part,
+//! and [`MultipartHandler::resume`](super::multipart::MultipartHandler::resume)
+//! picks the upload back up by its upload ID, still able to validate and
+//! complete it.
+//!
+//! A part is only acknowledged once its state is saved, so every part a
+//! client was told about is in the store. [`FileResumeStore`] keeps one file
+//! per upload, replaced atomically and synced to disk. [`SharedResumeStore`]
+//! keeps uploads in a shared [`Store`] such as Redis, so another instance
+//! can resume them too.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use mizuchi_uploadr::upload::local::LocalFsBackend;
+//! use mizuchi_uploadr::upload::multipart::MultipartHandler;
+//! use mizuchi_uploadr::upload::resume::FileResumeStore;
+//! use bytes::Bytes;
+//! use std::sync::Arc;
+//!
+//! # async fn example(upload_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+//! let backend = Arc::new(LocalFsBackend::new("uploads", "/var/lib/mizuchi/uploads")?);
+//! let store = Arc::new(FileResumeStore::open("/var/lib/mizuchi/multipart")?);
+//! let handler = MultipartHandler::with_backend(backend).with_resume_store(store);
+//!
+//! // After a restart, continue where the client left off
+//! let mut upload = handler.resume("uploads", upload_id).await?;
+//! let next = upload.parts.len() as u32 + 1;
+//! handler.upload_part(&mut upload, next, Bytes::from("last part")).await?;
+//! handler.complete(&upload).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use super::multipart::{CompletedPart, MultipartUpload};
+use super::UploadError;
+use crate::s3::hashing;
+use crate::state::Store;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Prefix of upload state in a shared store
+const SHARED_KEY_PREFIX: &str = "multipart:";
+
+/// How long a shared store keeps the state of an upload without progress
+///
+/// S3 lifecycle rules commonly abort incomplete uploads after a week.
+pub const SHARED_STATE_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Part of a resumable upload
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartRecord {
+    pub part_number: u32,
+    pub etag: String,
+    pub size: usize,
+    /// Hex SHA-256 of the part's body
+    pub sha256: String,
+    /// Base64 CRC32C the part was stored with, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crc32c: Option<String>,
+}
+
+/// Saved state of a multipart upload
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumeState {
+    pub bucket: String,
+    pub key: String,
+    pub upload_id: String,
+    /// When the state was last saved
+    pub updated_at: DateTime<Utc>,
+    /// Uploaded parts in part number order
+    pub parts: Vec<PartRecord>,
+}
+
+impl ResumeState {
+    /// Current state of `upload`
+    pub fn of(upload: &MultipartUpload) -> Self {
+        let parts = upload
+            .sorted_parts()
+            .into_iter()
+            .map(|part| PartRecord {
+                size: upload
+                    .part_sizes
+                    .get(&part.part_number)
+                    .copied()
+                    .unwrap_or_default(),
+                sha256: upload
+                    .part_hashes
+                    .get(&part.part_number)
+                    .cloned()
+                    .unwrap_or_default(),
+                part_number: part.part_number,
+                etag: part.etag,
+                crc32c: part.checksum_crc32c,
+            })
+            .collect();
+        Self {
+            bucket: upload.bucket.clone(),
+            key: upload.key.clone(),
+            upload_id: upload.upload_id.clone(),
+            updated_at: Utc::now(),
+            parts,
+        }
+    }
+
+    /// The upload this state was saved from
+    pub fn into_upload(self) -> MultipartUpload {
+        let mut upload = MultipartUpload {
+            upload_id: self.upload_id,
+            bucket: self.bucket,
+            key: self.key,
+            parts: Vec::with_capacity(self.parts.len()),
+            part_sizes: HashMap::new(),
+            part_hashes: HashMap::new(),
+        };
+        for part in self.parts {
+            upload.part_sizes.insert(part.part_number, part.size);
+            upload.part_hashes.insert(part.part_number, part.sha256);
+            upload.parts.push(CompletedPart {
+                part_number: part.part_number,
+                etag: part.etag,
+                checksum_crc32c: part.crc32c,
+            });
+        }
+        upload
+    }
+}
+
+/// Hex SHA-256 of a part body
+pub fn part_hash(body: &[u8]) -> String {
+    hashing::sha256_hex(body)
+}
+
+/// Durable store of multipart upload state
+#[async_trait]
+pub trait ResumeStore: Send + Sync {
+    /// Save `state`, replacing any earlier state of the upload
+    ///
+    /// Returns once the state is durable.
+    async fn save(&self, state: &ResumeState) -> Result<(), UploadError>;
+
+    /// State of an upload, if saved
+    async fn load(&self, bucket: &str, upload_id: &str)
+        -> Result<Option<ResumeState>, UploadError>;
+
+    /// Forget a completed or aborted upload
+    async fn remove(&self, bucket: &str, upload_id: &str) -> Result<(), UploadError>;
+}
+
+/// Upload state kept in a directory, one file per upload
+///
+/// Files are written to a temporary name, synced, and renamed over the
+/// previous state, so a crash leaves either the old or the new state.
+#[derive(Debug)]
+pub struct FileResumeStore {
+    dir: PathBuf,
+}
+
+impl FileResumeStore {
+    /// Open the store in `dir`, creating it if missing
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, UploadError> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Directory the state files are in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Every saved upload, skipping unreadable files
+    pub fn list(&self) -> Result<Vec<ResumeState>, UploadError> {
+        let mut states = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            match read_state(&path) {
+                Ok(Some(state)) => states.push(state),
+                Ok(None) => {}
+                Err(e) => tracing::warn!(
+                    path = %path.display(),
+                    error = %e,
+                    "Skipping unreadable multipart upload state"
+                ),
+            }
+        }
+        Ok(states)
+    }
+
+    /// File of an upload; upload IDs are hashed as they may hold any
+    /// character
+    fn path(&self, bucket: &str, upload_id: &str) -> PathBuf {
+        let name = Sha256::new()
+            .chain_update(bucket)
+            .chain_update([0])
+            .chain_update(upload_id)
+            .finalize();
+        self.dir.join(format!("{}.json", hex::encode(name)))
+    }
+}
+
+fn read_state(path: &Path) -> Result<Option<ResumeState>, UploadError> {
+    match std::fs::read(path) {
+        Ok(content) => serde_json::from_slice(&content).map(Some).map_err(|e| {
+            UploadError::MultipartError(format!(
+                "Invalid multipart upload state {}: {}",
+                path.display(),
+                e
+            ))
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Replace `path` with `content`, durably
+fn write_atomic(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let tmp = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+    let result = (|| {
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result?;
+    // Make the rename itself durable
+    if let Some(dir) = path.parent() {
+        std::fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl ResumeStore for FileResumeStore {
+    async fn save(&self, state: &ResumeState) -> Result<(), UploadError> {
+        let path = self.path(&state.bucket, &state.upload_id);
+        let content =
+            serde_json::to_vec(state).map_err(|e| UploadError::MultipartError(e.to_string()))?;
+        tokio::task::spawn_blocking(move || write_atomic(&path, &content))
+            .await
+            .map_err(|e| std::io::Error::other(format!("state writer panicked: {}", e)))??;
+        Ok(())
+    }
+
+    async fn load(
+        &self,
+        bucket: &str,
+        upload_id: &str,
+    ) -> Result<Option<ResumeState>, UploadError> {
+        let path = self.path(bucket, upload_id);
+        tokio::task::spawn_blocking(move || read_state(&path))
+            .await
+            .map_err(|e| std::io::Error::other(format!("state reader panicked: {}", e)))?
+    }
+
+    async fn remove(&self, bucket: &str, upload_id: &str) -> Result<(), UploadError> {
+        match tokio::fs::remove_file(self.path(bucket, upload_id)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Upload state kept in a [`Store`], shared between instances when the
+/// store is
+///
+/// Each save replaces the whole state with a single write, and state left
+/// without progress expires after [`SHARED_STATE_TTL`].
+pub struct SharedResumeStore {
+    store: Arc<dyn Store>,
+    key_prefix: String,
+}
+
+impl SharedResumeStore {
+    pub fn new(store: Arc<dyn Store>) -> Self {
+        Self {
+            store,
+            key_prefix: SHARED_KEY_PREFIX.to_string(),
+        }
+    }
+
+    fn key(&self, bucket: &str, upload_id: &str) -> String {
+        format!("{}{}:{}", self.key_prefix, bucket, upload_id)
+    }
+}
+
+fn store_error(error: crate::state::StateError) -> UploadError {
+    UploadError::MultipartError(format!("Multipart upload state unavailable: {}", error))
+}
+
+#[async_trait]
+impl ResumeStore for SharedResumeStore {
+    async fn save(&self, state: &ResumeState) -> Result<(), UploadError> {
+        let content =
+            serde_json::to_vec(state).map_err(|e| UploadError::MultipartError(e.to_string()))?;
+        self.store
+            .set(
+                &self.key(&state.bucket, &state.upload_id),
+                &content,
+                Some(SHARED_STATE_TTL),
+            )
+            .await
+            .map_err(store_error)
+    }
+
+    async fn load(
+        &self,
+        bucket: &str,
+        upload_id: &str,
+    ) -> Result<Option<ResumeState>, UploadError> {
+        let Some(content) = self
+            .store
+            .get(&self.key(bucket, upload_id))
+            .await
+            .map_err(store_error)?
+        else {
+            return Ok(None);
+        };
+        serde_json::from_slice(&content).map(Some).map_err(|e| {
+            UploadError::MultipartError(format!(
+                "Invalid multipart upload state of '{}': {}",
+                upload_id, e
+            ))
+        })
+    }
+
+    async fn remove(&self, bucket: &str, upload_id: &str) -> Result<(), UploadError> {
+        self.store
+            .delete(&self.key(bucket, upload_id))
+            .await
+            .map_err(store_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::MemoryStore;
+
+    fn state(upload_id: &str) -> ResumeState {
+        ResumeState {
+            bucket: "uploads".into(),
+            key: "video.mp4".into(),
+            upload_id: upload_id.into(),
+            updated_at: Utc::now(),
+            parts: vec![PartRecord {
+                part_number: 1,
+                etag: "\"e1\"".into(),
+                size: 5,
+                sha256: part_hash(b"hello"),
+                crc32c: Some("mnG7TA==".into()),
+            }],
+        }
+    }
+
+    async fn round_trip(store: &dyn ResumeStore) {
+        // Upload IDs may hold characters that aren't safe in file names
+        let saved = state("a/b+c==");
+        store.save(&saved).await.unwrap();
+        assert_eq!(
+            store.load("uploads", "a/b+c==").await.unwrap(),
+            Some(saved.clone())
+        );
+        assert_eq!(store.load("other", "a/b+c==").await.unwrap(), None);
+
+        let mut updated = saved;
+        updated.parts[0].etag = "\"e2\"".into();
+        store.save(&updated).await.unwrap();
+        assert_eq!(
+            store.load("uploads", "a/b+c==").await.unwrap(),
+            Some(updated)
+        );
+
+        store.remove("uploads", "a/b+c==").await.unwrap();
+        store.remove("uploads", "a/b+c==").await.unwrap();
+        assert_eq!(store.load("uploads", "a/b+c==").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_file_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileResumeStore::open(dir.path().join("state")).unwrap();
+        round_trip(&store).await;
+
+        store.save(&state("u1")).await.unwrap();
+        std::fs::write(store.dir().join("broken.json"), b"{").unwrap();
+        let listed = store.list().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].upload_id, "u1");
+        // No temporary files are left behind
+        assert_eq!(std::fs::read_dir(store.dir()).unwrap().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_shared_store() {
+        let store = SharedResumeStore::new(Arc::new(MemoryStore::new()));
+        round_trip(&store).await;
+    }
+
+    #[test]
+    fn test_state_round_trips_upload() {
+        let state = state("u1");
+        let upload = state.clone().into_upload();
+        assert_eq!(upload.part_sizes[&1], 5);
+        assert_eq!(upload.part_hashes[&1], part_hash(b"hello"));
+        let saved = ResumeState::of(&upload);
+        assert_eq!(saved.parts, state.parts);
+        assert_eq!(saved.upload_id, "u1");
+    }
+}
@@ -0,0 +1,616 @@
+//! Disk-backed spill buffer for request bodies
+//!
+//! The S3 client signs the SHA256 of the whole payload before sending it, so
+//! a body has to be fully received before it can be uploaded. Bodies without
+//! a `Content-Length`, or too large to hold in memory, are written to a spill
+//! file under the configured directory instead (see
+//! [`SpillConfig`](crate::config::SpillConfig)), then uploaded from there.
+//!
+//! # Disk Usage
+//!
+//! Every [`SpillBuffer`] enforces its own `max_disk_bytes` quota over the
+//! spill files it holds at once. A body that would exceed the quota is
+//! rejected with [`SpillError::QuotaExceeded`] and its partial file deleted.
+//!
+//! # Cleanup
+//!
+//! Spill files are deleted when the upload finishes or fails. Files left
+//! behind by a crash are deleted by [`SpillBuffer::new`] at startup, so the
+//! spill directory must not be shared with another running process.
+//!
+//! File I/O runs on the blocking thread pool; only [`Bytes`] cross between
+//! it and the request task.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use mizuchi_uploadr::config::{SpillConfig, UploadConfig};
+//! use mizuchi_uploadr::upload::local::LocalFsBackend;
+//! use mizuchi_uploadr::upload::spill::SpillBuffer;
+//! use bytes::Bytes;
+//! use http_body_util::Full;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let buffer = SpillBuffer::new("uploads", &SpillConfig::default())?;
+//! let backend = LocalFsBackend::new("uploads", "/var/lib/mizuchi/uploads")?;
+//!
+//! let spilled = buffer.spill(Full::new(Bytes::from("Hello"))).await?;
+//! let object = spilled
+//!     .upload(&backend, "hello.txt", Some("text/plain"), &UploadConfig::default())
+//!     .await?;
+//! println!("Stored with ETag: {}", object.etag);
+//! # Ok(())
+//! # }
+//! ```
+
+use super::backend::{StorageBackend, StoredObject, CONDITIONAL_HEADERS};
+use super::multipart::{part_size_for, validate_part_number, CompletedPart, MAX_PUT_SIZE};
+use super::progress::SessionHandle;
+use super::spool_crypto::{EncryptingWriter, SpoolKey, IV_LEN};
+use super::temp_file::TempFileUpload;
+use super::UploadError;
+use crate::config::{SpillConfig, UploadConfig};
+use crate::error::ErrorContext;
+use crate::metrics;
+use crate::profiling::profile_span;
+use crate::s3::hashing::Sha256Hasher;
+use bytes::Bytes;
+use http_body_util::BodyExt;
+use hyper::body::Body;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+/// File name prefix of spill files, used to find stale ones at startup
+const SPILL_PREFIX: &str = "mizuchi-spill-";
+
+/// Body frames queued between the request task and the spill writer
+const WRITE_QUEUE_DEPTH: usize = 16;
+
+/// Spill errors
+#[derive(Error, Debug)]
+pub enum SpillError {
+    #[error("Spill quota of {max_bytes} bytes exceeded")]
+    QuotaExceeded { max_bytes: u64 },
+
+    #[error("Failed to read body: {0}")]
+    Body(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("Spill IO error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Bytes spilled by one buffer, bounded by its quota
+#[derive(Debug)]
+struct Quota {
+    bucket: String,
+    max_bytes: u64,
+    used: AtomicU64,
+}
+
+impl Quota {
+    /// Account for `bytes` more, unless that would exceed the quota
+    fn reserve(&self, bytes: u64) -> bool {
+        let previous = self.used.fetch_add(bytes, Ordering::AcqRel);
+        if previous + bytes > self.max_bytes {
+            self.used.fetch_sub(bytes, Ordering::AcqRel);
+            return false;
+        }
+        metrics::record_spill_bytes(&self.bucket, previous + bytes);
+        true
+    }
+
+    fn release(&self, bytes: u64) {
+        let previous = self.used.fetch_sub(bytes, Ordering::AcqRel);
+        metrics::record_spill_bytes(&self.bucket, previous - bytes);
+    }
+}
+
+/// Share of the quota held by one spill file, released on drop
+#[derive(Debug)]
+struct Reservation {
+    quota: Arc<Quota>,
+    bytes: u64,
+}
+
+impl Reservation {
+    fn grow(&mut self, bytes: u64) -> bool {
+        if !self.quota.reserve(bytes) {
+            return false;
+        }
+        self.bytes += bytes;
+        true
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.quota.release(self.bytes);
+    }
+}
+
+/// Spill buffer of one bucket
+#[derive(Debug)]
+pub struct SpillBuffer {
+    dir: PathBuf,
+    memory_limit: u64,
+    encrypt: bool,
+    quota: Arc<Quota>,
+}
+
+impl SpillBuffer {
+    /// Create the spill buffer described by `config`
+    ///
+    /// The directory is created if it does not exist, and spill files left
+    /// in it by a previous process are deleted.
+    pub fn new(bucket: &str, config: &SpillConfig) -> Result<Self, UploadError> {
+        let dir = PathBuf::from(&config.dir);
+        std::fs::create_dir_all(&dir)?;
+        let removed = remove_stale_files(&dir)?;
+        if removed > 0 {
+            info!(
+                bucket = bucket,
+                dir = %dir.display(),
+                removed = removed,
+                "Removed stale spill files"
+            );
+        }
+        Ok(Self {
+            dir,
+            memory_limit: config.memory_limit,
+            encrypt: config.encrypt,
+            quota: Arc::new(Quota {
+                bucket: bucket.to_string(),
+                max_bytes: config.max_disk_bytes,
+                used: AtomicU64::new(0),
+            }),
+        })
+    }
+
+    /// Directory spill files are written to
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Bytes currently held in spill files
+    pub fn used_bytes(&self) -> u64 {
+        self.quota.used.load(Ordering::Acquire)
+    }
+
+    /// Check if a body with the declared length should be spilled
+    pub fn should_spill(&self, content_length: Option<u64>) -> bool {
+        content_length.is_none_or(|length| length > self.memory_limit)
+    }
+
+    /// Write `body` to a new spill file
+    ///
+    /// The partial file is deleted if reading the body fails or the quota
+    /// is exceeded.
+    pub async fn spill<B>(&self, mut body: B) -> Result<SpilledUpload, SpillError>
+    where
+        B: Body<Data = Bytes> + Unpin,
+        B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let path = self
+            .dir
+            .join(format!("{}{}.tmp", SPILL_PREFIX, uuid::Uuid::new_v4()));
+        let encrypt = self.encrypt;
+        let (tx, rx) = mpsc::channel(WRITE_QUEUE_DEPTH);
+        let writer = tokio::task::spawn_blocking(move || write_spill_file(path, encrypt, rx));
+
+        let mut reservation = Reservation {
+            quota: Arc::clone(&self.quota),
+            bytes: 0,
+        };
+        let mut failure = None;
+        while let Some(frame) = body.frame().await {
+            let data = match frame {
+                Ok(frame) => match frame.into_data() {
+                    Ok(data) => data,
+                    Err(_) => continue, // Trailers
+                },
+                Err(e) => {
+                    failure = Some(SpillError::Body(e.into()));
+                    break;
+                }
+            };
+            if !reservation.grow(data.len() as u64) {
+                failure = Some(SpillError::QuotaExceeded {
+                    max_bytes: self.quota.max_bytes,
+                });
+                break;
+            }
+            if tx.send(data).await.is_err() {
+                // The writer failed; its error is reported below
+                break;
+            }
+        }
+        drop(tx);
+
+        let written = writer
+            .await
+            .map_err(|e| io::Error::other(format!("spill writer panicked: {}", e)))?;
+        if let Some(e) = failure {
+            // Dropping the finished file deletes it
+            return Err(e);
+        }
+        Ok(SpilledUpload {
+            file: Arc::new(written?),
+            _reservation: reservation,
+        })
+    }
+}
+
+/// Request body held in a spill file
+///
+/// The file is deleted and its quota released when this is dropped.
+pub struct SpilledUpload {
+    file: Arc<TempFileUpload>,
+    _reservation: Reservation,
+}
+
+impl SpilledUpload {
+    /// The spill file
+    pub fn file(&self) -> &TempFileUpload {
+        &self.file
+    }
+
+    /// Size of the body in bytes
+    pub fn size(&self) -> u64 {
+        self.file.size()
+    }
+
+    /// First `len` bytes of the body (fewer if the body is shorter)
+    pub fn head(&self, len: usize) -> io::Result<Vec<u8>> {
+        let mut head = Vec::with_capacity(len);
+        self.file
+            .reader()?
+            .take(len as u64)
+            .read_to_end(&mut head)?;
+        Ok(head)
+    }
+
+    /// Upload the body to `backend`
+    ///
+    /// Bodies up to `config.multipart_threshold` (and at most
+    /// [`MAX_PUT_SIZE`]) are stored with a single request; larger ones as a
+    /// multipart upload of `config.part_size` parts, or larger parts if the
+    /// body would otherwise take too many, read from the spill file one
+    /// part at a time. A multipart upload that fails or is not completed is
+    /// aborted.
+    pub async fn upload(
+        &self,
+        backend: &dyn StorageBackend,
+        key: &str,
+        content_type: Option<&str>,
+        config: &UploadConfig,
+    ) -> Result<StoredObject, UploadError> {
+        self.upload_with_headers(backend, key, content_type, &[], config)
+            .await
+    }
+
+    /// Upload the body to `backend` with additional request headers
+    ///
+    /// `headers` go with the single request. Multipart uploads get object
+    /// metadata (tagging, encoding) on creation and the
+    /// [`CONDITIONAL_HEADERS`] on completion, where conditions are decided.
+    pub async fn upload_with_headers(
+        &self,
+        backend: &dyn StorageBackend,
+        key: &str,
+        content_type: Option<&str>,
+        headers: &[(String, String)],
+        config: &UploadConfig,
+    ) -> Result<StoredObject, UploadError> {
+        self.upload_with_progress(backend, key, content_type, headers, config, None)
+            .await
+    }
+
+    /// Like [`upload_with_headers`](Self::upload_with_headers), reporting
+    /// each stored multipart part to `session`
+    pub async fn upload_with_progress(
+        &self,
+        backend: &dyn StorageBackend,
+        key: &str,
+        content_type: Option<&str>,
+        headers: &[(String, String)],
+        config: &UploadConfig,
+        session: Option<&SessionHandle>,
+    ) -> Result<StoredObject, UploadError> {
+        if self.size() <= (config.multipart_threshold as u64).min(MAX_PUT_SIZE) {
+            let size =
+                usize::try_from(self.size()).map_err(|_| UploadError::InvalidContentLength)?;
+            let mut parts = self.parts(size.max(1));
+            let body = parts.recv().await.transpose()?.unwrap_or_default();
+            // Wait for the reader to finish so dropping `self` deletes the file
+            while parts.recv().await.is_some() {}
+            return backend
+                .put_object_with_headers(key, body, content_type, headers)
+                .await
+                .map_err(|e| e.in_object(ErrorContext::new(backend.bucket(), key)));
+        }
+
+        let (conditions, metadata): (Vec<_>, Vec<_>) = headers
+            .iter()
+            .cloned()
+            .partition(|(name, _)| CONDITIONAL_HEADERS.contains(&name.as_str()));
+        let upload_id = backend
+            .create_multipart_upload_with_headers(key, &metadata)
+            .await
+            .map_err(|e| e.in_object(ErrorContext::new(backend.bucket(), key)))?;
+        let result = match self
+            .upload_parts(backend, key, &upload_id, config, session)
+            .await
+        {
+            Ok(parts) => {
+                backend
+                    .complete_multipart_upload_with_headers(key, &upload_id, &parts, &conditions)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        // Also abort when completion is refused, e.g. by a failed precondition
+        if result.is_err() {
+            if let Err(abort_error) = backend.abort_multipart_upload(key, &upload_id).await {
+                warn!(
+                    key = key,
+                    upload_id = %upload_id,
+                    error = %abort_error,
+                    "Failed to abort spilled multipart upload"
+                );
+            }
+        }
+        result.map_err(|e| {
+            e.in_object(ErrorContext::new(backend.bucket(), key).with_upload_id(upload_id))
+        })
+    }
+
+    async fn upload_parts(
+        &self,
+        backend: &dyn StorageBackend,
+        key: &str,
+        upload_id: &str,
+        config: &UploadConfig,
+        session: Option<&SessionHandle>,
+    ) -> Result<Vec<CompletedPart>, UploadError> {
+        let mut parts = self.parts(part_size_for(self.size(), config.part_size));
+        let mut completed = Vec::new();
+        while let Some(body) = parts.recv().await {
+            let part_number = completed.len() as u32 + 1;
+            validate_part_number(part_number)?;
+            let part = backend
+                .upload_part(key, upload_id, part_number, body?)
+                .await?;
+            completed.push(CompletedPart::stored(part_number, &part));
+            if let Some(session) = session {
+                session.part_completed();
+            }
+        }
+        Ok(completed)
+    }
+
+    /// Read the plaintext in `part_size` chunks on the blocking thread pool
+    ///
+    /// At most one part waits in the channel while another is uploaded. The
+    /// reader lets go of the file before the channel closes.
+    fn parts(&self, part_size: usize) -> mpsc::Receiver<io::Result<Bytes>> {
+        let (tx, rx) = mpsc::channel(1);
+        let file = Arc::clone(&self.file);
+        tokio::task::spawn_blocking(move || {
+            send_parts(&file, part_size, &tx);
+            drop(file);
+        });
+        rx
+    }
+}
+
+/// Send the plaintext of `file` in `part_size` chunks until done or failed
+fn send_parts(file: &TempFileUpload, part_size: usize, tx: &mpsc::Sender<io::Result<Bytes>>) {
+    let mut reader = match file.reader() {
+        Ok(reader) => reader,
+        Err(e) => {
+            let _ = tx.blocking_send(Err(e));
+            return;
+        }
+    };
+    loop {
+        let mut part = Vec::with_capacity(part_size);
+        let read = profile_span!("spill.read", bytes = part_size)
+            .in_scope(|| (&mut reader).take(part_size as u64).read_to_end(&mut part));
+        let part = match read {
+            Ok(0) => return,
+            Ok(_) => Ok(Bytes::from(part)),
+            Err(e) => Err(e),
+        };
+        let failed = part.is_err();
+        if tx.blocking_send(part).is_err() || failed {
+            return;
+        }
+    }
+}
+
+/// Write the frames received on `rx` to `path`, deleting it on failure
+fn write_spill_file(
+    path: PathBuf,
+    encrypt: bool,
+    rx: mpsc::Receiver<Bytes>,
+) -> Result<TempFileUpload, SpillError> {
+    let result = write_frames(&path, encrypt, rx).and_then(|(size, content_hash, iv)| {
+        Ok(TempFileUpload::from_spilled(
+            path.clone(),
+            size,
+            content_hash,
+            iv,
+        )?)
+    });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&path);
+    }
+    result
+}
+
+fn write_frames(
+    path: &Path,
+    encrypt: bool,
+    mut rx: mpsc::Receiver<Bytes>,
+) -> Result<(u64, String, Option<[u8; IV_LEN]>), SpillError> {
+    let file = File::create(path)?;
+    let iv = if encrypt {
+        Some(SpoolKey::generate_iv()?)
+    } else {
+        None
+    };
+    let mut writer: Box<dyn Write> = match iv {
+        Some(ref iv) => Box::new(EncryptingWriter::new(file, SpoolKey::process_key()?, iv)?),
+        None => Box::new(file),
+    };
+
+    let mut hasher = Sha256Hasher::new();
+    let mut size = 0u64;
+    while let Some(data) = rx.blocking_recv() {
+        profile_span!("spill.write", bytes = data.len()).in_scope(|| writer.write_all(&data))?;
+        hasher.update(&data);
+        size += data.len() as u64;
+    }
+    writer.flush()?;
+    Ok((size, hasher.finish_hex(), iv))
+}
+
+/// Delete spill files left in `dir`, returning how many were removed
+fn remove_stale_files(dir: &Path) -> io::Result<usize> {
+    let mut removed = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !name.starts_with(SPILL_PREFIX) || !name.ends_with(".tmp") {
+            continue;
+        }
+        match std::fs::remove_file(entry.path()) {
+            Ok(()) => removed += 1,
+            Err(e) => warn!(
+                path = %entry.path().display(),
+                error = %e,
+                "Failed to remove stale spill file"
+            ),
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::upload::local::LocalFsBackend;
+    use http_body_util::Full;
+
+    fn config(dir: &Path, max_disk_bytes: u64) -> SpillConfig {
+        SpillConfig {
+            dir: dir.display().to_string(),
+            max_disk_bytes,
+            memory_limit: 16,
+            encrypt: false,
+        }
+    }
+
+    #[test]
+    fn test_should_spill() {
+        let dir = tempfile::tempdir().unwrap();
+        let buffer = SpillBuffer::new("test", &config(dir.path(), 1024)).unwrap();
+
+        assert!(buffer.should_spill(None));
+        assert!(buffer.should_spill(Some(17)));
+        assert!(!buffer.should_spill(Some(16)));
+    }
+
+    #[test]
+    fn test_stale_files_removed_at_startup() {
+        let dir = tempfile::tempdir().unwrap();
+        let stale = dir.path().join("mizuchi-spill-crashed.tmp");
+        let other = dir.path().join("keep.txt");
+        std::fs::write(&stale, b"left over").unwrap();
+        std::fs::write(&other, b"not ours").unwrap();
+
+        SpillBuffer::new("test", &config(dir.path(), 1024)).unwrap();
+
+        assert!(!stale.exists());
+        assert!(other.exists());
+    }
+
+    #[tokio::test]
+    async fn test_spill_and_upload() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = tempfile::tempdir().unwrap();
+        let mut spill_config = config(dir.path(), 1024);
+        spill_config.encrypt = true;
+        let buffer = SpillBuffer::new("test", &spill_config).unwrap();
+        let backend = LocalFsBackend::new("test", root.path()).unwrap();
+
+        let spilled = buffer.spill(Full::new(Bytes::from("hello"))).await.unwrap();
+        let path = spilled.file().path().to_path_buf();
+        assert!(spilled.file().is_encrypted());
+        assert_eq!(buffer.used_bytes(), 5);
+        assert_eq!(
+            spilled.file().content_hash(),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+
+        spilled
+            .upload(&backend, "a.txt", None, &UploadConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(root.path().join("a.txt")).unwrap(), b"hello");
+
+        drop(spilled);
+        assert!(!path.exists());
+        assert_eq!(buffer.used_bytes(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_large_spill_uses_multipart() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = tempfile::tempdir().unwrap();
+        let buffer = SpillBuffer::new("test", &config(dir.path(), 1024)).unwrap();
+        let backend = LocalFsBackend::new("test", root.path()).unwrap();
+        let upload_config = UploadConfig {
+            multipart_threshold: 4,
+            part_size: 4,
+            ..Default::default()
+        };
+
+        let spilled = buffer
+            .spill(Full::new(Bytes::from("0123456789")))
+            .await
+            .unwrap();
+        let object = spilled
+            .upload(&backend, "b.txt", None, &upload_config)
+            .await
+            .unwrap();
+
+        assert!(object.etag.ends_with("-3\""), "etag {}", object.etag);
+        assert_eq!(
+            std::fs::read(root.path().join("b.txt")).unwrap(),
+            b"0123456789"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_quota_exceeded() {
+        let dir = tempfile::tempdir().unwrap();
+        let buffer = SpillBuffer::new("test", &config(dir.path(), 4)).unwrap();
+
+        let result = buffer.spill(Full::new(Bytes::from("too large"))).await;
+
+        assert!(matches!(
+            result,
+            Err(SpillError::QuotaExceeded { max_bytes: 4 })
+        ));
+        assert_eq!(buffer.used_bytes(), 0);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
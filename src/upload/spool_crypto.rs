@@ -0,0 +1,257 @@
+//! Spool file encryption at rest
+//!
+//! Encrypts upload bodies spooled to local disk with AES-256-CTR so sensitive
+//! payloads never touch shared nodes in plaintext.
+//!
+//! # Key Management
+//!
+//! - One 256-bit key is generated per process on first use
+//! - The key lives only in memory and is never written to disk or logged
+//! - Every spool file gets a fresh random IV, kept in memory alongside the file
+//!
+//! Spool files left behind by a crash are therefore unreadable once the
+//! process exits.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::upload::spool_crypto::{DecryptingReader, EncryptingWriter, SpoolKey};
+//! use std::io::{Read, Write};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let key = SpoolKey::process_key()?;
+//! let iv = SpoolKey::generate_iv()?;
+//!
+//! let mut writer = EncryptingWriter::new(Vec::new(), key, &iv)?;
+//! writer.write_all(b"secret payload")?;
+//! let ciphertext = writer.into_inner();
+//! assert_ne!(ciphertext, b"secret payload");
+//!
+//! let mut plaintext = Vec::new();
+//! DecryptingReader::new(&ciphertext[..], key, &iv)?.read_to_end(&mut plaintext)?;
+//! assert_eq!(plaintext, b"secret payload");
+//! # Ok(())
+//! # }
+//! ```
+
+use aws_lc_rs::cipher::{
+    EncryptionContext, StreamingEncryptingKey, UnboundCipherKey, AES_256, AES_CTR_IV_LEN,
+};
+use aws_lc_rs::iv::FixedLength;
+use std::io::{self, Read, Write};
+use std::sync::OnceLock;
+
+/// Length of the per-file IV in bytes
+pub const IV_LEN: usize = AES_CTR_IV_LEN;
+
+/// AES block length, used to size scratch buffers for the streaming cipher
+const BLOCK_LEN: usize = 16;
+
+/// Chunk size used when encrypting or decrypting
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Per-process ephemeral key
+static PROCESS_KEY: OnceLock<SpoolKey> = OnceLock::new();
+
+fn crypto_error(context: &str) -> io::Error {
+    io::Error::other(format!("spool encryption: {}", context))
+}
+
+/// AES-256 key used for spool files
+pub struct SpoolKey {
+    key_bytes: [u8; 32],
+}
+
+impl SpoolKey {
+    /// Generate a new random key
+    pub fn generate() -> io::Result<Self> {
+        let mut key_bytes = [0u8; 32];
+        aws_lc_rs::rand::fill(&mut key_bytes).map_err(|_| crypto_error("key generation failed"))?;
+        Ok(Self { key_bytes })
+    }
+
+    /// Get the per-process key, generating it on first use
+    pub fn process_key() -> io::Result<&'static SpoolKey> {
+        if let Some(key) = PROCESS_KEY.get() {
+            return Ok(key);
+        }
+        let key = Self::generate()?;
+        Ok(PROCESS_KEY.get_or_init(|| key))
+    }
+
+    /// Generate a random IV for a new spool file
+    pub fn generate_iv() -> io::Result<[u8; IV_LEN]> {
+        let mut iv = [0u8; IV_LEN];
+        aws_lc_rs::rand::fill(&mut iv).map_err(|_| crypto_error("IV generation failed"))?;
+        Ok(iv)
+    }
+
+    /// Create a CTR keystream starting at the beginning of a file
+    fn keystream(&self, iv: &[u8; IV_LEN]) -> io::Result<KeyStream> {
+        let key = UnboundCipherKey::new(&AES_256, &self.key_bytes)
+            .map_err(|_| crypto_error("invalid key"))?;
+        let context = EncryptionContext::Iv128(FixedLength::from(*iv));
+        let cipher = StreamingEncryptingKey::less_safe_ctr(key, context)
+            .map_err(|_| crypto_error("cipher initialization failed"))?;
+        Ok(KeyStream {
+            cipher,
+            scratch: vec![0u8; CHUNK_SIZE + BLOCK_LEN],
+        })
+    }
+}
+
+impl Drop for SpoolKey {
+    fn drop(&mut self) {
+        self.key_bytes.fill(0);
+    }
+}
+
+impl std::fmt::Debug for SpoolKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SpoolKey(<redacted>)")
+    }
+}
+
+/// Streaming AES-CTR transform
+///
+/// CTR mode is symmetric, so the same transform encrypts and decrypts.
+struct KeyStream {
+    cipher: StreamingEncryptingKey,
+    scratch: Vec<u8>,
+}
+
+impl KeyStream {
+    /// Transform `input` and pass the result to `sink`, one chunk at a time
+    fn apply<F>(&mut self, input: &[u8], mut sink: F) -> io::Result<()>
+    where
+        F: FnMut(&[u8]) -> io::Result<()>,
+    {
+        for chunk in input.chunks(CHUNK_SIZE) {
+            let update = self
+                .cipher
+                .update(chunk, &mut self.scratch)
+                .map_err(|_| crypto_error("cipher update failed"))?;
+            sink(update.written())?;
+        }
+        Ok(())
+    }
+}
+
+/// Writer that encrypts everything written to it
+pub struct EncryptingWriter<W: Write> {
+    inner: W,
+    stream: KeyStream,
+}
+
+impl<W: Write> EncryptingWriter<W> {
+    /// Wrap a writer, encrypting with `key` and `iv`
+    pub fn new(inner: W, key: &SpoolKey, iv: &[u8; IV_LEN]) -> io::Result<Self> {
+        Ok(Self {
+            inner,
+            stream: key.keystream(iv)?,
+        })
+    }
+
+    /// Unwrap the underlying writer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for EncryptingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let inner = &mut self.inner;
+        self.stream
+            .apply(buf, |encrypted| inner.write_all(encrypted))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reader that decrypts everything read from it
+pub struct DecryptingReader<R: Read> {
+    inner: R,
+    stream: KeyStream,
+    buffer: Vec<u8>,
+}
+
+impl<R: Read> DecryptingReader<R> {
+    /// Wrap a reader, decrypting with `key` and `iv`
+    pub fn new(inner: R, key: &SpoolKey, iv: &[u8; IV_LEN]) -> io::Result<Self> {
+        Ok(Self {
+            inner,
+            stream: key.keystream(iv)?,
+            buffer: vec![0u8; CHUNK_SIZE],
+        })
+    }
+}
+
+impl<R: Read> Read for DecryptingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let want = buf.len().min(self.buffer.len());
+        let n = self.inner.read(&mut self.buffer[..want])?;
+        if n == 0 {
+            return Ok(0);
+        }
+
+        let mut written = 0;
+        self.stream.apply(&self.buffer[..n], |plain| {
+            buf[written..written + plain.len()].copy_from_slice(plain);
+            written += plain.len();
+            Ok(())
+        })?;
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_across_chunk_boundaries() {
+        let key = SpoolKey::generate().unwrap();
+        let iv = SpoolKey::generate_iv().unwrap();
+        let data: Vec<u8> = (0..(CHUNK_SIZE * 2 + 123)).map(|i| i as u8).collect();
+
+        let mut writer = EncryptingWriter::new(Vec::new(), &key, &iv).unwrap();
+        // Odd-sized writes exercise keystream continuity
+        for chunk in data.chunks(1000) {
+            writer.write_all(chunk).unwrap();
+        }
+        let ciphertext = writer.into_inner();
+        assert_eq!(ciphertext.len(), data.len());
+        assert_ne!(ciphertext, data);
+
+        let mut plaintext = Vec::new();
+        DecryptingReader::new(&ciphertext[..], &key, &iv)
+            .unwrap()
+            .read_to_end(&mut plaintext)
+            .unwrap();
+        assert_eq!(plaintext, data);
+    }
+
+    #[test]
+    fn test_different_iv_different_ciphertext() {
+        let key = SpoolKey::generate().unwrap();
+        let iv1 = SpoolKey::generate_iv().unwrap();
+        let iv2 = SpoolKey::generate_iv().unwrap();
+
+        let mut w1 = EncryptingWriter::new(Vec::new(), &key, &iv1).unwrap();
+        let mut w2 = EncryptingWriter::new(Vec::new(), &key, &iv2).unwrap();
+        w1.write_all(b"same plaintext").unwrap();
+        w2.write_all(b"same plaintext").unwrap();
+
+        assert_ne!(w1.into_inner(), w2.into_inner());
+    }
+
+    #[test]
+    fn test_process_key_is_stable() {
+        let a = SpoolKey::process_key().unwrap() as *const SpoolKey;
+        let b = SpoolKey::process_key().unwrap() as *const SpoolKey;
+        assert_eq!(a, b);
+    }
+}
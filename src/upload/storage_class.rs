@@ -0,0 +1,157 @@
+//! Storage class routing
+//!
+//! Buckets with `upload.storage_class_rules` pick the S3 storage class of
+//! each upload from its key, content type and size, e.g. to send large
+//! archives straight to `STANDARD_IA`. The first matching rule wins;
+//! uploads matching none get the backend bucket's default class.
+//!
+//! # Example
+//!
+//! ```
+//! use mizuchi_uploadr::config::{StorageClass, StorageClassRule};
+//! use mizuchi_uploadr::upload::storage_class::StorageClassRouter;
+//!
+//! let router = StorageClassRouter::new(&[StorageClassRule {
+//!     prefix: Some("/archive/".into()),
+//!     content_types: vec![],
+//!     min_size: Some(100 * 1024 * 1024),
+//!     max_size: None,
+//!     storage_class: StorageClass::StandardIa,
+//! }]);
+//!
+//! let large = 200 * 1024 * 1024;
+//! assert_eq!(
+//!     router.route("archive/2024.tar", None, large),
+//!     Some(StorageClass::StandardIa)
+//! );
+//! assert_eq!(router.route("archive/2024.tar", None, 1024), None);
+//! assert_eq!(router.route("inbox/2024.tar", None, large), None);
+//! ```
+
+use super::content_type::{essence, matches_any};
+use crate::config::{StorageClass, StorageClassRule};
+
+/// Storage class rule with normalized conditions
+#[derive(Debug)]
+struct Rule {
+    prefix: String,
+    content_types: Vec<String>,
+    min_size: u64,
+    max_size: u64,
+    storage_class: StorageClass,
+}
+
+impl Rule {
+    fn matches(&self, key: &str, content_type: Option<&str>, size: u64) -> bool {
+        key.starts_with(&self.prefix)
+            && (self.content_types.is_empty()
+                || content_type.is_some_and(|ct| matches_any(&self.content_types, &essence(ct))))
+            && (self.min_size..=self.max_size).contains(&size)
+    }
+}
+
+/// Storage class rules of one bucket
+#[derive(Debug)]
+pub struct StorageClassRouter {
+    rules: Vec<Rule>,
+}
+
+impl StorageClassRouter {
+    /// Create a router from bucket configuration
+    pub fn new(rules: &[StorageClassRule]) -> Self {
+        Self {
+            rules: rules
+                .iter()
+                .map(|rule| Rule {
+                    prefix: rule
+                        .prefix
+                        .as_deref()
+                        .unwrap_or_default()
+                        .trim_start_matches('/')
+                        .to_string(),
+                    content_types: rule
+                        .content_types
+                        .iter()
+                        .map(|t| t.trim().to_ascii_lowercase())
+                        .collect(),
+                    min_size: rule.min_size.unwrap_or(0),
+                    max_size: rule.max_size.unwrap_or(u64::MAX),
+                    storage_class: rule.storage_class,
+                })
+                .collect(),
+        }
+    }
+
+    /// Storage class for an upload of `size` bytes to `key`, if a rule matches
+    pub fn route(&self, key: &str, content_type: Option<&str>, size: u64) -> Option<StorageClass> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(key, content_type, size))
+            .map(|rule| rule.storage_class)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(storage_class: StorageClass) -> StorageClassRule {
+        StorageClassRule {
+            prefix: None,
+            content_types: Vec::new(),
+            min_size: None,
+            max_size: None,
+            storage_class,
+        }
+    }
+
+    #[test]
+    fn test_first_match_wins() {
+        let router = StorageClassRouter::new(&[
+            StorageClassRule {
+                content_types: vec!["video/*".into()],
+                ..rule(StorageClass::GlacierIr)
+            },
+            StorageClassRule {
+                max_size: Some(128 * 1024),
+                ..rule(StorageClass::Standard)
+            },
+            rule(StorageClass::IntelligentTiering),
+        ]);
+
+        assert_eq!(
+            router.route("a.mp4", Some("Video/MP4"), 10),
+            Some(StorageClass::GlacierIr)
+        );
+        assert_eq!(
+            router.route("a.txt", Some("text/plain"), 10),
+            Some(StorageClass::Standard)
+        );
+        assert_eq!(
+            router.route("a.txt", None, 1 << 30),
+            Some(StorageClass::IntelligentTiering)
+        );
+    }
+
+    #[test]
+    fn test_content_type_required() {
+        let router = StorageClassRouter::new(&[StorageClassRule {
+            content_types: vec!["application/pdf".into()],
+            ..rule(StorageClass::OnezoneIa)
+        }]);
+
+        assert_eq!(router.route("a.pdf", None, 10), None);
+        assert_eq!(
+            router.route("a.pdf", Some("application/pdf; charset=binary"), 10),
+            Some(StorageClass::OnezoneIa)
+        );
+    }
+
+    #[test]
+    fn test_header_value() {
+        let class: StorageClass = serde_yaml::from_str("GLACIER_IR").unwrap();
+        assert_eq!(class, StorageClass::GlacierIr);
+        assert_eq!(class.as_str(), "GLACIER_IR");
+        assert!(serde_yaml::from_str::<StorageClass>("COLD").is_err());
+    }
+}
@@ -9,6 +9,14 @@
 //! 2. Compute SHA256 hash for SigV4 signing
 //! 3. Use sendfile for zero-copy transfer to S3
 //!
+//! # Encryption at Rest
+//!
+//! `TempFileUpload::from_bytes_encrypted` writes the body encrypted with
+//! AES-256-CTR under a per-process ephemeral key (see [`super::spool_crypto`]).
+//! Reads through [`TempFileUpload::reader`] decrypt transparently. Encrypted
+//! spool files cannot be sent with `sendfile(2)`, so they fall back to
+//! buffered transfer.
+//!
 //! # Example
 //!
 //! ```no_run
@@ -27,7 +35,6 @@
 //! ```
 
 use bytes::Bytes;
-use sha2::{Digest, Sha256};
 use std::fs::File;
 use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
@@ -35,7 +42,10 @@ use std::path::{Path, PathBuf};
 #[cfg(target_os = "linux")]
 use std::os::fd::{AsFd, BorrowedFd};
 
+use super::spool_crypto::{DecryptingReader, EncryptingWriter, SpoolKey, IV_LEN};
 use super::UploadError;
+use crate::profiling::profile_span;
+use crate::s3::hashing;
 
 /// Temporary file for zero-copy uploads
 ///
@@ -45,6 +55,8 @@ pub struct TempFileUpload {
     file: File,
     size: u64,
     content_hash: String,
+    /// IV of the encrypted spool file (None if stored in plaintext)
+    iv: Option<[u8; IV_LEN]>,
 }
 
 impl TempFileUpload {
@@ -53,6 +65,19 @@ impl TempFileUpload {
     /// Writes the data to a temp file and computes SHA256 hash.
     /// Uses tmpfs (/dev/shm) on Linux when available for better performance.
     pub fn from_bytes(data: Bytes) -> Result<Self, UploadError> {
+        Self::create(data, false)
+    }
+
+    /// Create an encrypted temp file from Bytes
+    ///
+    /// Same as [`TempFileUpload::from_bytes`], but the file content is
+    /// encrypted at rest with the per-process spool key. The content hash is
+    /// still computed over the plaintext.
+    pub fn from_bytes_encrypted(data: Bytes) -> Result<Self, UploadError> {
+        Self::create(data, true)
+    }
+
+    fn create(data: Bytes, encrypt: bool) -> Result<Self, UploadError> {
         // Choose temp directory: prefer tmpfs on Linux
         let temp_dir = Self::get_temp_dir();
 
@@ -60,10 +85,22 @@ impl TempFileUpload {
         let file_name = format!("mizuchi-{}.tmp", uuid::Uuid::new_v4());
         let path = temp_dir.join(file_name);
 
-        // Write data to file
-        let mut file = File::create(&path)?;
-        file.write_all(&data)?;
-        file.flush()?;
+        // Write data to file, encrypting on the way if requested
+        let iv = profile_span!("temp_file.write", bytes = data.len()).in_scope(|| {
+            let file = File::create(&path)?;
+            io::Result::Ok(if encrypt {
+                let iv = SpoolKey::generate_iv()?;
+                let mut writer = EncryptingWriter::new(file, SpoolKey::process_key()?, &iv)?;
+                writer.write_all(&data)?;
+                writer.flush()?;
+                Some(iv)
+            } else {
+                let mut file = file;
+                file.write_all(&data)?;
+                file.flush()?;
+                None
+            })
+        })?;
 
         // Compute SHA256 hash
         let content_hash = Self::compute_sha256(&data);
@@ -76,6 +113,28 @@ impl TempFileUpload {
             file,
             size: data.len() as u64,
             content_hash,
+            iv,
+        })
+    }
+
+    /// Adopt a file already written by the spill buffer
+    ///
+    /// `content_hash` is the SHA256 of the plaintext and `iv` the IV the file
+    /// was encrypted with, if any. The file is removed on drop like any other
+    /// temp file.
+    pub(crate) fn from_spilled(
+        path: PathBuf,
+        size: u64,
+        content_hash: String,
+        iv: Option<[u8; IV_LEN]>,
+    ) -> io::Result<Self> {
+        let file = File::open(&path)?;
+        Ok(Self {
+            path,
+            file,
+            size,
+            content_hash,
+            iv,
         })
     }
 
@@ -96,12 +155,37 @@ impl TempFileUpload {
         &self.content_hash
     }
 
-    /// Check if zero-copy (sendfile) is available on this platform
+    /// Check if the file content is encrypted at rest
+    pub fn is_encrypted(&self) -> bool {
+        self.iv.is_some()
+    }
+
+    /// Check if zero-copy (sendfile) is available for this file
+    ///
+    /// Always `false` for encrypted files, which must be decrypted in userspace.
     pub fn supports_zero_copy(&self) -> bool {
-        cfg!(target_os = "linux")
+        cfg!(target_os = "linux") && !self.is_encrypted()
+    }
+
+    /// Open a fresh reader over the plaintext content
+    ///
+    /// Decrypts transparently when the file is encrypted.
+    pub fn reader(&self) -> io::Result<Box<dyn Read>> {
+        let file = File::open(&self.path)?;
+        match self.iv {
+            Some(ref iv) => Ok(Box::new(DecryptingReader::new(
+                file,
+                SpoolKey::process_key()?,
+                iv,
+            )?)),
+            None => Ok(Box::new(file)),
+        }
     }
 
     /// Get a reference to the underlying file
+    ///
+    /// Note: for encrypted files this yields ciphertext; use
+    /// [`TempFileUpload::reader`] to read the plaintext.
     pub fn file(&self) -> &File {
         &self.file
     }
@@ -114,9 +198,18 @@ impl TempFileUpload {
     /// Read all content into a buffer
     ///
     /// This is a fallback for platforms without zero-copy support.
+    /// Decrypts transparently when the file is encrypted.
     pub fn read_all(&mut self) -> io::Result<Vec<u8>> {
         let mut buffer = Vec::with_capacity(self.size as usize);
-        self.file.read_to_end(&mut buffer)?;
+        match self.iv {
+            Some(ref iv) => {
+                DecryptingReader::new(&mut self.file, SpoolKey::process_key()?, iv)?
+                    .read_to_end(&mut buffer)?;
+            }
+            None => {
+                self.file.read_to_end(&mut buffer)?;
+            }
+        }
         Ok(buffer)
     }
 
@@ -141,9 +234,7 @@ impl TempFileUpload {
 
     /// Compute SHA256 hash of data
     fn compute_sha256(data: &[u8]) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(data);
-        hex::encode(hasher.finalize())
+        hashing::sha256_hex(data)
     }
 }
 
@@ -206,4 +297,27 @@ mod tests {
         // Dropped
         assert!(!path.exists());
     }
+
+    #[test]
+    fn test_encrypted_temp_file() {
+        let data = Bytes::from("sensitive payload");
+        let mut temp = TempFileUpload::from_bytes_encrypted(data.clone()).unwrap();
+
+        assert!(temp.is_encrypted());
+        assert!(!temp.supports_zero_copy());
+
+        // Plaintext never hits disk
+        let on_disk = std::fs::read(temp.path()).unwrap();
+        assert_eq!(on_disk.len(), data.len());
+        assert_ne!(on_disk, data.to_vec());
+
+        // Hash covers the plaintext
+        assert_eq!(temp.content_hash(), TempFileUpload::compute_sha256(&data));
+
+        // Reads decrypt transparently
+        let mut plaintext = Vec::new();
+        temp.reader().unwrap().read_to_end(&mut plaintext).unwrap();
+        assert_eq!(plaintext, data.to_vec());
+        assert_eq!(temp.read_all().unwrap(), data.to_vec());
+    }
 }
@@ -0,0 +1,109 @@
+//! Upload transform plugins
+//!
+//! Buckets with `upload.transform` run every upload body through a WASM
+//! module before storing it. The module may return a rewritten body (to
+//! strip EXIF data, re-encode an image, ...) or reject the upload. Plugins
+//! run under wasmtime with per-run fuel and memory limits; support is
+//! compiled in with the `wasm` feature.
+//!
+//! # Plugin ABI
+//!
+//! The module imports nothing and exports:
+//!
+//! | Export | Signature | Purpose |
+//! |--------|-----------|---------|
+//! | `memory` | memory | Linear memory shared with the host |
+//! | `alloc` | `(len: i32) -> i32` | Reserve `len` bytes for the input, returning their offset |
+//! | `transform` | `(ptr: i32, len: i32) -> i32` | Process the input; `0` accepts, anything else rejects |
+//! | `output_ptr` | `() -> i32` | Offset of the output |
+//! | `output_len` | `() -> i32` | Length of the output |
+//!
+//! After an accepting `transform`, the output is the new body; after a
+//! rejecting one, it is a UTF-8 reason returned to the client.
+
+use crate::config::TransformConfig;
+use bytes::Bytes;
+use thiserror::Error;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+/// Transform errors
+#[derive(Error, Debug)]
+pub enum TransformError {
+    #[error("WASM transforms require the `wasm` feature")]
+    Unavailable,
+
+    #[error("Failed to load transform module: {0}")]
+    Module(String),
+
+    #[error("Transform exceeded its resource limits: {0}")]
+    ResourceLimit(String),
+
+    #[error("Transform failed: {0}")]
+    Failed(String),
+}
+
+/// Result of running a transform over an upload body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransformOutcome {
+    /// Store this body instead of the original
+    Body(Bytes),
+    /// Refuse the upload, with the plugin's reason
+    Rejected(String),
+}
+
+/// Transform stage of one bucket
+pub struct Transform {
+    #[cfg(feature = "wasm")]
+    plugin: std::sync::Arc<wasm::WasmTransform>,
+    max_input_bytes: u64,
+}
+
+impl Transform {
+    /// Load the plugin described by `config`
+    ///
+    /// Fails with [`TransformError::Unavailable`] when built without the
+    /// `wasm` feature, so a configured transform is never silently skipped.
+    pub fn from_config(config: &TransformConfig) -> Result<Self, TransformError> {
+        #[cfg(feature = "wasm")]
+        {
+            Ok(Self {
+                plugin: std::sync::Arc::new(wasm::WasmTransform::from_config(config)?),
+                max_input_bytes: config.max_input_bytes,
+            })
+        }
+        #[cfg(not(feature = "wasm"))]
+        {
+            let _ = config;
+            Err(TransformError::Unavailable)
+        }
+    }
+
+    /// Largest body the plugin accepts
+    pub fn max_input_bytes(&self) -> u64 {
+        self.max_input_bytes
+    }
+
+    /// Run the plugin over `body` on the blocking thread pool
+    pub async fn apply(&self, body: Bytes) -> Result<TransformOutcome, TransformError> {
+        if body.len() as u64 > self.max_input_bytes {
+            return Err(TransformError::ResourceLimit(format!(
+                "body of {} bytes exceeds max_input_bytes",
+                body.len()
+            )));
+        }
+        #[cfg(feature = "wasm")]
+        {
+            let plugin = std::sync::Arc::clone(&self.plugin);
+            tokio::task::spawn_blocking(move || plugin.run(&body))
+                .await
+                .map_err(|e| TransformError::Failed(e.to_string()))?
+        }
+        #[cfg(not(feature = "wasm"))]
+        {
+            let _ = body;
+            Err(TransformError::Unavailable)
+        }
+    }
+}
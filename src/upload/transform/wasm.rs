@@ -0,0 +1,199 @@
+//! wasmtime runtime for transform plugins
+//!
+//! The module is compiled once; each run instantiates it in a fresh store,
+//! so no state leaks between uploads. Execution is bounded by fuel (roughly
+//! one unit per WASM instruction) and linear memory by a store limiter.
+
+use super::{TransformError, TransformOutcome};
+use crate::config::TransformConfig;
+use bytes::Bytes;
+use wasmtime::{Config, Engine, Instance, Module, Store, StoreLimitsBuilder, Trap};
+
+/// Compiled transform plugin
+pub struct WasmTransform {
+    engine: Engine,
+    module: Module,
+    fuel: u64,
+    max_memory_bytes: usize,
+}
+
+impl WasmTransform {
+    /// Compile the module at `config.module`
+    pub fn from_config(config: &TransformConfig) -> Result<Self, TransformError> {
+        let bytes = std::fs::read(&config.module)
+            .map_err(|e| TransformError::Module(format!("{}: {}", config.module, e)))?;
+        Self::new(&bytes, config.fuel, config.max_memory_bytes)
+    }
+
+    /// Compile a module from its binary or text form
+    pub fn new(module: &[u8], fuel: u64, max_memory_bytes: usize) -> Result<Self, TransformError> {
+        let mut engine_config = Config::new();
+        engine_config.consume_fuel(true);
+        let engine =
+            Engine::new(&engine_config).map_err(|e| TransformError::Module(e.to_string()))?;
+        let module =
+            Module::new(&engine, module).map_err(|e| TransformError::Module(e.to_string()))?;
+        Ok(Self {
+            engine,
+            module,
+            fuel,
+            max_memory_bytes,
+        })
+    }
+
+    /// Run the plugin over `input`
+    pub fn run(&self, input: &[u8]) -> Result<TransformOutcome, TransformError> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.max_memory_bytes)
+            .instances(1)
+            .build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store
+            .set_fuel(self.fuel)
+            .map_err(|e| TransformError::Failed(e.to_string()))?;
+
+        let instance = Instance::new(&mut store, &self.module, &[]).map_err(run_error)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| TransformError::Module("missing `memory` export".into()))?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, "alloc")
+            .map_err(|e| TransformError::Module(e.to_string()))?;
+        let transform = instance
+            .get_typed_func::<(i32, i32), i32>(&mut store, "transform")
+            .map_err(|e| TransformError::Module(e.to_string()))?;
+        let output_ptr = instance
+            .get_typed_func::<(), i32>(&mut store, "output_ptr")
+            .map_err(|e| TransformError::Module(e.to_string()))?;
+        let output_len = instance
+            .get_typed_func::<(), i32>(&mut store, "output_len")
+            .map_err(|e| TransformError::Module(e.to_string()))?;
+
+        let len = i32::try_from(input.len())
+            .map_err(|_| TransformError::ResourceLimit("input exceeds 2GB".into()))?;
+        let ptr = alloc.call(&mut store, len).map_err(run_error)?;
+        memory
+            .write(&mut store, offset(ptr)?, input)
+            .map_err(|e| TransformError::Failed(format!("input out of bounds: {}", e)))?;
+
+        let status = transform.call(&mut store, (ptr, len)).map_err(run_error)?;
+        let ptr = output_ptr.call(&mut store, ()).map_err(run_error)?;
+        let len = output_len.call(&mut store, ()).map_err(run_error)?;
+        let mut output = vec![0u8; offset(len)?];
+        memory
+            .read(&store, offset(ptr)?, &mut output)
+            .map_err(|e| TransformError::Failed(format!("output out of bounds: {}", e)))?;
+
+        Ok(match status {
+            0 => TransformOutcome::Body(Bytes::from(output)),
+            _ => TransformOutcome::Rejected(String::from_utf8_lossy(&output).into_owned()),
+        })
+    }
+}
+
+/// Offset or length returned by the plugin, which must not be negative
+fn offset(value: i32) -> Result<usize, TransformError> {
+    usize::try_from(value)
+        .map_err(|_| TransformError::Failed(format!("negative offset or length {}", value)))
+}
+
+/// Classify an error raised while running plugin code
+fn run_error(error: wasmtime::Error) -> TransformError {
+    match error.downcast_ref::<Trap>() {
+        Some(Trap::OutOfFuel) => TransformError::ResourceLimit("out of fuel".into()),
+        _ => TransformError::Failed(error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Upper-cases ASCII letters; rejects bodies starting with `X`
+    const UPPERCASE: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (global $out_ptr (mut i32) (i32.const 0))
+          (global $out_len (mut i32) (i32.const 0))
+          (data (i32.const 0) "starts with X")
+          (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+          (func (export "output_ptr") (result i32) (global.get $out_ptr))
+          (func (export "output_len") (result i32) (global.get $out_len))
+          (func (export "transform") (param $ptr i32) (param $len i32) (result i32)
+            (local $i i32)
+            (local $c i32)
+            (if (i32.and (i32.gt_u (local.get $len) (i32.const 0))
+                         (i32.eq (i32.load8_u (local.get $ptr)) (i32.const 88)))
+              (then
+                (global.set $out_ptr (i32.const 0))
+                (global.set $out_len (i32.const 13))
+                (return (i32.const 1))))
+            (block $done
+              (loop $next
+                (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+                (local.set $c (i32.load8_u (i32.add (local.get $ptr) (local.get $i))))
+                (if (i32.and (i32.ge_u (local.get $c) (i32.const 97))
+                             (i32.le_u (local.get $c) (i32.const 122)))
+                  (then
+                    (i32.store8 (i32.add (local.get $ptr) (local.get $i))
+                                (i32.sub (local.get $c) (i32.const 32)))))
+                (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                (br $next)))
+            (global.set $out_ptr (local.get $ptr))
+            (global.set $out_len (local.get $len))
+            (i32.const 0)))
+    "#;
+
+    /// Never returns
+    const SPIN: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) (i32.const 0))
+          (func (export "output_ptr") (result i32) (i32.const 0))
+          (func (export "output_len") (result i32) (i32.const 0))
+          (func (export "transform") (param i32 i32) (result i32)
+            (loop $forever (br $forever))
+            (i32.const 0)))
+    "#;
+
+    #[test]
+    fn test_transform_body() {
+        let plugin = WasmTransform::new(UPPERCASE.as_bytes(), 1_000_000, 1 << 20).unwrap();
+
+        assert_eq!(
+            plugin.run(b"hello, wasm").unwrap(),
+            TransformOutcome::Body(Bytes::from("HELLO, WASM"))
+        );
+    }
+
+    #[test]
+    fn test_reject_body() {
+        let plugin = WasmTransform::new(UPPERCASE.as_bytes(), 1_000_000, 1 << 20).unwrap();
+
+        assert_eq!(
+            plugin.run(b"Xenomorph").unwrap(),
+            TransformOutcome::Rejected("starts with X".into())
+        );
+    }
+
+    #[test]
+    fn test_out_of_fuel() {
+        let plugin = WasmTransform::new(SPIN.as_bytes(), 10_000, 1 << 20).unwrap();
+
+        assert!(matches!(
+            plugin.run(b"anything"),
+            Err(TransformError::ResourceLimit(_))
+        ));
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        // The module's single page (64KB) exceeds a 1KB limit
+        let result = WasmTransform::new(SPIN.as_bytes(), 10_000, 1024)
+            .unwrap()
+            .run(b"anything");
+
+        assert!(result.is_err());
+    }
+}
@@ -30,9 +30,8 @@ mod linux {
     impl ZeroCopyTransfer {
         /// Create a new zero-copy transfer
         pub fn new(buffer_size: usize) -> io::Result<Self> {
-            let (pipe_read, pipe_write) = pipe().map_err(|e| {
-                io::Error::new(io::ErrorKind::Other, format!("pipe() failed: {}", e))
-            })?;
+            let (pipe_read, pipe_write) =
+                pipe().map_err(|e| io::Error::other(format!("pipe() failed: {}", e)))?;
 
             Ok(Self {
                 pipe_read,
@@ -42,6 +41,12 @@ mod linux {
         }
 
         /// Transfer data from source to destination using splice
+        #[tracing::instrument(
+            target = "mizuchi_uploadr::profile",
+            level = "trace",
+            name = "splice",
+            skip(self, source, dest)
+        )]
         pub async fn transfer<S, D>(&self, source: &S, dest: &D, len: usize) -> io::Result<usize>
         where
             S: AsFd,
@@ -57,24 +62,21 @@ mod linux {
 
                 // Splice from source to pipe
                 let spliced_to_pipe = match splice(
-                    &source_fd,
+                    source_fd,
                     None,
                     &self.pipe_write,
                     None,
                     chunk_size,
                     SpliceFFlags::SPLICE_F_MOVE | SpliceFFlags::SPLICE_F_NONBLOCK,
                 ) {
-                    Ok(n) if n == 0 => break, // EOF
+                    Ok(0) => break, // EOF
                     Ok(n) => n,
                     Err(nix::errno::Errno::EAGAIN) => {
                         tokio::task::yield_now().await;
                         continue;
                     }
                     Err(e) => {
-                        return Err(io::Error::new(
-                            io::ErrorKind::Other,
-                            format!("splice to pipe failed: {}", e),
-                        ));
+                        return Err(io::Error::other(format!("splice to pipe failed: {}", e)));
                     }
                 };
 
@@ -84,7 +86,7 @@ mod linux {
                     match splice(
                         &self.pipe_read,
                         None,
-                        &dest_fd,
+                        dest_fd,
                         None,
                         pipe_remaining,
                         SpliceFFlags::SPLICE_F_MOVE | SpliceFFlags::SPLICE_F_NONBLOCK,
@@ -98,10 +100,10 @@ mod linux {
                             tokio::task::yield_now().await;
                         }
                         Err(e) => {
-                            return Err(io::Error::new(
-                                io::ErrorKind::Other,
-                                format!("splice from pipe failed: {}", e),
-                            ));
+                            return Err(io::Error::other(format!(
+                                "splice from pipe failed: {}",
+                                e
+                            )));
                         }
                     }
                 }
@@ -140,6 +142,12 @@ mod fallback {
         }
 
         /// Transfer data using buffered I/O
+        #[tracing::instrument(
+            target = "mizuchi_uploadr::profile",
+            level = "trace",
+            name = "copy",
+            skip(self, source, dest)
+        )]
         pub async fn transfer<S, D>(
             &self,
             source: &mut S,
@@ -0,0 +1,141 @@
+//! Admin API Integration Tests
+//!
+//! Tests for the admin listener configured with `admin.address`.
+//!
+//! ## Test Coverage
+//!
+//! - The admin API listens on its own port
+//! - `GET /config` redacts secrets
+//! - `GET /uploads` and `GET /circuits` report server state
+//! - `GET`/`PUT /log-level` change the log filter at runtime
+//! - `admin.address` requires `admin.token`
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::logging::LogFilterHandle;
+    use mizuchi_uploadr::server::pingora::PingoraServer;
+    use std::net::SocketAddr;
+    use std::path::Path;
+    use std::sync::Arc;
+    use tracing_subscriber::{reload, EnvFilter, Registry};
+
+    const ADMIN_TOKEN: &str = "admin-token";
+
+    fn config(root: &Path) -> Config {
+        let mut config = common::config(&common::local_bucket("docs", root, ""));
+        config.buckets[0].s3.access_key = Some("AKIAEXAMPLE".into());
+        config.buckets[0].s3.secret_key = Some("very-secret".into());
+        common::with_admin(config, ADMIN_TOKEN)
+    }
+
+    async fn get(admin_addr: SocketAddr, path: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .get(format!("http://{}{}", admin_addr, path))
+            .bearer_auth(ADMIN_TOKEN)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_separate_port() {
+        let dir = tempfile::tempdir().unwrap();
+        let (addr, admin_addr) = common::start_with_admin(config(dir.path())).await;
+        assert_ne!(addr.port(), admin_addr.port());
+
+        // Data-plane endpoints are not served on the admin port
+        assert_eq!(get(admin_addr, "/health").await.status(), 404);
+        let status = reqwest::get(format!("http://{}/config", admin_addr))
+            .await
+            .unwrap()
+            .status();
+        assert_eq!(status, 401);
+    }
+
+    #[tokio::test]
+    async fn test_config_is_redacted() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, admin_addr) = common::start_with_admin(config(dir.path())).await;
+
+        let response = get(admin_addr, "/config").await;
+        assert_eq!(response.status(), 200);
+        let body = response.text().await.unwrap();
+        assert!(!body.contains("very-secret"));
+        assert!(!body.contains(ADMIN_TOKEN));
+
+        let config: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(config["buckets"][0]["name"], "docs");
+        assert_eq!(config["buckets"][0]["s3"]["secret_key"], "[REDACTED]");
+    }
+
+    #[tokio::test]
+    async fn test_uploads_and_circuits() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, admin_addr) = common::start_with_admin(config(dir.path())).await;
+
+        let uploads: serde_json::Value = get(admin_addr, "/uploads").await.json().await.unwrap();
+        assert_eq!(uploads["in_flight"], 0);
+        assert_eq!(uploads["multipart"]["docs"], serde_json::json!([]));
+
+        // Local storage has no circuit breaker
+        let circuits: serde_json::Value = get(admin_addr, "/circuits").await.json().await.unwrap();
+        assert_eq!(circuits, serde_json::json!({}));
+    }
+
+    #[tokio::test]
+    async fn test_log_level() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_layer, handle) = reload::Layer::<_, Registry>::new(EnvFilter::new("info"));
+        let log_filter = Arc::new(LogFilterHandle::new("info", handle));
+        let server = PingoraServer::new(config(dir.path()))
+            .await
+            .unwrap()
+            .with_log_filter(Arc::clone(&log_filter));
+        let (_, admin_addr) = common::spawn_with_admin(server);
+
+        let body: serde_json::Value = get(admin_addr, "/log-level").await.json().await.unwrap();
+        assert_eq!(body["filter"], "info");
+
+        let client = reqwest::Client::new();
+        let url = format!("http://{}/log-level", admin_addr);
+        let response = client
+            .put(&url)
+            .bearer_auth(ADMIN_TOKEN)
+            .body("info,mizuchi_uploadr=debug")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(log_filter.current(), "info,mizuchi_uploadr=debug");
+
+        let response = client
+            .put(&url)
+            .bearer_auth(ADMIN_TOKEN)
+            .body("mizuchi=loud")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(log_filter.current(), "info,mizuchi_uploadr=debug");
+    }
+
+    #[tokio::test]
+    async fn test_log_level_without_handle() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, admin_addr) = common::start_with_admin(config(dir.path())).await;
+
+        assert_eq!(get(admin_addr, "/log-level").await.status(), 501);
+    }
+
+    #[test]
+    fn test_address_requires_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = config(dir.path());
+        config.admin.token = None;
+        assert!(config.validate().is_err());
+    }
+}
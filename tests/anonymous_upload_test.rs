@@ -0,0 +1,181 @@
+//! Anonymous Upload Integration Tests
+//!
+//! Tests for public drop-box buckets (`auth.allow_anonymous`).
+//!
+//! ## Test Coverage
+//!
+//! - Anonymous uploads within the constraints are accepted
+//! - Size, content type and key prefix violations are rejected
+//! - Per-client rate limiting with `Retry-After`
+//! - Authenticated requests bypass the anonymous constraints
+//! - Invalid credentials are not downgraded to anonymous access
+//! - Incomplete constraints are rejected by config validation
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use mizuchi_uploadr::config::Config;
+    use std::net::SocketAddr;
+    use std::path::Path;
+
+    const SECRET: &str = "dropbox-secret";
+
+    fn config(root: &Path, auth_extra: &str, burst: u32) -> Config {
+        let bucket = format!(
+            r#"    auth:
+      enabled: true
+{auth_extra}
+      allow_anonymous:
+        max_size: 16
+        allowed_content_types: ["image/*", "text/plain"]
+        key_prefix: "public/"
+        rate_limit:
+          requests_per_minute: 60
+          burst: {burst}"#
+        );
+        common::config(&common::local_bucket("dropbox", root, &bucket))
+    }
+
+    async fn put(
+        addr: SocketAddr,
+        key: &str,
+        content_type: &str,
+        body: &'static str,
+    ) -> reqwest::Response {
+        let path = format!("/dropbox/{}", key);
+        common::put(addr, &path, &[("Content-Type", content_type)], body).await
+    }
+
+    fn jwt() -> String {
+        let claims = serde_json::json!({
+            "sub": "staff",
+            "exp": (chrono::Utc::now().timestamp() + 3600) as usize,
+        });
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    const JWT: &str = "      jwt:\n        secret: dropbox-secret\n        algorithm: HS256";
+
+    #[tokio::test]
+    async fn test_anonymous_upload_within_constraints() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path(), "", 10)).await;
+
+        let response = put(addr, "public/note.txt", "text/plain", "hello").await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            std::fs::read(dir.path().join("public/note.txt")).unwrap(),
+            b"hello"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_constraint_violations_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path(), "", 10)).await;
+
+        let too_large = put(
+            addr,
+            "public/big.txt",
+            "text/plain",
+            "this is over 16 bytes",
+        )
+        .await;
+        assert_eq!(too_large.status(), 413);
+
+        let wrong_type = put(addr, "public/page.html", "text/html", "<p>").await;
+        assert_eq!(wrong_type.status(), 415);
+
+        let outside = put(addr, "private/x.png", "image/png", "png").await;
+        assert_eq!(outside.status(), 403);
+
+        let traversal = put(addr, "public/../private/x.png", "image/png", "png").await;
+        assert_eq!(traversal.status(), 403);
+
+        assert!(std::fs::read_dir(dir.path()).unwrap().next().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_per_client() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path(), "", 2)).await;
+
+        for i in 0..2 {
+            let key = format!("public/{}.txt", i);
+            assert_eq!(put(addr, &key, "text/plain", "ok").await.status(), 200);
+        }
+        let limited = put(addr, "public/2.txt", "text/plain", "ok").await;
+        assert_eq!(limited.status(), 429);
+        assert!(limited.headers().contains_key("retry-after"));
+    }
+
+    #[tokio::test]
+    async fn test_authenticated_requests_bypass_constraints() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path(), JWT, 10)).await;
+
+        let response = reqwest::Client::new()
+            .put(format!("http://{}/dropbox/private/report.html", addr))
+            .bearer_auth(jwt())
+            .header("Content-Type", "text/html")
+            .body("<p>more than sixteen bytes</p>")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_credentials_not_downgraded() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path(), JWT, 10)).await;
+
+        let response = reqwest::Client::new()
+            .put(format!("http://{}/dropbox/public/a.txt", addr))
+            .bearer_auth("not-a-token")
+            .header("Content-Type", "text/plain")
+            .body("a")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+    }
+
+    #[test]
+    fn test_constraints_are_mandatory() {
+        let yaml = r#"
+server:
+  address: "127.0.0.1:0"
+buckets:
+  - name: dropbox
+    path_prefix: /dropbox
+    s3:
+      bucket: dropbox
+      region: us-east-1
+    auth:
+      allow_anonymous:
+        max_size: 16
+        key_prefix: "public/"
+"#;
+        assert!(serde_yaml::from_str::<Config>(yaml).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = config(dir.path(), "", 10);
+        config.buckets[0]
+            .auth
+            .allow_anonymous
+            .as_mut()
+            .unwrap()
+            .allowed_content_types
+            .clear();
+        assert!(config.validate().is_err());
+    }
+}
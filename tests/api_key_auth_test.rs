@@ -0,0 +1,210 @@
+//! API Key Authentication Integration Tests
+//!
+//! Tests for `ApiKeyAuthenticator` with inline, file and Redis key stores.
+//!
+//! ## Test Coverage
+//!
+//! - Uploads with an inline (hashed, peppered) key
+//! - Bucket and prefix scopes (403 outside the scope)
+//! - Unknown keys rejected with 401
+//! - File store picks up new keys when the file changes
+//! - Redis store lookups (against an in-process RESP server)
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::auth::api_key::{hash_key, ApiKeyAuthenticator, RedisKeyStore};
+    use mizuchi_uploadr::auth::{AuthError, AuthRequest, Authenticator};
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::redis::RedisClient;
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use std::path::Path;
+    use std::sync::Arc;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream};
+    use tokio::net::TcpListener;
+
+    const PEPPER: &str = "server-pepper";
+
+    fn config(root: &Path, api_key_yaml: &str) -> Config {
+        let bucket = format!(
+            "    auth:\n      enabled: true\n      api_key:\n        pepper: {PEPPER}\n{api_key_yaml}"
+        );
+        common::config(&common::local_bucket("uploads", root, &bucket))
+    }
+
+    async fn put(addr: SocketAddr, key: &str, api_key: &str) -> reqwest::StatusCode {
+        let path = format!("/uploads/{}", key);
+        common::put(addr, &path, &[("X-Api-Key", api_key)], "batch output")
+            .await
+            .status()
+    }
+
+    fn record(id: &str, key: &str, buckets: &str, prefixes: &str) -> String {
+        format!(
+            "- id: {}\n  hash: \"{}\"\n  buckets: {}\n  prefixes: {}\n",
+            id,
+            hash_key(key, Some(PEPPER)),
+            buckets,
+            prefixes
+        )
+    }
+
+    fn indent(yaml: &str, spaces: usize) -> String {
+        yaml.lines()
+            .map(|line| format!("{}{}\n", " ".repeat(spaces), line))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_inline_key_with_scopes() {
+        let dir = tempfile::tempdir().unwrap();
+        let keys = format!(
+            "        keys:\n{}",
+            indent(
+                &record("nightly", "k-nightly", "[uploads]", "[exports/]"),
+                10
+            )
+        );
+        let addr = common::start(config(dir.path(), &keys)).await;
+
+        assert_eq!(put(addr, "exports/a.csv", "k-nightly").await, 200);
+        assert!(dir.path().join("exports/a.csv").exists());
+
+        assert_eq!(put(addr, "other/a.csv", "k-nightly").await, 403);
+        assert_eq!(put(addr, "exports/b.csv", "k-unknown").await, 401);
+    }
+
+    #[tokio::test]
+    async fn test_key_scoped_to_other_bucket_forbidden() {
+        let dir = tempfile::tempdir().unwrap();
+        let keys = format!(
+            "        keys:\n{}",
+            indent(&record("archiver", "k-archive", "[archive]", "[]"), 10)
+        );
+        let addr = common::start(config(dir.path(), &keys)).await;
+
+        assert_eq!(put(addr, "a.csv", "k-archive").await, 403);
+    }
+
+    #[tokio::test]
+    async fn test_file_store_reloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let keys_path = dir.path().join("keys.yaml");
+        std::fs::write(&keys_path, record("first", "k-first", "[]", "[]")).unwrap();
+
+        let store = format!(
+            "        store:\n          type: file\n          path: \"{}\"",
+            keys_path.display()
+        );
+        let addr = common::start(config(dir.path(), &store)).await;
+
+        assert_eq!(put(addr, "one.txt", "k-first").await, 200);
+        assert_eq!(put(addr, "two.txt", "k-second").await, 401);
+
+        // Ensure the mtime changes even on coarse-grained filesystems
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        let both =
+            record("first", "k-first", "[]", "[]") + &record("second", "k-second", "[]", "[]");
+        std::fs::write(&keys_path, both).unwrap();
+
+        assert_eq!(put(addr, "two.txt", "k-second").await, 200);
+    }
+
+    /// Serve `GET` from a fixed map over RESP2
+    async fn fake_redis(entries: HashMap<String, String>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let entries = Arc::new(entries);
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let entries = Arc::clone(&entries);
+                tokio::spawn(async move {
+                    let mut stream = BufStream::new(socket);
+                    loop {
+                        let mut line = String::new();
+                        if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+                            return;
+                        }
+                        let argc: usize = line.trim()[1..].parse().unwrap();
+                        let mut args = Vec::new();
+                        for _ in 0..argc {
+                            line.clear();
+                            stream.read_line(&mut line).await.unwrap();
+                            let len: usize = line.trim()[1..].parse().unwrap();
+                            let mut arg = vec![0; len + 2];
+                            stream.read_exact(&mut arg).await.unwrap();
+                            arg.truncate(len);
+                            args.push(String::from_utf8(arg).unwrap());
+                        }
+                        let reply = match entries.get(&args[1]) {
+                            Some(value) if args[0] == "GET" => {
+                                format!("${}\r\n{}\r\n", value.len(), value)
+                            }
+                            _ => "$-1\r\n".to_string(),
+                        };
+                        stream.write_all(reply.as_bytes()).await.unwrap();
+                        stream.flush().await.unwrap();
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    fn request(api_key: &str) -> AuthRequest {
+        AuthRequest {
+            headers: [("x-api-key".to_string(), api_key.to_string())].into(),
+            query: None,
+            method: "PUT".into(),
+            path: "/uploads/reports/q1.csv".into(),
+            peer_certificates: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_redis_store_lookup() {
+        let record = serde_json::json!({
+            "id": "reporter",
+            "hash": hash_key("k-redis", None),
+            "prefixes": ["reports/"],
+        });
+        let addr = fake_redis(
+            [(
+                format!("mizuchi:apikey:{}", hash_key("k-redis", None)),
+                record.to_string(),
+            )]
+            .into(),
+        )
+        .await;
+
+        let client = RedisClient::from_url(&format!("redis://{}", addr)).unwrap();
+        let auth = ApiKeyAuthenticator::new("x-api-key", None)
+            .with_store(Arc::new(RedisKeyStore::new(client, "mizuchi:apikey:")))
+            .scoped_to_bucket("uploads", "/uploads");
+
+        let result = auth.authenticate(&request("k-redis")).await.unwrap();
+        assert_eq!(result.subject, "reporter");
+
+        // Second lookup reuses the connection
+        let err = auth.authenticate(&request("k-missing")).await.unwrap_err();
+        assert!(matches!(err, AuthError::InvalidToken(_)), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_redis_store_unavailable_fails_closed() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let client = RedisClient::from_url(&format!("redis://{}", addr)).unwrap();
+        let auth = ApiKeyAuthenticator::new("x-api-key", None)
+            .with_store(Arc::new(RedisKeyStore::new(client, "mizuchi:apikey:")));
+
+        let err = auth.authenticate(&request("k-redis")).await.unwrap_err();
+        assert!(matches!(err, AuthError::ConfigError(_)), "{}", err);
+    }
+}
@@ -0,0 +1,235 @@
+//! Auth Chain Integration Tests
+//!
+//! Tests for composing several authenticators per bucket.
+//!
+//! ## Test Coverage
+//!
+//! - Default chain order built from the configured authenticators
+//! - First-success: later authenticators accept what earlier ones cannot
+//! - First-success: the error from the authenticator that saw credentials wins
+//! - All-must-pass: every authenticator must accept, claims are merged
+//! - Chain order referencing an unconfigured authenticator is rejected
+//! - Anonymous fallback and bearer challenges on the upload endpoint
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use mizuchi_uploadr::auth::chain::AuthChain;
+    use mizuchi_uploadr::auth::mtls::MtlsAuthenticator;
+    use mizuchi_uploadr::auth::{AuthError, AuthRequest, Authenticator};
+    use mizuchi_uploadr::config::{BucketConfig, CertSubjectSource, Config};
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::CertificateDer;
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    const SECRET: &str = "chain-secret";
+
+    fn fixture(name: &str) -> Vec<u8> {
+        std::fs::read(format!(
+            "{}/tests/fixtures/mtls/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        ))
+        .unwrap()
+    }
+
+    fn token(exp_offset: i64) -> String {
+        let claims = serde_json::json!({
+            "sub": "jwt-user",
+            "exp": (chrono::Utc::now().timestamp() + exp_offset) as usize,
+            "iss": "https://auth.example.com",
+        });
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    fn request(bearer: Option<&str>, cert: Option<&str>) -> AuthRequest {
+        let mut headers = HashMap::new();
+        if let Some(token) = bearer {
+            headers.insert("authorization".to_string(), format!("Bearer {}", token));
+        }
+        AuthRequest {
+            headers,
+            query: None,
+            method: "PUT".into(),
+            path: "/uploads/file.txt".into(),
+            peer_certificates: cert
+                .map(|name| {
+                    vec![CertificateDer::from_pem_slice(&fixture(name))
+                        .unwrap()
+                        .to_vec()]
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    fn bucket(auth_yaml: &str) -> BucketConfig {
+        let auth: String = auth_yaml
+            .trim_start_matches('\n')
+            .lines()
+            .map(|line| format!("  {}\n", line))
+            .collect();
+        let yaml = format!(
+            "name: uploads\npath_prefix: /uploads\ns3:\n  bucket: uploads\n  region: us-east-1\nauth:\n{}",
+            auth
+        );
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    fn mtls() -> Arc<MtlsAuthenticator> {
+        Arc::new(MtlsAuthenticator::new(&fixture("ca.pem"), &[], CertSubjectSource::San).unwrap())
+    }
+
+    const JWT_AND_SIGV4: &str = r#"
+enabled: true
+jwt:
+  secret: chain-secret
+  algorithm: HS256
+sigv4:
+  service: s3
+  region: us-east-1
+"#;
+
+    #[test]
+    fn test_default_order() {
+        let chain = AuthChain::from_config(&bucket(JWT_AND_SIGV4), None).unwrap();
+        assert_eq!(chain.names().collect::<Vec<_>>(), vec!["sigv4", "jwt"]);
+    }
+
+    #[tokio::test]
+    async fn test_first_success_falls_through_to_jwt() {
+        let chain = AuthChain::from_config(&bucket(JWT_AND_SIGV4), None).unwrap();
+        let result = chain
+            .authenticate(&request(Some(&token(3600)), None))
+            .await
+            .unwrap();
+        assert_eq!(result.subject, "jwt-user");
+    }
+
+    #[tokio::test]
+    async fn test_first_success_reports_most_relevant_error() {
+        let chain = AuthChain::from_config(&bucket(JWT_AND_SIGV4), None).unwrap();
+
+        let err = chain
+            .authenticate(&request(Some(&token(-3600)), None))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AuthError::TokenExpired), "{}", err);
+
+        let err = chain.authenticate(&request(None, None)).await.unwrap_err();
+        assert!(matches!(err, AuthError::MissingAuth), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_all_must_pass() {
+        let config = bucket(
+            r#"
+enabled: true
+jwt:
+  secret: chain-secret
+  algorithm: HS256
+mtls:
+  ca_path: unused-here.pem
+chain:
+  mode: all_must_pass
+  order: [mtls, jwt]
+"#,
+        );
+        let chain = AuthChain::from_config(&config, Some(mtls())).unwrap();
+
+        let result = chain
+            .authenticate(&request(Some(&token(3600)), Some("client.pem")))
+            .await
+            .unwrap();
+        assert_eq!(result.subject, "uri:spiffe://example.org/uploader");
+        assert_eq!(result.claims["cn"], "uploader");
+        assert_eq!(result.claims["iss"], "https://auth.example.com");
+
+        let err = chain
+            .authenticate(&request(None, Some("client.pem")))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AuthError::MissingAuth), "{}", err);
+    }
+
+    #[test]
+    fn test_order_requires_configured_authenticator() {
+        let yaml = r#"
+server:
+  address: "127.0.0.1:0"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: uploads
+      region: us-east-1
+    auth:
+      enabled: true
+      chain:
+        order: [sigv4, anonymous]
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("sigv4"), "{}", err);
+    }
+
+    async fn start(root: &std::path::Path, order: &str) -> SocketAddr {
+        let bucket = format!(
+            "    auth:\n      enabled: true\n      jwt:\n        secret: {}\n        algorithm: HS256\n      chain:\n        order: {}",
+            SECRET, order
+        );
+        common::start(common::config(&common::local_bucket(
+            "uploads", root, &bucket,
+        )))
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_fallback_upload() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = start(dir.path(), "[jwt, anonymous]").await;
+
+        let response = reqwest::Client::new()
+            .put(format!("http://{}/uploads/anon.txt", addr))
+            .body("dropped off")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert!(dir.path().join("anon.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_missing_auth_gets_bearer_challenge() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = start(dir.path(), "[jwt]").await;
+        let client = reqwest::Client::new();
+
+        let response = client
+            .put(format!("http://{}/uploads/denied.txt", addr))
+            .body("denied")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+        assert_eq!(response.headers()["www-authenticate"], "Bearer");
+
+        let response = client
+            .put(format!("http://{}/uploads/allowed.txt", addr))
+            .bearer_auth(token(3600))
+            .body("allowed")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+}
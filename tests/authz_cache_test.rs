@@ -0,0 +1,156 @@
+//! Authorization Decision Cache Integration Tests
+//!
+//! Tests for the shared decision cache and its admin invalidation endpoint.
+//!
+//! ## Test Coverage
+//!
+//! - Cached OPA decisions are reused until invalidated
+//! - `DELETE /authz-cache?subject=...` on the admin API drops only that
+//!   subject's decisions
+//! - The admin API requires its token and is not served on the data-plane port
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use mizuchi_uploadr::config::Config;
+    use std::net::SocketAddr;
+    use std::path::Path;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const SECRET: &str = "cache-secret";
+    const ADMIN_TOKEN: &str = "admin-token";
+
+    fn config(root: &Path, opa_url: &str) -> Config {
+        let bucket = format!(
+            r#"    auth:
+      enabled: true
+      jwt:
+        secret: {SECRET}
+        algorithm: HS256
+    authz:
+      type: opa
+      url: "{opa_url}"
+      policy_path: mizuchi/allow
+      cache_ttl_secs: 300"#
+        );
+        common::with_admin(
+            common::config(&common::local_bucket("docs", root, &bucket)),
+            ADMIN_TOKEN,
+        )
+    }
+
+    async fn opa() -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/data/mizuchi/allow"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({ "result": true })),
+            )
+            .mount(&server)
+            .await;
+        server
+    }
+
+    async fn put(addr: SocketAddr, subject: &str) -> reqwest::StatusCode {
+        let claims = serde_json::json!({
+            "sub": subject,
+            "exp": (chrono::Utc::now().timestamp() + 3600) as usize,
+        });
+        let token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap();
+        let bearer = format!("Bearer {}", token);
+        common::put(
+            addr,
+            "/docs/readme.txt",
+            &[("Authorization", &bearer)],
+            "hello",
+        )
+        .await
+        .status()
+    }
+
+    /// Decision queries OPA received (startup health checks are GETs)
+    async fn opa_calls(server: &MockServer) -> usize {
+        server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .filter(|request| request.method == wiremock::http::Method::POST)
+            .count()
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_subject() {
+        let dir = tempfile::tempdir().unwrap();
+        let opa = opa().await;
+        let (addr, admin_addr) = common::start_with_admin(config(dir.path(), &opa.uri())).await;
+
+        assert_eq!(put(addr, "alice").await, 200);
+        assert_eq!(put(addr, "bob").await, 200);
+        assert_eq!(put(addr, "alice").await, 200);
+        assert_eq!(opa_calls(&opa).await, 2);
+
+        let response = reqwest::Client::new()
+            .delete(format!("http://{}/authz-cache?subject=alice", admin_addr))
+            .bearer_auth(ADMIN_TOKEN)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["removed"], 1);
+
+        // Alice is re-evaluated, Bob is still cached
+        assert_eq!(put(addr, "alice").await, 200);
+        assert_eq!(put(addr, "bob").await, 200);
+        assert_eq!(opa_calls(&opa).await, 3);
+    }
+
+    #[tokio::test]
+    async fn test_admin_token_required() {
+        let dir = tempfile::tempdir().unwrap();
+        let opa = opa().await;
+        let client = reqwest::Client::new();
+        let (addr, admin_addr) = common::start_with_admin(config(dir.path(), &opa.uri())).await;
+
+        let url = format!("http://{}/authz-cache", admin_addr);
+        let status = client.delete(&url).send().await.unwrap().status();
+        assert_eq!(status, 401);
+        let status = client
+            .delete(&url)
+            .bearer_auth("wrong")
+            .send()
+            .await
+            .unwrap()
+            .status();
+        assert_eq!(status, 401);
+
+        let status = client
+            .get(format!("http://{}/authz-cache", admin_addr))
+            .bearer_auth(ADMIN_TOKEN)
+            .send()
+            .await
+            .unwrap()
+            .status();
+        assert_eq!(status, 200);
+
+        // Not reachable through the data plane
+        let status = client
+            .delete(format!("http://{}/admin/authz-cache", addr))
+            .bearer_auth(ADMIN_TOKEN)
+            .send()
+            .await
+            .unwrap()
+            .status();
+        assert_eq!(status, 404);
+    }
+}
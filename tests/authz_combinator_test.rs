@@ -0,0 +1,223 @@
+//! Composite Authorizer Integration Tests
+//!
+//! Tests for `all_of` / `any_of` authorizer combinators.
+//!
+//! ## Test Coverage
+//!
+//! - `any_of`: a static allowlist OR OPA
+//! - `all_of`: a static policy AND OPA, short-circuiting before OPA
+//! - Nested combinators and empty combinator validation
+//! - One tracing span per evaluated leg, with its decision
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use mizuchi_uploadr::authz::{self, AuthzRequest};
+    use mizuchi_uploadr::config::{AuthzConfig, Config};
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::Layer;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const SECRET: &str = "combinator-secret";
+
+    async fn opa(allow: bool) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/data/mizuchi/allow"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({ "result": allow })),
+            )
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn authz_yaml(combinator: &str, opa_url: &str) -> String {
+        format!(
+            r#"
+type: {combinator}
+authorizers:
+  - type: static
+    rules:
+      - subjects: ["release-bot"]
+        effect: allow
+  - type: opa
+    url: "{opa_url}"
+    policy_path: mizuchi/allow
+"#
+        )
+    }
+
+    fn config(root: &Path, authz: &str) -> Config {
+        let bucket = format!(
+            "    auth:\n      enabled: true\n      jwt:\n        secret: {SECRET}\n        algorithm: HS256"
+        );
+        let mut config = common::config(&common::local_bucket("artifacts", root, &bucket));
+        config.buckets[0].authz = Some(serde_yaml::from_str(authz).unwrap());
+        config
+    }
+
+    async fn put(addr: SocketAddr, subject: &str) -> reqwest::StatusCode {
+        let claims = serde_json::json!({
+            "sub": subject,
+            "exp": (chrono::Utc::now().timestamp() + 3600) as usize,
+        });
+        let token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap();
+        let bearer = format!("Bearer {}", token);
+        common::put(
+            addr,
+            "/artifacts/build.tar",
+            &[("Authorization", &bearer)],
+            "artifact",
+        )
+        .await
+        .status()
+    }
+
+    /// Decision queries OPA received (startup health checks are GETs)
+    async fn opa_calls(server: &MockServer) -> usize {
+        server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .filter(|request| request.method == wiremock::http::Method::POST)
+            .count()
+    }
+
+    #[tokio::test]
+    async fn test_any_of_allowlist_or_opa() {
+        let dir = tempfile::tempdir().unwrap();
+        let denying_opa = opa(false).await;
+        let addr = common::start(config(
+            dir.path(),
+            &authz_yaml("any_of", &denying_opa.uri()),
+        ))
+        .await;
+
+        // The allowlist wins without asking OPA
+        assert_eq!(put(addr, "release-bot").await, 200);
+        assert_eq!(opa_calls(&denying_opa).await, 0);
+
+        assert_eq!(put(addr, "someone").await, 403);
+        assert_eq!(opa_calls(&denying_opa).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_all_of_static_and_opa() {
+        let dir = tempfile::tempdir().unwrap();
+        let allowing_opa = opa(true).await;
+        let addr = common::start(config(
+            dir.path(),
+            &authz_yaml("all_of", &allowing_opa.uri()),
+        ))
+        .await;
+
+        // The static leg denies first; OPA is never called
+        assert_eq!(put(addr, "someone").await, 403);
+        assert_eq!(opa_calls(&allowing_opa).await, 0);
+
+        assert_eq!(put(addr, "release-bot").await, 200);
+        assert_eq!(opa_calls(&allowing_opa).await, 1);
+    }
+
+    #[test]
+    fn test_empty_combinator_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = "type: any_of\nauthorizers:\n  - type: all_of\n    authorizers: []";
+        assert!(config(dir.path(), nested).validate().is_err());
+    }
+
+    /// Records the fields of every `authz.leg` span
+    #[derive(Clone, Default)]
+    struct LegRecorder(Arc<Mutex<HashMap<u64, HashMap<String, String>>>>);
+
+    struct Fields<'a>(&'a mut HashMap<String, String>);
+
+    impl Visit for Fields<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl<S: tracing::Subscriber> Layer<S> for LegRecorder {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _ctx: Context<'_, S>) {
+            if attrs.metadata().name() == "authz.leg" {
+                let mut fields = HashMap::new();
+                attrs.record(&mut Fields(&mut fields));
+                self.0.lock().unwrap().insert(id.into_u64(), fields);
+            }
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+            if let Some(fields) = self.0.lock().unwrap().get_mut(&id.into_u64()) {
+                values.record(&mut Fields(fields));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_leg_spans() {
+        let recorder = LegRecorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let config: AuthzConfig = serde_yaml::from_str(
+            r#"
+type: all_of
+authorizers:
+  - type: static
+    default: allow
+  - type: static
+  - type: static
+    default: allow
+"#,
+        )
+        .unwrap();
+        let authorizer = authz::from_config(&config).unwrap();
+        let request = AuthzRequest {
+            subject: "someone".into(),
+            action: "upload".into(),
+            resource: "bucket/artifacts/build.tar".into(),
+            context: HashMap::new(),
+        };
+        assert!(!authorizer.authorize(&request).await.unwrap());
+
+        let mut legs: Vec<_> = recorder
+            .0
+            .lock()
+            .unwrap()
+            .values()
+            .map(|f| (f["authz.leg"].clone(), f["authz.decision"].clone()))
+            .collect();
+        legs.sort();
+        // The third leg is never evaluated
+        assert_eq!(
+            legs,
+            vec![
+                ("0.static".to_string(), "allow".to_string()),
+                ("1.static".to_string(), "deny".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! Authorization Context Integration Tests
+//!
+//! Tests that policies receive the upload's object metadata in
+//! `AuthzRequest.context.object`.
+//!
+//! ## Test Coverage
+//!
+//! - OPA input carries key, key prefix, size and content type
+//! - Size-based rules ("no large videos for non-premium users") with mapped claims
+//! - Multipart part uploads are reported with their part number
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use mizuchi_uploadr::config::Config;
+    use std::net::SocketAddr;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    const SECRET: &str = "context-secret";
+    const MAX_FREE_VIDEO: u64 = 16;
+
+    fn config(root: &Path, opa_url: &str) -> Config {
+        let bucket = format!(
+            r#"    auth:
+      enabled: true
+      jwt:
+        secret: {SECRET}
+        algorithm: HS256
+      claim_mapping:
+        context:
+          tier: plan.tier
+    authz:
+      type: opa
+      url: "{opa_url}"
+      policy_path: mizuchi/allow"#
+        );
+        common::config(&common::local_bucket("media", root, &bucket))
+    }
+
+    /// Mock OPA: deny videos over the free limit unless the tier is premium
+    async fn opa(inputs: Arc<Mutex<Vec<serde_json::Value>>>) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/data/mizuchi/allow"))
+            .respond_with(move |request: &Request| {
+                let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+                let input = body["input"].clone();
+                inputs.lock().unwrap().push(input.clone());
+
+                let object = &input["object"];
+                let is_video = object["content_type"]
+                    .as_str()
+                    .is_some_and(|t| t.starts_with("video/"));
+                let large = object["size"].as_u64().is_none_or(|s| s > MAX_FREE_VIDEO);
+                let premium = input["tier"] == "premium";
+                let allowed = !(is_video && large && !premium);
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({ "result": allowed }))
+            })
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn token(tier: &str) -> String {
+        let claims = serde_json::json!({
+            "sub": "creator",
+            "exp": (chrono::Utc::now().timestamp() + 3600) as usize,
+            "plan": {"tier": tier},
+        });
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    async fn put(
+        addr: SocketAddr,
+        key: &str,
+        tier: &str,
+        content_type: &str,
+        body: &'static str,
+    ) -> reqwest::StatusCode {
+        let path = format!("/media/{}", key);
+        let bearer = format!("Bearer {}", token(tier));
+        let headers = [
+            ("Authorization", bearer.as_str()),
+            ("Content-Type", content_type),
+        ];
+        common::put(addr, &path, &headers, body).await.status()
+    }
+
+    #[tokio::test]
+    async fn test_object_metadata_in_opa_input() {
+        let inputs = Arc::new(Mutex::new(Vec::new()));
+        let opa = opa(Arc::clone(&inputs)).await;
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path(), &opa.uri())).await;
+
+        let status = put(addr, "clips/2026/intro.mp4", "free", "video/mp4", "short").await;
+        assert_eq!(status, 200);
+
+        let input = inputs.lock().unwrap()[0].clone();
+        assert_eq!(input["subject"], "creator");
+        assert_eq!(input["resource"], "bucket/media/clips/2026/intro.mp4");
+        assert_eq!(input["tier"], "free");
+        assert_eq!(
+            input["object"],
+            serde_json::json!({
+                "key": "clips/2026/intro.mp4",
+                "key_prefix": "clips/2026/",
+                "size": 5,
+                "content_type": "video/mp4",
+                "upload_type": "simple",
+                "part_number": null,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_size_rule_by_tier() {
+        let inputs = Arc::new(Mutex::new(Vec::new()));
+        let opa = opa(inputs).await;
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path(), &opa.uri())).await;
+
+        let long = "a video well over the free limit";
+        assert_eq!(put(addr, "free.mp4", "free", "video/mp4", long).await, 403);
+        assert_eq!(put(addr, "doc.txt", "free", "text/plain", long).await, 200);
+        assert_eq!(
+            put(addr, "paid.mp4", "premium", "video/mp4", long).await,
+            200
+        );
+        assert!(!dir.path().join("free.mp4").exists());
+    }
+
+    #[tokio::test]
+    async fn test_multipart_part_number() {
+        let inputs = Arc::new(Mutex::new(Vec::new()));
+        let opa = opa(Arc::clone(&inputs)).await;
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path(), &opa.uri())).await;
+
+        reqwest::Client::new()
+            .put(format!(
+                "http://{}/media/big.bin?partNumber=7&uploadId=upload-1",
+                addr
+            ))
+            .bearer_auth(token("premium"))
+            .body("part")
+            .send()
+            .await
+            .unwrap();
+
+        let input = inputs.lock().unwrap()[0].clone();
+        assert_eq!(input["object"]["upload_type"], "multipart");
+        assert_eq!(input["object"]["part_number"], 7);
+    }
+}
@@ -0,0 +1,139 @@
+//! Authorizer Failure Mode Integration Tests
+//!
+//! Tests for the per-bucket `authz_failure` setting when OPA is unreachable.
+//!
+//! ## Test Coverage
+//!
+//! - `fail_closed` (default) rejects uploads when OPA fails
+//! - `fail_open` allows them and counts degraded decisions
+//! - `fail_open` with a grace period reuses the last-known denial
+//! - Grace periods require `fail_open`
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::metrics::AUTHZ_DEGRADED_DECISIONS;
+    use std::net::SocketAddr;
+    use std::path::Path;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const SECRET: &str = "failure-secret";
+
+    fn config(root: &Path, bucket: &str, opa_url: &str, failure: &str) -> Config {
+        let buckets = format!(
+            r#"  - name: {bucket}
+    path_prefix: /files
+    s3:
+      bucket: files
+      region: us-east-1
+    storage:
+      type: local
+      root: "{root}"
+    auth:
+      enabled: true
+      jwt:
+        secret: {SECRET}
+        algorithm: HS256
+    authz:
+      type: opa
+      url: "{opa_url}"
+      policy_path: mizuchi/allow
+    authz_failure: {failure}
+"#,
+            root = root.display(),
+        );
+        common::config(&buckets)
+    }
+
+    async fn put(addr: SocketAddr) -> reqwest::StatusCode {
+        let claims = serde_json::json!({
+            "sub": "alice",
+            "exp": (chrono::Utc::now().timestamp() + 3600) as usize,
+        });
+        let token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap();
+        let bearer = format!("Bearer {}", token);
+        common::put(
+            addr,
+            "/files/report.csv",
+            &[("Authorization", &bearer)],
+            "a,b",
+        )
+        .await
+        .status()
+    }
+
+    /// OPA returning `status` (and `allow` for 200) to every query
+    async fn opa(server: &MockServer, status: u16, allow: bool) {
+        server.reset().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/data/mizuchi/allow"))
+            .respond_with(
+                ResponseTemplate::new(status).set_body_json(serde_json::json!({ "result": allow })),
+            )
+            .mount(server)
+            .await;
+    }
+
+    fn degraded(bucket: &str, outcome: &str) -> f64 {
+        AUTHZ_DEGRADED_DECISIONS
+            .with_label_values(&[bucket, outcome])
+            .get()
+    }
+
+    #[tokio::test]
+    async fn test_fail_closed_by_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = MockServer::start().await;
+        opa(&server, 500, false).await;
+        let addr = common::start(config(dir.path(), "closed", &server.uri(), "{}")).await;
+
+        assert_eq!(put(addr).await, 500);
+        assert_eq!(degraded("closed", "fail_closed"), 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_fail_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = MockServer::start().await;
+        opa(&server, 503, false).await;
+        let failure = "{ mode: fail_open }";
+        let addr = common::start(config(dir.path(), "open", &server.uri(), failure)).await;
+
+        assert_eq!(put(addr).await, 200);
+        assert_eq!(degraded("open", "fail_open"), 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_fail_open_reuses_last_known_decision() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = MockServer::start().await;
+        opa(&server, 200, false).await;
+        let failure = "{ mode: fail_open, grace_period_secs: 300 }";
+        let addr = common::start(config(dir.path(), "grace", &server.uri(), failure)).await;
+
+        assert_eq!(put(addr).await, 403);
+
+        // OPA goes down: the denial still applies
+        opa(&server, 500, false).await;
+        assert_eq!(put(addr).await, 403);
+        assert_eq!(degraded("grace", "last_known"), 1.0);
+    }
+
+    #[test]
+    fn test_grace_period_requires_fail_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let failure = "{ grace_period_secs: 300 }";
+        let config = config(dir.path(), "invalid", "http://localhost:8181", failure);
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,258 @@
+//! Authorizer Health Check Integration Tests
+//!
+//! Tests for `Authorizer::health_check` of OPA and OpenFGA against mock
+//! servers, and for how failing checks surface in `GET /ready`.
+//!
+//! ## Test Coverage
+//!
+//! - OPA policy paths are found as data or as rules of loaded policies
+//! - Undefined OPA policy paths name the loaded packages
+//! - Missing OpenFGA stores and models are reported
+//! - A failing `fail_closed` authorizer makes the server unready
+//! - A failing `fail_open` authorizer only degrades readiness
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::authz::opa::OpaAuthorizer;
+    use mizuchi_uploadr::authz::openfga::OpenFgaAuthorizer;
+    use mizuchi_uploadr::authz::{Authorizer, AuthzError};
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::server::pingora::PingoraServer;
+    use std::path::Path;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// `GET /v1/policies` body of a policy in package `mizuchi.uploads`
+    fn policies() -> serde_json::Value {
+        serde_json::json!({
+            "result": [{
+                "id": "uploads.rego",
+                "ast": {
+                    "package": {"path": [
+                        {"type": "var", "value": "data"},
+                        {"type": "string", "value": "mizuchi"},
+                        {"type": "string", "value": "uploads"},
+                    ]},
+                    "rules": [{"head": {"name": "allow"}}],
+                },
+            }]
+        })
+    }
+
+    /// OPA without base data, serving the policies above
+    async fn opa() -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/policies"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(policies()))
+            .mount(&server)
+            .await;
+        // Undefined documents have no `result`
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn opa_authorizer(opa: &MockServer, policy_path: &str) -> OpaAuthorizer {
+        OpaAuthorizer::builder()
+            .url(&opa.uri())
+            .policy_path(policy_path)
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_opa_policy_path_found() {
+        let opa = opa().await;
+
+        opa_authorizer(&opa, "mizuchi/uploads/allow")
+            .health_check()
+            .await
+            .unwrap();
+        opa_authorizer(&opa, "mizuchi/uploads")
+            .health_check()
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_opa_policy_path_found_as_data() {
+        let opa = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/data/mizuchi/allow"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "result": false
+            })))
+            .expect(1)
+            .mount(&opa)
+            .await;
+
+        opa_authorizer(&opa, "mizuchi/allow")
+            .health_check()
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_opa_undefined_policy_path() {
+        let opa = opa().await;
+
+        let err = opa_authorizer(&opa, "mizuchi/allow")
+            .health_check()
+            .await
+            .unwrap_err();
+        match err {
+            AuthzError::ConfigError(message) => {
+                assert!(message.contains("'mizuchi/allow'"), "{}", message);
+                assert!(message.contains("mizuchi/uploads"), "{}", message);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_opa_unreachable() {
+        let err = OpaAuthorizer::builder()
+            .url("http://127.0.0.1:1")
+            .policy_path("mizuchi/allow")
+            .build()
+            .unwrap()
+            .health_check()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AuthzError::BackendError(_)), "{:?}", err);
+    }
+
+    /// OpenFGA knowing store `store123` with model `model456`
+    async fn openfga() -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/stores/store123"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "store123",
+                "name": "uploads",
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/stores/store123/authorization-models/model456"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "authorization_model": {"id": "model456"}
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+                "code": "store_id_not_found"
+            })))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn openfga_authorizer(
+        openfga: &MockServer,
+        store_id: &str,
+        model_id: Option<&str>,
+    ) -> OpenFgaAuthorizer {
+        let builder = OpenFgaAuthorizer::builder()
+            .url(&openfga.uri())
+            .store_id(store_id);
+        match model_id {
+            Some(id) => builder.authorization_model_id(id),
+            None => builder,
+        }
+        .build()
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_openfga_store_and_model() {
+        let openfga = openfga().await;
+
+        openfga_authorizer(&openfga, "store123", None)
+            .health_check()
+            .await
+            .unwrap();
+        openfga_authorizer(&openfga, "store123", Some("model456"))
+            .health_check()
+            .await
+            .unwrap();
+
+        let err = openfga_authorizer(&openfga, "typo", Some("model456"))
+            .health_check()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, AuthzError::ConfigError(ref e) if e.contains("store 'typo'")),
+            "{:?}",
+            err
+        );
+
+        let err = openfga_authorizer(&openfga, "store123", Some("stale"))
+            .health_check()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, AuthzError::ConfigError(ref e) if e.contains("model 'stale'")),
+            "{:?}",
+            err
+        );
+    }
+
+    fn config(root: &Path, opa_url: &str, failure: &str) -> Config {
+        let bucket = format!(
+            r#"    auth:
+      enabled: true
+      jwt:
+        secret: health-secret
+        algorithm: HS256
+    authz:
+      type: opa
+      url: "{opa_url}"
+      policy_path: mizuchi/allow
+    authz_failure: {failure}"#
+        );
+        common::config(&common::local_bucket("files", root, &bucket))
+    }
+
+    async fn ready(config: Config) -> (reqwest::StatusCode, serde_json::Value) {
+        config.validate().unwrap();
+        let server = PingoraServer::new(config).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move { server.run().await });
+
+        let response = reqwest::get(format!("http://{}/ready", addr))
+            .await
+            .unwrap();
+        (response.status(), response.json().await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_failing_authorizer_makes_server_unready() {
+        let opa = opa().await;
+        let dir = tempfile::tempdir().unwrap();
+
+        let (status, body) = ready(config(dir.path(), &opa.uri(), "{mode: fail_closed}")).await;
+        assert_eq!(status, 503);
+        assert_eq!(body["status"], "unready");
+        let reason = body["authz"]["files"].as_str().unwrap();
+        assert!(reason.contains("'mizuchi/allow'"), "{}", reason);
+    }
+
+    #[tokio::test]
+    async fn test_failing_fail_open_authorizer_degrades() {
+        let opa = opa().await;
+        let dir = tempfile::tempdir().unwrap();
+
+        let (status, body) = ready(config(dir.path(), &opa.uri(), "{mode: fail_open}")).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["status"], "degraded");
+        assert!(body["authz"]["files"].is_string());
+    }
+}
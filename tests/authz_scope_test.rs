@@ -0,0 +1,135 @@
+//! Scope Rule Integration Tests
+//!
+//! Tests for `auth.claim_mapping.scopes`, which grant actions on key
+//! prefixes by token scope, enforced by the server ahead of a mock OPA.
+//!
+//! ## Test Coverage
+//!
+//! - Uploads under a prefix granted by a scope reach the authorizer
+//! - Uploads outside granted prefixes, or without scopes, get `403`
+//!   without the authorizer being asked
+//! - Scope mismatches are counted by reason
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::metrics::AUTHZ_SCOPE_MISMATCHES;
+    use std::net::SocketAddr;
+    use std::path::Path;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const SECRET: &str = "scope-secret";
+
+    fn token(scope: Option<&str>) -> String {
+        let mut claims = serde_json::json!({
+            "sub": "alice",
+            "exp": chrono::Utc::now().timestamp() + 3600,
+        });
+        if let Some(scope) = scope {
+            claims["scope"] = scope.into();
+        }
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    fn config(root: &Path, opa_url: &str) -> Config {
+        let bucket = format!(
+            r#"    auth:
+      enabled: true
+      jwt:
+        secret: {SECRET}
+        algorithm: HS256
+      claim_mapping:
+        scopes:
+          - scope: "upload:images"
+            prefix: images/
+          - scope: "read:images"
+            action: read
+            prefix: images/
+    authz:
+      type: opa
+      url: "{opa_url}"
+      policy_path: mizuchi/allow"#
+        );
+        common::config(&common::local_bucket("scoped", root, &bucket))
+    }
+
+    async fn server(root: &Path, opa: &MockServer) -> SocketAddr {
+        common::start(config(root, &opa.uri())).await
+    }
+
+    async fn opa() -> MockServer {
+        let opa = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/data/mizuchi/allow"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "result": true
+            })))
+            .mount(&opa)
+            .await;
+        opa
+    }
+
+    async fn put(addr: SocketAddr, key: &str, scope: Option<&str>) -> u16 {
+        reqwest::Client::new()
+            .put(format!("http://{}/scoped/{}", addr, key))
+            .bearer_auth(token(scope))
+            .body("hello")
+            .send()
+            .await
+            .unwrap()
+            .status()
+            .as_u16()
+    }
+
+    /// Decision queries, leaving out the startup health check
+    async fn queries(opa: &MockServer) -> usize {
+        let requests = opa.received_requests().await.unwrap();
+        requests
+            .iter()
+            .filter(|request| request.method == wiremock::http::Method::POST)
+            .count()
+    }
+
+    fn mismatches(reason: &str) -> f64 {
+        AUTHZ_SCOPE_MISMATCHES
+            .with_label_values(&["scoped", reason])
+            .get()
+    }
+
+    #[tokio::test]
+    async fn test_scopes_gate_uploads_before_authorizer() {
+        let opa = opa().await;
+        let dir = tempfile::tempdir().unwrap();
+        let addr = server(dir.path(), &opa).await;
+        let before = ["missing", "action", "prefix"].map(mismatches);
+
+        assert_eq!(
+            put(addr, "images/cat.png", Some("openid upload:images")).await,
+            200
+        );
+        assert_eq!(queries(&opa).await, 1);
+
+        assert_eq!(
+            put(addr, "docs/report.pdf", Some("upload:images")).await,
+            403
+        );
+        assert_eq!(put(addr, "images/cat.png", Some("read:images")).await, 403);
+        assert_eq!(put(addr, "images/cat.png", None).await, 403);
+        assert_eq!(queries(&opa).await, 1);
+
+        let after = ["missing", "action", "prefix"].map(mismatches);
+        for (before, after) in before.iter().zip(after) {
+            assert!(after > *before);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Authorization Single-Flight and Negative Caching Integration Tests
+//!
+//! Tests for coalescing of concurrent identical checks and for the separate
+//! cache TTL of denials, against mock OPA and OpenFGA servers.
+//!
+//! ## Test Coverage
+//!
+//! - Concurrent identical OPA checks make one query
+//! - Checks sharing a query all report its decision ID
+//! - Concurrent identical OpenFGA checks make one call, different ones don't
+//! - Denials expire after `deny_cache_ttl` while allows stay cached
+//! - A zero `deny_cache_ttl` doesn't cache denials
+
+#[cfg(test)]
+mod tests {
+    use mizuchi_uploadr::authz::opa::decision_log::{self, DecisionLog};
+    use mizuchi_uploadr::authz::opa::OpaAuthorizer;
+    use mizuchi_uploadr::authz::openfga::OpenFgaAuthorizer;
+    use mizuchi_uploadr::authz::{Authorizer, AuthzRequest};
+    use mizuchi_uploadr::config::OpaDecisionLogConfig;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// OPA allowing `alice` and denying everyone else, answering after
+    /// `delay`
+    async fn opa(delay: Duration) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/data/mizuchi/allow"))
+            .and(body_partial_json(serde_json::json!({
+                "input": {"subject": "alice"}
+            })))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"result": true}))
+                    .set_delay(delay),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/data/mizuchi/allow"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"result": false}))
+                    .set_delay(delay),
+            )
+            .mount(&server)
+            .await;
+        server
+    }
+
+    async fn calls(server: &MockServer) -> usize {
+        server.received_requests().await.unwrap().len()
+    }
+
+    fn request(subject: &str) -> AuthzRequest {
+        AuthzRequest {
+            subject: subject.into(),
+            action: "upload".into(),
+            resource: "bucket/uploads/report.pdf".into(),
+            context: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_opa_checks_share_one_query() {
+        let opa = opa(Duration::from_millis(200)).await;
+        let log = Arc::new(DecisionLog::new(&OpaDecisionLogConfig::default()));
+        let authorizer = OpaAuthorizer::builder()
+            .url(&opa.uri())
+            .policy_path("mizuchi/allow")
+            .decision_log(log)
+            .build()
+            .unwrap();
+
+        let alice = request("alice");
+        let results = futures::future::join_all(
+            (0..8).map(|_| decision_log::collect(authorizer.authorize(&alice))),
+        )
+        .await;
+
+        assert_eq!(calls(&opa).await, 1);
+        let (_, first_ids) = &results[0];
+        assert_eq!(first_ids.len(), 1);
+        for (allowed, ids) in &results {
+            assert!(allowed.as_ref().unwrap());
+            assert_eq!(ids, first_ids);
+        }
+
+        // Once answered, the next check queries again (no cache configured)
+        authorizer.authorize(&request("alice")).await.unwrap();
+        assert_eq!(calls(&opa).await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_openfga_checks_share_one_call() {
+        let openfga = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/stores/store123/check"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"allowed": true}))
+                    .set_delay(Duration::from_millis(200)),
+            )
+            .mount(&openfga)
+            .await;
+        let authorizer = OpenFgaAuthorizer::builder()
+            .url(&openfga.uri())
+            .store_id("store123")
+            .build()
+            .unwrap();
+
+        let alice = request("alice");
+        let bob = request("bob");
+        let checks = (0..6).map(|i| authorizer.authorize(if i % 2 == 0 { &alice } else { &bob }));
+        let results = futures::future::join_all(checks).await;
+
+        assert!(results.into_iter().all(|allowed| allowed.unwrap()));
+        assert_eq!(calls(&openfga).await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_denials_expire_before_allows() {
+        let opa = opa(Duration::ZERO).await;
+        let authorizer = OpaAuthorizer::builder()
+            .url(&opa.uri())
+            .policy_path("mizuchi/allow")
+            .cache_ttl(Duration::from_secs(60))
+            .deny_cache_ttl(Duration::from_millis(100))
+            .build()
+            .unwrap();
+
+        assert!(authorizer.authorize(&request("alice")).await.unwrap());
+        assert!(!authorizer.authorize(&request("bob")).await.unwrap());
+        assert!(authorizer.authorize(&request("alice")).await.unwrap());
+        assert!(!authorizer.authorize(&request("bob")).await.unwrap());
+        assert_eq!(calls(&opa).await, 2);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(authorizer.authorize(&request("alice")).await.unwrap());
+        assert!(!authorizer.authorize(&request("bob")).await.unwrap());
+        assert_eq!(calls(&opa).await, 3);
+    }
+
+    #[tokio::test]
+    async fn test_zero_deny_cache_ttl_does_not_cache_denials() {
+        let opa = opa(Duration::ZERO).await;
+        let authorizer = OpaAuthorizer::builder()
+            .url(&opa.uri())
+            .policy_path("mizuchi/allow")
+            .cache_ttl(Duration::from_secs(60))
+            .deny_cache_ttl(Duration::ZERO)
+            .build()
+            .unwrap();
+
+        for _ in 0..3 {
+            assert!(!authorizer.authorize(&request("bob")).await.unwrap());
+        }
+        assert_eq!(calls(&opa).await, 3);
+    }
+}
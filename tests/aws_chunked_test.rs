@@ -0,0 +1,108 @@
+//! aws-chunked Upload Integration Tests
+//!
+//! Tests for uploads sent with `Content-Encoding: aws-chunked` through
+//! `PingoraServer`.
+//!
+//! ## Test Coverage
+//!
+//! - Chunk framing and signatures are removed before the object is stored
+//! - Uploads whose trailer checksum does not match are rejected and not stored
+//! - Unsupported trailer checksums are rejected
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::config::Config;
+    use std::net::SocketAddr;
+    use std::path::Path;
+
+    fn config(root: &Path) -> Config {
+        common::config(&common::local_bucket("uploads", root, ""))
+    }
+
+    async fn put_chunked(
+        addr: SocketAddr,
+        headers: &[(&str, &str)],
+        body: &'static str,
+    ) -> reqwest::Response {
+        let mut request = reqwest::Client::new()
+            .put(format!("http://{}/uploads/a.txt", addr))
+            .header("content-encoding", "aws-chunked")
+            .body(body);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.send().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_signed_chunks_stored_without_framing() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path())).await;
+
+        let response = put_chunked(
+            addr,
+            &[
+                ("x-amz-content-sha256", "STREAMING-AWS4-HMAC-SHA256-PAYLOAD"),
+                ("x-amz-decoded-content-length", "11"),
+            ],
+            "6;chunk-signature=aa\r\nhello \r\n5;chunk-signature=bb\r\nworld\r\n\
+             0;chunk-signature=cc\r\n\r\n",
+        )
+        .await;
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            std::fs::read(dir.path().join("a.txt")).unwrap(),
+            b"hello world"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trailer_checksum_verified() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path())).await;
+        let headers = [
+            ("x-amz-content-sha256", "STREAMING-UNSIGNED-PAYLOAD-TRAILER"),
+            ("x-amz-trailer", "x-amz-checksum-crc32"),
+        ];
+
+        let response = put_chunked(
+            addr,
+            &headers,
+            "9\r\n123456780\r\n0\r\nx-amz-checksum-crc32:y/Q5Jg==\r\n\r\n",
+        )
+        .await;
+        assert_eq!(response.status(), 400);
+        assert!(!dir.path().join("a.txt").exists());
+
+        let response = put_chunked(
+            addr,
+            &headers,
+            "9\r\n123456789\r\n0\r\nx-amz-checksum-crc32:y/Q5Jg==\r\n\r\n",
+        )
+        .await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            std::fs::read(dir.path().join("a.txt")).unwrap(),
+            b"123456789"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_trailer_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path())).await;
+
+        let response = put_chunked(
+            addr,
+            &[("x-amz-trailer", "x-amz-checksum-md5")],
+            "0\r\n\r\n",
+        )
+        .await;
+
+        assert_eq!(response.status(), 400);
+    }
+}
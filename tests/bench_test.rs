@@ -0,0 +1,72 @@
+//! Load Generator Integration Tests
+//!
+//! Tests for `mizuchi_uploadr::bench` against a `PingoraServer` with local
+//! storage.
+//!
+//! ## Test Coverage
+//!
+//! - Every request uploads an object of the requested size under a new key
+//! - Failed uploads are counted
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::bench::{self, BenchOptions};
+    use mizuchi_uploadr::config::Config;
+    use std::path::Path;
+
+    fn config(root: &Path) -> Config {
+        common::config(&common::local_bucket("uploads", root, ""))
+    }
+
+    #[tokio::test]
+    async fn test_bench_uploads_objects() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path())).await;
+        let options = BenchOptions {
+            target: format!("http://{}/uploads", addr),
+            size: 4096,
+            concurrency: 4,
+            requests: 20,
+            ..Default::default()
+        };
+
+        let metrics = bench::run(&reqwest::Client::new(), &options).await;
+
+        assert_eq!(metrics.successful_requests, 20);
+        assert_eq!(metrics.failed_requests, 0);
+        assert_eq!(metrics.total_bytes, 20 * 4096);
+        assert_eq!(metrics.latencies_us.len(), 20);
+
+        let files: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .collect();
+        assert_eq!(files.len(), 20);
+        for file in files {
+            assert!(file.file_name().to_string_lossy().starts_with("bench-"));
+            assert_eq!(file.metadata().unwrap().len(), 4096);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bench_counts_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path())).await;
+        let options = BenchOptions {
+            target: format!("http://{}/unknown", addr),
+            size: 16,
+            concurrency: 2,
+            requests: 5,
+            ..Default::default()
+        };
+
+        let metrics = bench::run(&reqwest::Client::new(), &options).await;
+
+        assert_eq!(metrics.successful_requests, 0);
+        assert_eq!(metrics.failed_requests, 5);
+        assert_eq!(metrics.success_rate(), 0.0);
+    }
+}
@@ -0,0 +1,96 @@
+//! Body Length Integration Tests
+//!
+//! Tests for upload bodies checked against their declared `Content-Length`.
+//!
+//! ## Test Coverage
+//!
+//! - Bodies matching their Content-Length are stored
+//! - Truncated bodies are rejected and nothing is stored
+//! - Overlong bodies are rejected and nothing is stored
+//! - Mismatches are counted by kind
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use http_body_util::Full;
+    use hyper::{Request, StatusCode};
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::metrics::BODY_LENGTH_MISMATCHES;
+    use mizuchi_uploadr::UploadService;
+    use std::path::Path;
+    use tower_service::Service;
+
+    async fn service(root: &Path, bucket: &str) -> UploadService {
+        let yaml = format!(
+            r#"
+server:
+  address: "127.0.0.1:0"
+buckets:
+  - name: {bucket}
+    path_prefix: /uploads
+    s3:
+      bucket: uploads
+      region: us-east-1
+    storage:
+      type: local
+      root: "{}"
+"#,
+            root.display()
+        );
+        let config: Config = serde_yaml::from_str(&yaml).unwrap();
+        UploadService::builder()
+            .config(config)
+            .build_service()
+            .await
+            .unwrap()
+    }
+
+    async fn upload(service: &mut UploadService, body: &'static str, length: usize) -> StatusCode {
+        let request = Request::put("/uploads/a.txt")
+            .header("Content-Length", length)
+            .body(Full::new(Bytes::from(body)))
+            .unwrap();
+        service.call(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_matching_length_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut service = service(dir.path(), "length-ok").await;
+
+        let status = upload(&mut service, "hello", 5).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(std::fs::read(dir.path().join("a.txt")).unwrap(), b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_truncated_body_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut service = service(dir.path(), "length-truncated").await;
+
+        let status = upload(&mut service, "hel", 5).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!dir.path().join("a.txt").exists());
+        let truncated = BODY_LENGTH_MISMATCHES
+            .with_label_values(&["length-truncated", "truncated"])
+            .get();
+        assert_eq!(truncated, 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_overlong_body_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut service = service(dir.path(), "length-overlong").await;
+
+        let status = upload(&mut service, "hello world", 5).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!dir.path().join("a.txt").exists());
+        let overlong = BODY_LENGTH_MISMATCHES
+            .with_label_values(&["length-overlong", "overlong"])
+            .get();
+        assert_eq!(overlong, 1.0);
+    }
+}
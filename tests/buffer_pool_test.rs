@@ -0,0 +1,86 @@
+//! Upload Buffer Pool Integration Tests
+//!
+//! Tests for `server.buffer_pool`: single-PUT upload bodies received into
+//! pooled buffers that are reused by later uploads.
+//!
+//! ## Test Coverage
+//!
+//! - Buffers are reused by later uploads of the same size
+//! - A reused buffer holds only the new body, not the previous one
+//! - Pool occupancy is exported as metrics
+
+#[cfg(test)]
+mod tests {
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::metrics::{
+        BUFFER_POOL_ACQUIRES, BUFFER_POOL_IDLE_BUFFERS, BUFFER_POOL_IN_USE_BYTES,
+    };
+    use mizuchi_uploadr::server::pingora::PingoraServer;
+    use std::path::Path;
+
+    fn config(root: &Path) -> Config {
+        let yaml = format!(
+            r#"
+server:
+  address: "127.0.0.1:0"
+  zero_copy:
+    pipe_buffer_size: 4096
+  buffer_pool:
+    max_idle_bytes: 65536
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: uploads
+      region: us-east-1
+    storage:
+      type: local
+      root: "{root}"
+"#,
+            root = root.display(),
+        );
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    fn reused() -> f64 {
+        BUFFER_POOL_ACQUIRES.with_label_values(&["reused"]).get()
+    }
+
+    #[tokio::test]
+    async fn test_uploads_reuse_buffers() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path());
+        config.validate().unwrap();
+        let server = PingoraServer::new(config).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move { server.run().await });
+
+        let client = reqwest::Client::new();
+        let put = |name: &str, body: Vec<u8>| {
+            client
+                .put(format!("http://{}/uploads/{}", addr, name))
+                .body(body)
+                .send()
+        };
+
+        let before = reused();
+        let status = put("first.bin", vec![b'a'; 6000]).await.unwrap().status();
+        assert_eq!(status, 200);
+        assert!(BUFFER_POOL_IDLE_BUFFERS.get() >= 1);
+
+        // Needs the same two chunks, so gets the first upload's buffer
+        let status = put("second.bin", vec![b'b'; 5000]).await.unwrap().status();
+        assert_eq!(status, 200);
+        assert!(reused() > before);
+        assert_eq!(BUFFER_POOL_IN_USE_BYTES.get(), 0);
+
+        assert_eq!(
+            std::fs::read(dir.path().join("first.bin")).unwrap(),
+            vec![b'a'; 6000]
+        );
+        assert_eq!(
+            std::fs::read(dir.path().join("second.bin")).unwrap(),
+            vec![b'b'; 5000]
+        );
+    }
+}
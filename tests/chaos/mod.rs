@@ -0,0 +1,270 @@
+//! Fault-Injecting Mock S3
+//!
+//! A minimal S3 endpoint for chaos tests that misbehaves on demand. Unlike
+//! `wiremock` it speaks HTTP/1.1 over a raw socket, so it can also reset
+//! connections and send responses cut short.
+//!
+//! Faults are taken from a script, one per request, and then drawn at
+//! random if random faults are enabled; requests without a fault get a
+//! healthy response:
+//!
+//! - PUT: `200` with an ETag
+//! - POST `?uploads`: `InitiateMultipartUploadResult`
+//! - POST `?uploadId=`: `CompleteMultipartUploadResult`
+//! - HEAD: `404` (no object is stored)
+
+use parking_lot::Mutex;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A way for the mock to misbehave on one request
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fault {
+    /// Respond with this status and an S3 error body
+    Status(u16),
+    /// Wait before sending the healthy response
+    Slow(Duration),
+    /// Send the first half of the healthy XML body as a complete response
+    TruncatedXml,
+    /// Read the request, then reset the connection without responding
+    Reset,
+}
+
+/// Probabilities of random faults, each in `0.0..=1.0`
+#[derive(Debug, Clone, Default)]
+pub struct FaultRates {
+    /// 500 Internal Server Error
+    pub server_error: f64,
+    /// Response delayed by `slow_delay`
+    pub slow: f64,
+    pub slow_delay: Duration,
+    /// Truncated XML body
+    pub truncated_xml: f64,
+    /// Connection reset
+    pub reset: f64,
+}
+
+struct State {
+    script: VecDeque<Fault>,
+    random: Option<(FaultRates, StdRng)>,
+    requests: Vec<String>,
+}
+
+impl State {
+    fn next_fault(&mut self) -> Option<Fault> {
+        if let Some(fault) = self.script.pop_front() {
+            return Some(fault);
+        }
+        let (rates, rng) = self.random.as_mut()?;
+        let roll: f64 = rng.random();
+        let mut threshold = 0.0;
+        for (rate, fault) in [
+            (rates.server_error, Fault::Status(500)),
+            (rates.slow, Fault::Slow(rates.slow_delay)),
+            (rates.truncated_xml, Fault::TruncatedXml),
+            (rates.reset, Fault::Reset),
+        ] {
+            threshold += rate;
+            if roll < threshold {
+                return Some(fault);
+            }
+        }
+        None
+    }
+}
+
+/// Fault-injecting mock S3 endpoint
+#[derive(Clone)]
+pub struct FaultyS3 {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+}
+
+impl FaultyS3 {
+    /// Start a mock that answers every request healthily until faults are
+    /// scripted or enabled
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mock = Self {
+            addr: listener.local_addr().unwrap(),
+            state: Arc::new(Mutex::new(State {
+                script: VecDeque::new(),
+                random: None,
+                requests: Vec::new(),
+            })),
+        };
+
+        let state = Arc::clone(&mock.state);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, Arc::clone(&state)));
+            }
+        });
+        mock
+    }
+
+    /// Start a mock that injects `faults`, in order, into the next requests
+    pub async fn with_faults(faults: impl IntoIterator<Item = Fault>) -> Self {
+        let mock = Self::start().await;
+        mock.script(faults);
+        mock
+    }
+
+    /// Start a mock that injects random faults at `rates`, reproducibly
+    /// for a given `seed`
+    pub async fn random(rates: FaultRates, seed: u64) -> Self {
+        let mock = Self::start().await;
+        mock.state.lock().random = Some((rates, StdRng::seed_from_u64(seed)));
+        mock
+    }
+
+    /// Queue `faults` for the next requests
+    pub fn script(&self, faults: impl IntoIterator<Item = Fault>) {
+        self.state.lock().script.extend(faults);
+    }
+
+    /// Base URL, for `S3ClientConfig::endpoint`
+    pub fn uri(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Request lines (`METHOD /path?query`) received so far
+    pub fn requests(&self) -> Vec<String> {
+        self.state.lock().requests.clone()
+    }
+}
+
+/// Serve requests on one connection until the client or a fault closes it
+async fn serve(mut stream: TcpStream, state: Arc<Mutex<State>>) {
+    let mut buf = Vec::new();
+    loop {
+        let Some(request_line) = read_request(&mut stream, &mut buf).await else {
+            return;
+        };
+        let fault = {
+            let mut state = state.lock();
+            state.requests.push(request_line.clone());
+            state.next_fault()
+        };
+
+        let (status, body) = match fault {
+            Some(Fault::Reset) => {
+                // SO_LINGER 0 makes close send RST instead of FIN
+                let _ = stream.set_zero_linger();
+                return;
+            }
+            Some(Fault::Status(status)) => (status, error_body(status)),
+            Some(Fault::Slow(delay)) => {
+                tokio::time::sleep(delay).await;
+                healthy_response(&request_line)
+            }
+            Some(Fault::TruncatedXml) => {
+                let (status, body) = healthy_response(&request_line);
+                (status, body[..body.len() / 2].to_string())
+            }
+            None => healthy_response(&request_line),
+        };
+
+        let head_only = request_line.starts_with("HEAD ");
+        let response = format!(
+            "HTTP/1.1 {} {}\r\nETag: \"mock-etag\"\r\nContent-Type: application/xml\r\n\
+             Content-Length: {}\r\n\r\n{}",
+            status,
+            reason(status),
+            body.len(),
+            if head_only { "" } else { body.as_str() }
+        );
+        if stream.write_all(response.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Read one request from `stream`, returning its request line
+///
+/// `buf` carries bytes of the next request between calls.
+async fn read_request(stream: &mut TcpStream, buf: &mut Vec<u8>) -> Option<String> {
+    let header_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        let mut chunk = [0u8; 8192];
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let content_length = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+
+    while buf.len() < header_end + content_length {
+        let mut chunk = [0u8; 8192];
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    buf.drain(..header_end + content_length);
+
+    let request_line = head.lines().next()?;
+    let mut parts = request_line.split_whitespace();
+    Some(format!("{} {}", parts.next()?, parts.next()?))
+}
+
+/// Status and body S3 would send for `request_line`
+fn healthy_response(request_line: &str) -> (u16, String) {
+    if request_line.starts_with("HEAD ") {
+        (404, String::new())
+    } else if request_line.starts_with("POST ") && request_line.contains("?uploads") {
+        (
+            200,
+            "<InitiateMultipartUploadResult><Bucket>uploads</Bucket>\
+             <UploadId>mock-upload</UploadId></InitiateMultipartUploadResult>"
+                .to_string(),
+        )
+    } else if request_line.starts_with("POST ") && request_line.contains("uploadId=") {
+        (
+            200,
+            "<CompleteMultipartUploadResult><Bucket>uploads</Bucket>\
+             <ETag>\"mock-complete\"</ETag></CompleteMultipartUploadResult>"
+                .to_string(),
+        )
+    } else {
+        (200, String::new())
+    }
+}
+
+fn error_body(status: u16) -> String {
+    format!(
+        "<Error><Code>{}</Code><Message>Injected fault</Message></Error>",
+        if status >= 500 {
+            "InternalError"
+        } else {
+            "InvalidRequest"
+        }
+    )
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        404 => "Not Found",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Injected",
+    }
+}
@@ -0,0 +1,194 @@
+//! Claim Mapping Integration Tests
+//!
+//! Tests for mapping JWT claims into authorization input
+//! (`auth.claim_mapping`).
+//!
+//! ## Test Coverage
+//!
+//! - Custom JWT claims are reported in the authentication result
+//! - Mapped claims end up in the `AuthzRequest` context
+//! - Uploads are jailed under the claim-derived key prefix
+//! - Tokens without the prefix claim are rejected
+//! - Claim-derived tags are sent to S3 as `x-amz-tagging`
+//! - Invalid key prefix templates fail server startup
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use mizuchi_uploadr::auth::jwt::JwtAuthenticator;
+    use mizuchi_uploadr::auth::{AuthRequest, Authenticator};
+    use mizuchi_uploadr::authz::claims::ClaimMapper;
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::server::pingora::PingoraServer;
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use std::path::Path;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const SECRET: &str = "tenant-secret";
+
+    fn config(root: &Path, key_prefix: &str) -> Config {
+        let bucket = format!(
+            r#"    auth:
+      enabled: true
+      jwt:
+        secret: {SECRET}
+        algorithm: HS256
+      claim_mapping:
+        context:
+          tenant: tenant_id
+          groups: org.groups
+        key_prefix: "{key_prefix}""#
+        );
+        common::config(&common::local_bucket("shared", root, &bucket))
+    }
+
+    fn token(extra: serde_json::Value) -> String {
+        let mut claims = serde_json::json!({
+            "sub": "alice",
+            "exp": (chrono::Utc::now().timestamp() + 3600) as usize,
+        });
+        claims
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    async fn put(addr: SocketAddr, key: &str, token: &str) -> reqwest::StatusCode {
+        let path = format!("/shared/{}", key);
+        let bearer = format!("Bearer {}", token);
+        common::put(addr, &path, &[("Authorization", &bearer)], "tenant data")
+            .await
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_claims_mapped_into_authz_context() {
+        let token = token(serde_json::json!({
+            "tenant_id": "acme",
+            "org": {"groups": ["finance"]},
+            "scope": "upload",
+        }));
+        let mut headers = HashMap::new();
+        headers.insert("authorization".to_string(), format!("Bearer {}", token));
+        let request = AuthRequest {
+            headers,
+            query: None,
+            method: "PUT".into(),
+            path: "/shared/tenant/acme/a.csv".into(),
+            peer_certificates: vec![],
+        };
+
+        let result = JwtAuthenticator::new_hs256(SECRET)
+            .authenticate(&request)
+            .await
+            .unwrap();
+        assert_eq!(result.claims["scope"], "upload");
+        assert!(!result.claims.contains_key("exp"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path(), "tenant/{tenant_id}/");
+        let mapping = config.buckets[0].auth.claim_mapping.as_ref().unwrap();
+        let mapper = ClaimMapper::new(mapping).unwrap();
+
+        let authz = mapper.authz_request(&result, "upload", "shared/tenant/acme/a.csv");
+        assert_eq!(authz.subject, "alice");
+        assert_eq!(authz.context["tenant"], "acme");
+        assert_eq!(authz.context["groups"], serde_json::json!(["finance"]));
+        assert!(!authz.context.contains_key("scope"));
+    }
+
+    #[tokio::test]
+    async fn test_uploads_jailed_to_tenant_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path(), "tenant/{tenant_id}/")).await;
+        let acme = token(serde_json::json!({"tenant_id": "acme"}));
+
+        assert_eq!(put(addr, "tenant/acme/q1.csv", &acme).await, 200);
+        assert!(dir.path().join("tenant/acme/q1.csv").exists());
+
+        assert_eq!(put(addr, "tenant/globex/q1.csv", &acme).await, 403);
+        assert_eq!(put(addr, "tenant/acme/../globex/q1.csv", &acme).await, 403);
+        assert!(!dir.path().join("tenant/globex").exists());
+    }
+
+    #[tokio::test]
+    async fn test_missing_prefix_claim_forbidden() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path(), "tenant/{tenant_id}/")).await;
+
+        let no_tenant = token(serde_json::json!({}));
+        assert_eq!(put(addr, "tenant/acme/q1.csv", &no_tenant).await, 403);
+
+        let traversal = token(serde_json::json!({"tenant_id": ".."}));
+        assert_eq!(put(addr, "tenant/../q1.csv", &traversal).await, 403);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_template_fails_startup() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path(), "tenant/{tenant_id/");
+        assert!(PingoraServer::new(config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_claims_tagged_on_stored_objects() {
+        let s3 = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/shared/report.csv"))
+            .and(header("x-amz-tagging", "tenant=acme&uploader=alice"))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "\"abc\""))
+            .expect(1)
+            .mount(&s3)
+            .await;
+        let yaml = format!(
+            r#"
+server:
+  address: "127.0.0.1:0"
+buckets:
+  - name: shared
+    path_prefix: /shared
+    s3:
+      bucket: shared
+      region: us-east-1
+      endpoint: "{}"
+      access_key: test-access
+      secret_key: test-secret
+    auth:
+      enabled: true
+      jwt:
+        secret: {SECRET}
+        algorithm: HS256
+      claim_mapping:
+        tags:
+          uploader: "{{sub}}"
+          tenant: "{{tenant_id}}"
+"#,
+            s3.uri()
+        );
+        let addr = common::start(serde_yaml::from_str(&yaml).unwrap()).await;
+        let acme = token(serde_json::json!({"tenant_id": "acme"}));
+
+        let response = reqwest::Client::new()
+            .put(format!("http://{}/shared/report.csv", addr))
+            .bearer_auth(&acme)
+            // Client-supplied tags are ignored
+            .header("x-amz-tagging", "uploader=mallory")
+            .body("tenant data")
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+    }
+}
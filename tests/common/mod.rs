@@ -0,0 +1,90 @@
+//! Helpers shared by the server integration tests
+//!
+//! A test file includes them with `mod common;` and uses those it needs:
+//!
+//! - [`config`] / [`local_bucket`]: a config serving buckets from YAML
+//! - [`start`] / [`start_with_admin`] / [`spawn_with_admin`]: run a
+//!   `PingoraServer` on an ephemeral port
+//! - [`put`]: upload a body through the server
+
+#![allow(dead_code)]
+
+use mizuchi_uploadr::config::Config;
+use mizuchi_uploadr::server::pingora::PingoraServer;
+use std::net::SocketAddr;
+use std::path::Path;
+
+/// Config of a server on an ephemeral port serving `buckets`, the YAML of
+/// the entries of the bucket list
+pub fn config(buckets: &str) -> Config {
+    let yaml = format!("server:\n  address: \"127.0.0.1:0\"\nbuckets:\n{}", buckets);
+    serde_yaml::from_str(&yaml).unwrap()
+}
+
+/// YAML of bucket `name`, served at `/name` from local storage under
+/// `root`, with the bucket fields in `extra` (indented by four spaces)
+pub fn local_bucket(name: &str, root: &Path, extra: &str) -> String {
+    format!(
+        r#"  - name: {name}
+    path_prefix: /{name}
+    s3:
+      bucket: {name}
+      region: us-east-1
+    storage:
+      type: local
+      root: "{root}"
+{extra}
+"#,
+        root = root.display(),
+    )
+}
+
+/// Serve the admin API on an ephemeral port as well, behind `token`
+pub fn with_admin(mut config: Config, token: &str) -> Config {
+    config.admin.address = Some("127.0.0.1:0".into());
+    config.admin.token = Some(token.into());
+    config
+}
+
+/// Validate `config` and run a server with it, returning its address
+pub async fn start(config: Config) -> SocketAddr {
+    let server = server(config).await;
+    let addr = server.local_addr().unwrap();
+    tokio::spawn(async move { server.run().await });
+    addr
+}
+
+/// Validate `config` and run a server with it, returning its address and
+/// that of its admin API
+pub async fn start_with_admin(config: Config) -> (SocketAddr, SocketAddr) {
+    spawn_with_admin(server(config).await)
+}
+
+/// Run `server`, returning its address and that of its admin API
+pub fn spawn_with_admin(server: PingoraServer) -> (SocketAddr, SocketAddr) {
+    let addr = server.local_addr().unwrap();
+    let admin_addr = server.admin_addr().expect("config has no admin API");
+    tokio::spawn(async move { server.run().await });
+    (addr, admin_addr)
+}
+
+async fn server(config: Config) -> PingoraServer {
+    config.validate().unwrap();
+    PingoraServer::new(config).await.unwrap()
+}
+
+/// PUT `body` to `path` of the server at `addr` with `headers`
+pub async fn put(
+    addr: SocketAddr,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: impl Into<reqwest::Body>,
+) -> reqwest::Response {
+    let mut request = reqwest::Client::new()
+        .put(format!("http://{}{}", addr, path))
+        .body(body);
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    request.send().await.unwrap()
+}
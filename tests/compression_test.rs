@@ -0,0 +1,111 @@
+//! Compression Integration Tests
+//!
+//! Tests for `upload.compression` in `PingoraServer`.
+//!
+//! ## Test Coverage
+//!
+//! - Compressible uploads are stored compressed
+//! - Excluded content types and small bodies are stored as sent
+//! - Bodies the client already encoded are not compressed again
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::config::Config;
+    use std::io::Read;
+    use std::net::SocketAddr;
+    use std::path::Path;
+
+    fn config(root: &Path) -> Config {
+        let bucket = r#"    upload:
+      compression:
+        algorithm: gzip
+        min_size: 256"#;
+        common::config(&common::local_bucket("logs", root, bucket))
+    }
+
+    async fn put(addr: SocketAddr, key: &str, headers: &[(&str, &str)], body: Vec<u8>) -> u16 {
+        let path = format!("/logs/{}", key);
+        common::put(addr, &path, headers, body)
+            .await
+            .status()
+            .as_u16()
+    }
+
+    fn log_lines() -> Vec<u8> {
+        "2024-01-01T00:00:00Z INFO request handled\n"
+            .repeat(100)
+            .into_bytes()
+    }
+
+    #[tokio::test]
+    async fn test_compressible_upload_is_compressed() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path())).await;
+
+        let status = put(
+            addr,
+            "app.log",
+            &[("content-type", "text/plain")],
+            log_lines(),
+        )
+        .await;
+
+        assert_eq!(status, 200);
+        let stored = std::fs::read(dir.path().join("app.log")).unwrap();
+        assert!(stored.len() < log_lines().len());
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(&stored[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, log_lines());
+    }
+
+    #[tokio::test]
+    async fn test_excluded_and_small_uploads_are_stored_as_sent() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path())).await;
+
+        let status = put(
+            addr,
+            "cat.png",
+            &[("content-type", "image/png")],
+            log_lines(),
+        )
+        .await;
+        assert_eq!(status, 200);
+        assert_eq!(
+            std::fs::read(dir.path().join("cat.png")).unwrap(),
+            log_lines()
+        );
+
+        let status = put(addr, "short.log", &[], b"tiny".to_vec()).await;
+        assert_eq!(status, 200);
+        assert_eq!(
+            std::fs::read(dir.path().join("short.log")).unwrap(),
+            b"tiny"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_encoded_upload_is_not_compressed_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path())).await;
+
+        let status = put(
+            addr,
+            "app.log.br",
+            &[("content-encoding", "br")],
+            log_lines(),
+        )
+        .await;
+
+        assert_eq!(status, 200);
+        assert_eq!(
+            std::fs::read(dir.path().join("app.log.br")).unwrap(),
+            log_lines()
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! Conditional Write Integration Tests
+//!
+//! Tests for `If-None-Match` / `If-Match` on PUT in `PingoraServer`.
+//!
+//! ## Test Coverage
+//!
+//! - `If-None-Match: *` creates an object only once
+//! - `If-Match` overwrites only the matching version
+//! - Failed preconditions are S3-style `412 PreconditionFailed` errors
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::config::Config;
+    use std::net::SocketAddr;
+    use std::path::Path;
+
+    fn config(root: &Path) -> Config {
+        common::config(&common::local_bucket("docs", root, ""))
+    }
+
+    async fn put(addr: SocketAddr, condition: (&str, &str), body: &str) -> reqwest::Response {
+        common::put(addr, "/docs/report.txt", &[condition], body.to_string()).await
+    }
+
+    #[tokio::test]
+    async fn test_create_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path())).await;
+
+        let response = put(addr, ("if-none-match", "*"), "first").await;
+        assert_eq!(response.status(), 200);
+
+        let response = put(addr, ("if-none-match", "*"), "second").await;
+        assert_eq!(response.status(), 412);
+        let body = response.text().await.unwrap();
+        assert!(body.contains("<Code>PreconditionFailed</Code>"), "{}", body);
+        assert_eq!(
+            std::fs::read(dir.path().join("report.txt")).unwrap(),
+            b"first"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_if_match() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path())).await;
+
+        let response = put(addr, ("if-none-match", "*"), "first").await;
+        let etag = response.headers()["etag"].to_str().unwrap().to_string();
+
+        let response = put(addr, ("if-match", "\"00000000\""), "stale").await;
+        assert_eq!(response.status(), 412);
+
+        let response = put(addr, ("if-match", &etag), "second").await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            std::fs::read(dir.path().join("report.txt")).unwrap(),
+            b"second"
+        );
+    }
+}
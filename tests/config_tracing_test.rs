@@ -3,7 +3,7 @@
 //! This test suite validates the tracing configuration parsing,
 //! validation, and default values following TDD methodology.
 
-use mizuchi_uploadr::config::Config;
+use mizuchi_uploadr::config::{Config, ConfigLoader};
 use serial_test::serial;
 
 #[test]
@@ -178,7 +178,7 @@ tracing:
     endpoint: "${OTLP_ENDPOINT}"
 "#;
 
-    let config: Config = serde_yaml::from_str(yaml).expect("Failed to parse YAML");
+    let config = ConfigLoader::parse(yaml).expect("Failed to parse YAML");
 
     assert!(config.tracing.is_some());
     let tracing = config.tracing.unwrap();
@@ -224,7 +224,7 @@ tracing:
     endpoint: "http://localhost:4317"
 "#;
 
-    let config: Config = serde_yaml::from_str(yaml).expect("Failed to parse YAML");
+    let config = ConfigLoader::parse(yaml).expect("Failed to parse YAML");
 
     assert!(config.tracing.is_some());
     let tracing = config.tracing.unwrap();
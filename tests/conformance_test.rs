@@ -0,0 +1,95 @@
+//! S3 Conformance Integration Tests
+//!
+//! Tests for the embedded s3-tests subset of `mizuchi-uploadr conformance`.
+//!
+//! ## Test Coverage
+//!
+//! - Every case passes against a local storage backend
+//! - Backend behaviors show up in the results
+//! - UploadPart and CopyObject are rejected without reaching S3
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::conformance::{self, Case, ConformanceOptions};
+    use wiremock::matchers::{header_exists, method, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// Start a server with `storage` (YAML of the bucket's storage backend)
+    async fn start(storage: &str) -> String {
+        let bucket = format!(
+            r#"  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: uploads
+      region: eu-west-1
+{storage}
+"#
+        );
+        let addr = common::start(common::config(&bucket)).await;
+        format!("http://{}/uploads", addr)
+    }
+
+    #[tokio::test]
+    async fn test_local_backend_conforms() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = format!(
+            "    storage:\n      type: local\n      root: \"{}\"",
+            dir.path().display()
+        );
+        let options = ConformanceOptions {
+            target: start(&storage).await,
+            ..Default::default()
+        };
+
+        let results = conformance::run(&reqwest::Client::new(), &options).await;
+
+        assert_eq!(results.len(), Case::ALL.len());
+        let failed: Vec<_> = results.iter().filter(|r| !r.passed()).collect();
+        assert!(failed.is_empty(), "{}", conformance::report(&results));
+    }
+
+    #[tokio::test]
+    async fn test_backend_behaviors_reported() {
+        let s3 = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(query_param("partNumber", "1"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&s3)
+            .await;
+        Mock::given(method("PUT"))
+            .and(header_exists("x-amz-copy-source"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&s3)
+            .await;
+        // A backend that ignores conditions and returns the same ETag
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "\"fixed\""))
+            .mount(&s3)
+            .await;
+        let storage = format!(
+            "      endpoint: \"{}\"\n      access_key: test\n      secret_key: test",
+            s3.uri()
+        );
+        let options = ConformanceOptions {
+            target: start(&storage).await,
+            ..Default::default()
+        };
+
+        let results = conformance::run(&reqwest::Client::new(), &options).await;
+
+        let passed = |case: Case| results.iter().find(|r| r.case == case).unwrap().passed();
+        assert!(!passed(Case::WriteCheckEtag));
+        assert!(!passed(Case::IfNoneMatchExisting));
+        assert!(passed(Case::HeadBucket));
+        assert!(passed(Case::ReadsRejected));
+        assert!(passed(Case::UploadPartRejected));
+        assert!(passed(Case::CopyRejected));
+        let report = conformance::report(&results);
+        assert!(report.contains("expected ETag"));
+    }
+}
@@ -0,0 +1,94 @@
+//! Content Type Enforcement Integration Tests
+//!
+//! Tests for `upload.content_type` in `PingoraServer`.
+//!
+//! ## Test Coverage
+//!
+//! - Declared content types outside the allow-list are rejected
+//! - Sniffing catches executables labelled as images
+//! - Rejections are S3-style `InvalidArgument` XML errors
+//! - `enforce: log` stores violating uploads anyway
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::config::Config;
+    use std::net::SocketAddr;
+    use std::path::Path;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    /// An ELF header, padded to the length signature matching requires
+    fn elf() -> Vec<u8> {
+        let mut elf = vec![0u8; 64];
+        elf[..4].copy_from_slice(b"\x7fELF");
+        elf
+    }
+
+    fn config(root: &Path, enforce: &str) -> Config {
+        let bucket = format!(
+            r#"    upload:
+      content_type:
+        allowed_content_types: ["image/*"]
+        sniff: true
+        enforce: {}"#,
+            enforce
+        );
+        common::config(&common::local_bucket("images", root, &bucket))
+    }
+
+    async fn put(addr: SocketAddr, key: &str, content_type: &str, body: Vec<u8>) -> (u16, String) {
+        let path = format!("/images/{}", key);
+        let response = common::put(addr, &path, &[("content-type", content_type)], body).await;
+        (response.status().as_u16(), response.text().await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_allowed_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path(), "reject")).await;
+
+        let (status, _) = put(addr, "cat.png", "image/png", PNG.to_vec()).await;
+
+        assert_eq!(status, 200);
+        assert!(dir.path().join("cat.png").exists());
+    }
+
+    #[tokio::test]
+    async fn test_disallowed_declared_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path(), "reject")).await;
+
+        let (status, body) = put(addr, "page.html", "text/html", b"<html>".to_vec()).await;
+
+        assert_eq!(status, 400);
+        assert!(body.contains("<Code>InvalidArgument</Code>"), "{}", body);
+        assert!(body.contains("text/html"), "{}", body);
+        assert!(!dir.path().join("page.html").exists());
+    }
+
+    #[tokio::test]
+    async fn test_executable_labelled_as_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path(), "reject")).await;
+
+        let (status, body) = put(addr, "cat.png", "image/png", elf()).await;
+
+        assert_eq!(status, 400);
+        assert!(body.contains("application/x-executable"), "{}", body);
+        assert!(!dir.path().join("cat.png").exists());
+    }
+
+    #[tokio::test]
+    async fn test_log_mode_stores_upload() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path(), "log")).await;
+
+        let (status, _) = put(addr, "cat.png", "image/png", elf()).await;
+
+        assert_eq!(status, 200);
+        assert!(dir.path().join("cat.png").exists());
+    }
+}
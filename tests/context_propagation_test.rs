@@ -7,6 +7,7 @@
 
 #[cfg(feature = "tracing")]
 use mizuchi_uploadr::tracing::propagation::{extract_trace_context, inject_trace_context};
+#[cfg(feature = "tracing")]
 use std::collections::HashMap;
 
 #[cfg(feature = "tracing")]
@@ -181,6 +181,12 @@ impl E2ETestEnv {
             server: ServerConfig {
                 address: format!("127.0.0.1:{}", port),
                 zero_copy: ZeroCopyConfig::default(),
+                http: Default::default(),
+                tls: None,
+                listeners: Vec::new(),
+                tcp: Default::default(),
+                buffer_pool: Default::default(),
+                shutdown: Default::default(),
             },
             buckets: vec![BucketConfig {
                 name: TEST_BUCKET.into(),
@@ -191,12 +197,34 @@ impl E2ETestEnv {
                     endpoint: Some(RUSTFS_ENDPOINT.into()),
                     access_key: Some(RUSTFS_ACCESS_KEY.into()),
                     secret_key: Some(RUSTFS_SECRET_KEY.into()),
+                    pool: None,
+                    sigv4a_region_set: None,
+                    hedging: None,
+                    hashing: None,
+                    checksum: None,
+                    debug_log: None,
+                    access_key_file: None,
+                    secret_key_file: None,
                 },
                 auth: AuthConfig::default(),
                 upload: UploadConfig::default(),
+                failover: None,
+                replication: None,
+                storage: Default::default(),
+                authz: None,
+                authz_failure: Default::default(),
+                tenant: None,
+                hosts: Vec::new(),
+                key_prefix: None,
+                regions: None,
+                authz_shadow: None,
             }],
             metrics: MetricsConfig::default(),
             tracing: None,
+            notifications: Default::default(),
+            admin: Default::default(),
+            logging: Default::default(),
+            state: Default::default(),
         }
     }
 
@@ -207,11 +235,20 @@ impl E2ETestEnv {
             enabled: true,
             jwt: Some(JwtConfig {
                 secret: Some(JWT_SECRET.into()),
+                secret_file: None,
                 algorithm: "HS256".into(),
                 jwks_url: None,
                 token_sources: vec![],
+                token_exchange: None,
+                revocation: None,
             }),
             sigv4: None,
+            mtls: None,
+            chain: Default::default(),
+            allow_anonymous: None,
+            api_key: None,
+            claim_mapping: None,
+            custom: Vec::new(),
         };
         config
     }
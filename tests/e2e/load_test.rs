@@ -12,75 +12,9 @@
 //! - Zero-copy performance validation
 
 use super::common::E2ETestEnv;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use mizuchi_uploadr::bench::{self, BenchOptions, LoadTestMetrics};
 use std::time::{Duration, Instant};
 
-/// Performance metrics collected during load tests
-#[derive(Debug, Default)]
-pub struct LoadTestMetrics {
-    pub total_requests: u64,
-    pub successful_requests: u64,
-    pub failed_requests: u64,
-    pub total_bytes: u64,
-    pub total_duration_ms: u64,
-    pub min_latency_ms: u64,
-    pub max_latency_ms: u64,
-    pub latencies: Vec<u64>,
-}
-
-impl LoadTestMetrics {
-    /// Calculate throughput in MB/s
-    pub fn throughput_mbps(&self) -> f64 {
-        if self.total_duration_ms == 0 {
-            return 0.0;
-        }
-        let bytes_per_sec = (self.total_bytes as f64) / (self.total_duration_ms as f64 / 1000.0);
-        bytes_per_sec / (1024.0 * 1024.0)
-    }
-
-    /// Calculate requests per second
-    pub fn requests_per_sec(&self) -> f64 {
-        if self.total_duration_ms == 0 {
-            return 0.0;
-        }
-        (self.total_requests as f64) / (self.total_duration_ms as f64 / 1000.0)
-    }
-
-    /// Calculate p50 latency
-    pub fn p50_latency_ms(&self) -> u64 {
-        self.percentile(50)
-    }
-
-    /// Calculate p95 latency
-    pub fn p95_latency_ms(&self) -> u64 {
-        self.percentile(95)
-    }
-
-    /// Calculate p99 latency
-    pub fn p99_latency_ms(&self) -> u64 {
-        self.percentile(99)
-    }
-
-    fn percentile(&self, p: u8) -> u64 {
-        if self.latencies.is_empty() {
-            return 0;
-        }
-        let mut sorted = self.latencies.clone();
-        sorted.sort_unstable();
-        let idx = (sorted.len() as f64 * (p as f64 / 100.0)) as usize;
-        sorted[idx.min(sorted.len() - 1)]
-    }
-
-    /// Success rate as percentage
-    pub fn success_rate(&self) -> f64 {
-        if self.total_requests == 0 {
-            return 0.0;
-        }
-        (self.successful_requests as f64 / self.total_requests as f64) * 100.0
-    }
-}
-
 /// Run a load test with specified parameters
 async fn run_load_test(
     env: &E2ETestEnv,
@@ -88,70 +22,15 @@ async fn run_load_test(
     concurrency: usize,
     payload_size: usize,
 ) -> LoadTestMetrics {
-    let start = Instant::now();
-    let successful = Arc::new(AtomicU64::new(0));
-    let failed = Arc::new(AtomicU64::new(0));
-    let bytes_sent = Arc::new(AtomicU64::new(0));
-    let latencies = Arc::new(parking_lot::Mutex::new(Vec::new()));
-
-    let requests_per_worker = num_requests / concurrency;
-
-    let mut handles = vec![];
-    for worker_id in 0..concurrency {
-        let client = env.client.clone();
-        let base_url = env.base_url();
-        let successful = successful.clone();
-        let failed = failed.clone();
-        let bytes_sent = bytes_sent.clone();
-        let latencies = latencies.clone();
-
-        let handle = tokio::spawn(async move {
-            for i in 0..requests_per_worker {
-                let payload = E2ETestEnv::random_payload(payload_size);
-                let req_start = Instant::now();
-
-                let result = client
-                    .put(format!(
-                        "{}/uploads/load-test-{}-{}.bin",
-                        base_url, worker_id, i
-                    ))
-                    .body(payload.to_vec())
-                    .send()
-                    .await;
-
-                let latency = req_start.elapsed().as_millis() as u64;
-                latencies.lock().push(latency);
-
-                match result {
-                    Ok(resp) if resp.status().is_success() => {
-                        successful.fetch_add(1, Ordering::Relaxed);
-                        bytes_sent.fetch_add(payload_size as u64, Ordering::Relaxed);
-                    }
-                    _ => {
-                        failed.fetch_add(1, Ordering::Relaxed);
-                    }
-                }
-            }
-        });
-
-        handles.push(handle);
-    }
-
-    futures::future::join_all(handles).await;
-
-    let duration = start.elapsed();
-    let latencies_vec = latencies.lock().clone();
-
-    LoadTestMetrics {
-        total_requests: num_requests as u64,
-        successful_requests: successful.load(Ordering::Relaxed),
-        failed_requests: failed.load(Ordering::Relaxed),
-        total_bytes: bytes_sent.load(Ordering::Relaxed),
-        total_duration_ms: duration.as_millis() as u64,
-        min_latency_ms: *latencies_vec.iter().min().unwrap_or(&0),
-        max_latency_ms: *latencies_vec.iter().max().unwrap_or(&0),
-        latencies: latencies_vec,
-    }
+    let options = BenchOptions {
+        target: format!("{}/uploads", env.base_url()),
+        size: payload_size,
+        concurrency,
+        requests: num_requests,
+        key_prefix: "load-test".into(),
+        ..Default::default()
+    };
+    bench::run(&env.client, &options).await
 }
 
 /// Test: Baseline throughput with 1KB payloads
@@ -180,9 +59,9 @@ async fn test_throughput_1kb_payloads() {
     println!("Duration: {}ms", metrics.total_duration_ms);
     println!("Throughput: {:.2} req/s", metrics.requests_per_sec());
     println!("Data rate: {:.2} MB/s", metrics.throughput_mbps());
-    println!("p50 latency: {}ms", metrics.p50_latency_ms());
-    println!("p95 latency: {}ms", metrics.p95_latency_ms());
-    println!("p99 latency: {}ms", metrics.p99_latency_ms());
+    println!("p50 latency: {:.2}ms", metrics.p50_latency_ms());
+    println!("p95 latency: {:.2}ms", metrics.p95_latency_ms());
+    println!("p99 latency: {:.2}ms", metrics.p99_latency_ms());
 
     // Assertions
     assert!(
@@ -220,8 +99,8 @@ async fn test_throughput_1mb_payloads() {
     println!("Successful: {}", metrics.successful_requests);
     println!("Duration: {}ms", metrics.total_duration_ms);
     println!("Throughput: {:.2} MB/s", metrics.throughput_mbps());
-    println!("p50 latency: {}ms", metrics.p50_latency_ms());
-    println!("p95 latency: {}ms", metrics.p95_latency_ms());
+    println!("p50 latency: {:.2}ms", metrics.p50_latency_ms());
+    println!("p95 latency: {:.2}ms", metrics.p95_latency_ms());
 
     assert!(
         metrics.success_rate() >= 95.0,
@@ -251,8 +130,8 @@ async fn test_throughput_10mb_payloads() {
     println!("Successful: {}", metrics.successful_requests);
     println!("Duration: {}ms", metrics.total_duration_ms);
     println!("Throughput: {:.2} MB/s", metrics.throughput_mbps());
-    println!("p50 latency: {}ms", metrics.p50_latency_ms());
-    println!("p95 latency: {}ms", metrics.p95_latency_ms());
+    println!("p50 latency: {:.2}ms", metrics.p50_latency_ms());
+    println!("p95 latency: {:.2}ms", metrics.p95_latency_ms());
 
     assert!(
         metrics.success_rate() >= 95.0,
@@ -278,15 +157,15 @@ async fn test_latency_under_moderate_load() {
     let metrics = run_load_test(&env, 50, 5, 1024).await;
 
     println!("=== Latency Test Results ===");
-    println!("p50 latency: {}ms", metrics.p50_latency_ms());
-    println!("p95 latency: {}ms", metrics.p95_latency_ms());
-    println!("p99 latency: {}ms", metrics.p99_latency_ms());
-    println!("Min latency: {}ms", metrics.min_latency_ms);
-    println!("Max latency: {}ms", metrics.max_latency_ms);
+    println!("p50 latency: {:.2}ms", metrics.p50_latency_ms());
+    println!("p95 latency: {:.2}ms", metrics.p95_latency_ms());
+    println!("p99 latency: {:.2}ms", metrics.p99_latency_ms());
+    println!("Min latency: {:.2}ms", metrics.min_latency_ms());
+    println!("Max latency: {:.2}ms", metrics.max_latency_ms());
 
     // p95 latency target (adjust for environment)
     assert!(
-        metrics.p95_latency_ms() < 5000,
+        metrics.p95_latency_ms() < 5000.0,
         "p95 latency should be < 5000ms, got {}ms",
         metrics.p95_latency_ms()
     );
@@ -446,7 +325,7 @@ async fn test_zero_copy_performance() {
     println!("=== Zero-Copy Performance Test ===");
     println!("Platform: Linux (zero-copy enabled)");
     println!("Throughput: {:.2} MB/s", metrics.throughput_mbps());
-    println!("p50 latency: {}ms", metrics.p50_latency_ms());
+    println!("p50 latency: {:.2}ms", metrics.p50_latency_ms());
 
     // On Linux with zero-copy, we expect better throughput
     // This is a baseline check - actual improvement depends on hardware
@@ -2,6 +2,10 @@
 //!
 //! This is the main entry point for running E2E tests.
 //!
+//! These tests need the infrastructure below to be started by hand. The
+//! suite in `tests/integration` starts its own MinIO and OPA containers
+//! and only needs Docker (`cargo test --test integration`).
+//!
 //! ## Prerequisites
 //!
 //! 1. Start the test infrastructure:
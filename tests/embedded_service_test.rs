@@ -0,0 +1,119 @@
+//! Embedded Service Integration Tests
+//!
+//! Tests for `mizuchi_uploadr::layer`, the upload proxy as a `tower`
+//! service mounted by another application.
+//!
+//! ## Test Coverage
+//!
+//! - Uploads are stored through the bucket's backend
+//! - Bodies that are not `Sync` (like axum's) are accepted
+//! - Authentication is enforced as in the standalone server
+//! - Paths outside every bucket get 404
+//! - The service can be served by a plain hyper connection
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use bytes::Bytes;
+    use http_body_util::combinators::UnsyncBoxBody;
+    use http_body_util::{BodyExt, Full};
+    use hyper::{Request, StatusCode};
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::UploadService;
+    use std::convert::Infallible;
+    use std::path::Path;
+    use tower_service::Service;
+
+    fn config(root: &Path, auth: bool) -> Config {
+        let bucket = format!(
+            r#"    auth:
+      enabled: {}
+      jwt:
+        secret: embedded-secret
+        algorithm: HS256"#,
+            auth
+        );
+        common::config(&common::local_bucket("uploads", root, &bucket))
+    }
+
+    fn put(path: &str, body: &'static str) -> Request<UnsyncBoxBody<Bytes, Infallible>> {
+        Request::put(path)
+            .body(Full::new(Bytes::from(body)).boxed_unsync())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_upload_through_service() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut service = mizuchi_uploadr::layer(config(dir.path(), false))
+            .await
+            .unwrap();
+
+        let response = service.call(put("/uploads/a.txt", "hello")).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(std::fs::read(dir.path().join("a.txt")).unwrap(), b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_auth_enforced() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut service = UploadService::new(config(dir.path(), true)).await.unwrap();
+
+        let response = service.call(put("/uploads/a.txt", "hello")).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(!dir.path().join("a.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_path_not_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut service = mizuchi_uploadr::layer(config(dir.path(), false))
+            .await
+            .unwrap();
+
+        let response = service
+            .call(put("/elsewhere/a.txt", "hello"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_served_by_hyper() {
+        use hyper_util::rt::{TokioExecutor, TokioIo};
+        use hyper_util::server::conn::auto;
+        use hyper_util::service::TowerToHyperService;
+
+        let dir = tempfile::tempdir().unwrap();
+        let service = mizuchi_uploadr::layer(config(dir.path(), false))
+            .await
+            .unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let service = TowerToHyperService::new(service.clone());
+                tokio::spawn(async move {
+                    let _ = auto::Builder::new(TokioExecutor::new())
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+
+        let response = reqwest::Client::new()
+            .put(format!("http://{}/uploads/b.txt", addr))
+            .body("world")
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(std::fs::read(dir.path().join("b.txt")).unwrap(), b"world");
+    }
+}
@@ -0,0 +1,120 @@
+#!/usr/bin/env bash
+# Regenerate the mTLS test PKI used by tests/mtls_auth_test.rs
+#
+# Produces a CA, a server certificate for localhost, a valid client
+# certificate, a revoked client certificate (listed in crl.pem), a client
+# certificate issued by an untrusted CA, client certificates whose CN is a
+# BMPString and a UniversalString, and OCSP responses for the valid and the
+# revoked client certificates.
+#
+# Needs openssl and Python's cryptography package.
+set -euo pipefail
+cd "$(dirname "$0")"
+
+DAYS=36500
+WORK=$(mktemp -d)
+trap 'rm -rf "$WORK"' EXIT
+
+key() {
+    openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:P-256 -out "$1" 2>/dev/null
+}
+
+ca() {
+    local name=$1 cn=$2
+    key "$name.key"
+    openssl req -x509 -new -key "$name.key" -sha256 -days $DAYS -subj "/CN=$cn" \
+        -addext "basicConstraints=critical,CA:TRUE" \
+        -addext "keyUsage=critical,keyCertSign,cRLSign" \
+        -out "$name.pem"
+}
+
+issue() {
+    local issuer=$1 name=$2 cn=$3 ext=$4
+    key "$name.key"
+    openssl req -new -key "$name.key" -subj "/CN=$cn" -out "$WORK/$name.csr"
+    printf '%b' "$ext" > "$WORK/$name.ext"
+    openssl x509 -req -in "$WORK/$name.csr" -CA "$issuer.pem" -CAkey "$issuer.key" \
+        -CAcreateserial -sha256 -days $DAYS -extfile "$WORK/$name.ext" -out "$name.pem" 2>/dev/null
+}
+
+ca ca "Mizuchi Test CA"
+ca untrusted-ca "Untrusted Test CA"
+
+issue ca server localhost \
+    "basicConstraints=CA:FALSE\nextendedKeyUsage=serverAuth\nsubjectAltName=DNS:localhost,IP:127.0.0.1\n"
+issue ca client uploader \
+    "basicConstraints=CA:FALSE\nextendedKeyUsage=clientAuth\nsubjectAltName=URI:spiffe://example.org/uploader,DNS:uploader.internal,email:uploader@example.org\nauthorityInfoAccess=OCSP;URI:http://ocsp.mizuchi.test\n"
+issue ca cn-only cn-only-service \
+    "basicConstraints=CA:FALSE\nextendedKeyUsage=clientAuth\n"
+issue ca revoked revoked-service \
+    "basicConstraints=CA:FALSE\nextendedKeyUsage=clientAuth\nsubjectAltName=URI:spiffe://example.org/revoked\nauthorityInfoAccess=OCSP;URI:http://ocsp.mizuchi.test\n"
+issue ca ocsp "Mizuchi Test OCSP Responder" \
+    "basicConstraints=CA:FALSE\nextendedKeyUsage=OCSPSigning\n"
+issue untrusted-ca untrusted untrusted-service \
+    "basicConstraints=CA:FALSE\nextendedKeyUsage=clientAuth\nsubjectAltName=URI:spiffe://example.org/untrusted\n"
+
+# CRL revoking revoked.pem
+touch "$WORK/index.txt"
+echo 1000 > "$WORK/crlnumber"
+cat > "$WORK/ca.cnf" <<CNF
+[ca]
+default_ca = test_ca
+[test_ca]
+database = $WORK/index.txt
+crlnumber = $WORK/crlnumber
+default_md = sha256
+default_crl_days = $DAYS
+CNF
+openssl ca -config "$WORK/ca.cnf" -keyfile ca.key -cert ca.pem -revoke revoked.pem 2>/dev/null
+openssl ca -config "$WORK/ca.cnf" -keyfile ca.key -cert ca.pem -gencrl -out crl.pem 2>/dev/null
+
+# OCSP responses: client.pem is good (signed by the delegated responder),
+# revoked.pem is revoked (signed by the CA itself)
+respond() {
+    local name=$1 signer=$2
+    openssl ocsp -issuer ca.pem -cert "$name.pem" -no_nonce -reqout "$WORK/$name.req" 2>/dev/null
+    openssl ocsp -index "$WORK/index.txt" -CA ca.pem -rsigner "$signer.pem" -rkey "$signer.key" \
+        -reqin "$WORK/$name.req" -ndays $DAYS -respout "ocsp-$name.der" 2>/dev/null
+}
+serial() {
+    openssl x509 -in "$1.pem" -noout -serial | cut -d= -f2
+}
+printf 'V\t%s\t\t%s\tunknown\t/CN=uploader\n' \
+    "$(date -u -d "+$DAYS days" +%y%m%d%H%M%SZ)" "$(serial client)" >> "$WORK/index.txt"
+respond client ocsp
+respond revoked ca
+
+# CNs encoded as BMPString and UniversalString, which openssl req won't
+# produce for common names
+python3 - "$DAYS" <<'PY'
+import datetime, sys
+from cryptography import x509
+from cryptography.hazmat.primitives import hashes, serialization
+from cryptography.hazmat.primitives.asymmetric import ec
+from cryptography.x509.name import _ASN1Type
+from cryptography.x509.oid import ExtendedKeyUsageOID, NameOID
+
+ca = x509.load_pem_x509_certificate(open("ca.pem", "rb").read())
+ca_key = serialization.load_pem_private_key(open("ca.key", "rb").read(), None)
+now = datetime.datetime.now(datetime.timezone.utc)
+for name, cn, string_type in [
+    ("cn-bmp", "アップローダー", _ASN1Type.BMPString),
+    ("cn-universal", "𝔲𝔭𝔩𝔬𝔞𝔡𝔢𝔯", _ASN1Type.UniversalString),
+]:
+    subject = x509.Name([x509.NameAttribute(NameOID.COMMON_NAME, cn, _type=string_type)])
+    cert = (
+        x509.CertificateBuilder()
+        .subject_name(subject)
+        .issuer_name(ca.subject)
+        .public_key(ec.generate_private_key(ec.SECP256R1()).public_key())
+        .serial_number(x509.random_serial_number())
+        .not_valid_before(now)
+        .not_valid_after(now + datetime.timedelta(days=int(sys.argv[1])))
+        .add_extension(x509.BasicConstraints(ca=False, path_length=None), critical=False)
+        .add_extension(x509.ExtendedKeyUsage([ExtendedKeyUsageOID.CLIENT_AUTH]), critical=False)
+        .sign(ca_key, hashes.SHA256())
+    )
+    open(f"{name}.pem", "wb").write(cert.public_bytes(serialization.Encoding.PEM))
+PY
+
+rm -f ./*.srl ca.key untrusted-ca.key ocsp.key
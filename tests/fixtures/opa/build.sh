@@ -0,0 +1,14 @@
+#!/usr/bin/env bash
+# Rebuild the OPA bundle used by tests/opa_wasm_test.rs
+#
+# example.tar.gz is `opa build -t wasm` output for example.rego: a
+# policy.wasm with the example/project_permissions and example/user_project
+# entrypoints, and an empty data.json.
+set -euo pipefail
+cd "$(dirname "$0")"
+
+opa build -t wasm \
+    -e example/project_permissions \
+    -e example/user_project \
+    -o example.tar.gz \
+    example.rego
@@ -0,0 +1,115 @@
+//! Flow Control Integration Tests
+//!
+//! Tests for forwarding bodies with `upload.flow_control` in `PingoraServer`.
+//!
+//! ## Test Coverage
+//!
+//! - Bodies above the multipart threshold are stored as multipart uploads
+//! - Smaller and unsized bodies are stored with a single request
+//! - A client that disconnects mid-body leaves no object or parts behind
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use bytes::Bytes;
+    use http_body_util::StreamBody;
+    use hyper::body::Frame;
+    use mizuchi_uploadr::config::Config;
+    use std::path::Path;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    fn config(root: &Path) -> Config {
+        let bucket = r#"    upload:
+      multipart_threshold: 4096
+      part_size: 1024
+      concurrent_parts: 2
+      flow_control:
+        high_water_mark: 4096
+        low_water_mark: 2048"#;
+        common::config(&common::local_bucket("media", root, bucket))
+    }
+
+    fn payload(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[tokio::test]
+    async fn test_large_body_forwarded_in_parts() {
+        let root = tempfile::tempdir().unwrap();
+        let addr = common::start(config(root.path())).await;
+        let body = payload(20_000);
+
+        let response = reqwest::Client::new()
+            .put(format!("http://{}/media/video.bin", addr))
+            .body(body.clone())
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+        let etag = response.headers()["etag"].to_str().unwrap().to_string();
+        assert!(etag.ends_with("-20\""), "{}", etag);
+        assert_eq!(std::fs::read(root.path().join("video.bin")).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn test_small_and_unsized_bodies_not_forwarded() {
+        let root = tempfile::tempdir().unwrap();
+        let addr = common::start(config(root.path())).await;
+        let client = reqwest::Client::new();
+
+        let small = client
+            .put(format!("http://{}/media/small.bin", addr))
+            .body(payload(4096))
+            .send()
+            .await
+            .unwrap();
+        let frames = vec![Ok::<_, std::io::Error>(Frame::data(Bytes::from(payload(
+            8192,
+        ))))];
+        let chunked = client
+            .put(format!("http://{}/media/chunked.bin", addr))
+            .body(reqwest::Body::wrap(StreamBody::new(futures::stream::iter(
+                frames,
+            ))))
+            .send()
+            .await
+            .unwrap();
+
+        for response in [small, chunked] {
+            assert_eq!(response.status(), 200);
+            let etag = response.headers()["etag"].to_str().unwrap();
+            assert!(!etag.contains('-'), "{}", etag);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_disconnect_aborts_upload() {
+        let root = tempfile::tempdir().unwrap();
+        let addr = common::start(config(root.path())).await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(
+                b"PUT /media/partial.bin HTTP/1.1\r\nHost: localhost\r\nContent-Length: 20000\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        stream.write_all(&payload(6000)).await.unwrap();
+        stream.shutdown().await.unwrap();
+        let mut response = Vec::new();
+        let _ = stream.read_to_end(&mut response).await;
+
+        // The aborted upload is cleaned up after the response is decided
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert!(!root.path().join("partial.bin").exists());
+        let multipart = root.path().join(".mizuchi-multipart");
+        let leftover = std::fs::read_dir(&multipart)
+            .map(|dir| dir.count())
+            .unwrap_or(0);
+        assert_eq!(leftover, 0);
+    }
+}
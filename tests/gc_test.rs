@@ -0,0 +1,121 @@
+//! Multipart Garbage Collection Integration Tests
+//!
+//! Tests for `mizuchi_uploadr::gc` against a mock S3 backend.
+//!
+//! ## Test Coverage
+//!
+//! - Uploads older than the threshold are aborted, younger ones are kept
+//! - A dry run aborts nothing
+//! - A bucket whose listing fails is reported without stopping the others
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::gc;
+    use std::time::Duration;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const DAY: Duration = Duration::from_secs(86400);
+
+    /// S3 mock with one upload from 2020 and one started just now
+    async fn s3() -> MockServer {
+        let server = MockServer::start().await;
+        let now = chrono::Utc::now().to_rfc3339();
+        let listing = format!(
+            "<ListMultipartUploadsResult><Bucket>files</Bucket>\
+             <IsTruncated>false</IsTruncated>\
+             <Upload><Key>old.bin</Key><UploadId>u-old</UploadId>\
+             <Initiated>2020-01-01T00:00:00.000Z</Initiated></Upload>\
+             <Upload><Key>new.bin</Key><UploadId>u-new</UploadId>\
+             <Initiated>{}</Initiated></Upload>\
+             </ListMultipartUploadsResult>",
+            now
+        );
+        Mock::given(method("GET"))
+            .and(path("/files"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(listing))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/broken"))
+            .respond_with(ResponseTemplate::new(403))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn config(s3_url: &str, buckets: &[&str]) -> Config {
+        let buckets: String = buckets
+            .iter()
+            .map(|bucket| {
+                format!(
+                    r#"  - name: {bucket}
+    path_prefix: /{bucket}
+    s3:
+      bucket: {bucket}
+      region: us-east-1
+      endpoint: "{s3_url}"
+"#
+                )
+            })
+            .collect();
+        common::config(&buckets)
+    }
+
+    async fn abort_mock(server: &MockServer, expected: u64) {
+        Mock::given(method("DELETE"))
+            .and(path("/files/old.bin"))
+            .and(query_param("uploadId", "u-old"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(expected)
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_aborts_old_uploads() {
+        let server = s3().await;
+        abort_mock(&server, 1).await;
+
+        let results = gc::run(&config(&server.uri(), &["files"]), DAY, false).await;
+
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_ok());
+        let report = results[0].result.as_ref().unwrap();
+        assert_eq!(report.listed, 2);
+        assert_eq!(report.recent, 1);
+        assert_eq!(report.aborted.len(), 1);
+        assert_eq!(report.aborted[0].upload_id, "u-old");
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_aborts_nothing() {
+        let server = s3().await;
+        abort_mock(&server, 0).await;
+
+        let results = gc::run(&config(&server.uri(), &["files"]), DAY, true).await;
+
+        let report = results[0].result.as_ref().unwrap();
+        assert_eq!(report.aborted.len(), 1);
+        let table = gc::summary_table(&results, true);
+        assert!(table.contains("ORPHANED"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_bucket_reported() {
+        let server = s3().await;
+        abort_mock(&server, 1).await;
+
+        let results = gc::run(&config(&server.uri(), &["broken", "files"]), DAY, false).await;
+
+        assert_eq!(results.len(), 2);
+        assert!(!results[0].is_ok());
+        assert!(results[1].is_ok());
+        let table = gc::summary_table(&results, false);
+        assert!(table.contains("error: broken: "));
+    }
+}
@@ -0,0 +1,96 @@
+//! Host Routing Integration Tests
+//!
+//! Tests for selecting buckets by `Host` header (`hosts` in `BucketConfig`).
+//!
+//! ## Test Coverage
+//!
+//! - Uploads to a tenant subdomain are stored in that tenant's bucket
+//! - Wildcard host patterns match any single subdomain label
+//! - Hosts matching no pattern fall back to path prefix routing
+//! - Invalid host patterns fail validation
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::config::Config;
+    use std::net::SocketAddr;
+    use std::path::Path;
+
+    fn config(tenant_a: &Path, tenants: &Path, shared: &Path) -> Config {
+        let buckets = format!(
+            r#"  - name: tenant-a
+    path_prefix: /
+    hosts: ["tenant-a.upload.example.com"]
+    s3:
+      bucket: tenant-a
+      region: us-east-1
+    storage:
+      type: local
+      root: "{}"
+  - name: tenants
+    path_prefix: /
+    hosts: ["*.upload.example.com"]
+    s3:
+      bucket: tenants
+      region: us-east-1
+    storage:
+      type: local
+      root: "{}"
+  - name: shared
+    path_prefix: /uploads
+    s3:
+      bucket: shared
+      region: us-east-1
+    storage:
+      type: local
+      root: "{}"
+"#,
+            tenant_a.display(),
+            tenants.display(),
+            shared.display()
+        );
+        common::config(&buckets)
+    }
+
+    async fn put(addr: SocketAddr, host: &str, path: &str) -> u16 {
+        common::put(addr, path, &[("host", host)], "tenant data")
+            .await
+            .status()
+            .as_u16()
+    }
+
+    #[tokio::test]
+    async fn test_uploads_routed_by_host() {
+        let tenant_a = tempfile::tempdir().unwrap();
+        let tenants = tempfile::tempdir().unwrap();
+        let shared = tempfile::tempdir().unwrap();
+        let addr = common::start(config(tenant_a.path(), tenants.path(), shared.path())).await;
+
+        assert_eq!(
+            put(addr, "tenant-a.upload.example.com", "/a.txt").await,
+            200
+        );
+        assert_eq!(
+            put(addr, "tenant-b.upload.example.com", "/b.txt").await,
+            200
+        );
+        assert_eq!(put(addr, "upload.example.com", "/uploads/c.txt").await, 200);
+        assert_eq!(put(addr, "upload.example.com", "/d.txt").await, 404);
+
+        assert!(tenant_a.path().join("a.txt").exists());
+        assert!(tenants.path().join("b.txt").exists());
+        assert!(shared.path().join("c.txt").exists());
+        assert!(!tenants.path().join("a.txt").exists());
+    }
+
+    #[test]
+    fn test_invalid_host_pattern_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = config(dir.path(), dir.path(), dir.path());
+        config.buckets[1].hosts = vec!["tenant-*.upload.example.com".into()];
+
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,218 @@
+//! HTTP/2 Listener Integration Tests
+//!
+//! Tests for HTTP/2 on the inbound listener.
+//!
+//! ## Test Coverage
+//!
+//! - Prior-knowledge h2c uploads on a plaintext listener
+//! - Many concurrent uploads multiplexed over one HTTP/2 connection
+//! - HTTP/2 negotiated with ALPN on a TLS listener
+//! - HTTP/1.1 clients are still served
+//! - Disabling HTTP/2 falls back to HTTP/1.1 only
+//! - Invalid HTTP/2 tuning is rejected by config validation
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use bytes::Bytes;
+    use http_body_util::{BodyExt, Full};
+    use hyper::{Request, StatusCode, Version};
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use mizuchi_uploadr::config::Config;
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, ServerName};
+    use std::path::Path;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    type Http2Sender = hyper::client::conn::http2::SendRequest<Full<Bytes>>;
+
+    fn fixture_path(name: &str) -> String {
+        format!(
+            "{}/tests/fixtures/mtls/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        )
+    }
+
+    fn config(root: &Path, server_extra: &str) -> Config {
+        let yaml = format!(
+            r#"
+server:
+  address: "127.0.0.1:0"
+{server_extra}
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: uploads
+      region: us-east-1
+    storage:
+      type: local
+      root: "{root}"
+"#,
+            root = root.display(),
+        );
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    /// Open a prior-knowledge HTTP/2 connection over any stream
+    async fn h2_handshake<S>(stream: S) -> Http2Sender
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+    {
+        let (sender, conn) =
+            hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+                .await
+                .unwrap();
+        tokio::spawn(conn);
+        sender
+    }
+
+    async fn put(sender: &mut Http2Sender, key: &str, body: &'static str) -> StatusCode {
+        let request = Request::put(format!("http://localhost/uploads/{}", key))
+            .body(Full::new(Bytes::from_static(body.as_bytes())))
+            .unwrap();
+        let response = sender.send_request(request).await.unwrap();
+        assert_eq!(response.version(), Version::HTTP_2);
+        let status = response.status();
+        response.into_body().collect().await.unwrap();
+        status
+    }
+
+    #[tokio::test]
+    async fn test_h2c_prior_knowledge_upload() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path(), "")).await;
+
+        let mut sender = h2_handshake(TcpStream::connect(addr).await.unwrap()).await;
+        assert_eq!(
+            put(&mut sender, "h2c.txt", "over h2c").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            std::fs::read(dir.path().join("h2c.txt")).unwrap(),
+            b"over h2c"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_uploads_share_one_connection() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(
+            dir.path(),
+            "  http:\n    http2:\n      max_concurrent_streams: 8\n      initial_stream_window_size: 1048576",
+        ))
+        .await;
+
+        let mut sender = h2_handshake(TcpStream::connect(addr).await.unwrap()).await;
+        // Complete one request first so the client has the server's SETTINGS
+        // (and its stream limit) before opening streams concurrently
+        assert_eq!(
+            put(&mut sender, "file-0.txt", "warm-up").await,
+            StatusCode::OK
+        );
+
+        let uploads = (1..32).map(|i| {
+            let mut sender = sender.clone();
+            tokio::spawn(async move {
+                let key = format!("file-{}.txt", i);
+                put(&mut sender, &key, "multiplexed").await
+            })
+        });
+
+        for status in futures::future::join_all(uploads).await {
+            assert_eq!(status.unwrap(), StatusCode::OK);
+        }
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 32);
+    }
+
+    #[tokio::test]
+    async fn test_h2_negotiated_with_alpn() {
+        let dir = tempfile::tempdir().unwrap();
+        let tls = format!(
+            "  tls:\n    cert_path: \"{}\"\n    key_path: \"{}\"",
+            fixture_path("server.pem"),
+            fixture_path("server.key")
+        );
+        let addr = common::start(config(dir.path(), &tls)).await;
+
+        let mut roots = rustls::RootCertStore::empty();
+        roots
+            .add(CertificateDer::from_pem_file(fixture_path("ca.pem")).unwrap())
+            .unwrap();
+        let mut client_config = rustls::ClientConfig::builder_with_provider(Arc::new(
+            rustls::crypto::aws_lc_rs::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+        client_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(client_config));
+        let stream = connector
+            .connect(
+                ServerName::try_from("localhost").unwrap(),
+                TcpStream::connect(addr).await.unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(stream.get_ref().1.alpn_protocol(), Some(&b"h2"[..]));
+
+        let mut sender = h2_handshake(stream).await;
+        assert_eq!(put(&mut sender, "tls.txt", "over h2").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_http1_still_served() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path(), "")).await;
+
+        let response = reqwest::Client::new()
+            .put(format!("http://{}/uploads/h1.txt", addr))
+            .body("over http/1.1")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.version(), reqwest::Version::HTTP_11);
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_http2_disabled_rejects_prior_knowledge() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(
+            dir.path(),
+            "  http:\n    http2:\n      enabled: false",
+        ))
+        .await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        let response = String::from_utf8_lossy(&response);
+        assert!(
+            response.is_empty() || response.starts_with("HTTP/1.1 4"),
+            "HTTP/1.1-only listener should not speak h2: {}",
+            response
+        );
+    }
+
+    #[test]
+    fn test_invalid_window_size_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(
+            dir.path(),
+            "  http:\n    http2:\n      initial_stream_window_size: 4294967295",
+        );
+        assert!(config.validate().is_err());
+    }
+}
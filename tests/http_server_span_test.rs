@@ -0,0 +1,183 @@
+//! HTTP Server Span Tests
+//!
+//! Tests for the per-request server span created by `PingoraServer`.
+//!
+//! ## Test Coverage
+//!
+//! - Every request gets an `http.request` span with HTTP semconv fields
+//! - `http.route` is the matched bucket route, not the raw path
+//! - Request and response sizes are recorded
+//! - 5xx responses mark the span as failed and record an exception
+//! - Error responses record the error category as `error.type`
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::config::Config;
+    use std::collections::HashMap;
+    use std::fmt;
+    use std::net::SocketAddr;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Subscriber};
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::registry::LookupSpan;
+    use tracing_subscriber::Layer;
+
+    /// Fields recorded on one span
+    #[derive(Debug, Default, Clone)]
+    struct Fields(HashMap<String, String>);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    /// Layer collecting the fields of closed `http.request` spans
+    #[derive(Clone, Default)]
+    struct RequestSpans(Arc<Mutex<Vec<Fields>>>);
+
+    impl RequestSpans {
+        fn take(&self) -> Vec<Fields> {
+            std::mem::take(&mut *self.0.lock().unwrap())
+        }
+    }
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for RequestSpans {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            if attrs.metadata().name() == "http.request" {
+                let mut fields = Fields::default();
+                attrs.record(&mut fields);
+                ctx.span(id).unwrap().extensions_mut().insert(fields);
+            }
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+            if let Some(span) = ctx.span(id) {
+                if let Some(fields) = span.extensions_mut().get_mut::<Fields>() {
+                    values.record(fields);
+                }
+            }
+        }
+
+        fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+            let mut event_fields = Fields::default();
+            event.record(&mut event_fields);
+            let Some(message) = event_fields.0.remove("exception.message") else {
+                return;
+            };
+            if let Some(span) = ctx.event_span(event) {
+                if let Some(fields) = span.extensions_mut().get_mut::<Fields>() {
+                    fields.0.insert("exception".to_string(), message);
+                }
+            }
+        }
+
+        fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+            let span = ctx.span(&id).unwrap();
+            let fields = span.extensions().get::<Fields>().cloned();
+            if let Some(fields) = fields {
+                self.0.lock().unwrap().push(fields);
+            }
+        }
+    }
+
+    fn config(root: &Path) -> Config {
+        common::config(&common::local_bucket("docs", root, ""))
+    }
+
+    async fn start(root: &Path) -> SocketAddr {
+        common::start(config(root)).await
+    }
+
+    // The default current-thread runtime runs the server on the test thread,
+    // so the thread-local subscriber sees its spans.
+    #[tokio::test]
+    async fn test_request_span_fields() {
+        let spans = RequestSpans::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+        let dir = tempfile::tempdir().unwrap();
+        let addr = start(dir.path()).await;
+        let client = reqwest::Client::new();
+
+        let response = client
+            .put(format!("http://{}/docs/a/b.txt", addr))
+            .body("hello")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        let spans = spans.take();
+        assert_eq!(spans.len(), 1);
+        let fields = &spans[0].0;
+        assert_eq!(fields["otel.kind"], "server");
+        assert_eq!(fields["http.method"], "PUT");
+        assert_eq!(fields["http.target"], "/docs/a/b.txt");
+        assert_eq!(fields["http.route"], "/docs/{key}");
+        assert_eq!(fields["http.status_code"], "200");
+        assert_eq!(fields["http.request_content_length"], "5");
+        assert_eq!(fields["http.response_content_length"], "17");
+        assert_eq!(fields["client.address"], "127.0.0.1");
+        assert!(!fields.contains_key("otel.status_code"));
+        assert!(!fields.contains_key("exception"));
+    }
+
+    #[tokio::test]
+    async fn test_unrouted_request_has_no_route() {
+        let spans = RequestSpans::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+        let dir = tempfile::tempdir().unwrap();
+        let addr = start(dir.path()).await;
+
+        let response = reqwest::get(format!("http://{}/nowhere", addr))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+
+        let spans = spans.take();
+        let fields = &spans[0].0;
+        assert_eq!(fields["http.status_code"], "404");
+        assert!(!fields.contains_key("http.route"));
+        // Client errors are not server span errors
+        assert!(!fields.contains_key("otel.status_code"));
+    }
+
+    #[tokio::test]
+    async fn test_server_error_records_exception() {
+        let spans = RequestSpans::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+        let dir = tempfile::tempdir().unwrap();
+        // A file where the upload needs a directory makes the write fail
+        std::fs::write(dir.path().join("blocked"), b"").unwrap();
+        let addr = start(dir.path()).await;
+
+        let response = reqwest::Client::new()
+            .put(format!("http://{}/docs/blocked/c.txt", addr))
+            .body("hello")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 500);
+
+        let spans = spans.take();
+        let fields = &spans[0].0;
+        assert_eq!(fields["http.status_code"], "500");
+        assert_eq!(fields["otel.status_code"], "ERROR");
+        assert!(fields["exception"].starts_with("Upload failed"));
+        assert_eq!(fields["error.type"], "server");
+    }
+}
@@ -0,0 +1,74 @@
+//! Authentication and authorization failures
+//!
+//! ## Test Coverage
+//!
+//! - Uploads without a valid JWT are rejected with 401 and not stored
+//! - OPA denials are rejected with 403 and not stored
+
+use super::harness::{Harness, JWT_SECRET};
+
+fn jwt_auth() -> String {
+    format!(
+        r#"    auth:
+      enabled: true
+      jwt:
+        secret: {JWT_SECRET}
+        algorithm: HS256"#
+    )
+}
+
+#[tokio::test]
+async fn test_missing_and_invalid_tokens_rejected() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let proxy = harness.serve("jwt", &jwt_auth()).await;
+    let s3 = harness.s3_client("jwt");
+
+    let response = proxy.put("anonymous.txt", "x", None).await;
+    assert_eq!(response.status(), 401);
+    let response = proxy.put("forged.txt", "x", Some("not.a.jwt")).await;
+    assert_eq!(response.status(), 401);
+    let token = Harness::jwt("alice");
+    let response = proxy.put("alice.txt", "x", Some(&token)).await;
+    assert_eq!(response.status(), 200);
+
+    assert!(s3.head_object("anonymous.txt").await.unwrap().is_none());
+    assert!(s3.head_object("forged.txt").await.unwrap().is_none());
+    assert!(s3.head_object("alice.txt").await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn test_opa_denial_rejected() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let harness = harness
+        .with_opa(
+            r#"package mizuchi
+
+import rego.v1
+
+default allow := false
+
+allow if input.subject == "alice"
+"#,
+        )
+        .await;
+    let authz = format!(
+        "{}\n    authz:\n      type: opa\n      url: \"{}\"\n      policy_path: mizuchi/allow",
+        jwt_auth(),
+        harness.opa_url.as_deref().unwrap()
+    );
+    let proxy = harness.serve("opa", &authz).await;
+    let s3 = harness.s3_client("opa");
+
+    let alice = Harness::jwt("alice");
+    let response = proxy.put("alice.txt", "x", Some(&alice)).await;
+    assert_eq!(response.status(), 200);
+    let bob = Harness::jwt("bob");
+    let response = proxy.put("bob.txt", "x", Some(&bob)).await;
+    assert_eq!(response.status(), 403);
+
+    assert!(s3.head_object("bob.txt").await.unwrap().is_none());
+}
@@ -0,0 +1,286 @@
+//! Container-backed test harness
+//!
+//! Starts MinIO (and, when a test asks for it, OPA) with testcontainers and
+//! runs the real `mizuchi-uploadr` binary against them. Containers and the
+//! server process are removed when the [`Harness`] and [`Proxy`] are
+//! dropped.
+
+use aws_sigv4::http_request::{sign, SignableBody, SignableRequest, SigningSettings};
+use aws_sigv4::sign::v4;
+use mizuchi_uploadr::s3::{S3Client, S3ClientConfig};
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
+use std::process::Stdio;
+use std::time::{Duration, SystemTime};
+use testcontainers::core::{IntoContainerPort, WaitFor};
+use testcontainers::runners::AsyncRunner;
+use testcontainers::{ContainerAsync, GenericImage, ImageExt};
+use testcontainers_modules::minio::MinIO;
+use tokio::process::{Child, Command};
+
+/// Credentials of the MinIO container
+pub const ACCESS_KEY: &str = "minioadmin";
+pub const SECRET_KEY: &str = "minioadmin";
+
+/// JWT secret of [`Harness::jwt`] tokens
+pub const JWT_SECRET: &str = "integration-test-secret";
+
+/// SHA-256 of an empty payload
+const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+const OPA_IMAGE: &str = "openpolicyagent/opa";
+const OPA_TAG: &str = "0.70.0";
+
+/// How long the server binary gets to start listening
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Whether a Docker daemon is reachable
+///
+/// Checked up front so machines without Docker skip these tests rather than
+/// failing them.
+pub fn docker_available() -> bool {
+    std::env::var_os("DOCKER_HOST").is_some() || Path::new("/var/run/docker.sock").exists()
+}
+
+/// Running MinIO (and optionally OPA) containers
+pub struct Harness {
+    /// MinIO endpoint, e.g. `http://localhost:32768`
+    pub s3_endpoint: String,
+    /// OPA URL, once [`Harness::with_opa`] started it
+    pub opa_url: Option<String>,
+    _minio: ContainerAsync<MinIO>,
+    _opa: Option<ContainerAsync<GenericImage>>,
+}
+
+impl Harness {
+    /// Start MinIO, or `None` (with a note on stderr) without Docker
+    pub async fn start() -> Option<Self> {
+        if !docker_available() {
+            eprintln!("Skipping: Docker is not available");
+            return None;
+        }
+
+        let minio = MinIO::default()
+            .start()
+            .await
+            .expect("Failed to start MinIO");
+        let host = minio.get_host().await.expect("No MinIO host");
+        let port = minio.get_host_port_ipv4(9000).await.expect("No MinIO port");
+
+        Some(Self {
+            s3_endpoint: format!("http://{}:{}", host, port),
+            opa_url: None,
+            _minio: minio,
+            _opa: None,
+        })
+    }
+
+    /// Also start OPA, serving `policy` (Rego) as `mizuchi/allow`
+    pub async fn with_opa(mut self, policy: &str) -> Self {
+        let opa = GenericImage::new(OPA_IMAGE, OPA_TAG)
+            .with_exposed_port(8181.tcp())
+            .with_wait_for(WaitFor::message_on_stderr("Initializing server"))
+            .with_cmd(["run", "--server", "--addr=0.0.0.0:8181"])
+            .start()
+            .await
+            .expect("Failed to start OPA");
+        let host = opa.get_host().await.expect("No OPA host");
+        let port = opa.get_host_port_ipv4(8181).await.expect("No OPA port");
+        let url = format!("http://{}:{}", host, port);
+
+        let response = reqwest::Client::new()
+            .put(format!("{}/v1/policies/mizuchi", url))
+            .header("Content-Type", "text/plain")
+            .body(policy.to_string())
+            .send()
+            .await
+            .expect("Failed to load OPA policy");
+        assert!(
+            response.status().is_success(),
+            "OPA rejected the policy: {}",
+            response.text().await.unwrap_or_default()
+        );
+
+        self.opa_url = Some(url);
+        self._opa = Some(opa);
+        self
+    }
+
+    /// Create an S3 bucket on MinIO
+    pub async fn create_bucket(&self, bucket: &str) {
+        let url = format!("{}/{}", self.s3_endpoint, bucket);
+        let host = self.s3_endpoint.trim_start_matches("http://");
+
+        let credentials =
+            aws_credential_types::Credentials::new(ACCESS_KEY, SECRET_KEY, None, None, "harness");
+        let identity = aws_smithy_runtime_api::client::identity::Identity::new(credentials, None);
+        let params = v4::SigningParams::builder()
+            .identity(&identity)
+            .region("us-east-1")
+            .name("s3")
+            .time(SystemTime::now())
+            .settings(SigningSettings::default())
+            .build()
+            .unwrap()
+            .into();
+        let headers = [("host", host), ("x-amz-content-sha256", EMPTY_SHA256)];
+        let signable =
+            SignableRequest::new("PUT", &url, headers.into_iter(), SignableBody::Bytes(&[]))
+                .unwrap();
+        let (instructions, _) = sign(signable, &params).unwrap().into_parts();
+
+        let mut request = reqwest::Client::new()
+            .put(&url)
+            .header("x-amz-content-sha256", EMPTY_SHA256);
+        for (name, value) in instructions.headers() {
+            request = request.header(name, value);
+        }
+        let response = request.send().await.expect("CreateBucket failed");
+        assert!(
+            response.status().is_success(),
+            "CreateBucket {} returned {}",
+            bucket,
+            response.status()
+        );
+    }
+
+    /// S3 client of `bucket`, for checking what the proxy stored
+    pub fn s3_client(&self, bucket: &str) -> S3Client {
+        S3Client::new(S3ClientConfig {
+            bucket: bucket.to_string(),
+            region: "us-east-1".to_string(),
+            endpoint: Some(self.s3_endpoint.clone()),
+            access_key: Some(ACCESS_KEY.to_string()),
+            secret_key: Some(SECRET_KEY.to_string()),
+            retry: None,
+            timeout: None,
+            pool: None,
+        })
+        .unwrap()
+    }
+
+    /// Create `bucket` and start the server binary with it mounted at
+    /// `/uploads`
+    ///
+    /// `extra` is YAML appended to the bucket entry (indented by four
+    /// spaces), e.g. its `auth` or `upload` section.
+    pub async fn serve(&self, bucket: &str, extra: &str) -> Proxy {
+        self.create_bucket(bucket).await;
+
+        let addr = free_addr();
+        let config = format!(
+            r#"
+server:
+  address: "{addr}"
+metrics:
+  enabled: false
+buckets:
+  - name: {bucket}
+    path_prefix: /uploads
+    s3:
+      bucket: {bucket}
+      region: us-east-1
+      endpoint: "{endpoint}"
+      access_key: {ACCESS_KEY}
+      secret_key: {SECRET_KEY}
+{extra}
+"#,
+            endpoint = self.s3_endpoint,
+        );
+        Proxy::start(addr, &config).await
+    }
+
+    /// HS256 token for `subject`, valid for an hour
+    pub fn jwt(subject: &str) -> String {
+        use jsonwebtoken::{encode, EncodingKey, Header};
+
+        let exp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 3600;
+        encode(
+            &Header::default(),
+            &serde_json::json!({ "sub": subject, "exp": exp }),
+            &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+}
+
+/// `mizuchi-uploadr` process, killed on drop
+pub struct Proxy {
+    pub addr: SocketAddr,
+    pub client: reqwest::Client,
+    _process: Child,
+    _dir: tempfile::TempDir,
+}
+
+impl Proxy {
+    /// Run the binary with `config` and wait until it accepts connections
+    async fn start(addr: SocketAddr, config: &str) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.yaml");
+        std::fs::write(&config_path, config).unwrap();
+
+        let mut process = Command::new(env!("CARGO_BIN_EXE_mizuchi-uploadr"))
+            .arg("--config")
+            .arg(&config_path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .expect("Failed to start mizuchi-uploadr");
+
+        let deadline = tokio::time::Instant::now() + STARTUP_TIMEOUT;
+        while tokio::net::TcpStream::connect(addr).await.is_err() {
+            if let Ok(Some(status)) = process.try_wait() {
+                panic!("mizuchi-uploadr exited during startup: {}", status);
+            }
+            assert!(
+                tokio::time::Instant::now() < deadline,
+                "mizuchi-uploadr did not listen on {} within {:?}",
+                addr,
+                STARTUP_TIMEOUT
+            );
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        Self {
+            addr,
+            client: reqwest::Client::new(),
+            _process: process,
+            _dir: dir,
+        }
+    }
+
+    /// URL of `key` under the bucket
+    pub fn url(&self, key: &str) -> String {
+        format!("http://{}/uploads/{}", self.addr, key)
+    }
+
+    /// PUT `body` to `key`, with an optional bearer token
+    pub async fn put(
+        &self,
+        key: &str,
+        body: impl Into<reqwest::Body>,
+        token: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = self.client.put(self.url(key)).body(body);
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("Upload request failed")
+    }
+}
+
+/// Loopback address no one is listening on
+fn free_addr() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap()
+}
+
+/// `len` bytes of a repeating pattern
+pub fn payload(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
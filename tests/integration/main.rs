@@ -0,0 +1,21 @@
+//! Container-Backed Integration Tests
+//!
+//! Runs the real `mizuchi-uploadr` binary against MinIO (and OPA) started
+//! with testcontainers, so the only prerequisite is a running Docker
+//! daemon; nothing has to be started by hand. Without Docker every test is
+//! skipped.
+//!
+//! ```bash
+//! cargo test --test integration
+//! ```
+//!
+//! ## Test Categories
+//!
+//! - `upload`: Simple PUT uploads
+//! - `multipart`: Multipart and client-aborted uploads
+//! - `auth`: JWT authentication and OPA authorization failures
+
+mod auth;
+mod harness;
+mod multipart;
+mod upload;
@@ -0,0 +1,70 @@
+//! Multipart uploads and aborted uploads
+//!
+//! ## Test Coverage
+//!
+//! - Bodies above `multipart_threshold` are stored as multipart uploads
+//! - A client disconnecting mid-body leaves neither an object nor an
+//!   unfinished multipart upload behind
+
+use super::harness::{payload, Harness};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+const MIB: usize = 1024 * 1024;
+
+/// Spill bodies to disk and upload those above 5MiB in 5MiB parts
+const MULTIPART: &str = r#"    upload:
+      multipart_threshold: 5242880
+      part_size: 5242880
+      spill:
+        memory_limit: 1048576"#;
+
+#[tokio::test]
+async fn test_large_upload_uses_multipart() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let proxy = harness.serve("multipart", MULTIPART).await;
+
+    let response = proxy.put("large.bin", payload(12 * MIB), None).await;
+
+    assert_eq!(response.status(), 200);
+    // S3 ETags of multipart objects end with the part count
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert!(etag.ends_with("-3\""), "Not a multipart ETag: {}", etag);
+    let stored = harness
+        .s3_client("multipart")
+        .head_object("large.bin")
+        .await
+        .unwrap()
+        .expect("Object not stored");
+    assert_eq!(stored.etag, etag);
+}
+
+#[tokio::test]
+async fn test_client_abort_leaves_nothing() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let proxy = harness.serve("aborted", MULTIPART).await;
+
+    // Declare 12MiB, send half of it, then hang up
+    let mut stream = tokio::net::TcpStream::connect(proxy.addr).await.unwrap();
+    let head = format!(
+        "PUT /uploads/aborted.bin HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\n\r\n",
+        proxy.addr,
+        12 * MIB
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream.write_all(&payload(6 * MIB)).await.unwrap();
+    drop(stream);
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    let s3 = harness.s3_client("aborted");
+    assert!(s3.head_object("aborted.bin").await.unwrap().is_none());
+    assert!(s3.list_multipart_uploads().await.unwrap().is_empty());
+
+    // The proxy keeps serving
+    let response = proxy.put("after.bin", payload(MIB), None).await;
+    assert_eq!(response.status(), 200);
+}
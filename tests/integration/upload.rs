@@ -0,0 +1,47 @@
+//! Simple PUT uploads through the proxy into MinIO
+//!
+//! ## Test Coverage
+//!
+//! - Uploaded objects land in the bucket with the ETag the proxy returned
+//! - Nested keys are stored as sent
+
+use super::harness::{payload, Harness};
+
+#[tokio::test]
+async fn test_simple_put_stored() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let proxy = harness.serve("simple", "").await;
+
+    let response = proxy.put("hello.txt", "Hello, Mizuchi!", None).await;
+
+    assert_eq!(response.status(), 200);
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    let stored = harness
+        .s3_client("simple")
+        .head_object("hello.txt")
+        .await
+        .unwrap()
+        .expect("Object not stored");
+    assert_eq!(stored.etag, etag);
+}
+
+#[tokio::test]
+async fn test_nested_keys_stored() {
+    let Some(harness) = Harness::start().await else {
+        return;
+    };
+    let proxy = harness.serve("nested", "").await;
+    let s3 = harness.s3_client("nested");
+
+    for key in ["a/b/c.bin", "2024/10/16/report.pdf"] {
+        let response = proxy.put(key, payload(64 * 1024), None).await;
+        assert_eq!(response.status(), 200, "Upload of {} failed", key);
+        assert!(
+            s3.head_object(key).await.unwrap().is_some(),
+            "{} missing",
+            key
+        );
+    }
+}
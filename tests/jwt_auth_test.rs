@@ -31,6 +31,7 @@ mod tests {
             query: None,
             method: "PUT".to_string(),
             path: "/bucket/key".to_string(),
+            peer_certificates: Vec::new(),
         }
     }
 
@@ -127,6 +128,7 @@ mod tests {
             query: None,
             method: "PUT".to_string(),
             path: "/bucket/key".to_string(),
+            peer_certificates: Vec::new(),
         };
 
         let result = auth.authenticate(&request).await;
@@ -421,6 +423,7 @@ P2qpWPDC/qNK83UOu9Y26VY7qwVH4M6D8jPXpN8jSGwgCdVCpI5aEiYgbg==
             query: None,
             method: "PUT".to_string(),
             path: "/bucket/key".to_string(),
+            peer_certificates: Vec::new(),
         };
 
         let result = auth.authenticate(&request).await;
@@ -443,6 +446,7 @@ P2qpWPDC/qNK83UOu9Y26VY7qwVH4M6D8jPXpN8jSGwgCdVCpI5aEiYgbg==
             query: Some(format!("token={}", token)),
             method: "PUT".to_string(),
             path: "/bucket/key".to_string(),
+            peer_certificates: Vec::new(),
         };
 
         let result = auth.authenticate(&request).await;
@@ -0,0 +1,283 @@
+//! JWT Revocation Integration Tests
+//!
+//! Tests for rejecting revoked JWTs with revocation lists kept in a file or
+//! in Redis (an in-process RESP server), and for the server's response.
+//!
+//! ## Test Coverage
+//!
+//! - Tokens with a listed `jti` are rejected
+//! - Tokens issued before their subject's cutoff are rejected, reissued
+//!   ones accepted
+//! - File changes are picked up on the next refresh
+//! - Redis sets and hashes are loaded, and an unreachable Redis fails closed
+//! - The server answers revoked tokens with `401` and a Bearer challenge
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use mizuchi_uploadr::auth::jwt::JwtAuthenticator;
+    use mizuchi_uploadr::auth::revocation::{
+        FileRevocationStore, RedisRevocationStore, RevocationList,
+    };
+    use mizuchi_uploadr::auth::{AuthError, AuthRequest, Authenticator};
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::redis::RedisClient;
+    use mizuchi_uploadr::server::pingora::PingoraServer;
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream};
+    use tokio::net::TcpListener;
+
+    const SECRET: &str = "revocation-secret";
+
+    fn token(subject: &str, jti: &str, issued_at: i64) -> String {
+        let claims = serde_json::json!({
+            "sub": subject,
+            "jti": jti,
+            "iat": issued_at,
+            "exp": chrono::Utc::now().timestamp() + 3600,
+        });
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    fn request(token: &str) -> AuthRequest {
+        AuthRequest {
+            headers: [("authorization".to_string(), format!("Bearer {}", token))].into(),
+            query: None,
+            method: "PUT".into(),
+            path: "/files/report.pdf".into(),
+            peer_certificates: vec![],
+        }
+    }
+
+    fn authenticator(list: RevocationList) -> JwtAuthenticator {
+        JwtAuthenticator::new_hs256(SECRET).with_revocation_list(Arc::new(list))
+    }
+
+    #[tokio::test]
+    async fn test_file_revocation_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("revoked.yaml");
+        std::fs::write(&path, "jtis: [leaked]\nnot_before:\n  mallory: 2000\n").unwrap();
+
+        let store = FileRevocationStore::new(path.to_str().unwrap()).unwrap();
+        let auth = authenticator(RevocationList::new(Arc::new(store), Duration::ZERO));
+
+        let result = auth
+            .authenticate(&request(&token("alice", "leaked", 5000)))
+            .await;
+        assert!(
+            matches!(result, Err(AuthError::TokenRevoked)),
+            "{:?}",
+            result
+        );
+        let result = auth
+            .authenticate(&request(&token("mallory", "old", 1999)))
+            .await;
+        assert!(
+            matches!(result, Err(AuthError::TokenRevoked)),
+            "{:?}",
+            result
+        );
+
+        let result = auth
+            .authenticate(&request(&token("mallory", "reissued", 2000)))
+            .await
+            .unwrap();
+        assert_eq!(result.subject, "mallory");
+        auth.authenticate(&request(&token("alice", "fresh", 5000)))
+            .await
+            .unwrap();
+
+        // Revoked after the last load
+        std::fs::write(&path, "jtis: [leaked, fresh]\n").unwrap();
+        let result = auth
+            .authenticate(&request(&token("alice", "fresh", 5000)))
+            .await;
+        assert!(
+            matches!(result, Err(AuthError::TokenRevoked)),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_missing_file_is_rejected() {
+        assert!(matches!(
+            FileRevocationStore::new("/nonexistent/revoked.yaml"),
+            Err(AuthError::ConfigError(_))
+        ));
+    }
+
+    /// Serve `SMEMBERS` and `HGETALL` from fixed sets and hashes over RESP2
+    async fn fake_redis(
+        sets: HashMap<String, Vec<String>>,
+        hashes: HashMap<String, Vec<(String, String)>>,
+    ) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sets, hashes) = (Arc::new(sets), Arc::new(hashes));
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let (sets, hashes) = (Arc::clone(&sets), Arc::clone(&hashes));
+                tokio::spawn(async move {
+                    let mut stream = BufStream::new(socket);
+                    loop {
+                        let mut line = String::new();
+                        if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+                            return;
+                        }
+                        let argc: usize = line.trim()[1..].parse().unwrap();
+                        let mut args = Vec::new();
+                        for _ in 0..argc {
+                            line.clear();
+                            stream.read_line(&mut line).await.unwrap();
+                            let len: usize = line.trim()[1..].parse().unwrap();
+                            let mut arg = vec![0; len + 2];
+                            stream.read_exact(&mut arg).await.unwrap();
+                            arg.truncate(len);
+                            args.push(String::from_utf8(arg).unwrap());
+                        }
+                        let items: Vec<String> = match args[0].as_str() {
+                            "SMEMBERS" => sets.get(&args[1]).cloned().unwrap_or_default(),
+                            "HGETALL" => hashes
+                                .get(&args[1])
+                                .into_iter()
+                                .flatten()
+                                .flat_map(|(field, value)| [field.clone(), value.clone()])
+                                .collect(),
+                            _ => Vec::new(),
+                        };
+                        let mut reply = format!("*{}\r\n", items.len());
+                        for item in items {
+                            reply.push_str(&format!("${}\r\n{}\r\n", item.len(), item));
+                        }
+                        stream.write_all(reply.as_bytes()).await.unwrap();
+                        stream.flush().await.unwrap();
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_redis_revocation_list() {
+        let addr = fake_redis(
+            [("mizuchi:revoked:jti".into(), vec!["leaked".into()])].into(),
+            [(
+                "mizuchi:revoked:not_before".into(),
+                vec![("mallory".into(), "2000".into())],
+            )]
+            .into(),
+        )
+        .await;
+        let client = RedisClient::from_url(&format!("redis://{}", addr)).unwrap();
+        let store = RedisRevocationStore::new(client, "mizuchi:revoked:");
+        let auth = authenticator(RevocationList::new(
+            Arc::new(store),
+            Duration::from_secs(60),
+        ));
+
+        let result = auth
+            .authenticate(&request(&token("alice", "leaked", 5000)))
+            .await;
+        assert!(
+            matches!(result, Err(AuthError::TokenRevoked)),
+            "{:?}",
+            result
+        );
+        let result = auth
+            .authenticate(&request(&token("mallory", "old", 1000)))
+            .await;
+        assert!(
+            matches!(result, Err(AuthError::TokenRevoked)),
+            "{:?}",
+            result
+        );
+        auth.authenticate(&request(&token("mallory", "new", 3000)))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_redis_fails_closed() {
+        // Bind then drop a listener so nothing answers on the port
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let client = RedisClient::from_url(&format!("redis://{}", addr)).unwrap();
+        let store = RedisRevocationStore::new(client, "mizuchi:revoked:");
+        let auth = authenticator(RevocationList::new(
+            Arc::new(store),
+            Duration::from_secs(60),
+        ));
+
+        let result = auth
+            .authenticate(&request(&token("alice", "a", 5000)))
+            .await;
+        assert!(
+            matches!(result, Err(AuthError::ConfigError(_))),
+            "{:?}",
+            result
+        );
+    }
+
+    fn config(root: &Path, revoked: &Path) -> Config {
+        let bucket = format!(
+            r#"    auth:
+      enabled: true
+      jwt:
+        secret: {SECRET}
+        algorithm: HS256
+        revocation:
+          store:
+            type: file
+            path: "{revoked}""#,
+            revoked = revoked.display(),
+        );
+        common::config(&common::local_bucket("files", root, &bucket))
+    }
+
+    #[tokio::test]
+    async fn test_server_rejects_revoked_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let revoked = dir.path().join("revoked.json");
+        std::fs::write(&revoked, r#"{"jtis": ["leaked"]}"#).unwrap();
+        let config = config(dir.path(), &revoked);
+        config.validate().unwrap();
+
+        let server = PingoraServer::new(config).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move { server.run().await });
+
+        let put = |jti: &'static str| {
+            reqwest::Client::new()
+                .put(format!("http://{}/files/report.txt", addr))
+                .bearer_auth(token("alice", jti, chrono::Utc::now().timestamp()))
+                .body("hello")
+                .send()
+        };
+
+        let response = put("leaked").await.unwrap();
+        assert_eq!(response.status(), 401);
+        let challenge = response.headers()["www-authenticate"].to_str().unwrap();
+        assert!(challenge.contains("Token revoked"), "{}", challenge);
+
+        assert_eq!(put("fresh").await.unwrap().status(), 200);
+    }
+}
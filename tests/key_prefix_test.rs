@@ -0,0 +1,154 @@
+//! Key Prefix Integration Tests
+//!
+//! Tests for the per-bucket `key_prefix` jail.
+//!
+//! ## Test Coverage
+//!
+//! - Uploads are stored under the prefix rendered from the caller's subject
+//! - Keys naming another user's folder stay inside the caller's own
+//! - Keys with `..` segments are rejected
+//! - Callers without the claims the prefix needs are refused
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use http_body_util::Full;
+    use hyper::{Request, StatusCode};
+    use mizuchi_uploadr::auth::{AuthError, AuthRequest, AuthResult, Authenticator};
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::UploadService;
+    use std::path::Path;
+    use std::sync::Arc;
+    use tower_service::Service;
+
+    /// Accepts requests carrying `X-User`, with an optional `X-Team` claim
+    struct HeaderAuthenticator;
+
+    #[async_trait]
+    impl Authenticator for HeaderAuthenticator {
+        async fn authenticate(&self, request: &AuthRequest) -> Result<AuthResult, AuthError> {
+            let user = request
+                .headers
+                .get("x-user")
+                .ok_or(AuthError::MissingAuth)?;
+            let claims = request
+                .headers
+                .get("x-team")
+                .map(|team| ("team".to_string(), team.as_str().into()))
+                .into_iter()
+                .collect();
+            Ok(AuthResult {
+                subject: user.clone(),
+                claims,
+            })
+        }
+    }
+
+    async fn service(root: &Path, key_prefix: &str) -> UploadService {
+        let yaml = format!(
+            r#"
+server:
+  address: "127.0.0.1:0"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    key_prefix: "{}"
+    s3:
+      bucket: uploads
+      region: us-east-1
+    storage:
+      type: local
+      root: "{}"
+"#,
+            key_prefix,
+            root.display()
+        );
+        let config: Config = serde_yaml::from_str(&yaml).unwrap();
+        config.validate().unwrap();
+        UploadService::builder()
+            .config(config)
+            .with_authenticator("uploads", Arc::new(HeaderAuthenticator))
+            .build_service()
+            .await
+            .unwrap()
+    }
+
+    async fn upload(
+        service: &mut UploadService,
+        path: &str,
+        headers: &[(&str, &str)],
+    ) -> StatusCode {
+        let mut request = Request::put(path);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let request = request.body(Full::new(Bytes::from("hello"))).unwrap();
+        service.call(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_upload_stored_under_subject_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut service = service(dir.path(), "incoming/{sub}/").await;
+
+        let status = upload(&mut service, "/uploads/a.txt", &[("X-User", "alice")]).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(dir.path().join("incoming/alice/a.txt").exists());
+        assert!(!dir.path().join("a.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_other_folder_stays_inside_own_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut service = service(dir.path(), "incoming/{sub}/").await;
+
+        let status = upload(
+            &mut service,
+            "/uploads/incoming/bob/a.txt",
+            &[("X-User", "alice")],
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(dir
+            .path()
+            .join("incoming/alice/incoming/bob/a.txt")
+            .exists());
+        assert!(!dir.path().join("incoming/bob/a.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_parent_segment_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut service = service(dir.path(), "incoming/{sub}/").await;
+
+        let status = upload(
+            &mut service,
+            "/uploads/../bob/a.txt",
+            &[("X-User", "alice")],
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_missing_claim_forbidden() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut service = service(dir.path(), "teams/{team}/").await;
+
+        let without = upload(&mut service, "/uploads/a.txt", &[("X-User", "alice")]).await;
+        let with = upload(
+            &mut service,
+            "/uploads/a.txt",
+            &[("X-User", "alice"), ("X-Team", "blue")],
+        )
+        .await;
+
+        assert_eq!(without, StatusCode::FORBIDDEN);
+        assert_eq!(with, StatusCode::OK);
+        assert!(dir.path().join("teams/blue/a.txt").exists());
+    }
+}
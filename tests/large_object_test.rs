@@ -0,0 +1,120 @@
+//! Large Object Integration Tests
+//!
+//! Tests for bodies beyond the 5GB single PUT limit in `PingoraServer`.
+//!
+//! ## Test Coverage
+//!
+//! - Bodies declared over 5GB go to a multipart upload, never a single PUT
+//! - An interrupted forced multipart upload is aborted
+//! - Bodies over 5TB, or that would need transforming or sniffing whole,
+//!   are rejected with `EntityTooLarge` before anything reaches S3
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use std::net::SocketAddr;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use wiremock::matchers::{method, query_param, query_param_is_missing};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const SIX_GB: u64 = 6 * 1024 * 1024 * 1024;
+
+    /// Start a server storing to `s3`, with `upload` (YAML of the bucket's
+    /// upload section)
+    async fn start(s3: &MockServer, upload: &str) -> SocketAddr {
+        let bucket = format!(
+            r#"  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: uploads
+      region: us-east-1
+      endpoint: "{}"
+      access_key: test
+      secret_key: test
+{}
+"#,
+            s3.uri(),
+            upload
+        );
+        common::start(common::config(&bucket)).await
+    }
+
+    /// Declare `length` bytes, send `sent` of them and hang up, returning
+    /// the raw response
+    async fn put_partial(addr: SocketAddr, length: u64, sent: usize) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let head = format!(
+            "PUT /uploads/huge.bin HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n",
+            length
+        );
+        stream.write_all(head.as_bytes()).await.unwrap();
+        stream.write_all(&vec![b'x'; sent]).await.unwrap();
+        stream.shutdown().await.unwrap();
+        let mut response = Vec::new();
+        let _ = stream.read_to_end(&mut response).await;
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    #[tokio::test]
+    async fn test_over_5gb_forced_to_multipart() {
+        let s3 = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(query_param("uploads", ""))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "<InitiateMultipartUploadResult><UploadId>big-1</UploadId>\
+                 </InitiateMultipartUploadResult>",
+            ))
+            .expect(1)
+            .mount(&s3)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(query_param("uploadId", "big-1"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&s3)
+            .await;
+        Mock::given(method("PUT"))
+            .and(query_param_is_missing("partNumber"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&s3)
+            .await;
+        // A bucket without flow control or spill would otherwise PUT it whole
+        let addr = start(&s3, "").await;
+
+        let response = put_partial(addr, SIX_GB, 64 * 1024).await;
+
+        assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+        // The abort follows the response
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+
+    #[tokio::test]
+    async fn test_unsplittable_bodies_rejected() {
+        let s3 = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&s3)
+            .await;
+        let sniffing = start(&s3, "    upload:\n      content_type:\n        sniff: true").await;
+        let plain = start(&s3, "").await;
+
+        for (addr, length, reason) in [
+            (sniffing, SIX_GB, "sniffs upload content types"),
+            (plain, 6 * 1024 * SIX_GB, "maximum object size of 5TB"),
+        ] {
+            let response = put_partial(addr, length, 0).await;
+            assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+            assert!(
+                response.contains("<Code>EntityTooLarge</Code>"),
+                "{}",
+                response
+            );
+            assert!(response.contains(reason), "{}", response);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Multi-Listener Integration Tests
+//!
+//! Tests for `server.listeners`: several addresses served by one server,
+//! each serving some or all buckets, with or without TLS.
+//!
+//! ## Test Coverage
+//!
+//! - Every listener is bound and reachable by name
+//! - A listener restricted to some buckets answers `404` for the others
+//! - A TLS listener and a plaintext listener run side by side
+//! - Listeners bind and serve with `server.tcp` socket options
+//! - The shutdown signal closes every listener
+
+#[cfg(test)]
+mod tests {
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::server::pingora::PingoraServer;
+    use std::path::Path;
+    use std::time::Duration;
+    use tokio::net::TcpStream;
+
+    fn fixture(name: &str) -> String {
+        format!(
+            "{}/tests/fixtures/mtls/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        )
+    }
+
+    fn config(root: &Path) -> Config {
+        let yaml = format!(
+            r#"
+server:
+  tcp:
+    recv_buffer_size: 1048576
+    nodelay: true
+    keepalive_secs: 30
+    backlog: 64
+  listeners:
+    - name: internal
+      address: "127.0.0.1:0"
+    - name: public
+      address: "127.0.0.1:0"
+      buckets: [user-uploads]
+      tls:
+        cert_path: "{cert}"
+        key_path: "{key}"
+buckets:
+  - name: user-uploads
+    path_prefix: /uploads
+    s3:
+      bucket: user-uploads
+      region: us-east-1
+    storage:
+      type: local
+      root: "{root}/user"
+  - name: service
+    path_prefix: /service
+    s3:
+      bucket: service
+      region: us-east-1
+    storage:
+      type: local
+      root: "{root}/service"
+"#,
+            cert = fixture("server.pem"),
+            key = fixture("server.key"),
+            root = root.display(),
+        );
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    fn https_client() -> reqwest::Client {
+        let ca = std::fs::read(fixture("ca.pem")).unwrap();
+        reqwest::Client::builder()
+            .use_rustls_tls()
+            .add_root_certificate(reqwest::Certificate::from_pem(&ca).unwrap())
+            .build()
+            .unwrap()
+    }
+
+    async fn put(client: &reqwest::Client, url: String) -> u16 {
+        client
+            .put(url)
+            .body("hello")
+            .send()
+            .await
+            .unwrap()
+            .status()
+            .as_u16()
+    }
+
+    #[tokio::test]
+    async fn test_listeners_serve_their_buckets() {
+        let dir = tempfile::tempdir().unwrap();
+        for sub in ["user", "service"] {
+            std::fs::create_dir(dir.path().join(sub)).unwrap();
+        }
+        let config = config(dir.path());
+        config.validate().unwrap();
+
+        let server = PingoraServer::new(config).await.unwrap();
+        let internal = server.listener_addr("internal").unwrap();
+        let public = server.listener_addr("public").unwrap();
+        assert_ne!(internal, public);
+        assert_eq!(server.local_addr().unwrap(), internal);
+        assert!(server.listener_addr("default").is_none());
+        tokio::spawn(async move { server.run().await });
+
+        // The internal listener serves every bucket in plaintext
+        let plain = reqwest::Client::new();
+        let internal_url = |path: &str| format!("http://{}{}", internal, path);
+        assert_eq!(put(&plain, internal_url("/service/a.txt")).await, 200);
+        assert_eq!(put(&plain, internal_url("/uploads/a.txt")).await, 200);
+
+        // The public listener serves user uploads only, over TLS
+        let https = https_client();
+        let public_url = |path: &str| format!("https://localhost:{}{}", public.port(), path);
+        assert_eq!(put(&https, public_url("/uploads/b.txt")).await, 200);
+        assert_eq!(put(&https, public_url("/service/b.txt")).await, 404);
+        assert!(plain
+            .put(format!("http://{}/uploads/c.txt", public))
+            .body("hello")
+            .send()
+            .await
+            .is_err());
+
+        assert!(dir.path().join("user/b.txt").exists());
+        assert!(!dir.path().join("service/b.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_closes_every_listener() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = PingoraServer::new(config(dir.path())).await.unwrap();
+        let addrs = [
+            server.listener_addr("internal").unwrap(),
+            server.listener_addr("public").unwrap(),
+        ];
+
+        server
+            .run_until(tokio::time::sleep(Duration::from_millis(50)))
+            .await
+            .unwrap();
+
+        for addr in addrs {
+            assert!(
+                TcpStream::connect(addr).await.is_err(),
+                "{} still open",
+                addr
+            );
+        }
+    }
+}
@@ -184,6 +184,43 @@ mod tests {
         server.shutdown().await;
     }
 
+    #[tokio::test]
+    async fn test_metrics_include_process_and_runtime_metrics() {
+        use mizuchi_uploadr::metrics::server::{MetricsServer, MetricsServerConfig};
+
+        let config = MetricsServerConfig {
+            address: "127.0.0.1:0".to_string(),
+        };
+
+        let mut server = MetricsServer::new(config);
+        let addr = server.start().await.expect("Server should start");
+
+        let client = reqwest::Client::new();
+        let body = client
+            .get(format!("http://{}/metrics", addr))
+            .send()
+            .await
+            .expect("Should get metrics")
+            .text()
+            .await
+            .unwrap();
+
+        assert!(
+            body.contains("mizuchi_tokio_workers"),
+            "Should contain tokio runtime metrics"
+        );
+        #[cfg(target_os = "linux")]
+        for name in [
+            "process_cpu_seconds_total",
+            "process_resident_memory_bytes",
+            "process_open_fds",
+        ] {
+            assert!(body.contains(name), "Should contain {}", name);
+        }
+
+        server.shutdown().await;
+    }
+
     #[tokio::test]
     async fn test_health_endpoint() {
         use mizuchi_uploadr::metrics::server::{MetricsServer, MetricsServerConfig};
@@ -0,0 +1,409 @@
+//! mTLS Authentication Integration Tests
+//!
+//! Tests for client-certificate authentication over the TLS listener.
+//! Certificates are generated by `tests/fixtures/mtls/generate.sh`.
+//!
+//! ## Test Coverage
+//!
+//! - Certificates chaining to the configured CA are accepted
+//! - Subject is taken from the SAN or CN as configured
+//! - SANs are prefixed with their type
+//! - Revoked (CRL) and untrusted certificates are rejected
+//! - OCSP: good certificates are accepted and cached, revoked ones and
+//!   responses that are forged or about other certificates are rejected,
+//!   and unreachable responders fail closed unless `soft_fail` is set
+//! - Requests without a certificate are rejected for mTLS-only buckets
+//! - End-to-end upload over HTTPS with a client certificate
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::auth::mtls::MtlsAuthenticator;
+    use mizuchi_uploadr::auth::ocsp::OcspChecker;
+    use mizuchi_uploadr::auth::{AuthError, AuthRequest, Authenticator};
+    use mizuchi_uploadr::config::{CertSubjectSource, Config, MtlsOcspConfig, TlsConfig};
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::CertificateDer;
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use wiremock::matchers::{header, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn fixture_path(name: &str) -> String {
+        format!(
+            "{}/tests/fixtures/mtls/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        )
+    }
+
+    fn fixture(name: &str) -> Vec<u8> {
+        std::fs::read(fixture_path(name)).unwrap()
+    }
+
+    fn request_with_cert(name: &str) -> AuthRequest {
+        let cert = CertificateDer::from_pem_slice(&fixture(name)).unwrap();
+        AuthRequest {
+            headers: HashMap::new(),
+            query: None,
+            method: "PUT".into(),
+            path: "/uploads/file.txt".into(),
+            peer_certificates: vec![cert.to_vec()],
+        }
+    }
+
+    fn authenticator(subject: CertSubjectSource) -> MtlsAuthenticator {
+        MtlsAuthenticator::new(&fixture("ca.pem"), &[fixture("crl.pem")], subject).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_valid_certificate_uses_san_subject() {
+        let result = authenticator(CertSubjectSource::San)
+            .authenticate(&request_with_cert("client.pem"))
+            .await
+            .unwrap();
+
+        assert_eq!(result.subject, "uri:spiffe://example.org/uploader");
+        assert_eq!(result.claims["cn"], "uploader");
+        assert_eq!(
+            result.claims["sans"],
+            serde_json::json!([
+                "uri:spiffe://example.org/uploader",
+                "dns:uploader.internal",
+                "email:uploader@example.org"
+            ])
+        );
+        assert_eq!(result.claims["auth_method"], "mtls");
+    }
+
+    #[tokio::test]
+    async fn test_cn_subject() {
+        let result = authenticator(CertSubjectSource::Cn)
+            .authenticate(&request_with_cert("cn-only.pem"))
+            .await
+            .unwrap();
+        assert_eq!(result.subject, "cn-only-service");
+    }
+
+    #[tokio::test]
+    async fn test_san_subject_requires_san() {
+        let err = authenticator(CertSubjectSource::San)
+            .authenticate(&request_with_cert("cn-only.pem"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AuthError::InvalidCertificate(_)), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_revoked_certificate_rejected() {
+        let err = authenticator(CertSubjectSource::San)
+            .authenticate(&request_with_cert("revoked.pem"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AuthError::InvalidCertificate(_)), "{}", err);
+        assert!(
+            err.to_string().to_lowercase().contains("revoked"),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_untrusted_certificate_rejected() {
+        let err = authenticator(CertSubjectSource::San)
+            .authenticate(&request_with_cert("untrusted.pem"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AuthError::InvalidCertificate(_)), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_missing_certificate() {
+        let mut request = request_with_cert("client.pem");
+        request.peer_certificates.clear();
+
+        let err = authenticator(CertSubjectSource::San)
+            .authenticate(&request)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AuthError::MissingAuth));
+    }
+
+    // ========================================================================
+    // OCSP
+    // ========================================================================
+
+    fn ocsp_authenticator(responder_url: String, soft_fail: bool) -> MtlsAuthenticator {
+        let ca = fixture("ca.pem");
+        let ocsp = OcspChecker::new(
+            MtlsOcspConfig {
+                responder_url: Some(responder_url),
+                timeout_ms: 1000,
+                cache_secs: 300,
+                soft_fail,
+            },
+            &ca,
+        )
+        .unwrap();
+        MtlsAuthenticator::new(&ca, &[], CertSubjectSource::Cn)
+            .unwrap()
+            .with_ocsp(ocsp)
+    }
+
+    /// Responder answering every request with `response`
+    async fn responder(response: Vec<u8>, calls: u64) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header("content-type", "application/ocsp-request"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(response))
+            .expect(calls)
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[tokio::test]
+    async fn test_ocsp_good_certificate_accepted_and_cached() {
+        let server = responder(fixture("ocsp-client.der"), 1).await;
+        let auth = ocsp_authenticator(server.uri(), false);
+
+        for _ in 0..2 {
+            let result = auth
+                .authenticate(&request_with_cert("client.pem"))
+                .await
+                .unwrap();
+            assert_eq!(result.subject, "uploader");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ocsp_revoked_certificate_rejected() {
+        let server = responder(fixture("ocsp-revoked.der"), 1).await;
+        let auth = ocsp_authenticator(server.uri(), true);
+
+        let err = auth
+            .authenticate(&request_with_cert("revoked.pem"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AuthError::InvalidCertificate(_)), "{}", err);
+        assert!(err.to_string().contains("revoked"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_ocsp_response_for_other_certificate_rejected() {
+        let server = responder(fixture("ocsp-client.der"), 1).await;
+        let auth = ocsp_authenticator(server.uri(), false);
+
+        let err = auth
+            .authenticate(&request_with_cert("cn-only.pem"))
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("does not cover the certificate"),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ocsp_forged_response_rejected() {
+        // Change the response's producedAt time, which the signature covers
+        let mut response = fixture("ocsp-client.der");
+        let produced_at = response
+            .windows(2)
+            .position(|window| window == [0x18, 0x0f])
+            .unwrap();
+        response[produced_at + 2] ^= 0x01;
+        let server = responder(response, 1).await;
+        let auth = ocsp_authenticator(server.uri(), false);
+
+        let err = auth
+            .authenticate(&request_with_cert("client.pem"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not signed"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_ocsp_unreachable_responder() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let err = ocsp_authenticator(url.clone(), false)
+            .authenticate(&request_with_cert("client.pem"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("OCSP check failed"), "{}", err);
+
+        // Soft-fail accepts certificates whose status can't be learned
+        let result = ocsp_authenticator(url, true)
+            .authenticate(&request_with_cert("client.pem"))
+            .await;
+        assert!(result.is_ok(), "{:?}", result);
+    }
+
+    #[test]
+    fn test_ocsp_config_validated() {
+        let yaml = format!(
+            r#"
+server:
+  address: "127.0.0.1:0"
+  tls:
+    cert_path: "{cert}"
+    key_path: "{key}"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: uploads
+      region: us-east-1
+    auth:
+      enabled: true
+      mtls:
+        ca_path: "{ca}"
+        ocsp:
+          responder_url: "ldap://ocsp.example.com"
+          timeout_ms: 0
+"#,
+            cert = fixture_path("server.pem"),
+            key = fixture_path("server.key"),
+            ca = fixture_path("ca.pem"),
+        );
+        let config: Config = serde_yaml::from_str(&yaml).unwrap();
+        let ocsp = config.buckets[0].auth.mtls.as_ref().unwrap().ocsp.as_ref();
+        assert_eq!(ocsp.unwrap().cache_secs, 300);
+        assert!(!ocsp.unwrap().soft_fail);
+
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("auth.mtls.ocsp.responder_url"), "{}", err);
+        assert!(err.contains("auth.mtls.ocsp.timeout_ms"), "{}", err);
+    }
+
+    #[test]
+    fn test_mtls_requires_tls_listener() {
+        let yaml = format!(
+            r#"
+server:
+  address: "127.0.0.1:0"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: uploads
+      region: us-east-1
+    auth:
+      enabled: true
+      mtls:
+        ca_path: "{}"
+"#,
+            fixture_path("ca.pem")
+        );
+        let config: Config = serde_yaml::from_str(&yaml).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    async fn start(root: &std::path::Path) -> SocketAddr {
+        let bucket = format!(
+            r#"    auth:
+      enabled: true
+      mtls:
+        ca_path: "{ca}"
+        crl_paths: ["{crl}"]"#,
+            ca = fixture_path("ca.pem"),
+            crl = fixture_path("crl.pem"),
+        );
+        let mut config = common::config(&common::local_bucket("uploads", root, &bucket));
+        config.server.tls = Some(TlsConfig {
+            cert_path: fixture_path("server.pem"),
+            key_path: fixture_path("server.key"),
+        });
+        common::start(config).await
+    }
+
+    fn https_client(identity: Option<&str>) -> reqwest::Client {
+        let ca = reqwest::Certificate::from_pem(&fixture("ca.pem")).unwrap();
+        let mut builder = reqwest::Client::builder()
+            .use_rustls_tls()
+            .add_root_certificate(ca);
+        if let Some(name) = identity {
+            let mut pem = fixture(&format!("{}.pem", name));
+            pem.extend(fixture(&format!("{}.key", name)));
+            builder = builder.identity(reqwest::Identity::from_pem(&pem).unwrap());
+        }
+        builder.build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_upload_with_client_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = start(dir.path()).await;
+
+        let response = https_client(Some("client"))
+            .put(format!("https://localhost:{}/uploads/svc.txt", addr.port()))
+            .body("from a service")
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            std::fs::read(dir.path().join("svc.txt")).unwrap(),
+            b"from a service"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upload_without_client_certificate_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = start(dir.path()).await;
+
+        let response = https_client(None)
+            .put(format!(
+                "https://localhost:{}/uploads/anon.txt",
+                addr.port()
+            ))
+            .body("anonymous")
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 401);
+        assert!(!dir.path().join("anon.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_upload_with_revoked_certificate_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = start(dir.path()).await;
+
+        let response = https_client(Some("revoked"))
+            .put(format!(
+                "https://localhost:{}/uploads/revoked.txt",
+                addr.port()
+            ))
+            .body("revoked")
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 401);
+    }
+
+    #[tokio::test]
+    async fn test_untrusted_client_certificate_fails_handshake() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = start(dir.path()).await;
+
+        let result = https_client(Some("untrusted"))
+            .put(format!("https://localhost:{}/uploads/x.txt", addr.port()))
+            .body("untrusted")
+            .send()
+            .await;
+
+        assert!(result.is_err(), "Handshake should fail");
+        assert!(!dir.path().join("x.txt").exists());
+    }
+}
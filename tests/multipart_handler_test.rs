@@ -10,13 +10,16 @@
 //! - Complete multipart upload
 //! - Abort multipart upload
 //! - Error handling for S3 failures
+//! - Completions whose parts don't match the uploaded ones never reach S3
+//! - Parts of a client's CompleteMultipartUpload body are passed to S3
 //! - Bucket mismatch validation
 
 #[cfg(test)]
 mod tests {
     use bytes::Bytes;
-    use mizuchi_uploadr::s3::{S3Client, S3ClientConfig};
-    use mizuchi_uploadr::upload::multipart::MultipartHandler;
+    use mizuchi_uploadr::s3::{S3Client, S3ClientConfig, S3ErrorCode};
+    use mizuchi_uploadr::upload::multipart::{CompletedPart, MultipartHandler};
+    use mizuchi_uploadr::upload::UploadError;
     use wiremock::matchers::{body_string_contains, method, path, query_param};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
@@ -30,6 +33,7 @@ mod tests {
             secret_key: Some("test-secret".to_string()),
             retry: None,
             timeout: None,
+            pool: None,
         };
         S3Client::new(s3_config).unwrap()
     }
@@ -357,6 +361,108 @@ mod tests {
         assert!(result.is_err(), "Should return error for 500 response");
     }
 
+    /// Test that a completion listing a part with another ETag is refused locally
+    #[tokio::test]
+    async fn test_complete_rejects_unknown_part() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/test-bucket/forged.bin"))
+            .and(query_param("uploads", ""))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"<InitiateMultipartUploadResult><UploadId>forged-upload</UploadId></InitiateMultipartUploadResult>"#,
+            ))
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("PUT"))
+            .and(path("/test-bucket/forged.bin"))
+            .and(query_param("uploadId", "forged-upload"))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "\"part-etag\""))
+            .mount(&mock_server)
+            .await;
+
+        // CompleteMultipartUpload must not be sent
+        Mock::given(method("POST"))
+            .and(path("/test-bucket/forged.bin"))
+            .and(query_param("uploadId", "forged-upload"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let s3_client = create_test_s3_client(&mock_server, "test-bucket");
+        let handler = MultipartHandler::with_client(s3_client);
+
+        let mut upload = handler.create("test-bucket", "forged.bin").await.unwrap();
+        handler
+            .upload_part(&mut upload, 1, Bytes::from("data"))
+            .await
+            .unwrap();
+
+        let forged = [CompletedPart::new(1, "\"other-etag\"")];
+        let err = handler.complete_parts(&upload, &forged).await.unwrap_err();
+
+        assert!(matches!(
+            err.root(),
+            UploadError::InvalidParts {
+                code: S3ErrorCode::InvalidPart,
+                ..
+            }
+        ));
+        assert!(err.to_string().contains("upload forged-upload"));
+    }
+
+    /// Test that the parts of a client's completion body are sent to S3
+    #[tokio::test]
+    async fn test_complete_request_sends_client_parts() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/test-bucket/client.bin"))
+            .and(query_param("uploads", ""))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"<InitiateMultipartUploadResult><UploadId>client-upload</UploadId></InitiateMultipartUploadResult>"#,
+            ))
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("PUT"))
+            .and(path("/test-bucket/client.bin"))
+            .and(query_param("uploadId", "client-upload"))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "\"part-etag\""))
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/test-bucket/client.bin"))
+            .and(query_param("uploadId", "client-upload"))
+            .and(body_string_contains("<PartNumber>1</PartNumber>"))
+            .and(body_string_contains("part-etag"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"<CompleteMultipartUploadResult><ETag>"final-etag"</ETag></CompleteMultipartUploadResult>"#,
+            ))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let s3_client = create_test_s3_client(&mock_server, "test-bucket");
+        let handler = MultipartHandler::with_client(s3_client);
+
+        let mut upload = handler.create("test-bucket", "client.bin").await.unwrap();
+        handler
+            .upload_part(&mut upload, 1, Bytes::from("data"))
+            .await
+            .unwrap();
+
+        let body = br#"<CompleteMultipartUpload xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+            <Part><PartNumber>1</PartNumber><ETag>"part-etag"</ETag></Part>
+        </CompleteMultipartUpload>"#;
+        let result = handler.complete_request(&upload, body).await.unwrap();
+
+        assert_eq!(result.etag, "\"final-etag\"");
+    }
+
     // ========================================================================
     // TEST: Bucket Validation
     // ========================================================================
@@ -0,0 +1,194 @@
+//! Multipart Reconciliation Integration Tests
+//!
+//! Tests for `S3Client::list_multipart_uploads` and the multipart admin API.
+//!
+//! ## Test Coverage
+//!
+//! - ListMultipartUploads follows truncated listings
+//! - `GET /multipart-uploads` on the admin API lists uploads left on S3
+//! - `POST /multipart-uploads/reconcile` aborts only old, untracked uploads
+//! - Unknown buckets get 404
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::s3::{S3Client, S3ClientConfig};
+    use std::net::SocketAddr;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const ADMIN_TOKEN: &str = "admin-token";
+
+    fn listing(uploads: &[(&str, &str, &str)], next: Option<(&str, &str)>) -> String {
+        let entries: String = uploads
+            .iter()
+            .map(|(key, upload_id, initiated)| {
+                format!(
+                    "<Upload><Key>{}</Key><UploadId>{}</UploadId><Initiated>{}</Initiated></Upload>",
+                    key, upload_id, initiated
+                )
+            })
+            .collect();
+        let truncation = match next {
+            Some((key, upload_id)) => format!(
+                "<IsTruncated>true</IsTruncated><NextKeyMarker>{}</NextKeyMarker>\
+                 <NextUploadIdMarker>{}</NextUploadIdMarker>",
+                key, upload_id
+            ),
+            None => "<IsTruncated>false</IsTruncated>".to_string(),
+        };
+        format!(
+            "<ListMultipartUploadsResult><Bucket>files</Bucket>{}{}</ListMultipartUploadsResult>",
+            truncation, entries
+        )
+    }
+
+    /// S3 mock with one upload from 2020 and one started just now
+    async fn s3() -> MockServer {
+        let server = MockServer::start().await;
+        let now = chrono::Utc::now().to_rfc3339();
+        Mock::given(method("GET"))
+            .and(path("/files"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(listing(
+                &[
+                    ("old.bin", "u-old", "2020-01-01T00:00:00.000Z"),
+                    ("new.bin", "u-new", now.as_str()),
+                ],
+                None,
+            )))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/files/old.bin"))
+            .and(query_param("uploadId", "u-old"))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn config(s3_url: &str) -> Config {
+        let bucket = format!(
+            r#"  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: files
+      region: us-east-1
+      endpoint: "{s3_url}"
+"#
+        );
+        common::with_admin(common::config(&bucket), ADMIN_TOKEN)
+    }
+
+    /// Start the server, returning the admin API address
+    async fn start(config: Config) -> SocketAddr {
+        common::start_with_admin(config).await.1
+    }
+
+    #[tokio::test]
+    async fn test_list_follows_truncated_listing() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/files"))
+            .and(query_param("key-marker", "a.bin"))
+            .and(query_param("upload-id-marker", "u1"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(listing(
+                &[("b.bin", "u2", "2024-01-01T00:00:00.000Z")],
+                None,
+            )))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/files"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(listing(
+                &[("a.bin", "u1", "2024-01-01T00:00:00.000Z")],
+                Some(("a.bin", "u1")),
+            )))
+            .mount(&server)
+            .await;
+
+        let client = S3Client::new(S3ClientConfig {
+            bucket: "files".into(),
+            region: "us-east-1".into(),
+            endpoint: Some(server.uri()),
+            access_key: None,
+            secret_key: None,
+            retry: None,
+            timeout: None,
+            pool: None,
+        })
+        .unwrap();
+
+        let uploads = client.list_multipart_uploads().await.unwrap();
+        let ids: Vec<_> = uploads.iter().map(|u| u.upload_id.as_str()).collect();
+        assert_eq!(ids, ["u1", "u2"]);
+    }
+
+    #[tokio::test]
+    async fn test_admin_list() {
+        let s3 = s3().await;
+        let addr = start(config(&s3.uri())).await;
+
+        let response = reqwest::Client::new()
+            .get(format!("http://{}/multipart-uploads", addr))
+            .bearer_auth(ADMIN_TOKEN)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = response.json().await.unwrap();
+        let uploads = body["buckets"]["uploads"].as_array().unwrap();
+        assert_eq!(uploads.len(), 2);
+        assert_eq!(uploads[0]["upload_id"], "u-old");
+        assert_eq!(uploads[0]["tracked"], false);
+    }
+
+    #[tokio::test]
+    async fn test_admin_reconcile_aborts_orphans() {
+        let s3 = s3().await;
+        let addr = start(config(&s3.uri())).await;
+
+        let response = reqwest::Client::new()
+            .post(format!(
+                "http://{}/multipart-uploads/reconcile?max_age_secs=3600",
+                addr
+            ))
+            .bearer_auth(ADMIN_TOKEN)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = response.json().await.unwrap();
+        let report = &body["buckets"]["uploads"];
+        assert_eq!(report["listed"], 2);
+        assert_eq!(report["recent"], 1);
+        assert_eq!(report["aborted"][0]["upload_id"], "u-old");
+
+        let aborts = s3
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .filter(|r| r.method.as_str() == "DELETE")
+            .count();
+        assert_eq!(aborts, 1);
+    }
+
+    #[tokio::test]
+    async fn test_admin_unknown_bucket() {
+        let s3 = s3().await;
+        let addr = start(config(&s3.uri())).await;
+
+        let status = reqwest::Client::new()
+            .get(format!("http://{}/multipart-uploads?bucket=nope", addr))
+            .bearer_auth(ADMIN_TOKEN)
+            .send()
+            .await
+            .unwrap()
+            .status();
+        assert_eq!(status, 404);
+    }
+}
@@ -0,0 +1,233 @@
+//! Multipart Upload Resumption Integration Tests
+//!
+//! Tests for resuming multipart uploads with a `ResumeStore` after the
+//! handler that started them is gone, as after a proxy restart.
+//!
+//! ## Test Coverage
+//!
+//! - An upload started before a restart is resumed and completed
+//! - A part re-sent unchanged after resuming is not stored again
+//! - Completions are still validated against the saved parts
+//! - Finished and unknown uploads can't be resumed
+//! - Uploads can be resumed through a shared store
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use mizuchi_uploadr::s3::S3ErrorCode;
+    use mizuchi_uploadr::state::MemoryStore;
+    use mizuchi_uploadr::upload::backend::{StorageBackend, StoredObject};
+    use mizuchi_uploadr::upload::local::LocalFsBackend;
+    use mizuchi_uploadr::upload::multipart::{
+        CompletedPart, MultipartHandler, MultipartUpload, MIN_PART_SIZE,
+    };
+    use mizuchi_uploadr::upload::resume::{FileResumeStore, ResumeStore, SharedResumeStore};
+    use mizuchi_uploadr::upload::UploadError;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Local backend counting the parts it stores
+    struct Counting {
+        inner: LocalFsBackend,
+        parts: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl StorageBackend for Counting {
+        fn bucket(&self) -> &str {
+            self.inner.bucket()
+        }
+
+        async fn put_object(
+            &self,
+            key: &str,
+            body: Bytes,
+            content_type: Option<&str>,
+        ) -> Result<StoredObject, UploadError> {
+            self.inner.put_object(key, body, content_type).await
+        }
+
+        async fn create_multipart_upload(&self, key: &str) -> Result<String, UploadError> {
+            self.inner.create_multipart_upload(key).await
+        }
+
+        async fn upload_part(
+            &self,
+            key: &str,
+            upload_id: &str,
+            part_number: u32,
+            body: Bytes,
+        ) -> Result<StoredObject, UploadError> {
+            self.parts.fetch_add(1, Ordering::SeqCst);
+            self.inner
+                .upload_part(key, upload_id, part_number, body)
+                .await
+        }
+
+        async fn complete_multipart_upload(
+            &self,
+            key: &str,
+            upload_id: &str,
+            parts: &[CompletedPart],
+        ) -> Result<StoredObject, UploadError> {
+            self.inner
+                .complete_multipart_upload(key, upload_id, parts)
+                .await
+        }
+
+        async fn abort_multipart_upload(
+            &self,
+            key: &str,
+            upload_id: &str,
+        ) -> Result<(), UploadError> {
+            self.inner.abort_multipart_upload(key, upload_id).await
+        }
+    }
+
+    fn backend(root: &Path) -> Arc<Counting> {
+        Arc::new(Counting {
+            inner: LocalFsBackend::new("uploads", root).unwrap(),
+            parts: AtomicUsize::new(0),
+        })
+    }
+
+    /// A handler as a freshly started proxy would build it
+    fn handler(backend: &Arc<Counting>, store: Arc<dyn ResumeStore>) -> MultipartHandler {
+        MultipartHandler::with_backend(Arc::clone(backend) as Arc<dyn StorageBackend>)
+            .with_resume_store(store)
+    }
+
+    fn file_store(dir: &Path) -> Arc<dyn ResumeStore> {
+        Arc::new(FileResumeStore::open(dir).unwrap())
+    }
+
+    /// Start an upload and store its first part, returning its upload ID
+    async fn start(handler: &MultipartHandler, first: &Bytes) -> String {
+        let mut upload = handler.create("uploads", "video.bin").await.unwrap();
+        handler
+            .upload_part(&mut upload, 1, first.clone())
+            .await
+            .unwrap();
+        upload.upload_id
+    }
+
+    fn no_such_upload(error: &UploadError) -> bool {
+        matches!(
+            error.root(),
+            UploadError::InvalidParts {
+                code: S3ErrorCode::NoSuchUpload,
+                ..
+            }
+        )
+    }
+
+    #[tokio::test]
+    async fn test_resume_after_restart() {
+        let root = tempfile::tempdir().unwrap();
+        let state = tempfile::tempdir().unwrap();
+        let backend = backend(root.path());
+        let first = Bytes::from(vec![1u8; MIN_PART_SIZE]);
+        let upload_id = start(&handler(&backend, file_store(state.path())), &first).await;
+
+        // The restarted proxy only has the state directory
+        let handler = handler(&backend, file_store(state.path()));
+        let mut upload = handler.resume("uploads", &upload_id).await.unwrap();
+        assert_eq!(upload.key, "video.bin");
+        assert_eq!(upload.parts.len(), 1);
+
+        handler
+            .upload_part(&mut upload, 1, first.clone())
+            .await
+            .unwrap();
+        assert_eq!(backend.parts.load(Ordering::SeqCst), 1);
+        handler
+            .upload_part(&mut upload, 2, Bytes::from("tail"))
+            .await
+            .unwrap();
+        handler.complete(&upload).await.unwrap();
+
+        let stored = std::fs::read(root.path().join("video.bin")).unwrap();
+        assert_eq!(stored.len(), MIN_PART_SIZE + 4);
+        assert!(stored.ends_with(b"tail"));
+        let error = handler.resume("uploads", &upload_id).await.unwrap_err();
+        assert!(no_such_upload(&error));
+    }
+
+    #[tokio::test]
+    async fn test_resumed_completion_validated() {
+        let root = tempfile::tempdir().unwrap();
+        let state = tempfile::tempdir().unwrap();
+        let backend = backend(root.path());
+        let first = Bytes::from(vec![1u8; 1024]);
+        let upload_id = start(&handler(&backend, file_store(state.path())), &first).await;
+
+        let handler = handler(&backend, file_store(state.path()));
+        let mut upload: MultipartUpload = handler.resume("uploads", &upload_id).await.unwrap();
+        handler
+            .upload_part(&mut upload, 2, Bytes::from("tail"))
+            .await
+            .unwrap();
+
+        // The saved size of part 1 still counts against the minimum
+        let error = handler.complete(&upload).await.unwrap_err();
+        assert!(matches!(
+            error.root(),
+            UploadError::InvalidParts {
+                code: S3ErrorCode::EntityTooSmall,
+                ..
+            }
+        ));
+
+        handler.abort(&upload).await.unwrap();
+        assert!(handler.resume("uploads", &upload_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_uploads_not_resumed() {
+        let root = tempfile::tempdir().unwrap();
+        let state = tempfile::tempdir().unwrap();
+        let backend = backend(root.path());
+
+        let with_store = handler(&backend, file_store(state.path()));
+        let error = with_store.resume("uploads", "unknown").await.unwrap_err();
+        assert!(no_such_upload(&error));
+
+        let upload_id = start(&with_store, &Bytes::from("part")).await;
+        let without_store =
+            MultipartHandler::with_backend(Arc::clone(&backend) as Arc<dyn StorageBackend>);
+        let error = without_store
+            .resume("uploads", &upload_id)
+            .await
+            .unwrap_err();
+        assert!(no_such_upload(&error));
+
+        let error = with_store.resume("other", &upload_id).await.unwrap_err();
+        assert!(matches!(error, UploadError::BucketMismatch { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_resume_through_shared_store() {
+        let root = tempfile::tempdir().unwrap();
+        let backend = backend(root.path());
+        let store: Arc<dyn ResumeStore> =
+            Arc::new(SharedResumeStore::new(Arc::new(MemoryStore::new())));
+        let first = Bytes::from(vec![2u8; MIN_PART_SIZE]);
+        let upload_id = start(&handler(&backend, Arc::clone(&store)), &first).await;
+
+        // Another instance sharing the store
+        let handler = handler(&backend, store);
+        let mut upload = handler.resume("uploads", &upload_id).await.unwrap();
+        handler
+            .upload_part(&mut upload, 2, Bytes::from("end"))
+            .await
+            .unwrap();
+        handler.complete(&upload).await.unwrap();
+
+        assert_eq!(
+            std::fs::read(root.path().join("video.bin")).unwrap().len(),
+            MIN_PART_SIZE + 3
+        );
+    }
+}
@@ -15,6 +15,7 @@ fn create_authorizer(mock_server: &MockServer, policy_path: &str) -> OpaAuthoriz
         policy_path: policy_path.to_string(),
         timeout: None,
         cache_ttl: None, // No caching for basic tests
+        deny_cache_ttl: None,
     };
     OpaAuthorizer::new(config)
 }
@@ -175,6 +176,7 @@ mod tests {
             policy_path: "mizuchi/allow".to_string(),
             timeout: Some(std::time::Duration::from_millis(100)), // Short timeout
             cache_ttl: None,
+            deny_cache_ttl: None,
         };
         let authorizer = OpaAuthorizer::new(config);
         let request = create_request("user:alice", "upload", "bucket/uploads/file.txt");
@@ -319,6 +321,7 @@ mod tests {
             policy_path: "mizuchi/allow".to_string(),
             timeout: Some(std::time::Duration::from_millis(100)),
             cache_ttl: None,
+            deny_cache_ttl: None,
         };
         let authorizer = OpaAuthorizer::new(config);
         let request = create_request("user:alice", "upload", "bucket/uploads/file.txt");
@@ -355,6 +358,7 @@ mod tests {
             policy_path: "mizuchi/allow".to_string(),
             timeout: None,
             cache_ttl: Some(std::time::Duration::from_secs(1)),
+            deny_cache_ttl: None,
         };
         let authorizer = OpaAuthorizer::new(config);
 
@@ -399,6 +403,7 @@ mod tests {
             policy_path: "mizuchi/allow".to_string(),
             timeout: None,
             cache_ttl: Some(std::time::Duration::from_millis(50)),
+            deny_cache_ttl: None,
         };
         let authorizer = OpaAuthorizer::new(config);
         let request = create_request("user:alice", "upload", "bucket/uploads/file.txt");
@@ -442,6 +447,7 @@ mod tests {
             policy_path: "mizuchi/allow".to_string(),
             timeout: None,
             cache_ttl: Some(std::time::Duration::from_secs(60)),
+            deny_cache_ttl: None,
         };
         let authorizer = OpaAuthorizer::new(config);
 
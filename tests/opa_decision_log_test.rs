@@ -0,0 +1,163 @@
+//! OPA Decision Log Integration Tests
+//!
+//! Tests for decision IDs and decision log pushes of `OpaAuthorizer`, using
+//! mock OPA and decision log servers.
+//!
+//! ## Test Coverage
+//!
+//! - Each query carries a fresh `input.decision_id`, collected per request
+//! - Decisions are pushed to the sink as gzipped OPA decision log events
+//! - Cached decisions make no query and have no ID
+//! - Without `decision_logs`, queries carry no decision ID
+
+#[cfg(test)]
+mod tests {
+    use flate2::read::GzDecoder;
+    use mizuchi_uploadr::authz::cache::DecisionCache;
+    use mizuchi_uploadr::authz::opa::decision_log::{self, DecisionLog};
+    use mizuchi_uploadr::authz::opa::{OpaAuthorizer, OpaConfig};
+    use mizuchi_uploadr::authz::{Authorizer, AuthzRequest};
+    use mizuchi_uploadr::config::OpaDecisionLogConfig;
+    use std::collections::HashMap;
+    use std::io::Read;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    async fn opa(allow: bool) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/data/mizuchi/allow"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "result": allow
+            })))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn authorizer(opa: &MockServer, cache_ttl: Option<Duration>) -> OpaAuthorizer {
+        OpaAuthorizer::with_cache(
+            OpaConfig {
+                url: opa.uri(),
+                policy_path: "mizuchi/allow".into(),
+                timeout: None,
+                cache_ttl,
+                deny_cache_ttl: None,
+            },
+            Arc::new(DecisionCache::default()),
+        )
+    }
+
+    fn request(subject: &str) -> AuthzRequest {
+        AuthzRequest {
+            subject: subject.into(),
+            action: "upload".into(),
+            resource: "bucket/uploads/report.pdf".into(),
+            context: HashMap::new(),
+        }
+    }
+
+    /// `decision_id` of every input OPA received
+    async fn queried_ids(opa: &MockServer) -> Vec<Option<String>> {
+        opa.received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| {
+                let body: serde_json::Value = request.body_json().unwrap();
+                body["input"]["decision_id"].as_str().map(str::to_string)
+            })
+            .collect()
+    }
+
+    fn events(request: &Request) -> Vec<serde_json::Value> {
+        let mut json = String::new();
+        GzDecoder::new(&request.body[..])
+            .read_to_string(&mut json)
+            .unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_decision_ids_are_sent_and_collected() {
+        let opa = opa(true).await;
+        let log = Arc::new(DecisionLog::new(&OpaDecisionLogConfig::default()));
+        let authorizer = authorizer(&opa, None).with_decision_log(log);
+
+        let (allowed, first) = decision_log::collect(authorizer.authorize(&request("alice"))).await;
+        assert!(allowed.unwrap());
+        let (_, second) = decision_log::collect(authorizer.authorize(&request("alice"))).await;
+
+        assert_eq!(first.len(), 1);
+        assert_eq!(second.len(), 1);
+        assert_ne!(first, second);
+        assert_eq!(
+            queried_ids(&opa).await,
+            vec![Some(first[0].clone()), Some(second[0].clone())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decisions_are_pushed_to_sink() {
+        let opa = opa(false).await;
+        let sink = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/logs"))
+            .and(header("content-encoding", "gzip"))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&sink)
+            .await;
+
+        let log = Arc::new(DecisionLog::new(&OpaDecisionLogConfig {
+            url: Some(format!("{}/logs", sink.uri())),
+        }));
+        let authorizer = authorizer(&opa, None).with_decision_log(log);
+        let (allowed, ids) = decision_log::collect(authorizer.authorize(&request("bob"))).await;
+        assert!(!allowed.unwrap());
+
+        let mut pushed = Vec::new();
+        for _ in 0..100 {
+            pushed = sink.received_requests().await.unwrap();
+            if !pushed.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(pushed.len(), 1, "decision log was not pushed");
+
+        let events = events(&pushed[0]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["decision_id"], ids[0].as_str());
+        assert_eq!(events[0]["path"], "mizuchi/allow");
+        assert_eq!(events[0]["result"], false);
+        assert_eq!(events[0]["input"]["subject"], "bob");
+        assert_eq!(events[0]["labels"]["app"], "mizuchi-uploadr");
+    }
+
+    #[tokio::test]
+    async fn test_cached_decision_has_no_id() {
+        let opa = opa(true).await;
+        let log = Arc::new(DecisionLog::new(&OpaDecisionLogConfig::default()));
+        let authorizer = authorizer(&opa, Some(Duration::from_secs(60))).with_decision_log(log);
+
+        let (_, first) = decision_log::collect(authorizer.authorize(&request("carol"))).await;
+        let (_, cached) = decision_log::collect(authorizer.authorize(&request("carol"))).await;
+
+        assert_eq!(first.len(), 1);
+        assert!(cached.is_empty());
+        assert_eq!(queried_ids(&opa).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_no_decision_id_without_decision_logs() {
+        let opa = opa(true).await;
+        let authorizer = authorizer(&opa, None);
+
+        let (_, ids) = decision_log::collect(authorizer.authorize(&request("dave"))).await;
+
+        assert!(ids.is_empty());
+        assert_eq!(queried_ids(&opa).await, vec![None]);
+    }
+}
@@ -0,0 +1,496 @@
+//! Embedded OPA WASM Integration Tests
+//!
+//! Tests for `authz.bundle`, evaluating compiled policies in-process.
+//!
+//! ## Test Coverage
+//!
+//! - Allow and deny from a `.tar.gz` bundle and from a bare module (`opa-wasm` feature)
+//! - Decisions of a bundle built by `opa build -t wasm`
+//! - Bundle reload from disk and from an HTTP endpoint, keeping the last good policy
+//! - Policy paths that aren't entrypoints fail the health check
+//! - Policies calling unsupported builtins are rejected
+//! - Uploads are authorized by a bundle configured for a bucket
+//! - A configured bundle fails startup without the `opa-wasm` feature
+//!
+//! Besides the `opa build` output in `fixtures/opa`, the tests use a
+//! hand-written module implementing the same ABI, so policies can change
+//! without the opa binary: `mizuchi/allow` is true when the input JSON
+//! contains a needle.
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::config::Config;
+    use std::path::Path;
+
+    #[cfg(feature = "opa-wasm")]
+    use mizuchi_uploadr::authz::opa::wasm::{BundleSource, OpaBundle, WasmPolicy};
+    #[cfg(feature = "opa-wasm")]
+    use mizuchi_uploadr::authz::opa::OpaAuthorizer;
+    #[cfg(feature = "opa-wasm")]
+    use mizuchi_uploadr::authz::{Authorizer, AuthzRequest};
+    #[cfg(feature = "opa-wasm")]
+    use std::sync::Arc;
+
+    /// OPA ABI over a bump allocator; values are (pointer, length) pairs of
+    /// JSON text
+    #[cfg(feature = "opa-wasm")]
+    const POLICY: &str = r#"
+        (module
+          (import "env" "memory" (memory 2))
+          (import "env" "opa_abort" (func $abort (param i32)))
+          (global $heap (mut i32) (i32.const 65536))
+          (data (i32.const 0) "{\"mizuchi/allow\":0}")
+          (data (i32.const 64) "BUILTINS")
+          (data (i32.const 128) "[{\"result\":true}]")
+          (data (i32.const 192) "[{\"result\":false}]")
+          (data (i32.const 256) "NEEDLE")
+          (func $malloc (export "opa_malloc") (param $n i32) (result i32)
+            (local $p i32)
+            (local.set $p (global.get $heap))
+            (global.set $heap
+              (i32.and (i32.add (i32.add (local.get $p) (local.get $n)) (i32.const 7))
+                       (i32.const -8)))
+            (local.get $p))
+          (func $value (param $ptr i32) (param $len i32) (result i32)
+            (local $v i32)
+            (local.set $v (call $malloc (i32.const 8)))
+            (i32.store (local.get $v) (local.get $ptr))
+            (i32.store offset=4 (local.get $v) (local.get $len))
+            (local.get $v))
+          (func (export "opa_heap_ptr_get") (result i32) (global.get $heap))
+          (func (export "opa_heap_ptr_set") (param i32) (global.set $heap (local.get 0)))
+          (func (export "opa_json_parse") (param i32 i32) (result i32)
+            (call $value (local.get 0) (local.get 1)))
+          (func (export "opa_json_dump") (param $v i32) (result i32)
+            (local $len i32)
+            (local $out i32)
+            (local.set $len (i32.load offset=4 (local.get $v)))
+            (local.set $out (call $malloc (i32.add (local.get $len) (i32.const 1))))
+            (memory.copy (local.get $out) (i32.load (local.get $v)) (local.get $len))
+            (i32.store8 (i32.add (local.get $out) (local.get $len)) (i32.const 0))
+            (local.get $out))
+          (func (export "builtins") (result i32)
+            (call $value (i32.const 64) (i32.const BUILTINS_LEN)))
+          (func (export "entrypoints") (result i32)
+            (call $value (i32.const 0) (i32.const 19)))
+          (func (export "opa_eval_ctx_new") (result i32) (call $malloc (i32.const 16)))
+          (func (export "opa_eval_ctx_set_input") (param i32 i32)
+            (i32.store (local.get 0) (local.get 1)))
+          (func (export "opa_eval_ctx_set_data") (param i32 i32)
+            (i32.store offset=4 (local.get 0) (local.get 1)))
+          (func (export "opa_eval_ctx_set_entrypoint") (param i32 i32)
+            (i32.store offset=8 (local.get 0) (local.get 1)))
+          (func (export "opa_eval_ctx_get_result") (param i32) (result i32)
+            (i32.load offset=12 (local.get 0)))
+          (func (export "eval") (param $ctx i32) (result i32)
+            (local $input i32)
+            (local.set $input (i32.load (local.get $ctx)))
+            (i32.store offset=12 (local.get $ctx)
+              (if (result i32)
+                (call $contains (i32.load (local.get $input))
+                                (i32.load offset=4 (local.get $input))
+                                (i32.const 256) (i32.const NEEDLE_LEN))
+                (then (call $value (i32.const 128) (i32.const 17)))
+                (else (call $value (i32.const 192) (i32.const 18)))))
+            (i32.const 0))
+          (func $contains (param $h i32) (param $hl i32) (param $n i32) (param $nl i32)
+                          (result i32)
+            (local $i i32)
+            (local $j i32)
+            (block $none
+              (loop $outer
+                (br_if $none (i32.gt_u (i32.add (local.get $i) (local.get $nl))
+                                       (local.get $hl)))
+                (local.set $j (i32.const 0))
+                (block $mismatch
+                  (loop $inner
+                    (if (i32.eq (local.get $j) (local.get $nl))
+                      (then (return (i32.const 1))))
+                    (br_if $mismatch
+                      (i32.ne
+                        (i32.load8_u (i32.add (local.get $h)
+                                              (i32.add (local.get $i) (local.get $j))))
+                        (i32.load8_u (i32.add (local.get $n) (local.get $j)))))
+                    (local.set $j (i32.add (local.get $j) (i32.const 1)))
+                    (br $inner)))
+                (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                (br $outer)))
+            (i32.const 0)))
+    "#;
+
+    /// Fixture allowing inputs that contain `needle`
+    #[cfg(feature = "opa-wasm")]
+    fn policy_with_builtins(needle: &str, builtins: &str) -> String {
+        let escape = |s: &str| s.replace('"', "\\\"");
+        POLICY
+            .replace("BUILTINS_LEN", &builtins.len().to_string())
+            .replace("BUILTINS", &escape(builtins))
+            .replace("NEEDLE_LEN", &needle.len().to_string())
+            .replace("NEEDLE", &escape(needle))
+    }
+
+    #[cfg(feature = "opa-wasm")]
+    fn policy(needle: &str) -> String {
+        policy_with_builtins(needle, "{}")
+    }
+
+    /// `.tar.gz` bundle laid out like `opa build` output
+    #[cfg(feature = "opa-wasm")]
+    fn bundle(policy: &str, data: &str) -> Vec<u8> {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let wasm = wat::parse_str(policy).unwrap();
+        let mut archive = Vec::new();
+        for (name, contents) in [
+            ("/data.json", data.as_bytes()),
+            ("/policy.wasm", &wasm[..]),
+            ("/.manifest", &br#"{"revision":""}"#[..]),
+        ] {
+            let mut header = [0u8; 512];
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            header[100..107].copy_from_slice(b"0000644");
+            header[124..135].copy_from_slice(format!("{:011o}", contents.len()).as_bytes());
+            header[156] = b'0';
+            header[257..263].copy_from_slice(b"ustar\0");
+            header[148..156].fill(b' ');
+            let checksum: u32 = header.iter().map(|&b| u32::from(b)).sum();
+            header[148..155].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+            archive.extend_from_slice(&header);
+            archive.extend_from_slice(contents);
+            archive.resize(archive.len().div_ceil(512) * 512, 0);
+        }
+        archive.resize(archive.len() + 1024, 0);
+
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&archive).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[cfg(feature = "opa-wasm")]
+    fn authorizer(bundle: &Arc<OpaBundle>, policy_path: &str) -> OpaAuthorizer {
+        OpaAuthorizer::builder()
+            .policy_path(policy_path)
+            .bundle(Arc::clone(bundle))
+            .build()
+            .unwrap()
+    }
+
+    #[cfg(feature = "opa-wasm")]
+    fn request(subject: &str) -> AuthzRequest {
+        AuthzRequest {
+            subject: subject.into(),
+            action: "upload".into(),
+            resource: "bucket/uploads/file.txt".into(),
+            context: Default::default(),
+        }
+    }
+
+    #[cfg(feature = "opa-wasm")]
+    #[tokio::test]
+    async fn test_bundle_allows_and_denies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bundle.tar.gz");
+        std::fs::write(
+            &path,
+            bundle(&policy(r#""subject":"alice""#), r#"{"limits":{"max":1}}"#),
+        )
+        .unwrap();
+        let bundle = Arc::new(OpaBundle::new(BundleSource::Path(path)));
+        let authz = authorizer(&bundle, "mizuchi/allow");
+
+        assert!(authz.authorize(&request("alice")).await.unwrap());
+        assert!(!authz.authorize(&request("bob")).await.unwrap());
+        authz.health_check().await.unwrap();
+        assert_eq!(bundle.revision(), 1);
+    }
+
+    #[cfg(feature = "opa-wasm")]
+    #[tokio::test]
+    async fn test_bare_module() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("policy.wat");
+        std::fs::write(&path, policy(r#""action":"upload""#)).unwrap();
+        let bundle = Arc::new(OpaBundle::new(BundleSource::Path(path)));
+
+        assert!(authorizer(&bundle, "/mizuchi/allow/")
+            .authorize(&request("anyone"))
+            .await
+            .unwrap());
+    }
+
+    /// `opa build -t wasm -e example/project_permissions -e example/user_project`
+    /// output, see `fixtures/opa/build.sh`
+    #[cfg(feature = "opa-wasm")]
+    const EXAMPLE_BUNDLE: &[u8] = include_bytes!("fixtures/opa/example.tar.gz");
+
+    /// `policy.wasm` of a bundle
+    #[cfg(feature = "opa-wasm")]
+    fn policy_wasm(bundle: &[u8]) -> Vec<u8> {
+        use std::io::Read;
+
+        let mut archive = Vec::new();
+        flate2::read::GzDecoder::new(bundle)
+            .read_to_end(&mut archive)
+            .unwrap();
+        let mut offset = 0;
+        loop {
+            let header = &archive[offset..offset + 512];
+            let name = String::from_utf8_lossy(&header[..100]);
+            let size = std::str::from_utf8(&header[124..136]).unwrap();
+            let size = usize::from_str_radix(size.trim_matches(['\0', ' ']), 8).unwrap();
+            offset += 512;
+            if name.trim_end_matches('\0').ends_with("policy.wasm") {
+                return archive[offset..offset + size].to_vec();
+            }
+            offset += size.div_ceil(512) * 512;
+        }
+    }
+
+    #[cfg(feature = "opa-wasm")]
+    #[tokio::test]
+    async fn test_opa_built_bundle() {
+        use serde_json::json;
+
+        let policy = WasmPolicy::from_bundle(EXAMPLE_BUNDLE).unwrap();
+        assert_eq!(
+            policy.entrypoints(),
+            ["example/project_permissions", "example/user_project"]
+        );
+        // The bundle's data.json is empty, so nobody is in a project
+        let input = json!({"user_id": "alice", "project_id": "uploads"});
+        assert_eq!(
+            policy.evaluate("example/user_project", &input).unwrap(),
+            None
+        );
+        assert_eq!(
+            policy
+                .evaluate("example/project_permissions", &input)
+                .unwrap(),
+            Some(json!([]))
+        );
+
+        let data = json!({
+            "users": {"alice": {"projects": {"uploads": {"roles": ["owner"]}}}},
+            "projects": {"uploads": {}},
+        });
+        let policy =
+            WasmPolicy::new(&policy_wasm(EXAMPLE_BUNDLE), data.to_string().as_bytes()).unwrap();
+        assert_eq!(
+            policy.evaluate("example/user_project", &input).unwrap(),
+            Some(json!({"roles": ["owner"]}))
+        );
+        assert_eq!(
+            policy
+                .evaluate("example/project_permissions", &input)
+                .unwrap(),
+            Some(json!(["read", "write"]))
+        );
+        let stranger = json!({"user_id": "bob", "project_id": "uploads"});
+        assert_eq!(
+            policy
+                .evaluate("example/project_permissions", &stranger)
+                .unwrap(),
+            Some(json!([]))
+        );
+
+        // Set-valued rules can't authorize uploads
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("example.tar.gz");
+        std::fs::write(&path, EXAMPLE_BUNDLE).unwrap();
+        let bundle = Arc::new(OpaBundle::new(BundleSource::Path(path)));
+        let error = authorizer(&bundle, "example/project_permissions")
+            .authorize(&request("alice"))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("not a boolean"), "{}", error);
+    }
+
+    #[cfg(feature = "opa-wasm")]
+    #[tokio::test]
+    async fn test_reload_from_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bundle.tar.gz");
+        std::fs::write(&path, bundle(&policy(r#""subject":"alice""#), "{}")).unwrap();
+        let bundle_ref = Arc::new(OpaBundle::new(BundleSource::Path(path.clone())));
+        let authz = authorizer(&bundle_ref, "mizuchi/allow");
+        assert!(!authz.authorize(&request("bob")).await.unwrap());
+
+        std::fs::write(&path, bundle(&policy(r#""subject":"bob""#), "{}")).unwrap();
+        assert!(bundle_ref.reload().await.unwrap());
+        assert!(!bundle_ref.reload().await.unwrap());
+        assert!(authz.authorize(&request("bob")).await.unwrap());
+        assert!(!authz.authorize(&request("alice")).await.unwrap());
+
+        // A broken bundle leaves the last good policy in place
+        std::fs::write(&path, b"not a bundle").unwrap();
+        assert!(bundle_ref.reload().await.is_err());
+        assert!(authz.authorize(&request("bob")).await.unwrap());
+        assert_eq!(bundle_ref.revision(), 2);
+    }
+
+    #[cfg(feature = "opa-wasm")]
+    #[tokio::test]
+    async fn test_reload_from_http() {
+        use wiremock::matchers::{header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/bundles/mizuchi.tar.gz"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"v1\"")
+                    .set_body_bytes(bundle(&policy(r#""subject":"alice""#), "{}")),
+            )
+            .mount(&server)
+            .await;
+        let bundle_ref = Arc::new(OpaBundle::new(BundleSource::Url(format!(
+            "{}/bundles/mizuchi.tar.gz",
+            server.uri()
+        ))));
+        let authz = authorizer(&bundle_ref, "mizuchi/allow");
+        assert!(authz.authorize(&request("alice")).await.unwrap());
+
+        // Unchanged bundles aren't downloaded again
+        server.reset().await;
+        Mock::given(method("GET"))
+            .and(header("If-None-Match", "\"v1\""))
+            .respond_with(ResponseTemplate::new(304))
+            .expect(1)
+            .mount(&server)
+            .await;
+        assert!(!bundle_ref.reload().await.unwrap());
+        server.verify().await;
+
+        server.reset().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"v2\"")
+                    .set_body_bytes(bundle(&policy(r#""subject":"bob""#), "{}")),
+            )
+            .mount(&server)
+            .await;
+        assert!(bundle_ref.reload().await.unwrap());
+        assert!(authz.authorize(&request("bob")).await.unwrap());
+
+        // An unreachable endpoint keeps the loaded policy
+        server.reset().await;
+        assert!(bundle_ref.reload().await.is_err());
+        assert!(authz.authorize(&request("bob")).await.unwrap());
+    }
+
+    #[cfg(feature = "opa-wasm")]
+    #[tokio::test]
+    async fn test_unknown_entrypoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("policy.wat");
+        std::fs::write(&path, policy("alice")).unwrap();
+        let bundle = Arc::new(OpaBundle::new(BundleSource::Path(path)));
+        let authz = authorizer(&bundle, "mizuchi/deny");
+
+        let error = authz.health_check().await.unwrap_err().to_string();
+        assert!(error.contains("not an entrypoint"), "{}", error);
+        assert!(error.contains("mizuchi/allow"), "{}", error);
+        assert!(authz.authorize(&request("alice")).await.is_err());
+    }
+
+    #[cfg(feature = "opa-wasm")]
+    #[test]
+    fn test_unsupported_builtins_rejected() {
+        let module = policy_with_builtins("alice", r#"{"http.send":0}"#);
+        let error = WasmPolicy::from_bundle(module.as_bytes())
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("http.send"), "{}", error);
+    }
+
+    const SECRET: &str = "bundle-secret";
+
+    fn config(root: &Path, bundle: &Path) -> Config {
+        let extra = format!(
+            r#"    auth:
+      enabled: true
+      jwt:
+        secret: {SECRET}
+        algorithm: HS256
+    authz:
+      type: opa
+      policy_path: mizuchi/allow
+      bundle:
+        path: "{}"
+        reload_secs: 1"#,
+            bundle.display()
+        );
+        common::config(&common::local_bucket("docs", root, &extra))
+    }
+
+    #[cfg(feature = "opa-wasm")]
+    async fn put(addr: std::net::SocketAddr, subject: &str) -> reqwest::StatusCode {
+        use jsonwebtoken::{encode, EncodingKey, Header};
+
+        let claims = serde_json::json!({
+            "sub": subject,
+            "exp": (chrono::Utc::now().timestamp() + 3600) as usize,
+        });
+        let token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap();
+        let authorization = format!("Bearer {}", token);
+        common::put(
+            addr,
+            "/docs/a.txt",
+            &[("Authorization", &authorization)],
+            "hi",
+        )
+        .await
+        .status()
+    }
+
+    #[cfg(feature = "opa-wasm")]
+    #[tokio::test]
+    async fn test_uploads_authorized_by_bundle() {
+        let root = tempfile::tempdir().unwrap();
+        let policy_dir = tempfile::tempdir().unwrap();
+        let path = policy_dir.path().join("bundle.tar.gz");
+        std::fs::write(&path, bundle(&policy(r#""subject":"alice""#), "{}")).unwrap();
+        let addr = common::start(config(root.path(), &path)).await;
+
+        assert_eq!(put(addr, "alice").await, 200);
+        assert_eq!(put(addr, "bob").await, 403);
+
+        // The reloader picks up a new bundle
+        std::fs::write(&path, bundle(&policy(r#""subject":"bob""#), "{}")).unwrap();
+        let mut status = reqwest::StatusCode::FORBIDDEN;
+        for _ in 0..50 {
+            status = put(addr, "bob").await;
+            if status == 200 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert_eq!(status, 200);
+    }
+
+    #[cfg(not(feature = "opa-wasm"))]
+    #[tokio::test]
+    async fn test_bundle_requires_opa_wasm_feature() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join("bundle.tar.gz");
+
+        let result =
+            mizuchi_uploadr::server::pingora::PingoraServer::new(config(root.path(), &path)).await;
+
+        let error = result.err().unwrap().to_string();
+        assert!(error.contains("`opa-wasm` feature"), "{}", error);
+    }
+}
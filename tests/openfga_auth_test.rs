@@ -2,7 +2,7 @@
 //!
 //! Tests for OpenFGA fine-grained authorization using a mock server.
 
-use mizuchi_uploadr::authz::openfga::{OpenFgaAuthorizer, OpenFgaConfig};
+use mizuchi_uploadr::authz::openfga::{OpenFgaAuthorizer, OpenFgaConfig, OpenFgaObjectMapping};
 use mizuchi_uploadr::authz::{Authorizer, AuthzRequest};
 use std::collections::HashMap;
 use wiremock::matchers::{body_json, method, path};
@@ -16,6 +16,9 @@ fn create_authorizer(mock_server: &MockServer, store_id: &str) -> OpenFgaAuthori
         authorization_model_id: None,
         timeout: None,
         cache_ttl: None,
+        deny_cache_ttl: None,
+        object_mapping: Default::default(),
+        transport: Default::default(),
     };
     OpenFgaAuthorizer::new(config)
 }
@@ -108,6 +111,9 @@ mod tests {
             authorization_model_id: None,
             timeout: Some(std::time::Duration::from_millis(100)),
             cache_ttl: None,
+            deny_cache_ttl: None,
+            object_mapping: Default::default(),
+            transport: Default::default(),
         };
         let authorizer = OpenFgaAuthorizer::new(config);
         let request = create_request("alice", "upload", "uploads/file.txt");
@@ -215,6 +221,9 @@ mod tests {
             authorization_model_id: Some("model-123".to_string()),
             timeout: None,
             cache_ttl: None,
+            deny_cache_ttl: None,
+            object_mapping: Default::default(),
+            transport: Default::default(),
         };
         let authorizer = OpenFgaAuthorizer::new(config);
         let request = create_request("alice", "upload", "uploads/file.txt");
@@ -248,6 +257,9 @@ mod tests {
             authorization_model_id: None,
             timeout: Some(std::time::Duration::from_millis(100)),
             cache_ttl: None,
+            deny_cache_ttl: None,
+            object_mapping: Default::default(),
+            transport: Default::default(),
         };
         let authorizer = OpenFgaAuthorizer::new(config);
         let request = create_request("alice", "upload", "uploads/file.txt");
@@ -281,6 +293,9 @@ mod tests {
             authorization_model_id: None,
             timeout: None,
             cache_ttl: Some(std::time::Duration::from_secs(1)),
+            deny_cache_ttl: None,
+            object_mapping: Default::default(),
+            transport: Default::default(),
         };
         let authorizer = OpenFgaAuthorizer::new(config);
         let request = create_request("alice", "upload", "uploads/file.txt");
@@ -322,6 +337,9 @@ mod tests {
             authorization_model_id: None,
             timeout: None,
             cache_ttl: Some(std::time::Duration::from_millis(50)),
+            deny_cache_ttl: None,
+            object_mapping: Default::default(),
+            transport: Default::default(),
         };
         let authorizer = OpenFgaAuthorizer::new(config);
         let request = create_request("alice", "upload", "uploads/file.txt");
@@ -405,4 +423,40 @@ mod tests {
         assert!(!results[1]);
         assert!(results[2]);
     }
+
+    #[tokio::test]
+    async fn test_folder_object_mapping_with_contextual_tuples() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/stores/test-store/check"))
+            .and(body_json(json!({
+                "tuple_key": {
+                    "user": "user:alice",
+                    "relation": "writer",
+                    "object": "folder:docs/team/"
+                },
+                "contextual_tuples": {
+                    "tuple_keys": [
+                        { "user": "folder:docs/", "relation": "parent", "object": "folder:docs/team/" },
+                        { "user": "bucket:docs", "relation": "parent", "object": "folder:docs/" }
+                    ]
+                }
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "allowed": true })))
+            .mount(&mock_server)
+            .await;
+
+        let authorizer = OpenFgaAuthorizer::builder()
+            .url(&mock_server.uri())
+            .store_id("test-store")
+            .object_mapping(OpenFgaObjectMapping::Folder)
+            .build()
+            .expect("Should build authorizer");
+
+        let request = create_request("alice", "upload", "bucket/docs/team/plan.md");
+        let result = authorizer.authorize(&request).await;
+
+        assert!(result.unwrap(), "Folder check should match the mock");
+    }
 }
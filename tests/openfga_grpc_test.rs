@@ -0,0 +1,230 @@
+//! OpenFGA gRPC Transport Integration Tests
+//!
+//! Tests for `OpenFgaAuthorizer` with the gRPC transport, against a minimal
+//! HTTP/2 server answering `openfga.v1.OpenFGAService/Check`.
+//!
+//! Run with: `cargo test --features openfga-grpc --test openfga_grpc_test`
+//!
+//! ## Test Coverage
+//!
+//! - Checks reach the Check RPC with the store and tuple key
+//! - Consecutive checks reuse one connection
+//! - The client deadline is sent as `grpc-timeout`
+//! - Error statuses are backend errors
+
+#![cfg(feature = "openfga-grpc")]
+
+#[cfg(test)]
+mod tests {
+    use bytes::{BufMut, Bytes, BytesMut};
+    use http_body_util::{BodyExt, StreamBody};
+    use hyper::body::{Frame, Incoming};
+    use hyper::service::service_fn;
+    use hyper::{Request, Response};
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use mizuchi_uploadr::authz::openfga::{OpenFgaAuthorizer, OpenFgaTransport};
+    use mizuchi_uploadr::authz::{Authorizer, AuthzError, AuthzRequest};
+    use mizuchi_uploadr::s3::deadline;
+    use parking_lot::Mutex;
+    use prost::Message;
+    use std::collections::HashMap;
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    /// Fields of `openfga.v1.CheckRequest` inspected by the tests
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct CheckRequest {
+        #[prost(string, tag = "1")]
+        store_id: String,
+        #[prost(message, optional, tag = "2")]
+        tuple_key: Option<TupleKey>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct TupleKey {
+        #[prost(string, tag = "1")]
+        user: String,
+        #[prost(string, tag = "2")]
+        relation: String,
+        #[prost(string, tag = "3")]
+        object: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct CheckResponse {
+        #[prost(bool, tag = "1")]
+        allowed: bool,
+    }
+
+    /// A check received by the mock server
+    struct Received {
+        path: String,
+        grpc_timeout: Option<String>,
+        request: CheckRequest,
+    }
+
+    #[derive(Default)]
+    struct MockOpenFga {
+        connections: AtomicUsize,
+        received: Mutex<Vec<Received>>,
+    }
+
+    /// Serve Check calls, allowing `user:alice` and failing with `status`
+    /// (a gRPC status code) when it isn't 0
+    async fn start(status: u32) -> (String, Arc<MockOpenFga>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let mock = Arc::new(MockOpenFga::default());
+
+        let state = Arc::clone(&mock);
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                state.connections.fetch_add(1, Ordering::SeqCst);
+                let state = Arc::clone(&state);
+                tokio::spawn(async move {
+                    let service = service_fn(move |request: Request<Incoming>| {
+                        let state = Arc::clone(&state);
+                        async move { Ok::<_, Infallible>(check(&state, request, status).await) }
+                    });
+                    let _ = hyper::server::conn::http2::Builder::new(TokioExecutor::new())
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+        (url, mock)
+    }
+
+    type GrpcBody =
+        StreamBody<futures::stream::Iter<std::vec::IntoIter<Result<Frame<Bytes>, Infallible>>>>;
+
+    async fn check(
+        state: &MockOpenFga,
+        request: Request<Incoming>,
+        status: u32,
+    ) -> Response<GrpcBody> {
+        let path = request.uri().path().to_string();
+        let grpc_timeout = request
+            .headers()
+            .get("grpc-timeout")
+            .map(|v| v.to_str().unwrap().to_string());
+        let body = request.into_body().collect().await.unwrap().to_bytes();
+        // Skip the compression flag and length prefix of the gRPC frame
+        let request = CheckRequest::decode(&body[5..]).unwrap();
+        let allowed = request.tuple_key.as_ref().unwrap().user == "user:alice";
+        state.received.lock().push(Received {
+            path,
+            grpc_timeout,
+            request,
+        });
+
+        let mut frames = Vec::new();
+        if status == 0 {
+            let message = CheckResponse { allowed }.encode_to_vec();
+            let mut data = BytesMut::new();
+            data.put_u8(0);
+            data.put_u32(message.len() as u32);
+            data.put_slice(&message);
+            frames.push(Ok(Frame::data(data.freeze())));
+        }
+        let mut trailers = hyper::HeaderMap::new();
+        trailers.insert("grpc-status", status.to_string().parse().unwrap());
+        trailers.insert("grpc-message", "store not found".parse().unwrap());
+        frames.push(Ok(Frame::trailers(trailers)));
+
+        Response::builder()
+            .header("content-type", "application/grpc")
+            .body(StreamBody::new(futures::stream::iter(frames)))
+            .unwrap()
+    }
+
+    fn authorizer(url: &str) -> OpenFgaAuthorizer {
+        OpenFgaAuthorizer::builder()
+            .url(url)
+            .store_id("store123")
+            .timeout(Duration::from_secs(5))
+            .transport(OpenFgaTransport::Grpc)
+            .build()
+            .unwrap()
+    }
+
+    fn request(subject: &str) -> AuthzRequest {
+        AuthzRequest {
+            subject: subject.into(),
+            action: "upload".into(),
+            resource: "uploads".into(),
+            context: HashMap::new(),
+        }
+    }
+
+    /// `grpc-timeout` header value as a duration
+    fn parse_timeout(value: &str) -> Duration {
+        let (amount, unit) = value.split_at(value.len() - 1);
+        let amount: u64 = amount.parse().unwrap();
+        match unit {
+            "H" => Duration::from_secs(amount * 3600),
+            "M" => Duration::from_secs(amount * 60),
+            "S" => Duration::from_secs(amount),
+            "m" => Duration::from_millis(amount),
+            "u" => Duration::from_micros(amount),
+            "n" => Duration::from_nanos(amount),
+            _ => panic!("invalid grpc-timeout {}", value),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_over_grpc() {
+        let (url, mock) = start(0).await;
+        let authorizer = authorizer(&url);
+
+        assert!(authorizer.authorize(&request("alice")).await.unwrap());
+        assert!(!authorizer.authorize(&request("bob")).await.unwrap());
+
+        let received = mock.received.lock();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].path, "/openfga.v1.OpenFGAService/Check");
+        assert_eq!(received[0].request.store_id, "store123");
+        let tuple_key = received[0].request.tuple_key.as_ref().unwrap();
+        assert_eq!(tuple_key.relation, "writer");
+        assert_eq!(tuple_key.object, "bucket:uploads");
+        assert_eq!(mock.connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_deadline_is_propagated() {
+        let (url, mock) = start(0).await;
+        let authorizer = authorizer(&url);
+
+        authorizer.authorize(&request("alice")).await.unwrap();
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(500);
+        deadline::scope(Some(deadline), authorizer.authorize(&request("bob")))
+            .await
+            .unwrap();
+
+        let received = mock.received.lock();
+        let configured = parse_timeout(received[0].grpc_timeout.as_deref().unwrap());
+        let bounded = parse_timeout(received[1].grpc_timeout.as_deref().unwrap());
+        assert_eq!(configured, Duration::from_secs(5));
+        assert!(bounded <= Duration::from_millis(500), "{:?}", bounded);
+    }
+
+    #[tokio::test]
+    async fn test_error_status_is_backend_error() {
+        // NOT_FOUND
+        let (url, _mock) = start(5).await;
+
+        let err = authorizer(&url)
+            .authorize(&request("alice"))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, AuthzError::BackendError(ref e) if e.contains("store not found")),
+            "{:?}",
+            err
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Request Phase Metrics Integration Tests
+//!
+//! Tests for `mizuchi_request_phase_duration_seconds`, the time uploads
+//! spend in authentication, authorization, body handling and S3.
+//!
+//! ## Test Coverage
+//!
+//! - Every phase of a successful upload is observed once
+//! - Time spent in a slow authorizer is attributed to `authz`
+//! - Uploads rejected during auth only observe the auth phase
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use http_body_util::Full;
+    use hyper::{Request, StatusCode};
+    use mizuchi_uploadr::auth::{AuthError, AuthRequest, AuthResult, Authenticator};
+    use mizuchi_uploadr::authz::{Authorizer, AuthzError, AuthzRequest};
+    use mizuchi_uploadr::config::{BucketConfig, S3Config, StorageConfig};
+    use mizuchi_uploadr::metrics::REQUEST_PHASE_DURATION;
+    use mizuchi_uploadr::UploadService;
+    use std::collections::HashMap;
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tower_service::Service;
+
+    /// Accepts requests carrying `X-User`
+    struct HeaderAuthenticator;
+
+    #[async_trait]
+    impl Authenticator for HeaderAuthenticator {
+        async fn authenticate(&self, request: &AuthRequest) -> Result<AuthResult, AuthError> {
+            let user = request
+                .headers
+                .get("x-user")
+                .ok_or(AuthError::MissingAuth)?;
+            Ok(AuthResult {
+                subject: user.clone(),
+                claims: HashMap::new(),
+            })
+        }
+    }
+
+    /// Allows everything, slowly
+    struct SlowAuthorizer(Duration);
+
+    #[async_trait]
+    impl Authorizer for SlowAuthorizer {
+        async fn authorize(&self, _request: &AuthzRequest) -> Result<bool, AuthzError> {
+            tokio::time::sleep(self.0).await;
+            Ok(true)
+        }
+    }
+
+    /// Metrics are global, so each test uses its own bucket
+    async fn service(root: &Path, bucket: &str) -> UploadService {
+        let mut config = BucketConfig::new(
+            bucket,
+            &format!("/{}", bucket),
+            S3Config::new(bucket, "us-east-1"),
+        );
+        config.storage = StorageConfig::Local {
+            root: root.display().to_string(),
+        };
+        UploadService::builder()
+            .add_bucket(config)
+            .with_authenticator(bucket, Arc::new(HeaderAuthenticator))
+            .with_authorizer(bucket, Arc::new(SlowAuthorizer(Duration::from_millis(50))))
+            .build_service()
+            .await
+            .unwrap()
+    }
+
+    async fn upload(service: &mut UploadService, bucket: &str, user: Option<&str>) -> StatusCode {
+        let mut request = Request::put(format!("/{}/object", bucket));
+        if let Some(user) = user {
+            request = request.header("X-User", user);
+        }
+        let request = request.body(Full::new(Bytes::from("hello"))).unwrap();
+        service.call(request).await.unwrap().status()
+    }
+
+    fn observed(bucket: &str, phase: &str) -> (u64, f64) {
+        let histogram = REQUEST_PHASE_DURATION.with_label_values(&[bucket, phase]);
+        (histogram.get_sample_count(), histogram.get_sample_sum())
+    }
+
+    #[tokio::test]
+    async fn test_phases_observed() {
+        let dir = tempfile::tempdir().unwrap();
+        let bucket = "phases-observed";
+        let mut service = service(dir.path(), bucket).await;
+
+        assert_eq!(
+            upload(&mut service, bucket, Some("alice")).await,
+            StatusCode::OK
+        );
+
+        for phase in ["auth", "authz", "body", "s3", "total"] {
+            assert_eq!(observed(bucket, phase).0, 1, "phase {}", phase);
+        }
+        let (_, authz) = observed(bucket, "authz");
+        let (_, total) = observed(bucket, "total");
+        assert!(authz >= 0.05, "authz took {}s", authz);
+        assert!(total >= authz);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_upload_observes_auth_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let bucket = "phases-rejected";
+        let mut service = service(dir.path(), bucket).await;
+
+        assert_eq!(
+            upload(&mut service, bucket, None).await,
+            StatusCode::UNAUTHORIZED
+        );
+
+        assert_eq!(observed(bucket, "auth").0, 1);
+        for phase in ["authz", "body", "s3", "total"] {
+            assert_eq!(observed(bucket, phase).0, 0, "phase {}", phase);
+        }
+    }
+}
@@ -8,6 +8,7 @@
 //! - Health check endpoint
 //! - Basic HTTP request handling
 //! - Graceful shutdown
+//! - HeadBucket and GetBucketLocation for SDK compatibility
 //!
 
 use mizuchi_uploadr::config::{
@@ -23,6 +24,12 @@ fn test_config(port: u16) -> Config {
         server: ServerConfig {
             address: format!("127.0.0.1:{}", port),
             zero_copy: ZeroCopyConfig::default(),
+            http: Default::default(),
+            tls: None,
+            listeners: Vec::new(),
+            tcp: Default::default(),
+            buffer_pool: Default::default(),
+            shutdown: Default::default(),
         },
         buckets: vec![BucketConfig {
             name: "test".into(),
@@ -33,12 +40,34 @@ fn test_config(port: u16) -> Config {
                 endpoint: Some("http://localhost:9000".into()),
                 access_key: Some("minioadmin".into()),
                 secret_key: Some("minioadmin".into()),
+                pool: None,
+                sigv4a_region_set: None,
+                hedging: None,
+                hashing: None,
+                checksum: None,
+                debug_log: None,
+                access_key_file: None,
+                secret_key_file: None,
             },
             auth: Default::default(),
             upload: Default::default(),
+            failover: None,
+            replication: None,
+            storage: Default::default(),
+            authz: None,
+            authz_failure: Default::default(),
+            tenant: None,
+            hosts: Vec::new(),
+            key_prefix: None,
+            regions: None,
+            authz_shadow: None,
         }],
         metrics: MetricsConfig::default(),
         tracing: None,
+        notifications: Default::default(),
+        admin: Default::default(),
+        logging: Default::default(),
+        state: Default::default(),
     }
 }
 
@@ -140,6 +169,69 @@ async fn test_server_handles_basic_http_requests() {
     server_handle.abort();
 }
 
+/// Test: HEAD on a bucket prefix answers like HeadBucket
+#[tokio::test]
+async fn test_server_head_bucket() {
+    let server = PingoraServer::new(test_config(0))
+        .await
+        .expect("Failed to create server");
+    let addr = server.local_addr().expect("Failed to get local address");
+    let server_handle = tokio::spawn(async move { server.run().await });
+    sleep(Duration::from_millis(100)).await;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .head(format!("http://{}/uploads", addr))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["x-amz-bucket-region"], "us-east-1");
+
+    // Objects cannot be read through the proxy
+    let response = client
+        .head(format!("http://{}/uploads/test.txt", addr))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), 404);
+
+    server_handle.abort();
+}
+
+/// Test: GET ?location on a bucket prefix answers like GetBucketLocation
+#[tokio::test]
+async fn test_server_get_bucket_location() {
+    let mut config = test_config(0);
+    config.buckets[0].s3.region = "eu-west-1".into();
+    let server = PingoraServer::new(config)
+        .await
+        .expect("Failed to create server");
+    let addr = server.local_addr().expect("Failed to get local address");
+    let server_handle = tokio::spawn(async move { server.run().await });
+    sleep(Duration::from_millis(100)).await;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .get(format!("http://{}/uploads/?location", addr))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), 200);
+    let body = response.text().await.expect("Failed to read response body");
+    assert!(body.contains(">eu-west-1</LocationConstraint>"), "{}", body);
+
+    // Other bucket-level calls are still rejected
+    let response = client
+        .get(format!("http://{}/uploads/", addr))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), 404);
+
+    server_handle.abort();
+}
+
 /// Test: Server handles graceful shutdown
 ///
 /// RED Phase: This test will fail because graceful shutdown doesn't exist yet
@@ -30,6 +30,7 @@ mod tests {
             secret_key: Some("test-secret".to_string()),
             retry: None,
             timeout: None,
+            pool: None,
         };
         S3Client::new(s3_config).unwrap()
     }
@@ -237,8 +238,10 @@ mod tests {
                 initial_backoff_ms: 10,
                 max_backoff_ms: 100,
                 backoff_multiplier: 2.0,
+                budget: 0,
             }),
             timeout: None,
+            pool: None,
         };
         let s3_client = S3Client::new(s3_config).unwrap();
 
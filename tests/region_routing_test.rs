@@ -0,0 +1,146 @@
+//! Region Routing Integration Tests
+//!
+//! Tests for buckets with `regions`: a local home region and a mock S3
+//! regional target.
+//!
+//! ## Test Coverage
+//!
+//! - Clients in a target's networks upload to that region, tagged with it
+//! - Other clients upload to the home region
+//! - Uploads are counted per region
+//! - Invalid region names and networks fail config validation
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use bytes::Bytes;
+    use http_body_util::Full;
+    use hyper::{Request, StatusCode};
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::metrics::REGION_UPLOADS;
+    use mizuchi_uploadr::UploadService;
+    use std::net::SocketAddr;
+    use std::path::Path;
+    use tower_service::Service;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn config(root: &Path, bucket: &str, s3_url: &str, cidrs: &str) -> Config {
+        let buckets = format!(
+            r#"  - name: {bucket}
+    path_prefix: /uploads
+    s3:
+      bucket: uploads
+      region: us-east-1
+    storage:
+      type: local
+      root: "{}"
+    regions:
+      targets:
+        - name: eu-west-1
+          cidrs: {cidrs}
+          s3:
+            bucket: uploads-eu
+            region: eu-west-1
+            endpoint: "{s3_url}"
+            access_key: test
+            secret_key: test
+"#,
+            root.display()
+        );
+        common::config(&buckets)
+    }
+
+    async fn upload(service: &mut UploadService, client: &str) -> StatusCode {
+        let mut request = Request::put("/uploads/a.txt")
+            .body(Full::new(Bytes::from("hello")))
+            .unwrap();
+        let client: SocketAddr = client.parse().unwrap();
+        request.extensions_mut().insert(client);
+        service.call(request).await.unwrap().status()
+    }
+
+    async fn service(config: Config) -> UploadService {
+        UploadService::builder()
+            .config(config)
+            .build_service()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_client_network_picks_region() {
+        let s3 = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/uploads-eu/a.txt"))
+            .and(header("x-amz-tagging", "origin-region=eu-west-1"))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "\"eu\""))
+            .expect(1)
+            .mount(&s3)
+            .await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path(), "regional", &s3.uri(), r#"["10.20.0.0/16"]"#);
+        let mut service = service(config).await;
+
+        let status = upload(&mut service, "10.20.1.2:40000").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(!dir.path().join("a.txt").exists());
+        let eu = REGION_UPLOADS
+            .with_label_values(&["regional", "eu-west-1"])
+            .get();
+        assert_eq!(eu, 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_other_clients_use_home_region() {
+        let s3 = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&s3)
+            .await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(
+            dir.path(),
+            "regional-home",
+            &s3.uri(),
+            r#"["10.20.0.0/16"]"#,
+        );
+        let mut service = service(config).await;
+
+        let status = upload(&mut service, "192.168.1.5:40000").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(std::fs::read(dir.path().join("a.txt")).unwrap(), b"hello");
+        let home = REGION_UPLOADS
+            .with_label_values(&["regional-home", "us-east-1"])
+            .get();
+        assert_eq!(home, 1.0);
+    }
+
+    #[test]
+    fn test_invalid_regions_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = config(
+            dir.path(),
+            "regional-invalid",
+            "http://127.0.0.1:9000",
+            r#"["10.20.0.0/33"]"#,
+        );
+        config.buckets[0].regions.as_mut().unwrap().targets[0].name = "us-east-1".into();
+
+        let errors: Vec<String> = config
+            .validation_errors()
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+
+        assert!(errors.iter().any(|e| e.contains("regions.targets[0].name")));
+        assert!(errors
+            .iter()
+            .any(|e| e.contains("regions.targets[0].cidrs")));
+    }
+}
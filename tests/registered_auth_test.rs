@@ -0,0 +1,143 @@
+//! Registered Authenticator/Authorizer Integration Tests
+//!
+//! Tests for authenticators and authorizers registered in code and referred
+//! to by name from YAML (`auth.custom`, `authz: {type: custom}`).
+//!
+//! ## Test Coverage
+//!
+//! - A registered authenticator authenticates buckets listing it
+//! - A registered authorizer decides for buckets naming it
+//! - Registered authorizers can be combined with `all_of`/`any_of`
+//! - Unregistered names fail at startup
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use http_body_util::Full;
+    use hyper::{Request, StatusCode};
+    use mizuchi_uploadr::auth::{AuthError, AuthRequest, AuthResult, Authenticator};
+    use mizuchi_uploadr::authz::{Authorizer, AuthzError, AuthzRequest};
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::{ServerBuilder, UploadService};
+    use std::collections::HashMap;
+    use std::path::Path;
+    use std::sync::Arc;
+    use tower_service::Service;
+
+    /// Accepts `X-Team-Token: <team>` as the subject `<team>`
+    struct TeamTokenAuthenticator;
+
+    #[async_trait]
+    impl Authenticator for TeamTokenAuthenticator {
+        async fn authenticate(&self, request: &AuthRequest) -> Result<AuthResult, AuthError> {
+            let team = request
+                .headers
+                .get("x-team-token")
+                .ok_or(AuthError::MissingAuth)?;
+            Ok(AuthResult {
+                subject: team.clone(),
+                claims: HashMap::new(),
+            })
+        }
+    }
+
+    /// Allows one subject only
+    struct SubjectIs(&'static str);
+
+    #[async_trait]
+    impl Authorizer for SubjectIs {
+        async fn authorize(&self, request: &AuthzRequest) -> Result<bool, AuthzError> {
+            Ok(request.subject == self.0)
+        }
+    }
+
+    fn config(root: &Path, authz: &str) -> Config {
+        let bucket = format!(
+            r#"    auth:
+      enabled: true
+      custom: [team-token]
+    authz:
+{}"#,
+            authz
+        );
+        common::config(&common::local_bucket("uploads", root, &bucket))
+    }
+
+    fn builder(config: Config) -> ServerBuilder {
+        UploadService::builder()
+            .config(config)
+            .register_authenticator("team-token", Arc::new(TeamTokenAuthenticator))
+            .register_authorizer("storage-team", Arc::new(SubjectIs("storage")))
+            .register_authorizer("ops-team", Arc::new(SubjectIs("ops")))
+    }
+
+    async fn status(service: &mut UploadService, team: Option<&str>) -> StatusCode {
+        let mut request = Request::put("/uploads/a.txt");
+        if let Some(team) = team {
+            request = request.header("X-Team-Token", team);
+        }
+        let request = request.body(Full::new(Bytes::from("hello"))).unwrap();
+        service.call(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_registered_components_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let authz = "      type: custom\n      name: storage-team";
+        let mut service = builder(config(dir.path(), authz))
+            .build_service()
+            .await
+            .unwrap();
+
+        assert_eq!(status(&mut service, None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            status(&mut service, Some("marketing")).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(status(&mut service, Some("storage")).await, StatusCode::OK);
+        assert!(dir.path().join("a.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_registered_authorizers_combined() {
+        let dir = tempfile::tempdir().unwrap();
+        let authz = "      type: any_of\n      authorizers:\n        \
+                     - type: custom\n          name: storage-team\n        \
+                     - type: custom\n          name: ops-team";
+        let mut service = builder(config(dir.path(), authz))
+            .build_service()
+            .await
+            .unwrap();
+
+        assert_eq!(status(&mut service, Some("ops")).await, StatusCode::OK);
+        assert_eq!(
+            status(&mut service, Some("marketing")).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unregistered_names_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let authz = "      type: custom\n      name: unknown-team";
+        let err = builder(config(dir.path(), authz))
+            .build_service()
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("'unknown-team'"), "{err}");
+
+        let authz = "      type: custom\n      name: storage-team";
+        let err = UploadService::builder()
+            .config(config(dir.path(), authz))
+            .build_service()
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("'team-token'"), "{err}");
+    }
+}
@@ -0,0 +1,275 @@
+//! Replication Integration Tests
+//!
+//! Tests for mirroring uploads to a secondary bucket.
+//!
+//! ## Test Coverage
+//!
+//! - Sync mode writes both copies and fails if the secondary fails
+//! - Async mode returns after the primary write and replicates in the background
+//! - Failed async replications are written to the dead-letter file
+//! - Multipart uploads are mirrored part by part
+//! - Replication config wraps the bucket backend
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use mizuchi_uploadr::config::{Config, ReplicationMode};
+    use mizuchi_uploadr::s3::{RetryConfig, S3Client, S3ClientConfig};
+    use mizuchi_uploadr::upload::backend::{self, StorageBackend};
+    use mizuchi_uploadr::upload::local::LocalFsBackend;
+    use mizuchi_uploadr::upload::multipart::{MultipartHandler, MIN_PART_SIZE};
+    use mizuchi_uploadr::upload::replication::{
+        DeadLetterLog, DeadLetterRecord, ReplicatedBackend,
+    };
+    use std::sync::Arc;
+    use std::time::Duration;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn local(dir: &tempfile::TempDir) -> Arc<dyn StorageBackend> {
+        Arc::new(LocalFsBackend::new("primary", dir.path()).unwrap())
+    }
+
+    /// S3 secondary pointing at a mock server, without retries
+    fn s3_secondary(server: &MockServer) -> Arc<dyn StorageBackend> {
+        Arc::new(
+            S3Client::new(S3ClientConfig {
+                bucket: "replica".into(),
+                region: "eu-west-1".into(),
+                endpoint: Some(server.uri()),
+                access_key: Some("test-access".into()),
+                secret_key: Some("test-secret".into()),
+                retry: Some(RetryConfig {
+                    max_retries: 0,
+                    initial_backoff_ms: 1,
+                    max_backoff_ms: 1,
+                    backoff_multiplier: 1.0,
+                    budget: 0,
+                }),
+                timeout: None,
+                pool: None,
+            })
+            .unwrap(),
+        )
+    }
+
+    async fn wait_for<F: Fn() -> bool>(condition: F) -> bool {
+        for _ in 0..100 {
+            if condition() {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        condition()
+    }
+
+    #[tokio::test]
+    async fn test_sync_replication_writes_secondary() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/replica/a.txt"))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "\"replica\""))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let backend = ReplicatedBackend::sync(local(&dir), s3_secondary(&server));
+
+        let object = backend
+            .put_object("a.txt", Bytes::from("hello"), None)
+            .await
+            .unwrap();
+
+        // ETag comes from the primary
+        assert_eq!(object.etag, "\"5d41402abc4b2a76b9719d911017c592\"");
+        assert!(dir.path().join("a.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_sync_replication_fails_when_secondary_fails() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let backend = ReplicatedBackend::sync(local(&dir), s3_secondary(&server));
+
+        let err = backend
+            .put_object("a.txt", Bytes::from("hello"), None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Replication failed"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_async_replication_happens_in_background() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/replica/a.txt"))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "\"replica\""))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let dlq = dir.path().join("dead-letter.jsonl");
+        let backend = ReplicatedBackend::asynchronous(
+            local(&dir),
+            s3_secondary(&server),
+            16,
+            0,
+            Arc::new(DeadLetterLog::new(&dlq)),
+        );
+        assert_eq!(backend.mode(), ReplicationMode::Async);
+
+        backend
+            .put_object("a.txt", Bytes::from("hello"), None)
+            .await
+            .unwrap();
+
+        let mut delivered = false;
+        for _ in 0..100 {
+            if !server.received_requests().await.unwrap().is_empty() {
+                delivered = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(delivered, "Replica write should happen in the background");
+        assert!(!dlq.exists());
+    }
+
+    #[tokio::test]
+    async fn test_async_replication_failure_is_dead_lettered() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let dlq = dir.path().join("dead-letter.jsonl");
+        let backend = ReplicatedBackend::asynchronous(
+            local(&dir),
+            s3_secondary(&server),
+            16,
+            1,
+            Arc::new(DeadLetterLog::new(&dlq)),
+        );
+
+        // Upload succeeds even though the secondary is down
+        backend
+            .put_object("lost.txt", Bytes::from("hello"), None)
+            .await
+            .unwrap();
+
+        assert!(
+            wait_for(|| dlq.exists()).await,
+            "Dead-letter file not written"
+        );
+        let contents = std::fs::read_to_string(&dlq).unwrap();
+        let record: DeadLetterRecord =
+            serde_json::from_str(contents.lines().next().unwrap()).unwrap();
+        assert_eq!(record.bucket, "primary");
+        assert_eq!(record.key, "lost.txt");
+        assert_eq!(record.size, 5);
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload_is_mirrored() {
+        let primary_dir = tempfile::tempdir().unwrap();
+        let secondary_dir = tempfile::tempdir().unwrap();
+        let backend = Arc::new(ReplicatedBackend::sync(
+            local(&primary_dir),
+            Arc::new(LocalFsBackend::new("replica", secondary_dir.path()).unwrap()),
+        ));
+        let handler = MultipartHandler::with_backend(backend);
+
+        let mut upload = handler.create("primary", "big.bin").await.unwrap();
+        handler
+            .upload_part(&mut upload, 1, Bytes::from(vec![b'a'; MIN_PART_SIZE]))
+            .await
+            .unwrap();
+        handler
+            .upload_part(&mut upload, 2, Bytes::from("part two"))
+            .await
+            .unwrap();
+        handler.complete(&upload).await.unwrap();
+
+        let primary = std::fs::read(primary_dir.path().join("big.bin")).unwrap();
+        let secondary = std::fs::read(secondary_dir.path().join("big.bin")).unwrap();
+        assert_eq!(primary.len(), MIN_PART_SIZE + 8);
+        assert!(primary.ends_with(b"part two"));
+        assert_eq!(primary, secondary);
+    }
+
+    #[tokio::test]
+    async fn test_replication_from_config() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/replica/config.txt"))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "\"replica\""))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = format!(
+            r#"
+server:
+  address: "127.0.0.1:0"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: primary
+      region: us-east-1
+    storage:
+      type: local
+      root: "{}"
+    replication:
+      mode: sync
+      target:
+        bucket: replica
+        region: eu-west-1
+        endpoint: "{}"
+        access_key: test-access
+        secret_key: test-secret
+"#,
+            dir.path().display(),
+            server.uri()
+        );
+        let config: Config = serde_yaml::from_str(&yaml).unwrap();
+        config.validate().unwrap();
+
+        let backend = backend::from_bucket_config(&config.buckets[0]).unwrap();
+        backend
+            .put_object("config.txt", Bytes::from("hi"), None)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_async_replication_requires_dead_letter_path() {
+        let yaml = r#"
+server:
+  address: "127.0.0.1:0"
+buckets:
+  - name: uploads
+    path_prefix: /uploads
+    s3:
+      bucket: primary
+      region: us-east-1
+    replication:
+      mode: async
+      target:
+        bucket: replica
+        region: eu-west-1
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,107 @@
+//! Response Header Integration Tests
+//!
+//! Tests for the headers of `upload.response_headers`.
+//!
+//! ## Test Coverage
+//!
+//! - Successful uploads carry the configured headers, placeholders filled in
+//! - Placeholders see the stored key, with its `key_prefix`
+//! - Invalid templates fail config validation
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use bytes::Bytes;
+    use http_body_util::Full;
+    use hyper::{Request, StatusCode};
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::UploadService;
+    use std::path::Path;
+    use tower_service::Service;
+
+    fn config(root: &Path, extra: &str, headers: &str) -> Config {
+        let buckets = format!(
+            r#"  - name: cdn
+    path_prefix: /uploads
+    s3:
+      bucket: uploads
+      region: us-east-1
+    storage:
+      type: local
+      root: "{}"{}
+    upload:
+      response_headers:{}
+"#,
+            root.display(),
+            extra,
+            headers
+        );
+        common::config(&buckets)
+    }
+
+    const HEADERS: &str = r#"
+        X-Object-Url: "https://cdn.example.com/{key}"
+        X-Object-Etag: "{etag}"
+        Cache-Control: no-store"#;
+
+    #[tokio::test]
+    async fn test_configured_headers_added() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut service = UploadService::builder()
+            .config(config(dir.path(), "", HEADERS))
+            .build_service()
+            .await
+            .unwrap();
+
+        let request = Request::put("/uploads/photos/2024/beach.jpg")
+            .body(Full::new(Bytes::from("hello")))
+            .unwrap();
+        let response = service.call(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(
+            headers["x-object-url"],
+            "https://cdn.example.com/photos/2024/beach.jpg"
+        );
+        let etag = headers["etag"].to_str().unwrap().trim_matches('"');
+        assert_eq!(headers["x-object-etag"], etag);
+        assert_eq!(headers["cache-control"], "no-store");
+    }
+
+    #[tokio::test]
+    async fn test_key_includes_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let extra = "\n    key_prefix: incoming/";
+        let mut service = UploadService::builder()
+            .config(config(dir.path(), extra, HEADERS))
+            .build_service()
+            .await
+            .unwrap();
+
+        let request = Request::put("/uploads/a.txt")
+            .body(Full::new(Bytes::from("hello")))
+            .unwrap();
+        let response = service.call(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["x-object-url"],
+            "https://cdn.example.com/incoming/a.txt"
+        );
+    }
+
+    #[test]
+    fn test_invalid_template_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path(), "", "\n        X-Owner: \"{owner}\"");
+
+        let errors = config.validation_errors();
+
+        assert!(errors
+            .iter()
+            .any(|e| e.to_string().contains("upload.response_headers")));
+    }
+}
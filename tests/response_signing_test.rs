@@ -0,0 +1,90 @@
+//! Response Signing Integration Tests
+//!
+//! Tests for the `x-mizuchi-signature` header of `upload.signing`.
+//!
+//! ## Test Coverage
+//!
+//! - Successful uploads carry a signature over the stored object
+//! - The signature does not verify for another key or ETag
+//! - Buckets without `upload.signing` send no signature
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use bytes::Bytes;
+    use http_body_util::Full;
+    use hyper::{Request, Response, StatusCode};
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::server::signing::{ResponseSigner, SIGNATURE_HEADER};
+    use mizuchi_uploadr::UploadService;
+    use std::path::Path;
+    use tower_service::Service;
+
+    const SIGNING: &str = r#"
+      signing:
+        algorithm: hmac_sha256
+        key: test-secret
+        key_id: k1
+"#;
+
+    fn config(root: &Path, signing: &str) -> Config {
+        let buckets = format!(
+            r#"  - name: signed
+    path_prefix: /uploads
+    s3:
+      bucket: uploads
+      region: us-east-1
+    storage:
+      type: local
+      root: "{}"
+    upload:
+      multipart_threshold: 52428800{}
+"#,
+            root.display(),
+            signing
+        );
+        common::config(&buckets)
+    }
+
+    async fn upload(config: Config) -> Response<String> {
+        let mut service = UploadService::builder()
+            .config(config)
+            .build_service()
+            .await
+            .unwrap();
+        let request = Request::put("/uploads/docs/a.txt")
+            .body(Full::new(Bytes::from("hello")))
+            .unwrap();
+        service.call(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_upload_response_signed() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path(), SIGNING);
+        let signer =
+            ResponseSigner::new(config.buckets[0].upload.signing.as_ref().unwrap()).unwrap();
+
+        let response = upload(config).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()["etag"].to_str().unwrap();
+        let signature = response.headers()[SIGNATURE_HEADER].to_str().unwrap();
+        assert!(signature.contains("size=5,alg=hmac-sha256,kid=k1,sig="));
+        assert!(signer.verify(signature, "uploads", "docs/a.txt", etag));
+        assert!(!signer.verify(signature, "uploads", "docs/b.txt", etag));
+        assert!(!signer.verify(signature, "uploads", "docs/a.txt", "\"other\""));
+    }
+
+    #[tokio::test]
+    async fn test_unsigned_without_config() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let response = upload(config(dir.path(), "")).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(SIGNATURE_HEADER).is_none());
+    }
+}
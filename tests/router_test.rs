@@ -4,8 +4,8 @@
 //! These tests define the expected behavior before implementation.
 
 use mizuchi_uploadr::config::{
-    AuthConfig, BucketConfig, Config, MetricsConfig, S3Config, ServerConfig, UploadConfig,
-    ZeroCopyConfig,
+    AuthConfig, BucketConfig, Config, MetricsConfig, S3Config, ServerConfig, TenantConfig,
+    UploadConfig, ZeroCopyConfig,
 };
 use mizuchi_uploadr::router::{BucketResolver, RouterError, S3RequestParser};
 
@@ -157,12 +157,155 @@ fn test_integration_with_parser() {
     assert!(result.is_ok());
 }
 
+/// Test: Nested, multi-segment and wildcard prefixes coexist
+#[test]
+fn test_nested_and_wildcard_prefixes() {
+    let mut config = create_test_config();
+    for (name, prefix) in [
+        ("uploads-images", "/uploads/images"),
+        ("uploads-video", "/uploads/video/"),
+        ("tenant-inbox", "/tenants/*/inbox"),
+    ] {
+        let mut bucket = config.buckets[0].clone();
+        bucket.name = name.to_string();
+        bucket.path_prefix = prefix.to_string();
+        config.buckets.push(bucket);
+    }
+    let resolver = BucketResolver::new(&config);
+
+    let (bucket, key) = resolver
+        .resolve_bucket_and_key("/uploads/images/cat.png")
+        .unwrap();
+    assert_eq!(bucket.name, "uploads-images");
+    assert_eq!(key, "cat.png");
+
+    let (bucket, key) = resolver
+        .resolve_bucket_and_key("/uploads/video/clip.mp4")
+        .unwrap();
+    assert_eq!(bucket.name, "uploads-video");
+    assert_eq!(key, "clip.mp4");
+
+    // Segment boundaries are respected
+    let (bucket, key) = resolver
+        .resolve_bucket_and_key("/uploads/images2/cat.png")
+        .unwrap();
+    assert_eq!(bucket.name, "uploads");
+    assert_eq!(key, "images2/cat.png");
+
+    let (bucket, key) = resolver
+        .resolve_bucket_and_key("/tenants/acme/inbox/q1.csv")
+        .unwrap();
+    assert_eq!(bucket.name, "tenant-inbox");
+    assert_eq!(key, "q1.csv");
+    assert!(resolver
+        .resolve_bucket("/tenants/acme/outbox/q1.csv")
+        .is_err());
+}
+
+/// Test: Buckets restricted to hosts are resolved by Host header
+#[test]
+fn test_resolve_by_host() {
+    let mut config = create_test_config();
+    for (name, hosts) in [
+        ("tenant-a", vec!["tenant-a.upload.example.com"]),
+        ("tenants", vec!["*.upload.example.com"]),
+    ] {
+        let mut bucket = config.buckets[0].clone();
+        bucket.name = name.to_string();
+        bucket.path_prefix = "/".to_string();
+        bucket.hosts = hosts.into_iter().map(String::from).collect();
+        config.buckets.push(bucket);
+    }
+    let resolver = BucketResolver::new(&config);
+
+    let resolve = |host: Option<&str>, path: &str| {
+        let (buckets, key) = resolver
+            .resolve_buckets_and_key_for_host(host, path)
+            .unwrap();
+        (buckets[0].name.clone(), key)
+    };
+
+    // Exact hosts win over wildcards
+    assert_eq!(
+        resolve(Some("tenant-a.upload.example.com:443"), "/uploads/a.txt"),
+        ("tenant-a".to_string(), "uploads/a.txt".to_string())
+    );
+    assert_eq!(
+        resolve(Some("Tenant-B.upload.example.com"), "/a.txt"),
+        ("tenants".to_string(), "a.txt".to_string())
+    );
+
+    // Other hosts fall back to path prefix routing
+    assert_eq!(
+        resolve(Some("upload.example.com"), "/uploads/a.txt"),
+        ("uploads".to_string(), "a.txt".to_string())
+    );
+    assert_eq!(
+        resolve(None, "/uploads/a.txt"),
+        ("uploads".to_string(), "a.txt".to_string())
+    );
+    assert!(resolver
+        .resolve_buckets_and_key_for_host(Some("example.org"), "/a.txt")
+        .is_err());
+}
+
+/// Test: Buckets sharing a prefix are resolved on (prefix, tenant)
+#[test]
+fn test_resolve_bucket_for_tenant() {
+    let mut config = create_test_config();
+    for (name, values) in [
+        ("uploads-acme", vec!["acme"]),
+        ("uploads-globex", vec!["globex"]),
+    ] {
+        let mut bucket = config.buckets[0].clone();
+        bucket.name = name.to_string();
+        bucket.s3.bucket = format!("{}-bucket", name);
+        bucket.tenant = Some(TenantConfig {
+            claim: "tenant_id".to_string(),
+            values: values.into_iter().map(String::from).collect(),
+        });
+        config.buckets.push(bucket);
+    }
+    let resolver = BucketResolver::new(&config);
+
+    let acme = resolver
+        .resolve_bucket_for_tenant("/uploads/file.txt", Some("acme"))
+        .unwrap();
+    assert_eq!(acme.s3.bucket, "uploads-acme-bucket");
+    let globex = resolver
+        .resolve_bucket_for_tenant("/uploads/file.txt", Some("globex"))
+        .unwrap();
+    assert_eq!(globex.s3.bucket, "uploads-globex-bucket");
+
+    // Unknown and missing tenants go to the bucket without tenant routing
+    let other = resolver
+        .resolve_bucket_for_tenant("/uploads/file.txt", Some("initech"))
+        .unwrap();
+    assert_eq!(other.name, "uploads");
+    let anonymous = resolver
+        .resolve_bucket_for_tenant("/uploads/file.txt", None)
+        .unwrap();
+    assert_eq!(anonymous.name, "uploads");
+
+    // Without a default bucket, unknown tenants are not routed
+    config.buckets.remove(0);
+    let resolver = BucketResolver::new(&config);
+    let result = resolver.resolve_bucket_for_tenant("/uploads/file.txt", Some("initech"));
+    assert!(matches!(result, Err(RouterError::BucketNotFound(_))));
+}
+
 // Helper function to create test configuration
 fn create_test_config() -> Config {
     Config {
         server: ServerConfig {
             address: "127.0.0.1:8080".to_string(),
             zero_copy: ZeroCopyConfig::default(),
+            http: Default::default(),
+            tls: None,
+            listeners: Vec::new(),
+            tcp: Default::default(),
+            buffer_pool: Default::default(),
+            shutdown: Default::default(),
         },
         buckets: vec![
             BucketConfig {
@@ -174,9 +317,27 @@ fn create_test_config() -> Config {
                     endpoint: None,
                     access_key: None,
                     secret_key: None,
+                    pool: None,
+                    sigv4a_region_set: None,
+                    hedging: None,
+                    hashing: None,
+                    checksum: None,
+                    debug_log: None,
+                    access_key_file: None,
+                    secret_key_file: None,
                 },
                 auth: AuthConfig::default(),
                 upload: UploadConfig::default(),
+                failover: None,
+                replication: None,
+                storage: Default::default(),
+                authz: None,
+                authz_failure: Default::default(),
+                tenant: None,
+                hosts: Vec::new(),
+                key_prefix: None,
+                regions: None,
+                authz_shadow: None,
             },
             BucketConfig {
                 name: "documents".to_string(),
@@ -187,9 +348,27 @@ fn create_test_config() -> Config {
                     endpoint: None,
                     access_key: None,
                     secret_key: None,
+                    pool: None,
+                    sigv4a_region_set: None,
+                    hedging: None,
+                    hashing: None,
+                    checksum: None,
+                    debug_log: None,
+                    access_key_file: None,
+                    secret_key_file: None,
                 },
                 auth: AuthConfig::default(),
                 upload: UploadConfig::default(),
+                failover: None,
+                replication: None,
+                storage: Default::default(),
+                authz: None,
+                authz_failure: Default::default(),
+                tenant: None,
+                hosts: Vec::new(),
+                key_prefix: None,
+                regions: None,
+                authz_shadow: None,
             },
             BucketConfig {
                 name: "images".to_string(),
@@ -200,12 +379,34 @@ fn create_test_config() -> Config {
                     endpoint: None,
                     access_key: None,
                     secret_key: None,
+                    pool: None,
+                    sigv4a_region_set: None,
+                    hedging: None,
+                    hashing: None,
+                    checksum: None,
+                    debug_log: None,
+                    access_key_file: None,
+                    secret_key_file: None,
                 },
                 auth: AuthConfig::default(),
                 upload: UploadConfig::default(),
+                failover: None,
+                replication: None,
+                storage: Default::default(),
+                authz: None,
+                authz_failure: Default::default(),
+                tenant: None,
+                hosts: Vec::new(),
+                key_prefix: None,
+                regions: None,
+                authz_shadow: None,
             },
         ],
         metrics: MetricsConfig::default(),
         tracing: None,
+        notifications: Default::default(),
+        admin: Default::default(),
+        logging: Default::default(),
+        state: Default::default(),
     }
 }
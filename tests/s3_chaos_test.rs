@@ -0,0 +1,235 @@
+//! S3 Chaos Tests
+//!
+//! Tests for retries and the circuit breaker against a fault-injecting mock
+//! S3 (see `chaos::FaultyS3`).
+//!
+//! ## Test Coverage
+//!
+//! - 500/503 responses, connection resets and slow responses are retried
+//! - Truncated XML is reported as a response error and not retried
+//! - Persistent faults trip the breaker; the fallback takes the traffic
+//! - Uploads succeed under random faults when retries are available
+
+mod chaos;
+
+#[cfg(test)]
+mod tests {
+    use super::chaos::{Fault, FaultRates, FaultyS3};
+    use bytes::Bytes;
+    use mizuchi_uploadr::s3::{
+        CircuitState, FailoverClient, RetryConfig, S3Client, S3ClientConfig, S3ClientError,
+        TimeoutConfig,
+    };
+    use mizuchi_uploadr::upload::backend::StorageBackend;
+    use std::time::Duration;
+
+    fn client(s3: &FaultyS3, max_retries: u32) -> S3Client {
+        client_with_timeout(s3, max_retries, None)
+    }
+
+    fn client_with_timeout(
+        s3: &FaultyS3,
+        max_retries: u32,
+        timeout: Option<TimeoutConfig>,
+    ) -> S3Client {
+        S3Client::new(S3ClientConfig {
+            bucket: "uploads".into(),
+            region: "us-east-1".into(),
+            endpoint: Some(s3.uri()),
+            access_key: Some("test-access".into()),
+            secret_key: Some("test-secret".into()),
+            retry: Some(RetryConfig {
+                max_retries,
+                initial_backoff_ms: 1,
+                max_backoff_ms: 5,
+                backoff_multiplier: 1.0,
+                budget: 0,
+            }),
+            timeout,
+            pool: None,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_server_errors_retried() {
+        let s3 = FaultyS3::with_faults([Fault::Status(500), Fault::Status(503)]).await;
+
+        let object = client(&s3, 3)
+            .put_object("a.txt", Bytes::from("hello"), None)
+            .await
+            .unwrap();
+
+        assert_eq!(object.etag, "\"mock-etag\"");
+        assert_eq!(s3.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_connection_reset_retried() {
+        let s3 = FaultyS3::with_faults([Fault::Reset, Fault::Reset]).await;
+
+        client(&s3, 3)
+            .put_object("a.txt", Bytes::from("hello"), None)
+            .await
+            .unwrap();
+
+        assert_eq!(s3.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_slow_response_times_out_and_retried() {
+        let s3 = FaultyS3::with_faults([Fault::Slow(Duration::from_secs(2))]).await;
+        let timeout = TimeoutConfig {
+            request_timeout_ms: 200,
+            ..Default::default()
+        };
+
+        client_with_timeout(&s3, 3, Some(timeout))
+            .put_object("a.txt", Bytes::from("hello"), None)
+            .await
+            .unwrap();
+
+        assert_eq!(s3.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_retries_exhausted() {
+        let s3 =
+            FaultyS3::with_faults([Fault::Reset, Fault::Status(500), Fault::Status(500)]).await;
+
+        let error = client(&s3, 2)
+            .put_object("a.txt", Bytes::from("hello"), None)
+            .await
+            .unwrap_err();
+
+        assert!(error.is_transient(), "got {error:?}");
+        assert_eq!(s3.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_truncated_xml_not_retried() {
+        let s3 = FaultyS3::with_faults([Fault::TruncatedXml]).await;
+
+        let error = client(&s3, 3)
+            .create_multipart_upload("big.bin")
+            .await
+            .unwrap_err();
+
+        assert!(
+            matches!(error, S3ClientError::ResponseError(_)),
+            "got {error:?}"
+        );
+        assert!(!error.is_transient());
+        assert_eq!(s3.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_persistent_faults_trip_breaker() {
+        let primary = FaultyS3::random(
+            FaultRates {
+                server_error: 0.5,
+                reset: 0.5,
+                ..Default::default()
+            },
+            1,
+        )
+        .await;
+        let fallback = FaultyS3::start().await;
+        let failover = FailoverClient::new(
+            "chaos",
+            client(&primary, 1),
+            client(&fallback, 1),
+            3,
+            Duration::from_secs(60),
+        );
+
+        for _ in 0..3 {
+            failover
+                .put_object("a.txt", Bytes::from("hello"), None)
+                .await
+                .unwrap();
+        }
+        assert_eq!(failover.state(), CircuitState::Open);
+        let primary_requests = primary.requests().len();
+
+        for _ in 0..5 {
+            failover
+                .put_object("a.txt", Bytes::from("hello"), None)
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            primary.requests().len(),
+            primary_requests,
+            "Open breaker skips primary"
+        );
+        assert_eq!(fallback.requests().len(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_breaker_recovers_when_faults_stop() {
+        let primary = FaultyS3::with_faults([Fault::Status(500), Fault::Reset]).await;
+        let fallback = FaultyS3::start().await;
+        let failover = FailoverClient::new(
+            "chaos-recovers",
+            client(&primary, 0),
+            client(&fallback, 0),
+            2,
+            Duration::from_millis(50),
+        );
+
+        for _ in 0..2 {
+            failover
+                .put_object("a.txt", Bytes::from("hello"), None)
+                .await
+                .unwrap();
+        }
+        assert_eq!(failover.state(), CircuitState::Open);
+
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        failover
+            .put_object("a.txt", Bytes::from("hello"), None)
+            .await
+            .unwrap();
+        assert_eq!(failover.state(), CircuitState::Closed);
+        assert_eq!(primary.requests().len(), 3);
+        assert_eq!(fallback.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_uploads_survive_random_faults() {
+        let s3 = FaultyS3::random(
+            FaultRates {
+                server_error: 0.2,
+                slow: 0.05,
+                slow_delay: Duration::from_secs(2),
+                reset: 0.1,
+                ..Default::default()
+            },
+            42,
+        )
+        .await;
+        let timeout = TimeoutConfig {
+            request_timeout_ms: 200,
+            ..Default::default()
+        };
+        let client = client_with_timeout(&s3, 10, Some(timeout));
+
+        for i in 0..20 {
+            client
+                .put_object(&format!("object-{i}"), Bytes::from("hello"), None)
+                .await
+                .unwrap();
+        }
+
+        assert!(
+            s3.requests().len() > 20,
+            "Some requests should have faulted"
+        );
+        s3.script([Fault::Status(503)]);
+        client
+            .put_object("last", Bytes::from("hello"), None)
+            .await
+            .unwrap();
+    }
+}
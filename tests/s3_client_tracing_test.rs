@@ -18,6 +18,7 @@ mod tests {
             secret_key: Some("test-secret".to_string()),
             retry: None,
             timeout: None,
+            pool: None,
         };
 
         let client = S3Client::new(config).unwrap();
@@ -44,6 +45,7 @@ mod tests {
             secret_key: Some("test-secret".to_string()),
             retry: None,
             timeout: None,
+            pool: None,
         };
 
         let client = S3Client::new(config).unwrap();
@@ -76,6 +78,7 @@ mod tests {
             secret_key: Some("test-secret".to_string()),
             retry: None,
             timeout: None,
+            pool: None,
         };
 
         let client = S3Client::new(config).unwrap();
@@ -98,6 +101,7 @@ mod tests {
             secret_key: Some("test-secret".to_string()),
             retry: None,
             timeout: None,
+            pool: None,
         };
 
         let client = S3Client::new(config).unwrap();
@@ -123,6 +127,7 @@ mod tests {
             secret_key: Some("test-secret".to_string()),
             retry: None,
             timeout: None,
+            pool: None,
         };
 
         let client = S3Client::new(config).unwrap();
@@ -148,6 +153,7 @@ mod tests {
             secret_key: Some("test-secret".to_string()),
             retry: None,
             timeout: None,
+            pool: None,
         };
 
         let client = S3Client::new(config).unwrap();
@@ -0,0 +1,151 @@
+//! S3 Error Mapping Integration Tests
+//!
+//! Tests for S3 error responses parsed into `S3ServiceError` and mapped to
+//! proxy responses.
+//!
+//! ## Test Coverage
+//!
+//! - Error documents are parsed into code, message and request ID
+//! - NoSuchBucket and AccessDenied answer 502 without the backend's message
+//! - SlowDown answers 503 with Retry-After
+//! - Error responses are counted by code
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use bytes::Bytes;
+    use mizuchi_uploadr::s3::{RetryConfig, S3Client, S3ClientConfig, S3ClientError, S3ErrorCode};
+    use std::net::SocketAddr;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn error_body(code: &str, message: &str) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <Error><Code>{}</Code><Message>{}</Message>\
+             <RequestId>TX1234</RequestId></Error>",
+            code, message
+        )
+    }
+
+    async fn mock_error(server: &MockServer, status: u16, code: &str, message: &str) {
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(status).set_body_string(error_body(code, message)))
+            .mount(server)
+            .await;
+    }
+
+    /// Proxy in front of `s3`, uploading under `/uploads`
+    async fn start(s3: &MockServer) -> SocketAddr {
+        let bucket = format!(
+            r#"  - name: errors
+    path_prefix: /uploads
+    s3:
+      bucket: uploads
+      region: us-east-1
+      endpoint: "{}"
+      access_key: test-access
+      secret_key: test-secret
+"#,
+            s3.uri()
+        );
+        common::start(common::config(&bucket)).await
+    }
+
+    async fn put(addr: SocketAddr) -> reqwest::Response {
+        common::put(addr, "/uploads/a.txt", &[], "hello").await
+    }
+
+    #[tokio::test]
+    async fn test_error_document_parsed() {
+        let s3 = MockServer::start().await;
+        mock_error(&s3, 403, "AccessDenied", "Access Denied").await;
+        let client = S3Client::new(S3ClientConfig {
+            bucket: "uploads".into(),
+            region: "us-east-1".into(),
+            endpoint: Some(s3.uri()),
+            access_key: Some("test-access".into()),
+            secret_key: Some("test-secret".into()),
+            retry: Some(RetryConfig {
+                max_retries: 0,
+                ..Default::default()
+            }),
+            timeout: None,
+            pool: None,
+        })
+        .unwrap();
+
+        let error = client
+            .put_object("a.txt", Bytes::from("hello"), None)
+            .await
+            .unwrap_err();
+
+        let S3ClientError::ServiceError(error) = error else {
+            panic!("expected a service error, got {error:?}");
+        };
+        assert_eq!(error.status, 403);
+        assert_eq!(error.code, S3ErrorCode::AccessDenied);
+        assert_eq!(error.message, "Access Denied");
+        assert_eq!(error.request_id.as_deref(), Some("TX1234"));
+    }
+
+    #[tokio::test]
+    async fn test_no_such_bucket_is_bad_gateway() {
+        let s3 = MockServer::start().await;
+        mock_error(
+            &s3,
+            404,
+            "NoSuchBucket",
+            "The specified bucket does not exist",
+        )
+        .await;
+        let addr = start(&s3).await;
+
+        let response = put(addr).await;
+
+        assert_eq!(response.status(), 502);
+        let body = response.text().await.unwrap();
+        assert_eq!(body, "Storage bucket not found");
+    }
+
+    #[tokio::test]
+    async fn test_access_denied_is_bad_gateway() {
+        let s3 = MockServer::start().await;
+        mock_error(&s3, 403, "AccessDenied", "Access Denied").await;
+        let addr = start(&s3).await;
+
+        let response = put(addr).await;
+
+        assert_eq!(response.status(), 502);
+        assert_eq!(response.text().await.unwrap(), "Storage access denied");
+    }
+
+    #[tokio::test]
+    async fn test_slow_down_is_service_unavailable() {
+        let s3 = MockServer::start().await;
+        mock_error(&s3, 503, "SlowDown", "Please reduce your request rate.").await;
+        let addr = start(&s3).await;
+
+        let response = put(addr).await;
+
+        assert_eq!(response.status(), 503);
+        assert_eq!(response.headers()["retry-after"], "1");
+    }
+
+    #[tokio::test]
+    async fn test_errors_counted_by_code() {
+        let s3 = MockServer::start().await;
+        mock_error(&s3, 400, "KeyTooLongError", "Your key is too long").await;
+        let addr = start(&s3).await;
+
+        let response = put(addr).await;
+        assert_eq!(response.status(), 502);
+
+        let other = mizuchi_uploadr::metrics::S3_ERRORS_TOTAL
+            .with_label_values(&["uploads", "other"])
+            .get();
+        assert!(other >= 1.0);
+    }
+}
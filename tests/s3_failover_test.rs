@@ -0,0 +1,303 @@
+//! S3 Failover Integration Tests
+//!
+//! Tests for the circuit breaker and fallback endpoint in front of S3.
+//!
+//! ## Test Coverage
+//!
+//! - Transient primary failures are retried on the fallback
+//! - Repeated failures trip the breaker and skip the primary
+//! - The primary is probed and recovers after the probe interval
+//! - Client errors (4xx) do not trip the breaker
+//! - Multipart uploads stay on the endpoint they were created on
+//! - Readiness endpoint reports breaker state
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::s3::{
+        CircuitState, FailoverClient, RetryConfig, S3Client, S3ClientConfig,
+    };
+    use mizuchi_uploadr::server::pingora::PingoraServer;
+    use mizuchi_uploadr::upload::backend::StorageBackend;
+    use mizuchi_uploadr::upload::multipart::CompletedPart;
+    use std::time::Duration;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// S3 client pointing at a mock server, without retries
+    fn client(server: &MockServer) -> S3Client {
+        S3Client::new(S3ClientConfig {
+            bucket: "uploads".into(),
+            region: "us-east-1".into(),
+            endpoint: Some(server.uri()),
+            access_key: Some("test-access".into()),
+            secret_key: Some("test-secret".into()),
+            retry: Some(RetryConfig {
+                max_retries: 0,
+                initial_backoff_ms: 1,
+                max_backoff_ms: 1,
+                backoff_multiplier: 1.0,
+                budget: 0,
+            }),
+            timeout: None,
+            pool: None,
+        })
+        .unwrap()
+    }
+
+    async fn mock_put(server: &MockServer, status: u16, etag: &str) {
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(status).insert_header("ETag", etag))
+            .mount(server)
+            .await;
+    }
+
+    async fn requests(server: &MockServer) -> usize {
+        server.received_requests().await.unwrap().len()
+    }
+
+    #[tokio::test]
+    async fn test_transient_failure_falls_back() {
+        let primary = MockServer::start().await;
+        let fallback = MockServer::start().await;
+        mock_put(&primary, 503, "\"primary\"").await;
+        mock_put(&fallback, 200, "\"fallback\"").await;
+
+        let failover = FailoverClient::new(
+            "transient",
+            client(&primary),
+            client(&fallback),
+            3,
+            Duration::from_secs(60),
+        );
+
+        let object = failover
+            .put_object("a.txt", Bytes::from("hello"), None)
+            .await
+            .unwrap();
+        assert_eq!(object.etag, "\"fallback\"");
+        assert_eq!(failover.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_breaker_opens_and_skips_primary() {
+        let primary = MockServer::start().await;
+        let fallback = MockServer::start().await;
+        mock_put(&primary, 500, "\"primary\"").await;
+        mock_put(&fallback, 200, "\"fallback\"").await;
+
+        let failover = FailoverClient::new(
+            "opens",
+            client(&primary),
+            client(&fallback),
+            2,
+            Duration::from_secs(60),
+        );
+
+        for _ in 0..2 {
+            failover
+                .put_object("a.txt", Bytes::from("hello"), None)
+                .await
+                .unwrap();
+        }
+        assert_eq!(failover.state(), CircuitState::Open);
+        assert_eq!(failover.circuit_state(), Some(CircuitState::Open));
+
+        failover
+            .put_object("a.txt", Bytes::from("hello"), None)
+            .await
+            .unwrap();
+        assert_eq!(requests(&primary).await, 2, "Open breaker skips primary");
+        assert_eq!(requests(&fallback).await, 3);
+    }
+
+    #[tokio::test]
+    async fn test_primary_recovers_after_probe() {
+        let primary = MockServer::start().await;
+        let fallback = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&primary)
+            .await;
+        mock_put(&primary, 200, "\"primary\"").await;
+        mock_put(&fallback, 200, "\"fallback\"").await;
+
+        let failover = FailoverClient::new(
+            "recovers",
+            client(&primary),
+            client(&fallback),
+            1,
+            Duration::from_millis(50),
+        );
+
+        failover
+            .put_object("a.txt", Bytes::from("hello"), None)
+            .await
+            .unwrap();
+        assert_eq!(failover.state(), CircuitState::Open);
+
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert_eq!(failover.state(), CircuitState::HalfOpen);
+
+        let object = failover
+            .put_object("a.txt", Bytes::from("hello"), None)
+            .await
+            .unwrap();
+        assert_eq!(object.etag, "\"primary\"", "Probe should go to the primary");
+        assert_eq!(failover.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_client_error_does_not_fail_over() {
+        let primary = MockServer::start().await;
+        let fallback = MockServer::start().await;
+        mock_put(&primary, 403, "\"primary\"").await;
+        mock_put(&fallback, 200, "\"fallback\"").await;
+
+        let failover = FailoverClient::new(
+            "client-error",
+            client(&primary),
+            client(&fallback),
+            1,
+            Duration::from_secs(60),
+        );
+
+        let result = failover
+            .put_object("a.txt", Bytes::from("hello"), None)
+            .await;
+        assert!(result.is_err());
+        assert_eq!(failover.state(), CircuitState::Closed);
+        assert_eq!(requests(&fallback).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload_sticks_to_fallback() {
+        let primary = MockServer::start().await;
+        let fallback = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&primary)
+            .await;
+        Mock::given(method("POST"))
+            .and(query_param("uploads", ""))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "<InitiateMultipartUploadResult><UploadId>fb-1</UploadId></InitiateMultipartUploadResult>",
+            ))
+            .mount(&fallback)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/uploads/big.bin"))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "\"part1\""))
+            .expect(1)
+            .mount(&fallback)
+            .await;
+        Mock::given(method("POST"))
+            .and(query_param("uploadId", "fb-1"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "<CompleteMultipartUploadResult><ETag>\"done-1\"</ETag></CompleteMultipartUploadResult>",
+            ))
+            .expect(1)
+            .mount(&fallback)
+            .await;
+
+        let failover = FailoverClient::new(
+            "multipart",
+            client(&primary),
+            client(&fallback),
+            5,
+            Duration::from_secs(60),
+        );
+
+        let upload_id = failover.create_multipart_upload("big.bin").await.unwrap();
+        assert_eq!(upload_id, "fb-1");
+
+        let etag = failover
+            .upload_part("big.bin", &upload_id, 1, Bytes::from("part"))
+            .await
+            .unwrap()
+            .etag;
+        failover
+            .complete_multipart_upload("big.bin", &upload_id, &[CompletedPart::new(1, etag)])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            requests(&primary).await,
+            1,
+            "Only the create hit the primary"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_readiness_reports_circuit_state() {
+        let primary = MockServer::start().await;
+        let fallback = MockServer::start().await;
+        mock_put(&primary, 503, "\"primary\"").await;
+        mock_put(&fallback, 200, "\"fallback\"").await;
+
+        let yaml = format!(
+            r#"
+server:
+  address: "127.0.0.1:0"
+buckets:
+  - name: failover
+    path_prefix: /uploads
+    s3:
+      bucket: uploads
+      region: us-east-1
+      endpoint: "{}"
+      access_key: test-access
+      secret_key: test-secret
+    failover:
+      failure_threshold: 1
+      probe_interval_secs: 60
+      target:
+        bucket: uploads
+        region: us-east-1
+        endpoint: "{}"
+        access_key: test-access
+        secret_key: test-secret
+"#,
+            primary.uri(),
+            fallback.uri()
+        );
+        let config: Config = serde_yaml::from_str(&yaml).unwrap();
+        config.validate().unwrap();
+
+        let server = PingoraServer::new(config).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move { server.run().await });
+
+        let http = reqwest::Client::new();
+        let ready: serde_json::Value = http
+            .get(format!("http://{}/ready", addr))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(ready["status"], "ready");
+        assert_eq!(ready["circuits"]["failover"], "closed");
+
+        let response = http
+            .put(format!("http://{}/uploads/a.txt", addr))
+            .body("hello")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        let response = http
+            .get(format!("http://{}/ready", addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200, "Fallback still serves uploads");
+        let ready: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(ready["status"], "degraded");
+        assert_eq!(ready["circuits"]["failover"], "open");
+    }
+}
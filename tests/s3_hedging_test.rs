@@ -0,0 +1,93 @@
+//! S3 Request Hedging Integration Tests
+//!
+//! Tests for hedged PutObjects in `S3Client`.
+//!
+//! ## Test Coverage
+//!
+//! - A slow PutObject of a small object is hedged and the faster attempt wins
+//! - Objects over `max_object_size` are not hedged
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use mizuchi_uploadr::config::S3HedgingConfig;
+    use mizuchi_uploadr::s3::{RetryConfig, S3Client, S3ClientConfig};
+    use std::time::{Duration, Instant};
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// S3 client pointing at a mock server, hedging after 100ms
+    fn client(server: &MockServer) -> S3Client {
+        S3Client::new(S3ClientConfig {
+            bucket: "uploads".into(),
+            region: "us-east-1".into(),
+            endpoint: Some(server.uri()),
+            access_key: Some("test-access".into()),
+            secret_key: Some("test-secret".into()),
+            retry: Some(RetryConfig {
+                max_retries: 0,
+                initial_backoff_ms: 1,
+                max_backoff_ms: 1,
+                backoff_multiplier: 1.0,
+                budget: 0,
+            }),
+            timeout: None,
+            pool: None,
+        })
+        .unwrap()
+        .with_hedging(S3HedgingConfig {
+            max_object_size: 16,
+            percentile: 99,
+            min_delay_ms: 100,
+            max_delay_ms: 100,
+        })
+    }
+
+    /// First PutObject answers after `delay`, later ones at once
+    async fn mock_slow_then_fast(server: &MockServer, delay: Duration) {
+        Mock::given(method("PUT"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"slow\"")
+                    .set_delay(delay),
+            )
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(server)
+            .await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "\"fast\""))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_slow_put_is_hedged() {
+        let server = MockServer::start().await;
+        mock_slow_then_fast(&server, Duration::from_secs(10)).await;
+
+        let started = Instant::now();
+        let object = client(&server)
+            .put_object("a.txt", Bytes::from("hello"), None)
+            .await
+            .unwrap();
+
+        assert_eq!(object.etag, "\"fast\"");
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_large_put_not_hedged() {
+        let server = MockServer::start().await;
+        mock_slow_then_fast(&server, Duration::from_millis(300)).await;
+
+        let object = client(&server)
+            .put_object("a.bin", Bytes::from(vec![0u8; 17]), None)
+            .await
+            .unwrap();
+
+        assert_eq!(object.etag, "\"slow\"");
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,200 @@
+//! S3 Retry Integration Tests
+//!
+//! Tests for the retry behavior of `S3Client`.
+//!
+//! ## Test Coverage
+//!
+//! - Multipart operations are retried like PutObject
+//! - `Retry-After` is honored
+//! - A spent retry budget stops retries
+//! - Request timeouts are reported apart from other network errors
+//! - Per-operation timeouts and the client's deadline bound requests
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use mizuchi_uploadr::s3::{
+        deadline, RetryConfig, S3Client, S3ClientConfig, S3ClientError, TimeoutConfig,
+    };
+    use std::time::{Duration, Instant};
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn client(server: &MockServer, max_retries: u32, budget: u32) -> S3Client {
+        S3Client::new(S3ClientConfig {
+            bucket: "uploads".into(),
+            region: "us-east-1".into(),
+            endpoint: Some(server.uri()),
+            access_key: Some("test-access".into()),
+            secret_key: Some("test-secret".into()),
+            retry: Some(RetryConfig {
+                max_retries,
+                initial_backoff_ms: 1,
+                max_backoff_ms: 2_000,
+                backoff_multiplier: 1.0,
+                budget,
+            }),
+            timeout: None,
+            pool: None,
+        })
+        .unwrap()
+    }
+
+    async fn requests(server: &MockServer) -> usize {
+        server.received_requests().await.unwrap().len()
+    }
+
+    #[tokio::test]
+    async fn test_upload_part_retried_after_retry_after() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(503).insert_header("Retry-After", "1"))
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "\"part1\""))
+            .mount(&server)
+            .await;
+
+        let started = Instant::now();
+        let part = client(&server, 3, 500)
+            .upload_part("big.bin", "upload-1", 1, Bytes::from("hello"))
+            .await
+            .unwrap();
+
+        assert_eq!(part.etag, "\"part1\"");
+        assert_eq!(requests(&server).await, 2);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_retry_budget_stops_retries() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        // Enough for one retry
+        let client = client(&server, 3, 5);
+
+        let result = client.put_object("a.txt", Bytes::from("hello"), None).await;
+        assert!(result.is_err());
+        assert_eq!(requests(&server).await, 2);
+
+        let result = client.put_object("a.txt", Bytes::from("hello"), None).await;
+        assert!(result.is_err());
+        assert_eq!(requests(&server).await, 3, "Spent budget should not retry");
+    }
+
+    fn slow_client(server: &MockServer, timeout: TimeoutConfig) -> S3Client {
+        S3Client::new(S3ClientConfig {
+            bucket: "uploads".into(),
+            region: "us-east-1".into(),
+            endpoint: Some(server.uri()),
+            access_key: Some("test-access".into()),
+            secret_key: Some("test-secret".into()),
+            retry: Some(RetryConfig {
+                max_retries: 2,
+                initial_backoff_ms: 1,
+                max_backoff_ms: 1,
+                backoff_multiplier: 1.0,
+                budget: 0,
+            }),
+            timeout: Some(timeout),
+            pool: None,
+        })
+        .unwrap()
+    }
+
+    async fn mock_slow_put(server: &MockServer) {
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_per_operation_timeout() {
+        let server = MockServer::start().await;
+        mock_slow_put(&server).await;
+        let client = slow_client(
+            &server,
+            TimeoutConfig {
+                upload_part_timeout_ms: Some(100),
+                ..TimeoutConfig::default()
+            },
+        );
+
+        let started = Instant::now();
+        let result = client
+            .upload_part("big.bin", "upload-1", 1, Bytes::from("hello"))
+            .await;
+        assert!(matches!(result, Err(S3ClientError::Timeout(_))));
+        assert!(started.elapsed() < Duration::from_secs(3));
+    }
+
+    #[tokio::test]
+    async fn test_deadline_stops_requests() {
+        let server = MockServer::start().await;
+        mock_slow_put(&server).await;
+        let client = slow_client(&server, TimeoutConfig::default());
+
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(200);
+        let started = Instant::now();
+        let result = deadline::scope(
+            Some(deadline),
+            client.put_object("a.txt", Bytes::from("hello"), None),
+        )
+        .await;
+        assert!(matches!(result, Err(S3ClientError::DeadlineExceeded)));
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert_eq!(requests(&server).await, 1, "No retry after the deadline");
+
+        // A deadline already passed sends nothing
+        let result = deadline::scope(
+            Some(tokio::time::Instant::now()),
+            client.put_object("b.txt", Bytes::from("hello"), None),
+        )
+        .await;
+        assert!(matches!(result, Err(S3ClientError::DeadlineExceeded)));
+        assert_eq!(requests(&server).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+
+        let client = S3Client::new(S3ClientConfig {
+            bucket: "uploads".into(),
+            region: "us-east-1".into(),
+            endpoint: Some(server.uri()),
+            access_key: Some("test-access".into()),
+            secret_key: Some("test-secret".into()),
+            retry: Some(RetryConfig {
+                max_retries: 0,
+                ..RetryConfig::default()
+            }),
+            timeout: Some(TimeoutConfig {
+                connect_timeout_ms: 1_000,
+                request_timeout_ms: 100,
+                ..TimeoutConfig::default()
+            }),
+            pool: None,
+        })
+        .unwrap();
+
+        let result = client.put_object("a.txt", Bytes::from("hello"), None).await;
+        assert!(
+            matches!(result, Err(S3ClientError::Timeout(_))),
+            "{:?}",
+            result
+        );
+    }
+}
@@ -2,6 +2,7 @@
 //!
 //! Tests for W3C Trace Context propagation in S3 client HTTP requests.
 //! Verifies that traceparent and tracestate headers are injected into S3 requests.
+//! With the `tracing` feature, the injected context is the current span's.
 
 #[cfg(test)]
 mod tests {
@@ -20,6 +21,7 @@ mod tests {
             secret_key: Some("wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY".to_string()),
             retry: None,
             timeout: None,
+            pool: None,
         }
     }
 
@@ -145,10 +147,10 @@ mod tests {
         let config = create_test_config(mock_server.uri());
         let client = S3Client::new(config).unwrap();
 
-        let parts = vec![mizuchi_uploadr::s3::S3CompletedPart {
-            part_number: 1,
-            etag: "\"part-etag-1\"".to_string(),
-        }];
+        let parts = vec![mizuchi_uploadr::s3::S3CompletedPart::new(
+            1,
+            "\"part-etag-1\"",
+        )];
 
         let response = client
             .complete_multipart_upload("test-key", "test-upload-id", parts)
@@ -157,4 +159,52 @@ mod tests {
 
         assert_eq!(response.etag, "\"final-etag-123\"");
     }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test]
+    async fn test_put_object_propagates_current_span_context() {
+        use opentelemetry::trace::TracerProvider as _;
+        use tracing::Instrument;
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/test-key"))
+            .and(header_regex(
+                "traceparent",
+                r"^00-0af7651916cd43dd8448eb211c80319c-[0-9a-f]{16}-01$",
+            ))
+            .and(header_regex("tracestate", "^congo=t61rcWkgMzE$"))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "\"abc123\""))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = opentelemetry_sdk::trace::TracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        // Inbound request from a traced client
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"
+                .parse()
+                .unwrap(),
+        );
+        headers.insert("tracestate", "congo=t61rcWkgMzE".parse().unwrap());
+        let span = tracing::info_span!("http.request");
+        span.set_parent(mizuchi_uploadr::tracing::propagation::extract_context(
+            &headers,
+        ));
+
+        let client = S3Client::new(create_test_config(mock_server.uri())).unwrap();
+        client
+            .put_object("test-key", Bytes::from("test data"), Some("text/plain"))
+            .instrument(span)
+            .await
+            .unwrap();
+    }
 }
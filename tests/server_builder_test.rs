@@ -0,0 +1,175 @@
+//! Server Builder Integration Tests
+//!
+//! Tests for `UploadService::builder()` (the builder behind
+//! `Server::builder()`) with components supplied in code instead of YAML.
+//!
+//! ## Test Coverage
+//!
+//! - A supplied storage backend receives the uploads
+//! - A supplied authenticator is enforced and enables authentication
+//! - A supplied authorizer decides on authenticated uploads
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use http_body_util::Full;
+    use hyper::{Request, StatusCode};
+    use mizuchi_uploadr::auth::{AuthError, AuthRequest, AuthResult, Authenticator};
+    use mizuchi_uploadr::authz::{Authorizer, AuthzError, AuthzRequest};
+    use mizuchi_uploadr::config::{BucketConfig, S3Config};
+    use mizuchi_uploadr::upload::backend::{StorageBackend, StoredObject};
+    use mizuchi_uploadr::upload::multipart::CompletedPart;
+    use mizuchi_uploadr::upload::UploadError;
+    use mizuchi_uploadr::UploadService;
+    use parking_lot::Mutex;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tower_service::Service;
+
+    /// Backend keeping single-request uploads in memory
+    #[derive(Default)]
+    struct MemoryBackend {
+        objects: Mutex<HashMap<String, Bytes>>,
+    }
+
+    #[async_trait]
+    impl StorageBackend for MemoryBackend {
+        fn bucket(&self) -> &str {
+            "memory"
+        }
+
+        async fn put_object(
+            &self,
+            key: &str,
+            body: Bytes,
+            _content_type: Option<&str>,
+        ) -> Result<StoredObject, UploadError> {
+            self.objects.lock().insert(key.to_string(), body);
+            Ok(StoredObject::new("\"memory\""))
+        }
+
+        async fn create_multipart_upload(&self, _key: &str) -> Result<String, UploadError> {
+            Ok("memory-upload".to_string())
+        }
+
+        async fn upload_part(
+            &self,
+            _key: &str,
+            _upload_id: &str,
+            _part_number: u32,
+            _body: Bytes,
+        ) -> Result<StoredObject, UploadError> {
+            Ok(StoredObject::new("\"part\""))
+        }
+
+        async fn complete_multipart_upload(
+            &self,
+            _key: &str,
+            _upload_id: &str,
+            _parts: &[CompletedPart],
+        ) -> Result<StoredObject, UploadError> {
+            Ok(StoredObject::new("\"memory\""))
+        }
+
+        async fn abort_multipart_upload(
+            &self,
+            _key: &str,
+            _upload_id: &str,
+        ) -> Result<(), UploadError> {
+            Ok(())
+        }
+    }
+
+    /// Accepts `X-Team-Token: <team>` as the subject `<team>`
+    struct TeamTokenAuthenticator;
+
+    #[async_trait]
+    impl Authenticator for TeamTokenAuthenticator {
+        async fn authenticate(&self, request: &AuthRequest) -> Result<AuthResult, AuthError> {
+            let team = request
+                .headers
+                .get("x-team-token")
+                .ok_or(AuthError::MissingAuth)?;
+            Ok(AuthResult {
+                subject: team.clone(),
+                claims: HashMap::new(),
+            })
+        }
+    }
+
+    /// Lets only the `storage` team upload
+    struct StorageTeamOnly;
+
+    #[async_trait]
+    impl Authorizer for StorageTeamOnly {
+        async fn authorize(&self, request: &AuthzRequest) -> Result<bool, AuthzError> {
+            Ok(request.subject == "storage")
+        }
+    }
+
+    fn bucket() -> BucketConfig {
+        BucketConfig::new("uploads", "/uploads", S3Config::new("uploads", "us-east-1"))
+    }
+
+    fn put(path: &str, team: Option<&str>) -> Request<Full<Bytes>> {
+        let mut request = Request::put(path);
+        if let Some(team) = team {
+            request = request.header("X-Team-Token", team);
+        }
+        request.body(Full::new(Bytes::from("hello"))).unwrap()
+    }
+
+    async fn service(backend: Arc<MemoryBackend>) -> UploadService {
+        UploadService::builder()
+            .add_bucket(bucket())
+            .with_storage_backend("uploads", backend)
+            .with_authenticator("uploads", Arc::new(TeamTokenAuthenticator))
+            .with_authorizer("uploads", Arc::new(StorageTeamOnly))
+            .build_service()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_supplied_backend_stores_uploads() {
+        let backend = Arc::new(MemoryBackend::default());
+        let mut service = service(Arc::clone(&backend)).await;
+
+        let response = service
+            .call(put("/uploads/a.txt", Some("storage")))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            backend.objects.lock().get("a.txt"),
+            Some(&Bytes::from("hello"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_supplied_authenticator_enforced() {
+        let backend = Arc::new(MemoryBackend::default());
+        let mut service = service(Arc::clone(&backend)).await;
+
+        let response = service.call(put("/uploads/a.txt", None)).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(backend.objects.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_supplied_authorizer_denies() {
+        let backend = Arc::new(MemoryBackend::default());
+        let mut service = service(Arc::clone(&backend)).await;
+
+        let response = service
+            .call(put("/uploads/a.txt", Some("marketing")))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(backend.objects.lock().is_empty());
+    }
+}
@@ -0,0 +1,193 @@
+//! Shutdown Report Integration Tests
+//!
+//! Tests for graceful drain and the shutdown report.
+//!
+//! ## Test Coverage
+//!
+//! - Uploads in flight at shutdown are allowed to finish and are reported
+//! - Uploads still running at the drain deadline are reported as aborted
+//! - The report is delivered to the configured webhook
+
+#[cfg(test)]
+mod tests {
+    use mizuchi_uploadr::config::{
+        BucketConfig, Config, MetricsConfig, S3Config, ServerConfig, ShutdownConfig, StorageConfig,
+        WebhookConfig, ZeroCopyConfig,
+    };
+    use mizuchi_uploadr::server::pingora::PingoraServer;
+    use mizuchi_uploadr::server::shutdown::ShutdownReport;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio::sync::oneshot;
+    use wiremock::matchers::{header, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn test_config(root: &std::path::Path, shutdown: ShutdownConfig) -> Config {
+        Config {
+            server: ServerConfig {
+                address: "127.0.0.1:0".into(),
+                zero_copy: ZeroCopyConfig::default(),
+                http: Default::default(),
+                tls: None,
+                listeners: Vec::new(),
+                tcp: Default::default(),
+                buffer_pool: Default::default(),
+                shutdown,
+            },
+            buckets: vec![BucketConfig {
+                name: "test".into(),
+                path_prefix: "/uploads".into(),
+                s3: S3Config {
+                    bucket: "test-bucket".into(),
+                    region: "us-east-1".into(),
+                    endpoint: None,
+                    access_key: None,
+                    secret_key: None,
+                    pool: None,
+                    sigv4a_region_set: None,
+                    hedging: None,
+                    hashing: None,
+                    checksum: None,
+                    debug_log: None,
+                    access_key_file: None,
+                    secret_key_file: None,
+                },
+                auth: Default::default(),
+                upload: Default::default(),
+                failover: None,
+                replication: None,
+                storage: StorageConfig::Local {
+                    root: root.display().to_string(),
+                },
+                authz: None,
+                authz_failure: Default::default(),
+                tenant: None,
+                hosts: Vec::new(),
+                key_prefix: None,
+                regions: None,
+                authz_shadow: None,
+            }],
+            metrics: MetricsConfig::default(),
+            tracing: None,
+            notifications: Default::default(),
+            admin: Default::default(),
+            logging: Default::default(),
+            state: Default::default(),
+        }
+    }
+
+    /// Start a server and return its address, shutdown trigger and report handle
+    async fn start(
+        config: Config,
+    ) -> (
+        SocketAddr,
+        oneshot::Sender<()>,
+        tokio::task::JoinHandle<ShutdownReport>,
+    ) {
+        let server = PingoraServer::new(config).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let (tx, rx) = oneshot::channel::<()>();
+        let handle = tokio::spawn(async move {
+            server
+                .run_until(async {
+                    let _ = rx.await;
+                })
+                .await
+                .unwrap()
+        });
+        (addr, tx, handle)
+    }
+
+    /// Send the headers and first half of a 10-byte PUT, leaving it in flight
+    async fn begin_partial_upload(addr: SocketAddr) -> TcpStream {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(
+                b"PUT /uploads/drain.txt HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\n\r\nhello",
+            )
+            .await
+            .unwrap();
+        // Give the server time to start handling the request
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        stream
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_upload_completes_during_drain() {
+        let dir = tempfile::tempdir().unwrap();
+        let (addr, shutdown, handle) = start(test_config(dir.path(), Default::default())).await;
+
+        let mut stream = begin_partial_upload(addr).await;
+        shutdown.send(()).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        stream.write_all(b"world").await.unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        assert!(String::from_utf8_lossy(&response).starts_with("HTTP/1.1 200"));
+
+        let report = handle.await.unwrap();
+        assert_eq!(report.uploads_completed, 1);
+        assert_eq!(report.uploads_aborted, 0);
+        assert_eq!(report.bytes_flushed, 10);
+        assert!(!report.drain_timed_out);
+        assert_eq!(
+            std::fs::read(dir.path().join("drain.txt")).unwrap(),
+            b"helloworld"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stalled_upload_reported_as_aborted() {
+        let dir = tempfile::tempdir().unwrap();
+        let shutdown_config = ShutdownConfig {
+            drain_timeout_secs: 1,
+            report_webhook: None,
+        };
+        let (addr, shutdown, handle) = start(test_config(dir.path(), shutdown_config)).await;
+
+        let _stream = begin_partial_upload(addr).await;
+        shutdown.send(()).unwrap();
+
+        let report = handle.await.unwrap();
+        assert_eq!(report.uploads_completed, 0);
+        assert_eq!(report.uploads_aborted, 1);
+        assert!(report.drain_timed_out);
+    }
+
+    #[tokio::test]
+    async fn test_report_sent_to_webhook() {
+        let webhook_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header("X-Mizuchi-Event", "server.shutdown"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&webhook_server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let shutdown_config = ShutdownConfig {
+            drain_timeout_secs: 5,
+            report_webhook: Some(WebhookConfig {
+                url: webhook_server.uri(),
+                secret: None,
+                secret_file: None,
+                max_retries: 0,
+                initial_backoff_ms: 10,
+                timeout_ms: 1000,
+            }),
+        };
+        let (_addr, shutdown, handle) = start(test_config(dir.path(), shutdown_config)).await;
+
+        shutdown.send(()).unwrap();
+        let report = handle.await.unwrap();
+
+        let requests = webhook_server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        let delivered: ShutdownReport = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(delivered, report);
+        assert_eq!(delivered.event, "server.shutdown");
+    }
+}
@@ -54,6 +54,7 @@ mod tests {
             query: None,
             method: method.to_string(),
             path: path.to_string(),
+            peer_certificates: Vec::new(),
         }
     }
 
@@ -420,6 +421,7 @@ mod tests {
             query: None,
             method: "PUT".to_string(),
             path: "/bucket/key".to_string(),
+            peer_certificates: Vec::new(),
         };
 
         let result = auth.authenticate(&request).await;
@@ -489,6 +491,7 @@ mod tests {
             query: None,
             method: "PUT".to_string(),
             path: "/bucket/key".to_string(),
+            peer_certificates: Vec::new(),
         };
 
         let result = auth.authenticate(&request).await;
@@ -564,4 +567,469 @@ mod tests {
             result
         );
     }
+
+    // ========================================================================
+    // TEST: Presigned URLs (query string authentication)
+    // ========================================================================
+
+    /// Presign a request with the AWS SDK signer, returning the request as
+    /// the server would see it
+    fn presigned_request(
+        method: &str,
+        path: &str,
+        signed_at: std::time::SystemTime,
+        expires_in: std::time::Duration,
+    ) -> AuthRequest {
+        use aws_sigv4::http_request::{
+            sign, PercentEncodingMode, SignableBody, SignableRequest, SignatureLocation,
+            SigningParams, SigningSettings, UriPathNormalizationMode,
+        };
+        use aws_sigv4::sign::v4;
+        use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+        let host = "s3.us-east-1.amazonaws.com";
+        let credentials = aws_credential_types::Credentials::new(
+            TEST_ACCESS_KEY,
+            TEST_SECRET_KEY,
+            None,
+            None,
+            "test",
+        );
+        let identity = aws_smithy_runtime_api::client::identity::Identity::new(credentials, None);
+        let mut settings = SigningSettings::default();
+        settings.signature_location = SignatureLocation::QueryParams;
+        settings.expires_in = Some(expires_in);
+        settings.percent_encoding_mode = PercentEncodingMode::Single;
+        settings.uri_path_normalization_mode = UriPathNormalizationMode::Disabled;
+        let params = v4::SigningParams::builder()
+            .identity(&identity)
+            .region(TEST_REGION)
+            .name(TEST_SERVICE)
+            .time(signed_at)
+            .settings(settings)
+            .build()
+            .unwrap();
+        let url = format!("https://{}{}", host, path);
+        let signable = SignableRequest::new(
+            method,
+            &url,
+            std::iter::once(("host", host)),
+            SignableBody::UnsignedPayload,
+        )
+        .unwrap();
+        let (instructions, _) = sign(signable, &SigningParams::V4(params))
+            .unwrap()
+            .into_parts();
+
+        const ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+            .remove(b'-')
+            .remove(b'_')
+            .remove(b'.')
+            .remove(b'~');
+        let query = instructions
+            .params()
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, utf8_percent_encode(value, ENCODE_SET)))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        AuthRequest {
+            headers: HashMap::from([("host".to_string(), host.to_string())]),
+            query: Some(query),
+            method: method.to_string(),
+            path: path.to_string(),
+            peer_certificates: Vec::new(),
+        }
+    }
+
+    fn presigned_authenticator() -> SigV4Authenticator {
+        let mut auth = SigV4Authenticator::new(TEST_SERVICE, TEST_REGION);
+        auth.add_credentials(TEST_ACCESS_KEY, TEST_SECRET_KEY);
+        auth
+    }
+
+    /// Test that a presigned URL from the AWS signer is accepted
+    #[tokio::test]
+    async fn test_presigned_url_accepted() {
+        let request = presigned_request(
+            "PUT",
+            "/bucket/photo.jpg",
+            std::time::SystemTime::now(),
+            std::time::Duration::from_secs(300),
+        );
+
+        let result = presigned_authenticator().authenticate(&request).await;
+        assert_eq!(result.unwrap().subject, TEST_ACCESS_KEY);
+    }
+
+    /// Test that a presigned URL stops working once it expires
+    #[tokio::test]
+    async fn test_expired_presigned_url_rejected() {
+        let request = presigned_request(
+            "PUT",
+            "/bucket/photo.jpg",
+            std::time::SystemTime::now() - std::time::Duration::from_secs(3600),
+            std::time::Duration::from_secs(300),
+        );
+
+        let result = presigned_authenticator().authenticate(&request).await;
+        assert!(
+            matches!(&result, Err(AuthError::InvalidToken(msg)) if msg.contains("expired")),
+            "Expired presigned URL should be rejected: {:?}",
+            result
+        );
+    }
+
+    /// Test that a long-lived presigned URL is honored beyond the clock skew window
+    #[tokio::test]
+    async fn test_long_lived_presigned_url_accepted() {
+        let request = presigned_request(
+            "PUT",
+            "/bucket/photo.jpg",
+            std::time::SystemTime::now() - std::time::Duration::from_secs(3600),
+            std::time::Duration::from_secs(24 * 3600),
+        );
+
+        assert!(presigned_authenticator()
+            .authenticate(&request)
+            .await
+            .is_ok());
+    }
+
+    /// Test that the presigned signature covers the path and query
+    #[tokio::test]
+    async fn test_presigned_url_tampering_rejected() {
+        let auth = presigned_authenticator();
+        let now = std::time::SystemTime::now();
+        let expires_in = std::time::Duration::from_secs(300);
+
+        let mut request = presigned_request("PUT", "/bucket/photo.jpg", now, expires_in);
+        request.path = "/bucket/other.jpg".to_string();
+        let result = auth.authenticate(&request).await;
+        assert!(matches!(result, Err(AuthError::InvalidSignature)));
+
+        // Extending the expiry invalidates the signature
+        let mut request = presigned_request("PUT", "/bucket/photo.jpg", now, expires_in);
+        request.query = request
+            .query
+            .map(|q| q.replace("X-Amz-Expires=300", "X-Amz-Expires=86400"));
+        let result = auth.authenticate(&request).await;
+        assert!(matches!(result, Err(AuthError::InvalidSignature)));
+    }
+
+    // ========================================================================
+    // TEST: Replay Protection
+    // ========================================================================
+
+    /// Serve `SET key value NX EX ttl` over RESP2, remembering keys forever
+    async fn fake_redis() -> std::net::SocketAddr {
+        use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let keys = std::sync::Arc::new(std::sync::Mutex::new(std::collections::HashSet::new()));
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let keys = std::sync::Arc::clone(&keys);
+                tokio::spawn(async move {
+                    let mut stream = BufStream::new(socket);
+                    loop {
+                        let mut line = String::new();
+                        if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+                            return;
+                        }
+                        let argc: usize = line.trim()[1..].parse().unwrap();
+                        let mut args = Vec::new();
+                        for _ in 0..argc {
+                            line.clear();
+                            stream.read_line(&mut line).await.unwrap();
+                            let len: usize = line.trim()[1..].parse().unwrap();
+                            let mut arg = vec![0; len + 2];
+                            stream.read_exact(&mut arg).await.unwrap();
+                            arg.truncate(len);
+                            args.push(String::from_utf8(arg).unwrap());
+                        }
+                        assert_eq!(args[0], "SET");
+                        assert_eq!(args[3], "NX");
+                        let reply = if keys.lock().unwrap().insert(args[1].clone()) {
+                            "+OK\r\n"
+                        } else {
+                            "$-1\r\n"
+                        };
+                        stream.write_all(reply.as_bytes()).await.unwrap();
+                        stream.flush().await.unwrap();
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    /// Test that a header-signed request is accepted only once
+    #[tokio::test]
+    async fn test_replayed_request_rejected() {
+        use mizuchi_uploadr::auth::sigv4_replay::MemoryReplayGuard;
+
+        let mut auth = SigV4Authenticator::new(TEST_SERVICE, TEST_REGION)
+            .with_replay_guard(std::sync::Arc::new(MemoryReplayGuard::new(1000)));
+        auth.add_credentials(TEST_ACCESS_KEY, TEST_SECRET_KEY);
+
+        let request = create_valid_signed_request("PUT", "/bucket/key", b"test data");
+        assert!(auth.authenticate(&request).await.is_ok());
+
+        let result = auth.authenticate(&request).await;
+        assert!(
+            matches!(&result, Err(AuthError::InvalidToken(msg)) if msg.contains("Replayed")),
+            "Replayed request should be rejected: {:?}",
+            result
+        );
+
+        // A differently signed request from the same client is still fine
+        let request = create_valid_signed_request("PUT", "/bucket/other", b"test data");
+        assert!(auth.authenticate(&request).await.is_ok());
+    }
+
+    /// Test that instances sharing a Redis replay cache reject each other's replays
+    #[tokio::test]
+    async fn test_replay_rejected_across_instances_with_redis() {
+        use mizuchi_uploadr::auth::sigv4_replay::StoreReplayGuard;
+        use mizuchi_uploadr::redis::RedisClient;
+        use mizuchi_uploadr::state::RedisStore;
+
+        let addr = fake_redis().await;
+        let instance = || {
+            let client = RedisClient::from_url(&format!("redis://{}", addr)).unwrap();
+            let store = std::sync::Arc::new(RedisStore::new(client, "mizuchi:"));
+            let mut auth = SigV4Authenticator::new(TEST_SERVICE, TEST_REGION).with_replay_guard(
+                std::sync::Arc::new(StoreReplayGuard::new(store, "sigv4:seen:")),
+            );
+            auth.add_credentials(TEST_ACCESS_KEY, TEST_SECRET_KEY);
+            auth
+        };
+
+        let request = create_valid_signed_request("PUT", "/bucket/key", b"test data");
+        assert!(instance().authenticate(&request).await.is_ok());
+        assert!(matches!(
+            instance().authenticate(&request).await,
+            Err(AuthError::InvalidToken(_))
+        ));
+    }
+
+    /// Test that presigned URLs stay reusable until they expire
+    #[tokio::test]
+    async fn test_presigned_url_reusable_with_replay_protection() {
+        use mizuchi_uploadr::auth::sigv4_replay::MemoryReplayGuard;
+
+        let auth = presigned_authenticator()
+            .with_replay_guard(std::sync::Arc::new(MemoryReplayGuard::new(1000)));
+        let request = presigned_request(
+            "PUT",
+            "/bucket/photo.jpg",
+            std::time::SystemTime::now(),
+            std::time::Duration::from_secs(300),
+        );
+
+        assert!(auth.authenticate(&request).await.is_ok());
+        assert!(auth.authenticate(&request).await.is_ok());
+    }
+
+    // ========================================================================
+    // TEST: SigV4A (multi-region ECDSA signatures)
+    // ========================================================================
+
+    /// Sign a request with SigV4A for `region_set`, in headers or the query
+    fn sigv4a_request(
+        method: &str,
+        path: &str,
+        region_set: &str,
+        location: aws_sigv4::http_request::SignatureLocation,
+    ) -> AuthRequest {
+        use aws_sigv4::http_request::{
+            sign, PayloadChecksumKind, PercentEncodingMode, SignableBody, SignableRequest,
+            SigningParams, SigningSettings, UriPathNormalizationMode,
+        };
+        use aws_sigv4::sign::v4a;
+        use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+        let host = "s3.us-east-1.amazonaws.com";
+        let credentials = aws_credential_types::Credentials::new(
+            TEST_ACCESS_KEY,
+            TEST_SECRET_KEY,
+            None,
+            None,
+            "test",
+        );
+        let identity = aws_smithy_runtime_api::client::identity::Identity::new(credentials, None);
+        let mut settings = SigningSettings::default();
+        settings.signature_location = location;
+        settings.expires_in = Some(std::time::Duration::from_secs(300));
+        settings.payload_checksum_kind = PayloadChecksumKind::XAmzSha256;
+        settings.percent_encoding_mode = PercentEncodingMode::Single;
+        settings.uri_path_normalization_mode = UriPathNormalizationMode::Disabled;
+        let params = v4a::SigningParams::builder()
+            .identity(&identity)
+            .region_set(region_set)
+            .name(TEST_SERVICE)
+            .time(std::time::SystemTime::now())
+            .settings(settings)
+            .build()
+            .unwrap();
+        let url = format!("https://{}{}", host, path);
+        let signable = SignableRequest::new(
+            method,
+            &url,
+            std::iter::once(("host", host)),
+            SignableBody::UnsignedPayload,
+        )
+        .unwrap();
+        let (instructions, _) = sign(signable, &SigningParams::V4a(params))
+            .unwrap()
+            .into_parts();
+
+        let mut headers = HashMap::from([("host".to_string(), host.to_string())]);
+        for (name, value) in instructions.headers() {
+            headers.insert(name.to_lowercase(), value.to_string());
+        }
+        const ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+            .remove(b'-')
+            .remove(b'_')
+            .remove(b'.')
+            .remove(b'~');
+        let query = instructions
+            .params()
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, utf8_percent_encode(value, ENCODE_SET)))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        AuthRequest {
+            headers,
+            query: (!query.is_empty()).then_some(query),
+            method: method.to_string(),
+            path: path.to_string(),
+            peer_certificates: Vec::new(),
+        }
+    }
+
+    /// Test that SigV4A header signatures are verified
+    #[tokio::test]
+    async fn test_sigv4a_signed_request_accepted() {
+        use aws_sigv4::http_request::SignatureLocation;
+
+        let auth = presigned_authenticator();
+        for region_set in ["us-east-1", "us-*", "*", "eu-west-1,us-east-1"] {
+            let request =
+                sigv4a_request("PUT", "/bucket/key", region_set, SignatureLocation::Headers);
+            assert!(
+                request.headers["authorization"].starts_with("AWS4-ECDSA-P256-SHA256 "),
+                "Request should be SigV4A signed"
+            );
+            let result = auth.authenticate(&request).await;
+            assert!(
+                result.is_ok(),
+                "SigV4A request for {} should be accepted: {:?}",
+                region_set,
+                result
+            );
+            assert_eq!(result.unwrap().subject, TEST_ACCESS_KEY);
+        }
+    }
+
+    /// Test that SigV4A presigned URLs are verified
+    #[tokio::test]
+    async fn test_sigv4a_presigned_url_accepted() {
+        use aws_sigv4::http_request::SignatureLocation;
+
+        let auth = presigned_authenticator();
+        let request = sigv4a_request("GET", "/bucket/key", "*", SignatureLocation::QueryParams);
+        assert!(request
+            .query
+            .as_deref()
+            .unwrap()
+            .contains("X-Amz-Region-Set"));
+        let result = auth.authenticate(&request).await;
+        assert!(
+            result.is_ok(),
+            "SigV4A presigned URL should be accepted: {:?}",
+            result
+        );
+    }
+
+    /// Test that a region set not covering our region is rejected
+    #[tokio::test]
+    async fn test_sigv4a_wrong_region_set_rejected() {
+        use aws_sigv4::http_request::SignatureLocation;
+
+        let auth = presigned_authenticator();
+        let request = sigv4a_request("PUT", "/bucket/key", "eu-*", SignatureLocation::Headers);
+        assert!(auth.authenticate(&request).await.is_err());
+    }
+
+    /// Test that re-encoding a SigV4A signature does not get a replay past
+    /// the replay guard
+    #[tokio::test]
+    async fn test_sigv4a_reencoded_replay_rejected() {
+        use aws_sigv4::http_request::SignatureLocation;
+        use mizuchi_uploadr::auth::sigv4_replay::MemoryReplayGuard;
+        use p256::ecdsa::Signature;
+
+        let auth = presigned_authenticator()
+            .with_replay_guard(std::sync::Arc::new(MemoryReplayGuard::new(1000)));
+        let mut request = sigv4a_request("PUT", "/bucket/key", "*", SignatureLocation::Headers);
+        assert!(auth.authenticate(&request).await.is_ok());
+
+        let authorization = request.headers["authorization"].clone();
+        let (prefix, signature) = authorization.split_once("Signature=").unwrap();
+        let der = hex::decode(signature).unwrap();
+        let parsed = Signature::from_der(&der).unwrap();
+        let high_s =
+            Signature::from_scalars(parsed.r().to_bytes(), (-parsed.s()).to_bytes()).unwrap();
+        for replayed in [signature.to_uppercase(), hex::encode(high_s.to_der())] {
+            request.headers.insert(
+                "authorization".to_string(),
+                format!("{}Signature={}", prefix, replayed),
+            );
+            let result = auth.authenticate(&request).await;
+            assert!(
+                matches!(&result, Err(AuthError::InvalidToken(msg)) if msg.contains("Replayed")),
+                "Re-encoded signature {} should be rejected as a replay: {:?}",
+                replayed,
+                result
+            );
+        }
+    }
+
+    /// Test that tampering with a SigV4A request or its region set is detected
+    #[tokio::test]
+    async fn test_sigv4a_tampering_rejected() {
+        use aws_sigv4::http_request::SignatureLocation;
+
+        let auth = presigned_authenticator();
+        let mut request = sigv4a_request(
+            "PUT",
+            "/bucket/key",
+            "us-east-1",
+            SignatureLocation::Headers,
+        );
+        request.path = "/bucket/other".to_string();
+        assert!(matches!(
+            auth.authenticate(&request).await,
+            Err(AuthError::InvalidSignature)
+        ));
+
+        let mut request = sigv4a_request(
+            "PUT",
+            "/bucket/key",
+            "us-east-1",
+            SignatureLocation::Headers,
+        );
+        request
+            .headers
+            .insert("x-amz-region-set".to_string(), "*".to_string());
+        assert!(matches!(
+            auth.authenticate(&request).await,
+            Err(AuthError::InvalidSignature)
+        ));
+    }
 }
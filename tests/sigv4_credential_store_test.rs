@@ -0,0 +1,160 @@
+//! SigV4 Credential Store Integration Tests
+//!
+//! Tests for looking up SigV4 client secrets in Vault and AWS Secrets Manager.
+//!
+//! ## Test Coverage
+//!
+//! - Vault KV v2 secrets are read with the configured token
+//! - Secrets Manager values may be bare secrets or JSON objects
+//! - Unknown access keys are not errors
+//! - `SigV4Config.store` is wired into the authenticator
+
+#[cfg(test)]
+mod tests {
+    use mizuchi_uploadr::auth::sigv4::SigV4Authenticator;
+    use mizuchi_uploadr::auth::sigv4_store::{
+        CredentialStore, SecretsManagerCredentialStore, VaultCredentialStore,
+    };
+    use mizuchi_uploadr::auth::{AuthError, AuthRequest, Authenticator};
+    use mizuchi_uploadr::config::SigV4Config;
+    use mizuchi_uploadr::s3::Credentials;
+    use wiremock::matchers::{body_json, header, header_exists, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn vault() -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/secret/data/mizuchi/sigv4/AKIDVAULT"))
+            .and(header("X-Vault-Token", "s.test"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": { "data": { "secret_key": "vault-secret" }, "metadata": {} }
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+                "errors": []
+            })))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    async fn secrets_manager() -> MockServer {
+        let server = MockServer::start().await;
+        for (secret_id, value) in [
+            ("mizuchi/sigv4/AKIDPLAIN", "plain-secret"),
+            ("mizuchi/sigv4/AKIDJSON", r#"{"secret_key":"json-secret"}"#),
+        ] {
+            Mock::given(method("POST"))
+                .and(header("x-amz-target", "secretsmanager.GetSecretValue"))
+                .and(header_exists("authorization"))
+                .and(body_json(serde_json::json!({ "SecretId": secret_id })))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "Name": secret_id,
+                    "SecretString": value,
+                })))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "__type": "ResourceNotFoundException",
+                "Message": "Secrets Manager can't find the specified secret."
+            })))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[tokio::test]
+    async fn test_vault_store() {
+        let server = vault().await;
+        let store = VaultCredentialStore::new(&server.uri(), "s.test", "secret", "mizuchi/sigv4/");
+
+        let secret = store.secret_key("AKIDVAULT").await.unwrap();
+        assert_eq!(secret.as_deref(), Some("vault-secret"));
+        assert_eq!(store.secret_key("AKIDMISSING").await.unwrap(), None);
+        // Not a valid access key: never sent to Vault
+        assert_eq!(store.secret_key("../../sys/seal").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_vault_store_unavailable() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        let store = VaultCredentialStore::new(&server.uri(), "s.test", "secret", "mizuchi/sigv4/");
+
+        assert!(matches!(
+            store.secret_key("AKIDVAULT").await,
+            Err(AuthError::ConfigError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_secrets_manager_store() {
+        let server = secrets_manager().await;
+        let store =
+            SecretsManagerCredentialStore::new("us-east-1", Some(&server.uri()), "mizuchi/sigv4/")
+                .with_credentials(Credentials::new("AKIDCALLER", "caller-secret"));
+
+        let secret = store.secret_key("AKIDPLAIN").await.unwrap();
+        assert_eq!(secret.as_deref(), Some("plain-secret"));
+        let secret = store.secret_key("AKIDJSON").await.unwrap();
+        assert_eq!(secret.as_deref(), Some("json-secret"));
+        assert_eq!(store.secret_key("AKIDMISSING").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_authenticator_uses_configured_store() {
+        let server = vault().await;
+        let config: SigV4Config = serde_yaml::from_str(&format!(
+            r#"
+service: s3
+region: us-east-1
+store:
+  type: vault
+  address: "{}"
+  token: s.test
+"#,
+            server.uri()
+        ))
+        .unwrap();
+        let auth = SigV4Authenticator::from_config(&config).unwrap();
+
+        let timestamp = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let date = &timestamp[..8];
+        let request = |access_key: &str| AuthRequest {
+            headers: [
+                (
+                    "authorization".to_string(),
+                    format!(
+                        "AWS4-HMAC-SHA256 Credential={}/{}/us-east-1/s3/aws4_request, \
+                         SignedHeaders=host;x-amz-date, Signature=00",
+                        access_key, date
+                    ),
+                ),
+                ("host".to_string(), "localhost".to_string()),
+                ("x-amz-date".to_string(), timestamp.clone()),
+            ]
+            .into(),
+            query: None,
+            method: "PUT".into(),
+            path: "/uploads/a.txt".into(),
+            peer_certificates: Vec::new(),
+        };
+
+        // Known to Vault: the secret is found and the (bogus) signature checked
+        assert!(matches!(
+            auth.authenticate(&request("AKIDVAULT")).await,
+            Err(AuthError::InvalidSignature)
+        ));
+        assert!(matches!(
+            auth.authenticate(&request("AKIDMISSING")).await,
+            Err(AuthError::InvalidToken(_))
+        ));
+    }
+}
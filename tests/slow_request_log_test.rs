@@ -0,0 +1,167 @@
+//! Slow Request Log Integration Tests
+//!
+//! Tests for `logging.slow_request_threshold_ms`.
+//!
+//! ## Test Coverage
+//!
+//! - Requests over the threshold are logged at WARN with phase timings,
+//!   bucket, subject and size
+//! - Requests under the threshold are not logged
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use http_body_util::Full;
+    use hyper::{Request, StatusCode};
+    use mizuchi_uploadr::auth::{AuthError, AuthRequest, AuthResult, Authenticator};
+    use mizuchi_uploadr::authz::{Authorizer, AuthzError, AuthzRequest};
+    use mizuchi_uploadr::config::{Config, LoggingConfig};
+    use mizuchi_uploadr::UploadService;
+    use std::collections::HashMap;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tower_service::Service;
+    use tracing::field::{Field, Visit};
+    use tracing::{Event, Level};
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+    /// Fields of every WARN event
+    #[derive(Clone, Default)]
+    struct WarnRecorder(Arc<Mutex<Vec<HashMap<String, String>>>>);
+
+    struct Fields<'a>(&'a mut HashMap<String, String>);
+
+    impl Visit for Fields<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl<S: tracing::Subscriber> Layer<S> for WarnRecorder {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            if *event.metadata().level() == Level::WARN {
+                let mut fields = HashMap::new();
+                event.record(&mut Fields(&mut fields));
+                self.0.lock().unwrap().push(fields);
+            }
+        }
+    }
+
+    impl WarnRecorder {
+        fn slow_requests(&self) -> Vec<HashMap<String, String>> {
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|fields| fields.get("message").map(String::as_str) == Some("Slow request"))
+                .cloned()
+                .collect()
+        }
+    }
+
+    /// Accepts requests carrying `X-User`
+    struct HeaderAuthenticator;
+
+    #[async_trait]
+    impl Authenticator for HeaderAuthenticator {
+        async fn authenticate(&self, request: &AuthRequest) -> Result<AuthResult, AuthError> {
+            let user = request
+                .headers
+                .get("x-user")
+                .ok_or(AuthError::MissingAuth)?;
+            Ok(AuthResult {
+                subject: user.clone(),
+                claims: HashMap::new(),
+            })
+        }
+    }
+
+    /// Allows everything after a delay
+    struct SlowAuthorizer(Duration);
+
+    #[async_trait]
+    impl Authorizer for SlowAuthorizer {
+        async fn authorize(&self, _request: &AuthzRequest) -> Result<bool, AuthzError> {
+            tokio::time::sleep(self.0).await;
+            Ok(true)
+        }
+    }
+
+    fn config(root: &Path) -> Config {
+        common::config(&common::local_bucket("uploads", root, ""))
+    }
+
+    async fn service(root: &Path, authz_delay: Duration) -> UploadService {
+        let mut config = config(root);
+        config.logging = LoggingConfig {
+            slow_request_threshold_ms: Some(50),
+            ..Default::default()
+        };
+        UploadService::builder()
+            .config(config)
+            .with_authenticator("uploads", Arc::new(HeaderAuthenticator))
+            .with_authorizer("uploads", Arc::new(SlowAuthorizer(authz_delay)))
+            .build_service()
+            .await
+            .unwrap()
+    }
+
+    async fn upload(service: &mut UploadService) -> StatusCode {
+        let request = Request::put("/uploads/a.txt")
+            .header("X-User", "alice")
+            .body(Full::new(Bytes::from("hello")))
+            .unwrap();
+        service.call(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_slow_request_logged() {
+        let recorder = WarnRecorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let dir = tempfile::tempdir().unwrap();
+        let mut service = service(dir.path(), Duration::from_millis(80)).await;
+
+        assert_eq!(upload(&mut service).await, StatusCode::OK);
+
+        let logged = recorder.slow_requests();
+        assert_eq!(logged.len(), 1);
+        let fields = &logged[0];
+        assert_eq!(fields["method"], "PUT");
+        assert_eq!(fields["path"], "/uploads/a.txt");
+        assert_eq!(fields["status"], "200");
+        assert_eq!(fields["bucket"], "uploads");
+        assert_eq!(fields["subject"], "alice");
+        assert_eq!(fields["size"], "5");
+        let authz_ms: u64 = fields["authz_ms"].parse().unwrap();
+        let total_ms: u64 = fields["total_ms"].parse().unwrap();
+        assert!(authz_ms >= 80, "authz took {}ms", authz_ms);
+        assert!(total_ms >= authz_ms);
+        for phase in ["auth_ms", "body_ms", "s3_ms"] {
+            assert!(fields.contains_key(phase), "missing {}", phase);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fast_request_not_logged() {
+        let recorder = WarnRecorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let dir = tempfile::tempdir().unwrap();
+        let mut service = service(dir.path(), Duration::ZERO).await;
+
+        assert_eq!(upload(&mut service).await, StatusCode::OK);
+
+        assert!(recorder.slow_requests().is_empty());
+    }
+}
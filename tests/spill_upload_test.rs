@@ -0,0 +1,117 @@
+//! Spill Buffer Integration Tests
+//!
+//! Tests for spilling request bodies to disk in `PingoraServer`.
+//!
+//! ## Test Coverage
+//!
+//! - Chunked uploads without a `Content-Length` are spilled and stored
+//! - Bodies above the memory limit are spilled; smaller ones are not
+//! - Spill files are removed once the upload finishes
+//! - Bodies exceeding the spill quota are rejected with 503
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::config::Config;
+    use std::net::SocketAddr;
+    use std::path::Path;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    fn config(root: &Path, spill_dir: &Path, max_disk_bytes: u64) -> Config {
+        let bucket = format!(
+            r#"    upload:
+      spill:
+        dir: "{}"
+        max_disk_bytes: {}
+        memory_limit: 8"#,
+            spill_dir.display(),
+            max_disk_bytes
+        );
+        common::config(&common::local_bucket("docs", root, &bucket))
+    }
+
+    /// Send a chunked PUT with the given chunks and return the raw response
+    async fn put_chunked(addr: SocketAddr, path: &str, chunks: &[&str]) -> String {
+        let mut request = format!(
+            "PUT {} HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n",
+            path
+        );
+        for chunk in chunks {
+            request.push_str(&format!("{:x}\r\n{}\r\n", chunk.len(), chunk));
+        }
+        request.push_str("0\r\n\r\n");
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    #[tokio::test]
+    async fn test_chunked_upload_is_spilled() {
+        let root = tempfile::tempdir().unwrap();
+        let spill_dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(root.path(), spill_dir.path(), 1024)).await;
+
+        let response = put_chunked(addr, "/docs/a.txt", &["hello ", "chunked ", "world"]).await;
+
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert_eq!(
+            std::fs::read(root.path().join("a.txt")).unwrap(),
+            b"hello chunked world"
+        );
+        assert_eq!(std::fs::read_dir(spill_dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_sized_uploads() {
+        let root = tempfile::tempdir().unwrap();
+        let spill_dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(root.path(), spill_dir.path(), 1024)).await;
+        let client = reqwest::Client::new();
+
+        for (key, body) in [("small.txt", "tiny"), ("large.txt", "larger than eight")] {
+            let response = client
+                .put(format!("http://{}/docs/{}", addr, key))
+                .body(body)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 200);
+            assert_eq!(
+                std::fs::read(root.path().join(key)).unwrap(),
+                body.as_bytes()
+            );
+        }
+        assert_eq!(std::fs::read_dir(spill_dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_quota_exceeded() {
+        let root = tempfile::tempdir().unwrap();
+        let spill_dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(root.path(), spill_dir.path(), 10)).await;
+
+        let response = put_chunked(addr, "/docs/b.txt", &["0123456789", "abcdef"]).await;
+
+        assert!(response.starts_with("HTTP/1.1 503"), "{}", response);
+        assert!(!root.path().join("b.txt").exists());
+        assert_eq!(std::fs::read_dir(spill_dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_stale_spill_files_removed_at_startup() {
+        let root = tempfile::tempdir().unwrap();
+        let spill_dir = tempfile::tempdir().unwrap();
+        let stale = spill_dir.path().join("mizuchi-spill-previous-run.tmp");
+        std::fs::write(&stale, b"partial body").unwrap();
+
+        common::start(config(root.path(), spill_dir.path(), 1024)).await;
+
+        assert!(!stale.exists());
+    }
+}
@@ -0,0 +1,123 @@
+//! Static Policy Authorization Integration Tests
+//!
+//! Tests for the built-in `StaticPolicyAuthorizer` (`authz.type: static`).
+//!
+//! ## Test Coverage
+//!
+//! - Team members can write under their team prefix only
+//! - Deny rules ahead of allow rules take precedence
+//! - Per-subject home prefixes via `{subject}`
+//! - Anonymous uploads are authorized as the anonymous subject
+//! - `authz` without `auth.enabled` is rejected by config validation
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use mizuchi_uploadr::config::Config;
+    use std::net::SocketAddr;
+    use std::path::Path;
+
+    const SECRET: &str = "policy-secret";
+
+    const RULES: &str = r#"
+    authz:
+      type: static
+      rules:
+        - subjects: ["team-a-intern"]
+          effect: deny
+        - subjects: ["team-a-*"]
+          key_prefix: "team-a/"
+          actions: ["upload"]
+          effect: allow
+        - key_prefix: "users/{subject}/"
+          effect: allow
+        - subjects: ["anonymous"]
+          key_prefix: "public/inbox/"
+          effect: allow
+"#;
+
+    fn config(root: &Path) -> Config {
+        let bucket = format!(
+            r#"    auth:
+      enabled: true
+      jwt:
+        secret: {SECRET}
+        algorithm: HS256
+      allow_anonymous:
+        max_size: 1024
+        allowed_content_types: ["text/plain"]
+        key_prefix: "public/"
+        rate_limit:
+          requests_per_minute: 600
+{RULES}"#
+        );
+        common::config(&common::local_bucket("uploads", root, &bucket))
+    }
+
+    fn token(subject: &str) -> String {
+        let claims = serde_json::json!({
+            "sub": subject,
+            "exp": (chrono::Utc::now().timestamp() + 3600) as usize,
+        });
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    async fn put(addr: SocketAddr, key: &str, subject: Option<&str>) -> reqwest::StatusCode {
+        let path = format!("/uploads/{}", key);
+        let bearer = subject.map(|subject| format!("Bearer {}", token(subject)));
+        let mut headers = vec![("Content-Type", "text/plain")];
+        if let Some(bearer) = &bearer {
+            headers.push(("Authorization", bearer));
+        }
+        common::put(addr, &path, &headers, "data").await.status()
+    }
+
+    #[tokio::test]
+    async fn test_team_prefix_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path())).await;
+
+        assert_eq!(put(addr, "team-a/build.tar", Some("team-a-ci")).await, 200);
+        assert!(dir.path().join("team-a/build.tar").exists());
+
+        assert_eq!(put(addr, "team-b/build.tar", Some("team-a-ci")).await, 403);
+        assert_eq!(put(addr, "team-a/x.tar", Some("team-a-intern")).await, 403);
+        assert_eq!(put(addr, "team-a/x.tar", Some("team-b-ci")).await, 403);
+        assert!(!dir.path().join("team-b").exists());
+    }
+
+    #[tokio::test]
+    async fn test_subject_home_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path())).await;
+
+        assert_eq!(put(addr, "users/alice/notes.txt", Some("alice")).await, 200);
+        assert_eq!(put(addr, "users/bob/notes.txt", Some("alice")).await, 403);
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_subject_authorized() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path())).await;
+
+        // Both keys are inside the anonymous prefix jail; the policy narrows it
+        assert_eq!(put(addr, "public/inbox/hello.txt", None).await, 200);
+        assert_eq!(put(addr, "public/hello.txt", None).await, 403);
+    }
+
+    #[test]
+    fn test_authz_requires_auth() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = config(dir.path());
+        config.buckets[0].auth.enabled = false;
+        assert!(config.validate().is_err());
+    }
+}
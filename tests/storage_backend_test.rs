@@ -0,0 +1,157 @@
+//! Storage Backend Integration Tests
+//!
+//! Tests for upload handlers running against the local filesystem backend.
+//!
+//! ## Test Coverage
+//!
+//! - PutObjectHandler writes objects through a StorageBackend
+//! - MultipartHandler assembles parts in order and cleans up staging
+//! - Aborted multipart uploads leave no object behind
+//! - Bucket mismatch is still enforced for non-S3 backends
+//! - Storage backend selection from YAML config
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use mizuchi_uploadr::config::{Config, StorageConfig};
+    use mizuchi_uploadr::upload::backend::{self, StorageBackend};
+    use mizuchi_uploadr::upload::local::LocalFsBackend;
+    use mizuchi_uploadr::upload::multipart::{MultipartHandler, MIN_PART_SIZE};
+    use mizuchi_uploadr::upload::put_object::PutObjectHandler;
+    use mizuchi_uploadr::upload::{UploadError, UploadHandler};
+    use std::sync::Arc;
+
+    fn local_backend(dir: &tempfile::TempDir) -> Arc<dyn StorageBackend> {
+        Arc::new(LocalFsBackend::new("uploads", dir.path()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_put_object_handler_with_local_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let handler = PutObjectHandler::with_backend(local_backend(&dir));
+
+        let result = handler
+            .upload(
+                "uploads",
+                "docs/readme.txt",
+                Bytes::from("hello"),
+                Some("text/plain"),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.bytes_written, 5);
+        assert_eq!(result.etag, "\"5d41402abc4b2a76b9719d911017c592\"");
+        assert_eq!(
+            std::fs::read(dir.path().join("docs/readme.txt")).unwrap(),
+            b"hello"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_put_object_rejects_bucket_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let handler = PutObjectHandler::with_backend(local_backend(&dir));
+
+        let err = handler
+            .upload("other", "key", Bytes::from("data"), None)
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("Bucket mismatch"));
+    }
+
+    #[tokio::test]
+    async fn test_put_object_rejects_path_traversal() {
+        let dir = tempfile::tempdir().unwrap();
+        let handler = PutObjectHandler::with_backend(local_backend(&dir));
+
+        let err = handler
+            .upload("uploads", "../outside.txt", Bytes::from("data"), None)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, UploadError::InvalidKey(_)));
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload_with_local_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let handler = MultipartHandler::with_backend(local_backend(&dir));
+
+        let mut upload = handler.create("uploads", "big.bin").await.unwrap();
+        let part1 = Bytes::from(vec![b'a'; MIN_PART_SIZE]);
+        let part2 = Bytes::from(vec![b'b'; 1024]);
+        handler.upload_part(&mut upload, 1, part1).await.unwrap();
+        handler.upload_part(&mut upload, 2, part2).await.unwrap();
+
+        let result = handler.complete(&upload).await.unwrap();
+        assert!(
+            result.etag.ends_with("-2\""),
+            "Multipart ETag: {}",
+            result.etag
+        );
+
+        let stored = std::fs::read(dir.path().join("big.bin")).unwrap();
+        assert_eq!(stored.len(), MIN_PART_SIZE + 1024);
+        assert_eq!(stored[0], b'a');
+        assert_eq!(stored[stored.len() - 1], b'b');
+
+        // Staging area is cleaned up
+        let staging = dir
+            .path()
+            .join(".mizuchi-multipart")
+            .join(&upload.upload_id);
+        assert!(!staging.exists());
+    }
+
+    #[tokio::test]
+    async fn test_multipart_abort_with_local_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let handler = MultipartHandler::with_backend(local_backend(&dir));
+
+        let mut upload = handler.create("uploads", "aborted.bin").await.unwrap();
+        handler
+            .upload_part(&mut upload, 1, Bytes::from("partial"))
+            .await
+            .unwrap();
+        handler.abort(&upload).await.unwrap();
+
+        assert!(!dir.path().join("aborted.bin").exists());
+        let result = handler.complete(&upload).await;
+        assert!(result.is_err(), "Completing an aborted upload should fail");
+    }
+
+    #[tokio::test]
+    async fn test_backend_from_config_selects_local() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = format!(
+            r#"
+server:
+  address: "127.0.0.1:0"
+buckets:
+  - name: dev
+    path_prefix: /dev
+    s3:
+      bucket: dev-bucket
+      region: us-east-1
+    storage:
+      type: local
+      root: "{}"
+"#,
+            dir.path().display()
+        );
+        let config: Config = serde_yaml::from_str(&yaml).unwrap();
+        let bucket = &config.buckets[0];
+        assert!(matches!(bucket.storage, StorageConfig::Local { .. }));
+
+        let backend = backend::from_bucket_config(bucket).unwrap();
+        assert_eq!(backend.bucket(), "dev-bucket");
+
+        backend
+            .put_object("hello.txt", Bytes::from("hi"), None)
+            .await
+            .unwrap();
+        assert!(dir.path().join("hello.txt").exists());
+    }
+}
@@ -0,0 +1,112 @@
+//! Storage Class Integration Tests
+//!
+//! Tests for `upload.storage_class_rules` in `PingoraServer`.
+//!
+//! ## Test Coverage
+//!
+//! - Uploads matching a prefix and size rule are sent with its storage class
+//! - Uploads matching a content type rule are sent with its storage class
+//! - Uploads matching no rule are sent without `x-amz-storage-class`
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::config::Config;
+    use std::net::SocketAddr;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    fn config(endpoint: &str) -> Config {
+        let buckets = format!(
+            r#"  - name: media
+    path_prefix: /media
+    s3:
+      bucket: media
+      region: us-east-1
+      endpoint: "{}"
+      access_key: test-access
+      secret_key: test-secret
+    upload:
+      storage_class_rules:
+        - prefix: /archive/
+          min_size: 1024
+          storage_class: STANDARD_IA
+        - content_types: ["video/*"]
+          storage_class: GLACIER_IR
+"#,
+            endpoint
+        );
+        common::config(&buckets)
+    }
+
+    async fn put(addr: SocketAddr, key: &str, content_type: &str, body: Vec<u8>) -> u16 {
+        let path = format!("/media/{}", key);
+        common::put(addr, &path, &[("content-type", content_type)], body)
+            .await
+            .status()
+            .as_u16()
+    }
+
+    fn stored() -> ResponseTemplate {
+        ResponseTemplate::new(200).insert_header("ETag", "\"abc\"")
+    }
+
+    fn without_storage_class(req: &Request) -> bool {
+        !req.headers.contains_key("x-amz-storage-class")
+    }
+
+    #[tokio::test]
+    async fn test_storage_class_by_prefix_and_size() {
+        let s3 = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/media/archive/2024.tar"))
+            .and(header("x-amz-storage-class", "STANDARD_IA"))
+            .respond_with(stored())
+            .expect(1)
+            .mount(&s3)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/media/archive/note.txt"))
+            .and(without_storage_class)
+            .respond_with(stored())
+            .expect(1)
+            .mount(&s3)
+            .await;
+        let addr = common::start(config(&s3.uri())).await;
+
+        let status = put(addr, "archive/2024.tar", "application/x-tar", vec![0; 2048]).await;
+        assert_eq!(status, 200);
+
+        // Below the rule's min_size
+        let status = put(addr, "archive/note.txt", "text/plain", vec![b'a'; 16]).await;
+        assert_eq!(status, 200);
+    }
+
+    #[tokio::test]
+    async fn test_storage_class_by_content_type() {
+        let s3 = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/media/clip.mp4"))
+            .and(header("x-amz-storage-class", "GLACIER_IR"))
+            .respond_with(stored())
+            .expect(1)
+            .mount(&s3)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/media/photo.png"))
+            .and(without_storage_class)
+            .respond_with(stored())
+            .expect(1)
+            .mount(&s3)
+            .await;
+        let addr = common::start(config(&s3.uri())).await;
+
+        let status = put(addr, "clip.mp4", "video/mp4", vec![0; 64]).await;
+        assert_eq!(status, 200);
+
+        let status = put(addr, "photo.png", "image/png", vec![0; 64]).await;
+        assert_eq!(status, 200);
+    }
+}
@@ -0,0 +1,125 @@
+//! Tenant Metrics Integration Tests
+//!
+//! Tests for tenant labels on upload metrics (`metrics.tenant_labels`).
+//!
+//! ## Test Coverage
+//!
+//! - Uploads are counted per bucket and tenant, with their bytes
+//! - Tenants beyond `max_tenants` are counted as `other`
+//! - Uploads without the tenant claim are counted as `none`
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use http_body_util::Full;
+    use hyper::{Request, StatusCode};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::metrics::{TENANT_UPLOADS_TOTAL, TENANT_UPLOAD_BYTES};
+    use mizuchi_uploadr::UploadService;
+    use std::path::Path;
+    use tower_service::Service;
+
+    const SECRET: &str = "tenant-metrics-secret";
+
+    /// Metrics are global, so each test uses its own bucket
+    fn config(root: &Path, bucket: &str) -> Config {
+        let yaml = format!(
+            r#"
+server:
+  address: "127.0.0.1:0"
+metrics:
+  tenant_labels:
+    claim: org.tenant
+    max_tenants: 2
+buckets:
+  - name: {bucket}
+    path_prefix: /{bucket}
+    s3:
+      bucket: {bucket}
+      region: us-east-1
+    storage:
+      type: local
+      root: "{root}"
+    auth:
+      enabled: true
+      jwt:
+        secret: {SECRET}
+        algorithm: HS256
+"#,
+            root = root.display(),
+        );
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    fn token(tenant: Option<&str>) -> String {
+        let mut claims = serde_json::json!({
+            "sub": "alice",
+            "exp": (chrono::Utc::now().timestamp() + 3600) as usize,
+        });
+        if let Some(tenant) = tenant {
+            claims["org"] = serde_json::json!({ "tenant": tenant });
+        }
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    async fn upload(service: &mut UploadService, bucket: &str, tenant: Option<&str>) {
+        let request = Request::put(format!("/{}/object", bucket))
+            .header("Authorization", format!("Bearer {}", token(tenant)))
+            .body(Full::new(Bytes::from("hello")))
+            .unwrap();
+        let response = service.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    fn uploads(bucket: &str, tenant: &str) -> f64 {
+        TENANT_UPLOADS_TOTAL
+            .with_label_values(&[bucket, tenant, "success"])
+            .get()
+    }
+
+    #[tokio::test]
+    async fn test_uploads_counted_per_tenant() {
+        let dir = tempfile::tempdir().unwrap();
+        let bucket = "tenant-counted";
+        let mut service = UploadService::new(config(dir.path(), bucket))
+            .await
+            .unwrap();
+
+        upload(&mut service, bucket, Some("acme")).await;
+        upload(&mut service, bucket, Some("acme")).await;
+        upload(&mut service, bucket, None).await;
+
+        assert_eq!(uploads(bucket, "acme"), 2.0);
+        assert_eq!(uploads(bucket, "none"), 1.0);
+        assert_eq!(
+            TENANT_UPLOAD_BYTES
+                .with_label_values(&[bucket, "acme"])
+                .get(),
+            10.0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tenants_beyond_cap_counted_as_other() {
+        let dir = tempfile::tempdir().unwrap();
+        let bucket = "tenant-capped";
+        let mut service = UploadService::new(config(dir.path(), bucket))
+            .await
+            .unwrap();
+
+        for tenant in ["a", "b", "c", "d", "a"] {
+            upload(&mut service, bucket, Some(tenant)).await;
+        }
+
+        assert_eq!(uploads(bucket, "a"), 2.0);
+        assert_eq!(uploads(bucket, "b"), 1.0);
+        assert_eq!(uploads(bucket, "other"), 2.0);
+        assert_eq!(uploads(bucket, "c"), 0.0);
+    }
+}
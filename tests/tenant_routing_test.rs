@@ -0,0 +1,151 @@
+//! Tenant Routing Integration Tests
+//!
+//! Tests for buckets sharing a path prefix with `tenant` routing.
+//!
+//! ## Test Coverage
+//!
+//! - Uploads go to the bucket listing the caller's tenant claim
+//! - Unlisted tenants go to the bucket without tenant routing
+//! - Unlisted tenants are rejected when no such bucket exists
+//! - `GET ?uploads` lists only the uploads of the caller's tenant bucket
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use std::net::SocketAddr;
+    use std::path::Path;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpStream;
+
+    const SECRET: &str = "tenant-secret";
+
+    fn bucket(name: &str, root: &Path, tenants: Option<&str>) -> String {
+        let tenant = tenants
+            .map(|values| {
+                format!(
+                    r#"
+    tenant:
+      claim: org.tenant
+      values: [{values}]"#
+                )
+            })
+            .unwrap_or_default();
+        format!(
+            r#"  - name: {name}
+    path_prefix: /uploads
+    s3:
+      bucket: {name}
+      region: us-east-1
+    storage:
+      type: local
+      root: "{root}"
+    upload:
+      list_uploads: true
+    auth:
+      enabled: true
+      jwt:
+        secret: {SECRET}
+        algorithm: HS256{tenant}
+"#,
+            root = root.display(),
+        )
+    }
+
+    fn token(tenant: &str) -> String {
+        let claims = serde_json::json!({
+            "sub": "alice",
+            "org": {"tenant": tenant},
+            "exp": (chrono::Utc::now().timestamp() + 3600) as usize,
+        });
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    async fn put(addr: SocketAddr, key: &str, tenant: &str) -> u16 {
+        let path = format!("/uploads/{}", key);
+        let bearer = format!("Bearer {}", token(tenant));
+        common::put(addr, &path, &[("Authorization", &bearer)], "tenant data")
+            .await
+            .status()
+            .as_u16()
+    }
+
+    #[tokio::test]
+    async fn test_uploads_routed_by_tenant() {
+        let acme = tempfile::tempdir().unwrap();
+        let globex = tempfile::tempdir().unwrap();
+        let shared = tempfile::tempdir().unwrap();
+        let buckets = [
+            bucket("acme", acme.path(), Some("acme, acme-staging")),
+            bucket("globex", globex.path(), Some("globex")),
+            bucket("shared", shared.path(), None),
+        ];
+        let addr = common::start(common::config(&buckets.concat())).await;
+
+        assert_eq!(put(addr, "a.txt", "acme").await, 200);
+        assert_eq!(put(addr, "b.txt", "acme-staging").await, 200);
+        assert_eq!(put(addr, "c.txt", "globex").await, 200);
+        assert_eq!(put(addr, "d.txt", "initech").await, 200);
+
+        assert!(acme.path().join("a.txt").exists());
+        assert!(acme.path().join("b.txt").exists());
+        assert!(globex.path().join("c.txt").exists());
+        assert!(shared.path().join("d.txt").exists());
+        assert!(!shared.path().join("a.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_unlisted_tenant_forbidden_without_default() {
+        let acme = tempfile::tempdir().unwrap();
+        let globex = tempfile::tempdir().unwrap();
+        let buckets = [
+            bucket("acme", acme.path(), Some("acme")),
+            bucket("globex", globex.path(), Some("globex")),
+        ];
+        let addr = common::start(common::config(&buckets.concat())).await;
+
+        assert_eq!(put(addr, "a.txt", "initech").await, 403);
+        assert!(!acme.path().join("a.txt").exists());
+        assert!(!globex.path().join("a.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_list_uploads_limited_to_tenant_bucket() {
+        let acme = tempfile::tempdir().unwrap();
+        let globex = tempfile::tempdir().unwrap();
+        let buckets = [
+            bucket("acme", acme.path(), Some("acme")),
+            bucket("globex", globex.path(), Some("globex")),
+        ];
+        let addr = common::start(common::config(&buckets.concat())).await;
+
+        // The same subject uploads for acme and lists for both tenants
+        let mut upload = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "PUT /uploads/a.txt HTTP/1.1\r\nHost: {}\r\nAuthorization: Bearer {}\r\n\
+             x-mizuchi-upload-id: acme-1\r\nContent-Length: 10\r\n\r\n01234",
+            addr,
+            token("acme")
+        );
+        upload.write_all(request.as_bytes()).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let list = |tenant: &str| {
+            reqwest::Client::new()
+                .get(format!("http://{}/uploads?uploads", addr))
+                .bearer_auth(token(tenant))
+                .send()
+        };
+        let body = list("acme").await.unwrap().text().await.unwrap();
+        assert!(body.contains("<UploadId>acme-1</UploadId>"), "{}", body);
+        let body = list("globex").await.unwrap().text().await.unwrap();
+        assert!(!body.contains("<Upload>"), "{}", body);
+    }
+}
@@ -0,0 +1,195 @@
+//! Token Exchange Integration Tests
+//!
+//! Tests for `TokenExchangeAuthenticator` against a mock IdP token endpoint.
+//!
+//! ## Test Coverage
+//!
+//! - The client JWT is sent as an RFC 8693 `subject_token`, with the
+//!   configured audience, scope and client credentials
+//! - The exchanged token's subject and actor are reported
+//! - Exchanges are cached per client token
+//! - A refused exchange is `Forbidden`, a failing IdP a backend error
+//! - Invalid client tokens never reach the IdP
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use mizuchi_uploadr::auth::jwt::JwtAuthenticator;
+    use mizuchi_uploadr::auth::token_exchange::{
+        TokenExchangeAuthenticator, GRANT_TYPE, JWT_TOKEN_TYPE,
+    };
+    use mizuchi_uploadr::auth::{AuthError, AuthRequest, Authenticator};
+    use mizuchi_uploadr::config::TokenExchangeConfig;
+    use std::collections::HashMap;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use wiremock::matchers::{body_string_contains, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const SECRET: &str = "client-token-secret";
+
+    fn exp() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 3600
+    }
+
+    /// Client token of `alice`, issued to the `uploader` app
+    fn client_token() -> String {
+        encode(
+            &Header::default(),
+            &serde_json::json!({ "sub": "alice", "azp": "uploader", "exp": exp() }),
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    /// Token the IdP issues for `alice`, acted on by `uploader`
+    fn issued_token() -> String {
+        encode(
+            &Header::default(),
+            &serde_json::json!({
+                "sub": "alice",
+                "aud": "uploads",
+                "exp": exp(),
+                "act": { "sub": "uploader" },
+            }),
+            &EncodingKey::from_secret(b"idp-secret"),
+        )
+        .unwrap()
+    }
+
+    fn authenticator(idp: &MockServer) -> TokenExchangeAuthenticator {
+        TokenExchangeAuthenticator::new(
+            JwtAuthenticator::new_hs256(SECRET),
+            TokenExchangeConfig {
+                token_endpoint: format!("{}/oauth2/token", idp.uri()),
+                client_id: "mizuchi".into(),
+                client_secret: Some("s3cr3t".into()),
+                client_secret_file: None,
+                audience: Some("uploads".into()),
+                scopes: vec!["upload".into(), "tag".into()],
+                timeout_ms: 5000,
+            },
+        )
+    }
+
+    fn request(token: &str) -> AuthRequest {
+        AuthRequest {
+            headers: HashMap::from([("authorization".into(), format!("Bearer {}", token))]),
+            query: None,
+            method: "PUT".into(),
+            path: "/uploads/file.txt".into(),
+            peer_certificates: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_exchange_reports_subject_and_actor() {
+        let idp = MockServer::start().await;
+        let basic = base64::engine::general_purpose::STANDARD.encode("mizuchi:s3cr3t");
+        let token = client_token();
+        Mock::given(method("POST"))
+            .and(path("/oauth2/token"))
+            .and(header("authorization", format!("Basic {}", basic).as_str()))
+            .and(body_string_contains(
+                format!("grant_type={}", urlencode(GRANT_TYPE)).as_str(),
+            ))
+            .and(body_string_contains(
+                format!("subject_token={}", token).as_str(),
+            ))
+            .and(body_string_contains(
+                format!("subject_token_type={}", urlencode(JWT_TOKEN_TYPE)).as_str(),
+            ))
+            .and(body_string_contains("audience=uploads"))
+            .and(body_string_contains("scope=upload+tag"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": issued_token(),
+                "issued_token_type": "urn:ietf:params:oauth:token-type:access_token",
+                "token_type": "Bearer",
+                "expires_in": 300,
+                "scope": "upload",
+            })))
+            .expect(1)
+            .mount(&idp)
+            .await;
+
+        let auth = authenticator(&idp);
+        let result = auth.authenticate(&request(&token)).await.unwrap();
+        assert_eq!(result.subject, "alice");
+        assert_eq!(result.actor(), Some("uploader"));
+        assert_eq!(result.claims["aud"], "uploads");
+        assert_eq!(result.claims["scope"], "upload");
+
+        // Served from the cache: the mock expects a single exchange
+        let again = auth.authenticate(&request(&token)).await.unwrap();
+        assert_eq!(again.actor(), Some("uploader"));
+    }
+
+    #[tokio::test]
+    async fn test_refused_exchange_is_forbidden() {
+        let idp = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error": "invalid_target",
+                "error_description": "uploader may not act for alice",
+            })))
+            .mount(&idp)
+            .await;
+
+        let err = authenticator(&idp)
+            .authenticate(&request(&client_token()))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, AuthError::Forbidden(ref e) if e.contains("may not act")),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failing_idp_is_backend_error() {
+        let idp = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&idp)
+            .await;
+
+        let err = authenticator(&idp)
+            .authenticate(&request(&client_token()))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AuthError::TokenExchangeError(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_client_token_is_not_exchanged() {
+        let idp = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&idp)
+            .await;
+
+        let auth = authenticator(&idp);
+        let err = auth.authenticate(&request("not-a-jwt")).await.unwrap_err();
+        assert!(matches!(err, AuthError::InvalidToken(_)), "{:?}", err);
+
+        let err = auth
+            .authenticate(&AuthRequest {
+                headers: HashMap::new(),
+                ..request("")
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AuthError::MissingAuth));
+    }
+
+    /// Form encoding of the `urn:` token types
+    fn urlencode(value: &str) -> String {
+        value.replace(':', "%3A")
+    }
+}
@@ -5,6 +5,8 @@
 //!
 //! Phase 2.1: Tracing Initialization Module (RED phase)
 
+#![cfg(feature = "tracing")]
+
 use mizuchi_uploadr::config::{BatchConfig, OtlpConfig, SamplingConfig, TracingConfig};
 use mizuchi_uploadr::tracing::init::{init_tracing, shutdown_tracing};
 
@@ -23,6 +25,7 @@ fn test_init_tracing_with_valid_config() {
         sampling: SamplingConfig {
             strategy: "always".to_string(),
             ratio: 1.0,
+            rules: None,
         },
         batch: BatchConfig {
             max_queue_size: 2048,
@@ -56,6 +59,7 @@ fn test_init_tracing_when_disabled() {
         sampling: SamplingConfig {
             strategy: "never".to_string(),
             ratio: 0.0,
+            rules: None,
         },
         batch: BatchConfig::default(),
     };
@@ -85,6 +89,7 @@ fn test_init_tracing_with_invalid_endpoint() {
         sampling: SamplingConfig {
             strategy: "always".to_string(),
             ratio: 1.0,
+            rules: None,
         },
         batch: BatchConfig::default(),
     };
@@ -111,6 +116,7 @@ fn test_graceful_shutdown() {
         sampling: SamplingConfig {
             strategy: "always".to_string(),
             ratio: 1.0,
+            rules: None,
         },
         batch: BatchConfig::default(),
     };
@@ -137,6 +143,7 @@ fn test_tracing_guard_drop() {
         sampling: SamplingConfig {
             strategy: "always".to_string(),
             ratio: 1.0,
+            rules: None,
         },
         batch: BatchConfig::default(),
     };
@@ -57,6 +57,8 @@ async fn test_span_records_error() {
         bucket: "test-bucket".to_string(),
         key: "test-key".to_string(),
         parts: Vec::new(),
+        part_sizes: Default::default(),
+        part_hashes: Default::default(),
     };
 
     let result = handler.complete(&upload).await;
@@ -33,6 +33,7 @@ mod tests {
             sampling: SamplingConfig {
                 strategy: "always".to_string(),
                 ratio: 1.0,
+                rules: None,
             },
             batch: BatchConfig {
                 max_queue_size: 100,
@@ -80,6 +81,7 @@ mod tests {
             sampling: SamplingConfig {
                 strategy: "always".to_string(),
                 ratio: 1.0,
+                rules: None,
             },
             batch: BatchConfig {
                 max_queue_size: 100,
@@ -132,6 +134,7 @@ mod tests {
             sampling: SamplingConfig {
                 strategy: "always".to_string(),
                 ratio: 1.0,
+                rules: None,
             },
             batch: BatchConfig {
                 max_queue_size: 100,
@@ -2,6 +2,8 @@
 //!
 //! RED PHASE: Tests for layered subscriber with OpenTelemetry integration
 
+#![cfg(feature = "tracing")]
+
 use mizuchi_uploadr::config::TracingConfig;
 
 /// Test that subscriber can be initialized with tracing disabled
@@ -0,0 +1,199 @@
+//! Upload Progress Integration Tests
+//!
+//! Tests for `X-Mizuchi-Upload-Id` and `GET ?uploadId=` in `PingoraServer`.
+//!
+//! ## Test Coverage
+//!
+//! - Finished uploads report their final progress
+//! - Progress is only returned for the key it was uploaded to
+//! - Upload IDs still tracked cannot be reused
+//! - `GET ?uploads` lists uploads in progress only when `list_uploads` is enabled
+//! - Listings honour `max-uploads` and continue from the markers
+//! - `max-uploads=0` returns an empty page that is not truncated
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::config::Config;
+    use std::net::SocketAddr;
+    use std::path::Path;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    fn config(root: &Path) -> Config {
+        common::config(&common::local_bucket("videos", root, ""))
+    }
+
+    async fn put(addr: SocketAddr, upload_id: &str, body: &str) -> reqwest::Response {
+        let headers = [("x-mizuchi-upload-id", upload_id)];
+        common::put(addr, "/videos/clip.mp4", &headers, body.to_string()).await
+    }
+
+    #[tokio::test]
+    async fn test_finished_upload_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path())).await;
+
+        let response = put(addr, "clip-1", "0123456789").await;
+        assert_eq!(response.status(), 200);
+
+        let response = reqwest::get(format!(
+            "http://{}/videos/clip.mp4?uploadId=clip-1&wait=5",
+            addr
+        ))
+        .await
+        .unwrap();
+        assert_eq!(response.status(), 200);
+        let progress: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(progress["state"], "completed");
+        assert_eq!(progress["bytes_received"], 10);
+        assert_eq!(progress["total_bytes"], 10);
+        assert_eq!(progress["key"], "clip.mp4");
+    }
+
+    #[tokio::test]
+    async fn test_progress_of_other_key_not_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path())).await;
+
+        put(addr, "clip-1", "0123456789").await;
+
+        for url in [
+            format!("http://{}/videos/other.mp4?uploadId=clip-1", addr),
+            format!("http://{}/videos/clip.mp4?uploadId=unknown", addr),
+        ] {
+            let response = reqwest::get(url).await.unwrap();
+            assert_eq!(response.status(), 404);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upload_id_reuse_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path())).await;
+
+        assert_eq!(put(addr, "clip-1", "first").await.status(), 200);
+        assert_eq!(put(addr, "clip-1", "second").await.status(), 409);
+        assert_eq!(put(addr, "bad id", "third").await.status(), 409);
+        assert_eq!(
+            std::fs::read(dir.path().join("clip.mp4")).unwrap(),
+            b"first"
+        );
+    }
+
+    /// Start an upload and send only part of its body
+    async fn start_partial_put(addr: SocketAddr, upload_id: &str) -> TcpStream {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "PUT /videos/clip.mp4 HTTP/1.1\r\nHost: {}\r\n\
+             x-mizuchi-upload-id: {}\r\nContent-Length: 10\r\n\r\n01234",
+            addr, upload_id
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        stream
+    }
+
+    async fn list_uploads(addr: SocketAddr) -> reqwest::Response {
+        reqwest::get(format!("http://{}/videos?uploads", addr))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_list_uploads_disabled_by_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path())).await;
+
+        let _upload = start_partial_put(addr, "clip-1").await;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        assert_eq!(list_uploads(addr).await.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn test_list_uploads_in_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = config(dir.path());
+        config.buckets[0].upload.list_uploads = true;
+        let addr = common::start(config).await;
+
+        let mut upload = start_partial_put(addr, "clip-1").await;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let response = list_uploads(addr).await;
+        assert_eq!(response.status(), 200);
+        let body = response.text().await.unwrap();
+        assert!(
+            body.contains("<Upload><Key>clip.mp4</Key><UploadId>clip-1</UploadId>"),
+            "{}",
+            body
+        );
+
+        // Finished uploads are no longer listed
+        upload.write_all(b"56789").await.unwrap();
+        let mut response = [0u8; 12];
+        upload.read_exact(&mut response).await.unwrap();
+        assert_eq!(&response, b"HTTP/1.1 200");
+        let body = list_uploads(addr).await.text().await.unwrap();
+        assert!(!body.contains("<Upload>"), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn test_list_uploads_paginated() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = config(dir.path());
+        config.buckets[0].upload.list_uploads = true;
+        let addr = common::start(config).await;
+
+        let mut uploads = Vec::new();
+        for upload_id in ["clip-1", "clip-2", "clip-3"] {
+            uploads.push(start_partial_put(addr, upload_id).await);
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let list = |query: &str| {
+            let url = format!("http://{}/videos?uploads&{}", addr, query);
+            async move { reqwest::get(url).await.unwrap().text().await.unwrap() }
+        };
+        let body = list("max-uploads=2").await;
+        assert!(body.contains("<MaxUploads>2</MaxUploads>"), "{}", body);
+        assert!(body.contains("<IsTruncated>true</IsTruncated>"), "{}", body);
+        assert!(
+            body.contains("<NextKeyMarker>clip.mp4</NextKeyMarker>"),
+            "{}",
+            body
+        );
+        assert!(
+            body.contains("<NextUploadIdMarker>clip-2</NextUploadIdMarker>"),
+            "{}",
+            body
+        );
+        assert_eq!(body.matches("<Upload>").count(), 2, "{}", body);
+
+        let body = list("max-uploads=2&key-marker=clip.mp4&upload-id-marker=clip-2").await;
+        assert!(
+            body.contains("<IsTruncated>false</IsTruncated>"),
+            "{}",
+            body
+        );
+        assert!(body.contains("<UploadId>clip-3</UploadId>"), "{}", body);
+        assert_eq!(body.matches("<Upload>").count(), 1, "{}", body);
+
+        // Larger limits are capped as in S3
+        let body = list("max-uploads=5000").await;
+        assert!(body.contains("<MaxUploads>1000</MaxUploads>"), "{}", body);
+
+        // An empty page is not truncated, so clients stop paginating
+        let body = list("max-uploads=0").await;
+        assert!(body.contains("<MaxUploads>0</MaxUploads>"), "{}", body);
+        assert!(
+            body.contains("<IsTruncated>false</IsTruncated>"),
+            "{}",
+            body
+        );
+        assert!(!body.contains("<NextKeyMarker>"), "{}", body);
+        assert!(!body.contains("<Upload>"), "{}", body);
+    }
+}
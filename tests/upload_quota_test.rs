@@ -0,0 +1,66 @@
+//! Upload Quota Integration Tests
+//!
+//! Tests for `upload.quota` in `PingoraServer`.
+//!
+//! ## Test Coverage
+//!
+//! - Uploads over a byte or object limit get an S3-style `QuotaExceeded` error
+//! - Counts persisted to `path` survive a restart
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::config::Config;
+    use std::net::SocketAddr;
+    use std::path::Path;
+
+    fn config(root: &Path, quota_file: &Path) -> Config {
+        let bucket = format!(
+            r#"    upload:
+      quota:
+        limits:
+          - window: daily
+            max_bytes: 10
+            max_objects: 2
+        path: "{}""#,
+            quota_file.display()
+        );
+        common::config(&common::local_bucket("docs", root, &bucket))
+    }
+
+    async fn put(addr: SocketAddr, key: &str, body: &str) -> reqwest::Response {
+        common::put(addr, &format!("/docs/{}", key), &[], body.to_string()).await
+    }
+
+    #[tokio::test]
+    async fn test_byte_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = common::start(config(dir.path(), &dir.path().join("quota.json"))).await;
+
+        assert_eq!(put(addr, "a.txt", "123456").await.status(), 200);
+
+        let response = put(addr, "b.txt", "123456").await;
+        assert_eq!(response.status(), 403);
+        assert!(response.headers().contains_key("retry-after"));
+        let body = response.text().await.unwrap();
+        assert!(body.contains("<Code>QuotaExceeded</Code>"), "{}", body);
+        assert!(!dir.path().join("b.txt").exists());
+
+        assert_eq!(put(addr, "c.txt", "1234").await.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_object_limit_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let quota_file = dir.path().join("quota.json");
+
+        let addr = common::start(config(dir.path(), &quota_file)).await;
+        assert_eq!(put(addr, "a.txt", "1").await.status(), 200);
+        assert_eq!(put(addr, "b.txt", "1").await.status(), 200);
+
+        let addr = common::start(config(dir.path(), &quota_file)).await;
+        assert_eq!(put(addr, "c.txt", "1").await.status(), 403);
+    }
+}
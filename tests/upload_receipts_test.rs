@@ -0,0 +1,128 @@
+//! Upload Receipt Integration Tests
+//!
+//! Tests for `upload.receipts` and the admin API's `GET /receipts`.
+//!
+//! ## Test Coverage
+//!
+//! - Completed uploads are recorded with their subject
+//! - Receipts can be looked up by subject, key, prefix and bucket
+//! - Receipts survive a restart
+//! - Rejected uploads leave no receipt
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::auth::api_key::hash_key;
+    use mizuchi_uploadr::config::Config;
+    use serde_json::Value;
+    use std::net::SocketAddr;
+    use std::path::Path;
+
+    const ADMIN_TOKEN: &str = "admin-token";
+
+    fn config(root: &Path) -> Config {
+        let bucket = format!(
+            r#"    auth:
+      enabled: true
+      api_key:
+        keys:
+          - id: alice
+            hash: "{alice}"
+          - id: bob
+            hash: "{bob}"
+    upload:
+      receipts:
+        path: "{root}/receipts.jsonl""#,
+            root = root.display(),
+            alice = hash_key("k-alice", None),
+            bob = hash_key("k-bob", None),
+        );
+        let objects = root.join("objects");
+        common::with_admin(
+            common::config(&common::local_bucket("uploads", &objects, &bucket)),
+            ADMIN_TOKEN,
+        )
+    }
+
+    async fn put(addr: SocketAddr, key: &str, api_key: &str) -> reqwest::StatusCode {
+        let path = format!("/uploads/{}", key);
+        common::put(addr, &path, &[("X-Api-Key", api_key)], "report")
+            .await
+            .status()
+    }
+
+    async fn receipts(admin_addr: SocketAddr, query: &str) -> Vec<Value> {
+        let body: Value = reqwest::Client::new()
+            .get(format!("http://{}/receipts{}", admin_addr, query))
+            .bearer_auth(ADMIN_TOKEN)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        body["receipts"].as_array().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn test_receipts_recorded_and_queried() {
+        let dir = tempfile::tempdir().unwrap();
+        let (addr, admin_addr) = common::start_with_admin(config(dir.path())).await;
+
+        assert_eq!(put(addr, "q3/report.pdf", "k-alice").await, 200);
+        assert_eq!(put(addr, "q3/notes.txt", "k-bob").await, 200);
+        assert_eq!(put(addr, "q4/report.pdf", "k-alice").await, 200);
+        assert_eq!(put(addr, "q4/other.pdf", "k-unknown").await, 401);
+
+        let all = receipts(admin_addr, "").await;
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0]["key"], "q4/report.pdf");
+        assert_eq!(all[0]["bucket"], "uploads");
+        assert_eq!(all[0]["size"], 6);
+        assert!(all[0]["etag"].as_str().unwrap().starts_with('"'));
+
+        let alice = all[0]["subject"].as_str().unwrap();
+        let by_alice = receipts(admin_addr, &format!("?subject={}", alice)).await;
+        assert_eq!(by_alice.len(), 2);
+
+        let q3 = receipts(admin_addr, "?prefix=q3/&limit=1").await;
+        assert_eq!(q3.len(), 1);
+        assert_eq!(q3[0]["key"], "q3/notes.txt");
+
+        let exact = receipts(admin_addr, "?bucket=uploads&key=q3/report.pdf").await;
+        assert_eq!(exact.len(), 1);
+        assert_eq!(exact[0]["subject"], alice);
+    }
+
+    #[tokio::test]
+    async fn test_receipts_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let (addr, _) = common::start_with_admin(config(dir.path())).await;
+        assert_eq!(put(addr, "a.txt", "k-alice").await, 200);
+
+        let (_, admin_addr) = common::start_with_admin(config(dir.path())).await;
+
+        let all = receipts(admin_addr, "").await;
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0]["key"], "a.txt");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_bucket_and_bad_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, admin_addr) = common::start_with_admin(config(dir.path())).await;
+        let client = reqwest::Client::new();
+
+        for (query, status) in [("?bucket=missing", 404), ("?limit=zero", 400)] {
+            let response = client
+                .get(format!("http://{}/receipts{}", admin_addr, query))
+                .bearer_auth(ADMIN_TOKEN)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! WASM Transform Integration Tests
+//!
+//! Tests for `upload.transform` in `PingoraServer`.
+//!
+//! ## Test Coverage
+//!
+//! - The plugin's output is stored instead of the upload body (`wasm` feature)
+//! - Plugin rejections are returned as 422 (`wasm` feature)
+//! - A configured transform fails startup without the `wasm` feature
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mizuchi_uploadr::config::Config;
+    use mizuchi_uploadr::server::pingora::PingoraServer;
+    use std::path::Path;
+
+    /// Upper-cases ASCII letters; rejects bodies starting with `X`
+    #[cfg(feature = "wasm")]
+    const UPPERCASE: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (global $out_ptr (mut i32) (i32.const 0))
+          (global $out_len (mut i32) (i32.const 0))
+          (data (i32.const 0) "starts with X")
+          (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+          (func (export "output_ptr") (result i32) (global.get $out_ptr))
+          (func (export "output_len") (result i32) (global.get $out_len))
+          (func (export "transform") (param $ptr i32) (param $len i32) (result i32)
+            (local $i i32)
+            (local $c i32)
+            (if (i32.and (i32.gt_u (local.get $len) (i32.const 0))
+                         (i32.eq (i32.load8_u (local.get $ptr)) (i32.const 88)))
+              (then
+                (global.set $out_ptr (i32.const 0))
+                (global.set $out_len (i32.const 13))
+                (return (i32.const 1))))
+            (block $done
+              (loop $next
+                (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+                (local.set $c (i32.load8_u (i32.add (local.get $ptr) (local.get $i))))
+                (if (i32.and (i32.ge_u (local.get $c) (i32.const 97))
+                             (i32.le_u (local.get $c) (i32.const 122)))
+                  (then
+                    (i32.store8 (i32.add (local.get $ptr) (local.get $i))
+                                (i32.sub (local.get $c) (i32.const 32)))))
+                (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                (br $next)))
+            (global.set $out_ptr (local.get $ptr))
+            (global.set $out_len (local.get $len))
+            (i32.const 0)))
+    "#;
+
+    fn config(root: &Path, module: &Path) -> Config {
+        let bucket = format!(
+            r#"    upload:
+      transform:
+        module: "{}"
+        fuel: 1000000
+        max_memory_bytes: 1048576
+        max_input_bytes: 1024"#,
+            module.display()
+        );
+        common::config(&common::local_bucket("docs", root, &bucket))
+    }
+
+    #[cfg(feature = "wasm")]
+    #[tokio::test]
+    async fn test_transformed_body_is_stored() {
+        let root = tempfile::tempdir().unwrap();
+        let module = root.path().join("uppercase.wat");
+        std::fs::write(&module, UPPERCASE).unwrap();
+        let server = PingoraServer::new(config(root.path(), &module))
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move { server.run().await });
+        let client = reqwest::Client::new();
+
+        let response = client
+            .put(format!("http://{}/docs/a.txt", addr))
+            .body("hello")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(std::fs::read(root.path().join("a.txt")).unwrap(), b"HELLO");
+
+        let response = client
+            .put(format!("http://{}/docs/b.txt", addr))
+            .body("Xylophone")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 422);
+        assert_eq!(response.text().await.unwrap(), "Rejected: starts with X");
+        assert!(!root.path().join("b.txt").exists());
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    #[tokio::test]
+    async fn test_transform_requires_wasm_feature() {
+        let root = tempfile::tempdir().unwrap();
+        let module = root.path().join("plugin.wasm");
+
+        let result = PingoraServer::new(config(root.path(), &module)).await;
+
+        let error = result.err().unwrap().to_string();
+        assert!(error.contains("`wasm` feature"), "{}", error);
+    }
+}
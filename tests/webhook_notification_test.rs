@@ -0,0 +1,206 @@
+//! Webhook Notification Integration Tests
+//!
+//! Tests for upload completion notifications delivered to webhooks.
+//!
+//! ## Test Coverage
+//!
+//! - Event payload is POSTed as JSON to the webhook
+//! - Payload is signed with HMAC-SHA256 when a secret is configured
+//! - Failed deliveries are retried with backoff
+//! - PutObjectHandler emits an event after a successful upload
+//! - A full queue drops events instead of blocking
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use mizuchi_uploadr::config::{NotificationsConfig, WebhookConfig};
+    use mizuchi_uploadr::notifications::{
+        sign_payload, NotificationError, UploadEvent, WebhookNotifier, SIGNATURE_HEADER,
+    };
+    use mizuchi_uploadr::upload::put_object::PutObjectHandler;
+    use mizuchi_uploadr::upload::UploadHandler;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use wiremock::matchers::{header, header_exists, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn webhook(url: String, secret: Option<&str>) -> WebhookConfig {
+        WebhookConfig {
+            url,
+            secret: secret.map(|s| s.to_string()),
+            secret_file: None,
+            max_retries: 2,
+            initial_backoff_ms: 10,
+            timeout_ms: 1000,
+        }
+    }
+
+    fn notifications(webhooks: Vec<WebhookConfig>) -> NotificationsConfig {
+        NotificationsConfig {
+            enabled: true,
+            queue_capacity: 16,
+            webhooks,
+        }
+    }
+
+    /// Poll the mock server until it has received `count` requests
+    async fn wait_for_requests(server: &MockServer, count: usize) -> Vec<wiremock::Request> {
+        for _ in 0..100 {
+            let requests = server.received_requests().await.unwrap_or_default();
+            if requests.len() >= count {
+                return requests;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        server.received_requests().await.unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn test_event_delivered_as_json() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(header("Content-Type", "application/json"))
+            .and(header("X-Mizuchi-Event", "upload.completed"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let notifier = WebhookNotifier::new(&notifications(vec![webhook(
+            format!("{}/hook", server.uri()),
+            None,
+        )]))
+        .unwrap();
+        notifier
+            .notify(UploadEvent::completed(
+                "uploads",
+                "a.txt",
+                "\"etag\"",
+                5,
+                Some("alice"),
+            ))
+            .unwrap();
+
+        let requests = wait_for_requests(&server, 1).await;
+        assert_eq!(requests.len(), 1);
+
+        let event: UploadEvent = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(event.bucket, "uploads");
+        assert_eq!(event.key, "a.txt");
+        assert_eq!(event.etag, "\"etag\"");
+        assert_eq!(event.size, 5);
+        assert_eq!(event.subject.as_deref(), Some("alice"));
+    }
+
+    #[tokio::test]
+    async fn test_payload_is_signed() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header_exists(SIGNATURE_HEADER))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let notifier =
+            WebhookNotifier::new(&notifications(vec![webhook(server.uri(), Some("s3cret"))]))
+                .unwrap();
+        notifier
+            .notify(UploadEvent::completed(
+                "uploads", "a.txt", "\"etag\"", 5, None,
+            ))
+            .unwrap();
+
+        let requests = wait_for_requests(&server, 1).await;
+        assert_eq!(requests.len(), 1);
+
+        let signature = requests[0]
+            .headers
+            .get(SIGNATURE_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert_eq!(signature, sign_payload("s3cret", &requests[0].body));
+    }
+
+    #[tokio::test]
+    async fn test_failed_delivery_is_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let notifier =
+            WebhookNotifier::new(&notifications(vec![webhook(server.uri(), None)])).unwrap();
+        notifier
+            .notify(UploadEvent::completed(
+                "uploads", "a.txt", "\"etag\"", 5, None,
+            ))
+            .unwrap();
+
+        let requests = wait_for_requests(&server, 3).await;
+        assert_eq!(requests.len(), 3, "Two failures followed by one success");
+    }
+
+    #[tokio::test]
+    async fn test_put_object_handler_emits_event() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let notifier = WebhookNotifier::new(&notifications(vec![webhook(
+            format!("{}/hook", server.uri()),
+            None,
+        )]))
+        .unwrap();
+        let handler =
+            PutObjectHandler::new("test-bucket", "us-east-1").with_notifier(Arc::new(notifier));
+
+        handler
+            .upload("test-bucket", "key.bin", Bytes::from("hello"), None)
+            .await
+            .unwrap();
+
+        let requests = wait_for_requests(&server, 1).await;
+        let event: UploadEvent = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(event.bucket, "test-bucket");
+        assert_eq!(event.key, "key.bin");
+        assert_eq!(event.size, 5);
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_drops_without_blocking() {
+        // Unroutable webhook with a long timeout keeps the worker busy
+        let mut config = notifications(vec![WebhookConfig {
+            url: "http://10.255.255.1:9/hook".to_string(),
+            secret: None,
+            secret_file: None,
+            max_retries: 0,
+            initial_backoff_ms: 10,
+            timeout_ms: 10_000,
+        }]);
+        config.queue_capacity = 1;
+
+        let notifier = WebhookNotifier::new(&config).unwrap();
+        let event = UploadEvent::completed("uploads", "a.txt", "\"etag\"", 5, None);
+
+        let mut dropped = false;
+        for _ in 0..10 {
+            if let Err(NotificationError::QueueFull) = notifier.notify(event.clone()) {
+                dropped = true;
+                break;
+            }
+        }
+        assert!(dropped, "Queue should reject events once full");
+    }
+}
@@ -209,6 +209,7 @@ mod tests {
             secret_key: Some("minioadmin".into()),
             retry: None,
             timeout: None,
+            pool: None,
         };
 
         let client = S3Client::new(config).expect("Should create client");
@@ -226,6 +227,52 @@ mod tests {
         assert!(result.is_err(), "Should fail without MinIO running");
     }
 
+    /// Test that an encrypted spool file is decrypted before being sent to S3
+    #[tokio::test]
+    async fn test_put_object_from_encrypted_file_sends_plaintext() {
+        use mizuchi_uploadr::s3::{S3Client, S3ClientConfig};
+        use mizuchi_uploadr::upload::temp_file::TempFileUpload;
+        use wiremock::matchers::{body_bytes, method};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let mock_server = MockServer::start().await;
+        let plaintext = b"sensitive payload spooled to disk".to_vec();
+
+        Mock::given(method("PUT"))
+            .and(body_bytes(plaintext.clone()))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "\"encrypted-etag\""))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let config = S3ClientConfig {
+            bucket: "test-bucket".into(),
+            region: "us-east-1".into(),
+            endpoint: Some(mock_server.uri()),
+            access_key: Some("test-access".into()),
+            secret_key: Some("test-secret".into()),
+            retry: None,
+            timeout: None,
+            pool: None,
+        };
+        let client = S3Client::new(config).expect("Should create client");
+
+        let temp = TempFileUpload::from_bytes_encrypted(Bytes::from(plaintext.clone()))
+            .expect("Should create encrypted temp file");
+        assert!(temp.is_encrypted());
+        assert!(!temp.supports_zero_copy());
+
+        // Bytes on disk must not be the plaintext
+        let on_disk = std::fs::read(temp.path()).expect("Should read temp file");
+        assert_ne!(on_disk, plaintext);
+
+        let response = client
+            .put_object_from_file("secret.bin", &temp, None)
+            .await
+            .expect("Upload should succeed");
+        assert_eq!(response.etag, "\"encrypted-etag\"");
+    }
+
     // ========================================================================
     // TEST: Threshold-Based Routing
     // ========================================================================